@@ -0,0 +1,162 @@
+//! Firmware boot entries, managed with `efibootmgr`
+
+use crate::error::Result;
+use crate::exec::CommandExecutor;
+use std::path::Path;
+use std::process::Command;
+
+/// Firmware boot entries as listed by `efibootmgr`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BootEntries {
+    /// `BootOrder`, as four-digit hex boot numbers
+    pub order: Vec<String>,
+    /// Every `BootXXXX` entry as (boot number, label)
+    pub entries: Vec<(String, String)>,
+}
+
+impl BootEntries {
+    /// Parse `efibootmgr` output
+    ///
+    /// Entry lines look like `Boot0003* ZFSBootMenu (sda)`; newer releases
+    /// append the device path after a tab, which is dropped.
+    pub fn parse(output: &str) -> Self {
+        let mut entries = Self::default();
+
+        for line in output.lines() {
+            if let Some(order) = line.strip_prefix("BootOrder:") {
+                entries.order = order
+                    .split(',')
+                    .map(|n| n.trim().to_string())
+                    .filter(|n| !n.is_empty())
+                    .collect();
+                continue;
+            }
+
+            let Some(rest) = line.strip_prefix("Boot") else {
+                continue;
+            };
+            let Some(bootnum) = rest.get(..4) else {
+                continue;
+            };
+            if !bootnum.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+            let label = rest[4..].trim_start_matches('*');
+            let label = label.split('\t').next().unwrap_or_default().trim();
+            entries
+                .entries
+                .push((bootnum.to_string(), label.to_string()));
+        }
+
+        entries
+    }
+
+    /// Boot numbers of the entries labelled `label`
+    pub fn with_label(&self, label: &str) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(_, l)| l == label)
+            .map(|(n, _)| n.as_str())
+            .collect()
+    }
+}
+
+/// Read the current boot entries
+///
+/// Returns no entries in dry-run mode.
+pub fn list(executor: &dyn CommandExecutor) -> Result<BootEntries> {
+    let result = executor.execute(&mut Command::new("efibootmgr"))?;
+    Ok(result
+        .stdout_string()
+        .map(|stdout| BootEntries::parse(&stdout))
+        .unwrap_or_default())
+}
+
+/// Delete the entry `bootnum`
+pub fn delete(executor: &dyn CommandExecutor, bootnum: &str) -> Result<()> {
+    executor.execute(
+        Command::new("efibootmgr")
+            .arg("--bootnum")
+            .arg(bootnum)
+            .arg("--delete-bootnum"),
+    )?;
+    Ok(())
+}
+
+/// Create an entry labelled `label` that starts `loader` from the ESP at
+/// `partition` of `disk`
+///
+/// A `second_stage` is passed to the loader as its load options, which is
+/// how shim is told what to start.
+///
+/// Returns the new entry's boot number, or `None` when it can't be told
+/// (dry-run mode).
+pub fn create(
+    executor: &dyn CommandExecutor,
+    disk: &Path,
+    partition: u32,
+    label: &str,
+    loader: &str,
+    second_stage: Option<&str>,
+) -> Result<Option<String>> {
+    let mut cmd = Command::new("efibootmgr");
+    cmd.arg("--create")
+        .arg("--disk")
+        .arg(disk)
+        .arg("--part")
+        .arg(partition.to_string())
+        .arg("--label")
+        .arg(label)
+        .arg("--loader")
+        .arg(loader);
+    if let Some(second_stage) = second_stage {
+        cmd.arg("--unicode").arg(second_stage);
+    }
+    let result = executor.execute(&mut cmd)?;
+
+    Ok(result.stdout_string().and_then(|stdout| {
+        BootEntries::parse(&stdout)
+            .with_label(label)
+            .first()
+            .map(|n| n.to_string())
+    }))
+}
+
+/// Replace `BootOrder`
+pub fn set_order(executor: &dyn CommandExecutor, order: &[String]) -> Result<()> {
+    executor.execute(
+        Command::new("efibootmgr")
+            .arg("--bootorder")
+            .arg(order.join(",")),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = "BootCurrent: 0003
+Timeout: 1 seconds
+BootOrder: 0003,0000,0001
+Boot0000* debian\tHD(1,GPT,9c1a...,0x800,0x100000)/File(\\EFI\\debian\\shimx64.efi)
+Boot0001  UEFI: Built-in EFI Shell
+Boot0003* ZFSBootMenu (sda)\tHD(1,GPT,4f0e...,0x800,0x200000)/File(\\EFI\\ZBM\\zfsbootmenu.EFI)
+";
+
+    #[test]
+    fn test_parse_listing() {
+        let entries = BootEntries::parse(LISTING);
+        assert_eq!(entries.order, vec!["0003", "0000", "0001"]);
+        assert_eq!(
+            entries.entries,
+            vec![
+                ("0000".to_string(), "debian".to_string()),
+                ("0001".to_string(), "UEFI: Built-in EFI Shell".to_string()),
+                ("0003".to_string(), "ZFSBootMenu (sda)".to_string()),
+            ]
+        );
+        assert_eq!(entries.with_label("ZFSBootMenu (sda)"), vec!["0003"]);
+        assert!(entries.with_label("ZFSBootMenu").is_empty());
+    }
+}
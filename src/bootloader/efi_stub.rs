@@ -0,0 +1,157 @@
+//! Firmware-started ZFSBootMenu
+//!
+//! The ZFSBootMenu image is itself an EFI executable, so the firmware boot
+//! entries can start it with no bootloader in between. Every disk gets two
+//! entries: one for the image and one for its backup copy, so a broken update
+//! of the image still leaves something to boot.
+
+use super::zbm::{BACKUP_LOADER, ZBM_LOADER};
+use super::{efi, entry_label, secure_boot, BootEntryManager};
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Boot entries that start ZFSBootMenu directly
+pub struct EfiStub {
+    shim: bool,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl EfiStub {
+    /// Create a new EFI stub boot entry manager
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            shim: false,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Start the loader through shim, for Secure Boot
+    pub fn with_shim(mut self, shim: bool) -> Self {
+        self.shim = shim;
+        self
+    }
+
+    /// Replace this disk's entries and put them first in the boot order
+    ///
+    /// Entries left with the same labels by an earlier installation are
+    /// removed first. The previous boot order follows the new entries, so
+    /// the firmware falls back to whatever booted before.
+    fn register(&self, disk: &Path, partition: u32) -> Result<()> {
+        let executor = &*self.executor;
+        let existing = efi::list(executor)?;
+        let entries = [
+            (entry_label("ZFSBootMenu", disk), ZBM_LOADER),
+            (entry_label("ZFSBootMenu Backup", disk), BACKUP_LOADER),
+        ];
+
+        let mut order = existing.order.clone();
+        for (label, _) in &entries {
+            for bootnum in existing.with_label(label) {
+                log::info!("Removing stale boot entry {} \"{}\"", bootnum, label);
+                efi::delete(executor, bootnum)?;
+                order.retain(|n| n != bootnum);
+            }
+        }
+
+        let mut created = Vec::new();
+        for (label, loader) in &entries {
+            log::info!(
+                "Registering boot entry \"{}\" for {} partition {}",
+                label,
+                disk.display(),
+                partition
+            );
+            let (loader, second_stage) = secure_boot::chain(loader, self.shim);
+            created.extend(efi::create(
+                executor,
+                disk,
+                partition,
+                label,
+                loader,
+                second_stage,
+            )?);
+        }
+
+        // Boot numbers are unknown in dry-run mode
+        if created.is_empty() {
+            return Ok(());
+        }
+        order.retain(|n| !created.contains(n));
+        created.extend(order);
+        efi::set_order(executor, &created)
+    }
+}
+
+impl BootEntryManager for EfiStub {
+    fn install(&self) -> Result<()> {
+        // The backup image comes with ZFSBootMenu itself
+        log::info!("No bootloader to install: the firmware starts ZFSBootMenu directly");
+        Ok(())
+    }
+
+    /// Failures are logged rather than fatal, as for the other backends
+    fn add_entry(&self, disk: &Path, partition: u32) -> Result<()> {
+        match self.register(disk, partition) {
+            Ok(()) => Ok(()),
+            Err(InstallerError::CommandFailed { cmd, stderr, .. }) => {
+                log::warn!("Command failed (non-fatal): {}\n{}", cmd, stderr);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    #[test]
+    fn test_entries_replace_stale_ones_and_go_first() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program_times(
+            "efibootmgr",
+            1,
+            "BootOrder: 0003,0000\nBoot0000* debian\nBoot0003* ZFSBootMenu (sda)\n",
+        );
+        executor.respond_program_times("efibootmgr", 1, "");
+        executor.respond_program_times(
+            "efibootmgr",
+            1,
+            "BootOrder: 0004,0000\nBoot0000* debian\nBoot0004* ZFSBootMenu (sda)\n",
+        );
+        executor.respond_program_times(
+            "efibootmgr",
+            1,
+            "BootOrder: 0005,0004,0000\nBoot0000* debian\nBoot0004* ZFSBootMenu (sda)\nBoot0005* ZFSBootMenu Backup (sda)\n",
+        );
+        let stub = EfiStub::new(false).with_executor(executor.clone());
+
+        stub.add_entry(Path::new("/dev/sda"), 1).unwrap();
+
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            argv,
+            vec![
+                "efibootmgr",
+                "efibootmgr --bootnum 0003 --delete-bootnum",
+                "efibootmgr --create --disk /dev/sda --part 1 --label ZFSBootMenu (sda) --loader \\EFI\\ZBM\\zfsbootmenu.EFI",
+                "efibootmgr --create --disk /dev/sda --part 1 --label ZFSBootMenu Backup (sda) --loader \\EFI\\ZBM\\zfsbootmenu-backup.EFI",
+                "efibootmgr --bootorder 0004,0005,0000",
+            ]
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! GRUB boot paths
+//!
+//! [`Grub`] is the UEFI bootloader backend: GRUB on the ESP chain-loads the
+//! ZFSBootMenu image, which suits distributions that boot through a Secure
+//! Boot shim.
+//!
+//! [`GrubBios`] is used by hybrid layouts: GRUB is embedded in each disk's
+//! BIOS boot partition and chain-loads the ZFSBootMenu kernel and initramfs
+//! components from the ESP.
+
+use super::BootEntryManager;
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, ExecResult, FileOp, SystemExecutor};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// ZFSBootMenu components on the ESP, relative to its root
+const ZBM_KERNEL: &str = "/EFI/ZBM/vmlinuz-bootmenu";
+const ZBM_INITRAMFS: &str = "/EFI/ZBM/initramfs-bootmenu.img";
+
+/// ZFSBootMenu EFI image on the ESP, relative to its root
+const ZBM_IMAGE: &str = "/EFI/ZBM/zfsbootmenu.EFI";
+
+/// Directory `grub-install` puts GRUB in, under `/EFI` on the ESP
+const GRUB_BOOTLOADER_ID: &str = "GRUB";
+
+/// GRUB EFI image as a firmware loader path
+const GRUB_LOADER: &str = "\\EFI\\GRUB\\grubx64.efi";
+
+/// Custom menu entry script in the target system's `/etc/grub.d`
+const GRUB_CUSTOM_ENTRY: &str = "etc/grub.d/40_zfsbootmenu";
+
+/// GRUB installer for UEFI, chain-loading ZFSBootMenu
+pub struct Grub {
+    efi_mountpoint: PathBuf,
+    target_root: PathBuf,
+    shim: bool,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl Grub {
+    /// Create a new GRUB EFI installer for the ESP at `efi_mountpoint`
+    ///
+    /// The custom menu entry is also written to the system mounted at
+    /// `target_root`, so later `grub-mkconfig` runs there keep it.
+    pub fn new(efi_mountpoint: PathBuf, target_root: PathBuf, dry_run: bool) -> Self {
+        Self {
+            efi_mountpoint,
+            target_root,
+            shim: false,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Start the loader through shim, for Secure Boot
+    pub fn with_shim(mut self, shim: bool) -> Self {
+        self.shim = shim;
+        self
+    }
+
+    /// Path of the GRUB EFI image on an ESP
+    pub(crate) fn image_path(esp: &Path) -> PathBuf {
+        esp.join("EFI").join(GRUB_BOOTLOADER_ID).join("grubx64.efi")
+    }
+
+    /// Menu entry chain-loading the ZFSBootMenu image
+    fn menu_entry() -> String {
+        format!(
+            r#"menuentry "ZFSBootMenu" {{
+    insmod part_gpt
+    insmod fat
+    insmod chain
+    search --no-floppy --set=root --file {image}
+    chainloader {image}
+}}
+"#,
+            image = ZBM_IMAGE
+        )
+    }
+
+    /// `/etc/grub.d` script emitting the menu entry
+    fn custom_entry_script() -> String {
+        format!(
+            "#!/bin/sh\nexec tail -n +3 $0\n# ZFSBootMenu chain-load entry\n{}",
+            Self::menu_entry()
+        )
+    }
+
+    /// GRUB configuration on the ESP
+    fn config() -> String {
+        format!(
+            "# ZFSBootMenu EFI chain\nset timeout=0\n\n{}",
+            Self::menu_entry()
+        )
+    }
+}
+
+impl BootEntryManager for Grub {
+    fn install(&self) -> Result<()> {
+        log::info!("Installing GRUB for UEFI");
+
+        // NVRAM entries are added per disk by add_entry
+        let boot_dir = self.efi_mountpoint.join("boot");
+        execute(
+            &*self.executor,
+            Command::new("grub-install")
+                .arg("--target=x86_64-efi")
+                .arg(format!("--efi-directory={}", self.efi_mountpoint.display()))
+                .arg(format!("--boot-directory={}", boot_dir.display()))
+                .arg(format!("--bootloader-id={}", GRUB_BOOTLOADER_ID))
+                .arg("--no-nvram"),
+        )?;
+
+        let grub_dir = boot_dir.join("grub");
+        self.executor.apply(FileOp::create_dir(&grub_dir))?;
+        self.executor
+            .apply(FileOp::write(grub_dir.join("grub.cfg"), Self::config()))?;
+
+        let script = self.target_root.join(GRUB_CUSTOM_ENTRY);
+        if let Some(parent) = script.parent() {
+            self.executor.apply(FileOp::create_dir(parent))?;
+        }
+        self.executor
+            .apply(FileOp::write(&script, Self::custom_entry_script()))?;
+        self.executor.apply(FileOp::set_mode(&script, 0o755))?;
+
+        log::info!("GRUB installed successfully");
+        Ok(())
+    }
+
+    fn add_entry(&self, disk: &Path, partition: u32) -> Result<()> {
+        super::register_efi_entry(
+            &*self.executor,
+            disk,
+            partition,
+            "GRUB",
+            GRUB_LOADER,
+            self.shim,
+        )
+    }
+}
+
+/// GRUB installer for BIOS boot partitions
+pub struct GrubBios {
+    efi_mountpoint: PathBuf,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl GrubBios {
+    /// Create a new GRUB BIOS installer
+    pub fn new(efi_mountpoint: PathBuf, dry_run: bool) -> Self {
+        Self {
+            efi_mountpoint,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Install GRUB to every disk and write its configuration to the ESP
+    pub fn install(&self, devices: &[PathBuf]) -> Result<()> {
+        log::info!("Installing GRUB for legacy BIOS boot");
+
+        let boot_dir = self.efi_mountpoint.join("boot");
+        for device in devices {
+            execute(
+                &*self.executor,
+                Command::new("grub-install")
+                    .arg("--target=i386-pc")
+                    .arg(format!("--boot-directory={}", boot_dir.display()))
+                    .arg("--modules=part_gpt fat")
+                    .arg(device),
+            )?;
+        }
+
+        let grub_dir = boot_dir.join("grub");
+        self.executor.apply(FileOp::create_dir(&grub_dir))?;
+        self.executor
+            .apply(FileOp::write(grub_dir.join("grub.cfg"), Self::config()))?;
+
+        log::info!("GRUB installed successfully");
+        Ok(())
+    }
+
+    /// GRUB configuration booting the ZFSBootMenu components
+    fn config() -> String {
+        format!(
+            r#"# ZFSBootMenu legacy BIOS chain
+set timeout=0
+
+menuentry "ZFSBootMenu" {{
+    search --no-floppy --set=root --file {kernel}
+    linux {kernel} ro quiet loglevel=0
+    initrd {initramfs}
+}}
+"#,
+            kernel = ZBM_KERNEL,
+            initramfs = ZBM_INITRAMFS
+        )
+    }
+}
+
+/// Execute a command, reporting failures as bootloader errors
+fn execute(executor: &dyn CommandExecutor, cmd: &mut Command) -> Result<ExecResult> {
+    executor.execute_as(
+        cmd,
+        &ExecOptions::default(),
+        InstallerError::bootloader_command,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exec::RecordingExecutor;
+
+    #[test]
+    fn test_efi_install_chainloads_zbm_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let esp = dir.path().join("efi");
+        let root = dir.path().join("root");
+        let executor = Arc::new(RecordingExecutor::new());
+        let grub = Grub::new(esp.clone(), root.clone(), false).with_executor(executor.clone());
+
+        grub.install().unwrap();
+        grub.add_entry(Path::new("/dev/sda"), 1).unwrap();
+
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            argv,
+            vec![
+                format!(
+                    "grub-install --target=x86_64-efi --efi-directory={} --boot-directory={}/boot --bootloader-id=GRUB --no-nvram",
+                    esp.display(),
+                    esp.display()
+                ),
+                "efibootmgr --create --disk /dev/sda --part 1 --label GRUB (sda) --loader \\EFI\\GRUB\\grubx64.efi".to_string(),
+            ]
+        );
+
+        let config = executor.written(&esp.join("boot/grub/grub.cfg")).unwrap();
+        assert!(config.contains("chainloader /EFI/ZBM/zfsbootmenu.EFI"));
+
+        let script = root.join("etc/grub.d/40_zfsbootmenu");
+        let content = executor.written(&script).unwrap();
+        assert!(content.starts_with("#!/bin/sh\nexec tail -n +3 $0\n"));
+        assert!(content.contains("chainloader /EFI/ZBM/zfsbootmenu.EFI"));
+        assert!(executor
+            .file_ops()
+            .contains(&FileOp::set_mode(&script, 0o755)));
+    }
+
+    #[test]
+    fn test_config_boots_zbm_components() {
+        let config = GrubBios::config();
+        assert!(config.contains("linux /EFI/ZBM/vmlinuz-bootmenu"));
+        assert!(config.contains("initrd /EFI/ZBM/initramfs-bootmenu.img"));
+    }
+}
@@ -1,7 +1,80 @@
 //! Bootloader installation and configuration
+//!
+//! ZFSBootMenu is always installed on the ESP; a [`BootEntryManager`] then
+//! sets up what the firmware boot entries start, chosen by
+//! [`Bootloader`](crate::config::Bootloader). Legacy BIOS installs use
+//! [`Syslinux`] on a boot partition instead. Under Secure Boot the images are
+//! signed, and the entries may start them through shim ([`SecureBoot`]).
 
+pub mod efi;
+pub mod efi_stub;
+pub mod grub;
+pub mod secure_boot;
+pub mod syslinux;
 pub mod systemd_boot;
 pub mod zbm;
 
+pub use efi_stub::EfiStub;
+pub use grub::{Grub, GrubBios};
+pub use secure_boot::SecureBoot;
+pub use syslinux::Syslinux;
 pub use systemd_boot::SystemdBoot;
-pub use zbm::ZbmInstaller;
+pub use zbm::{ZbmImage, ZbmInstaller};
+
+use crate::error::{InstallerError, Result};
+use crate::exec::CommandExecutor;
+use std::path::Path;
+
+/// A bootloader started by the firmware boot entries
+///
+/// Each instance manages one ESP. The installer installs it on every ESP,
+/// then adds one firmware entry per disk.
+pub trait BootEntryManager {
+    /// Install the bootloader onto the ESP
+    fn install(&self) -> Result<()>;
+
+    /// Add a firmware boot entry for the ESP at `partition` of `disk`
+    fn add_entry(&self, disk: &Path, partition: u32) -> Result<()>;
+}
+
+/// Boot entry label for `disk`, e.g. `ZFSBootMenu (sda)`
+///
+/// Naming the disk keeps the entries of a multi-disk pool apart.
+pub(crate) fn entry_label(name: &str, disk: &Path) -> String {
+    match disk.file_name() {
+        Some(disk_name) => format!("{} ({})", name, disk_name.to_string_lossy()),
+        None => name.to_string(),
+    }
+}
+
+/// Add a firmware boot entry labelled `name (<disk>)` that starts `loader`,
+/// through shim if `shim` is set
+///
+/// Failures are logged rather than fatal: firmware without writable EFI
+/// variables still finds the ESP through its own boot menu.
+pub(crate) fn register_efi_entry(
+    executor: &dyn CommandExecutor,
+    disk: &Path,
+    partition: u32,
+    name: &str,
+    loader: &str,
+    shim: bool,
+) -> Result<()> {
+    let label = entry_label(name, disk);
+    log::info!(
+        "Registering boot entry \"{}\" for {} partition {}",
+        label,
+        disk.display(),
+        partition
+    );
+
+    let (loader, second_stage) = secure_boot::chain(loader, shim);
+    match efi::create(executor, disk, partition, &label, loader, second_stage) {
+        Ok(_) => Ok(()),
+        Err(InstallerError::CommandFailed { cmd, stderr, .. }) => {
+            log::warn!("Command failed (non-fatal): {}\n{}", cmd, stderr);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,252 @@
+//! Secure Boot signing and shim
+//!
+//! The ZFSBootMenu release images are unsigned, so firmware enforcing Secure
+//! Boot refuses them. They are signed with a user-supplied key pair, either
+//! one already in the firmware's db (sign-local mode) or one enrolled as a
+//! Machine Owner Key, trusted by the distribution's Microsoft-signed shim
+//! (shim mode). In shim mode the boot entries start shim, which is handed the
+//! real loader's path as its second stage.
+
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, ExecResult, FileOp, SystemExecutor};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// Where distributions ship shim and MokManager, most common first
+const SHIM_PATHS: &[(&str, &str)] = &[
+    (
+        "/usr/lib/shim/shimx64.efi.signed",
+        "/usr/lib/shim/mmx64.efi.signed",
+    ),
+    ("/usr/lib/shim/shimx64.efi", "/usr/lib/shim/mmx64.efi"),
+    (
+        "/boot/efi/EFI/fedora/shimx64.efi",
+        "/boot/efi/EFI/fedora/mmx64.efi",
+    ),
+    (
+        "/usr/share/efi/x86_64/shim.efi",
+        "/usr/share/efi/x86_64/MokManager.efi",
+    ),
+    (
+        "/usr/share/shim-signed/shimx64.efi",
+        "/usr/share/shim-signed/mmx64.efi",
+    ),
+];
+
+/// shim and MokManager on the ESP, next to ZFSBootMenu; shim looks for
+/// MokManager in its own directory
+const SHIM_IMAGE: &str = "EFI/ZBM/shimx64.efi";
+const MOK_MANAGER_IMAGE: &str = "EFI/ZBM/mmx64.efi";
+
+/// shim as a firmware loader path
+const SHIM_LOADER: &str = "\\EFI\\ZBM\\shimx64.efi";
+
+/// The signing certificate in DER form on the ESP, for enrolling it again
+/// from MokManager
+const MOK_CERT: &str = "EFI/ZBM/MOK.cer";
+
+/// What a boot entry for `loader` starts: `loader` itself, or shim with
+/// `loader` as its second stage
+pub(crate) fn chain(loader: &str, shim: bool) -> (&str, Option<&str>) {
+    if shim {
+        (SHIM_LOADER, Some(loader))
+    } else {
+        (loader, None)
+    }
+}
+
+/// Signs EFI executables and sets up shim
+pub struct SecureBoot {
+    key: PathBuf,
+    cert: PathBuf,
+    dry_run: bool,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl SecureBoot {
+    /// Create a new signer for the PEM key pair `key` and `cert`
+    pub fn new(key: PathBuf, cert: PathBuf, dry_run: bool) -> Self {
+        Self {
+            key,
+            cert,
+            dry_run,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Execute a command, reporting failures as bootloader errors
+    fn execute(&self, cmd: &mut Command) -> Result<ExecResult> {
+        self.executor.execute_as(
+            cmd,
+            &ExecOptions::default(),
+            InstallerError::bootloader_command,
+        )
+    }
+
+    /// Sign the EFI executable at `image` in place, then verify the signature
+    pub fn sign(&self, image: &Path) -> Result<()> {
+        log::info!("Signing {}", image.display());
+
+        self.execute(
+            Command::new("sbsign")
+                .arg("--key")
+                .arg(&self.key)
+                .arg("--cert")
+                .arg(&self.cert)
+                .arg("--output")
+                .arg(image)
+                .arg(image),
+        )?;
+        self.execute(
+            Command::new("sbverify")
+                .arg("--cert")
+                .arg(&self.cert)
+                .arg(image),
+        )?;
+        Ok(())
+    }
+
+    /// Copy shim and MokManager onto the ESP at `esp`, next to ZFSBootMenu
+    pub fn install_shim(&self, esp: &Path) -> Result<()> {
+        let (shim, mok_manager) = self.find_shim()?;
+        log::info!("Installing shim from {} on {}", shim, esp.display());
+
+        self.executor
+            .apply(FileOp::create_dir(esp.join("EFI").join("ZBM")))?;
+        self.executor
+            .apply(FileOp::copy(shim, esp.join(SHIM_IMAGE)))?;
+        self.executor
+            .apply(FileOp::copy(mok_manager, esp.join(MOK_MANAGER_IMAGE)))?;
+        Ok(())
+    }
+
+    /// Request enrollment of the signing certificate as a Machine Owner Key
+    ///
+    /// MokManager asks to confirm the enrollment on the next boot, with the
+    /// running system's root password. Failures are logged rather than
+    /// fatal: the certificate is also left on the ESP, to enroll from
+    /// MokManager's "Enroll key from disk".
+    pub fn enroll(&self, esp: &Path) -> Result<()> {
+        let der = esp.join(MOK_CERT);
+        log::info!("Requesting MOK enrollment of {}", self.cert.display());
+
+        self.execute(
+            Command::new("openssl")
+                .arg("x509")
+                .arg("-in")
+                .arg(&self.cert)
+                .arg("-outform")
+                .arg("DER")
+                .arg("-out")
+                .arg(&der),
+        )?;
+        match self.execute(
+            Command::new("mokutil")
+                .arg("--import")
+                .arg(&der)
+                .arg("--root-pw"),
+        ) {
+            Ok(_) => Ok(()),
+            Err(InstallerError::BootloaderError(message)) => {
+                log::warn!(
+                    "MOK enrollment failed (non-fatal), enroll {} from MokManager: {}",
+                    MOK_CERT,
+                    message
+                );
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The distribution's shim and MokManager
+    fn find_shim(&self) -> Result<(&'static str, &'static str)> {
+        let found = SHIM_PATHS.iter().find(|(shim, mok_manager)| {
+            Path::new(shim).exists() && Path::new(mok_manager).exists()
+        });
+        match found {
+            Some(&paths) => Ok(paths),
+            None if self.dry_run => Ok(SHIM_PATHS[0]),
+            None => Err(InstallerError::BootloaderError(
+                "shim not found; install the distribution's signed shim package".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exec::RecordingExecutor;
+
+    fn secure_boot(executor: &Arc<RecordingExecutor>) -> SecureBoot {
+        SecureBoot::new(
+            PathBuf::from("/etc/secureboot/db.key"),
+            PathBuf::from("/etc/secureboot/db.crt"),
+            true,
+        )
+        .with_executor(executor.clone())
+    }
+
+    #[test]
+    fn test_sign_verifies_the_signature() {
+        let executor = Arc::new(RecordingExecutor::new());
+        secure_boot(&executor)
+            .sign(Path::new("/mnt/boot/efi/EFI/ZBM/zfsbootmenu.EFI"))
+            .unwrap();
+
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            argv,
+            vec![
+                "sbsign --key /etc/secureboot/db.key --cert /etc/secureboot/db.crt --output /mnt/boot/efi/EFI/ZBM/zfsbootmenu.EFI /mnt/boot/efi/EFI/ZBM/zfsbootmenu.EFI",
+                "sbverify --cert /etc/secureboot/db.crt /mnt/boot/efi/EFI/ZBM/zfsbootmenu.EFI",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shim_is_installed_and_the_key_enrolled() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("mokutil", 1, "EFI variables are not supported");
+        let secure_boot = secure_boot(&executor);
+        let esp = Path::new("/mnt/boot/efi");
+
+        secure_boot.install_shim(esp).unwrap();
+        // A failed enrollment leaves the certificate for MokManager
+        secure_boot.enroll(esp).unwrap();
+
+        let ops = executor.file_ops();
+        assert!(ops
+            .iter()
+            .any(|op| *op == FileOp::copy(SHIM_PATHS[0].0, esp.join(SHIM_IMAGE))));
+        assert!(ops
+            .iter()
+            .any(|op| *op == FileOp::copy(SHIM_PATHS[0].1, esp.join(MOK_MANAGER_IMAGE))));
+        let commands = executor.commands();
+        assert_eq!(commands[0].program, "openssl");
+        assert_eq!(
+            commands[1].argv().join(" "),
+            "mokutil --import /mnt/boot/efi/EFI/ZBM/MOK.cer --root-pw"
+        );
+    }
+
+    #[test]
+    fn test_entries_chain_through_shim() {
+        let loader = "\\EFI\\ZBM\\zfsbootmenu.EFI";
+        assert_eq!(chain(loader, false), (loader, None));
+        assert_eq!(chain(loader, true), (SHIM_LOADER, Some(loader)));
+    }
+}
@@ -0,0 +1,274 @@
+//! syslinux for legacy BIOS layouts
+//!
+//! BIOS layouts give each disk an ext4 boot partition, mounted on
+//! `/boot/syslinux` in the installed system rather than on `/boot`, where
+//! ZFSBootMenu looks for the boot environments' kernels. `generate-zbm`
+//! builds the ZFSBootMenu kernel and initramfs onto it in components mode,
+//! extlinux is installed next to them, and the disk's MBR gets syslinux's
+//! GPT boot code, which starts the partition marked legacy BIOS bootable.
+
+use super::BootEntryManager;
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, ExecResult, FileOp, SystemExecutor};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// Where the first disk's boot partition is mounted in the installed system
+pub const BOOT_MOUNTPOINT: &str = "/boot/syslinux";
+
+/// Directory of the ZFSBootMenu components on the boot partition
+const COMPONENTS_DIR: &str = "zfsbootmenu";
+
+/// ZFSBootMenu components, as `generate-zbm` names them without versions
+const ZBM_KERNEL: &str = "vmlinuz-bootmenu";
+const ZBM_INITRAMFS: &str = "initramfs-bootmenu.img";
+
+/// `generate-zbm` configuration for building the components
+const GENERATE_ZBM_CONFIG: &str = "/etc/zfsbootmenu/syslinux.yaml";
+
+/// Where distributions ship syslinux's GPT boot code, most common first
+const GPTMBR_PATHS: &[&str] = &[
+    "/usr/lib/syslinux/mbr/gptmbr.bin",
+    "/usr/lib/syslinux/bios/gptmbr.bin",
+    "/usr/share/syslinux/gptmbr.bin",
+];
+
+/// GPT attribute the boot code looks for: legacy BIOS bootable
+const LEGACY_BOOTABLE_BIT: u32 = 2;
+
+/// syslinux installer for one boot partition
+pub struct Syslinux {
+    boot_mountpoint: PathBuf,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl Syslinux {
+    /// Create a new syslinux installer for the boot partition at `boot_mountpoint`
+    pub fn new(boot_mountpoint: PathBuf, dry_run: bool) -> Self {
+        Self {
+            boot_mountpoint,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Build the ZFSBootMenu kernel and initramfs onto the boot partition
+    ///
+    /// Runs the host's `generate-zbm`, so ZFSBootMenu has to be installed on
+    /// the running system.
+    pub fn generate_components(&self) -> Result<()> {
+        log::info!(
+            "Building the ZFSBootMenu components in {}",
+            self.boot_mountpoint.display()
+        );
+
+        self.executor.apply(FileOp::create_dir(
+            self.boot_mountpoint.join(COMPONENTS_DIR),
+        ))?;
+        let config = Path::new(GENERATE_ZBM_CONFIG);
+        if let Some(dir) = config.parent() {
+            self.executor.apply(FileOp::create_dir(dir))?;
+        }
+        self.executor
+            .apply(FileOp::write(config, self.generate_zbm_config()))?;
+
+        execute(
+            &*self.executor,
+            Command::new("generate-zbm").arg("--config").arg(config),
+        )?;
+        Ok(())
+    }
+
+    /// Copy the components [`generate_components`](Self::generate_components)
+    /// built on the boot partition at `primary` onto this one
+    pub fn copy_components(&self, primary: &Path) -> Result<()> {
+        log::info!(
+            "Copying the ZFSBootMenu components to {}",
+            self.boot_mountpoint.display()
+        );
+
+        let dir = self.boot_mountpoint.join(COMPONENTS_DIR);
+        self.executor.apply(FileOp::create_dir(&dir))?;
+        for name in [ZBM_KERNEL, ZBM_INITRAMFS] {
+            self.executor.apply(FileOp::copy(
+                primary.join(COMPONENTS_DIR).join(name),
+                dir.join(name),
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// `generate-zbm` configuration building unversioned components only
+    fn generate_zbm_config(&self) -> String {
+        let boot = self.boot_mountpoint.display();
+        format!(
+            r#"# ZFSBootMenu components for syslinux
+Global:
+  ManageImages: true
+  BootMountPoint: {boot}
+  DracutConfDir: /etc/zfsbootmenu/dracut.conf.d
+  PreHooksDir: /etc/zfsbootmenu/hooks.d
+
+Components:
+  Enabled: true
+  ImageDir: {boot}/{dir}
+  Versions: false
+
+EFI:
+  Enabled: false
+
+Kernel:
+  CommandLine: ro quiet loglevel=0
+"#,
+            dir = COMPONENTS_DIR
+        )
+    }
+
+    /// syslinux configuration booting the ZFSBootMenu components
+    fn config() -> String {
+        format!(
+            r#"# ZFSBootMenu legacy BIOS boot
+PROMPT 0
+TIMEOUT 0
+DEFAULT zfsbootmenu
+
+LABEL zfsbootmenu
+  KERNEL /{dir}/{kernel}
+  INITRD /{dir}/{initramfs}
+  APPEND zfsbootmenu ro quiet loglevel=0
+"#,
+            dir = COMPONENTS_DIR,
+            kernel = ZBM_KERNEL,
+            initramfs = ZBM_INITRAMFS
+        )
+    }
+
+    /// syslinux's GPT boot code on the running system
+    fn gptmbr() -> &'static Path {
+        GPTMBR_PATHS
+            .iter()
+            .map(Path::new)
+            .find(|path| path.exists())
+            .unwrap_or(Path::new(GPTMBR_PATHS[0]))
+    }
+}
+
+impl BootEntryManager for Syslinux {
+    fn install(&self) -> Result<()> {
+        log::info!("Installing syslinux");
+
+        execute(
+            &*self.executor,
+            Command::new("extlinux")
+                .arg("--install")
+                .arg(&self.boot_mountpoint),
+        )?;
+        self.executor.apply(FileOp::write(
+            self.boot_mountpoint.join("syslinux.cfg"),
+            Self::config(),
+        ))?;
+
+        log::info!("syslinux installed successfully");
+        Ok(())
+    }
+
+    /// BIOS firmware has no boot entries: the boot partition is marked
+    /// bootable and the disk's MBR gets the boot code that starts it
+    fn add_entry(&self, disk: &Path, partition: u32) -> Result<()> {
+        log::info!(
+            "Writing syslinux boot code to {} for partition {}",
+            disk.display(),
+            partition
+        );
+
+        execute(
+            &*self.executor,
+            Command::new("sgdisk")
+                .arg(format!(
+                    "--attributes={}:set:{}",
+                    partition, LEGACY_BOOTABLE_BIT
+                ))
+                .arg(disk),
+        )?;
+        execute(
+            &*self.executor,
+            Command::new("dd")
+                .arg("bs=440")
+                .arg("count=1")
+                .arg("conv=notrunc")
+                .arg(format!("if={}", Self::gptmbr().display()))
+                .arg(format!("of={}", disk.display())),
+        )?;
+        Ok(())
+    }
+}
+
+/// Execute a command, reporting failures as bootloader errors
+fn execute(executor: &dyn CommandExecutor, cmd: &mut Command) -> Result<ExecResult> {
+    executor.execute_as(
+        cmd,
+        &ExecOptions::default(),
+        InstallerError::bootloader_command,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exec::RecordingExecutor;
+
+    #[test]
+    fn test_install_boots_zbm_components() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let boot = PathBuf::from("/mnt/boot/syslinux");
+        let syslinux = Syslinux::new(boot.clone(), true).with_executor(executor.clone());
+
+        syslinux.generate_components().unwrap();
+        syslinux.install().unwrap();
+        syslinux.add_entry(Path::new("/dev/sda"), 2).unwrap();
+
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            argv[0],
+            "generate-zbm --config /etc/zfsbootmenu/syslinux.yaml"
+        );
+        assert_eq!(argv[1], "extlinux --install /mnt/boot/syslinux");
+        assert_eq!(argv[2], "sgdisk --attributes=2:set:2 /dev/sda");
+        assert!(argv[3].starts_with("dd bs=440 count=1 conv=notrunc if=/usr/"));
+        assert!(argv[3].ends_with("gptmbr.bin of=/dev/sda"));
+
+        let generate = executor.written(Path::new(GENERATE_ZBM_CONFIG)).unwrap();
+        assert!(generate.contains("ImageDir: /mnt/boot/syslinux/zfsbootmenu"));
+        assert!(generate.contains("EFI:\n  Enabled: false"));
+
+        let config = executor.written(&boot.join("syslinux.cfg")).unwrap();
+        assert!(config.contains("KERNEL /zfsbootmenu/vmlinuz-bootmenu"));
+        assert!(config.contains("INITRD /zfsbootmenu/initramfs-bootmenu.img"));
+    }
+
+    #[test]
+    fn test_mirror_copies_the_components() {
+        let executor = Arc::new(RecordingExecutor::new());
+        Syslinux::new(PathBuf::from("/mnt/boot/syslinux2"), true)
+            .with_executor(executor.clone())
+            .copy_components(Path::new("/mnt/boot/syslinux"))
+            .unwrap();
+
+        assert!(executor.file_ops().contains(&FileOp::copy(
+            "/mnt/boot/syslinux/zfsbootmenu/vmlinuz-bootmenu",
+            "/mnt/boot/syslinux2/zfsbootmenu/vmlinuz-bootmenu",
+        )));
+        assert!(executor.commands().is_empty());
+    }
+}
@@ -1,14 +1,29 @@
 //! systemd-boot configuration
 
-use crate::error::Result;
-use std::fs;
+use super::zbm::ZBM_LOADER;
+use super::BootEntryManager;
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, FileOp, RetryPolicy, SystemExecutor};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+
+/// systemd-boot as `bootctl install` puts it on the ESP
+const SYSTEMD_BOOT_IMAGE: &str = "EFI/systemd/systemd-bootx64.efi";
+
+/// `bootctl` failures that leave a working systemd-boot behind, as the
+/// operation and part of its stderr; every other failure is an error
+const NON_FATAL: &[(&str, &str)] = &[
+    // The ESP's systemd-boot is as new as the running system's, or newer
+    ("update", "boot loader version in place already"),
+    ("update", "newer boot loader version exists already"),
+];
 
 /// systemd-boot manager
 pub struct SystemdBoot {
     efi_mountpoint: PathBuf,
-    dry_run: bool,
+    shim: bool,
+    executor: Arc<dyn CommandExecutor>,
 }
 
 impl SystemdBoot {
@@ -16,49 +31,50 @@ impl SystemdBoot {
     pub fn new(efi_mountpoint: PathBuf, dry_run: bool) -> Self {
         Self {
             efi_mountpoint,
-            dry_run,
+            shim: false,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
         }
     }
 
-    /// Execute a command
-    fn execute(&self, cmd: &mut Command) -> Result<std::process::Output> {
-        let cmd_str = format!("{:?}", cmd);
-
-        if self.dry_run {
-            log::info!("[DRY RUN] Would execute: {}", cmd_str);
-            return Ok(std::process::Output {
-                status: std::process::ExitStatus::default(),
-                stdout: Vec::new(),
-                stderr: Vec::new(),
-            });
-        }
-
-        log::debug!("Executing: {}", cmd_str);
-        let output = cmd.output()?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            log::warn!("Command failed (non-fatal): {}\n{}", cmd_str, stderr);
-        }
-
-        Ok(output)
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
     }
 
-    /// Install systemd-boot
-    pub fn install(&self) -> Result<()> {
-        log::info!("Installing systemd-boot");
+    /// Start the loader through shim, for Secure Boot
+    pub fn with_shim(mut self, shim: bool) -> Self {
+        self.shim = shim;
+        self
+    }
 
-        self.execute(
+    /// Run `bootctl <operation>` on the ESP
+    ///
+    /// Failures are bootloader errors, except the ones in [`NON_FATAL`],
+    /// which are logged.
+    fn bootctl(&self, operation: &str) -> Result<()> {
+        let result = self.executor.execute_with(
             Command::new("bootctl")
                 .arg("--path")
                 .arg(&self.efi_mountpoint)
-                .arg("install"),
-        )?;
-
-        self.configure()?;
-
-        log::info!("systemd-boot installed successfully");
-        Ok(())
+                .arg(operation),
+            &ExecOptions::new().retry(RetryPolicy::transient()),
+        );
+        match result {
+            Ok(_) => Ok(()),
+            Err(InstallerError::CommandFailed { cmd, stderr, .. })
+                if NON_FATAL
+                    .iter()
+                    .any(|(op, message)| *op == operation && stderr.contains(message)) =>
+            {
+                log::warn!("Command failed (non-fatal): {}\n{}", cmd, stderr);
+                Ok(())
+            }
+            Err(InstallerError::CommandFailed { cmd, stderr, .. }) => {
+                Err(InstallerError::bootloader_command(cmd, stderr))
+            }
+            Err(e) => Err(e),
+        }
     }
 
     /// Configure systemd-boot
@@ -69,9 +85,7 @@ impl SystemdBoot {
         let entries_dir = loader_dir.join("entries");
 
         // Create directories
-        if !self.dry_run {
-            fs::create_dir_all(&entries_dir)?;
-        }
+        self.executor.apply(FileOp::create_dir(&entries_dir))?;
 
         // Write loader.conf
         let loader_conf = loader_dir.join("loader.conf");
@@ -81,7 +95,8 @@ console-mode max
 editor no
 "#;
 
-        self.write_file(&loader_conf, loader_content)?;
+        self.executor
+            .apply(FileOp::write(&loader_conf, loader_content))?;
 
         // Write ZFSBootMenu boot entry
         let zbm_entry = entries_dir.join("zfsbootmenu.conf");
@@ -89,30 +104,119 @@ editor no
 efi /EFI/ZBM/zfsbootmenu.EFI
 "#;
 
-        self.write_file(&zbm_entry, entry_content)?;
+        self.executor
+            .apply(FileOp::write(&zbm_entry, entry_content))?;
+
+        // And one for the backup image, in case an update breaks the first
+        let backup_entry = entries_dir.join("zfsbootmenu-backup.conf");
+        let backup_content = r#"title ZFSBootMenu (Backup)
+efi /EFI/ZBM/zfsbootmenu-backup.EFI
+"#;
+
+        self.executor
+            .apply(FileOp::write(&backup_entry, backup_content))?;
 
         Ok(())
     }
+}
+
+impl BootEntryManager for SystemdBoot {
+    fn install(&self) -> Result<()> {
+        log::info!("Installing systemd-boot");
 
-    /// Helper to write file
-    fn write_file(&self, path: &Path, content: &str) -> Result<()> {
-        if self.dry_run {
-            log::info!("[DRY RUN] Would write to: {}", path.display());
-            return Ok(());
+        // An ESP that has systemd-boot already, e.g. one kept from another
+        // install, is updated rather than installed over
+        if self.efi_mountpoint.join(SYSTEMD_BOOT_IMAGE).exists() {
+            self.bootctl("update")?;
+        } else {
+            self.bootctl("install")?;
         }
 
-        fs::write(path, content)?;
+        self.configure()?;
+
+        log::info!("systemd-boot installed successfully");
         Ok(())
     }
+
+    /// Firmware entries start ZFSBootMenu directly; systemd-boot is the
+    /// fallback that `bootctl install` registers for itself
+    fn add_entry(&self, disk: &Path, partition: u32) -> Result<()> {
+        super::register_efi_entry(
+            &*self.executor,
+            disk,
+            partition,
+            "ZFSBootMenu",
+            ZBM_LOADER,
+            self.shim,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::exec::RecordingExecutor;
+
+    #[test]
+    fn test_install_writes_loader_config() {
+        let executor = Arc::new(RecordingExecutor::new());
+        SystemdBoot::new(PathBuf::from("/boot/efi"), true)
+            .with_executor(executor.clone())
+            .install()
+            .unwrap();
+
+        let loader = executor
+            .written(Path::new("/boot/efi/loader/loader.conf"))
+            .unwrap();
+        assert!(loader.starts_with("default zfsbootmenu.conf\n"));
+        let entry = executor
+            .written(Path::new("/boot/efi/loader/entries/zfsbootmenu.conf"))
+            .unwrap();
+        assert!(entry.contains("efi /EFI/ZBM/zfsbootmenu.EFI"));
+        let backup = executor
+            .written(Path::new(
+                "/boot/efi/loader/entries/zfsbootmenu-backup.conf",
+            ))
+            .unwrap();
+        assert!(backup.starts_with("title ZFSBootMenu (Backup)\n"));
+        assert!(backup.contains("efi /EFI/ZBM/zfsbootmenu-backup.EFI"));
+    }
+
+    #[test]
+    fn test_bootctl_failures_are_fatal() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program(
+            "bootctl",
+            1,
+            "Failed to write file: No space left on device",
+        );
+        let err = SystemdBoot::new(PathBuf::from("/boot/efi"), true)
+            .with_executor(executor.clone())
+            .install()
+            .unwrap_err();
+        assert!(matches!(err, InstallerError::BootloaderError(_)));
+        assert!(executor.file_ops().is_empty());
+    }
+
     #[test]
-    fn test_systemd_boot_creation() {
-        let systemd_boot = SystemdBoot::new(PathBuf::from("/boot/efi"), true);
-        assert!(systemd_boot.dry_run);
+    fn test_update_of_a_current_loader_is_not_fatal() {
+        let esp = tempfile::tempdir().unwrap();
+        let image = esp.path().join(SYSTEMD_BOOT_IMAGE);
+        std::fs::create_dir_all(image.parent().unwrap()).unwrap();
+        std::fs::write(&image, b"MZ").unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program(
+            "bootctl",
+            1,
+            "Skipping \"/boot/efi/EFI/systemd/systemd-bootx64.efi\", since same boot loader version in place already.",
+        );
+        SystemdBoot::new(esp.path().to_path_buf(), true)
+            .with_executor(executor.clone())
+            .install()
+            .unwrap();
+
+        assert_eq!(executor.commands()[0].args.last().unwrap(), "update");
     }
 }
@@ -1,9 +1,81 @@
 //! ZFSBootMenu installation and configuration
 
+use crate::config::ZbmSource;
 use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, ExecResult, FileOp, SystemExecutor};
+use std::env;
+use std::ffi::OsString;
 use std::fs;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+
+/// ZFSBootMenu release assumed when the latest one can't be queried (dry run)
+pub const ZBM_VERSION: &str = "2.3.0";
+
+/// ZFSBootMenu image as a firmware loader path
+pub(crate) const ZBM_LOADER: &str = "\\EFI\\ZBM\\zfsbootmenu.EFI";
+
+/// Backup copy of the ZFSBootMenu image, next to it on the ESP
+const BACKUP_IMAGE: &str = "zfsbootmenu-backup.EFI";
+
+/// Backup image as a firmware loader path
+pub(crate) const BACKUP_LOADER: &str = "\\EFI\\ZBM\\zfsbootmenu-backup.EFI";
+
+/// Removable-media fallback loader, started when no boot entry works
+const FALLBACK_IMAGE: &str = "EFI/BOOT/BOOTX64.EFI";
+
+/// Property ZFSBootMenu reads a boot environment's kernel command line from
+pub const COMMANDLINE_PROPERTY: &str = "org.zfsbootmenu:commandline";
+
+/// Property naming the dataset ZFSBootMenu mounts to find key files
+pub const KEYSOURCE_PROPERTY: &str = "org.zfsbootmenu:keysource";
+
+/// Kernel command line set on new boot environments
+pub const DEFAULT_KERNEL_CMDLINE: &str = "rw quiet";
+
+/// ZFSBootMenu releases on GitHub
+const RELEASES_URL: &str = "https://github.com/zbm-dev/zfsbootmenu/releases";
+
+/// GitHub API endpoint describing the latest release
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/zbm-dev/zfsbootmenu/releases/latest";
+
+/// Where downloaded images are kept, one directory per version
+pub const DEFAULT_CACHE_DIR: &str = "/var/cache/zbm-installer";
+
+/// Checksums of a release, as published and as kept next to the image
+const CHECKSUMS_FILE: &str = "sha256.txt";
+
+/// `generate-zbm` configuration on the running system
+pub const GENERATE_ZBM_CONFIG: &str = "/etc/zfsbootmenu/config.yaml";
+
+/// `Kernel.Prefix` of the images `generate-zbm` builds in `EFI/ZBM`; the
+/// EFI images are versioned (`vmlinuz-2.3.0_1.EFI`)
+const LOCAL_PREFIX: &str = "vmlinuz";
+
+/// Components `generate-zbm` builds next to the EFI image, which the GRUB
+/// BIOS path starts
+const LOCAL_COMPONENTS: &[&str] = &["vmlinuz-bootmenu", "initramfs-bootmenu.img"];
+
+/// A ZFSBootMenu EFI image ready to copy onto the ESP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZbmImage {
+    /// Release version, or `local` for a custom image or local build
+    pub version: String,
+    /// Where the image is
+    pub path: PathBuf,
+    /// SHA-256 of the image (None in dry-run mode)
+    pub sha256: Option<String>,
+}
+
+impl ZbmImage {
+    /// Whether the image was checksummed, rather than only planned in
+    /// dry-run mode, where its path is where it would be
+    pub fn is_verified(&self) -> bool {
+        self.sha256.is_some()
+    }
+}
 
 /// ZFSBootMenu installer
 pub struct ZbmInstaller {
@@ -11,6 +83,12 @@ pub struct ZbmInstaller {
     pool_name: String,
     efi_mountpoint: PathBuf,
     dry_run: bool,
+    executor: Arc<dyn CommandExecutor>,
+    source: ZbmSource,
+    release_fallback: bool,
+    cache_dir: PathBuf,
+    artifact_dir: Option<PathBuf>,
+    search_path: Option<OsString>,
 }
 
 impl ZbmInstaller {
@@ -20,99 +98,460 @@ impl ZbmInstaller {
             pool_name,
             efi_mountpoint,
             dry_run,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+            source: ZbmSource::default(),
+            release_fallback: false,
+            cache_dir: PathBuf::from(DEFAULT_CACHE_DIR),
+            artifact_dir: None,
+            search_path: None,
         }
     }
 
-    /// Execute a command
-    fn execute(&self, cmd: &mut Command) -> Result<std::process::Output> {
-        let cmd_str = format!("{:?}", cmd);
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
 
-        if self.dry_run {
-            log::info!("[DRY RUN] Would execute: {}", cmd_str);
-            return Ok(std::process::Output {
-                status: std::process::ExitStatus::default(),
-                stdout: Vec::new(),
-                stderr: Vec::new(),
-            });
-        }
+    /// Take the image from `source` instead of the latest release
+    pub fn with_source(mut self, source: ZbmSource) -> Self {
+        self.source = source;
+        self
+    }
 
-        log::debug!("Executing: {}", cmd_str);
-        let output = cmd.output()?;
+    /// Install the latest release when a local build finds no `generate-zbm`
+    pub fn with_release_fallback(mut self, release_fallback: bool) -> Self {
+        self.release_fallback = release_fallback;
+        self
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(InstallerError::BootloaderError(format!(
-                "Command failed: {}\n{}",
-                cmd_str, stderr
-            )));
+    /// Keep downloads in `cache_dir` instead of [`DEFAULT_CACHE_DIR`]
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    /// Take releases from `artifact_dir`, filled by [`fetch_artifacts`],
+    /// instead of downloading them
+    pub fn with_artifact_dir(mut self, artifact_dir: Option<PathBuf>) -> Self {
+        self.artifact_dir = artifact_dir;
+        self
+    }
+
+    /// Look for `generate-zbm` in `search_path` instead of `$PATH`
+    pub fn with_search_path(mut self, search_path: impl Into<OsString>) -> Self {
+        self.search_path = Some(search_path.into());
+        self
+    }
+
+    /// Execute a command, reporting failures as bootloader errors
+    fn execute(&self, cmd: &mut Command) -> Result<ExecResult> {
+        self.executor.execute_as(
+            cmd,
+            &ExecOptions::default(),
+            InstallerError::bootloader_command,
+        )
+    }
+
+    /// The release to install: the pinned one, or the latest
+    pub fn resolve_version(&self) -> Result<String> {
+        if let ZbmSource::Release {
+            version: Some(version),
+        } = &self.source
+        {
+            return Ok(version.trim_start_matches('v').to_string());
+        }
+        if let Some(dir) = &self.artifact_dir {
+            return Ok(artifact_image(dir, None)?.version);
+        }
+        if self.dry_run {
+            log::info!(
+                "[DRY RUN] Would query the latest ZFSBootMenu release, assuming {}",
+                ZBM_VERSION
+            );
+            return Ok(ZBM_VERSION.to_string());
         }
 
-        Ok(output)
+        let response = self.fetch(LATEST_RELEASE_URL)?;
+        let release: serde_json::Value = serde_json::from_str(&response).map_err(|e| {
+            InstallerError::BootloaderError(format!(
+                "Unreadable response from {}: {}",
+                LATEST_RELEASE_URL, e
+            ))
+        })?;
+        let tag = release["tag_name"].as_str().ok_or_else(|| {
+            InstallerError::BootloaderError(format!("No release tag in {}", LATEST_RELEASE_URL))
+        })?;
+
+        log::info!("Latest ZFSBootMenu release is {}", tag);
+        Ok(tag.trim_start_matches('v').to_string())
     }
 
-    /// Download ZFSBootMenu release
-    pub fn download_zbm(&self, version: &str) -> Result<PathBuf> {
-        log::info!("Downloading ZFSBootMenu version {}", version);
+    /// Download a ZFSBootMenu release, verified against its `sha256.txt`
+    ///
+    /// Downloads are cached per version, next to their `sha256.txt`; a
+    /// cached image is used again as long as it still matches the published
+    /// checksum. With an artifact directory nothing is downloaded: the
+    /// release is taken from there, verified the same way.
+    pub fn download_zbm(&self, version: &str) -> Result<ZbmImage> {
+        if let Some(dir) = &self.artifact_dir {
+            return self.artifact(dir, version);
+        }
 
-        let url = format!(
-            "https://get.zfsbootmenu.org/efi/zfsbootmenu-release-x86_64-v{}.EFI",
-            version
-        );
+        log::info!("Downloading ZFSBootMenu version {}", version);
 
-        let temp_path = PathBuf::from(format!("/tmp/zfsbootmenu-v{}.EFI", version));
+        let release_url = format!("{}/download/v{}", RELEASES_URL, version);
+        let cache_dir = self.cache_dir.join(version);
 
         if self.dry_run {
             log::info!(
-                "[DRY RUN] Would download {} to {}",
-                url,
-                temp_path.display()
+                "[DRY RUN] Would download {}/sha256.txt and the release EFI image it lists to {}",
+                release_url,
+                cache_dir.display()
             );
-            return Ok(temp_path);
+            return Ok(ZbmImage {
+                version: version.to_string(),
+                path: cache_dir.join(format!("zfsbootmenu-release-x86_64-v{}.EFI", version)),
+                sha256: None,
+            });
         }
 
-        // Use curl to download
+        let checksums = self.fetch(&format!("{}/{}", release_url, CHECKSUMS_FILE))?;
+        let (file, expected) = release_image(&checksums, version)?;
+
+        let path = cache_dir.join(&file);
+        if path.exists() && self.sha256(&path)?.as_deref() == Some(expected.as_str()) {
+            log::info!("Using cached {}", path.display());
+        } else {
+            self.executor.apply(FileOp::create_dir(&cache_dir))?;
+            self.execute(
+                Command::new("curl")
+                    .arg("-fsSL")
+                    .arg("-o")
+                    .arg(&path)
+                    .arg(format!("{}/{}", release_url, file)),
+            )?;
+
+            let actual = self.sha256(&path)?;
+            if actual.as_deref() != Some(expected.as_str()) {
+                let _ = fs::remove_file(&path);
+                return Err(InstallerError::BootloaderError(format!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    file,
+                    expected,
+                    actual.as_deref().unwrap_or("nothing")
+                )));
+            }
+        }
+
+        self.executor
+            .apply(FileOp::write(cache_dir.join(CHECKSUMS_FILE), checksums))?;
+
+        log::info!("ZFSBootMenu image {} sha256 {}", file, expected);
+        Ok(ZbmImage {
+            version: version.to_string(),
+            path,
+            sha256: Some(expected),
+        })
+    }
+
+    /// Release `version` from the artifact directory `dir`, verified
+    /// against the `sha256.txt` kept with it
+    fn artifact(&self, dir: &Path, version: &str) -> Result<ZbmImage> {
+        let image = artifact_image(dir, Some(version))?;
+        log::info!(
+            "Using ZFSBootMenu {} from {}",
+            image.version,
+            image.path.display()
+        );
+
+        match self.sha256(&image.path)? {
+            None => Ok(ZbmImage {
+                sha256: None,
+                ..image
+            }),
+            Some(actual) if image.sha256.as_deref() == Some(actual.as_str()) => Ok(image),
+            Some(actual) => Err(InstallerError::BootloaderError(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                image.path.display(),
+                image.sha256.as_deref().unwrap_or_default(),
+                actual
+            ))),
+        }
+    }
+
+    /// The image to copy onto the ESP: the custom one if given, otherwise a
+    /// download
+    fn image(&self) -> Result<ZbmImage> {
+        let ZbmSource::CustomImage { path } = &self.source else {
+            return self.download_zbm(&self.resolve_version()?);
+        };
+
+        if !self.dry_run && !path.is_file() {
+            return Err(InstallerError::BootloaderError(format!(
+                "ZFSBootMenu image {} not found",
+                path.display()
+            )));
+        }
+        let sha256 = self.sha256(path)?;
+        log::info!(
+            "Using local ZFSBootMenu image {} (sha256 {})",
+            path.display(),
+            sha256.as_deref().unwrap_or("not computed in dry run")
+        );
+
+        Ok(ZbmImage {
+            version: "local".to_string(),
+            path: path.clone(),
+            sha256,
+        })
+    }
+
+    /// Whether `generate-zbm` is installed on the running system
+    ///
+    /// Searches `$PATH` itself rather than running `which`, so a dry run
+    /// plans the same source as a real one would.
+    fn has_generate_zbm(&self) -> bool {
+        let search_path = self
+            .search_path
+            .clone()
+            .or_else(|| env::var_os("PATH"))
+            .unwrap_or_default();
+        env::split_paths(&search_path).any(|dir| {
+            fs::metadata(dir.join("generate-zbm"))
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
+    }
+
+    /// Build the image with the host's `generate-zbm`, straight onto the ESP
+    ///
+    /// `generate-zbm` names the image after its version; the newest one is
+    /// copied to the name the boot entries start once every expected file is
+    /// there.
+    fn build_local(&self) -> Result<ZbmImage> {
+        log::info!("Building ZFSBootMenu with generate-zbm");
+
         self.execute(
-            Command::new("curl")
-                .arg("-L")
-                .arg("-o")
-                .arg(&temp_path)
-                .arg(&url),
+            Command::new("generate-zbm")
+                .arg("--config")
+                .arg(GENERATE_ZBM_CONFIG),
         )?;
 
-        Ok(temp_path)
+        let zbm_dir = self.efi_mountpoint.join("EFI").join("ZBM");
+        let built = if self.dry_run {
+            zbm_dir.join(format!("{}.EFI", LOCAL_PREFIX))
+        } else {
+            let mut missing: Vec<&str> = LOCAL_COMPONENTS
+                .iter()
+                .copied()
+                .filter(|name| !zbm_dir.join(name).is_file())
+                .collect();
+            let built = newest_local_image(&zbm_dir);
+            if built.is_none() {
+                missing.insert(0, "an EFI image");
+            }
+            match built {
+                Some(built) if missing.is_empty() => built,
+                _ => {
+                    return Err(InstallerError::BootloaderError(format!(
+                        "generate-zbm did not build {} in {}",
+                        missing.join(", "),
+                        zbm_dir.display()
+                    )))
+                }
+            }
+        };
+
+        let dest = Self::image_path(&self.efi_mountpoint);
+        self.executor.apply(FileOp::copy(built, &dest))?;
+        Ok(ZbmImage {
+            version: "local".to_string(),
+            sha256: self.sha256(&dest)?,
+            path: dest,
+        })
+    }
+
+    /// Fetch a URL, returning the body
+    ///
+    /// A skipped `curl` is an error rather than an empty body, which would
+    /// read as a release without checksums.
+    fn fetch(&self, url: &str) -> Result<String> {
+        let result = self.execute(Command::new("curl").arg("-fsSL").arg(url))?;
+        result.stdout_string().ok_or_else(|| {
+            InstallerError::BootloaderError(format!("{} was not fetched (dry run)", url))
+        })
+    }
+
+    /// SHA-256 of a file (None in dry-run mode)
+    fn sha256(&self, path: &Path) -> Result<Option<String>> {
+        let result = self.execute(Command::new("sha256sum").arg(path))?;
+        Ok(result
+            .stdout_string()
+            .and_then(|stdout| stdout.split_whitespace().next().map(str::to_lowercase)))
     }
 
-    /// Install ZFSBootMenu to EFI partition
-    pub fn install(&self) -> Result<()> {
+    /// Install ZFSBootMenu to EFI partition, along with a backup copy
+    ///
+    /// A local build falls back to the latest release, with a warning, when
+    /// `generate-zbm` is missing and the fallback is allowed.
+    pub fn install(&self) -> Result<ZbmImage> {
         log::info!("Installing ZFSBootMenu");
 
         // Create EFI directory structure
         let zbm_dir = self.efi_mountpoint.join("EFI").join("ZBM");
-        self.create_directory(&zbm_dir)?;
+        self.executor.apply(FileOp::create_dir(&zbm_dir))?;
+
+        // A local build reads it; otherwise it is there for later rebuilds
+        self.generate_config()?;
 
-        // Download latest ZBM
-        let zbm_efi = self.download_zbm("2.3.0")?; // Use stable version
+        let image = match self.source {
+            ZbmSource::Local if self.has_generate_zbm() => self.build_local()?,
+            ZbmSource::Local if !self.release_fallback => {
+                return Err(InstallerError::BootloaderError(
+                    "generate-zbm not found; install ZFSBootMenu or allow the release fallback"
+                        .to_string(),
+                ))
+            }
+            ZbmSource::Local => {
+                log::warn!("generate-zbm not found, installing the latest release instead");
+                self.install_release(self.download_zbm(&self.resolve_version()?)?)?
+            }
+            _ => self.install_release(self.image()?)?,
+        };
 
-        // Copy to EFI partition
-        let dest = zbm_dir.join("zfsbootmenu.EFI");
-        self.copy_file(&zbm_efi, &dest)?;
+        // A broken update of the image still leaves the backup to boot
+        self.executor.apply(FileOp::copy(
+            Self::image_path(&self.efi_mountpoint),
+            Self::backup_path(&self.efi_mountpoint),
+        ))?;
 
-        // Generate ZBM configuration
-        self.generate_config()?;
+        if image.is_verified() {
+            log::info!("ZFSBootMenu {} installed successfully", image.version);
+        } else {
+            log::info!(
+                "[DRY RUN] ZFSBootMenu {} (unverified) would be installed",
+                image.version
+            );
+        }
+        Ok(image)
+    }
+
+    /// Copy a downloaded or custom image onto the ESP
+    fn install_release(&self, image: ZbmImage) -> Result<ZbmImage> {
+        let dest = Self::image_path(&self.efi_mountpoint);
+        self.executor.apply(FileOp::copy(&image.path, &dest))?;
+        Ok(image)
+    }
+
+    /// Path of the ZFSBootMenu EFI image on an ESP
+    pub(crate) fn image_path(esp: &Path) -> PathBuf {
+        esp.join("EFI").join("ZBM").join("zfsbootmenu.EFI")
+    }
 
-        // Generate initramfs with ZFS support
-        self.generate_initramfs()?;
+    /// Path of the backup ZFSBootMenu EFI image on an ESP
+    pub(crate) fn backup_path(esp: &Path) -> PathBuf {
+        Self::image_path(esp).with_file_name(BACKUP_IMAGE)
+    }
+
+    /// Copy the image to the removable-media fallback path of the ESP at
+    /// `esp`, `EFI/BOOT/BOOTX64.EFI`
+    ///
+    /// The firmware starts it when no boot entry works, e.g. after its NVRAM
+    /// was reset. A different loader already there belongs to another system
+    /// and is left alone, with a warning.
+    pub fn install_fallback(&self, esp: &Path) -> Result<()> {
+        let fallback = esp.join(FALLBACK_IMAGE);
+        let image = Self::image_path(esp);
+        if fallback.exists() && fs::read(&fallback).ok() != fs::read(&image).ok() {
+            log::warn!(
+                "Not installing the fallback loader: {} belongs to another system",
+                fallback.display()
+            );
+            return Ok(());
+        }
+
+        log::info!(
+            "Installing ZFSBootMenu as fallback loader {}",
+            fallback.display()
+        );
+        if let Some(dir) = fallback.parent() {
+            self.executor.apply(FileOp::create_dir(dir))?;
+        }
+        self.executor.apply(FileOp::copy(image, fallback))
+    }
+
+    /// Copy the installed ZFSBootMenu image onto another ESP
+    ///
+    /// Call after [`install`](Self::install) has populated the primary ESP.
+    pub fn install_mirror(&self, esp: &Path) -> Result<()> {
+        log::info!("Installing ZFSBootMenu on mirror ESP {}", esp.display());
+
+        self.executor
+            .apply(FileOp::create_dir(esp.join("EFI").join("ZBM")))?;
+        self.executor.apply(FileOp::copy(
+            Self::image_path(&self.efi_mountpoint),
+            Self::image_path(esp),
+        ))?;
+        self.executor.apply(FileOp::copy(
+            Self::backup_path(&self.efi_mountpoint),
+            Self::backup_path(esp),
+        ))?;
+
+        Ok(())
+    }
+
+    /// Add a firmware boot entry for the ZFSBootMenu image on `disk`
+    ///
+    /// Failures are logged rather than fatal: the image stays reachable
+    /// through systemd-boot and the removable-media fallback.
+    pub fn register_boot_entry(&self, disk: &Path, partition: u32) -> Result<()> {
+        super::register_efi_entry(
+            &*self.executor,
+            disk,
+            partition,
+            "ZFSBootMenu",
+            ZBM_LOADER,
+            false,
+        )
+    }
+
+    /// Make every mirror ESP identical to the primary one
+    ///
+    /// Run after kernel or ZFSBootMenu updates, which only touch the primary.
+    pub fn sync_esp(&self, mirrors: &[PathBuf]) -> Result<()> {
+        let source = format!("{}/", self.efi_mountpoint.display());
+        for mirror in mirrors {
+            log::info!(
+                "Syncing ESP {} to {}",
+                self.efi_mountpoint.display(),
+                mirror.display()
+            );
+            // FAT has no owners or permissions and 2-second timestamps
+            self.execute(
+                Command::new("rsync")
+                    .arg("--recursive")
+                    .arg("--times")
+                    .arg("--modify-window=1")
+                    .arg("--delete")
+                    .arg(&source)
+                    .arg(format!("{}/", mirror.display())),
+            )?;
+        }
 
-        log::info!("ZFSBootMenu installed successfully");
         Ok(())
     }
 
     /// Generate ZFSBootMenu configuration
+    ///
+    /// `generate-zbm` keeps two versions of the EFI image, so a rebuild
+    /// still leaves the previous one to boot.
     fn generate_config(&self) -> Result<()> {
         log::info!("Generating ZFSBootMenu configuration");
 
-        let config_dir = Path::new("/etc/zfsbootmenu");
-        self.create_directory(config_dir)?;
+        let config_file = Path::new(GENERATE_ZBM_CONFIG);
+        if let Some(config_dir) = config_file.parent() {
+            self.executor.apply(FileOp::create_dir(config_dir))?;
+        }
 
         let config_content = format!(
             r#"# ZFSBootMenu configuration
@@ -126,142 +565,462 @@ Global:
 Components:
   Enabled: true
   ImageDir: {}/EFI/ZBM
-  Versions: 3
+  Versions: false
   Cmdline: ro quiet loglevel=0
 
 EFI:
   ImageDir: {}/EFI/ZBM
-  Versions: false
+  Versions: 2
   Enabled: true
 
 Kernel:
   CommandLine: ro quiet loglevel=4
+  Prefix: vmlinuz
 "#,
             self.efi_mountpoint.display(),
             self.efi_mountpoint.display(),
             self.efi_mountpoint.display()
         );
 
-        let config_file = config_dir.join("config.yaml");
-        self.write_file(&config_file, &config_content)?;
+        self.executor
+            .apply(FileOp::write(config_file, config_content))?;
 
         Ok(())
     }
+}
 
-    /// Generate initramfs with ZFS support
-    fn generate_initramfs(&self) -> Result<()> {
-        log::info!("Generating initramfs with ZFS support");
+/// Download a ZFSBootMenu release (the latest unless `version` is given)
+/// with its checksums into `dest`, for offline installs to use as their
+/// artifact directory
+pub fn fetch_artifacts(dest: &Path, version: Option<String>) -> Result<ZbmImage> {
+    let downloader = ZbmInstaller::new(String::new(), PathBuf::new(), false)
+        .with_source(ZbmSource::Release { version })
+        .with_cache_dir(dest.to_path_buf());
+    downloader.download_zbm(&downloader.resolve_version()?)
+}
 
-        // Check if we're using dracut or mkinitcpio
-        if Path::new("/usr/bin/dracut").exists() {
-            self.generate_dracut()?;
-        } else if Path::new("/usr/bin/mkinitcpio").exists() {
-            self.generate_mkinitcpio()?;
-        } else {
-            return Err(InstallerError::BootloaderError(
-                "No supported initramfs generator found (dracut or mkinitcpio)".to_string(),
-            ));
-        }
+/// The release EFI image of `version` in an artifact directory, with its
+/// expected checksum; the newest release there unless `version` is given
+///
+/// Releases are kept as [`download_zbm`](ZbmInstaller::download_zbm) caches
+/// them: a directory per version with the image and its `sha256.txt`.
+pub fn artifact_image(dir: &Path, version: Option<&str>) -> Result<ZbmImage> {
+    let version = match version {
+        Some(version) => version.trim_start_matches('v').to_string(),
+        None => newest_release(dir).ok_or_else(|| {
+            InstallerError::BootloaderError(format!(
+                "No ZFSBootMenu release in {}; fill it with fetch-artifacts",
+                dir.display()
+            ))
+        })?,
+    };
 
-        Ok(())
+    let release_dir = dir.join(&version);
+    let checksums = fs::read_to_string(release_dir.join(CHECKSUMS_FILE)).map_err(|e| {
+        InstallerError::BootloaderError(format!(
+            "No checksums for ZFSBootMenu {} in {}: {}",
+            version,
+            release_dir.display(),
+            e
+        ))
+    })?;
+    let (file, expected) = release_image(&checksums, &version)?;
+    let path = release_dir.join(file);
+    if !path.is_file() {
+        return Err(InstallerError::BootloaderError(format!(
+            "ZFSBootMenu image {} not found",
+            path.display()
+        )));
     }
 
-    /// Generate initramfs using dracut
-    fn generate_dracut(&self) -> Result<()> {
-        log::info!("Generating initramfs with dracut");
+    Ok(ZbmImage {
+        version,
+        path,
+        sha256: Some(expected),
+    })
+}
 
-        // Create dracut config for ZFS
-        let dracut_conf_dir = Path::new("/etc/dracut.conf.d");
-        self.create_directory(dracut_conf_dir)?;
+/// The newest release directory with checksums in `dir`
+fn newest_release(dir: &Path) -> Option<String> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().join(CHECKSUMS_FILE).is_file())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let key: Vec<u64> = name
+                .split('.')
+                .map(str::parse)
+                .collect::<std::result::Result<_, _>>()
+                .ok()?;
+            Some((key, name))
+        })
+        .max()
+        .map(|(_, name)| name)
+}
 
-        let dracut_conf = dracut_conf_dir.join("zfsbootmenu.conf");
-        let conf_content = r#"# ZFSBootMenu dracut configuration
-add_dracutmodules+=" zfsbootmenu "
-omit_dracutmodules+=" network "
-hostonly=no
-compress="zstd"
-"#;
+/// File name and checksum of the release EFI image of `version` in the
+/// `sha256.txt` `checksums`
+fn release_image(checksums: &str, version: &str) -> Result<(String, String)> {
+    let prefix = format!("zfsbootmenu-release-x86_64-v{}", version);
+    parse_checksums(checksums)
+        .into_iter()
+        .find(|(file, _)| file.starts_with(&prefix) && file.ends_with(".EFI"))
+        .ok_or_else(|| {
+            InstallerError::BootloaderError(format!(
+                "sha256.txt of ZFSBootMenu v{} lists no release EFI image",
+                version
+            ))
+        })
+}
 
-        self.write_file(&dracut_conf, conf_content)?;
+/// The newest versioned EFI image `generate-zbm` built in `dir`
+fn newest_local_image(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(LOCAL_PREFIX) && name.ends_with(".EFI")
+        })
+        .max_by_key(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+        .map(|entry| entry.path())
+}
 
-        // Run generate-zbm if available
-        if Path::new("/usr/bin/generate-zbm").exists() {
-            self.execute(&mut Command::new("generate-zbm"))?;
-        } else {
-            // Fallback: run dracut manually
-            self.execute(
-                Command::new("dracut")
-                    .arg("--force")
-                    .arg("--add")
-                    .arg("zfsbootmenu")
-                    .arg(self.efi_mountpoint.join("EFI/ZBM/vmlinuz.efi")),
-            )?;
-        }
+/// Parse a `sha256.txt` into (file name, lowercase hex digest) pairs
+///
+/// Accepts both the BSD-style `SHA256 (file) = digest` lines ZFSBootMenu
+/// publishes and the `digest  file` lines of `sha256sum`.
+pub fn parse_checksums(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if let Some(rest) = line.strip_prefix("SHA256 (") {
+                let (file, digest) = rest.split_once(") = ")?;
+                return Some((file.to_string(), digest.trim().to_lowercase()));
+            }
+            let (digest, file) = line.split_once(char::is_whitespace)?;
+            let file = file.trim().trim_start_matches('*');
+            Some((file.to_string(), digest.to_lowercase()))
+        })
+        .collect()
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    const DIGEST: &str = "3f5a9c1e0b7d2a4c6e8f0a1b2c3d4e5f60718293a4b5c6d7e8f9a0b1c2d3e4f5";
+
+    const CHECKSUMS: &str = "SHA256 (zfsbootmenu-recovery-x86_64-v2.3.0-linux6.1.EFI) = 0000000000000000000000000000000000000000000000000000000000000000
+SHA256 (zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI) = 3F5A9C1E0B7D2A4C6E8F0A1B2C3D4E5F60718293A4B5C6D7E8F9A0B1C2D3E4F5
+SHA256 (zfsbootmenu-release-x86_64-v2.3.0-linux6.1.tar.gz) = 1111111111111111111111111111111111111111111111111111111111111111
+";
+
+    fn downloader(executor: Arc<RecordingExecutor>, cache: &Path) -> ZbmInstaller {
+        ZbmInstaller::new("zroot".to_string(), PathBuf::from("/mnt/boot/efi"), false)
+            .with_executor(executor)
+            .with_source(ZbmSource::Release {
+                version: Some("v2.3.0".to_string()),
+            })
+            .with_cache_dir(cache.to_path_buf())
     }
 
-    /// Generate initramfs using mkinitcpio
-    fn generate_mkinitcpio(&self) -> Result<()> {
-        log::info!("Generating initramfs with mkinitcpio");
+    #[test]
+    fn test_parse_checksum_formats() {
+        let sums = parse_checksums(CHECKSUMS);
+        assert_eq!(sums.len(), 3);
+        assert_eq!(
+            sums[1],
+            (
+                "zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI".to_string(),
+                DIGEST.to_string()
+            )
+        );
+        assert_eq!(
+            parse_checksums(&format!("{} *image.EFI\n", DIGEST)),
+            vec![("image.EFI".to_string(), DIGEST.to_string())]
+        );
+    }
 
-        // Create mkinitcpio config
-        let conf_content = r#"# ZFSBootMenu mkinitcpio configuration
-HOOKS=(base udev autodetect modconf block filesystems keyboard fsck zfsbootmenu)
-"#;
+    #[test]
+    fn test_download_verifies_checksum() {
+        let cache = tempfile::tempdir().unwrap();
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program_times("curl", 1, CHECKSUMS);
+        executor.respond_program("sha256sum", format!("{}  image.EFI\n", DIGEST));
+        let installer = downloader(executor.clone(), cache.path());
 
-        self.write_file(
-            Path::new("/etc/mkinitcpio.conf.d/zfsbootmenu.conf"),
-            conf_content,
-        )?;
+        assert_eq!(installer.resolve_version().unwrap(), "2.3.0");
+        let image = installer.download_zbm("2.3.0").unwrap();
+        assert_eq!(
+            image.path,
+            cache
+                .path()
+                .join("2.3.0/zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI")
+        );
+        assert_eq!(image.sha256.as_deref(), Some(DIGEST));
 
-        // Run mkinitcpio
-        self.execute(Command::new("mkinitcpio").arg("-P"))?;
+        let curl: Vec<String> = executor
+            .commands()
+            .iter()
+            .filter(|c| c.program == "curl")
+            .map(|c| c.args.last().unwrap().clone())
+            .collect();
+        assert_eq!(
+            curl,
+            vec![
+                "https://github.com/zbm-dev/zfsbootmenu/releases/download/v2.3.0/sha256.txt",
+                "https://github.com/zbm-dev/zfsbootmenu/releases/download/v2.3.0/zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI",
+            ]
+        );
+    }
 
-        Ok(())
+    #[test]
+    fn test_checksum_mismatch_fails() {
+        let cache = tempfile::tempdir().unwrap();
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program_times("curl", 1, CHECKSUMS);
+        executor.respond_program("sha256sum", format!("{}  image.EFI\n", "ab".repeat(32)));
+
+        let err = downloader(executor, cache.path())
+            .download_zbm("2.3.0")
+            .unwrap_err();
+        assert!(matches!(err, InstallerError::BootloaderError(_)));
+        assert!(err.to_string().contains("Checksum mismatch"));
     }
 
-    /// Helper to create directory
-    fn create_directory(&self, path: &Path) -> Result<()> {
-        if self.dry_run {
-            log::info!("[DRY RUN] Would create directory: {}", path.display());
-            return Ok(());
-        }
+    #[test]
+    fn test_dry_run_image_is_unverified() {
+        let cache = tempfile::tempdir().unwrap();
+        let executor = Arc::new(RecordingExecutor::new());
+        let image = ZbmInstaller::new("zroot".to_string(), PathBuf::from("/mnt/boot/efi"), true)
+            .with_executor(executor.clone())
+            .with_cache_dir(cache.path().to_path_buf())
+            .download_zbm("2.3.0")
+            .unwrap();
+        assert!(!image.is_verified());
+        assert!(executor.commands().is_empty());
 
-        fs::create_dir_all(path)?;
-        Ok(())
+        // A skipped curl is not an empty response
+        let err = ZbmInstaller::new("zroot".to_string(), PathBuf::from("/mnt/boot/efi"), false)
+            .with_executor(Arc::new(SystemExecutor::new(true)))
+            .resolve_version()
+            .unwrap_err();
+        assert!(err.to_string().contains("not fetched"), "{err}");
     }
 
-    /// Helper to copy file
-    fn copy_file(&self, src: &Path, dest: &Path) -> Result<()> {
-        if self.dry_run {
-            log::info!(
-                "[DRY RUN] Would copy {} to {}",
-                src.display(),
-                dest.display()
-            );
-            return Ok(());
-        }
+    #[test]
+    fn test_cached_image_is_reused() {
+        let cache = tempfile::tempdir().unwrap();
+        let cached = cache
+            .path()
+            .join("2.3.0/zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI");
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, b"MZ").unwrap();
 
-        fs::copy(src, dest)?;
-        Ok(())
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program_times("curl", 1, CHECKSUMS);
+        executor.respond_program("sha256sum", format!("{}  image.EFI\n", DIGEST));
+        downloader(executor.clone(), cache.path())
+            .download_zbm("2.3.0")
+            .unwrap();
+
+        // Only sha256.txt is fetched
+        let curl = executor
+            .commands()
+            .iter()
+            .filter(|c| c.program == "curl")
+            .count();
+        assert_eq!(curl, 1);
+        // and kept, so the cache can serve as an artifact directory
+        assert_eq!(
+            executor.written(&cache.path().join("2.3.0/sha256.txt")),
+            Some(CHECKSUMS.to_string())
+        );
     }
 
-    /// Helper to write file
-    fn write_file(&self, path: &Path, content: &str) -> Result<()> {
-        if self.dry_run {
-            log::info!("[DRY RUN] Would write to: {}", path.display());
-            return Ok(());
+    #[test]
+    fn test_artifact_dir_replaces_the_download() {
+        let dir = tempfile::tempdir().unwrap();
+        for version in ["2.2.2", "2.3.0"] {
+            let release = dir.path().join(version);
+            fs::create_dir(&release).unwrap();
+            fs::write(
+                release.join(CHECKSUMS_FILE),
+                CHECKSUMS.replace("2.3.0", version),
+            )
+            .unwrap();
+            fs::write(
+                release.join(format!(
+                    "zfsbootmenu-release-x86_64-v{}-linux6.1.EFI",
+                    version
+                )),
+                b"MZ",
+            )
+            .unwrap();
         }
+        fs::create_dir(dir.path().join("2.10.0")).unwrap();
 
-        fs::write(path, content)?;
-        Ok(())
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("sha256sum", format!("{}  image.EFI\n", DIGEST));
+        let installer =
+            ZbmInstaller::new("zroot".to_string(), PathBuf::from("/mnt/boot/efi"), false)
+                .with_executor(executor.clone())
+                .with_artifact_dir(Some(dir.path().to_path_buf()));
+
+        // Only releases with checksums count
+        assert_eq!(installer.resolve_version().unwrap(), "2.3.0");
+        let image = installer.download_zbm("2.3.0").unwrap();
+        assert_eq!(
+            image.path,
+            dir.path()
+                .join("2.3.0/zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI")
+        );
+        assert!(image.is_verified());
+        assert!(executor.commands().iter().all(|c| c.program != "curl"));
+
+        let err = installer.download_zbm("2.1.0").unwrap_err();
+        assert!(err.to_string().contains("No checksums"), "{err}");
+        fs::remove_file(image.path).unwrap();
+        let err = artifact_image(dir.path(), None).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_local_image_skips_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = dir.path().join("zfsbootmenu.EFI");
+        fs::write(&local, b"MZ").unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("sha256sum", format!("{}  {}\n", DIGEST, local.display()));
+        let installer = ZbmInstaller::new("zroot".to_string(), dir.path().join("efi"), false)
+            .with_executor(executor.clone())
+            .with_source(ZbmSource::CustomImage {
+                path: local.clone(),
+            });
+
+        let image = installer.image().unwrap();
+        assert_eq!(image.version, "local");
+        assert_eq!(image.path, local);
+        assert_eq!(image.sha256.as_deref(), Some(DIGEST));
+        assert!(executor.commands().iter().all(|c| c.program != "curl"));
+    }
+
+    /// Directory holding an executable `generate-zbm`, for `$PATH`
+    fn generate_zbm_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("generate-zbm");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_local_build_runs_generate_zbm() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let esp = PathBuf::from("/mnt/boot/efi");
+        let bin = generate_zbm_dir();
+        let image = ZbmInstaller::new("zroot".to_string(), esp.clone(), true)
+            .with_executor(executor.clone())
+            .with_source(ZbmSource::Local)
+            .with_search_path(bin.path())
+            .install()
+            .unwrap();
+
+        assert_eq!(image.version, "local");
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            argv[0],
+            "generate-zbm --config /etc/zfsbootmenu/config.yaml"
+        );
+        assert!(executor.commands().iter().all(|c| c.program != "curl"));
+        assert!(executor.file_ops().contains(&FileOp::copy(
+            esp.join("EFI/ZBM/vmlinuz.EFI"),
+            ZbmInstaller::image_path(&esp),
+        )));
+        let config = executor.written(Path::new(GENERATE_ZBM_CONFIG)).unwrap();
+        assert!(config.contains("ImageDir: /mnt/boot/efi/EFI/ZBM"));
+    }
+
+    #[test]
+    fn test_local_build_falls_back_to_the_release() {
+        // Not executable, so not the command either
+        let bin = tempfile::tempdir().unwrap();
+        fs::write(bin.path().join("generate-zbm"), "").unwrap();
+        let installer = |release_fallback| {
+            let executor = Arc::new(RecordingExecutor::new());
+            let result =
+                ZbmInstaller::new("zroot".to_string(), PathBuf::from("/mnt/boot/efi"), true)
+                    .with_executor(executor.clone())
+                    .with_source(ZbmSource::Local)
+                    .with_release_fallback(release_fallback)
+                    .with_search_path(bin.path())
+                    .install();
+            (result, executor)
+        };
+
+        let (result, _) = installer(false);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("generate-zbm not found"));
+
+        let (result, executor) = installer(true);
+        assert_eq!(result.unwrap().version, ZBM_VERSION);
+        assert!(executor
+            .commands()
+            .iter()
+            .all(|c| c.program != "generate-zbm"));
+    }
+
+    #[test]
+    fn test_backup_and_fallback_images() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let esp = tempfile::tempdir().unwrap();
+        let mirror = tempfile::tempdir().unwrap();
+        let bin = generate_zbm_dir();
+        let installer = ZbmInstaller::new("zroot".to_string(), esp.path().to_path_buf(), true)
+            .with_executor(executor.clone())
+            .with_source(ZbmSource::Local)
+            .with_search_path(bin.path());
+
+        installer.install().unwrap();
+        installer.install_mirror(mirror.path()).unwrap();
+        installer.install_fallback(esp.path()).unwrap();
+
+        let ops = executor.file_ops();
+        assert!(ops.contains(&FileOp::copy(
+            ZbmInstaller::image_path(esp.path()),
+            ZbmInstaller::backup_path(esp.path()),
+        )));
+        assert!(ops.contains(&FileOp::copy(
+            ZbmInstaller::backup_path(esp.path()),
+            ZbmInstaller::backup_path(mirror.path()),
+        )));
+        assert!(ops.contains(&FileOp::copy(
+            ZbmInstaller::image_path(esp.path()),
+            esp.path().join(FALLBACK_IMAGE),
+        )));
+
+        // Another system's loader is left alone
+        let fallback = mirror.path().join(FALLBACK_IMAGE);
+        fs::create_dir_all(fallback.parent().unwrap()).unwrap();
+        fs::write(&fallback, b"MZ other").unwrap();
+        let executor = Arc::new(RecordingExecutor::new());
+        installer
+            .with_executor(executor.clone())
+            .install_fallback(mirror.path())
+            .unwrap();
+        assert!(executor.file_ops().is_empty());
+    }
 
     #[test]
     fn test_zbm_installer_creation() {
@@ -270,4 +1029,49 @@ mod tests {
         assert_eq!(installer.pool_name, "zroot");
         assert!(installer.dry_run);
     }
+
+    #[test]
+    fn test_mirror_esp_operations() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let installer =
+            ZbmInstaller::new("zroot".to_string(), PathBuf::from("/mnt/boot/efi"), true)
+                .with_executor(executor.clone());
+
+        installer
+            .register_boot_entry(Path::new("/dev/sdb"), 2)
+            .unwrap();
+        installer
+            .sync_esp(&[PathBuf::from("/mnt/boot/efi2")])
+            .unwrap();
+
+        let plan: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            plan,
+            vec![
+                "efibootmgr --create --disk /dev/sdb --part 2 --label ZFSBootMenu (sdb) --loader \\EFI\\ZBM\\zfsbootmenu.EFI",
+                "rsync --recursive --times --modify-window=1 --delete /mnt/boot/efi/ /mnt/boot/efi2/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_boot_entry_failure_not_fatal() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program(
+            "efibootmgr",
+            5,
+            "EFI variables are not supported on this system.",
+        );
+        let installer =
+            ZbmInstaller::new("zroot".to_string(), PathBuf::from("/mnt/boot/efi"), false)
+                .with_executor(executor);
+
+        assert!(installer
+            .register_boot_entry(Path::new("/dev/sda"), 1)
+            .is_ok());
+    }
 }
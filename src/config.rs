@@ -3,13 +3,33 @@
 //! Defines the configuration state for the installer, including installation mode,
 //! device selection, RAID configuration, and all user-configurable options.
 
+use crate::bootloader::zbm::DEFAULT_KERNEL_CMDLINE;
+use crate::disk::partition_table::{BootLayout, MIN_ZFS_SIZE};
+use crate::disk::{SuitabilityPolicy, WipeMode, DEVICE_RETRIES, DEVICE_TIMEOUT};
 use crate::error::{InstallerError, Result};
+use crate::installer::hooks::{HookPoint, HookSpec};
+use crate::installer::Phase;
+use crate::system::Distro;
+use crate::zfs::dataset::{self, DEFAULT_INITIAL_SNAPSHOT};
+use crate::zfs::layout::{BOOT_ENVIRONMENTS, DEFAULT_BOOT_ENVIRONMENT};
+use crate::zfs::pool::{DEFAULT_CACHEFILE, GRUB_COMPATIBILITY};
+use crate::zfs::{DatasetLayout, MIN_ARC_MAX};
 use bytesize::ByteSize;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+/// Most disks prepared at once when `jobs` isn't set
+pub const DEFAULT_MAX_JOBS: usize = 4;
 
 /// Installation mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum InstallMode {
     /// Fresh installation on empty drives
     New,
@@ -28,6 +48,7 @@ impl std::fmt::Display for InstallMode {
 
 /// ZFS RAID level
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RaidLevel {
     /// No redundancy (single device or striped)
     None,
@@ -42,6 +63,15 @@ pub enum RaidLevel {
 }
 
 impl RaidLevel {
+    /// Every level, from least to most parity
+    pub const ALL: [RaidLevel; 5] = [
+        RaidLevel::None,
+        RaidLevel::Mirror,
+        RaidLevel::Raidz1,
+        RaidLevel::Raidz2,
+        RaidLevel::Raidz3,
+    ];
+
     /// Get minimum number of drives required for this RAID level
     pub fn min_drives(&self) -> usize {
         match self {
@@ -64,6 +94,34 @@ impl RaidLevel {
         }
     }
 
+    /// Drives of a vdev with `devices` drives that can fail without losing it
+    pub fn tolerated_failures(&self, devices: usize) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Mirror => devices.saturating_sub(1),
+            Self::Raidz1 => 1,
+            Self::Raidz2 => 2,
+            Self::Raidz3 => 3,
+        }
+    }
+
+    /// Bytes a vdev of devices this large can store, before ZFS's own overhead
+    ///
+    /// Every device counts as large as the smallest, except without
+    /// redundancy: a mirror holds one device's worth and raidzN all but N.
+    pub fn usable_capacity(&self, device_sizes: &[u64]) -> u64 {
+        let smallest = device_sizes.iter().copied().min().unwrap_or(0);
+        let devices = device_sizes.len() as u64;
+        match self {
+            Self::None => device_sizes.iter().sum(),
+            Self::Mirror => smallest,
+            Self::Raidz1 | Self::Raidz2 | Self::Raidz3 => {
+                let parity = self.tolerated_failures(device_sizes.len()) as u64;
+                devices.saturating_sub(parity) * smallest
+            }
+        }
+    }
+
     /// Get description of RAID level
     pub fn description(&self) -> &'static str {
         match self {
@@ -88,8 +146,255 @@ impl std::fmt::Display for RaidLevel {
     }
 }
 
+impl std::str::FromStr for RaidLevel {
+    type Err = InstallerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" | "stripe" => Ok(Self::None),
+            "mirror" => Ok(Self::Mirror),
+            "raidz1" | "raidz" => Ok(Self::Raidz1),
+            "raidz2" => Ok(Self::Raidz2),
+            "raidz3" => Ok(Self::Raidz3),
+            _ => Err(InstallerError::config(format!(
+                "Invalid RAID level '{}' (expected none, mirror, raidz1, raidz2 or raidz3)",
+                s
+            ))),
+        }
+    }
+}
+
+/// One top-level vdev of the pool (`[[vdevs]]` in a config file)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VdevSpec {
+    /// Redundancy within the vdev
+    pub raid: RaidLevel,
+    /// Devices making up the vdev
+    pub devices: Vec<PathBuf>,
+}
+
+impl VdevSpec {
+    /// Create a vdev
+    pub fn new(raid: RaidLevel, devices: Vec<PathBuf>) -> Self {
+        Self { raid, devices }
+    }
+}
+
+impl std::fmt::Display for VdevSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let devices: Vec<String> = self
+            .devices
+            .iter()
+            .map(|d| d.display().to_string())
+            .collect();
+        write!(f, "{}:{}", self.raid, devices.join(","))
+    }
+}
+
+/// Parses `TYPE:DEV1,DEV2,...` as given to `--vdev`, e.g. `mirror:/dev/sda,/dev/sdb`
+impl std::str::FromStr for VdevSpec {
+    type Err = InstallerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (raid, devices) = s.split_once(':').ok_or_else(|| {
+            InstallerError::config(format!(
+                "Invalid vdev '{}' (expected TYPE:DEV1,DEV2,..., e.g. mirror:/dev/sda,/dev/sdb)",
+                s
+            ))
+        })?;
+        let devices = devices
+            .split(',')
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        Ok(Self::new(raid.parse()?, devices))
+    }
+}
+
+/// Tool that installs a minimal system into a new pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BootstrapMethod {
+    /// Debian or Ubuntu, with `debootstrap`
+    Debootstrap,
+    /// Fedora, with `dnf --installroot`
+    Dnf,
+    /// Arch Linux, with `pacstrap`
+    Pacstrap,
+}
+
+impl BootstrapMethod {
+    /// The program that does the bootstrap
+    pub fn program(&self) -> &'static str {
+        match self {
+            Self::Debootstrap => "debootstrap",
+            Self::Dnf => "dnf",
+            Self::Pacstrap => "pacstrap",
+        }
+    }
+
+    /// os-release `ID` of the system it installs
+    pub fn distro_id(&self) -> &'static str {
+        match self {
+            Self::Debootstrap => "debian",
+            Self::Dnf => "fedora",
+            Self::Pacstrap => "arch",
+        }
+    }
+
+    /// Where packages come from unless a mirror is given
+    pub fn default_mirror(&self) -> &'static str {
+        match self {
+            Self::Debootstrap => "http://deb.debian.org/debian",
+            Self::Dnf => "https://mirrors.fedoraproject.org",
+            Self::Pacstrap => "https://geo.mirror.pkgbuild.com",
+        }
+    }
+}
+
+impl std::fmt::Display for BootstrapMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program())
+    }
+}
+
+/// A minimal system installed into a new pool, so new mode leaves
+/// something to boot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootstrapSpec {
+    /// Tool doing the install
+    pub method: BootstrapMethod,
+    /// Debian or Ubuntu suite (`bookworm`, `noble`) or Fedora release
+    /// (`40`); pacstrap always installs the current Arch Linux
+    #[serde(default)]
+    pub release: Option<String>,
+    /// Package mirror for debootstrap, e.g. `http://archive.ubuntu.com/ubuntu`
+    #[serde(default)]
+    pub mirror: Option<String>,
+    /// Packages installed on top of the minimal system, e.g. a kernel and ZFS
+    #[serde(default)]
+    pub extra_packages: Vec<String>,
+}
+
+impl BootstrapSpec {
+    /// Bootstrap `release` with `method`, from the default mirror
+    pub fn new(method: BootstrapMethod, release: Option<String>) -> Self {
+        Self {
+            method,
+            release,
+            mirror: None,
+            extra_packages: Vec::new(),
+        }
+    }
+
+    /// Check the release, mirror and package names
+    pub fn validate(&self) -> Result<()> {
+        match (&self.release, self.method) {
+            (None, BootstrapMethod::Debootstrap | BootstrapMethod::Dnf) => {
+                return Err(InstallerError::validation(format!(
+                    "Bootstrapping with {} needs a release",
+                    self.method
+                )));
+            }
+            (Some(release), _) if !is_package_name(release) => {
+                return Err(InstallerError::validation(format!(
+                    "'{}' is not a valid release",
+                    release
+                )));
+            }
+            _ => {}
+        }
+        if let Some(mirror) = &self.mirror {
+            if self.method != BootstrapMethod::Debootstrap {
+                return Err(InstallerError::validation(format!(
+                    "{} takes its mirrors from the host's configuration, not a mirror setting",
+                    self.method
+                )));
+            }
+            if mirror_address(mirror).is_none() && !mirror.starts_with("file:///") {
+                return Err(InstallerError::validation(format!(
+                    "'{}' is not an http, https or file mirror URL",
+                    mirror
+                )));
+            }
+        }
+        if let Some(package) = self
+            .extra_packages
+            .iter()
+            .find(|package| !is_package_name(package))
+        {
+            return Err(InstallerError::validation(format!(
+                "'{}' is not a valid package name",
+                package
+            )));
+        }
+        Ok(())
+    }
+
+    /// Host and port the bootstrap downloads from; `None` for a local mirror
+    pub fn mirror_address(&self) -> Option<(String, u16)> {
+        mirror_address(
+            self.mirror
+                .as_deref()
+                .unwrap_or(self.method.default_mirror()),
+        )
+    }
+}
+
+/// Parses `METHOD[:RELEASE]` as given to `--bootstrap`, e.g. `debootstrap:bookworm`
+impl std::str::FromStr for BootstrapSpec {
+    type Err = InstallerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (method, release) = match s.split_once(':') {
+            Some((method, release)) => (method, Some(release.to_string())),
+            None => (s, None),
+        };
+        let method = match method {
+            "debootstrap" => BootstrapMethod::Debootstrap,
+            "dnf" => BootstrapMethod::Dnf,
+            "pacstrap" => BootstrapMethod::Pacstrap,
+            _ => {
+                return Err(InstallerError::config(format!(
+                    "Unknown bootstrap method '{}' (expected debootstrap, dnf or pacstrap)",
+                    method
+                )))
+            }
+        };
+        Ok(Self::new(method, release))
+    }
+}
+
+/// Whether `name` is a plain package or release name, not an option
+fn is_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '+' | ':' | '@'))
+}
+
+/// Host and port of an http or https URL
+fn mirror_address(url: &str) -> Option<(String, u16)> {
+    let (rest, default_port) = if let Some(rest) = url.strip_prefix("https://") {
+        (rest, 443)
+    } else {
+        (url.strip_prefix("http://")?, 80)
+    };
+    let authority = rest.split('/').next()?;
+    let host_port = authority.rsplit('@').next()?;
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (host_port, default_port),
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
 /// ZFS compression algorithm
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum Compression {
     Off,
     Lz4,
@@ -99,6 +404,17 @@ pub enum Compression {
     Lzjb,
 }
 
+impl Compression {
+    /// Every algorithm, in menu order
+    pub const ALL: [Compression; 5] = [
+        Compression::Zstd,
+        Compression::Lz4,
+        Compression::Gzip,
+        Compression::Lzjb,
+        Compression::Off,
+    ];
+}
+
 impl std::fmt::Display for Compression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -111,161 +427,1872 @@ impl std::fmt::Display for Compression {
     }
 }
 
-/// Main installer configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    /// Installation mode
-    pub mode: InstallMode,
-
-    /// ZFS pool name
-    pub pool_name: String,
-
-    /// Devices to use for the pool
-    pub devices: Vec<PathBuf>,
-
-    /// RAID level
-    pub raid_level: RaidLevel,
-
-    /// EFI partition size
-    pub efi_size: ByteSize,
-
-    /// Swap partition size (0 to disable)
-    pub swap_size: ByteSize,
-
-    /// ZFS ashift value (None = auto-detect)
-    pub ashift: Option<u8>,
-
-    /// Compression algorithm
-    pub compression: Compression,
+/// Where swap lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SwapMode {
+    /// A swap partition on every disk
+    #[default]
+    Partition,
+    /// A single zvol on the pool (`<pool>/swap`), protected by the pool's redundancy
+    Zvol,
+    /// No swap
+    None,
+}
 
-    /// Hostname for new installation
-    pub hostname: Option<String>,
+impl std::fmt::Display for SwapMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Partition => write!(f, "partition"),
+            Self::Zvol => write!(f, "zvol"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
 
-    /// Dry run mode (don't actually make changes)
-    pub dry_run: bool,
+/// How the disks are partitioned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartitioningMode {
+    /// Wipe each disk and give it a new partition table
+    #[default]
+    WholeDisk,
+    /// Keep the partitions already on each disk and install into the
+    /// largest free region
+    UseFreeSpace {
+        /// Use the disk's existing EFI system partition instead of adding one
+        reuse_esp: bool,
+    },
+}
 
-    /// Force mode (skip confirmations)
-    pub force: bool,
+impl PartitioningMode {
+    /// Whether the disks' existing partitions are kept
+    pub fn keeps_partitions(&self) -> bool {
+        matches!(self, Self::UseFreeSpace { .. })
+    }
+}
 
-    /// Source root for existing mode
-    pub source_root: PathBuf,
+impl std::fmt::Display for PartitioningMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WholeDisk => write!(f, "whole disk"),
+            Self::UseFreeSpace { reuse_esp: false } => write!(f, "free space"),
+            Self::UseFreeSpace { reuse_esp: true } => write!(f, "free space, existing ESP"),
+        }
+    }
+}
 
-    /// Paths to exclude from existing system migration
-    pub exclude_paths: Vec<PathBuf>,
+/// What a device is used for in the pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceRole {
+    /// Partitioned for boot and holds the data vdev
+    #[default]
+    Data,
+    /// Separate intent log (SLOG)
+    Log,
+    /// Read cache (L2ARC)
+    Cache,
+    /// Hot spare
+    Spare,
+}
 
-    /// Copy home directories in existing mode
-    pub copy_home: bool,
+impl DeviceRole {
+    /// All roles, in the order the TUI cycles through them
+    pub const ALL: [DeviceRole; 4] = [Self::Data, Self::Log, Self::Cache, Self::Spare];
 
-    /// Skip pre-flight checks (not recommended)
-    pub skip_preflight: bool,
+    /// The role after this one, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&r| r == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            mode: InstallMode::New,
-            pool_name: "zroot".to_string(),
-            devices: Vec::new(),
-            raid_level: RaidLevel::None,
-            efi_size: ByteSize::gib(1),
-            swap_size: ByteSize::gib(8),
-            ashift: None,
-            compression: Compression::default(),
-            hostname: None,
-            dry_run: false,
-            force: false,
-            source_root: PathBuf::from("/"),
-            exclude_paths: Vec::new(),
-            copy_home: true,
-            skip_preflight: false,
+impl std::fmt::Display for DeviceRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Data => write!(f, "data"),
+            Self::Log => write!(f, "log"),
+            Self::Cache => write!(f, "cache"),
+            Self::Spare => write!(f, "spare"),
         }
     }
 }
 
-impl Config {
-    /// Create a new configuration with defaults
-    pub fn new() -> Self {
-        Self::default()
+/// Firmware boot paths to set up on the target disks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BootMode {
+    /// UEFI only
+    #[default]
+    Uefi,
+    /// UEFI plus a legacy BIOS boot path
+    Hybrid,
+}
+
+impl BootMode {
+    /// Whether a BIOS boot partition and legacy bootloader are installed
+    pub fn has_bios(&self) -> bool {
+        matches!(self, Self::Hybrid)
     }
 
-    /// Validate the configuration
-    pub fn validate(&self) -> Result<()> {
-        // Validate pool name
-        if self.pool_name.is_empty() {
-            return Err(InstallerError::validation("Pool name cannot be empty"));
-        }
-        if !self
-            .pool_name
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
-        {
-            return Err(InstallerError::validation(
-                "Pool name must contain only alphanumeric characters, underscores, hyphens, and dots",
-            ));
+    /// Get description of boot mode
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Uefi => "ZFSBootMenu on the ESP",
+            Self::Hybrid => "ZFSBootMenu on the ESP plus GRUB on a BIOS boot partition",
         }
+    }
+}
 
-        // Validate devices
-        if self.devices.is_empty() {
-            return Err(InstallerError::validation(
-                "At least one device must be selected",
-            ));
+impl std::fmt::Display for BootMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uefi => write!(f, "uefi"),
+            Self::Hybrid => write!(f, "hybrid"),
         }
+    }
+}
 
-        // Validate RAID level vs device count
-        let min_drives = self.raid_level.min_drives();
-        if self.devices.len() < min_drives {
-            return Err(InstallerError::validation(format!(
-                "RAID level {} requires at least {} device(s), but only {} provided",
-                self.raid_level,
-                min_drives,
-                self.devices.len()
-            )));
-        }
+/// Firmware the installed system boots with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FirmwareMode {
+    /// UEFI: ZFSBootMenu on the ESP, laid out by [`BootMode`]
+    #[default]
+    Uefi,
+    /// Legacy BIOS: syslinux starting the ZFSBootMenu components
+    Bios,
+    /// Whichever firmware the running system was booted with
+    Auto,
+}
 
-        // Validate ashift
-        if let Some(ashift) = self.ashift {
-            if !(9..=16).contains(&ashift) {
-                return Err(InstallerError::validation(format!(
-                    "ashift must be between 9 and 16, got {}",
-                    ashift
-                )));
-            }
+impl FirmwareMode {
+    /// `Auto` as [`Uefi`](Self::Uefi) or [`Bios`](Self::Bios), going by whether
+    /// the running system was booted with UEFI
+    pub fn resolve(self, uefi: bool) -> Self {
+        match self {
+            Self::Auto if uefi => Self::Uefi,
+            Self::Auto => Self::Bios,
+            mode => mode,
         }
+    }
 
-        // Validate EFI size
-        if self.efi_size < ByteSize::mib(100) {
-            return Err(InstallerError::validation(
-                "EFI partition must be at least 100MB",
-            ));
+    /// Get description of firmware mode
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Uefi => "UEFI",
+            Self::Bios => "legacy BIOS, booting through syslinux",
+            Self::Auto => "as the running system was booted",
         }
+    }
+}
 
-        // Validate source root for existing mode
-        if self.mode == InstallMode::Existing && !self.source_root.exists() {
-            return Err(InstallerError::validation(format!(
-                "Source root does not exist: {}",
-                self.source_root.display()
-            )));
+impl std::fmt::Display for FirmwareMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uefi => write!(f, "uefi"),
+            Self::Bios => write!(f, "bios"),
+            Self::Auto => write!(f, "auto"),
         }
-
-        Ok(())
     }
+}
 
-    /// Get the total number of partitions that will be created per device
-    pub fn partitions_per_device(&self) -> usize {
-        let mut count = 2; // EFI + ZFS
-        if self.swap_size > ByteSize(0) {
-            count += 1;
+/// What the firmware boot entries start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Bootloader {
+    /// systemd-boot, with a loader entry for ZFSBootMenu
+    #[default]
+    SystemdBoot,
+    /// GRUB for EFI, chain-loading ZFSBootMenu (e.g. behind a Secure Boot shim)
+    Grub,
+    /// No bootloader: the firmware starts the ZFSBootMenu image directly
+    EfiStub,
+}
+
+impl Bootloader {
+    /// Get description of bootloader
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::SystemdBoot => "systemd-boot menu with a ZFSBootMenu entry",
+            Self::Grub => "GRUB chain-loading ZFSBootMenu",
+            Self::EfiStub => "ZFSBootMenu started directly by the firmware",
         }
-        count
     }
+}
 
-    /// Calculate estimated total size needed per device
-    pub fn min_device_size(&self) -> ByteSize {
-        self.efi_size + self.swap_size + ByteSize::gib(10) // 10GB minimum for ZFS
+impl std::fmt::Display for Bootloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SystemdBoot => write!(f, "systemd-boot"),
+            Self::Grub => write!(f, "grub"),
+            Self::EfiStub => write!(f, "efi-stub"),
+        }
     }
 }
 
-#[cfg(test)]
+/// Where the ZFSBootMenu EFI image comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ZbmSource {
+    /// A release downloaded from GitHub, verified against its checksums
+    Release {
+        /// Release to install (None = the latest)
+        version: Option<String>,
+    },
+    /// Built by the host's `generate-zbm`, matching its ZFS version
+    Local,
+    /// A local EFI image
+    CustomImage {
+        /// Where the image is
+        path: PathBuf,
+    },
+}
+
+impl Default for ZbmSource {
+    fn default() -> Self {
+        Self::Release { version: None }
+    }
+}
+
+impl std::fmt::Display for ZbmSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Release { version: None } => write!(f, "latest release"),
+            Self::Release {
+                version: Some(version),
+            } => write!(f, "release {}", version),
+            Self::Local => write!(f, "local generate-zbm build"),
+            Self::CustomImage { path } => write!(f, "image {}", path.display()),
+        }
+    }
+}
+
+/// How the ZFSBootMenu image is made bootable with Secure Boot enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecureBootMode {
+    /// Nothing is signed; Secure Boot has to be disabled
+    #[default]
+    Off,
+    /// The distribution's shim starts the image, signed with a key enrolled
+    /// as a Machine Owner Key
+    Shim,
+    /// The image is signed with a key already in the firmware's db
+    SignLocal,
+}
+
+impl SecureBootMode {
+    /// Whether the installed EFI executables are signed
+    pub fn signs(&self) -> bool {
+        !matches!(self, Self::Off)
+    }
+
+    /// Get description of Secure Boot mode
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Off => "unsigned, Secure Boot disabled",
+            Self::Shim => "signed, started through shim with an enrolled MOK",
+            Self::SignLocal => "signed with a key the firmware trusts",
+        }
+    }
+}
+
+impl std::fmt::Display for SecureBootMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Shim => write!(f, "shim"),
+            Self::SignLocal => write!(f, "sign-local"),
+        }
+    }
+}
+
+/// Rough strength of a passphrase, weakest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PassphraseStrength {
+    /// Shorter than ZFS accepts
+    TooShort,
+    /// Under 50 bits
+    Weak,
+    /// Under 70 bits
+    Fair,
+    /// 70 bits or more
+    Strong,
+}
+
+impl std::fmt::Display for PassphraseStrength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "too short"),
+            Self::Weak => write!(f, "weak"),
+            Self::Fair => write!(f, "fair"),
+            Self::Strong => write!(f, "strong"),
+        }
+    }
+}
+
+/// Secret key material (a passphrase)
+///
+/// Never serialized, and redacted from `Debug` output so it can't reach logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    /// Minimum passphrase length accepted by ZFS
+    pub const MIN_LEN: usize = 8;
+
+    /// Wrap a passphrase
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Self(passphrase.into())
+    }
+
+    /// Build a passphrase from two entries, rejecting mismatches and short input
+    pub fn confirmed(first: &str, second: &str) -> Result<Self> {
+        if first != second {
+            return Err(InstallerError::validation("Passphrases do not match"));
+        }
+
+        let passphrase = Self::new(first);
+        passphrase.check()?;
+        Ok(passphrase)
+    }
+
+    /// Reject passphrases ZFS won't accept
+    pub fn check(&self) -> Result<()> {
+        if self.0.is_empty() {
+            return Err(InstallerError::validation("Passphrase cannot be empty"));
+        }
+        if self.0.chars().count() < Self::MIN_LEN {
+            return Err(InstallerError::validation(format!(
+                "Passphrase must be at least {} characters",
+                Self::MIN_LEN
+            )));
+        }
+        Ok(())
+    }
+
+    /// The secret itself; only for handing to ZFS
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Estimate how hard `passphrase` is to guess
+    ///
+    /// Its length times the bits per character of the classes it draws
+    /// from (lowercase, uppercase, digits, anything else). Only a hint:
+    /// dictionary words count as random characters.
+    pub fn strength(passphrase: &str) -> PassphraseStrength {
+        let len = passphrase.chars().count();
+        if len < Self::MIN_LEN {
+            return PassphraseStrength::TooShort;
+        }
+
+        let uses = |class: fn(&char) -> bool| passphrase.chars().any(|c| class(&c));
+        let alphabet: u32 = [
+            (uses(char::is_ascii_lowercase), 26),
+            (uses(char::is_ascii_uppercase), 26),
+            (uses(char::is_ascii_digit), 10),
+            (uses(|c| !c.is_ascii_alphanumeric()), 33),
+        ]
+        .iter()
+        .filter(|(used, _)| *used)
+        .map(|(_, size)| size)
+        .sum();
+        let bits = len as f64 * f64::from(alphabet).log2();
+
+        match bits {
+            b if b < 50.0 => PassphraseStrength::Weak,
+            b if b < 70.0 => PassphraseStrength::Fair,
+            _ => PassphraseStrength::Strong,
+        }
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Passphrase(<redacted>)")
+    }
+}
+
+/// A crypt(3) password hash as stored in `/etc/shadow`, e.g. `$6$salt$...`
+///
+/// Redacted from `Debug` output; it only ever reaches `chpasswd` on stdin.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PasswordHash(String);
+
+impl PasswordHash {
+    /// Wrap a hash, rejecting anything that isn't one
+    pub fn new(hash: impl Into<String>) -> Result<Self> {
+        let hash = Self(hash.into());
+        hash.check()?;
+        Ok(hash)
+    }
+
+    /// Reject values `chpasswd -e` would misread: a hash is `$id$...` with
+    /// no colons or whitespace
+    pub fn check(&self) -> Result<()> {
+        let valid = self.0.starts_with('$')
+            && self.0.split('$').filter(|part| !part.is_empty()).count() >= 2
+            && self.0.chars().all(|c| c.is_ascii_graphic() && c != ':');
+        if !valid {
+            return Err(InstallerError::validation(
+                "Password hashes must be in crypt(3) form, e.g. from `openssl passwd -6`",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The hash itself; only for handing to `chpasswd`
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for PasswordHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PasswordHash(<redacted>)")
+    }
+}
+
+/// A login account created in the installed system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserSpec {
+    /// Login name
+    pub name: String,
+    /// Supplementary groups
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Login shell (None = the distribution's default)
+    #[serde(default)]
+    pub shell: Option<PathBuf>,
+    /// Add to the administrators' group: `sudo` on Debian-likes, else `wheel`
+    #[serde(default)]
+    pub admin: bool,
+    /// Password (None = asked for at install time)
+    #[serde(default)]
+    pub password_hash: Option<PasswordHash>,
+}
+
+impl UserSpec {
+    /// An account with no extra groups and the default shell
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            groups: Vec::new(),
+            shell: None,
+            admin: false,
+            password_hash: None,
+        }
+    }
+
+    /// Check the names, shell and password hash
+    pub fn validate(&self) -> Result<()> {
+        Config::validate_account_name("user", &self.name)?;
+        if self.name == "root" {
+            return Err(InstallerError::validation(
+                "root already exists; set its password with root_password_hash",
+            ));
+        }
+        for group in &self.groups {
+            Config::validate_account_name("group", group)?;
+        }
+        if let Some(shell) = &self.shell {
+            if !shell.is_absolute() {
+                return Err(InstallerError::validation(format!(
+                    "The shell of {} must be an absolute path, not {}",
+                    self.name,
+                    shell.display()
+                )));
+            }
+        }
+        if let Some(hash) = &self.password_hash {
+            hash.check()?;
+        }
+        Ok(())
+    }
+}
+
+/// ZFS `keyformat`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyFormat {
+    /// Passphrase of at least 8 characters
+    #[default]
+    Passphrase,
+    /// 64 hexadecimal characters
+    Hex,
+    /// 32 raw bytes
+    Raw,
+}
+
+impl std::fmt::Display for KeyFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Passphrase => write!(f, "passphrase"),
+            Self::Hex => write!(f, "hex"),
+            Self::Raw => write!(f, "raw"),
+        }
+    }
+}
+
+/// Native ZFS encryption settings for the pool's root dataset
+///
+/// Every dataset in the pool inherits them, so the whole pool is one
+/// encryption root that ZFSBootMenu unlocks at boot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncryptionConfig {
+    /// Cipher (`encryption` property)
+    pub algorithm: String,
+    /// Key format
+    pub keyformat: KeyFormat,
+    /// Key location, e.g. `file:///etc/zfs/zroot.key` (`None` = prompt)
+    pub keylocation: Option<String>,
+    /// Passphrase for prompt-based keys, supplied at install time
+    #[serde(skip)]
+    pub passphrase: Option<Passphrase>,
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: "aes-256-gcm".to_string(),
+            keyformat: KeyFormat::default(),
+            keylocation: None,
+            passphrase: None,
+        }
+    }
+}
+
+impl EncryptionConfig {
+    /// The `keylocation` property value
+    pub fn keylocation(&self) -> &str {
+        self.keylocation.as_deref().unwrap_or("prompt")
+    }
+
+    /// Whether the key is typed in rather than read from a file
+    pub fn uses_prompt(&self) -> bool {
+        self.keylocation() == "prompt"
+    }
+
+    /// Filesystem properties enabling encryption on the pool root
+    pub fn properties(&self) -> Vec<(String, String)> {
+        vec![
+            ("encryption".to_string(), self.algorithm.clone()),
+            ("keyformat".to_string(), self.keyformat.to_string()),
+            ("keylocation".to_string(), self.keylocation().to_string()),
+        ]
+    }
+
+    /// Validate the encryption settings
+    pub fn validate(&self) -> Result<()> {
+        if self.uses_prompt() {
+            if self.keyformat != KeyFormat::Passphrase {
+                return Err(InstallerError::validation(format!(
+                    "keyformat {} needs a key file (keylocation = \"file:///...\")",
+                    self.keyformat
+                )));
+            }
+            return match &self.passphrase {
+                Some(passphrase) => passphrase.check(),
+                None => Err(InstallerError::validation(
+                    "Encryption requires a passphrase",
+                )),
+            };
+        }
+
+        let keylocation = self.keylocation();
+        let Some(path) = keylocation.strip_prefix("file://") else {
+            return Err(InstallerError::validation(format!(
+                "keylocation must be \"prompt\" or a file:// URI, got {}",
+                keylocation
+            )));
+        };
+        if !std::path::Path::new(path).is_file() {
+            return Err(InstallerError::validation(format!(
+                "Key file does not exist: {}",
+                path
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Main installer configuration
+///
+/// Serialized as TOML by [`Config::from_file`] and [`Config::to_file`]; keys
+/// missing from a file keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Installation mode
+    pub mode: InstallMode,
+
+    /// ZFS pool name
+    pub pool_name: String,
+
+    /// Install onto `pool_name`, which already exists, instead of creating it
+    pub use_existing_pool: bool,
+
+    /// Keep layout datasets that already exist in an existing pool
+    pub adopt_datasets: bool,
+
+    /// Devices to use for the pool (with an existing pool, the disks holding its ESPs)
+    pub devices: Vec<PathBuf>,
+
+    /// RAID level
+    pub raid_level: RaidLevel,
+
+    /// Top-level vdevs, striped together; replaces `devices` and `raid_level`
+    pub vdevs: Vec<VdevSpec>,
+
+    /// Separate intent log devices, used whole
+    pub log_devices: Vec<PathBuf>,
+
+    /// Mirror the log devices instead of striping them
+    pub mirror_log: bool,
+
+    /// L2ARC cache devices, used whole
+    pub cache_devices: Vec<PathBuf>,
+
+    /// Hot spare devices, used whole
+    pub spare_devices: Vec<PathBuf>,
+
+    /// EFI partition size
+    #[serde(serialize_with = "serialize_size")]
+    pub efi_size: ByteSize,
+
+    /// Where swap is created
+    pub swap_mode: SwapMode,
+
+    /// Swap size, per disk for partitions (0 to disable)
+    #[serde(serialize_with = "serialize_size")]
+    pub swap_size: ByteSize,
+
+    /// Encrypt the swap partitions with a new random key at every boot,
+    /// through `/etc/crypttab` (rules out hibernation)
+    pub encrypt_swap: bool,
+
+    /// Largest the ARC may grow in the installed system, written to
+    /// `/etc/modprobe.d/zfs.conf` (None = the ZFS default, half the RAM)
+    #[serde(
+        serialize_with = "serialize_optional_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub arc_max: Option<ByteSize>,
+
+    /// ZFS ashift value (None = auto-detect)
+    pub ashift: Option<u8>,
+
+    /// Compression algorithm
+    pub compression: Compression,
+
+    /// Firmware the installed system boots with
+    pub firmware: FirmwareMode,
+
+    /// Firmware boot paths to install (UEFI only)
+    pub boot_mode: BootMode,
+
+    /// Bootloader the UEFI boot entries start
+    pub bootloader: Bootloader,
+
+    /// Where the ZFSBootMenu image comes from
+    pub zbm_source: ZbmSource,
+
+    /// Install the latest release when a local build finds no `generate-zbm`
+    pub zbm_release_fallback: bool,
+
+    /// Install without network access: releases come from `artifact_dir`
+    pub offline: bool,
+
+    /// Directory filled by `fetch-artifacts` that ZFSBootMenu releases are
+    /// taken from instead of downloading them
+    pub artifact_dir: Option<PathBuf>,
+
+    /// Local package repository the package manager installs from
+    pub pkg_dir: Option<PathBuf>,
+
+    /// Also install ZFSBootMenu as the removable-media fallback loader
+    /// `EFI/BOOT/BOOTX64.EFI`, unless another system's loader is there
+    pub install_fallback: bool,
+
+    /// Finish the installation when the bootloader fails to install,
+    /// reporting the failure in the summary instead of rolling back
+    pub ignore_bootloader_errors: bool,
+
+    /// How the EFI executables are made bootable under Secure Boot
+    pub secure_boot: SecureBootMode,
+
+    /// Private key signing the EFI executables (PEM, for `sbsign`)
+    pub secure_boot_key: Option<PathBuf>,
+
+    /// Certificate of the signing key (PEM); shim mode enrolls it as a MOK
+    pub secure_boot_cert: Option<PathBuf>,
+
+    /// Mount a tmpfs on /tmp in the installed system
+    pub tmpfs_tmp: bool,
+
+    /// Create the pool from stable /dev/disk/by-id paths instead of kernel names
+    pub use_by_id: bool,
+
+    /// Native encryption (None = unencrypted pool)
+    pub encryption: Option<EncryptionConfig>,
+
+    /// Pool cachefile copied into the target (None = don't cache, `"none"` in a file)
+    #[serde(
+        serialize_with = "serialize_cachefile",
+        deserialize_with = "deserialize_cachefile"
+    )]
+    pub cachefile: Option<PathBuf>,
+
+    /// Trim freed blocks automatically (None = only when every device is an SSD)
+    pub autotrim: Option<bool>,
+
+    /// Feature sets the pool is limited to, e.g. `grub2` (None = grub2 with GRUB, else all)
+    pub pool_compatibility: Option<String>,
+
+    /// Extra pool properties (`zpool create -o`), overriding the built-in ones
+    pub pool_options: BTreeMap<String, String>,
+
+    /// Extra properties of the pool root (`zpool create -O`), overriding the built-in ones
+    pub filesystem_properties: BTreeMap<String, String>,
+
+    /// Hostname for new installation
+    pub hostname: Option<String>,
+
+    /// Timezone of the installed system, under `/usr/share/zoneinfo`
+    pub timezone: Option<String>,
+
+    /// Locale of the installed system, e.g. `en_US.UTF-8`
+    pub locale: Option<String>,
+
+    /// Console keymap of the installed system, e.g. `us`
+    pub keymap: Option<String>,
+
+    /// Accounts created in the installed system
+    pub users: Vec<UserSpec>,
+
+    /// New root password (None = keep the migrated system's)
+    pub root_password_hash: Option<PasswordHash>,
+
+    /// Lock the root account; an admin user has to be created instead
+    pub lock_root: bool,
+
+    /// Name of the boot environment under `ROOT`
+    pub boot_environment: String,
+
+    /// Kernel command line ZFSBootMenu boots the boot environment with
+    pub kernel_cmdline: String,
+
+    /// Name of the snapshot taken after install, with strftime placeholders
+    /// such as `install-%Y%m%d` (None = no snapshot, `"none"` in a file)
+    #[serde(
+        serialize_with = "serialize_initial_snapshot",
+        deserialize_with = "deserialize_initial_snapshot"
+    )]
+    pub initial_snapshot: Option<String>,
+
+    /// Snapshot the whole pool at once instead of only the boot environment
+    pub recursive_snapshot: bool,
+
+    /// Dry run mode (don't actually make changes)
+    pub dry_run: bool,
+
+    /// Force mode (skip confirmations)
+    pub force: bool,
+
+    /// Accept removable devices (USB sticks, SD cards) as install targets
+    pub allow_removable: bool,
+
+    /// Never prompt: fail where a confirmation or passphrase would be asked for
+    pub unattended: bool,
+
+    /// Source root for existing mode
+    pub source_root: PathBuf,
+
+    /// Paths to exclude from existing system migration
+    pub exclude_paths: Vec<PathBuf>,
+
+    /// Copy home directories in existing mode
+    pub copy_home: bool,
+
+    /// Keep the source's network interface names in existing mode, with
+    /// link files matching their MAC addresses
+    pub pin_interface_names: bool,
+
+    /// Install a minimal system into the new pool (new mode only)
+    pub bootstrap: Option<BootstrapSpec>,
+
+    /// Skip pre-flight checks (not recommended)
+    pub skip_preflight: bool,
+
+    /// Whether the disks are wiped or installed to alongside what's on them
+    pub partitioning: PartitioningMode,
+
+    /// How thoroughly the disks are wiped before they are partitioned
+    pub wipe_mode: WipeMode,
+
+    /// Seconds to wait for the device nodes of new partitions to appear
+    pub device_timeout: u64,
+
+    /// Times a disk command failing because the device is busy is retried
+    pub device_retries: u32,
+
+    /// Disks wiped, partitioned and formatted at once (None = one per disk,
+    /// at most [`DEFAULT_MAX_JOBS`])
+    pub jobs: Option<usize>,
+
+    /// Stop md arrays, LVM volume groups and LUKS mappings on the disks
+    /// before wiping them (set once the user has confirmed it)
+    pub release_holders: bool,
+
+    /// Undo the changes made so far if the installation fails
+    pub rollback: bool,
+
+    /// Also wipe the disks' new partition tables when rolling back
+    pub wipe_on_rollback: bool,
+
+    /// Re-import the pool under the target mountpoint after exporting it,
+    /// so the new system can be chrooted into
+    pub keep_mounted: bool,
+
+    /// Datasets to create under the pool root (`[[datasets]]` in a config file)
+    pub datasets: DatasetLayout,
+
+    /// Hook executables to run before/after each phase
+    #[serde(deserialize_with = "crate::installer::hooks::deserialize_hooks")]
+    pub hooks: HashMap<HookPoint, Vec<HookSpec>>,
+
+    /// `pre_install` hooks given outside `[hooks]`, moved there by
+    /// [`Config::from_toml`]
+    #[serde(skip_serializing)]
+    pub pre_install: Vec<HookSpec>,
+
+    /// `post_pool` hooks given outside `[hooks]`, moved there by
+    /// [`Config::from_toml`]
+    #[serde(skip_serializing)]
+    pub post_pool: Vec<HookSpec>,
+
+    /// `post_install` hooks given outside `[hooks]`, moved there by
+    /// [`Config::from_toml`]
+    #[serde(skip_serializing)]
+    pub post_install: Vec<HookSpec>,
+
+    /// Actually execute hooks in dry-run mode instead of only logging them
+    pub run_hooks_in_dry_run: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: InstallMode::New,
+            pool_name: "zroot".to_string(),
+            use_existing_pool: false,
+            adopt_datasets: false,
+            devices: Vec::new(),
+            raid_level: RaidLevel::None,
+            vdevs: Vec::new(),
+            log_devices: Vec::new(),
+            mirror_log: false,
+            cache_devices: Vec::new(),
+            spare_devices: Vec::new(),
+            efi_size: ByteSize::gib(1),
+            swap_mode: SwapMode::default(),
+            swap_size: ByteSize::gib(8),
+            encrypt_swap: false,
+            arc_max: None,
+            ashift: None,
+            compression: Compression::default(),
+            firmware: FirmwareMode::default(),
+            boot_mode: BootMode::default(),
+            bootloader: Bootloader::default(),
+            zbm_source: ZbmSource::default(),
+            zbm_release_fallback: false,
+            offline: false,
+            artifact_dir: None,
+            pkg_dir: None,
+            install_fallback: false,
+            ignore_bootloader_errors: false,
+            secure_boot: SecureBootMode::default(),
+            secure_boot_key: None,
+            secure_boot_cert: None,
+            tmpfs_tmp: false,
+            use_by_id: true,
+            encryption: None,
+            cachefile: Some(PathBuf::from(DEFAULT_CACHEFILE)),
+            autotrim: None,
+            pool_compatibility: None,
+            pool_options: BTreeMap::new(),
+            filesystem_properties: BTreeMap::new(),
+            hostname: None,
+            timezone: None,
+            locale: None,
+            keymap: None,
+            users: Vec::new(),
+            root_password_hash: None,
+            lock_root: false,
+            boot_environment: DEFAULT_BOOT_ENVIRONMENT.to_string(),
+            kernel_cmdline: DEFAULT_KERNEL_CMDLINE.to_string(),
+            initial_snapshot: Some(DEFAULT_INITIAL_SNAPSHOT.to_string()),
+            recursive_snapshot: false,
+            dry_run: false,
+            force: false,
+            allow_removable: false,
+            unattended: false,
+            source_root: PathBuf::from("/"),
+            exclude_paths: Vec::new(),
+            copy_home: true,
+            pin_interface_names: false,
+            bootstrap: None,
+            skip_preflight: false,
+            partitioning: PartitioningMode::default(),
+            wipe_mode: WipeMode::default(),
+            device_timeout: DEVICE_TIMEOUT.as_secs(),
+            device_retries: DEVICE_RETRIES,
+            jobs: None,
+            release_holders: false,
+            rollback: true,
+            wipe_on_rollback: false,
+            keep_mounted: false,
+            datasets: DatasetLayout::default(),
+            hooks: HashMap::new(),
+            pre_install: Vec::new(),
+            post_pool: Vec::new(),
+            post_install: Vec::new(),
+            run_hooks_in_dry_run: false,
+        }
+    }
+}
+
+impl Config {
+    /// Create a new configuration with defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a configuration from a TOML file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| {
+            InstallerError::config(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        Self::from_toml(&content).map_err(|e| match e {
+            InstallerError::ConfigError(msg) => {
+                InstallerError::config(format!("{}: {}", path.display(), msg))
+            }
+            other => other,
+        })
+    }
+
+    /// Parse a configuration from TOML
+    ///
+    /// Top-level `pre_install`, `post_pool` and `post_install` lists are
+    /// added to the hooks of those points in `[hooks]`.
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let mut config: Self = toml::from_str(content)
+            .map_err(|e| InstallerError::config(describe_toml_error(content, &e)))?;
+        for (point, specs) in [
+            (
+                HookPoint::Pre(Phase::Validate),
+                mem::take(&mut config.pre_install),
+            ),
+            (
+                HookPoint::Post(Phase::CreatePool),
+                mem::take(&mut config.post_pool),
+            ),
+            (
+                HookPoint::Post(Phase::Finalize),
+                mem::take(&mut config.post_install),
+            ),
+        ] {
+            if !specs.is_empty() {
+                config.hooks.entry(point).or_default().extend(specs);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Write the configuration to a TOML file
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = self.to_toml()?;
+        fs::write(path, content).map_err(|e| {
+            InstallerError::config(format!("Failed to write {}: {}", path.display(), e))
+        })
+    }
+
+    /// Serialize the configuration as TOML
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| {
+            InstallerError::config(format!("Failed to serialize configuration: {}", e))
+        })
+    }
+
+    /// Check a pool name is usable
+    pub fn validate_pool_name(name: &str) -> Result<()> {
+        if name.is_empty() {
+            return Err(InstallerError::validation("Pool name cannot be empty"));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return Err(InstallerError::validation(
+                "Pool name must contain only alphanumeric characters, underscores, hyphens, and dots",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a property name follows the ZFS rules
+    ///
+    /// User properties (`module:property`) are lowercase letters, digits and
+    /// `:+._-`, and need the colon; native properties are lowercase letters,
+    /// digits and `_`, with `@` for pool features.
+    pub fn validate_property_name(name: &str) -> Result<()> {
+        let valid = if name.contains(':') {
+            name.len() <= 256
+                && !name.starts_with(':')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || ":+._-".contains(c))
+        } else {
+            name.starts_with(|c: char| c.is_ascii_lowercase())
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_@".contains(c))
+        };
+        if !valid {
+            return Err(InstallerError::validation(format!(
+                "'{}' is not a valid ZFS property name (user properties look like module:property)",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check extra properties for names the installer manages itself
+    ///
+    /// `managed` pairs each such name with where it's set instead.
+    fn validate_extra_properties(
+        section: &str,
+        properties: &BTreeMap<String, String>,
+        managed: &[(&str, &str)],
+    ) -> Result<()> {
+        for (name, value) in properties {
+            Self::validate_property_name(name)?;
+            if let Some((_, instead)) = managed.iter().find(|(key, _)| key == name) {
+                return Err(InstallerError::validation(format!(
+                    "{}.{} is managed by the installer: {}",
+                    section, name, instead
+                )));
+            }
+            if value.is_empty() {
+                return Err(InstallerError::validation(format!(
+                    "{}.{} has an empty value",
+                    section, name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a kernel command line fits on one line
+    pub fn validate_kernel_cmdline(cmdline: &str) -> Result<()> {
+        if cmdline.chars().any(char::is_control) {
+            return Err(InstallerError::validation(
+                "The kernel command line must not contain control characters",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Expand the strftime placeholders in a snapshot name template
+    pub fn expand_snapshot_name(template: &str, now: NaiveDateTime) -> Result<String> {
+        let invalid = || {
+            InstallerError::validation(format!(
+                "Invalid placeholder in snapshot name '{}'",
+                template
+            ))
+        };
+        let items: Vec<Item> = StrftimeItems::new(template).collect();
+        if items.contains(&Item::Error) {
+            return Err(invalid());
+        }
+
+        let mut name = String::new();
+        write!(name, "{}", now.format_with_items(items.into_iter())).map_err(|_| invalid())?;
+        dataset::validate_snapshot_name(&name)?;
+        Ok(name)
+    }
+
+    /// Name of the snapshot to take after install, if any
+    pub fn initial_snapshot_name(&self, now: NaiveDateTime) -> Result<Option<String>> {
+        self.initial_snapshot
+            .as_deref()
+            .map(|template| Self::expand_snapshot_name(template, now))
+            .transpose()
+    }
+
+    /// Check a hostname is a valid DNS name: dot-separated labels of
+    /// letters, digits and hyphens, at most 63 characters each
+    pub fn validate_hostname(hostname: &str) -> Result<()> {
+        let valid = hostname.len() <= 253
+            && hostname.split('.').all(|label| {
+                (1..=63).contains(&label.len())
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+        if !valid {
+            return Err(InstallerError::validation(format!(
+                "'{}' is not a valid hostname",
+                hostname
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check a timezone is a name under `/usr/share/zoneinfo`, such as
+    /// `Europe/Berlin`; whether it exists is a pre-flight check
+    pub fn validate_timezone(timezone: &str) -> Result<()> {
+        let valid = !timezone.is_empty()
+            && timezone
+                .split('/')
+                .all(|part| !part.is_empty() && part != "." && part != "..")
+            && timezone
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-' | '+'));
+        if !valid {
+            return Err(InstallerError::validation(format!(
+                "'{}' is not a valid timezone name",
+                timezone
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check a locale or keymap name has no characters that would break
+    /// the files it is written to
+    pub fn validate_locale_name(kind: &str, name: &str) -> Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '@'))
+        {
+            return Err(InstallerError::validation(format!(
+                "'{}' is not a valid {} name",
+                name, kind
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the hostname, timezone, locale or keymap is set
+    pub fn sets_identity(&self) -> bool {
+        self.hostname.is_some()
+            || self.timezone.is_some()
+            || self.locale.is_some()
+            || self.keymap.is_some()
+    }
+
+    /// Check a user or group name is one `useradd` accepts everywhere:
+    /// lowercase letters, digits, `_` and `-`, not starting with a digit or
+    /// `-`, at most 32 characters
+    pub fn validate_account_name(kind: &str, name: &str) -> Result<()> {
+        let valid = (1..=32).contains(&name.len())
+            && name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-'));
+        if !valid {
+            return Err(InstallerError::validation(format!(
+                "'{}' is not a valid {} name",
+                name, kind
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check an offline install has somewhere to take ZFSBootMenu from and
+    /// downloads nothing
+    fn validate_offline(&self) -> Result<()> {
+        if !self.offline {
+            return Ok(());
+        }
+        let downloads_zbm = match self.zbm_source {
+            ZbmSource::Release { .. } => true,
+            ZbmSource::Local => self.zbm_release_fallback,
+            ZbmSource::CustomImage { .. } => false,
+        };
+        if downloads_zbm && self.artifact_dir.is_none() {
+            return Err(InstallerError::validation(
+                "Offline installs need an artifact directory with the ZFSBootMenu release, \
+                 or a custom image",
+            ));
+        }
+        if let Some(spec) = &self.bootstrap {
+            if spec.mirror_address().is_some() {
+                return Err(InstallerError::validation(
+                    "Offline installs can only bootstrap from a file:/// mirror",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the pool ends up with an operating system: a migrated or a
+    /// bootstrapped one
+    pub fn installs_system(&self) -> bool {
+        self.mode == InstallMode::Existing || self.bootstrap.is_some()
+    }
+
+    /// Whether users are created or the root account is changed
+    pub fn sets_accounts(&self) -> bool {
+        !self.users.is_empty() || self.root_password_hash.is_some() || self.lock_root
+    }
+
+    /// Check a boot environment name is a valid dataset name component
+    pub fn validate_boot_environment(name: &str) -> Result<()> {
+        if name.is_empty() {
+            return Err(InstallerError::validation(
+                "Boot environment name cannot be empty",
+            ));
+        }
+        if name == "." || name == ".." {
+            return Err(InstallerError::validation(format!(
+                "'{}' is not a valid boot environment name",
+                name
+            )));
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+        {
+            return Err(InstallerError::validation(format!(
+                "Boot environment name '{}' must contain only alphanumeric characters, underscores, hyphens, dots, and colons",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Boot environment name to use instead of the default, if there is one
+    ///
+    /// Only offered when a hostname is set and the name is still the
+    /// default: `<distro>-<date>`, e.g. `ubuntu-2024-05-01`.
+    pub fn suggested_boot_environment(&self, distro: Distro, date: NaiveDate) -> Option<String> {
+        if self.hostname.is_none()
+            || self.boot_environment != DEFAULT_BOOT_ENVIRONMENT
+            || !distro.is_supported()
+        {
+            return None;
+        }
+        Some(format!("{}-{}", distro.id(), date.format("%Y-%m-%d")))
+    }
+
+    /// The dataset layout with the boot environment named as configured
+    pub fn dataset_layout(&self) -> DatasetLayout {
+        self.datasets
+            .clone()
+            .with_boot_environment(&self.boot_environment)
+    }
+
+    /// Check an EFI partition size is large enough
+    pub fn validate_efi_size(size: ByteSize) -> Result<()> {
+        if size < ByteSize::mib(100) {
+            return Err(InstallerError::validation(
+                "EFI partition must be at least 100MB",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the accounts to create and what happens to root
+    fn validate_accounts(&self) -> Result<()> {
+        if !self.sets_accounts() {
+            return Ok(());
+        }
+        // Empty new pools have no system to create them in
+        if !self.installs_system() {
+            return Err(InstallerError::validation(
+                "Users and the root password can only be set in existing mode or with a bootstrap",
+            ));
+        }
+
+        let mut names = HashSet::new();
+        for user in &self.users {
+            user.validate()?;
+            if !names.insert(user.name.as_str()) {
+                return Err(InstallerError::validation(format!(
+                    "User {} is given more than once",
+                    user.name
+                )));
+            }
+        }
+        if let Some(hash) = &self.root_password_hash {
+            hash.check()?;
+        }
+
+        if self.lock_root {
+            if self.root_password_hash.is_some() {
+                return Err(InstallerError::validation(
+                    "root can't both be locked and get a password",
+                ));
+            }
+            // Otherwise nobody could administer the system
+            if !self.users.iter().any(|user| user.admin) {
+                return Err(InstallerError::validation(
+                    "Locking root needs an admin user",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the configuration
+    pub fn validate(&self) -> Result<()> {
+        Self::validate_pool_name(&self.pool_name)?;
+
+        // An existing pool keeps the layout and properties it was created with
+        if self.use_existing_pool {
+            let support = self.devices_by_role().len() > self.data_devices().len();
+            if !self.vdevs.is_empty() || self.raid_level != RaidLevel::None || support {
+                return Err(InstallerError::validation(
+                    "The vdevs of an existing pool can't be changed; give only the disks holding its ESPs",
+                ));
+            }
+            if self.encryption.is_some()
+                || self.pool_compatibility.is_some()
+                || !self.pool_options.is_empty()
+                || !self.filesystem_properties.is_empty()
+            {
+                return Err(InstallerError::validation(
+                    "An existing pool keeps its own encryption, compatibility and properties",
+                ));
+            }
+            if self.partitioning.keeps_partitions() {
+                return Err(InstallerError::validation(
+                    "An existing pool's disks are already partitioned; free-space partitioning doesn't apply",
+                ));
+            }
+        } else if self.adopt_datasets {
+            return Err(InstallerError::validation(
+                "Adopting datasets needs an existing pool (use_existing_pool)",
+            ));
+        }
+
+        // Validate devices
+        if !self.vdevs.is_empty()
+            && (!self.devices.is_empty() || self.raid_level != RaidLevel::None)
+        {
+            return Err(InstallerError::validation(
+                "Give the data devices either as vdevs or as devices and raid_level, not both",
+            ));
+        }
+        if self.data_devices().is_empty() {
+            return Err(InstallerError::validation(
+                "At least one device must be selected",
+            ));
+        }
+
+        // zpool would add plain devices listed after a mirror or raidz to it
+        let groups = self.vdev_groups();
+        if groups.len() > 1 && groups.iter().any(|vdev| vdev.raid == RaidLevel::None) {
+            return Err(InstallerError::validation(
+                "Unredundant (none) vdevs can't be striped with other vdevs",
+            ));
+        }
+
+        // Validate RAID level vs device count, for each vdev on its own
+        for vdev in groups {
+            let min_drives = vdev.raid.min_drives();
+            if vdev.devices.len() < min_drives {
+                return Err(InstallerError::validation(format!(
+                    "RAID level {} requires at least {} device(s), but only {} provided",
+                    vdev.raid,
+                    min_drives,
+                    vdev.devices.len()
+                )));
+            }
+        }
+
+        // Validate ashift
+        if let Some(ashift) = self.ashift {
+            if !(9..=16).contains(&ashift) {
+                return Err(InstallerError::validation(format!(
+                    "ashift must be between 9 and 16, got {}",
+                    ashift
+                )));
+            }
+        }
+
+        if self.jobs == Some(0) {
+            return Err(InstallerError::validation("jobs must be at least 1"));
+        }
+
+        // Log, cache and spare devices are used whole, so none can also hold data
+        let mut seen: HashMap<&Path, DeviceRole> = HashMap::new();
+        for (role, device) in self.devices_by_role() {
+            if let Some(other) = seen.insert(device, role) {
+                return Err(InstallerError::validation(format!(
+                    "{} is given as both a {} and a {} device",
+                    device.display(),
+                    other,
+                    role
+                )));
+            }
+        }
+        if self.mirror_log && self.log_devices.len() < 2 {
+            return Err(InstallerError::validation(format!(
+                "Mirroring the log needs at least 2 log devices, but {} provided",
+                self.log_devices.len()
+            )));
+        }
+
+        Self::validate_efi_size(self.efi_size)?;
+        // A swap zvol is encrypted along with the pool
+        if self.encrypt_swap && !self.encrypts_swap() {
+            return Err(InstallerError::validation(
+                "encrypt_swap needs swap partitions (swap_mode = partition and a swap_size)",
+            ));
+        }
+        if let Some(arc_max) = self.arc_max {
+            if !self.installs_system() {
+                return Err(InstallerError::validation(
+                    "arc_max can only be set in existing mode or with a bootstrap",
+                ));
+            }
+            if arc_max < MIN_ARC_MAX {
+                return Err(InstallerError::validation(format!(
+                    "arc_max must be at least {}, got {}",
+                    MIN_ARC_MAX, arc_max
+                )));
+            }
+        }
+        Self::validate_boot_environment(&self.boot_environment)?;
+        Self::validate_kernel_cmdline(&self.kernel_cmdline)?;
+        if let Some(hostname) = &self.hostname {
+            Self::validate_hostname(hostname)?;
+        }
+        if let Some(timezone) = &self.timezone {
+            Self::validate_timezone(timezone)?;
+        }
+        if let Some(locale) = &self.locale {
+            Self::validate_locale_name("locale", locale)?;
+        }
+        if let Some(keymap) = &self.keymap {
+            Self::validate_locale_name("keymap", keymap)?;
+        }
+        self.validate_accounts()?;
+        if let Some(bootstrap) = &self.bootstrap {
+            if self.mode != InstallMode::New {
+                return Err(InstallerError::validation(
+                    "Only new installs can bootstrap a system; existing mode copies one",
+                ));
+            }
+            bootstrap.validate()?;
+        }
+        self.validate_offline()?;
+        if let Some(template) = &self.initial_snapshot {
+            Self::expand_snapshot_name(template, NaiveDateTime::default())?;
+        }
+        self.datasets.validate()?;
+
+        // A custom layout names its boot environment itself
+        let boot_environment = format!("{}/{}", BOOT_ENVIRONMENTS, self.boot_environment);
+        if self.datasets != DatasetLayout::default()
+            && self.datasets.root_name() != boot_environment
+        {
+            return Err(InstallerError::validation(format!(
+                "The dataset layout mounts {} at /, but boot_environment is '{}'",
+                self.datasets.root_name(),
+                self.boot_environment
+            )));
+        }
+
+        // The cachefile is copied to the same path in the target
+        if let Some(cachefile) = &self.cachefile {
+            if !cachefile.is_absolute() {
+                return Err(InstallerError::validation(format!(
+                    "cachefile must be an absolute path, got {}",
+                    cachefile.display()
+                )));
+            }
+        }
+
+        Self::validate_extra_properties(
+            "pool_options",
+            &self.pool_options,
+            &[
+                ("ashift", "set ashift instead"),
+                ("autotrim", "set autotrim instead"),
+                ("cachefile", "set cachefile instead"),
+                ("compatibility", "set pool_compatibility instead"),
+                ("bootfs", "it is set to the boot environment"),
+            ],
+        )?;
+        Self::validate_extra_properties(
+            "filesystem_properties",
+            &self.filesystem_properties,
+            &[
+                (
+                    "mountpoint",
+                    "the pool root is never mounted (mountpoint=none)",
+                ),
+                ("canmount", "the pool root is never mounted (canmount=off)"),
+                ("encryption", "configure [encryption] instead"),
+                ("keyformat", "configure [encryption] instead"),
+                ("keylocation", "configure [encryption] instead"),
+                ("pbkdf2iters", "configure [encryption] instead"),
+            ],
+        )?;
+
+        self.validate_secure_boot()?;
+
+        // Validate encryption
+        if let Some(encryption) = &self.encryption {
+            encryption.validate()?;
+        }
+
+        // Validate source root for existing mode
+        if self.mode == InstallMode::Existing && !self.source_root.exists() {
+            return Err(InstallerError::validation(format!(
+                "Source root does not exist: {}",
+                self.source_root.display()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Check the Secure Boot mode has what it signs with
+    fn validate_secure_boot(&self) -> Result<()> {
+        if !self.secure_boot.signs() {
+            return Ok(());
+        }
+        if self.firmware == FirmwareMode::Bios {
+            return Err(InstallerError::validation(
+                "Secure Boot signing needs UEFI firmware, not --firmware bios",
+            ));
+        }
+        for (name, path) in [
+            ("secure_boot_key", &self.secure_boot_key),
+            ("secure_boot_cert", &self.secure_boot_cert),
+        ] {
+            match path {
+                None => {
+                    return Err(InstallerError::validation(format!(
+                        "secure_boot = \"{}\" needs {} to sign with",
+                        self.secure_boot, name
+                    )))
+                }
+                Some(path) if !self.dry_run && !path.is_file() => {
+                    return Err(InstallerError::validation(format!(
+                        "{} {} not found",
+                        name,
+                        path.display()
+                    )))
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The pool's top-level vdevs: `vdevs`, or a single one of `devices`
+    pub fn vdev_groups(&self) -> Vec<VdevSpec> {
+        if self.vdevs.is_empty() {
+            vec![VdevSpec::new(self.raid_level, self.devices.clone())]
+        } else {
+            self.vdevs.clone()
+        }
+    }
+
+    /// Every data device, in vdev order
+    ///
+    /// These are the disks that get partitioned and hold the pool.
+    pub fn data_devices(&self) -> Vec<&Path> {
+        if self.vdevs.is_empty() {
+            self.devices.iter().map(PathBuf::as_path).collect()
+        } else {
+            self.vdevs
+                .iter()
+                .flat_map(|vdev| vdev.devices.iter().map(PathBuf::as_path))
+                .collect()
+        }
+    }
+
+    /// The RAID level, or how the vdevs are laid out ("2 x mirror")
+    pub fn raid_summary(&self) -> String {
+        let levels: Vec<RaidLevel> = self.vdev_groups().iter().map(|vdev| vdev.raid).collect();
+        match levels.as_slice() {
+            [level] => level.to_string(),
+            [first, rest @ ..] if rest.iter().all(|level| level == first) => {
+                format!("{} x {}", levels.len(), first)
+            }
+            _ => levels
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" + "),
+        }
+    }
+
+    /// How many drives can fail without losing the pool ("survives 1 failed drive")
+    pub fn redundancy_summary(&self) -> String {
+        let groups = self.vdev_groups();
+        let failures = groups
+            .iter()
+            .map(|vdev| vdev.raid.tolerated_failures(vdev.devices.len()))
+            .min()
+            .unwrap_or(0);
+        let drives = if failures == 1 { "drive" } else { "drives" };
+        match (failures, groups.len()) {
+            (0, _) => "no redundancy: losing any drive loses the pool".to_string(),
+            (_, 1) => format!("survives {} failed {}", failures, drives),
+            _ => format!("survives {} failed {} in each vdev", failures, drives),
+        }
+    }
+
+    /// Estimated space the pool will have, from the size of each data device
+    ///
+    /// The EFI and swap partitions are taken off every disk first. `None` if
+    /// `device_size` doesn't know a device, or if the pool doesn't get whole
+    /// disks (an existing pool, or free-space partitioning).
+    pub fn usable_capacity(&self, device_size: impl Fn(&Path) -> Option<u64>) -> Option<ByteSize> {
+        if self.use_existing_pool || self.partitioning.keeps_partitions() {
+            return None;
+        }
+        let overhead = self.efi_size.as_u64() + self.swap_partition_size().as_u64();
+        let mut total = 0;
+        for vdev in self.vdev_groups() {
+            let sizes = vdev
+                .devices
+                .iter()
+                .map(|device| Some(device_size(device)?.saturating_sub(overhead)))
+                .collect::<Option<Vec<u64>>>()?;
+            total += vdev.raid.usable_capacity(&sizes);
+        }
+        Some(ByteSize(total))
+    }
+
+    /// Whether any top-level vdev has redundancy
+    pub fn is_redundant(&self) -> bool {
+        self.vdev_groups()
+            .iter()
+            .any(|vdev| vdev.raid.vdev_type().is_some())
+    }
+
+    /// Every configured device with its role, data devices first
+    pub fn devices_by_role(&self) -> Vec<(DeviceRole, &Path)> {
+        let support = [
+            (DeviceRole::Log, &self.log_devices),
+            (DeviceRole::Cache, &self.cache_devices),
+            (DeviceRole::Spare, &self.spare_devices),
+        ]
+        .into_iter()
+        .flat_map(|(role, devices)| devices.iter().map(move |d| (role, d.as_path())));
+        self.data_devices()
+            .into_iter()
+            .map(|d| (DeviceRole::Data, d))
+            .chain(support)
+            .collect()
+    }
+
+    /// Number of disks to prepare at once
+    pub fn prepare_jobs(&self) -> usize {
+        let devices = self.data_devices().len();
+        self.jobs.unwrap_or(DEFAULT_MAX_JOBS).min(devices).max(1)
+    }
+
+    /// Size of the swap partition on each device (0 when there is none)
+    pub fn swap_partition_size(&self) -> ByteSize {
+        match self.swap_mode {
+            SwapMode::Partition => self.swap_size,
+            SwapMode::Zvol | SwapMode::None => ByteSize(0),
+        }
+    }
+
+    /// Compatibility feature sets to create the pool with
+    ///
+    /// GRUB can't read pools with features it doesn't know, so it gets
+    /// `grub2` unless `pool_compatibility` says otherwise.
+    pub fn compatibility(&self) -> Option<String> {
+        match &self.pool_compatibility {
+            Some(compatibility) => Some(compatibility.clone()),
+            None if self.bootloader == Bootloader::Grub => Some(GRUB_COMPATIBILITY.to_string()),
+            None => None,
+        }
+    }
+
+    /// Size of the swap zvol, if one is created
+    pub fn swap_zvol_size(&self) -> Option<ByteSize> {
+        (self.swap_mode == SwapMode::Zvol && self.swap_size > ByteSize(0)).then_some(self.swap_size)
+    }
+
+    /// Whether swap partitions are created and encrypted with a random key
+    pub fn encrypts_swap(&self) -> bool {
+        self.encrypt_swap && self.swap_partition_size() > ByteSize(0)
+    }
+
+    /// Get the total number of partitions that will be created per device
+    pub fn partitions_per_device(&self) -> usize {
+        let mut count = 2; // EFI + ZFS
+        if self.swap_partition_size() > ByteSize(0) {
+            count += 1;
+        }
+        count
+    }
+
+    /// Calculate estimated total size needed per device
+    pub fn min_device_size(&self) -> ByteSize {
+        self.efi_size + self.swap_partition_size() + MIN_ZFS_SIZE
+    }
+
+    /// Firmware to install for, `auto` resolved against the running system
+    pub fn target_firmware(&self) -> FirmwareMode {
+        match self.firmware {
+            FirmwareMode::Auto => self.firmware.resolve(crate::system::is_uefi()),
+            mode => mode,
+        }
+    }
+
+    /// Boot partitions each disk gets
+    pub fn boot_layout(&self) -> BootLayout {
+        match self.target_firmware() {
+            FirmwareMode::Bios => BootLayout::Bios,
+            _ if self.boot_mode.has_bios() => BootLayout::Hybrid,
+            _ => BootLayout::Uefi,
+        }
+    }
+
+    /// Which devices may be installed to
+    ///
+    /// Only a wiped disk has to hold the whole layout; an existing pool or
+    /// free space is checked on its own terms.
+    pub fn suitability_policy(&self) -> SuitabilityPolicy {
+        let wipes = !self.use_existing_pool
+            && !matches!(self.partitioning, PartitioningMode::UseFreeSpace { .. });
+        SuitabilityPolicy {
+            min_size: if wipes { self.min_device_size().0 } else { 0 },
+            allow_removable: self.allow_removable,
+            force: self.force,
+        }
+    }
+}
+
+/// Parse a size as given on the command line ("512M", "8G", "1 GiB")
+pub fn parse_size(size_str: &str) -> Result<ByteSize> {
+    size_str
+        .trim()
+        .parse()
+        .map_err(|e| InstallerError::ParseError(format!("Invalid size '{}': {}", size_str, e)))
+}
+
+/// Whether `typed` confirms an operation that destroys `phrase` (a pool name)
+///
+/// Only the line ending left by reading a line is ignored; case and any
+/// other whitespace must match exactly.
+pub fn confirms(typed: &str, phrase: &str) -> bool {
+    !phrase.is_empty() && typed.trim_end_matches(['\r', '\n']) == phrase
+}
+
+/// Write sizes exactly ("512 MiB") rather than ByteSize's rounded display form
+fn serialize_size<S: serde::Serializer>(
+    size: &ByteSize,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let bytes = size.as_u64();
+    let exact = [
+        ("GiB", ByteSize::gib(1)),
+        ("MiB", ByteSize::mib(1)),
+        ("KiB", ByteSize::kib(1)),
+    ]
+    .into_iter()
+    .find(|(_, unit)| bytes > 0 && bytes.is_multiple_of(unit.as_u64()))
+    .map(|(name, unit)| format!("{} {}", bytes / unit.as_u64(), name))
+    .unwrap_or_else(|| bytes.to_string());
+
+    serializer.serialize_str(&exact)
+}
+
+/// Write a size that is set like [`serialize_size`]
+fn serialize_optional_size<S: serde::Serializer>(
+    size: &Option<ByteSize>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match size {
+        Some(size) => serialize_size(size, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Write a disabled cachefile as `"none"`, like the zpool property
+///
+/// TOML has no null, so leaving the key out would restore the default.
+fn serialize_cachefile<S: serde::Serializer>(
+    cachefile: &Option<PathBuf>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match cachefile {
+        Some(path) => path.serialize(serializer),
+        None => serializer.serialize_str("none"),
+    }
+}
+
+/// Write no initial snapshot as `"none"`, like a disabled cachefile
+fn serialize_initial_snapshot<S: serde::Serializer>(
+    snapshot: &Option<String>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(snapshot.as_deref().unwrap_or("none"))
+}
+
+/// Read `"none"` as no initial snapshot
+fn deserialize_initial_snapshot<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    let snapshot = String::deserialize(deserializer)?;
+    Ok((snapshot != "none").then_some(snapshot))
+}
+
+/// Read `"none"` as a disabled cachefile
+fn deserialize_cachefile<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<PathBuf>, D::Error> {
+    let path = PathBuf::deserialize(deserializer)?;
+    Ok((path != Path::new("none")).then_some(path))
+}
+
+/// Turn a TOML error into a one-line message naming the offending key
+fn describe_toml_error(content: &str, err: &toml::de::Error) -> String {
+    let Some(span) = err.span() else {
+        return err.message().to_string();
+    };
+
+    let line_number = content[..span.start].matches('\n').count() + 1;
+    let line = content.lines().nth(line_number - 1).unwrap_or("");
+    match line.split_once('=') {
+        Some((key, _)) => format!(
+            "invalid `{}` (line {}): {}",
+            key.trim().trim_matches('"'),
+            line_number,
+            err.message()
+        ),
+        None => format!("line {}: {}", line_number, err.message()),
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -278,6 +2305,166 @@ mod tests {
         assert_eq!(RaidLevel::Raidz3.min_drives(), 5);
     }
 
+    #[test]
+    fn test_raid_level_usable_capacity() {
+        const TB: u64 = 1_000_000_000_000;
+        let equal = [TB; 5];
+        assert_eq!(RaidLevel::None.usable_capacity(&equal), 5 * TB);
+        assert_eq!(RaidLevel::Mirror.usable_capacity(&equal), TB);
+        assert_eq!(RaidLevel::Raidz1.usable_capacity(&equal), 4 * TB);
+        assert_eq!(RaidLevel::Raidz2.usable_capacity(&equal), 3 * TB);
+        assert_eq!(RaidLevel::Raidz3.usable_capacity(&equal), 2 * TB);
+
+        // Redundant vdevs only use as much of each device as the smallest has
+        let unequal = [4 * TB, TB, 2 * TB, 3 * TB, 2 * TB];
+        assert_eq!(RaidLevel::None.usable_capacity(&unequal), 12 * TB);
+        assert_eq!(RaidLevel::Mirror.usable_capacity(&unequal[..2]), TB);
+        assert_eq!(RaidLevel::Raidz1.usable_capacity(&unequal[..3]), 2 * TB);
+        assert_eq!(RaidLevel::Raidz2.usable_capacity(&unequal[..4]), 2 * TB);
+        assert_eq!(RaidLevel::Raidz3.usable_capacity(&unequal), 2 * TB);
+
+        // Too few devices for the parity leaves nothing
+        assert_eq!(RaidLevel::Raidz2.usable_capacity(&[TB, TB]), 0);
+        assert_eq!(RaidLevel::Mirror.usable_capacity(&[]), 0);
+    }
+
+    #[test]
+    fn test_config_usable_capacity() {
+        const GB: u64 = 1_000_000_000;
+        let sizes = |path: &Path| match path.to_str()? {
+            "/dev/sda" => Some(500 * GB),
+            "/dev/sdb" => Some(250 * GB),
+            "/dev/sdc" => Some(250 * GB),
+            "/dev/sdd" => Some(1000 * GB),
+            _ => None,
+        };
+        let devices = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let mut config = Config {
+            devices: devices(&["/dev/sda", "/dev/sdb"]),
+            raid_level: RaidLevel::Mirror,
+            efi_size: ByteSize(GB),
+            swap_size: ByteSize(8 * GB),
+            ..Default::default()
+        };
+
+        // EFI and swap partitions come off every disk
+        assert_eq!(config.usable_capacity(sizes), Some(ByteSize(241 * GB)));
+        config.swap_mode = SwapMode::Zvol;
+        assert_eq!(config.usable_capacity(sizes), Some(ByteSize(249 * GB)));
+        config.raid_level = RaidLevel::None;
+        assert_eq!(config.usable_capacity(sizes), Some(ByteSize(748 * GB)));
+
+        // Striped vdevs add up
+        config.devices.clear();
+        config.vdevs = vec![
+            VdevSpec::new(RaidLevel::Mirror, devices(&["/dev/sda", "/dev/sdd"])),
+            VdevSpec::new(RaidLevel::Mirror, devices(&["/dev/sdb", "/dev/sdc"])),
+        ];
+        assert_eq!(config.usable_capacity(sizes), Some(ByteSize(748 * GB)));
+
+        // Unknown devices and kept partitions can't be estimated
+        config.vdevs[1].devices.push(PathBuf::from("/dev/sdx"));
+        assert_eq!(config.usable_capacity(sizes), None);
+        config.vdevs.pop();
+        config.partitioning = PartitioningMode::UseFreeSpace { reuse_esp: false };
+        assert_eq!(config.usable_capacity(sizes), None);
+    }
+
+    #[test]
+    fn test_redundancy_summary() {
+        let config = |raid_level, count: usize| Config {
+            devices: (0..count)
+                .map(|i| PathBuf::from(format!("/dev/sd{}", i)))
+                .collect(),
+            raid_level,
+            ..Default::default()
+        };
+        assert!(config(RaidLevel::None, 2)
+            .redundancy_summary()
+            .starts_with("no redundancy"));
+        assert_eq!(
+            config(RaidLevel::Mirror, 3).redundancy_summary(),
+            "survives 2 failed drives"
+        );
+        assert_eq!(
+            config(RaidLevel::Raidz1, 3).redundancy_summary(),
+            "survives 1 failed drive"
+        );
+
+        let mut striped = config(RaidLevel::None, 0);
+        striped.vdevs = vec![
+            VdevSpec::new(
+                RaidLevel::Mirror,
+                vec!["/dev/sda".into(), "/dev/sdb".into()],
+            ),
+            VdevSpec::new(RaidLevel::Raidz2, vec!["/dev/sdc".into(); 4]),
+        ];
+        assert_eq!(
+            striped.redundancy_summary(),
+            "survives 1 failed drive in each vdev"
+        );
+    }
+
+    #[test]
+    fn test_secure_boot_needs_a_key_pair() {
+        let config = |secure_boot, key: Option<&str>, firmware| Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            secure_boot,
+            secure_boot_key: key.map(PathBuf::from),
+            secure_boot_cert: Some(PathBuf::from("/etc/secureboot/db.crt")),
+            firmware,
+            dry_run: true,
+            ..Default::default()
+        };
+        let key = Some("/etc/secureboot/db.key");
+        assert!(config(SecureBootMode::Off, None, FirmwareMode::Bios)
+            .validate()
+            .is_ok());
+        assert!(config(SecureBootMode::Shim, key, FirmwareMode::Uefi)
+            .validate()
+            .is_ok());
+
+        let err = config(SecureBootMode::SignLocal, None, FirmwareMode::Uefi)
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("secure_boot_key"), "{err}");
+        assert!(config(SecureBootMode::Shim, key, FirmwareMode::Bios)
+            .validate()
+            .is_err());
+
+        // Outside a dry run the key pair has to exist
+        let mut live = config(SecureBootMode::SignLocal, key, FirmwareMode::Uefi);
+        live.dry_run = false;
+        let err = live.validate().unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
+    }
+
+    #[test]
+    fn test_firmware_selects_the_boot_layout() {
+        assert_eq!(FirmwareMode::Auto.resolve(true), FirmwareMode::Uefi);
+        assert_eq!(FirmwareMode::Auto.resolve(false), FirmwareMode::Bios);
+        assert_eq!(FirmwareMode::Uefi.resolve(false), FirmwareMode::Uefi);
+
+        let layout = |firmware, boot_mode| {
+            Config {
+                firmware,
+                boot_mode,
+                ..Default::default()
+            }
+            .boot_layout()
+        };
+        assert_eq!(layout(FirmwareMode::Uefi, BootMode::Uefi), BootLayout::Uefi);
+        assert_eq!(
+            layout(FirmwareMode::Uefi, BootMode::Hybrid),
+            BootLayout::Hybrid
+        );
+        // The boot mode only lays out UEFI installs
+        assert_eq!(
+            layout(FirmwareMode::Bios, BootMode::Hybrid),
+            BootLayout::Bios
+        );
+    }
+
     #[test]
     fn test_config_validation_empty_devices() {
         let config = Config::default();
@@ -292,6 +2479,56 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_vdev_groups_are_validated_independently() {
+        let striped_mirrors = Config {
+            vdevs: vec![
+                "mirror:/dev/sda,/dev/sdb".parse().unwrap(),
+                "mirror:/dev/sdc,/dev/sdd".parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        striped_mirrors.validate().unwrap();
+        assert_eq!(striped_mirrors.data_devices().len(), 4);
+        assert_eq!(striped_mirrors.vdev_groups()[1].raid, RaidLevel::Mirror);
+        assert_eq!(striped_mirrors.raid_summary(), "2 x mirror");
+        assert_eq!(
+            striped_mirrors.vdevs[0].to_string(),
+            "mirror:/dev/sda,/dev/sdb"
+        );
+        assert_eq!(
+            Config::from_toml(&striped_mirrors.to_toml().unwrap()).unwrap(),
+            striped_mirrors
+        );
+
+        let short = Config {
+            vdevs: vec![
+                "mirror:/dev/sda,/dev/sdb".parse().unwrap(),
+                "mirror:/dev/sdc".parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        assert!(short.validate().is_err());
+
+        let both = Config {
+            devices: vec![PathBuf::from("/dev/sde")],
+            ..striped_mirrors
+        };
+        assert!(both.validate().is_err());
+
+        let mixed = Config {
+            vdevs: vec![
+                "mirror:/dev/sda,/dev/sdb".parse().unwrap(),
+                "none:/dev/sdc".parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        assert!(mixed.validate().is_err());
+
+        assert!("mirror".parse::<VdevSpec>().is_err());
+        assert!("raid10:/dev/sda".parse::<VdevSpec>().is_err());
+    }
+
     #[test]
     fn test_config_validation_valid() {
         let mut config = Config::default();
@@ -308,9 +2545,800 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_field_validators() {
+        assert!(Config::validate_pool_name("tank-1.old_2").is_ok());
+        assert!(Config::validate_pool_name("").is_err());
+        assert!(Config::validate_pool_name("my pool").is_err());
+
+        assert_eq!(parse_size("512M").unwrap(), ByteSize::mb(512));
+        assert_eq!(parse_size(" 1 GiB ").unwrap(), ByteSize::gib(1));
+        assert!(parse_size("lots").is_err());
+
+        assert!(Config::validate_efi_size(ByteSize::mib(512)).is_ok());
+        assert!(Config::validate_efi_size(ByteSize::mib(64)).is_err());
+
+        assert!(Config::validate_kernel_cmdline("rw quiet loglevel=3").is_ok());
+        assert!(Config::validate_kernel_cmdline("").is_ok());
+        assert!(Config::validate_kernel_cmdline("rw\nquiet").is_err());
+    }
+
+    #[test]
+    fn test_initial_snapshot_name() {
+        let now = NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(13, 45, 0)
+            .unwrap();
+        let name = |template: &str| Config::expand_snapshot_name(template, now);
+        assert_eq!(name("initial").unwrap(), "initial");
+        assert_eq!(name("install-%Y%m%d").unwrap(), "install-20240501");
+        assert_eq!(name("%F_%H%M").unwrap(), "2024-05-01_1345");
+        assert!(name("%Q").is_err());
+        assert!(name("").is_err());
+        assert!(name("root@initial").is_err());
+        // %D expands to 05/01/24
+        assert!(name("install-%D").is_err());
+
+        let mut config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            ..Default::default()
+        };
+        assert_eq!(
+            config.initial_snapshot_name(now).unwrap().as_deref(),
+            Some("initial")
+        );
+        config.initial_snapshot = Some("a/b".to_string());
+        assert!(config.validate().is_err());
+        config.initial_snapshot = None;
+        assert_eq!(config.initial_snapshot_name(now).unwrap(), None);
+        assert!(config
+            .to_toml()
+            .unwrap()
+            .contains("initial_snapshot = \"none\""));
+    }
+
     #[test]
     fn test_compression_display() {
         assert_eq!(Compression::Zstd.to_string(), "zstd");
         assert_eq!(Compression::Lz4.to_string(), "lz4");
     }
+
+    #[test]
+    fn test_swap_zvol_takes_no_partition() {
+        let mut config = Config::default();
+        assert_eq!(config.partitions_per_device(), 3);
+        assert_eq!(config.swap_zvol_size(), None);
+
+        config.swap_mode = SwapMode::Zvol;
+        assert_eq!(config.partitions_per_device(), 2);
+        assert_eq!(config.swap_partition_size(), ByteSize(0));
+        assert_eq!(config.swap_zvol_size(), Some(ByteSize::gib(8)));
+        assert_eq!(config.min_device_size(), ByteSize::gib(11));
+
+        config.swap_mode = SwapMode::None;
+        assert_eq!(config.swap_zvol_size(), None);
+        assert_eq!(config.partitions_per_device(), 2);
+    }
+
+    #[test]
+    fn test_prepare_jobs() {
+        let mut config = Config {
+            devices: (0..6)
+                .map(|i| PathBuf::from(format!("/dev/sd{}", i)))
+                .collect(),
+            raid_level: RaidLevel::Raidz2,
+            ..Default::default()
+        };
+        assert_eq!(config.prepare_jobs(), DEFAULT_MAX_JOBS);
+        config.jobs = Some(8);
+        assert_eq!(config.prepare_jobs(), 6);
+        config.jobs = Some(1);
+        assert_eq!(config.prepare_jobs(), 1);
+        config.validate().unwrap();
+        config.jobs = Some(0);
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("jobs"), "{err}");
+
+        config.devices.truncate(2);
+        config.raid_level = RaidLevel::Mirror;
+        config.jobs = None;
+        assert_eq!(config.prepare_jobs(), 2);
+    }
+
+    #[test]
+    fn test_default_config_round_trips() {
+        let config = Config::default();
+        let toml = config.to_toml().unwrap();
+        assert_eq!(Config::from_toml(&toml).unwrap(), config);
+    }
+
+    #[test]
+    fn test_populated_config_round_trips() {
+        let config = Config {
+            mode: InstallMode::Existing,
+            pool_name: "tank".to_string(),
+            use_existing_pool: true,
+            adopt_datasets: true,
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            raid_level: RaidLevel::Mirror,
+            vdevs: Vec::new(),
+            log_devices: vec![PathBuf::from("/dev/nvme0n1"), PathBuf::from("/dev/nvme1n1")],
+            mirror_log: true,
+            cache_devices: vec![PathBuf::from("/dev/sdc")],
+            spare_devices: vec![PathBuf::from("/dev/sdd")],
+            efi_size: ByteSize::mib(512),
+            swap_mode: SwapMode::Zvol,
+            swap_size: ByteSize::b(1_500_000_001),
+            encrypt_swap: true,
+            arc_max: Some(ByteSize::gib(4)),
+            ashift: Some(12),
+            compression: Compression::Lz4,
+            firmware: FirmwareMode::Auto,
+            boot_mode: BootMode::Hybrid,
+            bootloader: Bootloader::EfiStub,
+            zbm_source: ZbmSource::CustomImage {
+                path: PathBuf::from("/srv/zfsbootmenu.EFI"),
+            },
+            zbm_release_fallback: true,
+            offline: true,
+            artifact_dir: Some(PathBuf::from("/srv/artifacts")),
+            pkg_dir: Some(PathBuf::from("/srv/packages")),
+            install_fallback: true,
+            ignore_bootloader_errors: true,
+            secure_boot: SecureBootMode::SignLocal,
+            secure_boot_key: Some(PathBuf::from("/etc/secureboot/db.key")),
+            secure_boot_cert: Some(PathBuf::from("/etc/secureboot/db.crt")),
+            tmpfs_tmp: true,
+            use_by_id: false,
+            encryption: Some(EncryptionConfig {
+                keyformat: KeyFormat::Raw,
+                keylocation: Some("file:///etc/zfs/tank.key".to_string()),
+                ..Default::default()
+            }),
+            cachefile: None,
+            autotrim: Some(true),
+            pool_compatibility: Some("openzfs-2.1-linux".to_string()),
+            pool_options: BTreeMap::from([(
+                "feature@large_dnode".to_string(),
+                "disabled".to_string(),
+            )]),
+            filesystem_properties: BTreeMap::from([("atime".to_string(), "off".to_string())]),
+            hostname: Some("nas".to_string()),
+            timezone: Some("Europe/Berlin".to_string()),
+            locale: Some("de_DE.UTF-8".to_string()),
+            keymap: Some("de-latin1".to_string()),
+            users: vec![UserSpec {
+                name: "alice".to_string(),
+                groups: vec!["video".to_string()],
+                shell: Some(PathBuf::from("/bin/zsh")),
+                admin: true,
+                password_hash: Some(PasswordHash::new("$6$salt$hash").unwrap()),
+            }],
+            root_password_hash: None,
+            lock_root: true,
+            boot_environment: "nas".to_string(),
+            kernel_cmdline: "rw console=ttyS0".to_string(),
+            initial_snapshot: None,
+            recursive_snapshot: true,
+            dry_run: true,
+            force: true,
+            allow_removable: true,
+            unattended: true,
+            source_root: PathBuf::from("/mnt/old"),
+            exclude_paths: vec![PathBuf::from("/var/cache")],
+            copy_home: false,
+            pin_interface_names: true,
+            bootstrap: None,
+            skip_preflight: true,
+            partitioning: PartitioningMode::UseFreeSpace { reuse_esp: true },
+            wipe_mode: WipeMode::ZeroLabels,
+            device_timeout: 90,
+            device_retries: 5,
+            jobs: Some(2),
+            release_holders: true,
+            rollback: false,
+            wipe_on_rollback: true,
+            keep_mounted: true,
+            datasets: DatasetLayout::new(vec![
+                crate::zfs::DatasetSpec::new("ROOT"),
+                crate::zfs::DatasetSpec::new("ROOT/nas")
+                    .with_canmount(crate::zfs::CanMount::Noauto)
+                    .with_mountpoint("/"),
+            ]),
+            hooks: HashMap::from([(
+                HookPoint::Post(Phase::CreatePool),
+                vec![HookSpec::new("/hooks/a").optional()],
+            )]),
+            pre_install: Vec::new(),
+            post_pool: Vec::new(),
+            post_install: Vec::new(),
+            run_hooks_in_dry_run: true,
+        };
+
+        let toml = config.to_toml().unwrap();
+        assert!(toml.contains("efi_size = \"512 MiB\""));
+        assert!(toml.contains("raid_level = \"mirror\""));
+        assert!(toml.contains("bootloader = \"efi-stub\""));
+        assert_eq!(Config::from_toml(&toml).unwrap(), config);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("install.toml");
+        config.to_file(&path).unwrap();
+        assert_eq!(Config::from_file(&path).unwrap(), config);
+    }
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let config = Config::from_toml("pool_name = \"tank\"\nefi_size = \"1 GiB\"\n").unwrap();
+        assert_eq!(config.pool_name, "tank");
+        assert_eq!(config.efi_size, ByteSize::gib(1));
+        assert_eq!(config.swap_size, Config::default().swap_size);
+    }
+
+    #[test]
+    fn test_zbm_source_from_file() {
+        let source = |toml: &str| Config::from_toml(toml).unwrap().zbm_source;
+        assert_eq!(source(""), ZbmSource::Release { version: None });
+        assert_eq!(source("zbm_source = \"local\"\n"), ZbmSource::Local);
+        assert_eq!(
+            source("[zbm_source.release]\nversion = \"2.3.0\"\n"),
+            ZbmSource::Release {
+                version: Some("2.3.0".to_string())
+            }
+        );
+        assert_eq!(
+            source("[zbm_source.custom-image]\npath = \"/srv/zbm.EFI\"\n"),
+            ZbmSource::CustomImage {
+                path: PathBuf::from("/srv/zbm.EFI")
+            }
+        );
+    }
+
+    #[test]
+    fn test_custom_dataset_layout_from_file() {
+        let config = Config::from_toml(
+            r#"devices = ["/dev/sda"]
+boot_environment = "arch"
+
+[[datasets]]
+name = "ROOT"
+canmount = "off"
+mountpoint = "none"
+
+[[datasets]]
+name = "ROOT/arch"
+canmount = "noauto"
+mountpoint = "/"
+
+[[datasets]]
+name = "var"
+canmount = "off"
+
+[[datasets]]
+name = "var/lib"
+canmount = "off"
+
+[[datasets]]
+name = "var/lib/docker"
+mountpoint = "/var/lib/docker"
+properties = { recordsize = "1M", "com.sun:auto-snapshot" = "false" }
+"#,
+        )
+        .unwrap();
+
+        config.validate().unwrap();
+        assert_eq!(config.datasets.root_name(), "ROOT/arch");
+        assert_eq!(
+            config.datasets.datasets()[4].zfs_properties(),
+            vec![
+                ("mountpoint".to_string(), "/var/lib/docker".to_string()),
+                ("com.sun:auto-snapshot".to_string(), "false".to_string()),
+                ("recordsize".to_string(), "1M".to_string()),
+            ]
+        );
+        assert_eq!(
+            Config::from_toml(&config.to_toml().unwrap()).unwrap(),
+            config
+        );
+
+        let mut config = config;
+        config.datasets = DatasetLayout::new(Vec::new());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_support_devices_are_validated() {
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            raid_level: RaidLevel::Mirror,
+            log_devices: vec![PathBuf::from("/dev/nvme0n1")],
+            cache_devices: vec![PathBuf::from("/dev/sdc")],
+            ..Default::default()
+        };
+        config.validate().unwrap();
+        assert_eq!(
+            config.devices_by_role()[2],
+            (DeviceRole::Log, Path::new("/dev/nvme0n1"))
+        );
+
+        let overlapping = Config {
+            spare_devices: vec![PathBuf::from("/dev/sdb")],
+            ..config.clone()
+        };
+        let err = overlapping.validate().unwrap_err().to_string();
+        assert!(err.contains("both a data and a spare device"), "{}", err);
+
+        let mirrored = Config {
+            mirror_log: true,
+            ..config
+        };
+        assert!(mirrored.validate().is_err());
+        let mirrored = Config {
+            log_devices: vec![PathBuf::from("/dev/nvme0n1"), PathBuf::from("/dev/nvme1n1")],
+            ..mirrored
+        };
+        mirrored.validate().unwrap();
+    }
+
+    #[test]
+    fn test_extra_properties_are_validated() {
+        for name in [
+            "atime",
+            "recordsize",
+            "feature@large_dnode",
+            "org.zfsbootmenu:commandline",
+        ] {
+            Config::validate_property_name(name).unwrap();
+        }
+        for name in [
+            "",
+            "Atime",
+            "com.example",
+            ":commandline",
+            "org.zfsbootmenu:Cmd",
+            "a b",
+        ] {
+            assert!(Config::validate_property_name(name).is_err(), "{}", name);
+        }
+
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            pool_options: BTreeMap::from([("autoexpand".to_string(), "on".to_string())]),
+            filesystem_properties: BTreeMap::from([
+                ("atime".to_string(), "off".to_string()),
+                ("compression".to_string(), "zstd-3".to_string()),
+            ]),
+            ..Default::default()
+        };
+        config.validate().unwrap();
+
+        let mut mounted = config.clone();
+        mounted
+            .filesystem_properties
+            .insert("mountpoint".to_string(), "/".to_string());
+        let err = mounted.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("filesystem_properties.mountpoint is managed"),
+            "{}",
+            err
+        );
+
+        let mut ashift = config;
+        ashift
+            .pool_options
+            .insert("ashift".to_string(), "12".to_string());
+        let err = ashift.validate().unwrap_err().to_string();
+        assert!(err.contains("set ashift instead"), "{}", err);
+    }
+
+    #[test]
+    fn test_grub_defaults_to_grub2_compatibility() {
+        let mut config = Config::default();
+        assert_eq!(config.compatibility(), None);
+
+        config.bootloader = Bootloader::Grub;
+        assert_eq!(config.compatibility().as_deref(), Some("grub2"));
+
+        config.pool_compatibility = Some("openzfs-2.1-linux".to_string());
+        assert_eq!(config.compatibility().as_deref(), Some("openzfs-2.1-linux"));
+    }
+
+    #[test]
+    fn test_existing_pool_is_validated() {
+        let config = Config {
+            use_existing_pool: true,
+            devices: vec![PathBuf::from("/dev/sda")],
+            ..Default::default()
+        };
+        config.validate().unwrap();
+
+        for changed in [
+            Config {
+                raid_level: RaidLevel::Mirror,
+                devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+                ..config.clone()
+            },
+            Config {
+                spare_devices: vec![PathBuf::from("/dev/sdb")],
+                ..config.clone()
+            },
+        ] {
+            let err = changed.validate().unwrap_err().to_string();
+            assert!(err.contains("can't be changed"), "{}", err);
+        }
+        let err = Config {
+            partitioning: PartitioningMode::UseFreeSpace { reuse_esp: false },
+            ..config.clone()
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("free-space"), "{err}");
+
+        let adopting = Config {
+            adopt_datasets: true,
+            ..Default::default()
+        };
+        assert!(adopting.validate().is_err());
+        Config {
+            use_existing_pool: true,
+            devices: vec![PathBuf::from("/dev/sda")],
+            ..adopting
+        }
+        .validate()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_device_role_cycles() {
+        assert_eq!(DeviceRole::Data.next(), DeviceRole::Log);
+        assert_eq!(DeviceRole::Spare.next(), DeviceRole::Data);
+    }
+
+    #[test]
+    fn test_boot_environment_name() {
+        for name in ["default", "ubuntu-24.04", "void_2024:1"] {
+            Config::validate_boot_environment(name).unwrap();
+        }
+        for name in ["", "..", "a/b", "a@b", "with space"] {
+            assert!(Config::validate_boot_environment(name).is_err(), "{}", name);
+        }
+
+        let mut config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            boot_environment: "void".to_string(),
+            ..Default::default()
+        };
+        config.validate().unwrap();
+        assert_eq!(config.dataset_layout().root_name(), "ROOT/void");
+
+        // A custom layout has to name the same boot environment
+        config.datasets = DatasetLayout::default().with_boot_environment("arch");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("mounts ROOT/arch at /"), "{}", err);
+    }
+
+    #[test]
+    fn test_accounts() {
+        let admin = UserSpec {
+            admin: true,
+            ..UserSpec::new("alice")
+        };
+        let config = |users: Vec<UserSpec>, lock_root: bool| Config {
+            mode: InstallMode::Existing,
+            devices: vec![PathBuf::from("/dev/sda")],
+            users,
+            lock_root,
+            ..Config::default()
+        };
+        let invalid = |config: Config| config.validate_accounts().unwrap_err().to_string();
+
+        config(vec![admin.clone()], true)
+            .validate_accounts()
+            .unwrap();
+        assert!(invalid(config(vec![UserSpec::new("bob")], true)).contains("admin user"));
+        assert!(
+            invalid(config(vec![admin.clone(), admin.clone()], false)).contains("more than once")
+        );
+        assert!(invalid(config(vec![UserSpec::new("root")], false)).contains("root"));
+        assert!(invalid(config(vec![UserSpec::new("Alice")], false)).contains("valid user"));
+        assert!(invalid(Config {
+            mode: InstallMode::New,
+            ..config(vec![admin], false)
+        })
+        .contains("existing mode"));
+
+        PasswordHash::new("$y$j9T$salt$hash").unwrap();
+        for hash in ["hunter2", "$6$", "$6$salt$ha:sh", "$6$salt$hash\nroot:x"] {
+            assert!(PasswordHash::new(hash).is_err(), "{}", hash);
+        }
+        let hash = PasswordHash::new("$6$salt$hash").unwrap();
+        assert_eq!(format!("{:?}", hash), "PasswordHash(<redacted>)");
+    }
+
+    #[test]
+    fn test_bootstrap_spec() {
+        let spec = |s: &str| s.parse::<BootstrapSpec>().unwrap();
+        assert_eq!(
+            spec("debootstrap:bookworm"),
+            BootstrapSpec::new(BootstrapMethod::Debootstrap, Some("bookworm".to_string()))
+        );
+        assert!("apt:bookworm".parse::<BootstrapSpec>().is_err());
+
+        spec("debootstrap:bookworm").validate().unwrap();
+        spec("pacstrap").validate().unwrap();
+        assert!(spec("debootstrap").validate().is_err());
+        assert!(spec("dnf:--nogpgcheck").validate().is_err());
+        assert!(BootstrapSpec {
+            mirror: Some("https://example.org".to_string()),
+            ..spec("dnf:40")
+        }
+        .validate()
+        .is_err());
+        assert!(BootstrapSpec {
+            mirror: Some("ftp://example.org/debian".to_string()),
+            ..spec("debootstrap:bookworm")
+        }
+        .validate()
+        .is_err());
+
+        let mirror = |url: &str| {
+            BootstrapSpec {
+                mirror: Some(url.to_string()),
+                ..spec("debootstrap:noble")
+            }
+            .mirror_address()
+        };
+        assert_eq!(
+            spec("debootstrap:bookworm").mirror_address(),
+            Some(("deb.debian.org".to_string(), 80))
+        );
+        assert_eq!(
+            mirror("https://mirror.example.org/ubuntu"),
+            Some(("mirror.example.org".to_string(), 443))
+        );
+        assert_eq!(
+            mirror("http://10.0.0.2:3142/ubuntu"),
+            Some(("10.0.0.2".to_string(), 3142))
+        );
+        assert_eq!(mirror("file:///srv/mirror"), None);
+
+        let mut config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            bootstrap: Some(spec("pacstrap")),
+            ..Config::default()
+        };
+        config.validate().unwrap();
+        assert!(config.installs_system());
+        config.mode = InstallMode::Existing;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_offline_needs_local_sources() {
+        let config = |zbm_source: ZbmSource, artifact_dir: Option<&str>| Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            offline: true,
+            zbm_source,
+            artifact_dir: artifact_dir.map(PathBuf::from),
+            ..Config::default()
+        };
+
+        config(ZbmSource::default(), Some("/srv/artifacts"))
+            .validate()
+            .unwrap();
+        config(
+            ZbmSource::CustomImage {
+                path: PathBuf::from("/srv/zbm.EFI"),
+            },
+            None,
+        )
+        .validate()
+        .unwrap();
+        let err = config(ZbmSource::default(), None).validate().unwrap_err();
+        assert!(err.to_string().contains("artifact directory"), "{err}");
+
+        let mut bootstrap = "debootstrap:bookworm".parse::<BootstrapSpec>().unwrap();
+        let with_bootstrap = |bootstrap: &BootstrapSpec| Config {
+            bootstrap: Some(bootstrap.clone()),
+            ..config(ZbmSource::default(), Some("/srv/artifacts"))
+        };
+        assert!(with_bootstrap(&bootstrap).validate().is_err());
+        bootstrap.mirror = Some("file:///srv/debian".to_string());
+        with_bootstrap(&bootstrap).validate().unwrap();
+    }
+
+    #[test]
+    fn test_arc_max_needs_a_system() {
+        let config = |mode: InstallMode, arc_max: ByteSize| Config {
+            mode,
+            devices: vec![PathBuf::from("/dev/sda")],
+            source_root: std::env::temp_dir(),
+            arc_max: Some(arc_max),
+            ..Config::default()
+        };
+
+        config(InstallMode::Existing, ByteSize::gib(2))
+            .validate()
+            .unwrap();
+        assert!(config(InstallMode::New, ByteSize::gib(2))
+            .validate()
+            .is_err());
+        let err = config(InstallMode::Existing, ByteSize::mib(32))
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("at least"), "{err}");
+
+        let toml = config(InstallMode::Existing, ByteSize::gib(2))
+            .to_toml()
+            .unwrap();
+        assert!(toml.contains("arc_max = \"2 GiB\""), "{toml}");
+        assert!(!Config::default().to_toml().unwrap().contains("arc_max"));
+    }
+
+    #[test]
+    fn test_encrypt_swap_needs_swap_partitions() {
+        let config = |swap_mode: SwapMode, swap_size: ByteSize| Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            swap_mode,
+            swap_size,
+            encrypt_swap: true,
+            ..Config::default()
+        };
+
+        assert!(config(SwapMode::Partition, ByteSize::gib(8)).encrypts_swap());
+        config(SwapMode::Partition, ByteSize::gib(8))
+            .validate()
+            .unwrap();
+        assert!(config(SwapMode::Zvol, ByteSize::gib(8)).validate().is_err());
+        assert!(config(SwapMode::Partition, ByteSize(0)).validate().is_err());
+    }
+
+    #[test]
+    fn test_identity_names() {
+        for hostname in ["nas", "nas-01.example.org"] {
+            Config::validate_hostname(hostname).unwrap();
+        }
+        for hostname in ["", "-nas", "nas_01", "a..b", &"a".repeat(64)] {
+            assert!(Config::validate_hostname(hostname).is_err(), "{}", hostname);
+        }
+        for timezone in [
+            "UTC",
+            "Europe/Berlin",
+            "America/Argentina/Buenos_Aires",
+            "Etc/GMT+5",
+        ] {
+            Config::validate_timezone(timezone).unwrap();
+        }
+        for timezone in ["", "/etc/passwd", "../../etc/passwd", "Europe//Berlin"] {
+            assert!(Config::validate_timezone(timezone).is_err(), "{}", timezone);
+        }
+        Config::validate_locale_name("locale", "sr_RS.UTF-8@latin").unwrap();
+        assert!(Config::validate_locale_name("keymap", "de\nKEYMAP=us").is_err());
+    }
+
+    #[test]
+    fn test_boot_environment_suggestion() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let mut config = Config::default();
+        assert_eq!(
+            config.suggested_boot_environment(Distro::Ubuntu, date),
+            None
+        );
+
+        config.hostname = Some("nas".to_string());
+        assert_eq!(
+            config.suggested_boot_environment(Distro::Ubuntu, date),
+            Some("ubuntu-2024-05-01".to_string())
+        );
+        assert_eq!(
+            config.suggested_boot_environment(Distro::Unknown, date),
+            None
+        );
+
+        config.boot_environment = "mine".to_string();
+        assert_eq!(
+            config.suggested_boot_environment(Distro::Ubuntu, date),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_key() {
+        let err = Config::from_toml("pool_name = \"tank\"\nraid_level = \"raid9\"\n").unwrap_err();
+        let msg = err.to_string();
+        assert!(matches!(err, InstallerError::ConfigError(_)));
+        assert!(msg.contains("`raid_level` (line 2)"), "{msg}");
+
+        let err = Config::from_toml("pool_nmae = \"tank\"\n").unwrap_err();
+        assert!(err.to_string().contains("`pool_nmae` (line 1)"), "{err}");
+    }
+
+    #[test]
+    fn test_passphrase_confirmation() {
+        assert!(Passphrase::confirmed("correct horse", "correct horse").is_ok());
+        assert!(Passphrase::confirmed("correct horse", "correct house").is_err());
+        assert!(Passphrase::confirmed("", "").is_err());
+        assert!(Passphrase::confirmed("short", "short").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_strength() {
+        assert_eq!(
+            Passphrase::strength("hunter2"),
+            PassphraseStrength::TooShort
+        );
+        assert_eq!(Passphrase::strength("password"), PassphraseStrength::Weak);
+        assert_eq!(Passphrase::strength("Tr0ub4dor3"), PassphraseStrength::Fair);
+        assert_eq!(
+            Passphrase::strength("correct horse battery staple"),
+            PassphraseStrength::Strong
+        );
+        // Characters, not bytes
+        assert_eq!(
+            Passphrase::strength("äöüäöüä"),
+            PassphraseStrength::TooShort
+        );
+    }
+
+    #[test]
+    fn test_confirms() {
+        assert!(confirms("tank", "tank"));
+        // As read from stdin
+        assert!(confirms("tank\n", "tank"));
+        assert!(confirms("tank\r\n", "tank"));
+
+        assert!(!confirms("Tank", "tank"));
+        assert!(!confirms("TANK", "tank"));
+        assert!(!confirms("tank ", "tank"));
+        assert!(!confirms(" tank", "tank"));
+        assert!(!confirms("tank \n", "tank"));
+        assert!(!confirms("tan", "tank"));
+        assert!(!confirms("tanks", "tank"));
+        assert!(!confirms("yes", "tank"));
+        assert!(!confirms("", ""));
+        assert!(!confirms("\n", ""));
+    }
+
+    #[test]
+    fn test_passphrase_never_printed_or_saved() {
+        let config = Config {
+            encryption: Some(EncryptionConfig {
+                passphrase: Some(Passphrase::new("hunter2hunter2")),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(!format!("{:?}", config).contains("hunter2"));
+        let toml = config.to_toml().unwrap();
+        assert!(!toml.contains("hunter2"));
+        assert!(toml.contains("[encryption]"));
+    }
+
+    #[test]
+    fn test_encryption_validation() {
+        let mut encryption = EncryptionConfig::default();
+        assert!(encryption.validate().is_err()); // no passphrase
+
+        encryption.passphrase = Some(Passphrase::new(""));
+        assert!(encryption.validate().is_err());
+
+        encryption.passphrase = Some(Passphrase::new("long enough"));
+        assert!(encryption.validate().is_ok());
+
+        // Raw keys can't be typed in
+        encryption.keyformat = KeyFormat::Raw;
+        assert!(encryption.validate().is_err());
+
+        let key = tempfile::NamedTempFile::new().unwrap();
+        encryption.keylocation = Some(format!("file://{}", key.path().display()));
+        assert!(encryption.validate().is_ok());
+        assert_eq!(
+            encryption.properties()[2],
+            (
+                "keylocation".to_string(),
+                format!("file://{}", key.path().display())
+            )
+        );
+    }
 }
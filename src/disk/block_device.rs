@@ -2,9 +2,57 @@
 //!
 //! Represents a physical or virtual block device with all relevant properties.
 
+use crate::disk::holders::read_holders;
+use crate::disk::smart::{self, SmartStatus};
+use crate::disk::sysroot::{SysRoot, BY_ID_DIR, BY_PARTUUID_DIR};
 use crate::error::{InstallerError, Result};
+use crate::exec::CommandExecutor;
+use crate::zfs::PoolMember;
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Symlinks followed when resolving a mount source before giving up
+const MAX_LINK_DEPTH: usize = 8;
+
+/// Where live and installer systems mount the medium they booted from, and
+/// the root filesystem, which lives on it when booted from an installed
+/// system
+const BOOT_MEDIUM_MOUNTS: &[&str] = &[
+    "/run/live/medium",
+    "/lib/live/mount/medium",
+    "/run/initramfs/live",
+    "/cdrom",
+    "/",
+];
+
+/// Where desktop automounters (udisks) mount removable filesystems
+const AUTOMOUNT_DIRS: &[&str] = &["/media", "/run/media"];
+
+/// Largest optimal I/O size taken as the drive's real sector size
+///
+/// Anything bigger is a RAID stripe or an erase block, not a sector.
+const MAX_OPTIMAL_IO_SECTOR: u32 = 16 * 1024;
+
+/// NVMe models that ship formatted with 512-byte sectors but offer a 4K LBA
+/// format (`nvme id-ns -H` lists it); not exhaustive
+const KNOWN_4K_NVME_MODELS: &[&str] = &[
+    "WDC WDS",
+    "WD_BLACK SN",
+    "WD Red SN",
+    "FireCuda 530",
+    "KINGSTON SKC3000",
+    "KINGSTON SFYR",
+    "INTEL SSDPE2K",
+    "INTEL SSDPF2K",
+    "Micron_7400",
+    "Micron_7450",
+    "SAMSUNG MZQL2",
+    "SAMSUNG MZ1L2",
+];
 
 /// Represents a partition on a block device
 #[derive(Debug, Clone)]
@@ -15,21 +63,62 @@ pub struct Partition {
     pub number: u32,
     /// Partition size in bytes
     pub size: u64,
-    /// Filesystem type (if any)
+    /// Filesystem type (if any), filled in by [`BlockDevice::probe_filesystems`]
     pub fstype: Option<String>,
+    /// Filesystem label (if any)
+    pub label: Option<String>,
+    /// Filesystem UUID (if any)
+    pub uuid: Option<String>,
     /// Mount point (if mounted)
     pub mountpoint: Option<PathBuf>,
 }
 
+/// Which devices [`BlockDevice::is_suitable`] accepts
+///
+/// Built by [`Config::suitability_policy`](crate::config::Config::suitability_policy),
+/// so the pre-flight checks and disk preparation apply the same rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuitabilityPolicy {
+    /// Smallest device accepted, in bytes
+    pub min_size: u64,
+    /// Accept removable devices (USB sticks, SD cards) like fixed ones
+    pub allow_removable: bool,
+    /// Accept removable devices, to be warned about
+    pub force: bool,
+}
+
+impl SuitabilityPolicy {
+    /// Whether removable devices pass
+    pub fn allows_removable(&self) -> bool {
+        self.allow_removable || self.force
+    }
+
+    /// Whether `device` only passes because of `force`, and should be warned about
+    pub fn forces(&self, device: &BlockDevice) -> bool {
+        device.removable && !self.allow_removable && self.force
+    }
+}
+
 /// Storage controller type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ControllerType {
+    /// SATA
     Sata,
+    /// NVMe
     Nvme,
+    /// SAS, directly or behind an expander
+    Sas,
+    /// Other SCSI transports
     Scsi,
+    /// A dm-multipath map over several paths to one LUN
+    Multipath,
+    /// USB mass storage
     Usb,
+    /// SD card or eMMC
     Mmc,
+    /// Virtio disk or loop device
     Virtual,
+    /// Could not be determined
     Unknown,
 }
 
@@ -38,6 +127,8 @@ impl std::fmt::Display for ControllerType {
         match self {
             Self::Sata => write!(f, "SATA"),
             Self::Nvme => write!(f, "NVMe"),
+            Self::Sas => write!(f, "SAS"),
+            Self::Multipath => write!(f, "Multipath"),
             Self::Scsi => write!(f, "SCSI"),
             Self::Usb => write!(f, "USB"),
             Self::Mmc => write!(f, "MMC"),
@@ -47,6 +138,18 @@ impl std::fmt::Display for ControllerType {
     }
 }
 
+impl Partition {
+    /// Most stable /dev/disk/by-id path for this partition, if it has one
+    pub fn by_id_path(&self) -> Option<PathBuf> {
+        self.by_id_path_in(&SysRoot::default())
+    }
+
+    /// Most stable by-id path, looking up links under `root`
+    pub fn by_id_path_in(&self, root: &SysRoot) -> Option<PathBuf> {
+        stable_path_in(root, &self.path)
+    }
+}
+
 /// Represents a block device
 #[derive(Debug, Clone)]
 pub struct BlockDevice {
@@ -64,6 +167,8 @@ pub struct BlockDevice {
     pub logical_block_size: u32,
     /// Physical sector size
     pub physical_block_size: u32,
+    /// Preferred I/O size the device reports (0 if it reports none)
+    pub optimal_io_size: u32,
     /// Device model
     pub model: Option<String>,
     /// Device serial number
@@ -76,15 +181,47 @@ pub struct BlockDevice {
     pub readonly: bool,
     /// Is device rotational (HDD vs SSD)
     pub rotational: bool,
+    /// Mount point of the whole disk, if it holds a filesystem without a
+    /// partition table and is mounted
+    pub mountpoint: Option<PathBuf>,
     /// Partitions on this device
     pub partitions: Vec<Partition>,
+    /// Kernel names of the md arrays and device-mapper targets stacked on
+    /// the disk or its partitions, outermost first
+    pub holders: Vec<String>,
+    /// Whether the running system was booted from this disk
+    pub is_boot_medium: bool,
+    /// Device-mapper name (e.g. mpatha) of a dm device
+    pub dm_name: Option<String>,
+    /// Kernel names of the devices a dm device is built on; for a multipath
+    /// map, its paths
+    pub slaves: Vec<String>,
+    /// SMART health, read on first use by [`probe_smart`](Self::probe_smart)
+    pub(crate) smart: OnceLock<Option<SmartStatus>>,
 }
 
 impl BlockDevice {
     /// Create a new BlockDevice from a device name (e.g., "sda")
     pub fn from_name(name: &str) -> Result<Self> {
+        Self::from_name_in(&SysRoot::default(), name)
+    }
+
+    /// Create a BlockDevice from a device name, reading sysfs under `root`
+    pub fn from_name_in(root: &SysRoot, name: &str) -> Result<Self> {
+        Self::from_name_with_mounts(root, name, &read_mounts(root))
+    }
+
+    /// Create a BlockDevice using an already-read mount table
+    ///
+    /// Lets a scan over many devices read /proc/mounts once instead of once
+    /// per device.
+    pub(crate) fn from_name_with_mounts(
+        root: &SysRoot,
+        name: &str,
+        mounts: &[(PathBuf, PathBuf)],
+    ) -> Result<Self> {
         let path = PathBuf::from(format!("/dev/{}", name));
-        let sys_path = PathBuf::from(format!("/sys/block/{}", name));
+        let sys_path = root.sys_block(name);
 
         if !sys_path.exists() {
             return Err(InstallerError::DeviceNotFound(path));
@@ -104,6 +241,10 @@ impl BlockDevice {
         let physical_block_size = Self::read_sys_value(&sys_path, "queue/physical_block_size")?
             .parse::<u32>()
             .map_err(|e| InstallerError::ParseError(e.to_string()))?;
+        let optimal_io_size = Self::read_sys_value(&sys_path, "queue/optimal_io_size")
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(0);
 
         // Read device properties
         let removable =
@@ -114,16 +255,51 @@ impl BlockDevice {
             .unwrap_or_else(|_| "1".to_string())
             == "1";
 
-        // Try to read model, vendor, serial (may not exist for all devices)
-        let model = Self::read_sys_value(&sys_path, "device/model").ok();
-        let vendor = Self::read_sys_value(&sys_path, "device/vendor").ok();
-        let serial = Self::read_sys_value(&sys_path, "device/serial").ok();
+        // Device-mapper devices name what they're built on
+        let dm_name = Self::read_sys_value(&sys_path, "dm/name").ok();
+        let multipath =
+            Self::read_sys_value(&sys_path, "dm/uuid").is_ok_and(|uuid| uuid.starts_with("mpath-"));
+        let mut slaves: Vec<String> = fs::read_dir(sys_path.join("slaves"))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        slaves.sort();
+
+        // Try to read model, vendor, serial (may not exist for all devices);
+        // a multipath map has them from its paths
+        let identity = match slaves.first() {
+            Some(path) if multipath => root.sys_block(path),
+            _ => sys_path.clone(),
+        };
+        let model = Self::read_sys_value(&identity, "device/model").ok();
+        let vendor = Self::read_sys_value(&identity, "device/vendor").ok();
+        let serial = Self::read_sys_value(&identity, "device/serial").ok();
 
         // Determine controller type
-        let controller_type = Self::detect_controller_type(name);
+        let controller_type = if multipath {
+            ControllerType::Multipath
+        } else {
+            Self::detect_controller_type(&sys_path, name)
+        };
 
         // Discover partitions
-        let partitions = Self::discover_partitions(&sys_path, name)?;
+        let partitions = Self::discover_partitions(&sys_path, name, mounts)?;
+        let mountpoint = mount_target(mounts, &path);
+        let partition_sys_paths: Vec<PathBuf> = partitions
+            .iter()
+            .filter_map(|p| Some(sys_path.join(p.path.file_name()?)))
+            .collect();
+        let holders = read_holders(
+            root,
+            std::iter::once(sys_path.as_path())
+                .chain(partition_sys_paths.iter().map(PathBuf::as_path)),
+        );
+
+        let is_boot_medium = boot_medium_disks(root, mounts).iter().any(|d| d == name);
 
         Ok(Self {
             name: name.to_string(),
@@ -133,32 +309,96 @@ impl BlockDevice {
             size,
             logical_block_size,
             physical_block_size,
+            optimal_io_size,
             model,
             serial,
             vendor,
             removable,
             readonly,
             rotational,
+            mountpoint,
             partitions,
+            holders,
+            is_boot_medium,
+            dm_name,
+            slaves,
+            smart: OnceLock::new(),
         })
     }
 
+    /// SMART health of the device, queried with `smartctl` the first time
+    ///
+    /// `None` if the device doesn't report SMART data or nothing was run.
+    pub fn probe_smart(&self, executor: &dyn CommandExecutor) -> Result<Option<&SmartStatus>> {
+        if let Some(status) = self.smart.get() {
+            return Ok(status.as_ref());
+        }
+        let status = smart::probe(executor, &self.path)?;
+        Ok(self.smart.get_or_init(|| status).as_ref())
+    }
+
     /// Read a value from sysfs
-    fn read_sys_value(sys_path: &PathBuf, attr: &str) -> Result<String> {
+    fn read_sys_value(sys_path: &Path, attr: &str) -> Result<String> {
         let path = sys_path.join(attr);
         fs::read_to_string(&path)
             .map(|s| s.trim().to_string())
-            .map_err(|e| InstallerError::Io(e))
+            .map_err(InstallerError::Io)
     }
 
-    /// Detect controller type from device name
-    fn detect_controller_type(name: &str) -> ControllerType {
+    /// Detect how the device is attached
+    ///
+    /// Asks `device/transport` first, then walks the `device` link into
+    /// /sys/devices: a `usbN` hop means USB, `ataN` SATA, and SAS expanders
+    /// (`port-`, `end_device-`) or a SAS HBA driver on the SCSI host mean
+    /// SAS. Falls back to the kernel name.
+    fn detect_controller_type(sys_path: &Path, name: &str) -> ControllerType {
+        let device = sys_path.join("device");
+        if let Ok(transport) = Self::read_sys_value(sys_path, "device/transport") {
+            match transport.as_str() {
+                "usb" => return ControllerType::Usb,
+                "sata" | "ata" => return ControllerType::Sata,
+                "sas" => return ControllerType::Sas,
+                "pcie" | "tcp" | "rdma" | "fc" | "loop" if name.starts_with("nvme") => {
+                    return ControllerType::Nvme
+                }
+                _ => {}
+            }
+        }
+
+        if let Ok(target) = fs::canonicalize(&device) {
+            let hops: Vec<String> = target
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let is_hop = |prefix: &str| {
+                hops.iter().any(|hop| {
+                    hop.strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+                })
+            };
+            if is_hop("usb") {
+                return ControllerType::Usb;
+            }
+            if is_hop("ata") {
+                return ControllerType::Sata;
+            }
+            if hops
+                .iter()
+                .any(|hop| hop.starts_with("port-") || hop.starts_with("end_device-"))
+                || Self::sas_host(&target)
+            {
+                return ControllerType::Sas;
+            }
+            if is_hop("virtio") {
+                return ControllerType::Virtual;
+            }
+        }
+
         if name.starts_with("nvme") {
             ControllerType::Nvme
         } else if name.starts_with("sd") {
-            // Could be SATA, SCSI, or USB - need more investigation
-            // For now, default to SATA
-            ControllerType::Sata
+            // A SCSI disk with nothing more specific on the way
+            ControllerType::Scsi
         } else if name.starts_with("mmcblk") {
             ControllerType::Mmc
         } else if name.starts_with("vd") || name.starts_with("loop") {
@@ -168,8 +408,25 @@ impl BlockDevice {
         }
     }
 
+    /// Whether the SCSI host `device` hangs off is driven by a SAS HBA driver
+    /// (mpt3sas, megaraid_sas, ...)
+    fn sas_host(device: &Path) -> bool {
+        device.ancestors().any(|dir| {
+            let Some(host) = dir.file_name().and_then(OsStr::to_str) else {
+                return false;
+            };
+            host.starts_with("host")
+                && Self::read_sys_value(dir, &format!("scsi_host/{}/proc_name", host))
+                    .is_ok_and(|driver| driver.contains("sas"))
+        })
+    }
+
     /// Discover partitions on this device
-    fn discover_partitions(sys_path: &PathBuf, device_name: &str) -> Result<Vec<Partition>> {
+    fn discover_partitions(
+        sys_path: &Path,
+        device_name: &str,
+        mounts: &[(PathBuf, PathBuf)],
+    ) -> Result<Vec<Partition>> {
         let mut partitions = Vec::new();
 
         // Read partition entries from sysfs
@@ -181,8 +438,8 @@ impl BlockDevice {
                 // Check if this is a partition (starts with device name + number)
                 if name_str.starts_with(device_name) && name_str.len() > device_name.len() {
                     let suffix = &name_str[device_name.len()..];
-                    // For NVMe, partitions are like nvme0n1p1, for others like sda1
-                    let part_num_str = if device_name.starts_with("nvme") {
+                    // For NVMe and MMC, partitions are like nvme0n1p1, for others like sda1
+                    let part_num_str = if device_name.ends_with(|c: char| c.is_ascii_digit()) {
                         suffix.strip_prefix('p').unwrap_or(suffix)
                     } else {
                         suffix
@@ -199,12 +456,16 @@ impl BlockDevice {
                             .unwrap_or(0)
                             * 512;
 
+                        let mountpoint = mount_target(mounts, &part_path);
+
                         partitions.push(Partition {
                             path: part_path,
                             number: part_num,
                             size,
-                            fstype: None,     // Would need blkid to determine
-                            mountpoint: None, // Would need to parse /proc/mounts
+                            fstype: None,
+                            label: None,
+                            uuid: None,
+                            mountpoint,
                         });
                     }
                 }
@@ -216,29 +477,131 @@ impl BlockDevice {
         Ok(partitions)
     }
 
-    /// Check if device is currently mounted
-    pub fn is_mounted(&self) -> Result<bool> {
-        let mounts = fs::read_to_string("/proc/mounts")?;
-        Ok(mounts.lines().any(|line| {
-            line.starts_with(&format!("{} ", self.path.display()))
-                || self
-                    .partitions
-                    .iter()
-                    .any(|p| line.starts_with(&format!("{} ", p.path.display())))
-        }))
+    /// Most stable /dev/disk/by-id path for this disk, if it has one
+    pub fn by_id_path(&self) -> Option<PathBuf> {
+        self.by_id_path_in(&SysRoot::default())
+    }
+
+    /// Most stable by-id path, looking up links under `root`
+    pub fn by_id_path_in(&self, root: &SysRoot) -> Option<PathBuf> {
+        stable_path_in(root, &self.path)
+    }
+
+    /// Fill in partition filesystem type, label and UUID with `blkid`
+    ///
+    /// Partitions blkid finds no signature on are left untouched.
+    pub fn probe_filesystems(&mut self, executor: &dyn CommandExecutor) -> Result<()> {
+        if self.partitions.is_empty() {
+            return Ok(());
+        }
+
+        let mut cmd = Command::new("blkid");
+        cmd.args(["-o", "export"]);
+        for partition in &self.partitions {
+            cmd.arg(&partition.path);
+        }
+
+        let output = match executor.execute(&mut cmd) {
+            Ok(result) => match result.stdout_string() {
+                Some(output) => output,
+                // Skipped in dry-run mode: nothing is known about the partitions
+                None => return Ok(()),
+            },
+            // blkid exits 2 when none of the devices has a signature
+            Err(InstallerError::CommandFailed { code: 2, .. }) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let mut probed = parse_blkid_export(&output);
+        for partition in &mut self.partitions {
+            if let Some(mut tags) = probed.remove(&partition.path) {
+                partition.fstype = tags.remove("TYPE");
+                partition.label = tags.remove("LABEL");
+                partition.uuid = tags.remove("UUID");
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if the disk or any of its partitions is mounted
+    ///
+    /// Uses the mount points read when the device was created, so mounts made
+    /// through /dev/disk/by-* links are recognised too.
+    pub fn is_mounted(&self) -> bool {
+        self.mountpoint.is_some() || self.partitions.iter().any(|p| p.mountpoint.is_some())
     }
 
-    /// Check if device is part of a ZFS pool
-    pub fn is_in_zfs_pool(&self) -> Result<bool> {
-        // Check if zpool.cache exists and contains this device
-        // This is a simplified check; a full implementation would parse zpool.cache
-        // or run `zpool status -v` and check output
-        // For now, we'll just check if any partition has zfs_member filesystem
-        Ok(false) // TODO: Implement proper ZFS check
+    /// Mount points of the disk and its partitions
+    pub fn mountpoints(&self) -> Vec<&Path> {
+        self.mountpoint
+            .iter()
+            .chain(self.partitions.iter().filter_map(|p| p.mountpoint.as_ref()))
+            .map(PathBuf::as_path)
+            .collect()
     }
 
-    /// Check if device is suitable for installation
-    pub fn is_suitable(&self) -> Result<()> {
+    /// Mount points a desktop automounter made, under /media or /run/media
+    pub fn automounts(&self) -> Vec<&Path> {
+        self.mountpoints()
+            .into_iter()
+            .filter(|m| is_automount(m))
+            .collect()
+    }
+
+    /// Name of the ZFS pool this disk belongs to, if any
+    pub fn zfs_pool(&self, members: &[PoolMember]) -> Option<String> {
+        self.zfs_pool_in(&SysRoot::default(), members)
+    }
+
+    /// Name of the ZFS pool this disk belongs to, resolving links under `root`
+    ///
+    /// `members` (from `zpool status -P`) covers imported pools, whose vdevs
+    /// may be named through /dev/disk/by-* links. Exported pools only show up
+    /// as a `zfs_member` partition found by
+    /// [`probe_filesystems`](Self::probe_filesystems), labelled with the pool
+    /// name.
+    pub fn zfs_pool_in(&self, root: &SysRoot, members: &[PoolMember]) -> Option<String> {
+        let imported = members.iter().find(|member| {
+            let device = resolve_dev_path(root, &member.device);
+            device == self.path || self.partitions.iter().any(|p| p.path == device)
+        });
+        if let Some(member) = imported {
+            return Some(member.pool.clone());
+        }
+
+        self.partitions
+            .iter()
+            .find(|p| p.fstype.as_deref() == Some("zfs_member"))
+            .map(|p| p.label.clone().unwrap_or_else(|| "unknown".to_string()))
+    }
+
+    /// Partitions carrying an inactive md, LVM or LUKS signature, described
+    ///
+    /// Found by [`probe_filesystems`](Self::probe_filesystems). Wiping
+    /// destroys them, but they don't have to be stopped first; active ones
+    /// are in [`holders`](Self::holders).
+    pub fn stacked_members(&self) -> Vec<String> {
+        self.partitions
+            .iter()
+            .filter_map(|p| {
+                let kind = match p.fstype.as_deref()? {
+                    "linux_raid_member" => "md RAID member",
+                    "LVM2_member" => "LVM physical volume",
+                    "crypto_LUKS" => "LUKS container",
+                    _ => return None,
+                };
+                Some(format!("{} ({})", p.path.display(), kind))
+            })
+            .collect()
+    }
+
+    /// Check the device can be installed to under `policy`
+    ///
+    /// Read-only and mounted devices, and devices smaller than
+    /// `policy.min_size`, are refused whatever the policy: the layout
+    /// couldn't be written to them. Removable devices pass with
+    /// `allow_removable` or `force`.
+    pub fn is_suitable(&self, policy: &SuitabilityPolicy) -> Result<()> {
         if self.readonly {
             return Err(InstallerError::InvalidDevice {
                 path: self.path.clone(),
@@ -246,22 +609,29 @@ impl BlockDevice {
             });
         }
 
-        if self.removable {
+        if self.removable && !policy.allows_removable() {
             return Err(InstallerError::InvalidDevice {
                 path: self.path.clone(),
-                reason: "Device is removable (use --force to override)".to_string(),
+                reason: format!(
+                    "{} device is removable (use --allow-removable or --force to override)",
+                    self.controller_type
+                ),
             });
         }
 
-        if self.size < 8 * 1024 * 1024 * 1024 {
-            // 8GB minimum
+        if self.size < policy.min_size {
             return Err(InstallerError::InvalidDevice {
                 path: self.path.clone(),
-                reason: format!("Device too small ({} bytes, need at least 8GB)", self.size),
+                reason: format!(
+                    "Device is too small ({}, need at least {})",
+                    self.size_human(),
+                    bytesize::ByteSize(policy.min_size)
+                ),
             });
         }
 
-        if self.is_mounted()? {
+        // Automounted filesystems are unmounted before the disk is wiped
+        if self.mountpoints().iter().any(|m| !is_automount(m)) {
             return Err(InstallerError::DeviceInUse(self.path.clone()));
         }
 
@@ -273,14 +643,52 @@ impl BlockDevice {
         bytesize::ByteSize(self.size).to_string()
     }
 
-    /// Get recommended ashift value based on physical block size
-    pub fn recommended_ashift(&self) -> u8 {
-        match self.physical_block_size {
-            512 => 9,
-            4096 => 12,
-            8192 => 13,
-            _ => 12, // Default to 4K
+    /// Sector size the drive really writes in
+    ///
+    /// The physical block size, unless the optimal I/O size gives away a
+    /// bigger page: some 512e drives report 512-byte physical sectors but
+    /// 4K or 8K optimal I/O. Unusual values are warned about and ignored.
+    pub fn effective_sector_size(&self) -> u32 {
+        let mut sector = self.physical_block_size.max(self.logical_block_size);
+        if !sector.is_power_of_two() {
+            log::warn!(
+                "{}: unusual physical sector size {} bytes, assuming 4096",
+                self.path.display(),
+                sector
+            );
+            sector = 4096;
+        }
+
+        let optimal = self.optimal_io_size;
+        if optimal > sector {
+            if !optimal.is_power_of_two() {
+                log::warn!(
+                    "{}: ignoring unusual optimal I/O size {} bytes",
+                    self.path.display(),
+                    optimal
+                );
+            } else if optimal <= MAX_OPTIMAL_IO_SECTOR {
+                sector = optimal;
+            }
         }
+        sector
+    }
+
+    /// Get recommended ashift value based on the effective sector size
+    pub fn recommended_ashift(&self) -> u8 {
+        // ZFS accepts ashift 9 to 16
+        (self.effective_sector_size().trailing_zeros() as u8).clamp(9, 16)
+    }
+
+    /// NVMe drive running 512-byte sectors that could be reformatted to 4K
+    pub fn offers_4k_format(&self) -> bool {
+        self.controller_type == ControllerType::Nvme
+            && self.logical_block_size == 512
+            && self.model.as_deref().is_some_and(|model| {
+                KNOWN_4K_NVME_MODELS
+                    .iter()
+                    .any(|known| model.trim().starts_with(known))
+            })
     }
 
     /// Get a display name for the device
@@ -290,32 +698,347 @@ impl BlockDevice {
             .as_ref()
             .map(|m| format!(" ({})", m.trim()))
             .unwrap_or_default();
-        format!("{}{} - {}", self.name, model_info, self.size_human())
+        let paths = match (&self.dm_name, self.controller_type) {
+            (Some(map), ControllerType::Multipath) => {
+                format!(" [{}, {} paths]", map, self.slaves.len())
+            }
+            _ => String::new(),
+        };
+        format!(
+            "{}{}{} - {}",
+            self.name,
+            model_info,
+            paths,
+            self.size_human()
+        )
+    }
+
+    /// Everything known about the device, one `label: value` line at a time
+    ///
+    /// `pool` is the result of [`zfs_pool`](Self::zfs_pool). Partitions are
+    /// listed last, one per line.
+    pub fn details(&self, pool: Option<&str>) -> Vec<String> {
+        let or_dash =
+            |value: &Option<String>| value.as_deref().map(str::trim).unwrap_or("-").to_string();
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        let mountpoint = |value: &Option<PathBuf>| {
+            value
+                .as_ref()
+                .map_or("-".to_string(), |m| m.display().to_string())
+        };
+
+        let mut lines = vec![
+            format!("Device:       {}", self.path.display()),
+            format!("Model:        {}", or_dash(&self.model)),
+            format!("Serial:       {}", or_dash(&self.serial)),
+            format!("Vendor:       {}", or_dash(&self.vendor)),
+            format!("Size:         {} ({} bytes)", self.size_human(), self.size),
+            format!("Controller:   {}", self.controller_type),
+            format!(
+                "Sectors:      {} logical / {} physical",
+                self.logical_block_size, self.physical_block_size
+            ),
+            format!(
+                "Optimal I/O:  {}",
+                match self.optimal_io_size {
+                    0 => "-".to_string(),
+                    size => size.to_string(),
+                }
+            ),
+            format!("Rotational:   {}", yes_no(self.rotational)),
+            format!("Removable:    {}", yes_no(self.removable)),
+            format!("Read-only:    {}", yes_no(self.readonly)),
+            format!("Boot medium:  {}", yes_no(self.is_boot_medium)),
+            format!("Mounted:      {}", mountpoint(&self.mountpoint)),
+            format!("ZFS pool:     {}", pool.unwrap_or("-")),
+            format!("Partitions:   {}", self.partitions.len()),
+        ];
+        for partition in &self.partitions {
+            lines.push(format!(
+                "  {:<14} {:>10}  {:<10} {:<12} {}",
+                partition.path.display(),
+                bytesize::ByteSize(partition.size).to_string(),
+                or_dash(&partition.fstype),
+                or_dash(&partition.label),
+                mountpoint(&partition.mountpoint)
+            ));
+        }
+        lines
+    }
+}
+
+/// Read (source, target) pairs from the mount table under `root`
+///
+/// Sources under /dev are resolved through symlinks, so a filesystem mounted
+/// as `/dev/disk/by-uuid/...` is reported against its kernel device node.
+pub(crate) fn read_mounts(root: &SysRoot) -> Vec<(PathBuf, PathBuf)> {
+    let Ok(mounts) = fs::read_to_string(root.proc_mounts()) else {
+        return Vec::new();
+    };
+
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let source = fields.next()?.replace("\\040", " ");
+            let target = fields.next()?.replace("\\040", " ");
+            Some((
+                resolve_dev_path(root, Path::new(&source)),
+                PathBuf::from(target),
+            ))
+        })
+        .collect()
+}
+
+/// Kernel names of the disks the running system was booted from
+///
+/// Looks at the mount points in [`BOOT_MEDIUM_MOUNTS`]. A mount whose source
+/// isn't a known block device (`/dev/root`, say) is traced through its
+/// device number instead.
+pub(crate) fn boot_medium_disks(root: &SysRoot, mounts: &[(PathBuf, PathBuf)]) -> Vec<String> {
+    let mut disks = Vec::new();
+    for mountpoint in BOOT_MEDIUM_MOUNTS {
+        let Some((source, _)) = mounts
+            .iter()
+            .find(|(_, target)| target == Path::new(mountpoint))
+        else {
+            continue;
+        };
+        let name = source
+            .strip_prefix("/dev")
+            .ok()
+            .and_then(|name| name.to_str())
+            .filter(|name| root.class_block().join(name).exists())
+            .map(str::to_string)
+            .or_else(|| block_device_of(root, Path::new(mountpoint)));
+        if let Some(disk) = name.and_then(|name| disk_of(root, &name)) {
+            if !disks.contains(&disk) {
+                disks.push(disk);
+            }
+        }
     }
+    disks
+}
+
+/// Whether `mountpoint` is where an automounter puts removable filesystems
+fn is_automount(mountpoint: &Path) -> bool {
+    AUTOMOUNT_DIRS.iter().any(|dir| mountpoint.starts_with(dir))
+}
+
+/// Kernel name of the block device holding the filesystem at `mountpoint`
+fn block_device_of(root: &SysRoot, mountpoint: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = fs::metadata(root.join(mountpoint)).ok()?.dev();
+    let link = root.join(format!(
+        "/sys/dev/block/{}:{}",
+        nix::sys::stat::major(dev),
+        nix::sys::stat::minor(dev)
+    ));
+    let target = fs::read_link(link).ok()?;
+    Some(target.file_name()?.to_str()?.to_string())
+}
+
+/// Kernel name of the disk `name` is, or is a partition of
+fn disk_of(root: &SysRoot, name: &str) -> Option<String> {
+    let dir = fs::canonicalize(root.class_block().join(name)).ok()?;
+    let disk = if dir.join("partition").exists() {
+        dir.parent()?
+    } else {
+        &dir
+    };
+    Some(disk.file_name()?.to_str()?.to_string())
+}
+
+/// Mount point of `device` in an already-read mount table
+fn mount_target(mounts: &[(PathBuf, PathBuf)], device: &Path) -> Option<PathBuf> {
+    mounts
+        .iter()
+        .find(|(source, _)| source == device)
+        .map(|(_, target)| target.clone())
+}
+
+/// Follow symlinks for a /dev path, looking them up under `root`
+///
+/// Returns a live-system path. Links are resolved lexically, so this works on
+/// a fixture tree whose link targets don't exist. Anything that isn't an
+/// absolute /dev path, or that isn't a link, is returned unchanged.
+pub(crate) fn resolve_dev_path(root: &SysRoot, path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_LINK_DEPTH {
+        if !path.starts_with("/dev") {
+            break;
+        }
+        let Ok(target) = fs::read_link(root.join(&path)) else {
+            break;
+        };
+        let parent = path.parent().unwrap_or(Path::new("/"));
+        path = normalize(&parent.join(target));
+    }
+    path
+}
+
+/// Remove `.` and `..` components without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Parse `blkid -o export` output into tags keyed by device path
+///
+/// Each device is a block of `KEY=value` lines, separated by blank lines and
+/// starting with `DEVNAME=`.
+pub fn parse_blkid_export(output: &str) -> HashMap<PathBuf, HashMap<String, String>> {
+    let mut devices = HashMap::new();
+    for block in output.split("\n\n") {
+        let mut tags: HashMap<String, String> = block
+            .lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        if let Some(devname) = tags.remove("DEVNAME") {
+            devices.insert(PathBuf::from(devname), tags);
+        }
+    }
+    devices
+}
+
+/// Preference of a by-id link name, lower is more stable
+///
+/// World Wide Names are assigned by the manufacturer and survive controller
+/// changes; bus-specific IDs (model and serial) come next; anything else
+/// (usb-, scsi-, dm-, ...) is a last resort.
+fn by_id_rank(name: &str) -> u8 {
+    if name.starts_with("wwn-") {
+        0
+    } else if name.starts_with("ata-") || name.starts_with("nvme-") {
+        1
+    } else {
+        2
+    }
+}
+
+/// Names of the links in `dir` that point at the device node `dev_name`
+fn links_to(dir: &Path, dev_name: &OsStr) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| {
+            fs::read_link(entry.path()).is_ok_and(|target| target.file_name() == Some(dev_name))
+        })
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+/// Most stable persistent path for a kernel device path (`/dev/sda3`)
+///
+/// Prefers /dev/disk/by-id links (WWN, then ATA/NVMe model and serial, then
+/// others) and falls back to /dev/disk/by-partuuid. Returns `None` when the
+/// device has no persistent links, e.g. virtio disks in some VMs. Returned
+/// paths are live-system paths, not prefixed with `root`.
+pub fn stable_path_in(root: &SysRoot, device: &Path) -> Option<PathBuf> {
+    let dev_name = device.file_name()?;
+
+    let by_id = links_to(&root.dev_by_id(), dev_name)
+        .into_iter()
+        .min_by(|a, b| (by_id_rank(a), a).cmp(&(by_id_rank(b), b)));
+    if let Some(name) = by_id {
+        return Some(Path::new(BY_ID_DIR).join(name));
+    }
+
+    links_to(&root.dev_by_partuuid(), dev_name)
+        .into_iter()
+        .min()
+        .map(|name| Path::new(BY_PARTUUID_DIR).join(name))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exec::RecordingExecutor;
 
     #[test]
     fn test_controller_type_detection() {
-        assert_eq!(
-            BlockDevice::detect_controller_type("sda"),
-            ControllerType::Sata
+        // Without sysfs to go on, only the name is left
+        let detect =
+            |name: &str| BlockDevice::detect_controller_type(Path::new("/nonexistent"), name);
+        assert_eq!(detect("sda"), ControllerType::Scsi);
+        assert_eq!(detect("nvme0n1"), ControllerType::Nvme);
+        assert_eq!(detect("vda"), ControllerType::Virtual);
+        assert_eq!(detect("mmcblk0"), ControllerType::Mmc);
+
+        let root = SysRoot::fixture();
+        let detect = |name: &str| {
+            BlockDevice::from_name_in(&root, name)
+                .unwrap()
+                .controller_type
+        };
+        assert_eq!(detect("sda"), ControllerType::Sata);
+        assert_eq!(detect("sdb"), ControllerType::Usb);
+        assert_eq!(detect("nvme0n1"), ControllerType::Nvme);
+    }
+
+    #[test]
+    fn test_controller_type_from_device_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let sys = dir.path().join("sys");
+        let disk = |name: &str, device: &str| {
+            let target = sys.join("devices").join(device);
+            fs::create_dir_all(&target).unwrap();
+            let block = sys.join("block").join(name);
+            fs::create_dir_all(&block).unwrap();
+            std::os::unix::fs::symlink(&target, block.join("device")).unwrap();
+            target
+        };
+
+        disk(
+            "sda",
+            "pci0000:00/0000:00:14.0/usb2/2-1/2-1:1.0/host4/target4:0:0/4:0:0:0",
         );
-        assert_eq!(
-            BlockDevice::detect_controller_type("nvme0n1"),
-            ControllerType::Nvme
+        disk(
+            "sdb",
+            "pci0000:00/0000:00:17.0/ata3/host2/target2:0:0/2:0:0:0",
         );
-        assert_eq!(
-            BlockDevice::detect_controller_type("vda"),
-            ControllerType::Virtual
+        disk(
+            "sdc",
+            "pci0000:00/0000:03:00.0/host0/port-0:0/expander-0:0/port-0:0:4/end_device-0:0:4/target0:0:4/0:0:4:0",
         );
-        assert_eq!(
-            BlockDevice::detect_controller_type("mmcblk0"),
-            ControllerType::Mmc
+        // A SAS HBA without an expander on the way
+        disk("sdd", "pci0000:00/0000:04:00.0/host1/target1:0:0/1:0:0:0");
+        let proc_name = sys.join("devices/pci0000:00/0000:04:00.0/host1/scsi_host/host1");
+        fs::create_dir_all(&proc_name).unwrap();
+        fs::write(proc_name.join("proc_name"), "mpt3sas\n").unwrap();
+        // A plain SCSI disk (e.g. iSCSI)
+        disk("sde", "platform/host5/session1/target5:0:0/5:0:0:0");
+        disk(
+            "sdf",
+            "pci0000:00/0000:00:05.0/virtio2/host0/target0:0:0/0:0:0:0",
         );
+        // The transport file wins where there is one
+        let target = disk("sdg", "pci0000:00/0000:05:00.0/host6/target6:0:0/6:0:0:0");
+        fs::write(target.join("transport"), "usb\n").unwrap();
+
+        let root = SysRoot::new(dir.path());
+        let detect = |name: &str| BlockDevice::detect_controller_type(&root.sys_block(name), name);
+        assert_eq!(detect("sda"), ControllerType::Usb);
+        assert_eq!(detect("sdb"), ControllerType::Sata);
+        assert_eq!(detect("sdc"), ControllerType::Sas);
+        assert_eq!(detect("sdd"), ControllerType::Sas);
+        assert_eq!(detect("sde"), ControllerType::Scsi);
+        assert_eq!(detect("sdf"), ControllerType::Virtual);
+        assert_eq!(detect("sdg"), ControllerType::Usb);
     }
 
     #[test]
@@ -328,18 +1051,375 @@ mod tests {
             size: 1_000_000_000_000,
             logical_block_size: 512,
             physical_block_size: 4096,
+            optimal_io_size: 0,
             model: None,
             serial: None,
             vendor: None,
             removable: false,
             readonly: false,
             rotational: false,
+            mountpoint: None,
             partitions: Vec::new(),
+            holders: Vec::new(),
+            is_boot_medium: false,
+            dm_name: None,
+            slaves: Vec::new(),
+            smart: OnceLock::new(),
         };
 
         assert_eq!(device.recommended_ashift(), 12);
 
         device.physical_block_size = 512;
         assert_eq!(device.recommended_ashift(), 9);
+
+        device.physical_block_size = 8192;
+        assert_eq!(device.recommended_ashift(), 13);
+
+        // A 512e drive owning up to its 4K pages in the optimal I/O size
+        device.physical_block_size = 512;
+        device.optimal_io_size = 4096;
+        assert_eq!(device.recommended_ashift(), 12);
+
+        // Stripe widths and odd values aren't sectors
+        device.optimal_io_size = 1024 * 1024;
+        assert_eq!(device.recommended_ashift(), 9);
+        device.optimal_io_size = 33_553_920;
+        assert_eq!(device.recommended_ashift(), 9);
+        device.physical_block_size = 3000;
+        assert_eq!(device.recommended_ashift(), 12);
+    }
+
+    #[test]
+    fn test_boot_medium() {
+        // The fixture's root filesystem is on nvme0n1
+        let root = SysRoot::fixture();
+        assert!(
+            BlockDevice::from_name_in(&root, "nvme0n1")
+                .unwrap()
+                .is_boot_medium
+        );
+        assert!(
+            !BlockDevice::from_name_in(&root, "sdb")
+                .unwrap()
+                .is_boot_medium
+        );
+
+        // A live system: the stick carries the medium, / is an overlay
+        let mounts = vec![
+            (PathBuf::from("overlay"), PathBuf::from("/")),
+            (
+                PathBuf::from("/dev/sdb1"),
+                PathBuf::from("/run/live/medium"),
+            ),
+        ];
+        assert_eq!(boot_medium_disks(&root, &mounts), vec!["sdb"]);
+
+        // An installer CD mounts the whole disk
+        let mounts = vec![(PathBuf::from("/dev/sda"), PathBuf::from("/cdrom"))];
+        assert_eq!(boot_medium_disks(&root, &mounts), vec!["sda"]);
+    }
+
+    #[test]
+    fn test_offers_4k_format() {
+        let root = SysRoot::fixture();
+        let nvme = BlockDevice::from_name_in(&root, "nvme0n1").unwrap();
+        assert!(nvme.offers_4k_format());
+
+        let mut formatted = nvme.clone();
+        formatted.logical_block_size = 4096;
+        assert!(!formatted.offers_4k_format());
+
+        let mut unknown = nvme;
+        unknown.model = Some("Samsung SSD 980 PRO 1TB".to_string());
+        assert!(!unknown.offers_4k_format());
+
+        assert!(!BlockDevice::from_name_in(&root, "sda")
+            .unwrap()
+            .offers_4k_format());
+    }
+
+    #[test]
+    fn test_from_name_in_fixture() {
+        let device = BlockDevice::from_name_in(&SysRoot::fixture(), "sda").unwrap();
+
+        assert_eq!(device.path, PathBuf::from("/dev/sda"));
+        assert_eq!(device.size, 976_773_168 * 512);
+        assert_eq!(device.logical_block_size, 512);
+        assert_eq!(device.physical_block_size, 4096);
+        assert_eq!(device.model.as_deref(), Some("Samsung SSD 870 EVO 500GB"));
+        assert_eq!(device.vendor.as_deref(), Some("ATA"));
+        assert_eq!(device.serial.as_deref(), Some("S6PWNX0R123456A"));
+        assert!(!device.removable);
+        assert!(!device.rotational);
+        assert_eq!(device.recommended_ashift(), 12);
+
+        let numbers: Vec<u32> = device.partitions.iter().map(|p| p.number).collect();
+        assert_eq!(numbers, vec![1, 2]);
+        assert_eq!(device.partitions[0].path, PathBuf::from("/dev/sda1"));
+        assert_eq!(device.partitions[0].size, 1024 * 1024 * 1024);
+        assert!(device.partitions.iter().all(|p| p.mountpoint.is_none()));
+    }
+
+    #[test]
+    fn test_nvme_partitions_and_mounts() {
+        let root = SysRoot::fixture();
+        let device = BlockDevice::from_name_in(&root, "nvme0n1").unwrap();
+
+        assert_eq!(device.controller_type, ControllerType::Nvme);
+        assert_eq!(device.serial.as_deref(), Some("21042P801234"));
+
+        let parts: Vec<(u32, &Path)> = device
+            .partitions
+            .iter()
+            .map(|p| (p.number, p.path.as_path()))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                (1, Path::new("/dev/nvme0n1p1")),
+                (2, Path::new("/dev/nvme0n1p2"))
+            ]
+        );
+        assert_eq!(
+            device.partitions[0].mountpoint,
+            Some(PathBuf::from("/boot/efi"))
+        );
+        assert_eq!(device.partitions[1].mountpoint, Some(PathBuf::from("/")));
+
+        assert!(device.is_mounted());
+        assert!(matches!(
+            device.is_suitable(&policy()),
+            Err(InstallerError::DeviceInUse(_))
+        ));
+    }
+
+    #[test]
+    fn test_mount_through_by_uuid_link() {
+        let root = SysRoot::fixture();
+        let mmc = BlockDevice::from_name_in(&root, "mmcblk0").unwrap();
+
+        assert_eq!(
+            mmc.partitions[0].mountpoint,
+            Some(PathBuf::from("/media/sd card"))
+        );
+        assert!(mmc.is_mounted());
+        assert_eq!(mmc.automounts(), vec![Path::new("/media/sd card")]);
+        assert!(!BlockDevice::from_name_in(&root, "sda")
+            .unwrap()
+            .is_mounted());
+
+        // Automounts don't make a disk unsuitable; other mounts do
+        let mut stick = BlockDevice::from_name_in(&root, "sda").unwrap();
+        stick.partitions[0].mountpoint = Some(PathBuf::from("/run/media/user/STICK"));
+        stick.is_suitable(&policy()).unwrap();
+        stick.partitions[1].mountpoint = Some(PathBuf::from("/mnt/data"));
+        assert!(matches!(
+            stick.is_suitable(&policy()),
+            Err(InstallerError::DeviceInUse(_))
+        ));
+        assert_eq!(stick.mountpoints().len(), 2);
+    }
+
+    #[test]
+    fn test_probe_filesystems_with_blkid() {
+        let executor = RecordingExecutor::new();
+        executor.respond_program(
+            "blkid",
+            "DEVNAME=/dev/nvme0n1p1\nUUID=5D7E-0C41\nTYPE=vfat\nPARTUUID=5d7e0c41-01\n\n\
+             DEVNAME=/dev/nvme0n1p2\nLABEL=root\nUUID=0b6f1e7a-3c55-4a6e-9d0e-2f1d3c4b5a69\nTYPE=ext4\n",
+        );
+        let mut device = BlockDevice::from_name_in(&SysRoot::fixture(), "nvme0n1").unwrap();
+        device.probe_filesystems(&executor).unwrap();
+
+        assert_eq!(
+            executor.commands()[0].argv(),
+            vec!["blkid", "-o", "export", "/dev/nvme0n1p1", "/dev/nvme0n1p2"]
+        );
+        let esp = &device.partitions[0];
+        assert_eq!(esp.fstype.as_deref(), Some("vfat"));
+        assert_eq!(esp.uuid.as_deref(), Some("5D7E-0C41"));
+        assert_eq!(esp.label, None);
+        let root = &device.partitions[1];
+        assert_eq!(root.fstype.as_deref(), Some("ext4"));
+        assert_eq!(root.label.as_deref(), Some("root"));
+    }
+
+    #[test]
+    fn test_details_list_partitions() {
+        let executor = RecordingExecutor::new();
+        executor.respond_program(
+            "blkid",
+            "DEVNAME=/dev/nvme0n1p1\nUUID=5D7E-0C41\nTYPE=vfat\n\n\
+             DEVNAME=/dev/nvme0n1p2\nLABEL=root\nTYPE=ext4\n",
+        );
+        let mut device = BlockDevice::from_name_in(&SysRoot::fixture(), "nvme0n1").unwrap();
+        device.probe_filesystems(&executor).unwrap();
+
+        let details = device.details(Some("tank"));
+        assert!(details.contains(&"Serial:       21042P801234".to_string()));
+        assert!(details.contains(&"ZFS pool:     tank".to_string()));
+        assert!(details.contains(&"Partitions:   2".to_string()));
+        assert!(details.contains(&"Boot medium:  yes".to_string()));
+
+        let partitions = &details[details.len() - 2..];
+        assert!(partitions[0].starts_with("  /dev/nvme0n1p1"));
+        assert!(partitions[0].contains("vfat") && partitions[0].ends_with("/boot/efi"));
+        assert!(partitions[1].contains("ext4") && partitions[1].contains("root"));
+    }
+
+    #[test]
+    fn test_probe_without_signatures() {
+        let executor = RecordingExecutor::new();
+        executor.fail_program("blkid", 2, "");
+        let mut device = BlockDevice::from_name_in(&SysRoot::fixture(), "sda").unwrap();
+        device.probe_filesystems(&executor).unwrap();
+        assert!(device.partitions.iter().all(|p| p.fstype.is_none()));
+    }
+
+    #[test]
+    fn test_probe_in_dry_run_learns_nothing() {
+        let mut device = BlockDevice::from_name_in(&SysRoot::fixture(), "nvme0n1").unwrap();
+        device
+            .probe_filesystems(&crate::exec::SystemExecutor::new(true))
+            .unwrap();
+        assert!(device
+            .partitions
+            .iter()
+            .all(|p| p.fstype.is_none() && p.uuid.is_none()));
+    }
+
+    #[test]
+    fn test_zfs_pool_membership() {
+        let root = SysRoot::fixture();
+        let mut sda = BlockDevice::from_name_in(&root, "sda").unwrap();
+        assert_eq!(sda.zfs_pool_in(&root, &[]), None);
+
+        // Imported pool named through a by-id link to a partition
+        let members = [PoolMember {
+            pool: "tank".to_string(),
+            device: PathBuf::from("/dev/disk/by-id/wwn-0x5002538f42a1b2c3-part3"),
+        }];
+        let mut with_part3 = sda.clone();
+        with_part3.partitions.push(Partition {
+            path: PathBuf::from("/dev/sda3"),
+            number: 3,
+            size: 0,
+            fstype: None,
+            label: None,
+            uuid: None,
+            mountpoint: None,
+        });
+        assert_eq!(
+            with_part3.zfs_pool_in(&root, &members).as_deref(),
+            Some("tank")
+        );
+        assert_eq!(sda.zfs_pool_in(&root, &members), None);
+
+        // Exported pool found by blkid
+        sda.partitions[1].fstype = Some("zfs_member".to_string());
+        sda.partitions[1].label = Some("oldpool".to_string());
+        assert_eq!(sda.zfs_pool_in(&root, &[]).as_deref(), Some("oldpool"));
+    }
+
+    #[test]
+    fn test_stable_paths() {
+        let root = SysRoot::fixture();
+        let sda = BlockDevice::from_name_in(&root, "sda").unwrap();
+
+        // WWN wins over the ATA model/serial link
+        assert_eq!(
+            sda.by_id_path_in(&root),
+            Some(PathBuf::from("/dev/disk/by-id/wwn-0x5002538f42a1b2c3"))
+        );
+        assert_eq!(
+            stable_path_in(&root, Path::new("/dev/sda3")),
+            Some(PathBuf::from(
+                "/dev/disk/by-id/wwn-0x5002538f42a1b2c3-part3"
+            ))
+        );
+        assert_eq!(
+            stable_path_in(&root, Path::new("/dev/sdb")),
+            Some(PathBuf::from(
+                "/dev/disk/by-id/usb-SanDisk_Ultra_Fit_4C530001-0:0"
+            ))
+        );
+
+        // No by-id link: fall back to the partition UUID, then to nothing
+        let mmc = BlockDevice::from_name_in(&root, "mmcblk0").unwrap();
+        assert_eq!(
+            mmc.partitions[0].by_id_path_in(&root),
+            Some(PathBuf::from("/dev/disk/by-partuuid/5d7e0c41-01"))
+        );
+        let nvme = BlockDevice::from_name_in(&root, "nvme0n1").unwrap();
+        assert_eq!(nvme.by_id_path_in(&root), None);
+        assert_eq!(nvme.partitions[0].by_id_path_in(&root), None);
+    }
+
+    /// The policy of a default whole-disk install
+    fn policy() -> SuitabilityPolicy {
+        crate::config::Config::default().suitability_policy()
+    }
+
+    #[test]
+    fn test_suitability_policy_overrides() {
+        let root = SysRoot::fixture();
+        let usb = BlockDevice::from_name_in(&root, "sdb").unwrap();
+        let fixed = BlockDevice::from_name_in(&root, "sda").unwrap();
+
+        for (allow_removable, force, accepted, forced) in [
+            (false, false, false, false),
+            (true, false, true, false),
+            (false, true, true, true),
+            (true, true, true, false),
+        ] {
+            let policy = SuitabilityPolicy {
+                min_size: 0,
+                allow_removable,
+                force,
+            };
+            assert_eq!(usb.is_suitable(&policy).is_ok(), accepted, "{policy:?}");
+            assert_eq!(policy.forces(&usb), forced, "{policy:?}");
+            fixed.is_suitable(&policy).unwrap();
+            assert!(!policy.forces(&fixed));
+        }
+
+        // Neither flag makes a device large enough, or writable
+        let small = SuitabilityPolicy {
+            min_size: usb.size + 1,
+            allow_removable: true,
+            force: true,
+        };
+        let err = usb.is_suitable(&small).unwrap_err();
+        assert!(err.to_string().contains("too small"), "{err}");
+        let mut readonly = fixed.clone();
+        readonly.readonly = true;
+        assert!(readonly.is_suitable(&small).is_err());
+    }
+
+    #[test]
+    fn test_removable_and_missing_devices() {
+        let root = SysRoot::fixture();
+
+        let usb = BlockDevice::from_name_in(&root, "sdb").unwrap();
+        assert!(usb.removable);
+        assert_eq!(usb.vendor.as_deref(), Some("SanDisk"));
+        assert!(matches!(
+            usb.is_suitable(&policy()),
+            Err(InstallerError::InvalidDevice { .. })
+        ));
+
+        let mmc = BlockDevice::from_name_in(&root, "mmcblk0").unwrap();
+        assert_eq!(mmc.controller_type, ControllerType::Mmc);
+        assert_eq!(mmc.partitions.len(), 1);
+        assert_eq!(mmc.partitions[0].path, PathBuf::from("/dev/mmcblk0p1"));
+
+        assert!(BlockDevice::from_name_in(&root, "sda")
+            .unwrap()
+            .is_suitable(&policy())
+            .is_ok());
+        assert!(matches!(
+            BlockDevice::from_name_in(&root, "sdz"),
+            Err(InstallerError::DeviceNotFound(_))
+        ));
     }
 }
@@ -2,92 +2,255 @@
 //!
 //! Inspired by Growlight's approach to device discovery and hotplug detection.
 
-use crate::disk::block_device::BlockDevice;
+use crate::disk::block_device::{read_mounts, resolve_dev_path, BlockDevice, ControllerType};
+use crate::disk::sysroot::SysRoot;
 use crate::error::{InstallerError, Result};
-use inotify::{Inotify, WatchMask};
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::installer::parallel;
+use inotify::{EventMask, Inotify, WatchMask};
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Upper bound on threads reading device attributes during a scan
+const MAX_SCAN_WORKERS: usize = 16;
+
+/// Bytes read from inotify at a time
+const EVENT_BUFFER_SIZE: usize = 4096;
+
+/// A block device appearing or disappearing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    /// A device was plugged in
+    Added(String),
+    /// A device was removed
+    Removed(String),
+}
+
+impl DeviceEvent {
+    /// Kernel name of the device
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Added(name) | Self::Removed(name) => name,
+        }
+    }
+}
 
 /// Device discovery manager
 pub struct DeviceDiscovery {
     /// Inotify instance for monitoring device changes
     inotify: Option<Inotify>,
+    /// Root prefix for sysfs and procfs
+    sys_root: SysRoot,
+    /// Threads used to read device attributes during a scan
+    scan_workers: usize,
+    /// Executor for filesystem probing, if not the live system's
+    probe_executor: Option<Arc<dyn CommandExecutor>>,
 }
 
 impl DeviceDiscovery {
     /// Create a new device discovery manager
     pub fn new() -> Result<Self> {
-        Ok(Self { inotify: None })
+        Ok(Self {
+            inotify: None,
+            sys_root: SysRoot::default(),
+            scan_workers: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+                .min(MAX_SCAN_WORKERS),
+            probe_executor: None,
+        })
+    }
+
+    /// Read sysfs and procfs under a different root
+    pub fn with_sys_root(mut self, sys_root: SysRoot) -> Self {
+        self.sys_root = sys_root;
+        self
+    }
+
+    /// Root prefix used for sysfs and procfs lookups
+    pub fn sys_root(&self) -> &SysRoot {
+        &self.sys_root
+    }
+
+    /// Run filesystem probes (`blkid`) through a custom executor
+    ///
+    /// Without one, probes run on the live system, and only when reading the
+    /// live sysfs: a synthetic tree's devices don't exist in /dev.
+    pub fn with_probe_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.probe_executor = Some(executor);
+        self
+    }
+
+    /// Limit the number of threads used by [`scan_devices`](Self::scan_devices)
+    pub fn with_scan_workers(mut self, workers: usize) -> Self {
+        self.scan_workers = workers.max(1);
+        self
     }
 
     /// Initialize inotify watches for device hotplug detection
     pub fn enable_hotplug_detection(&mut self) -> Result<()> {
         let inotify = Inotify::init()?;
+        let block_path = self.sys_root.class_block();
 
         // Watch /sys/class/block for device additions/removals
         inotify
             .watches()
-            .add("/sys/class/block", WatchMask::CREATE | WatchMask::DELETE)
+            .add(&block_path, WatchMask::CREATE | WatchMask::DELETE)
             .map_err(|e| {
-                InstallerError::SystemError(format!("Failed to watch /sys/class/block: {}", e))
+                InstallerError::SystemError(format!(
+                    "Failed to watch {}: {}",
+                    block_path.display(),
+                    e
+                ))
             })?;
 
         self.inotify = Some(inotify);
         Ok(())
     }
 
+    /// Drain pending hotplug events without blocking
+    ///
+    /// Returns nothing until [`enable_hotplug_detection`](Self::enable_hotplug_detection)
+    /// has been called. Names a scan would skip (partitions, loop devices,
+    /// optical drives) are left out.
+    pub fn poll_events(&mut self) -> Result<Vec<DeviceEvent>> {
+        let Some(inotify) = self.inotify.as_mut() else {
+            return Ok(Vec::new());
+        };
+
+        let mut buffer = [0u8; EVENT_BUFFER_SIZE];
+        let mut events = Vec::new();
+        loop {
+            let batch = match inotify.read_events(&mut buffer) {
+                Ok(batch) => batch,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            let mut read_any = false;
+            for event in batch {
+                read_any = true;
+                let Some(name) = event.name.map(|n| n.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                if Self::is_excluded_name(&name) {
+                    continue;
+                }
+                if event.mask.contains(EventMask::CREATE) {
+                    events.push(DeviceEvent::Added(name));
+                } else if event.mask.contains(EventMask::DELETE) {
+                    events.push(DeviceEvent::Removed(name));
+                }
+            }
+            if !read_any {
+                break;
+            }
+        }
+        Ok(events)
+    }
+
+    /// Read one device the way [`scan_devices`](Self::scan_devices) would
+    ///
+    /// Returns None if it can't be read or a scan would leave it out.
+    pub fn read_device(&self, name: &str) -> Option<BlockDevice> {
+        let mounts = read_mounts(&self.sys_root);
+        self.read_devices(&[name.to_string()], &mounts)
+            .into_iter()
+            .next()
+    }
+
     /// Scan for all block devices
+    ///
+    /// Names are filtered before any attributes are read, and the remaining
+    /// devices are read in parallel on up to `scan_workers` threads.
     pub fn scan_devices(&self) -> Result<Vec<BlockDevice>> {
-        let mut devices = Vec::new();
-        let block_path = Path::new("/sys/class/block");
+        let block_path = self.sys_root.class_block();
 
         if !block_path.exists() {
-            return Err(InstallerError::SystemError(
-                "/sys/class/block not found - are you on Linux?".to_string(),
-            ));
+            return Err(InstallerError::SystemError(format!(
+                "{} not found - are you on Linux?",
+                block_path.display()
+            )));
         }
 
-        // Iterate through all entries in /sys/class/block
-        for entry in fs::read_dir(block_path)? {
-            let entry = entry?;
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-
-            // Skip partitions (they contain numbers or 'p' followed by numbers)
-            if Self::is_partition(&name_str) {
-                continue;
+        // Collect candidate names from /sys/class/block
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&block_path)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if !Self::is_excluded_name(&name) {
+                names.push(name);
             }
+        }
 
-            // Skip loop devices by default
-            if name_str.starts_with("loop") {
-                continue;
-            }
+        // Every device shares one read of the mount table
+        let mounts = read_mounts(&self.sys_root);
+        let mut devices = self.read_devices(&names, &mounts);
 
-            // Try to create BlockDevice - skip if it fails
-            match BlockDevice::from_name(&name_str) {
-                Ok(device) => {
-                    // Additional filtering
-                    if Self::should_include(&device) {
-                        devices.push(device);
-                    }
-                }
-                Err(e) => {
-                    log::debug!("Failed to create device {}: {}", name_str, e);
-                }
-            }
-        }
+        // A multipath map stands in for its paths; writing to a path
+        // behind multipathd's back corrupts the map
+        let paths: Vec<String> = devices
+            .iter()
+            .filter(|d| d.controller_type == ControllerType::Multipath)
+            .flat_map(|d| d.slaves.iter().cloned())
+            .collect();
+        devices.retain(|d| !paths.contains(&d.name));
 
         // Sort devices by name
         devices.sort_by(|a, b| a.name.cmp(&b.name));
         Ok(devices)
     }
 
+    /// Read and probe devices on a bounded set of worker threads, skipping
+    /// failures and devices [`should_include`](Self::should_include) rejects
+    fn read_devices(&self, names: &[String], mounts: &[(PathBuf, PathBuf)]) -> Vec<BlockDevice> {
+        let sys_root = &self.sys_root;
+        let read = |name: &str| match BlockDevice::from_name_with_mounts(sys_root, name, mounts) {
+            Ok(device) if !Self::should_include(&device) => None,
+            Ok(mut device) => {
+                self.probe(&mut device);
+                Some(device)
+            }
+            Err(e) => {
+                log::debug!("Failed to create device {}: {}", name, e);
+                None
+            }
+        };
+
+        parallel::map_bounded(names, self.scan_workers, |name| read(name))
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Names skipped without reading any attributes
+    fn is_excluded_name(name: &str) -> bool {
+        // Partitions, loop devices and CD/DVD drives (sr0, sr1, etc.)
+        Self::is_partition(name) || name.starts_with("loop") || name.starts_with("sr")
+    }
+
     /// Check if a device name represents a partition
-    fn is_partition(name: &str) -> bool {
-        // For nvme: nvme0n1p1, nvme0n1p2, etc.
-        if name.contains("nvme") && name.contains('p') {
-            let after_p = name.split('p').last().unwrap_or("");
-            return after_p.chars().all(|c| c.is_ascii_digit());
+    pub(crate) fn is_partition(name: &str) -> bool {
+        // Device-mapper devices (dm-0) are whole devices
+        if name.starts_with("dm-") {
+            return false;
+        }
+
+        // Disks whose names end in a digit use a 'p' separator:
+        // nvme0n1p1, mmcblk0p1, loop0p1, etc.
+        if ["nvme", "mmcblk", "loop"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            return match name.rsplit_once('p') {
+                Some((disk, part)) => {
+                    disk.ends_with(|c: char| c.is_ascii_digit())
+                        && !part.is_empty()
+                        && part.chars().all(|c| c.is_ascii_digit())
+                }
+                None => false,
+            };
         }
 
         // For other devices: sda1, sdb2, etc.
@@ -97,22 +260,43 @@ impl DeviceDiscovery {
 
     /// Determine if a device should be included in results
     fn should_include(device: &BlockDevice) -> bool {
-        // Exclude devices smaller than 1GB
-        if device.size < 1024 * 1024 * 1024 {
+        // Of the device-mapper devices only multipath maps are disks; LVM
+        // volumes, LUKS mappings and partition maps live on one
+        if device.name.starts_with("dm-") && device.controller_type != ControllerType::Multipath {
             return false;
         }
 
-        // Exclude CD/DVD drives (sr0, sr1, etc.)
-        if device.name.starts_with("sr") {
-            return false;
-        }
+        // Exclude devices smaller than 1GB
+        device.size >= 1024 * 1024 * 1024
+    }
 
-        true
+    /// Fill in partition filesystem details, logging probe failures
+    fn probe(&self, device: &mut BlockDevice) {
+        let result = match &self.probe_executor {
+            Some(executor) => device.probe_filesystems(executor.as_ref()),
+            None if self.sys_root == SysRoot::default() => {
+                device.probe_filesystems(&SystemExecutor::new(false))
+            }
+            None => return,
+        };
+        if let Err(e) = result {
+            log::debug!("Failed to probe filesystems on {}: {}", device.name, e);
+        }
     }
 
     /// Find a specific device by name
+    ///
+    /// Device-mapper names (`mpatha` for /dev/mapper/mpatha) are resolved to
+    /// their dm-N device.
     pub fn find_device(&self, name: &str) -> Result<BlockDevice> {
-        BlockDevice::from_name(name)
+        let mapped = resolve_dev_path(&self.sys_root, &Path::new("/dev/mapper").join(name));
+        let name = match mapped.file_name().and_then(|n| n.to_str()) {
+            Some(dm) if dm.starts_with("dm-") => dm,
+            _ => name,
+        };
+        let mut device = BlockDevice::from_name_in(&self.sys_root, name)?;
+        self.probe(&mut device);
+        Ok(device)
     }
 
     /// Find devices by path
@@ -140,9 +324,57 @@ impl Default for DeviceDiscovery {
     }
 }
 
+/// Build a sysfs tree with one LUN seen through two paths, sdc and sdd,
+/// gathered into the multipath map mpatha (dm-0), plus an LVM volume
+/// (dm-1) on sde
+#[cfg(test)]
+pub(crate) fn multipath_tree() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let sys = dir.path().join("sys");
+    let write = |path: &str, contents: &str| {
+        let path = sys.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    };
+    let link = |target: &str, link: &Path| {
+        fs::create_dir_all(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(target, link).unwrap();
+    };
+
+    for name in ["sdc", "sdd", "sde", "dm-0", "dm-1"] {
+        write(&format!("block/{}/size", name), "976773168\n");
+        write(&format!("block/{}/queue/logical_block_size", name), "512\n");
+        write(
+            &format!("block/{}/queue/physical_block_size", name),
+            "4096\n",
+        );
+        write(&format!("block/{}/queue/rotational", name), "1\n");
+        link(
+            &format!("../../block/{}", name),
+            &sys.join("class/block").join(name),
+        );
+    }
+    for path in ["sdc", "sdd"] {
+        write(&format!("block/{}/device/model", path), "MSA 2050 SAN\n");
+        write(&format!("block/{}/holders/dm-0", path), "");
+        write(&format!("block/dm-0/slaves/{}", path), "");
+    }
+    write("block/dm-0/dm/name", "mpatha\n");
+    write("block/dm-0/dm/uuid", "mpath-3600c0ff000d5e2a1\n");
+    write("block/sde/holders/dm-1", "");
+    write("block/dm-1/slaves/sde", "");
+    write("block/dm-1/dm/name", "vg0-data\n");
+    write("block/dm-1/dm/uuid", "LVM-abc\n");
+    link("../dm-0", &dir.path().join("dev/mapper/mpatha"));
+    dir
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exec::{ExecOptions, ExecResult, RecordingExecutor};
+    use std::process::Command;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[test]
     fn test_is_partition() {
@@ -154,6 +386,46 @@ mod tests {
         assert!(DeviceDiscovery::is_partition("nvme0n1p12"));
         assert!(!DeviceDiscovery::is_partition("vda"));
         assert!(DeviceDiscovery::is_partition("vda1"));
+        assert!(!DeviceDiscovery::is_partition("mmcblk0"));
+        assert!(DeviceDiscovery::is_partition("mmcblk0p1"));
+        assert!(!DeviceDiscovery::is_partition("loop0"));
+        assert!(!DeviceDiscovery::is_partition("dm-0"));
+    }
+
+    #[test]
+    fn test_multipath_map_replaces_its_paths() {
+        let dir = multipath_tree();
+        let discovery = DeviceDiscovery::new()
+            .unwrap()
+            .with_sys_root(SysRoot::new(dir.path()));
+        let devices = discovery.scan_devices().unwrap();
+
+        // The paths and the LVM volume are gone, the map is listed
+        let names: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["dm-0", "sde"]);
+        let map = &devices[0];
+        assert_eq!(map.controller_type, ControllerType::Multipath);
+        assert_eq!(map.dm_name.as_deref(), Some("mpatha"));
+        assert_eq!(map.slaves, vec!["sdc", "sdd"]);
+        assert_eq!(map.model.as_deref(), Some("MSA 2050 SAN"));
+        assert!(
+            map.display_name()
+                .starts_with("dm-0 (MSA 2050 SAN) [mpatha, 2 paths] - "),
+            "{}",
+            map.display_name()
+        );
+
+        // /dev/mapper names lead to the map
+        assert_eq!(discovery.find_device("mpatha").unwrap().name, "dm-0");
+    }
+
+    #[test]
+    fn test_excluded_names() {
+        assert!(DeviceDiscovery::is_excluded_name("sda1"));
+        assert!(DeviceDiscovery::is_excluded_name("loop0"));
+        assert!(DeviceDiscovery::is_excluded_name("sr0"));
+        assert!(!DeviceDiscovery::is_excluded_name("sda"));
+        assert!(!DeviceDiscovery::is_excluded_name("nvme0n1"));
     }
 
     #[test]
@@ -166,13 +438,20 @@ mod tests {
             size: 10_000_000_000, // 10GB
             logical_block_size: 512,
             physical_block_size: 4096,
+            optimal_io_size: 0,
             model: None,
             serial: None,
             vendor: None,
             removable: false,
             readonly: false,
             rotational: false,
+            mountpoint: None,
             partitions: Vec::new(),
+            holders: Vec::new(),
+            is_boot_medium: false,
+            dm_name: None,
+            slaves: Vec::new(),
+            smart: Default::default(),
         };
 
         assert!(DeviceDiscovery::should_include(&device));
@@ -187,4 +466,198 @@ mod tests {
         let discovery = DeviceDiscovery::new();
         assert!(discovery.is_ok());
     }
+
+    #[test]
+    fn test_scan_fixture_tree() {
+        let discovery = DeviceDiscovery::new()
+            .unwrap()
+            .with_sys_root(SysRoot::fixture());
+        let devices = discovery.scan_devices().unwrap();
+
+        // Partitions and loop devices are skipped, the 512MB eMMC is too small
+        let names: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["nvme0n1", "sda", "sdb"]);
+
+        let sdb = &devices[2];
+        assert!(sdb.removable);
+        assert_eq!(sdb.size, 31_260_672 * 512);
+    }
+
+    #[test]
+    fn test_scan_probes_filesystems() {
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        executor.respond_program("blkid", "DEVNAME=/dev/sda1\nTYPE=vfat\n");
+        let devices = DeviceDiscovery::new()
+            .unwrap()
+            .with_sys_root(SysRoot::fixture())
+            .with_probe_executor(executor.clone())
+            .with_scan_workers(1)
+            .scan_devices()
+            .unwrap();
+
+        let sda = devices.iter().find(|d| d.name == "sda").unwrap();
+        assert_eq!(sda.partitions[0].fstype.as_deref(), Some("vfat"));
+        assert_eq!(sda.partitions[1].fstype, None);
+        // One probe per included disk; the too-small eMMC is never probed
+        let probed: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.args[2].clone())
+            .collect();
+        assert_eq!(probed.len(), 3);
+        assert!(!probed.iter().any(|arg| arg.contains("mmcblk0")));
+    }
+
+    #[test]
+    fn test_find_devices_by_path_in_fixture() {
+        let discovery = DeviceDiscovery::new()
+            .unwrap()
+            .with_sys_root(SysRoot::fixture());
+
+        let devices = discovery
+            .find_devices_by_path(&[
+                std::path::PathBuf::from("/dev/sda"),
+                std::path::PathBuf::from("/dev/mmcblk0"),
+            ])
+            .unwrap();
+        assert_eq!(devices[0].partitions.len(), 2);
+        assert!(!DeviceDiscovery::should_include(&devices[1]));
+
+        assert!(discovery
+            .find_devices_by_path(&[std::path::PathBuf::from("/dev/sdz")])
+            .is_err());
+    }
+
+    #[test]
+    fn test_poll_events_reports_added_and_removed_disks() {
+        let dir = tempfile::tempdir().unwrap();
+        let class_block = dir.path().join("sys/class/block");
+        fs::create_dir_all(&class_block).unwrap();
+
+        let mut discovery = DeviceDiscovery::new()
+            .unwrap()
+            .with_sys_root(SysRoot::new(dir.path()));
+        assert!(discovery.poll_events().unwrap().is_empty());
+
+        discovery.enable_hotplug_detection().unwrap();
+        assert!(discovery.poll_events().unwrap().is_empty());
+
+        fs::write(class_block.join("sdc"), "").unwrap();
+        fs::write(class_block.join("sdc1"), "").unwrap();
+        fs::remove_file(class_block.join("sdc")).unwrap();
+
+        assert_eq!(
+            discovery.poll_events().unwrap(),
+            vec![
+                DeviceEvent::Added("sdc".to_string()),
+                DeviceEvent::Removed("sdc".to_string()),
+            ]
+        );
+        assert!(discovery.poll_events().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_device_applies_scan_filters() {
+        let discovery = DeviceDiscovery::new()
+            .unwrap()
+            .with_sys_root(SysRoot::fixture());
+        assert_eq!(discovery.read_device("sda").unwrap().name, "sda");
+        // Too small to be listed
+        assert!(discovery.read_device("mmcblk0").is_none());
+        assert!(discovery.read_device("sdz").is_none());
+    }
+
+    /// sysfs tree with `count` copies of the fixture's sda (sda, sdb, ..., sdaa, ...)
+    fn replicated_tree(count: usize) -> tempfile::TempDir {
+        let fixture = SysRoot::fixture();
+        let dir = tempfile::tempdir().unwrap();
+        let class_block = dir.path().join("sys/class/block");
+        fs::create_dir_all(&class_block).unwrap();
+        fs::create_dir_all(dir.path().join("proc")).unwrap();
+        fs::copy(fixture.proc_mounts(), dir.path().join("proc/mounts")).unwrap();
+
+        for i in 0..count {
+            let mut suffix = String::new();
+            let mut n = i + 1;
+            while n > 0 {
+                n -= 1;
+                suffix.insert(0, (b'a' + (n % 26) as u8) as char);
+                n /= 26;
+            }
+            let name = format!("sd{}", suffix);
+
+            for entry in walkdir::WalkDir::new(fixture.sys_block("sda")).follow_links(true) {
+                let entry = entry.unwrap();
+                let relative = entry
+                    .path()
+                    .strip_prefix(fixture.sys_block("sda"))
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace("sda", &name);
+                let target = dir.path().join("sys/block").join(&name).join(relative);
+                if entry.file_type().is_dir() {
+                    fs::create_dir_all(&target).unwrap();
+                } else {
+                    fs::copy(entry.path(), &target).unwrap();
+                }
+            }
+            std::os::unix::fs::symlink(format!("../../block/{}", name), class_block.join(&name))
+                .unwrap();
+        }
+
+        dir
+    }
+
+    /// Executor whose every command takes a few milliseconds, like blkid
+    /// on a real disk, and which counts how many run at once
+    #[derive(Default)]
+    struct SlowExecutor {
+        inner: RecordingExecutor,
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl CommandExecutor for SlowExecutor {
+        fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            self.inner.execute_with(cmd, options)
+        }
+    }
+
+    #[test]
+    fn test_scan_scales_to_hundreds_of_disks() {
+        let tree = replicated_tree(200);
+        let scan = |workers| {
+            let executor = Arc::new(SlowExecutor::default());
+            let devices = DeviceDiscovery::new()
+                .unwrap()
+                .with_sys_root(SysRoot::new(tree.path()))
+                .with_probe_executor(executor.clone())
+                .with_scan_workers(workers)
+                .scan_devices()
+                .unwrap();
+            (devices, executor.peak.load(Ordering::SeqCst))
+        };
+
+        let (sequential, sequential_peak) = scan(1);
+        let (parallel, parallel_peak) = scan(8);
+
+        let names = |devices: &[BlockDevice]| -> Vec<String> {
+            devices.iter().map(|d| d.name.clone()).collect()
+        };
+        assert_eq!(parallel.len(), 200);
+        assert_eq!(names(&parallel), names(&sequential));
+        assert!(parallel.iter().all(|d| d.partitions.len() == 2));
+
+        // Probes overlap, but never more than there are workers
+        assert_eq!(sequential_peak, 1);
+        assert!(
+            (2..=8).contains(&parallel_peak),
+            "{} probes ran at once",
+            parallel_peak
+        );
+    }
 }
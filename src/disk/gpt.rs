@@ -0,0 +1,246 @@
+//! Reading an existing GPT
+//!
+//! Installing alongside another system keeps the partitions already on the
+//! disk. Their layout comes from `sgdisk --print`, which reports the table in
+//! logical sectors along with the usable range between the two GPT copies.
+
+use crate::error::{InstallerError, Result};
+use std::path::Path;
+
+/// sgdisk type code of an EFI system partition
+pub const ESP_TYPE_CODE: &str = "EF00";
+
+/// sgdisk type code of a BIOS boot partition
+pub const BIOS_BOOT_TYPE_CODE: &str = "EF02";
+
+/// A partition already on the disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GptEntry {
+    /// Partition number
+    pub number: u32,
+    /// First sector
+    pub start: u64,
+    /// Last sector (inclusive)
+    pub end: u64,
+    /// sgdisk type code (e.g. EF00, 8300)
+    pub type_code: String,
+    /// Partition name (may be empty)
+    pub name: String,
+}
+
+/// The partition table on a disk, as `sgdisk --print` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GptLayout {
+    /// Logical sector size
+    pub sector_size: u64,
+    /// First sector a partition may use
+    pub first_usable: u64,
+    /// Last sector a partition may use
+    pub last_usable: u64,
+    /// Partitions, in table order
+    pub partitions: Vec<GptEntry>,
+}
+
+impl GptLayout {
+    /// Parse `sgdisk --print` output for `device`
+    ///
+    /// A disk with an MBR is refused: sgdisk would convert it to GPT on the
+    /// first write, which the other system may not boot from.
+    pub fn parse(device: &Path, output: &str) -> Result<Self> {
+        let invalid = |details: &str| {
+            InstallerError::disk(
+                format!("read the partition table of {}", device.display()),
+                details.to_string(),
+            )
+        };
+        if output.contains("converting MBR to GPT") {
+            return Err(invalid(
+                "the disk has an MBR partition table; only GPT disks can be installed alongside",
+            ));
+        }
+
+        let mut sector_size = None;
+        let mut usable = None;
+        let mut partitions = Vec::new();
+        let mut in_table = false;
+        for line in output.lines() {
+            let line = line.trim();
+            if let Some(sizes) = line.strip_prefix("Sector size (logical/physical):") {
+                // "512/4096 bytes"
+                sector_size = sizes
+                    .split('/')
+                    .next()
+                    .and_then(|s| s.trim().parse::<u64>().ok());
+            } else if let Some(rest) = line.strip_prefix("First usable sector is ") {
+                // "34, last usable sector is 976773134"
+                usable = rest
+                    .split_once(", last usable sector is ")
+                    .and_then(|(first, last)| {
+                        Some((first.trim().parse().ok()?, last.trim().parse().ok()?))
+                    });
+            } else if line.starts_with("Number") {
+                in_table = true;
+            } else if in_table && !line.is_empty() {
+                partitions
+                    .push(parse_entry(line).ok_or_else(|| {
+                        invalid(&format!("unexpected partition line '{}'", line))
+                    })?);
+            }
+        }
+
+        let sector_size = sector_size.ok_or_else(|| invalid("no sector size in sgdisk output"))?;
+        let (first_usable, last_usable) =
+            usable.ok_or_else(|| invalid("no usable sector range in sgdisk output"))?;
+        Ok(Self {
+            sector_size,
+            first_usable,
+            last_usable,
+            partitions,
+        })
+    }
+
+    /// The first EFI system partition, if there is one
+    pub fn esp(&self) -> Option<&GptEntry> {
+        self.find_type(ESP_TYPE_CODE)
+    }
+
+    /// The first partition with sgdisk type code `code`
+    pub fn find_type(&self, code: &str) -> Option<&GptEntry> {
+        self.partitions
+            .iter()
+            .find(|p| p.type_code.eq_ignore_ascii_case(code))
+    }
+
+    /// The `count` lowest partition numbers not in use
+    pub fn next_numbers(&self, count: usize) -> Vec<u32> {
+        (1..)
+            .filter(|n| !self.partitions.iter().any(|p| p.number == *n))
+            .take(count)
+            .collect()
+    }
+
+    /// First and last sector of the largest unpartitioned region
+    ///
+    /// The region is trimmed to start and end on `align`-sector boundaries;
+    /// `None` if no gap holds a whole aligned unit.
+    pub fn largest_free_region(&self, align: u64) -> Option<(u64, u64)> {
+        let align = align.max(1);
+        let mut used: Vec<(u64, u64)> = self.partitions.iter().map(|p| (p.start, p.end)).collect();
+        used.sort_unstable();
+
+        let mut gaps = Vec::new();
+        let mut next_free = self.first_usable;
+        for (start, end) in used {
+            if start > next_free {
+                gaps.push((next_free, start - 1));
+            }
+            next_free = next_free.max(end + 1);
+        }
+        if next_free <= self.last_usable {
+            gaps.push((next_free, self.last_usable));
+        }
+
+        gaps.into_iter()
+            .filter_map(|(start, end)| {
+                let start = start.div_ceil(align) * align;
+                let end = ((end + 1) / align * align).checked_sub(1)?;
+                (end >= start).then_some((start, end))
+            })
+            .max_by_key(|(start, end)| end - start)
+    }
+}
+
+/// Parse a table row: "1  2048  1050623  512.0 MiB  EF00  EFI system partition"
+fn parse_entry(line: &str) -> Option<GptEntry> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 6 {
+        return None;
+    }
+    Some(GptEntry {
+        number: fields[0].parse().ok()?,
+        start: fields[1].parse().ok()?,
+        end: fields[2].parse().ok()?,
+        // fields[3..5] are the size and its unit
+        type_code: fields[5].to_string(),
+        name: fields[6..].join(" "),
+    })
+}
+
+#[cfg(test)]
+pub(crate) const SAMPLE_PRINT: &str = "\
+Disk /dev/sda: 976773168 sectors, 465.8 GiB
+Model: Samsung SSD 860
+Sector size (logical/physical): 512/512 bytes
+Disk identifier (GUID): 4A3B2C1D-0000-4000-8000-123456789ABC
+Partition table holds up to 128 entries
+Main partition table begins at sector 2 and ends at sector 33
+First usable sector is 34, last usable sector is 976773134
+Partitions will be aligned on 2048-sector boundaries
+Total free space is 566235247 sectors (270.0 GiB)
+
+Number  Start (sector)    End (sector)  Size       Code  Name
+   1            2048         1050623   512.0 MiB   EF00  EFI system partition
+   2         1050624       410650623   195.3 GiB   0700  Basic data partition
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> GptLayout {
+        GptLayout::parse(Path::new("/dev/sda"), SAMPLE_PRINT).unwrap()
+    }
+
+    #[test]
+    fn test_parse_print_output() {
+        let gpt = sample();
+        assert_eq!(gpt.sector_size, 512);
+        assert_eq!((gpt.first_usable, gpt.last_usable), (34, 976773134));
+        assert_eq!(gpt.partitions.len(), 2);
+        assert_eq!(gpt.partitions[1].name, "Basic data partition");
+
+        let esp = gpt.esp().unwrap();
+        assert_eq!((esp.number, esp.start, esp.end), (1, 2048, 1050623));
+        assert!(gpt.find_type(BIOS_BOOT_TYPE_CODE).is_none());
+        assert_eq!(gpt.next_numbers(2), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_largest_free_region() {
+        let mut gpt = sample();
+        // Everything after the Windows partition, trimmed to 1 MiB
+        assert_eq!(gpt.largest_free_region(2048), Some((410650624, 976773119)));
+
+        // A gap between partitions is found, and its number reused
+        gpt.partitions.push(GptEntry {
+            number: 4,
+            start: 600000000,
+            end: 976773134,
+            type_code: "8300".to_string(),
+            name: String::new(),
+        });
+        assert_eq!(gpt.largest_free_region(2048), Some((410650624, 599998463)));
+        assert_eq!(gpt.next_numbers(2), vec![3, 5]);
+
+        // A full disk has no room
+        gpt.partitions[2].start = 410650624;
+        assert_eq!(gpt.largest_free_region(2048), None);
+    }
+
+    #[test]
+    fn test_empty_and_mbr_disks() {
+        let empty = SAMPLE_PRINT.split("Number").next().unwrap().to_string()
+            + "Number  Start (sector)    End (sector)  Size       Code  Name\n";
+        let gpt = GptLayout::parse(Path::new("/dev/sda"), &empty).unwrap();
+        assert!(gpt.partitions.is_empty());
+        assert_eq!(gpt.largest_free_region(2048), Some((2048, 976773119)));
+
+        let mbr = format!(
+            "Found invalid GPT and valid MBR; converting MBR to GPT format\nin memory.\n{}",
+            SAMPLE_PRINT
+        );
+        let err = GptLayout::parse(Path::new("/dev/sda"), &mbr).unwrap_err();
+        assert!(err.to_string().contains("MBR"), "{err}");
+        assert!(GptLayout::parse(Path::new("/dev/sda"), "garbage").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Devices stacked on a disk
+//!
+//! md arrays, dm-crypt mappings and LVM volumes keep the disks under them
+//! open, so wiping such a disk fails or, worse, half succeeds and leaves the
+//! device-mapper stack confused. The kernel lists each device's holders under
+//! `/sys/block/<dev>/holders`; they are stopped, outermost first, before the
+//! disk is wiped.
+
+use crate::disk::sysroot::SysRoot;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// What a stacked device is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HolderKind {
+    /// An md RAID array
+    MdArray,
+    /// A dm-crypt (LUKS) mapping
+    Crypt,
+    /// An LVM logical volume
+    LogicalVolume,
+    /// A dm-multipath map; the disk is one of its paths
+    Multipath,
+    /// Any other device-mapper target
+    DeviceMapper,
+}
+
+/// A device stacked on a disk or one of its partitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    /// Kernel name (e.g. md127, dm-0)
+    pub name: String,
+    /// What the device is
+    pub kind: HolderKind,
+    /// Device-mapper name (e.g. luks-4f3c..., vg0-root)
+    pub dm_name: Option<String>,
+}
+
+impl Holder {
+    /// Find out what the device `name` is from sysfs
+    pub fn read(root: &SysRoot, name: &str) -> Self {
+        let dm = root.sys_block(name).join("dm");
+        let read = |attr: &str| {
+            fs::read_to_string(dm.join(attr))
+                .ok()
+                .map(|s| s.trim().to_string())
+        };
+
+        // The dm UUID is prefixed with the subsystem that set the target up
+        let kind = match read("uuid") {
+            Some(uuid) if uuid.starts_with("CRYPT-") => HolderKind::Crypt,
+            Some(uuid) if uuid.starts_with("LVM-") => HolderKind::LogicalVolume,
+            Some(uuid) if uuid.starts_with("mpath-") => HolderKind::Multipath,
+            Some(_) => HolderKind::DeviceMapper,
+            None if name.starts_with("md") => HolderKind::MdArray,
+            None => HolderKind::DeviceMapper,
+        };
+        Self {
+            name: name.to_string(),
+            kind,
+            dm_name: read("name"),
+        }
+    }
+
+    /// Name the device is known by (its mapper name if it has one)
+    pub fn display_name(&self) -> &str {
+        self.dm_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Volume group of a logical volume
+    ///
+    /// Taken from the mapper name, `<vg>-<lv>` with dashes inside either
+    /// name doubled.
+    pub fn volume_group(&self) -> Option<String> {
+        if self.kind != HolderKind::LogicalVolume {
+            return None;
+        }
+        let name = self.dm_name.as_deref()?;
+        let mut chars = name.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c != '-' {
+                continue;
+            }
+            if chars.peek().map(|&(_, next)| next) == Some('-') {
+                chars.next();
+                continue;
+            }
+            return Some(name[..i].replace("--", "-"));
+        }
+        None
+    }
+
+    /// Command that stops the device, releasing what it holds
+    pub fn release_command(&self) -> Command {
+        let mut cmd;
+        match self.kind {
+            HolderKind::MdArray => {
+                cmd = Command::new("mdadm");
+                cmd.arg("--stop").arg(Path::new("/dev").join(&self.name));
+            }
+            HolderKind::Crypt => {
+                cmd = Command::new("cryptsetup");
+                cmd.arg("close").arg(self.display_name());
+            }
+            HolderKind::LogicalVolume => {
+                cmd = Command::new("vgchange");
+                cmd.arg("-an");
+                match self.volume_group() {
+                    Some(vg) => cmd.arg(vg),
+                    None => cmd.arg(Path::new("/dev/mapper").join(self.display_name())),
+                };
+            }
+            HolderKind::Multipath => {
+                cmd = Command::new("multipath");
+                cmd.arg("-f").arg(self.display_name());
+            }
+            HolderKind::DeviceMapper => {
+                cmd = Command::new("dmsetup");
+                cmd.arg("remove").arg(self.display_name());
+            }
+        }
+        cmd
+    }
+}
+
+impl std::fmt::Display for Holder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            HolderKind::MdArray => "md RAID array",
+            HolderKind::Crypt => "LUKS mapping",
+            HolderKind::LogicalVolume => "LVM volume",
+            HolderKind::Multipath => "multipath map",
+            HolderKind::DeviceMapper => "device-mapper device",
+        };
+        write!(f, "{} ({})", self.display_name(), kind)
+    }
+}
+
+/// Kernel names of the devices stacked on the devices at `sys_paths`
+///
+/// Follows holders of holders, and lists each device before the ones it
+/// holds, so they can be stopped in order.
+pub fn read_holders<'a>(
+    root: &SysRoot,
+    sys_paths: impl IntoIterator<Item = &'a Path>,
+) -> Vec<String> {
+    let mut holders = Vec::new();
+    for sys_path in sys_paths {
+        collect_holders(root, sys_path, &mut holders);
+    }
+    holders
+}
+
+fn collect_holders(root: &SysRoot, sys_path: &Path, holders: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(sys_path.join("holders")) else {
+        return;
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+
+    for name in names {
+        if holders.contains(&name) {
+            continue;
+        }
+        collect_holders(root, &root.sys_block(&name), holders);
+        holders.push(name);
+    }
+}
+
+/// Describe the stacked devices `names`, e.g. "md127 (md RAID array), ..."
+pub fn describe(root: &SysRoot, names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| Holder::read(root, name).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Build a sysfs tree with sdc, whose partition holds an md array carrying
+/// a LUKS mapping with an LVM volume group on it
+#[cfg(test)]
+pub(crate) fn stacked_tree() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let block = dir.path().join("sys/block");
+    let write = |path: &str, contents: &str| {
+        let path = block.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    };
+
+    write("sdc/size", "976773168\n");
+    write("sdc/queue/logical_block_size", "512\n");
+    write("sdc/queue/physical_block_size", "4096\n");
+    write("sdc/queue/rotational", "1\n");
+    write("sdc/sdc1/size", "976771072\n");
+    write("sdc/sdc1/partition", "1\n");
+    write("sdc/sdc1/holders/md127", "");
+    write("md127/holders/dm-0", "");
+    write("dm-0/dm/uuid", "CRYPT-LUKS2-4f3c2a1e-crypt\n");
+    write("dm-0/dm/name", "luks-4f3c2a1e\n");
+    write("dm-0/holders/dm-1", "");
+    write("dm-0/holders/dm-2", "");
+    write("dm-1/dm/uuid", "LVM-abc\n");
+    write("dm-1/dm/name", "data--vg-root\n");
+    write("dm-2/dm/uuid", "LVM-def\n");
+    write("dm-2/dm/name", "data--vg-home\n");
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_holders_outermost_first() {
+        let dir = stacked_tree();
+        let root = SysRoot::new(dir.path());
+        let sdc = root.sys_block("sdc");
+        let holders = read_holders(&root, [sdc.as_path(), sdc.join("sdc1").as_path()]);
+        assert_eq!(holders, vec!["dm-1", "dm-2", "dm-0", "md127"]);
+        assert_eq!(
+            describe(&root, &holders),
+            "data--vg-root (LVM volume), data--vg-home (LVM volume), \
+             luks-4f3c2a1e (LUKS mapping), md127 (md RAID array)"
+        );
+
+        let fixture = SysRoot::fixture();
+        assert!(read_holders(&fixture, [fixture.sys_block("sda").as_path()]).is_empty());
+    }
+
+    #[test]
+    fn test_release_commands() {
+        let dir = stacked_tree();
+        let root = SysRoot::new(dir.path());
+        let argv = |name: &str| {
+            let cmd = Holder::read(&root, name).release_command();
+            std::iter::once(cmd.get_program())
+                .chain(cmd.get_args())
+                .map(|a| a.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(argv("md127"), "mdadm --stop /dev/md127");
+        assert_eq!(argv("dm-0"), "cryptsetup close luks-4f3c2a1e");
+        assert_eq!(argv("dm-1"), "vgchange -an data-vg");
+    }
+}
@@ -4,8 +4,23 @@
 
 pub mod block_device;
 pub mod discovery;
+pub mod gpt;
+pub mod holders;
+pub mod mount;
 pub mod operations;
+pub mod partition_table;
+pub mod smart;
+pub mod sysroot;
 
-pub use block_device::{BlockDevice, ControllerType, Partition};
-pub use discovery::DeviceDiscovery;
-pub use operations::{DiskOperations, PartitionSpec, ZbmPartitions};
+pub use block_device::{stable_path_in, BlockDevice, ControllerType, Partition, SuitabilityPolicy};
+pub use discovery::{DeviceDiscovery, DeviceEvent};
+pub use gpt::GptLayout;
+pub use holders::Holder;
+pub use mount::MountGuard;
+pub use operations::{
+    parent_disk, partition_number, DiskOperations, PartitionSpec, WipeMode, ZbmPartitions,
+    DEVICE_RETRIES, DEVICE_RETRY_BACKOFF, DEVICE_TIMEOUT,
+};
+pub use partition_table::{BootLayout, PartitionRole, PartitionTable};
+pub use smart::SmartStatus;
+pub use sysroot::SysRoot;
@@ -0,0 +1,160 @@
+//! Mounting filesystems
+//!
+//! Mounts are made with `mount(2)` instead of running `mount`, so a failure
+//! comes back as an errno that can be explained. They go through
+//! [`FileOp::Mount`] and [`FileOp::Unmount`], which keeps them in dry-run
+//! plans like any other change to the target.
+
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, FileOp};
+use nix::errno::Errno;
+use nix::mount::{MntFlags, MsFlags};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Mount `source` on `target` as `fstype`
+///
+/// FAT filesystems are mounted like the fstab entries written for them:
+/// only root can read them, and nothing on them is a device or setuid.
+pub fn mount(source: &Path, target: &Path, fstype: &str) -> Result<()> {
+    let (flags, data) = match fstype {
+        "vfat" => (MsFlags::MS_NODEV | MsFlags::MS_NOSUID, Some("umask=0077")),
+        _ => (MsFlags::empty(), None),
+    };
+
+    nix::mount::mount(Some(source), target, Some(fstype), flags, data).map_err(|errno| {
+        InstallerError::disk(
+            format!("mount {} on {}", source.display(), target.display()),
+            describe_errno(errno, target),
+        )
+    })
+}
+
+/// Unmount the filesystem mounted on `target`
+pub fn unmount(target: &Path) -> Result<()> {
+    nix::mount::umount2(target, MntFlags::empty()).map_err(|errno| {
+        InstallerError::disk(
+            format!("unmount {}", target.display()),
+            describe_errno(errno, target),
+        )
+    })
+}
+
+/// Explain a failed mount or unmount of `target`
+fn describe_errno(errno: Errno, target: &Path) -> String {
+    let target = target.display();
+    match errno {
+        Errno::EBUSY => format!(
+            "{} is busy; see what is using it with `fuser -vm {}`",
+            target, target
+        ),
+        Errno::ENOENT => format!("{} or the device does not exist", target),
+        Errno::ENOTDIR => format!("{} is not a directory", target),
+        Errno::ENODEV => "the filesystem type isn't supported by the kernel".to_string(),
+        Errno::ENOTBLK => "the source is not a block device".to_string(),
+        Errno::EINVAL => format!(
+            "{} is not a mountpoint, or the device holds no valid filesystem",
+            target
+        ),
+        Errno::EPERM | Errno::EACCES => "permission denied (run as root)".to_string(),
+        other => other.desc().to_string(),
+    }
+}
+
+/// A mount that is undone when dropped
+///
+/// Keeps a failed step from leaving filesystems mounted in the target; call
+/// [`persist`](Self::persist) once the mount should outlive the guard.
+pub struct MountGuard {
+    target: PathBuf,
+    executor: Arc<dyn CommandExecutor>,
+    persisted: bool,
+}
+
+impl MountGuard {
+    /// Mount `source` on `target`, creating `target` if needed
+    pub fn mount(
+        executor: Arc<dyn CommandExecutor>,
+        source: &Path,
+        target: &Path,
+        fstype: &str,
+    ) -> Result<Self> {
+        executor.apply(FileOp::create_dir(target))?;
+        executor.apply(FileOp::mount(source, target, fstype))?;
+        Ok(Self {
+            target: target.to_path_buf(),
+            executor,
+            persisted: false,
+        })
+    }
+
+    /// Where the filesystem is mounted
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Leave the filesystem mounted when the guard is dropped
+    pub fn persist(mut self) -> PathBuf {
+        self.persisted = true;
+        std::mem::take(&mut self.target)
+    }
+}
+
+impl Drop for MountGuard {
+    fn drop(&mut self) {
+        if self.persisted {
+            return;
+        }
+        if let Err(e) = self.executor.apply(FileOp::unmount(&self.target)) {
+            log::warn!("Failed to unmount {}: {}", self.target.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    #[test]
+    fn test_guard_unmounts_unless_persisted() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let esp = Path::new("/mnt/boot/efi");
+
+        let guard =
+            MountGuard::mount(executor.clone(), Path::new("/dev/sda1"), esp, "vfat").unwrap();
+        assert_eq!(guard.target(), esp);
+        drop(guard);
+
+        let guard =
+            MountGuard::mount(executor.clone(), Path::new("/dev/sdb1"), esp, "vfat").unwrap();
+        assert_eq!(guard.persist(), esp);
+
+        let ops: Vec<String> = executor
+            .file_ops()
+            .iter()
+            .map(|op| op.to_string())
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                "create directory: /mnt/boot/efi",
+                "mount -t vfat /dev/sda1 /mnt/boot/efi",
+                "umount /mnt/boot/efi",
+                "create directory: /mnt/boot/efi",
+                "mount -t vfat /dev/sdb1 /mnt/boot/efi",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors_are_explained() {
+        let esp = Path::new("/mnt/boot/efi");
+        assert_eq!(
+            describe_errno(Errno::EBUSY, esp),
+            "/mnt/boot/efi is busy; see what is using it with `fuser -vm /mnt/boot/efi`"
+        );
+        assert!(describe_errno(Errno::ENODEV, esp).contains("filesystem type"));
+        assert_eq!(describe_errno(Errno::EIO, esp), Errno::EIO.desc());
+    }
+}
@@ -2,11 +2,18 @@
 //!
 //! Provides safe wrappers around disk manipulation commands.
 
-use crate::disk::block_device::BlockDevice;
+use crate::disk::block_device::{BlockDevice, ControllerType};
+use crate::disk::gpt::GptLayout;
+use crate::disk::holders::{Holder, HolderKind};
+use crate::disk::partition_table::{BootLayout, PartitionRole, PartitionTable, CRYPTSWAP_LABEL};
+use crate::disk::sysroot::SysRoot;
 use crate::error::{InstallerError, Result};
-use bytesize::ByteSize;
-use std::path::PathBuf;
+use crate::exec::{CommandExecutor, ExecOptions, ExecResult, FileOp, RetryPolicy, SystemExecutor};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// Partition specification
 #[derive(Debug, Clone)]
@@ -23,49 +30,276 @@ pub struct PartitionSpec {
     pub name: Option<String>,
 }
 
+/// Bytes zeroed at each end of a disk or partition by [`WipeMode::ZeroLabels`]
+///
+/// ZFS keeps two labels in the first and two in the last 512 KiB of a vdev;
+/// the margin covers vdevs that didn't start or end exactly there.
+pub const LABEL_AREA: u64 = 10 * 1024 * 1024;
+
+/// How long to wait for a new partition's device node by default
+pub const DEVICE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a disk command that fails because the device is busy is retried by default
+pub const DEVICE_RETRIES: u32 = 3;
+
+/// Pause before the first retry of a busy device; doubles after each
+pub const DEVICE_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// First and longest pause between looks for a device node
+const DEVICE_POLL_MIN: Duration = Duration::from_millis(50);
+const DEVICE_POLL_MAX: Duration = Duration::from_secs(2);
+
+/// How thoroughly a disk is wiped before it is partitioned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum WipeMode {
+    /// Remove filesystem and partition table signatures only
+    #[default]
+    Signatures,
+    /// Discard every block (SSDs and NVMe), then remove signatures
+    Discard,
+    /// Zero both ends of the disk and of each old partition, then remove signatures
+    ZeroLabels,
+}
+
+impl WipeMode {
+    /// Get description of wipe mode
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Signatures => "remove signatures; old data stays on the disk",
+            Self::Discard => "discard all blocks (SSD/NVMe)",
+            Self::ZeroLabels => "zero the ZFS label areas",
+        }
+    }
+}
+
+impl std::fmt::Display for WipeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Signatures => write!(f, "signatures"),
+            Self::Discard => write!(f, "discard"),
+            Self::ZeroLabels => write!(f, "zero-labels"),
+        }
+    }
+}
+
 /// Disk operations manager
 pub struct DiskOperations {
     /// Dry run mode - don't actually execute commands
     dry_run: bool,
+    /// Executor that runs the commands
+    executor: Arc<dyn CommandExecutor>,
+    /// How [`wipe_device`](Self::wipe_device) wipes
+    wipe_mode: WipeMode,
+    /// Where stacked devices are looked up
+    sys_root: SysRoot,
+    /// How long to wait for device nodes to appear
+    device_timeout: Duration,
+    /// How commands failing on a busy device are retried
+    retry: RetryPolicy,
+    /// Retries and unmounts since the last [`take_notices`](Self::take_notices)
+    notices: Arc<Mutex<Vec<String>>>,
 }
 
 impl DiskOperations {
     /// Create a new disk operations manager
     pub fn new(dry_run: bool) -> Self {
-        Self { dry_run }
+        Self {
+            dry_run,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+            wipe_mode: WipeMode::default(),
+            sys_root: SysRoot::default(),
+            device_timeout: DEVICE_TIMEOUT,
+            retry: RetryPolicy::new(DEVICE_RETRIES + 1, DEVICE_RETRY_BACKOFF),
+            notices: Arc::default(),
+        }
+    }
+
+    /// Look up stacked devices under a different root
+    pub fn with_sys_root(mut self, sys_root: SysRoot) -> Self {
+        self.sys_root = sys_root;
+        self
+    }
+
+    /// Wipe devices the given way
+    pub fn with_wipe_mode(mut self, wipe_mode: WipeMode) -> Self {
+        self.wipe_mode = wipe_mode;
+        self
+    }
+
+    /// Wait this long for device nodes before giving up
+    pub fn with_device_timeout(mut self, timeout: Duration) -> Self {
+        self.device_timeout = timeout;
+        self
+    }
+
+    /// Retry commands that fail because the device is busy according to `retry`
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
     }
 
     /// Execute a command, respecting dry-run mode
-    fn execute(&self, cmd: &mut Command) -> Result<std::process::Output> {
-        let cmd_str = format!("{:?}", cmd);
+    ///
+    /// A device goes busy for a moment while udev probes it or a desktop
+    /// mounts it, so failures saying so are retried with a growing pause.
+    fn execute(&self, cmd: &mut Command) -> Result<ExecResult> {
+        let notices = self.notices.clone();
+        let options = ExecOptions::new()
+            .retry(self.retry)
+            .on_retry(move |message| notices.lock().unwrap().push(message.to_string()));
+        self.executor.execute_with(cmd, &options)
+    }
+
+    /// Retries and unmounts done since the last call, oldest first
+    ///
+    /// Both are logged as they happen; this lets the installer pass them on
+    /// as progress events.
+    pub fn take_notices(&self) -> Vec<String> {
+        std::mem::take(&mut *self.notices.lock().unwrap())
+    }
+
+    /// Ask the kernel to re-read the partition table and wait for udev
+    fn settle(&self, device: &Path) -> Result<()> {
+        self.execute(Command::new("partprobe").arg(device))?;
+        self.settle_udev()
+    }
+
+    /// Wait for udev to finish creating device nodes and links
+    pub fn settle_udev(&self) -> Result<()> {
+        self.execute(Command::new("udevadm").arg("settle"))?;
+
+        Ok(())
+    }
+
+    /// Unmount whatever a desktop automounter mounted from a device
+    ///
+    /// Other mounts make [`BlockDevice::is_suitable`] refuse the device, so
+    /// they are left alone.
+    pub fn unmount_automounts(&self, device: &BlockDevice) -> Result<()> {
+        for mountpoint in device.automounts() {
+            let message = format!("Unmounting automounted {}", mountpoint.display());
+            log::info!("{}", message);
+            self.executor.apply(FileOp::unmount(mountpoint))?;
+            self.notices.lock().unwrap().push(message);
+        }
+        Ok(())
+    }
 
+    /// Wait until the device node `path` exists
+    ///
+    /// udev creates partition nodes (and the /dev/disk links) some time after
+    /// the kernel learns about the partition, which on a busy or slow system
+    /// can be after sgdisk and partprobe have returned. Looks again with
+    /// growing pauses, settling udev once if the node isn't there at first.
+    /// `path` is looked up under the sys root. Nothing is waited for in
+    /// dry-run mode.
+    pub fn wait_for_device(&self, path: &Path, timeout: Duration) -> Result<()> {
         if self.dry_run {
-            log::info!("[DRY RUN] Would execute: {}", cmd_str);
-            return Ok(std::process::Output {
-                status: std::process::ExitStatus::default(),
-                stdout: Vec::new(),
-                stderr: Vec::new(),
-            });
+            return Ok(());
         }
 
-        log::debug!("Executing: {}", cmd_str);
-        let output = cmd.output()?;
+        let node = self.sys_root.join(path);
+        let started = Instant::now();
+        let mut pause = DEVICE_POLL_MIN;
+        let mut settled = false;
+        while !node.exists() {
+            if !settled {
+                settled = true;
+                // Not every system has udevadm; the node may still turn up
+                if let Err(e) = self.settle_udev() {
+                    log::debug!("udevadm settle failed: {}", e);
+                }
+                continue;
+            }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(InstallerError::CommandFailed {
-                cmd: cmd_str,
-                code: output.status.code().unwrap_or(-1),
-                stderr: stderr.to_string(),
-            });
+            let waited = started.elapsed();
+            if waited >= timeout {
+                return Err(InstallerError::disk(
+                    format!("wait for {}", path.display()),
+                    format!(
+                        "device node did not appear within {:.1}s",
+                        timeout.as_secs_f64()
+                    ),
+                ));
+            }
+            std::thread::sleep(pause.min(timeout - waited));
+            pause = (pause * 2).min(DEVICE_POLL_MAX);
+        }
+
+        if settled {
+            log::debug!(
+                "{} appeared after {:.1}s",
+                path.display(),
+                started.elapsed().as_secs_f64()
+            );
+        }
+        Ok(())
+    }
+
+    /// Stop every md array, LVM volume group and LUKS mapping on a device
+    ///
+    /// Works through [`BlockDevice::holders`] outermost first. Everything
+    /// stored on them is lost with the disk, so only call this once the user
+    /// has agreed to it.
+    pub fn release_device(&self, device: &BlockDevice) -> Result<()> {
+        let mut volume_groups = Vec::new();
+        for name in &device.holders {
+            let holder = Holder::read(&self.sys_root, name);
+            // Deactivating a volume group releases all of its volumes
+            if holder.kind == HolderKind::LogicalVolume {
+                if let Some(vg) = holder.volume_group() {
+                    if volume_groups.contains(&vg) {
+                        continue;
+                    }
+                    volume_groups.push(vg);
+                }
+            }
+
+            log::info!("Stopping {} on {}", holder, device.path.display());
+            self.execute(&mut holder.release_command()).map_err(|e| {
+                InstallerError::disk(
+                    format!("release {} from {}", holder, device.path.display()),
+                    e.to_string(),
+                )
+            })?;
         }
 
-        Ok(output)
+        if !device.holders.is_empty() {
+            self.settle_udev()?;
+        }
+        Ok(())
     }
 
     /// Wipe all data from a device
+    ///
+    /// What goes beyond removing signatures depends on the [`WipeMode`].
     pub fn wipe_device(&self, device: &BlockDevice) -> Result<()> {
-        log::info!("Wiping device: {}", device.path.display());
+        log::info!(
+            "Wiping device: {} ({})",
+            device.path.display(),
+            self.wipe_mode
+        );
+        self.unmount_automounts(device)?;
+
+        match self.wipe_mode {
+            WipeMode::Signatures => {}
+            WipeMode::Discard => self.discard(device),
+            WipeMode::ZeroLabels => {
+                // Old partitions first: labels of a pool on a partition
+                // aren't at the ends of the disk
+                for partition in &device.partitions {
+                    self.zero_ends(&partition.path, partition.size)?;
+                }
+                self.zero_ends(&device.path, device.size)?;
+            }
+        }
 
         // Use wipefs to remove filesystem signatures
         self.execute(Command::new("wipefs").arg("-a").arg(&device.path))?;
@@ -74,8 +308,52 @@ impl DiskOperations {
         self.execute(Command::new("sgdisk").arg("--zap-all").arg(&device.path))?;
 
         // Wait for kernel to update
-        self.execute(Command::new("partprobe").arg(&device.path))?;
+        self.settle(&device.path)?;
+
+        Ok(())
+    }
 
+    /// Discard every block of a device
+    ///
+    /// Not every device supports it, so a failure only leaves the old data
+    /// in place and is logged.
+    fn discard(&self, device: &BlockDevice) {
+        if device.rotational {
+            log::warn!(
+                "{} is a rotational disk and can't be discarded; only removing signatures",
+                device.path.display()
+            );
+            return;
+        }
+        if let Err(e) = self.execute(Command::new("blkdiscard").arg("-f").arg(&device.path)) {
+            log::warn!(
+                "Could not discard {} ({}); only removing signatures",
+                device.path.display(),
+                e
+            );
+        }
+    }
+
+    /// Zero the first and last [`LABEL_AREA`] bytes of a `size` byte device
+    fn zero_ends(&self, path: &Path, size: u64) -> Result<()> {
+        let length = size.min(LABEL_AREA);
+        let zero = |offset: u64| {
+            self.execute(
+                Command::new("dd")
+                    .arg("if=/dev/zero")
+                    .arg(format!("of={}", path.display()))
+                    .arg("bs=1M")
+                    .arg(format!("count={}", length))
+                    .arg(format!("seek={}", offset))
+                    .arg("iflag=count_bytes")
+                    .arg("oflag=seek_bytes")
+                    .arg("conv=fsync"),
+            )
+        };
+        zero(0)?;
+        if size > length {
+            zero(size - length)?;
+        }
         Ok(())
     }
 
@@ -88,6 +366,44 @@ impl DiskOperations {
         Ok(())
     }
 
+    /// Read the partition table already on a device
+    ///
+    /// `sgdisk --print` only reads, so on the live system it runs even in
+    /// dry-run mode: a plan into free space is only as good as the table it
+    /// was made from.
+    pub fn read_gpt(&self, device: &BlockDevice) -> Result<GptLayout> {
+        let mut cmd = Command::new("sgdisk");
+        cmd.arg("--print").arg(&device.path);
+        let mut result = self.execute(&mut cmd)?;
+        if result.is_skipped() && self.sys_root == SysRoot::default() {
+            result = SystemExecutor::new(false).execute(&mut cmd)?;
+        }
+
+        let output = result.stdout_string().ok_or_else(|| {
+            InstallerError::disk(
+                format!("read the partition table of {}", device.path.display()),
+                "not read in dry-run mode".to_string(),
+            )
+        })?;
+        GptLayout::parse(&device.path, &output)
+    }
+
+    /// Delete partitions added to a device, leaving the rest of its table
+    pub fn delete_partitions(&self, device: &BlockDevice, numbers: &[u32]) -> Result<()> {
+        log::info!(
+            "Deleting partitions {:?} from {}",
+            numbers,
+            device.path.display()
+        );
+
+        let mut cmd = Command::new("sgdisk");
+        for number in numbers {
+            cmd.arg(format!("--delete={}", number));
+        }
+        self.execute(cmd.arg(&device.path))?;
+        self.settle(&device.path)
+    }
+
     /// Create a partition
     pub fn create_partition(&self, device: &BlockDevice, spec: &PartitionSpec) -> Result<PathBuf> {
         log::info!(
@@ -117,78 +433,140 @@ impl DiskOperations {
         self.execute(&mut cmd)?;
 
         // Wait for kernel to update
-        self.execute(Command::new("partprobe").arg(&device.path))?;
+        self.settle(&device.path)?;
 
-        // Construct partition path
-        let partition_path = if device.name.starts_with("nvme") {
-            PathBuf::from(format!("{}p{}", device.path.display(), spec.number))
-        } else {
-            PathBuf::from(format!("{}{}", device.path.display(), spec.number))
-        };
+        let partition_path = partition_path(device, spec.number);
+        self.wait_for_device(&partition_path, self.device_timeout)?;
 
         Ok(partition_path)
     }
 
     /// Create standard ZBM partitions on a device
+    ///
+    /// The device should have been wiped with [`wipe_device`](Self::wipe_device).
+    ///
+    /// `table` comes from [`PartitionTable::plan`] for the same device: EFI,
+    /// swap (if enabled), ZFS, with a 1MiB BIOS boot partition for GRUB first
+    /// on hybrid layouts. BIOS layouts have an ext4 boot partition for
+    /// syslinux in place of the ESP. A table from [`PartitionTable::plan_free_space`]
+    /// keeps the device's GPT and only adds the partitions that aren't
+    /// already there.
     pub fn create_zbm_partitions(
         &self,
         device: &BlockDevice,
-        efi_size: ByteSize,
-        swap_size: ByteSize,
+        table: &PartitionTable,
     ) -> Result<ZbmPartitions> {
         log::info!("Creating ZBM partitions on {}", device.path.display());
 
-        // Wipe device first
-        self.wipe_device(device)?;
-
-        // Create GPT
-        self.create_gpt(device)?;
+        if !table.keep_existing {
+            self.create_gpt(device)?;
+        }
 
-        // Partition 1: EFI System Partition
-        let efi_spec = PartitionSpec {
-            number: 1,
-            start: "1MiB".to_string(),
-            end: format!("+{}MiB", efi_size.0 / (1024 * 1024)),
-            type_guid: Some("EF00".to_string()), // EFI System
-            name: Some("EFI".to_string()),
+        let mut partitions = ZbmPartitions {
+            bios_boot: None,
+            efi: None,
+            boot: None,
+            swap: None,
+            zfs: PathBuf::new(),
         };
-        let efi_path = self.create_partition(device, &efi_spec)?;
-
-        // Partition 2: Swap (if size > 0)
-        let swap_path = if swap_size.0 > 0 {
-            let swap_spec = PartitionSpec {
-                number: 2,
-                start: "0".to_string(), // Auto-start after previous
-                end: format!("+{}GiB", swap_size.0 / (1024 * 1024 * 1024)),
-                type_guid: Some("8200".to_string()), // Linux swap
-                name: Some("swap".to_string()),
+        for planned in &table.partitions {
+            let path = if planned.existing {
+                partition_path(device, planned.number)
+            } else {
+                let spec = PartitionSpec {
+                    number: planned.number,
+                    start: planned.start.to_string(),
+                    end: planned.end.to_string(),
+                    type_guid: Some(planned.role.type_code().to_string()),
+                    name: Some(table.name_of(planned).to_string()),
+                };
+                self.create_partition(device, &spec)?
             };
-            Some(self.create_partition(device, &swap_spec)?)
-        } else {
-            None
-        };
+            match planned.role {
+                PartitionRole::BiosBoot => partitions.bios_boot = Some(path),
+                PartitionRole::Efi => partitions.efi = Some(path),
+                PartitionRole::Boot => partitions.boot = Some(path),
+                PartitionRole::Swap => partitions.swap = Some(path),
+                PartitionRole::Zfs => partitions.zfs = path,
+            }
+        }
 
-        // Partition 3 (or 2 if no swap): ZFS pool
-        let zfs_part_num = if swap_path.is_some() { 3 } else { 2 };
-        let zfs_spec = PartitionSpec {
-            number: zfs_part_num,
-            start: "0".to_string(),
-            end: "0".to_string(),                // Use remaining space
-            type_guid: Some("BF00".to_string()), // Solaris root (ZFS)
-            name: Some("zfs".to_string()),
+        Ok(partitions)
+    }
+
+    /// Find the ZBM partitions an earlier installation left on a device
+    ///
+    /// Partitions are told apart by their filesystem: the ESP is FAT, the
+    /// boot partition of a BIOS layout ext4 and the pool partition a ZFS
+    /// member. Nothing is probed in dry-run mode, so the layout
+    /// [`create_zbm_partitions`](Self::create_zbm_partitions) creates is
+    /// assumed.
+    pub fn find_zbm_partitions(
+        &self,
+        device: &BlockDevice,
+        swap: bool,
+        layout: BootLayout,
+    ) -> Result<ZbmPartitions> {
+        log::info!("Looking for ZBM partitions on {}", device.path.display());
+
+        let bios = layout == BootLayout::Bios;
+        if self.dry_run {
+            let loader = 1 + u32::from(layout.has_bios_boot());
+            let zfs = loader + 1 + u32::from(swap);
+            let loader_path = partition_path(device, loader);
+            return Ok(ZbmPartitions {
+                bios_boot: layout.has_bios_boot().then(|| partition_path(device, 1)),
+                efi: (!bios).then(|| loader_path.clone()),
+                boot: bios.then_some(loader_path),
+                swap: swap.then(|| partition_path(device, loader + 1)),
+                zfs: partition_path(device, zfs),
+            });
+        }
+
+        let mut device = device.clone();
+        device.probe_filesystems(self.executor.as_ref())?;
+        let find = |fstype: &str| {
+            device
+                .partitions
+                .iter()
+                .find(|p| p.fstype.as_deref() == Some(fstype))
+                .map(|p| p.path.clone())
+        };
+        let missing = |what: &str| InstallerError::InvalidDevice {
+            path: device.path.clone(),
+            reason: format!("No {} found", what),
         };
-        let zfs_path = self.create_partition(device, &zfs_spec)?;
 
+        let (efi, boot) = if bios {
+            let boot = find("ext4").ok_or_else(|| missing("boot partition (ext4)"))?;
+            (None, Some(boot))
+        } else {
+            let efi = find("vfat").ok_or_else(|| missing("EFI system partition (FAT)"))?;
+            (Some(efi), None)
+        };
+        // Swap encrypted with a random key has no signature, only its name
+        let swap = match find("swap") {
+            None if swap => self
+                .read_gpt(&device)?
+                .partitions
+                .iter()
+                .find(|p| p.name == CRYPTSWAP_LABEL)
+                .map(|p| partition_path(&device, p.number)),
+            found => found,
+        };
         Ok(ZbmPartitions {
-            efi: efi_path,
-            swap: swap_path,
-            zfs: zfs_path,
+            bios_boot: None,
+            efi,
+            boot,
+            swap,
+            zfs: find("zfs_member").ok_or_else(|| missing("ZFS partition"))?,
         })
     }
 
     /// Format a partition as FAT32 (for EFI)
     pub fn format_efi(&self, partition: &PathBuf) -> Result<()> {
         log::info!("Formatting EFI partition: {}", partition.display());
+        self.wait_for_device(partition, self.device_timeout)?;
 
         self.execute(
             Command::new("mkfs.vfat")
@@ -201,30 +579,141 @@ impl DiskOperations {
         Ok(())
     }
 
+    /// Format a partition as ext4 (for syslinux on BIOS layouts)
+    pub fn format_boot(&self, partition: &PathBuf) -> Result<()> {
+        log::info!("Formatting boot partition: {}", partition.display());
+        self.wait_for_device(partition, self.device_timeout)?;
+
+        // syslinux can't read ext4 filesystems with 64-bit block numbers
+        self.execute(
+            Command::new("mkfs.ext4")
+                .arg("-F")
+                .arg("-O")
+                .arg("^64bit")
+                .arg("-L")
+                .arg("boot")
+                .arg(partition),
+        )?;
+
+        Ok(())
+    }
+
     /// Create swap on a partition
     pub fn create_swap(&self, partition: &PathBuf) -> Result<()> {
         log::info!("Creating swap on: {}", partition.display());
+        self.wait_for_device(partition, self.device_timeout)?;
 
         self.execute(Command::new("mkswap").arg(partition))?;
 
         Ok(())
     }
+
+    /// Read a partition's PARTUUID (`None` in dry-run or if it has none)
+    pub fn partuuid(&self, partition: &Path) -> Result<Option<String>> {
+        let result = self.execute(
+            Command::new("blkid")
+                .arg("-s")
+                .arg("PARTUUID")
+                .arg("-o")
+                .arg("value")
+                .arg(partition),
+        )?;
+
+        Ok(result
+            .stdout_string()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty()))
+    }
+}
+
+/// Path of partition `number` on `device`
+fn partition_path(device: &BlockDevice, number: u32) -> PathBuf {
+    // kpartx names the partitions of a multipath map after the map
+    if let (ControllerType::Multipath, Some(map)) = (device.controller_type, &device.dm_name) {
+        return PathBuf::from(format!("/dev/mapper/{}-part{}", map, number));
+    }
+    if device.name.starts_with("nvme") {
+        PathBuf::from(format!("{}p{}", device.path.display(), number))
+    } else {
+        PathBuf::from(format!("{}{}", device.path.display(), number))
+    }
+}
+
+/// Partition number from a partition path (`/dev/sda2` → 2, `/dev/nvme0n1p3` → 3)
+pub fn partition_number(partition: &Path) -> Option<u32> {
+    let name = partition.file_name()?.to_string_lossy();
+    let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    name[name.len() - digits..].parse().ok()
+}
+
+/// Disk holding a partition (`/dev/sda2` → `/dev/sda`, `/dev/nvme0n1p3` → `/dev/nvme0n1`)
+///
+/// Kernel names of disks ending in a digit (NVMe, MMC) separate the partition
+/// number with `p`; `/dev/disk/by-*` links use `-part`.
+pub fn parent_disk(partition: &Path) -> Option<PathBuf> {
+    let name = partition.file_name()?.to_string_lossy();
+    let stem = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    if stem.len() == name.len() {
+        return None;
+    }
+
+    let disk = match stem.strip_suffix("-part") {
+        Some(disk) => disk,
+        None => match stem.strip_suffix('p') {
+            Some(disk) if disk.ends_with(|c: char| c.is_ascii_digit()) => disk,
+            _ => stem,
+        },
+    };
+    (!disk.is_empty()).then(|| partition.with_file_name(disk))
 }
 
 /// Result of creating ZBM partitions
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ZbmPartitions {
-    /// EFI system partition path
-    pub efi: PathBuf,
+    /// BIOS boot partition path (hybrid and BIOS layouts only)
+    pub bios_boot: Option<PathBuf>,
+    /// EFI system partition path (None on BIOS layouts)
+    pub efi: Option<PathBuf>,
+    /// ext4 boot partition path for syslinux (BIOS layouts only)
+    #[serde(default)]
+    pub boot: Option<PathBuf>,
     /// Swap partition path (None if disabled)
     pub swap: Option<PathBuf>,
     /// ZFS partition path
     pub zfs: PathBuf,
 }
 
+impl ZbmPartitions {
+    /// Every partition, BIOS boot first and the ZFS partition last
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.bios_boot
+            .iter()
+            .chain(&self.efi)
+            .chain(&self.boot)
+            .chain(&self.swap)
+            .chain([&self.zfs])
+            .map(PathBuf::as_path)
+    }
+
+    /// The partition ZFSBootMenu is installed on: the ESP, or the boot
+    /// partition of a BIOS layout
+    pub fn loader(&self) -> Option<&Path> {
+        self.efi.as_deref().or(self.boot.as_deref())
+    }
+
+    /// Disk and partition number of the ESP, as firmware boot entries need them
+    ///
+    /// On BIOS layouts this is the boot partition syslinux is installed on.
+    pub fn efi_location(&self) -> Option<(PathBuf, u32)> {
+        let loader = self.loader()?;
+        Some((parent_disk(loader)?, partition_number(loader)?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bytesize::ByteSize;
 
     #[test]
     fn test_disk_operations_dry_run() {
@@ -245,4 +734,372 @@ mod tests {
         assert_eq!(spec.number, 1);
         assert_eq!(spec.type_guid.unwrap(), "EF00");
     }
+
+    #[test]
+    fn test_hybrid_layout_numbering() {
+        let device =
+            BlockDevice::from_name_in(&crate::disk::SysRoot::fixture(), "nvme0n1").unwrap();
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        let ops = DiskOperations::new(true).with_executor(executor.clone());
+
+        let table = PartitionTable::plan(
+            &device,
+            ByteSize::mib(512),
+            ByteSize::gib(8),
+            BootLayout::Hybrid,
+        )
+        .unwrap();
+        let parts = ops.create_zbm_partitions(&device, &table).unwrap();
+        assert_eq!(parts.bios_boot, Some(PathBuf::from("/dev/nvme0n1p1")));
+        assert_eq!(parts.efi, Some(PathBuf::from("/dev/nvme0n1p2")));
+        assert_eq!(parts.swap, Some(PathBuf::from("/dev/nvme0n1p3")));
+        assert_eq!(parts.zfs, PathBuf::from("/dev/nvme0n1p4"));
+        assert_eq!(parts.efi.as_deref().and_then(partition_number), Some(2));
+        assert_eq!(partition_number(Path::new("/dev/sda12")), Some(12));
+        assert_eq!(
+            parts.efi_location(),
+            Some((PathBuf::from("/dev/nvme0n1"), 2))
+        );
+        assert_eq!(
+            parent_disk(Path::new("/dev/mmcblk0p1")),
+            Some(PathBuf::from("/dev/mmcblk0"))
+        );
+        assert_eq!(
+            parent_disk(Path::new("/dev/sda12")),
+            Some(PathBuf::from("/dev/sda"))
+        );
+        assert_eq!(
+            parent_disk(Path::new("/dev/disk/by-id/wwn-0x5002538f42a1b2c3-part2")),
+            Some(PathBuf::from("/dev/disk/by-id/wwn-0x5002538f42a1b2c3"))
+        );
+        assert_eq!(parent_disk(Path::new("/dev/sda")), None);
+
+        let new_args: Vec<String> = executor
+            .commands()
+            .iter()
+            .flat_map(|c| c.args.clone())
+            .filter(|a| a.starts_with("--new=") || a.starts_with("--typecode="))
+            .collect();
+        assert_eq!(
+            new_args,
+            vec![
+                "--new=1:2048:4095",
+                "--typecode=1:EF02",
+                "--new=2:4096:1052671",
+                "--typecode=2:EF00",
+                "--new=3:1052672:17829887",
+                "--typecode=3:8200",
+                "--new=4:17829888:1953523711",
+                "--typecode=4:BF00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uefi_layout_without_swap() {
+        let device = BlockDevice::from_name_in(&crate::disk::SysRoot::fixture(), "sda").unwrap();
+        let ops = DiskOperations::new(true)
+            .with_executor(Arc::new(crate::exec::RecordingExecutor::new()));
+
+        let table =
+            PartitionTable::plan(&device, ByteSize::gib(1), ByteSize::b(0), BootLayout::Uefi)
+                .unwrap();
+        let parts = ops.create_zbm_partitions(&device, &table).unwrap();
+        assert_eq!(parts.bios_boot, None);
+        assert_eq!(parts.efi, Some(PathBuf::from("/dev/sda1")));
+        assert_eq!(parts.swap, None);
+        assert_eq!(parts.zfs, PathBuf::from("/dev/sda2"));
+    }
+
+    #[test]
+    fn test_free_space_keeps_existing_partitions() {
+        let device = BlockDevice::from_name_in(&crate::disk::SysRoot::fixture(), "sda").unwrap();
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        executor.respond_program("sgdisk", crate::disk::gpt::SAMPLE_PRINT);
+        let ops = DiskOperations::new(true)
+            .with_sys_root(crate::disk::SysRoot::fixture())
+            .with_executor(executor.clone());
+
+        let gpt = ops.read_gpt(&device).unwrap();
+        let table = PartitionTable::plan_free_space(
+            &device,
+            &gpt,
+            ByteSize::gib(1),
+            ByteSize(0),
+            BootLayout::Uefi,
+            true,
+        )
+        .unwrap();
+        let parts = ops.create_zbm_partitions(&device, &table).unwrap();
+        assert_eq!(parts.efi, Some(PathBuf::from("/dev/sda1")));
+        assert_eq!(parts.zfs, PathBuf::from("/dev/sda3"));
+
+        ops.delete_partitions(&device, &[3]).unwrap();
+        let sgdisk: Vec<String> = executor
+            .commands()
+            .iter()
+            .filter(|c| c.program == "sgdisk")
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            sgdisk,
+            vec![
+                "sgdisk --print /dev/sda",
+                "sgdisk /dev/sda --new=3:410650624:976773119 --typecode=3:BF00 --change-name=3:zfs",
+                "sgdisk --delete=3 /dev/sda",
+            ]
+        );
+    }
+
+    fn wipe(name: &str, mode: WipeMode, executor: Arc<crate::exec::RecordingExecutor>) {
+        let device = BlockDevice::from_name_in(&crate::disk::SysRoot::fixture(), name).unwrap();
+        DiskOperations::new(false)
+            .with_executor(executor)
+            .with_wipe_mode(mode)
+            .wipe_device(&device)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_multipath_partitions_are_named_after_the_map() {
+        let dir = crate::disk::discovery::multipath_tree();
+        let device = crate::disk::DeviceDiscovery::new()
+            .unwrap()
+            .with_sys_root(crate::disk::SysRoot::new(dir.path()))
+            .find_device("mpatha")
+            .unwrap();
+        let parts = DiskOperations::new(true)
+            .find_zbm_partitions(&device, false, BootLayout::Uefi)
+            .unwrap();
+        assert_eq!(parts.efi, Some(PathBuf::from("/dev/mapper/mpatha-part1")));
+        assert_eq!(parts.zfs, PathBuf::from("/dev/mapper/mpatha-part2"));
+        assert_eq!(
+            parent_disk(&parts.zfs),
+            Some(PathBuf::from("/dev/mapper/mpatha"))
+        );
+    }
+
+    #[test]
+    fn test_wipe_modes() {
+        let commands = |name, mode| {
+            let executor = Arc::new(crate::exec::RecordingExecutor::new());
+            wipe(name, mode, executor.clone());
+            executor
+                .commands()
+                .iter()
+                .map(|c| c.argv().join(" "))
+                .filter(|c| !c.starts_with("partprobe") && !c.starts_with("udevadm"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            commands("sda", WipeMode::Signatures),
+            vec!["wipefs -a /dev/sda", "sgdisk --zap-all /dev/sda"]
+        );
+        assert_eq!(
+            commands("sda", WipeMode::Discard)[0],
+            "blkdiscard -f /dev/sda"
+        );
+        // sdb is rotational
+        assert_eq!(commands("sdb", WipeMode::Discard).len(), 2);
+
+        let zeroed: Vec<String> = commands("sda", WipeMode::ZeroLabels)
+            .into_iter()
+            .filter(|c| c.starts_with("dd"))
+            .map(|c| {
+                let of = c
+                    .split(' ')
+                    .find(|a| a.starts_with("of="))
+                    .unwrap()
+                    .to_string();
+                let seek = c.split(' ').find(|a| a.starts_with("seek=")).unwrap();
+                format!("{} {}", of, seek)
+            })
+            .collect();
+        assert_eq!(
+            zeroed,
+            vec![
+                "of=/dev/sda1 seek=0",
+                "of=/dev/sda1 seek=1063256064",
+                "of=/dev/sda2 seek=0",
+                "of=/dev/sda2 seek=499022561280",
+                "of=/dev/sda seek=0",
+                "of=/dev/sda seek=500097376256",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_busy_device_is_retried_and_unmounted() {
+        let mut device =
+            BlockDevice::from_name_in(&crate::disk::SysRoot::fixture(), "sda").unwrap();
+        device.partitions[0].mountpoint = Some(PathBuf::from("/media/user/OLD"));
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        executor.fail_program_times(
+            "wipefs",
+            2,
+            1,
+            "wipefs: error: /dev/sda: probing initialization failed: Device or resource busy",
+        );
+        let ops = DiskOperations::new(false)
+            .with_executor(executor.clone())
+            .with_retry(RetryPolicy::new(3, Duration::ZERO));
+
+        ops.wipe_device(&device).unwrap();
+        assert_eq!(
+            executor.file_ops(),
+            vec![FileOp::unmount("/media/user/OLD")]
+        );
+        let wipes = executor
+            .commands()
+            .iter()
+            .filter(|c| c.program == "wipefs")
+            .count();
+        assert_eq!(wipes, 3);
+
+        let notices = ops.take_notices();
+        assert_eq!(notices.len(), 3, "{notices:?}");
+        assert_eq!(notices[0], "Unmounting automounted /media/user/OLD");
+        assert!(notices[1].starts_with("Attempt 1/3 of wipefs -a /dev/sda failed"));
+        assert!(ops.take_notices().is_empty());
+
+        // A device that stays busy gives up after the last attempt
+        executor.fail_program("sgdisk", 1, "Device or resource busy");
+        let err = ops.wipe_device(&device).unwrap_err();
+        assert!(
+            err.to_string().contains("gave up after 3 attempts"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_failed_discard_falls_back() {
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        executor.fail_program(
+            "blkdiscard",
+            1,
+            "BLKDISCARD ioctl failed: Operation not supported",
+        );
+        wipe("nvme0n1", WipeMode::Discard, executor.clone());
+        assert!(executor.commands().iter().any(|c| c.program == "wipefs"));
+    }
+
+    #[test]
+    fn test_release_device() {
+        let dir = crate::disk::holders::stacked_tree();
+        let root = crate::disk::SysRoot::new(dir.path());
+        let device = BlockDevice::from_name_in(&root, "sdc").unwrap();
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        DiskOperations::new(false)
+            .with_executor(executor.clone())
+            .with_sys_root(root)
+            .release_device(&device)
+            .unwrap();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                "vgchange -an data-vg",
+                "cryptsetup close luks-4f3c2a1e",
+                "mdadm --stop /dev/md127",
+                "udevadm settle",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_existing_partitions() {
+        let device = BlockDevice::from_name_in(&crate::disk::SysRoot::fixture(), "sda").unwrap();
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        let ops = DiskOperations::new(false).with_executor(executor.clone());
+
+        executor.respond_program_times(
+            "blkid",
+            1,
+            "DEVNAME=/dev/sda1\nTYPE=vfat\n\nDEVNAME=/dev/sda2\nTYPE=zfs_member\nLABEL=tank\n",
+        );
+        let parts = ops
+            .find_zbm_partitions(&device, false, BootLayout::Uefi)
+            .unwrap();
+        assert_eq!(parts.efi, Some(PathBuf::from("/dev/sda1")));
+        assert_eq!(parts.swap, None);
+        assert_eq!(parts.zfs, PathBuf::from("/dev/sda2"));
+
+        executor.respond_program_times("blkid", 1, "DEVNAME=/dev/sda2\nTYPE=zfs_member\n");
+        let err = ops
+            .find_zbm_partitions(&device, false, BootLayout::Uefi)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("No EFI system partition"),
+            "{}",
+            err
+        );
+
+        // BIOS layouts have an ext4 boot partition instead
+        executor.respond_program_times(
+            "blkid",
+            1,
+            "DEVNAME=/dev/sda1\nTYPE=ext4\n\nDEVNAME=/dev/sda2\nTYPE=zfs_member\n",
+        );
+        let parts = ops
+            .find_zbm_partitions(&device, false, BootLayout::Bios)
+            .unwrap();
+        assert_eq!(parts.efi, None);
+        assert_eq!(parts.boot, Some(PathBuf::from("/dev/sda1")));
+        assert_eq!(parts.loader(), Some(Path::new("/dev/sda1")));
+    }
+
+    #[test]
+    fn test_wait_for_device() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = dir.path().join("sda3");
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        let ops = DiskOperations::new(false).with_executor(executor.clone());
+
+        // udev creates the node a little after partprobe returns
+        let late = node.clone();
+        let udev = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            std::fs::write(late, "").unwrap();
+        });
+        ops.wait_for_device(&node, Duration::from_secs(10)).unwrap();
+        udev.join().unwrap();
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(commands, vec!["udevadm settle"]);
+
+        // Already there: nothing to settle
+        ops.wait_for_device(&node, Duration::from_secs(10)).unwrap();
+        assert_eq!(executor.commands().len(), 1);
+    }
+
+    #[test]
+    fn test_wait_for_device_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = dir.path().join("sda3");
+        let ops = DiskOperations::new(false)
+            .with_executor(Arc::new(crate::exec::RecordingExecutor::new()));
+
+        let started = Instant::now();
+        let err = ops
+            .wait_for_device(&node, Duration::from_millis(200))
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(matches!(err, InstallerError::DiskError { .. }));
+        let message = err.to_string();
+        assert!(message.contains(&node.display().to_string()), "{message}");
+        assert!(message.contains("did not appear within 0.2s"), "{message}");
+
+        // Nothing is created in dry-run mode, so nothing is waited for
+        let dry = DiskOperations::new(true);
+        dry.wait_for_device(&node, Duration::from_secs(60)).unwrap();
+    }
 }
@@ -0,0 +1,731 @@
+//! Partition table layout
+//!
+//! The ZBM layout is worked out to the sector before anything is written, so
+//! it can be checked against the disk first and shown in dry-run plans.
+
+use crate::disk::block_device::BlockDevice;
+use crate::disk::gpt::{GptLayout, BIOS_BOOT_TYPE_CODE};
+use crate::error::{InstallerError, Result};
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+
+/// Partitions start and end on 1 MiB boundaries, or on physical sector
+/// boundaries where those are larger
+pub const ALIGNMENT: u64 = 1024 * 1024;
+
+/// Size of the BIOS boot partition GRUB embeds its core image in
+pub const BIOS_BOOT_SIZE: ByteSize = ByteSize::mib(1);
+
+/// Smallest pool partition worth installing to
+pub const MIN_ZFS_SIZE: ByteSize = ByteSize::gib(10);
+
+/// Bytes of partition entries in a GPT (128 entries of 128 bytes)
+const GPT_ENTRIES_SIZE: u64 = 16 * 1024;
+
+/// Boot partitions at the start of a layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootLayout {
+    /// An EFI system partition
+    Uefi,
+    /// A BIOS boot partition for GRUB, then an EFI system partition
+    Hybrid,
+    /// A BIOS boot partition, then an ext4 partition for syslinux
+    Bios,
+}
+
+impl BootLayout {
+    /// Whether the layout starts with a BIOS boot partition
+    pub fn has_bios_boot(&self) -> bool {
+        !matches!(self, Self::Uefi)
+    }
+
+    /// The partition ZFSBootMenu is installed on
+    pub fn loader_role(&self) -> PartitionRole {
+        match self {
+            Self::Bios => PartitionRole::Boot,
+            Self::Uefi | Self::Hybrid => PartitionRole::Efi,
+        }
+    }
+}
+
+/// GPT name of a swap partition encrypted with a random key
+pub const CRYPTSWAP_LABEL: &str = "cryptswap";
+
+/// What a partition is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartitionRole {
+    /// The BIOS boot partition (hybrid and BIOS layouts only)
+    BiosBoot,
+    /// EFI system partition
+    Efi,
+    /// ext4 partition holding syslinux and ZFSBootMenu (BIOS layouts only)
+    Boot,
+    /// Swap
+    Swap,
+    /// The pool
+    Zfs,
+}
+
+impl PartitionRole {
+    /// sgdisk type code
+    pub fn type_code(&self) -> &'static str {
+        match self {
+            Self::BiosBoot => "EF02",
+            Self::Efi => "EF00",
+            Self::Boot => "8300",
+            Self::Swap => "8200",
+            Self::Zfs => "BF00",
+        }
+    }
+
+    /// GPT partition name
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::BiosBoot => "BIOS",
+            Self::Efi => "EFI",
+            Self::Boot => "boot",
+            Self::Swap => "swap",
+            Self::Zfs => "zfs",
+        }
+    }
+}
+
+impl std::fmt::Display for PartitionRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BiosBoot => write!(f, "bios-boot"),
+            Self::Efi => write!(f, "efi"),
+            Self::Boot => write!(f, "boot"),
+            Self::Swap => write!(f, "swap"),
+            Self::Zfs => write!(f, "zfs"),
+        }
+    }
+}
+
+/// One partition of a planned table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedPartition {
+    /// Partition number
+    pub number: u32,
+    /// What the partition is for
+    pub role: PartitionRole,
+    /// First sector
+    pub start: u64,
+    /// Last sector (inclusive)
+    pub end: u64,
+    /// Already on the disk and used as it is
+    #[serde(default)]
+    pub existing: bool,
+}
+
+impl PlannedPartition {
+    /// Number of sectors
+    pub fn sectors(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// A GPT layout for one disk, in logical sectors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionTable {
+    /// Logical sector size of the disk
+    pub sector_size: u64,
+    /// Bytes every partition start and size is a multiple of
+    pub alignment: u64,
+    /// Partitions, in order
+    pub partitions: Vec<PlannedPartition>,
+    /// The disk's other partitions stay (installing into free space)
+    #[serde(default)]
+    pub keep_existing: bool,
+    /// The swap partition is encrypted with a random key, and named
+    /// [`CRYPTSWAP_LABEL`] since it has no signature to be found by
+    #[serde(default)]
+    pub encrypted_swap: bool,
+}
+
+impl PartitionTable {
+    /// Lay out the ZBM partitions on `device`
+    ///
+    /// Everything is aligned to [`ALIGNMENT`] or the drive's physical sector,
+    /// whichever is larger. The ESP (or the syslinux partition of a BIOS
+    /// layout) and swap get the requested size rounded down to that, the pool
+    /// the rest of the disk. Fails if a size rounds down to nothing or the
+    /// pool partition would be smaller than [`MIN_ZFS_SIZE`].
+    pub fn plan(
+        device: &BlockDevice,
+        efi_size: ByteSize,
+        swap_size: ByteSize,
+        layout: BootLayout,
+    ) -> Result<Self> {
+        let sector_size = u64::from(device.logical_block_size.max(512));
+        let alignment = Self::alignment_for(device);
+        let align = alignment / sector_size;
+        let total_sectors = device.size / sector_size;
+        // The backup GPT: header plus entries at the end of the disk
+        let last_usable = total_sectors.saturating_sub(2 + GPT_ENTRIES_SIZE / sector_size);
+
+        let mut sized = Vec::new();
+        if layout.has_bios_boot() {
+            sized.push((
+                PartitionRole::BiosBoot,
+                whole_units(
+                    BIOS_BOOT_SIZE.max(ByteSize(alignment)),
+                    alignment,
+                    "BIOS boot",
+                )?,
+            ));
+        }
+        sized.push(loader_partition(layout, efi_size, alignment)?);
+        if swap_size.0 > 0 {
+            sized.push((
+                PartitionRole::Swap,
+                whole_units(swap_size, alignment, "Swap partition")?,
+            ));
+        }
+
+        let mut partitions = Vec::new();
+        let mut start = align;
+        for (role, units) in sized {
+            let sectors = units * align;
+            partitions.push(PlannedPartition {
+                number: partitions.len() as u32 + 1,
+                role,
+                start,
+                end: start + sectors - 1,
+                existing: false,
+            });
+            start += sectors;
+        }
+
+        // The pool takes the rest, ending on an alignment boundary
+        let end = ((last_usable + 1) / align * align).saturating_sub(1);
+        let zfs_size = (end + 1).saturating_sub(start) * sector_size;
+        if zfs_size < MIN_ZFS_SIZE.0 {
+            let needed = start * sector_size + MIN_ZFS_SIZE.0 + alignment;
+            return Err(InstallerError::InvalidDevice {
+                path: device.path.clone(),
+                reason: format!(
+                    "Device is too small for the partition layout ({}, need at least {})",
+                    ByteSize(device.size),
+                    ByteSize(needed)
+                ),
+            });
+        }
+        partitions.push(PlannedPartition {
+            number: partitions.len() as u32 + 1,
+            role: PartitionRole::Zfs,
+            start,
+            end,
+            existing: false,
+        });
+
+        Ok(Self {
+            sector_size,
+            alignment,
+            partitions,
+            keep_existing: false,
+            encrypted_swap: false,
+        })
+    }
+
+    /// Lay out the ZBM partitions in the largest free region of `gpt`
+    ///
+    /// The partitions already on `device` stay where they are, and the new
+    /// ones get the lowest unused numbers. With `reuse_esp`, the disk's first
+    /// EFI system partition is used instead of creating one (BIOS layouts
+    /// always get a new syslinux partition); an existing BIOS boot partition
+    /// always is. Fails like [`plan`](Self::plan) if the region can't hold
+    /// the pool partition.
+    pub fn plan_free_space(
+        device: &BlockDevice,
+        gpt: &GptLayout,
+        efi_size: ByteSize,
+        swap_size: ByteSize,
+        layout: BootLayout,
+        reuse_esp: bool,
+    ) -> Result<Self> {
+        let sector_size = gpt.sector_size;
+        let alignment = Self::alignment_for(device).max(sector_size);
+        let align = alignment / sector_size;
+
+        let mut partitions = Vec::new();
+        let mut sized = Vec::new();
+        let existing = |role: PartitionRole, code: Option<&str>| {
+            let entry = match code {
+                Some(code) => gpt.find_type(code),
+                None => gpt.esp(),
+            }?;
+            Some(PlannedPartition {
+                number: entry.number,
+                role,
+                start: entry.start,
+                end: entry.end,
+                existing: true,
+            })
+        };
+        if layout.has_bios_boot() {
+            match existing(PartitionRole::BiosBoot, Some(BIOS_BOOT_TYPE_CODE)) {
+                Some(partition) => partitions.push(partition),
+                None => sized.push((
+                    PartitionRole::BiosBoot,
+                    whole_units(
+                        BIOS_BOOT_SIZE.max(ByteSize(alignment)),
+                        alignment,
+                        "BIOS boot",
+                    )?,
+                )),
+            }
+        }
+        let esp = existing(PartitionRole::Efi, None);
+        match esp.filter(|_| reuse_esp && layout.loader_role() == PartitionRole::Efi) {
+            Some(partition) => partitions.push(partition),
+            None => sized.push(loader_partition(layout, efi_size, alignment)?),
+        }
+        if swap_size.0 > 0 {
+            sized.push((
+                PartitionRole::Swap,
+                whole_units(swap_size, alignment, "Swap partition")?,
+            ));
+        }
+
+        // The new partitions and the smallest worthwhile pool partition
+        let needed = sized
+            .iter()
+            .map(|(_, units)| units * alignment)
+            .sum::<u64>()
+            + MIN_ZFS_SIZE.0;
+        let region = gpt.largest_free_region(align);
+        let free = region.map_or(0, |(start, end)| (end + 1 - start) * sector_size);
+        let (mut start, region_end) = match region {
+            Some(region) if free >= needed => region,
+            _ => {
+                return Err(InstallerError::InvalidDevice {
+                    path: device.path.clone(),
+                    reason: format!(
+                        "Largest free region is too small for the partition layout ({}, need at least {})",
+                        ByteSize(free),
+                        ByteSize(needed)
+                    ),
+                })
+            }
+        };
+
+        let mut numbers = gpt.next_numbers(sized.len() + 1).into_iter();
+        for (role, units) in sized {
+            let sectors = units * align;
+            partitions.push(PlannedPartition {
+                number: numbers.next().unwrap_or_default(),
+                role,
+                start,
+                end: start + sectors - 1,
+                existing: false,
+            });
+            start += sectors;
+        }
+
+        partitions.push(PlannedPartition {
+            number: numbers.next().unwrap_or_default(),
+            role: PartitionRole::Zfs,
+            start,
+            end: region_end,
+            existing: false,
+        });
+
+        Ok(Self {
+            sector_size,
+            alignment,
+            partitions,
+            keep_existing: true,
+            encrypted_swap: false,
+        })
+    }
+
+    /// Bytes every partition on `device` is aligned to: [`ALIGNMENT`] or the
+    /// drive's physical sector, whichever is larger
+    pub fn alignment_for(device: &BlockDevice) -> u64 {
+        ALIGNMENT.max(u64::from(device.effective_sector_size()))
+    }
+
+    /// Partitions this layout adds to the disk
+    pub fn new_partitions(&self) -> impl Iterator<Item = &PlannedPartition> {
+        self.partitions.iter().filter(|p| !p.existing)
+    }
+
+    /// The partition with `role`, if the layout has one
+    pub fn get(&self, role: PartitionRole) -> Option<&PlannedPartition> {
+        self.partitions.iter().find(|p| p.role == role)
+    }
+
+    /// Size of a partition
+    pub fn size_of(&self, partition: &PlannedPartition) -> ByteSize {
+        ByteSize(partition.sectors() * self.sector_size)
+    }
+
+    /// Name the swap partition for random-key encryption
+    pub fn with_encrypted_swap(mut self, encrypted_swap: bool) -> Self {
+        self.encrypted_swap = encrypted_swap;
+        self
+    }
+
+    /// GPT partition name of a partition
+    pub fn name_of(&self, partition: &PlannedPartition) -> &'static str {
+        match partition.role {
+            PartitionRole::Swap if self.encrypted_swap => CRYPTSWAP_LABEL,
+            role => role.label(),
+        }
+    }
+}
+
+impl std::fmt::Display for PartitionTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>2}  {:>12}  {:>12}  {:>10}  {:<4}  Name",
+            "#", "Start", "End", "Size", "Type"
+        )?;
+        for p in &self.partitions {
+            writeln!(
+                f,
+                "{:>2}  {:>12}  {:>12}  {:>10}  {:<4}  {}{}",
+                p.number,
+                p.start,
+                p.end,
+                self.size_of(p).to_string(),
+                p.role.type_code(),
+                self.name_of(p),
+                if p.existing { " (existing)" } else { "" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The ESP, or the syslinux partition of a BIOS layout, in whole `alignment`s
+fn loader_partition(
+    layout: BootLayout,
+    size: ByteSize,
+    alignment: u64,
+) -> Result<(PartitionRole, u64)> {
+    let role = layout.loader_role();
+    let what = match role {
+        PartitionRole::Boot => "Boot partition",
+        _ => "EFI partition",
+    };
+    Ok((role, whole_units(size, alignment, what)?))
+}
+
+/// Whole `unit`s in `size`, refusing sizes that would round down to nothing
+fn whole_units(size: ByteSize, unit: u64, what: &str) -> Result<u64> {
+    let units = size.0 / unit;
+    if units == 0 {
+        return Err(InstallerError::config(format!(
+            "{} size {} is less than the {} alignment",
+            what,
+            size,
+            ByteSize(unit)
+        )));
+    }
+    if !size.0.is_multiple_of(unit) {
+        log::warn!(
+            "{} size {} is not a multiple of the {} alignment; using {}",
+            what,
+            size,
+            ByteSize(unit),
+            ByteSize(units * unit)
+        );
+    }
+    Ok(units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::SysRoot;
+
+    fn device(name: &str) -> BlockDevice {
+        BlockDevice::from_name_in(&SysRoot::fixture(), name).unwrap()
+    }
+
+    #[test]
+    fn test_exact_sizes() {
+        let sda = device("sda");
+        let table = PartitionTable::plan(
+            &sda,
+            ByteSize::mib(512),
+            ByteSize::mib(1536),
+            BootLayout::Hybrid,
+        )
+        .unwrap();
+
+        let sizes: Vec<(PartitionRole, u64)> = table
+            .partitions
+            .iter()
+            .map(|p| (p.role, table.size_of(p).0))
+            .collect();
+        assert_eq!(sizes[0], (PartitionRole::BiosBoot, 1024 * 1024));
+        assert_eq!(sizes[1], (PartitionRole::Efi, 512 * 1024 * 1024));
+        assert_eq!(sizes[2], (PartitionRole::Swap, 1536 * 1024 * 1024));
+        assert_eq!(sizes[3].0, PartitionRole::Zfs);
+
+        // Back to back, every boundary on 1 MiB
+        let align = ALIGNMENT / table.sector_size;
+        assert_eq!(table.partitions[0].start, 2048);
+        for pair in table.partitions.windows(2) {
+            assert_eq!(pair[1].start, pair[0].end + 1);
+        }
+        for p in &table.partitions {
+            assert_eq!(p.start % align, 0);
+            assert_eq!((p.end + 1) % align, 0);
+        }
+        let zfs = table.get(PartitionRole::Zfs).unwrap();
+        assert!(zfs.end * table.sector_size < sda.size);
+        assert_eq!(zfs.number, 4);
+    }
+
+    #[test]
+    fn test_4kn_and_large_physical_sectors() {
+        // 4Kn: 256 sectors to the MiB
+        let mut disk = device("sda");
+        disk.logical_block_size = 4096;
+        disk.physical_block_size = 4096;
+        let table = PartitionTable::plan(
+            &disk,
+            ByteSize::mib(512),
+            ByteSize::mib(1536),
+            BootLayout::Uefi,
+        )
+        .unwrap();
+        assert_eq!(table.sector_size, 4096);
+        assert_eq!(table.partitions[0].start, 256);
+        assert_eq!(table.size_of(&table.partitions[1]), ByteSize::mib(1536));
+
+        // Physical sectors above 1 MiB set the alignment
+        disk.physical_block_size = 2 * 1024 * 1024;
+        let table = PartitionTable::plan(
+            &disk,
+            ByteSize::mib(513),
+            ByteSize::mib(1536),
+            BootLayout::Hybrid,
+        )
+        .unwrap();
+        assert_eq!(table.alignment, 2 * 1024 * 1024);
+        for p in &table.partitions {
+            assert_eq!(p.start * 4096 % table.alignment, 0);
+            assert_eq!(table.size_of(p).0 % table.alignment, 0);
+        }
+        let efi = table.get(PartitionRole::Efi).unwrap();
+        assert_eq!(table.size_of(efi), ByteSize::mib(512));
+        assert!(
+            PartitionTable::plan(&disk, ByteSize::mib(1), ByteSize(0), BootLayout::Uefi).is_err()
+        );
+    }
+
+    #[test]
+    fn test_sizes_below_a_mib_are_rejected() {
+        let sda = device("sda");
+        assert!(
+            PartitionTable::plan(&sda, ByteSize::kib(512), ByteSize(0), BootLayout::Uefi).is_err()
+        );
+        assert!(
+            PartitionTable::plan(&sda, ByteSize::gib(1), ByteSize::kib(900), BootLayout::Uefi)
+                .is_err()
+        );
+
+        // Whole MiB are kept, the remainder dropped
+        let table = PartitionTable::plan(
+            &sda,
+            ByteSize::gib(1),
+            ByteSize::kib(1536),
+            BootLayout::Uefi,
+        )
+        .unwrap();
+        let swap = table.get(PartitionRole::Swap).unwrap();
+        assert_eq!(table.size_of(swap), ByteSize::mib(1));
+    }
+
+    #[test]
+    fn test_disk_too_small_for_layout() {
+        // The fixture's sdb is a 16GB stick
+        let sdb = device("sdb");
+        assert!(
+            PartitionTable::plan(&sdb, ByteSize::gib(1), ByteSize::gib(2), BootLayout::Uefi)
+                .is_ok()
+        );
+        let err = PartitionTable::plan(&sdb, ByteSize::gib(1), ByteSize::gib(8), BootLayout::Uefi)
+            .unwrap_err();
+        assert!(err.to_string().contains("too small"), "{err}");
+    }
+
+    #[test]
+    fn test_free_space_layout() {
+        let sda = device("sda");
+        let gpt = GptLayout::parse(&sda.path, crate::disk::gpt::SAMPLE_PRINT).unwrap();
+
+        let table = PartitionTable::plan_free_space(
+            &sda,
+            &gpt,
+            ByteSize::gib(1),
+            ByteSize::gib(2),
+            BootLayout::Uefi,
+            true,
+        )
+        .unwrap();
+        assert!(table.keep_existing);
+        let layout: Vec<(u32, PartitionRole, bool)> = table
+            .partitions
+            .iter()
+            .map(|p| (p.number, p.role, p.existing))
+            .collect();
+        assert_eq!(
+            layout,
+            vec![
+                (1, PartitionRole::Efi, true),
+                (3, PartitionRole::Swap, false),
+                (4, PartitionRole::Zfs, false),
+            ]
+        );
+        // New partitions start after the Windows partition
+        let swap = table.get(PartitionRole::Swap).unwrap();
+        assert_eq!(swap.start, 410650624);
+        assert_eq!(table.size_of(swap), ByteSize::gib(2));
+        assert_eq!(table.new_partitions().count(), 2);
+        assert!(table.to_string().contains("EFI (existing)"));
+
+        // Without reuse a second ESP is created
+        let table = PartitionTable::plan_free_space(
+            &sda,
+            &gpt,
+            ByteSize::gib(1),
+            ByteSize(0),
+            BootLayout::Hybrid,
+            false,
+        )
+        .unwrap();
+        let numbers: Vec<(u32, PartitionRole)> =
+            table.new_partitions().map(|p| (p.number, p.role)).collect();
+        assert_eq!(
+            numbers,
+            vec![
+                (3, PartitionRole::BiosBoot),
+                (4, PartitionRole::Efi),
+                (5, PartitionRole::Zfs),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bios_layout_has_a_boot_partition_instead_of_an_esp() {
+        let sda = device("sda");
+        let table =
+            PartitionTable::plan(&sda, ByteSize::mib(512), ByteSize::gib(2), BootLayout::Bios)
+                .unwrap();
+        let roles: Vec<PartitionRole> = table.partitions.iter().map(|p| p.role).collect();
+        assert_eq!(
+            roles,
+            vec![
+                PartitionRole::BiosBoot,
+                PartitionRole::Boot,
+                PartitionRole::Swap,
+                PartitionRole::Zfs,
+            ]
+        );
+        let boot = table.get(PartitionRole::Boot).unwrap();
+        assert_eq!(table.size_of(boot), ByteSize::mib(512));
+        assert_eq!(boot.role.type_code(), "8300");
+
+        // The disk's ESP is of no use to syslinux
+        let gpt = GptLayout::parse(&sda.path, crate::disk::gpt::SAMPLE_PRINT).unwrap();
+        let table = PartitionTable::plan_free_space(
+            &sda,
+            &gpt,
+            ByteSize::mib(512),
+            ByteSize(0),
+            BootLayout::Bios,
+            true,
+        )
+        .unwrap();
+        let numbers: Vec<(u32, PartitionRole)> =
+            table.new_partitions().map(|p| (p.number, p.role)).collect();
+        assert_eq!(
+            numbers,
+            vec![
+                (3, PartitionRole::BiosBoot),
+                (4, PartitionRole::Boot),
+                (5, PartitionRole::Zfs),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_free_space_too_small() {
+        let sda = device("sda");
+        let mut gpt = GptLayout::parse(&sda.path, crate::disk::gpt::SAMPLE_PRINT).unwrap();
+        // Leave about 10 GiB free at the end of the disk
+        gpt.partitions[1].end = gpt.last_usable - 10 * 1024 * 1024 * 2;
+
+        let plan = |swap| {
+            PartitionTable::plan_free_space(
+                &sda,
+                &gpt,
+                ByteSize::gib(1),
+                swap,
+                BootLayout::Uefi,
+                true,
+            )
+        };
+        let err = plan(ByteSize::gib(2)).unwrap_err();
+        assert!(err.to_string().contains("free region"), "{err}");
+        // Trimmed to 1 MiB boundaries, the region falls short even without swap
+        assert!(plan(ByteSize(0)).is_err());
+
+        gpt.partitions[1].end = gpt.last_usable - 12 * 1024 * 1024 * 2;
+        let table = PartitionTable::plan_free_space(
+            &sda,
+            &gpt,
+            ByteSize::gib(1),
+            ByteSize(0),
+            BootLayout::Uefi,
+            true,
+        )
+        .unwrap();
+        assert_eq!(table.partitions.len(), 2);
+    }
+
+    #[test]
+    fn test_display() {
+        let table = PartitionTable::plan(
+            &device("sda"),
+            ByteSize::gib(1),
+            ByteSize(0),
+            BootLayout::Uefi,
+        )
+        .unwrap();
+        let text = table.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[1].contains("2048") && lines[1].contains("EF00"),
+            "{text}"
+        );
+        assert!(lines[2].ends_with("zfs"), "{text}");
+    }
+
+    #[test]
+    fn test_encrypted_swap_is_named_apart() {
+        let table = PartitionTable::plan(
+            &device("sda"),
+            ByteSize::gib(1),
+            ByteSize::gib(2),
+            BootLayout::Uefi,
+        )
+        .unwrap();
+        let swap = table.get(PartitionRole::Swap).unwrap().clone();
+        assert_eq!(table.name_of(&swap), "swap");
+
+        let table = table.with_encrypted_swap(true);
+        assert_eq!(table.name_of(&swap), CRYPTSWAP_LABEL);
+        assert_eq!(table.name_of(&table.partitions[0]), "EFI");
+        assert!(table.to_string().contains("8200  cryptswap"));
+    }
+}
@@ -0,0 +1,199 @@
+//! SMART drive health
+//!
+//! Reads the overall health assessment and the attributes that predict a
+//! failing drive from `smartctl`'s JSON output, so a pool isn't built on a
+//! disk that is already dying.
+
+use crate::error::{InstallerError, Result};
+use crate::exec::CommandExecutor;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+/// ATA attribute counting sectors remapped to spares
+const REALLOCATED_SECTORS: u64 = 5;
+
+/// ATA attribute counting unstable sectors waiting to be remapped
+const PENDING_SECTORS: u64 = 197;
+
+/// NVMe wear level from which a drive is reported as worn out
+pub const WORN_OUT_PERCENT: u64 = 90;
+
+/// smartctl exit status bits meaning the drive couldn't be queried at all
+const EXIT_QUERY_FAILED: i32 = 0b0000_0111;
+
+/// smartctl exit status bit: the overall health assessment failed
+const EXIT_FAILING: i32 = 0b0000_1000;
+
+/// smartctl exit status bit: a pre-failure attribute is at or below its threshold
+const EXIT_PREFAIL: i32 = 0b0001_0000;
+
+/// What SMART reports about a drive
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartStatus {
+    /// The drive's own overall health assessment passed
+    pub passed: bool,
+    /// A pre-failure attribute is at or below its threshold
+    pub prefail: bool,
+    /// Sectors remapped to spares (ATA)
+    pub reallocated_sectors: Option<u64>,
+    /// Sectors waiting to be remapped (ATA)
+    pub pending_sectors: Option<u64>,
+    /// Share of the rated endurance used up (NVMe)
+    pub percentage_used: Option<u64>,
+}
+
+impl SmartStatus {
+    /// Parse `smartctl -H -A -j` output
+    pub fn parse(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json).map_err(|e| {
+            InstallerError::disk("parse smartctl output".to_string(), e.to_string())
+        })?;
+        let Some(passed) = value["smart_status"]["passed"].as_bool() else {
+            return Err(InstallerError::disk(
+                "parse smartctl output",
+                "no overall health assessment",
+            ));
+        };
+
+        let attribute = |id: u64| {
+            value["ata_smart_attributes"]["table"]
+                .as_array()?
+                .iter()
+                .find(|a| a["id"].as_u64() == Some(id))?["raw"]["value"]
+                .as_u64()
+        };
+        Ok(Self {
+            passed,
+            prefail: false,
+            reallocated_sectors: attribute(REALLOCATED_SECTORS),
+            pending_sectors: attribute(PENDING_SECTORS),
+            percentage_used: value["nvme_smart_health_information_log"]["percentage_used"].as_u64(),
+        })
+    }
+
+    /// What a non-zero smartctl exit status says, when it printed nothing usable
+    ///
+    /// `None` if the drive couldn't be queried (no SMART support, a USB
+    /// bridge that doesn't pass commands through, ...).
+    pub fn from_exit_code(code: i32) -> Option<Self> {
+        if code & EXIT_QUERY_FAILED != 0 {
+            return None;
+        }
+        Some(Self {
+            passed: code & EXIT_FAILING == 0,
+            prefail: code & EXIT_PREFAIL != 0,
+            ..Default::default()
+        })
+    }
+
+    /// Attributes suggesting the drive is wearing out, described
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.prefail {
+            problems.push("a pre-failure attribute is at or below its threshold".to_string());
+        }
+        if let Some(count) = self.reallocated_sectors.filter(|&n| n > 0) {
+            problems.push(format!("{} reallocated sectors", count));
+        }
+        if let Some(count) = self.pending_sectors.filter(|&n| n > 0) {
+            problems.push(format!("{} pending sectors", count));
+        }
+        if let Some(used) = self.percentage_used.filter(|&n| n >= WORN_OUT_PERCENT) {
+            problems.push(format!("{}% of rated endurance used", used));
+        }
+        problems
+    }
+}
+
+/// Query a drive's SMART health with `smartctl`
+///
+/// `None` if the drive doesn't report SMART data, or nothing was run
+/// (dry-run mode).
+pub fn probe(executor: &dyn CommandExecutor, device: &Path) -> Result<Option<SmartStatus>> {
+    let mut cmd = Command::new("smartctl");
+    cmd.arg("-H").arg("-A").arg("-j").arg(device);
+    match executor.execute(&mut cmd) {
+        Ok(result) => result
+            .stdout_string()
+            .filter(|stdout| !stdout.trim().is_empty())
+            .map(|stdout| SmartStatus::parse(&stdout))
+            .transpose(),
+        // smartctl reports findings through its exit status, and the
+        // executor keeps no output from a failed command
+        Err(InstallerError::CommandFailed { code, .. }) => {
+            let status = SmartStatus::from_exit_code(code);
+            if status.is_none() {
+                log::info!("{} doesn't report SMART data", device.display());
+            }
+            Ok(status)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    const ATA: &str = r#"{
+        "smart_status": {"passed": true},
+        "ata_smart_attributes": {"table": [
+            {"id": 5, "name": "Reallocated_Sector_Ct", "raw": {"value": 8}},
+            {"id": 9, "name": "Power_On_Hours", "raw": {"value": 31000}},
+            {"id": 197, "name": "Current_Pending_Sector", "raw": {"value": 0}}
+        ]}
+    }"#;
+
+    const NVME: &str = r#"{
+        "smart_status": {"passed": true},
+        "nvme_smart_health_information_log": {"percentage_used": 97}
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let ata = SmartStatus::parse(ATA).unwrap();
+        assert!(ata.passed);
+        assert_eq!(ata.reallocated_sectors, Some(8));
+        assert_eq!(ata.pending_sectors, Some(0));
+        assert_eq!(ata.percentage_used, None);
+        assert_eq!(ata.problems(), vec!["8 reallocated sectors"]);
+
+        let nvme = SmartStatus::parse(NVME).unwrap();
+        assert_eq!(nvme.percentage_used, Some(97));
+        assert_eq!(nvme.problems(), vec!["97% of rated endurance used"]);
+
+        assert!(SmartStatus::parse(r#"{"device": {}}"#).is_err());
+        assert!(SmartStatus::parse("not json").is_err());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let failing = SmartStatus::from_exit_code(EXIT_FAILING | EXIT_PREFAIL).unwrap();
+        assert!(!failing.passed);
+        assert!(failing.prefail);
+        assert!(SmartStatus::from_exit_code(0b0100_0000).unwrap().passed);
+        assert_eq!(SmartStatus::from_exit_code(2), None);
+    }
+
+    #[test]
+    fn test_probe() {
+        let executor = RecordingExecutor::new();
+        executor.respond_program("smartctl", NVME);
+        let status = probe(&executor, Path::new("/dev/nvme0n1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.percentage_used, Some(97));
+        assert_eq!(
+            executor.commands()[0].argv(),
+            vec!["smartctl", "-H", "-A", "-j", "/dev/nvme0n1"]
+        );
+
+        let executor = RecordingExecutor::new();
+        executor.fail_program("smartctl", EXIT_FAILING, "");
+        let status = probe(&executor, Path::new("/dev/sda")).unwrap().unwrap();
+        assert!(!status.passed);
+    }
+}
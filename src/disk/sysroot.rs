@@ -0,0 +1,93 @@
+//! Root prefix for sysfs and procfs lookups
+//!
+//! Lets discovery run against a synthetic tree instead of the live system.
+
+use std::path::{Path, PathBuf};
+
+/// Persistent device links, as seen on the live system
+pub const BY_ID_DIR: &str = "/dev/disk/by-id";
+/// Persistent partition links, as seen on the live system
+pub const BY_PARTUUID_DIR: &str = "/dev/disk/by-partuuid";
+
+/// Root directory under which `/sys` and `/proc` are resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysRoot(PathBuf);
+
+impl SysRoot {
+    /// Create a root prefix
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self(root.into())
+    }
+
+    /// The root directory itself
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Resolve an absolute system path (e.g. "/sys/block") under this root
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        self.0.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Sysfs directory of a whole disk (e.g. /sys/block/sda)
+    pub fn sys_block(&self, name: &str) -> PathBuf {
+        self.join("/sys/block").join(name)
+    }
+
+    /// Directory listing every block device and partition
+    pub fn class_block(&self) -> PathBuf {
+        self.join("/sys/class/block")
+    }
+
+    /// Mount table
+    pub fn proc_mounts(&self) -> PathBuf {
+        self.join("/proc/mounts")
+    }
+
+    /// Directory of persistent device links named by hardware identity
+    pub fn dev_by_id(&self) -> PathBuf {
+        self.join(BY_ID_DIR)
+    }
+
+    /// Directory of persistent partition links named by GPT partition UUID
+    pub fn dev_by_partuuid(&self) -> PathBuf {
+        self.join(BY_PARTUUID_DIR)
+    }
+
+    /// Synthetic sysfs tree shipped with the tests
+    #[cfg(test)]
+    pub(crate) fn fixture() -> Self {
+        Self::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sysfs"))
+    }
+}
+
+impl Default for SysRoot {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_root_resolves_live_paths() {
+        let root = SysRoot::default();
+        assert_eq!(root.sys_block("sda"), PathBuf::from("/sys/block/sda"));
+        assert_eq!(root.class_block(), PathBuf::from("/sys/class/block"));
+        assert_eq!(root.proc_mounts(), PathBuf::from("/proc/mounts"));
+        assert_eq!(root.dev_by_id(), PathBuf::from("/dev/disk/by-id"));
+    }
+
+    #[test]
+    fn test_prefixed_root() {
+        let root = SysRoot::new("/tmp/fake");
+        assert_eq!(
+            root.sys_block("nvme0n1"),
+            PathBuf::from("/tmp/fake/sys/block/nvme0n1")
+        );
+        assert_eq!(root.join("proc/mounts"), root.proc_mounts());
+    }
+}
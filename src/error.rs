@@ -2,6 +2,8 @@
 //!
 //! Provides comprehensive error handling using thiserror for ergonomic error definitions.
 
+use crate::installer::Phase;
+use std::fmt::Write;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -63,6 +65,17 @@ pub enum InstallerError {
         stderr: String,
     },
 
+    /// Hook script failed
+    #[error("Hook {hook} ({point}) failed: {details}")]
+    HookFailed {
+        /// The hook executable
+        hook: PathBuf,
+        /// Hook point it ran at
+        point: String,
+        /// Why it failed
+        details: String,
+    },
+
     /// System error
     #[error("System error: {0}")]
     SystemError(String),
@@ -82,6 +95,136 @@ pub enum InstallerError {
     /// Generic error with context
     #[error("{0}")]
     Other(String),
+
+    /// A phase was run before the phases it depends on, or twice
+    #[error("Phase {phase} is out of order: {reason}")]
+    PhaseOrder {
+        /// The phase run out of order
+        phase: Phase,
+        /// What it was missing
+        reason: String,
+    },
+
+    /// `--resume` found no state it can continue from
+    #[error(
+        "Cannot resume: {0}. Start a fresh installation without --resume (the disks are wiped again)"
+    )]
+    ResumeMismatch(String),
+
+    /// One or more devices failed an operation applied to all of them
+    #[error("{0}")]
+    MultiDevice(MultiDeviceError),
+
+    /// Work on a device was stopped because another device failed
+    #[error("Stopped because another device failed")]
+    AnotherDeviceFailed,
+
+    /// Error annotated with the phase and device it occurred in
+    #[error("{}{source}", context_prefix(.phase, .device))]
+    WithContext {
+        /// Phase the error occurred in
+        phase: Option<Phase>,
+        /// Label of the device it occurred on
+        device: Option<String>,
+        /// The underlying error
+        source: Box<InstallerError>,
+    },
+}
+
+/// Failures collected while applying an operation to several devices
+#[derive(Debug, Default)]
+pub struct MultiDeviceError {
+    /// Device path and the error it failed with, in device order
+    pub failures: Vec<(PathBuf, InstallerError)>,
+}
+
+impl MultiDeviceError {
+    /// Create an empty collection
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a device failure
+    pub fn push(&mut self, device: PathBuf, error: InstallerError) {
+        self.failures.push((device, error));
+    }
+
+    /// Whether no device failed
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// `Ok` if no device failed, otherwise the collected failures
+    pub fn into_result(self) -> Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(InstallerError::MultiDevice(self))
+        }
+    }
+
+    /// Render the failures as a two-column table
+    pub fn render_table(&self) -> String {
+        let rows: Vec<(String, String)> = self
+            .failures
+            .iter()
+            .map(|(path, error)| match error {
+                // Prefer the richer device label over the bare path
+                InstallerError::WithContext {
+                    phase: None,
+                    device: Some(label),
+                    source,
+                } => (label.clone(), source.to_string()),
+                other => (path.display().to_string(), other.to_string()),
+            })
+            .collect();
+
+        let width = rows
+            .iter()
+            .map(|(device, _)| device.len())
+            .chain(std::iter::once("DEVICE".len()))
+            .max()
+            .unwrap_or_default();
+
+        let mut table = format!("{:<width$}  ERROR\n", "DEVICE");
+        for (device, error) in rows {
+            let mut lines = error.lines();
+            let _ = writeln!(table, "{:<width$}  {}", device, lines.next().unwrap_or(""));
+            for line in lines {
+                let _ = writeln!(table, "{:<width$}  {}", "", line);
+            }
+        }
+        table
+    }
+}
+
+impl std::fmt::Display for MultiDeviceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of the selected devices failed:\n{}",
+            self.failures.len(),
+            self.render_table().trim_end()
+        )
+    }
+}
+
+/// Render the "Phase X, device Y: " prefix of a `WithContext` error
+fn context_prefix(phase: &Option<Phase>, device: &Option<String>) -> String {
+    let mut prefix = String::new();
+    if let Some(phase) = phase {
+        let _ = write!(prefix, "Phase {}", phase);
+    }
+    if let Some(device) = device {
+        if !prefix.is_empty() {
+            prefix.push_str(", ");
+        }
+        let _ = write!(prefix, "device {}", device);
+    }
+    if !prefix.is_empty() {
+        prefix.push_str(": ");
+    }
+    prefix
 }
 
 impl InstallerError {
@@ -111,15 +254,99 @@ impl InstallerError {
         }
     }
 
+    /// Bootloader error for a failed command
+    pub fn bootloader_command(cmd: String, stderr: String) -> Self {
+        Self::BootloaderError(format!("Command failed: {}\n{}", cmd, stderr))
+    }
+
+    /// System error for a failed package manager command
+    pub fn package_command(cmd: String, stderr: String) -> Self {
+        Self::SystemError(format!("Package command failed: {}\n{}", cmd, stderr))
+    }
+
+    /// Attach the phase the error occurred in (kept if already set)
+    pub fn in_phase(self, phase: Phase) -> Self {
+        match self {
+            Self::WithContext {
+                phase: existing,
+                device,
+                source,
+            } => Self::WithContext {
+                phase: existing.or(Some(phase)),
+                device,
+                source,
+            },
+            other => Self::WithContext {
+                phase: Some(phase),
+                device: None,
+                source: Box::new(other),
+            },
+        }
+    }
+
+    /// Attach the device the error occurred on (kept if already set)
+    pub fn on_device<S: Into<String>>(self, device: S) -> Self {
+        match self {
+            Self::WithContext {
+                phase,
+                device: existing,
+                source,
+            } => Self::WithContext {
+                phase,
+                device: existing.or_else(|| Some(device.into())),
+                source,
+            },
+            other => Self::WithContext {
+                phase: None,
+                device: Some(device.into()),
+                source: Box::new(other),
+            },
+        }
+    }
+
+    /// The underlying error, without any context wrappers
+    pub fn root_cause(&self) -> &InstallerError {
+        match self {
+            Self::WithContext { source, .. } => source.root_cause(),
+            other => other,
+        }
+    }
+
+    /// Whether the user cancelled the operation (not a failure)
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.root_cause(), Self::UserCancelled)
+    }
+
     /// Check if error is recoverable
+    ///
+    /// A cancellation is not recoverable: the user asked to stop.
     pub fn is_recoverable(&self) -> bool {
         matches!(
-            self,
+            self.root_cause(),
             Self::ValidationError(_) | Self::ConfigError(_) | Self::UiError(_)
         )
     }
 }
 
+/// Extension methods for attaching context to results
+pub trait ResultExt<T> {
+    /// Attach the phase the error occurred in
+    fn in_phase(self, phase: Phase) -> Result<T>;
+
+    /// Attach the device the error occurred on
+    fn on_device<S: Into<String>>(self, device: S) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for Result<T> {
+    fn in_phase(self, phase: Phase) -> Result<T> {
+        self.map_err(|e| e.in_phase(phase))
+    }
+
+    fn on_device<S: Into<String>>(self, device: S) -> Result<T> {
+        self.map_err(|e| e.on_device(device))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +373,120 @@ mod tests {
         };
         assert!(err.to_string().contains("zpool create"));
     }
+
+    #[test]
+    fn test_multi_device_aggregation() {
+        let mut failures = MultiDeviceError::new();
+        assert!(failures.is_empty());
+
+        failures.push(
+            PathBuf::from("/dev/sdb"),
+            InstallerError::DeviceNotFound(PathBuf::from("/dev/sdb")),
+        );
+        failures.push(
+            PathBuf::from("/dev/sdc"),
+            InstallerError::CommandFailed {
+                cmd: "wipefs -a /dev/sdc".to_string(),
+                code: 1,
+                stderr: "I/O error".to_string(),
+            }
+            .on_device("/dev/sdc (Samsung SSD 870)"),
+        );
+
+        let err = failures.into_result().unwrap_err();
+        assert!(!err.is_recoverable());
+        match err {
+            InstallerError::MultiDevice(failures) => assert_eq!(failures.failures.len(), 2),
+            other => panic!("unexpected error: {other}"),
+        }
+
+        assert!(MultiDeviceError::new().into_result().is_ok());
+    }
+
+    #[test]
+    fn test_multi_device_table() {
+        let mut failures = MultiDeviceError::new();
+        failures.push(
+            PathBuf::from("/dev/sdb"),
+            InstallerError::DeviceNotFound(PathBuf::from("/dev/sdb")),
+        );
+        failures.push(
+            PathBuf::from("/dev/sdc"),
+            InstallerError::zfs("zpool labelclear /dev/sdc", "label missing")
+                .on_device("/dev/sdc (Samsung SSD 870)"),
+        );
+
+        assert_eq!(
+            failures.to_string(),
+            "2 of the selected devices failed:\n\
+             DEVICE                      ERROR\n\
+             /dev/sdb                    Device not found: /dev/sdb\n\
+             /dev/sdc (Samsung SSD 870)  ZFS operation failed: zpool labelclear /dev/sdc\n\
+             \x20                           Details: label missing"
+        );
+    }
+
+    #[test]
+    fn test_user_cancelled() {
+        let err = InstallerError::UserCancelled;
+        assert!(err.is_cancelled());
+        assert!(!err.is_recoverable());
+
+        let err = InstallerError::UserCancelled.in_phase(Phase::PrepareDisks);
+        assert!(err.is_cancelled());
+        assert!(!InstallerError::validation("bad").is_cancelled());
+    }
+
+    #[test]
+    fn test_context_display() {
+        let err = InstallerError::CommandFailed {
+            cmd: "sgdisk --zap-all /dev/sdc".to_string(),
+            code: 2,
+            stderr: "device busy".to_string(),
+        }
+        .on_device("/dev/sdc (Samsung SSD 870)")
+        .in_phase(Phase::PrepareDisks);
+
+        assert_eq!(
+            err.to_string(),
+            "Phase PrepareDisks, device /dev/sdc (Samsung SSD 870): \
+             Command 'sgdisk --zap-all /dev/sdc' failed with exit code 2: device busy"
+        );
+
+        let err = InstallerError::validation("bad").in_phase(Phase::Validate);
+        assert_eq!(err.to_string(), "Phase Validate: Validation failed: bad");
+    }
+
+    #[test]
+    fn test_context_merges_instead_of_nesting() {
+        let err: Result<()> = Err(InstallerError::DeviceNotFound(PathBuf::from("/dev/sdz")));
+        let err = err
+            .on_device("/dev/sdz")
+            .in_phase(Phase::PrepareDisks)
+            .in_phase(Phase::Finalize)
+            .unwrap_err();
+
+        match &err {
+            InstallerError::WithContext {
+                phase,
+                device,
+                source,
+            } => {
+                assert_eq!(*phase, Some(Phase::PrepareDisks));
+                assert_eq!(device.as_deref(), Some("/dev/sdz"));
+                assert!(matches!(**source, InstallerError::DeviceNotFound(_)));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_is_recoverable_sees_through_context() {
+        let err = InstallerError::config("bad pool name").in_phase(Phase::Validate);
+        assert!(err.is_recoverable());
+        assert!(matches!(err.root_cause(), InstallerError::ConfigError(_)));
+
+        let err = InstallerError::zfs("zpool create", "failed").in_phase(Phase::CreatePool);
+        assert!(!err.is_recoverable());
+    }
 }
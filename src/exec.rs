@@ -0,0 +1,1544 @@
+//! Command execution abstraction
+//!
+//! Every external program the installer runs goes through a [`CommandExecutor`].
+//! [`SystemExecutor`] runs commands on the host (honouring dry-run mode), while
+//! [`RecordingExecutor`] captures them so tests can assert on what would have run.
+//!
+//! In dry-run mode nothing is spawned and the executor returns
+//! [`ExecResult::Skipped`], so callers have to handle the missing output rather
+//! than parse a fabricated one.
+//!
+//! Changes to files go through the executor too, as [`FileOp`]s, so dry-run
+//! mode also covers them and [`PlanExecutor`] can collect every command and
+//! file change into a plan.
+//!
+//! Commands run with piped output that is streamed line by line to the log (and
+//! optionally a caller-supplied sink) as it arrives, and are killed if they run
+//! past their timeout. Timeouts default per [`CommandClass`] and can be
+//! overridden per call through [`ExecOptions`].
+//!
+//! Each command runs in its own process group, so an interrupt on the
+//! terminal doesn't reach it directly. Once SIGINT or SIGTERM has been
+//! received (see [`cancel_on_signals`](crate::installer::cancel_on_signals)),
+//! [`CommandClass::LongRunning`] commands are killed, group and all, and fail
+//! with [`InstallerError::UserCancelled`]; shorter ones are left to finish.
+
+use crate::error::{InstallerError, Result};
+use crate::installer::cancel;
+use crate::log_sink::REDACTED;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of trailing output lines kept for error messages
+const TAIL_LINES: usize = 20;
+
+/// How often a running command is checked against its deadline
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// stderr fragments (matched case-insensitively) that indicate a failure worth retrying
+const TRANSIENT_PATTERNS: &[&str] = &[
+    "device or resource busy",
+    "resource temporarily unavailable",
+    "try again",
+    "no such pool available",
+    "is currently unavailable",
+];
+
+/// How long to keep reading after exit if a background process holds the pipes
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+
+/// Broad category of command, used to pick a default timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandClass {
+    /// Partitioning, formatting and device queries; should finish in seconds
+    Quick,
+    /// `zpool` and `zfs` operations
+    Zfs,
+    /// Downloads
+    Network,
+    /// Hooks and anything not otherwise classified
+    Standard,
+    /// Copying the system, building initramfs, installing packages
+    LongRunning,
+}
+
+impl CommandClass {
+    /// Classify a command by its program name
+    pub fn for_program(program: &str) -> Self {
+        let name = Path::new(program)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "wipefs" | "sgdisk" | "partprobe" | "mkfs.vfat" | "mkswap" | "udevadm" | "blkid"
+            | "efibootmgr" | "bootctl" | "modprobe" | "mount" | "umount" | "sync" => Self::Quick,
+            "zpool" | "zfs" => Self::Zfs,
+            "curl" | "wget" => Self::Network,
+            "rsync" | "dracut" | "mkinitcpio" | "generate-zbm" | "apt-get" | "apt" | "dnf"
+            | "pacman" | "debootstrap" | "pacstrap" => Self::LongRunning,
+            _ => Self::Standard,
+        }
+    }
+
+    /// Default timeout for this class (`None` means no timeout)
+    pub fn default_timeout(&self) -> Option<Duration> {
+        match self {
+            Self::Quick => Some(Duration::from_secs(120)),
+            Self::Zfs => Some(Duration::from_secs(600)),
+            Self::Network => Some(Duration::from_secs(900)),
+            Self::Standard => Some(Duration::from_secs(1800)),
+            Self::LongRunning => None,
+        }
+    }
+}
+
+/// Stream an output line was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    /// Standard output
+    Stdout,
+    /// Standard error
+    Stderr,
+}
+
+/// Callback receiving output lines as they arrive
+pub type LineSink = Arc<dyn Fn(OutputStream, &str) + Send + Sync>;
+
+/// Callback told about each retry, with the message that is logged for it
+pub type RetrySink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Per-call execution options
+#[derive(Clone, Default)]
+pub struct ExecOptions {
+    timeout: Option<Duration>,
+    class: Option<CommandClass>,
+    line_sink: Option<LineSink>,
+    discard_stdout: bool,
+    retry: RetryPolicy,
+    retry_sink: Option<RetrySink>,
+    stdin: Option<Arc<[u8]>>,
+}
+
+impl ExecOptions {
+    /// Options using the defaults for the command's class
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the timeout for this call
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Override the command class used to pick the default timeout
+    pub fn class(mut self, class: CommandClass) -> Self {
+        self.class = Some(class);
+        self
+    }
+
+    /// Receive each output line as it arrives
+    pub fn on_line(mut self, sink: impl Fn(OutputStream, &str) + Send + Sync + 'static) -> Self {
+        self.line_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Don't buffer stdout (for chatty commands whose output is only streamed)
+    pub fn discard_stdout(mut self) -> Self {
+        self.discard_stdout = true;
+        self
+    }
+
+    /// Retry transient failures according to `policy`
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Be told about each retry as it happens
+    pub fn on_retry(mut self, sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.retry_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Feed `data` to the command's standard input
+    ///
+    /// Used for secrets such as encryption passphrases, which must never
+    /// appear in the command line (and therefore in logs).
+    pub fn stdin(mut self, data: impl AsRef<[u8]>) -> Self {
+        self.stdin = Some(Arc::from(data.as_ref()));
+        self
+    }
+
+    /// Timeout that applies to `program` under these options
+    pub fn effective_timeout(&self, program: &str) -> Option<Duration> {
+        self.timeout
+            .or_else(|| self.effective_class(program).default_timeout())
+    }
+
+    /// Class that applies to `program` under these options
+    pub fn effective_class(&self, program: &str) -> CommandClass {
+        self.class
+            .unwrap_or_else(|| CommandClass::for_program(program))
+    }
+}
+
+/// How often to retry a command that fails transiently
+///
+/// Only failures classified by [`is_transient`] are retried. The delay doubles
+/// after every attempt, starting at `backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first
+    pub attempts: u32,
+    /// Delay before the first retry
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Create a retry policy
+    pub fn new(attempts: u32, backoff: Duration) -> Self {
+        Self { attempts, backoff }
+    }
+
+    /// Run once, never retry
+    pub fn none() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+
+    /// Policy for operations known to fail transiently (partprobe, udev, bootctl)
+    pub fn transient() -> Self {
+        Self::new(3, Duration::from_secs(1))
+    }
+
+    /// Run `attempt` until it succeeds, fails permanently, or attempts run out
+    ///
+    /// Each retry is logged and passed to `on_retry`.
+    fn run<T>(
+        &self,
+        cmd_str: &str,
+        on_retry: Option<&RetrySink>,
+        mut attempt: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let attempts = self.attempts.max(1);
+        let mut delay = self.backoff;
+
+        for n in 1..=attempts {
+            match attempt() {
+                Err(e) if n < attempts && is_transient(&e) => {
+                    let message = format!(
+                        "Attempt {}/{} of {} failed, retrying in {:.1}s: {}",
+                        n,
+                        attempts,
+                        cmd_str,
+                        delay.as_secs_f64(),
+                        e
+                    );
+                    log::warn!("{}", message);
+                    if let Some(sink) = on_retry {
+                        sink(&message);
+                    }
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(InstallerError::CommandFailed { cmd, code, stderr }) if n > 1 => {
+                    return Err(InstallerError::CommandFailed {
+                        cmd,
+                        code,
+                        stderr: format!("{} (gave up after {} attempts)", stderr.trim_end(), n),
+                    });
+                }
+                result => return result,
+            }
+        }
+
+        unreachable!("retry loop always returns")
+    }
+}
+
+/// Whether an error is a transient command failure that may succeed on retry
+pub fn is_transient(err: &InstallerError) -> bool {
+    match err.root_cause() {
+        InstallerError::CommandFailed { stderr, .. } => {
+            let stderr = stderr.to_lowercase();
+            TRANSIENT_PATTERNS.iter().any(|p| stderr.contains(p))
+        }
+        _ => false,
+    }
+}
+
+/// Result of a successful [`CommandExecutor`] call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecResult {
+    /// The command was not run (dry-run mode)
+    Skipped {
+        /// Program followed by its arguments
+        argv: Vec<String>,
+    },
+    /// The command ran and exited successfully
+    Ran {
+        /// Captured standard output
+        stdout: Vec<u8>,
+        /// Captured standard error
+        stderr: Vec<u8>,
+        /// Exit status
+        status: ExitStatus,
+    },
+}
+
+impl ExecResult {
+    /// Whether the command was skipped rather than run
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, Self::Skipped { .. })
+    }
+
+    /// Standard output, or `None` if the command was skipped
+    pub fn stdout(&self) -> Option<&[u8]> {
+        match self {
+            Self::Skipped { .. } => None,
+            Self::Ran { stdout, .. } => Some(stdout),
+        }
+    }
+
+    /// Standard output as (lossy) UTF-8, or `None` if the command was skipped
+    pub fn stdout_string(&self) -> Option<String> {
+        self.stdout()
+            .map(|stdout| String::from_utf8_lossy(stdout).to_string())
+    }
+}
+
+/// A change to the filesystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum FileOp {
+    /// Create a directory and any missing parents
+    CreateDir {
+        /// Directory to create
+        path: PathBuf,
+    },
+    /// Create or replace a file
+    Write {
+        /// File to write
+        path: PathBuf,
+        /// New contents
+        contents: String,
+    },
+    /// Create or replace a binary file
+    WriteBytes {
+        /// File to write
+        path: PathBuf,
+        /// New contents
+        contents: Vec<u8>,
+    },
+    /// Copy a file
+    Copy {
+        /// Source file
+        from: PathBuf,
+        /// Destination file
+        to: PathBuf,
+    },
+    /// Create or replace a symbolic link
+    Symlink {
+        /// What the link points to
+        target: PathBuf,
+        /// The link
+        link: PathBuf,
+    },
+    /// Delete a file or directory tree if it exists
+    Remove {
+        /// File or directory to delete
+        path: PathBuf,
+    },
+    /// Change a file's permission bits
+    SetMode {
+        /// File to change
+        path: PathBuf,
+        /// Permission bits, e.g. `0o755`
+        mode: u32,
+    },
+    /// Mount a filesystem
+    Mount {
+        /// Device to mount
+        source: PathBuf,
+        /// Directory to mount it on
+        target: PathBuf,
+        /// Filesystem type, e.g. `vfat`
+        fstype: String,
+    },
+    /// Unmount a filesystem
+    Unmount {
+        /// Directory it is mounted on
+        target: PathBuf,
+    },
+}
+
+impl FileOp {
+    /// Create `path` and its parents
+    pub fn create_dir(path: impl Into<PathBuf>) -> Self {
+        Self::CreateDir { path: path.into() }
+    }
+
+    /// Write `contents` to `path`
+    pub fn write(path: impl Into<PathBuf>, contents: impl Into<String>) -> Self {
+        Self::Write {
+            path: path.into(),
+            contents: contents.into(),
+        }
+    }
+
+    /// Write binary `contents` to `path`
+    pub fn write_bytes(path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> Self {
+        Self::WriteBytes {
+            path: path.into(),
+            contents: contents.into(),
+        }
+    }
+
+    /// Copy `from` to `to`
+    pub fn copy(from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Self {
+        Self::Copy {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// Make `link` a symbolic link to `target`, replacing what was there
+    pub fn symlink(target: impl Into<PathBuf>, link: impl Into<PathBuf>) -> Self {
+        Self::Symlink {
+            target: target.into(),
+            link: link.into(),
+        }
+    }
+
+    /// Delete `path`, with everything in it if it is a directory; nothing to
+    /// do if it doesn't exist
+    pub fn remove(path: impl Into<PathBuf>) -> Self {
+        Self::Remove { path: path.into() }
+    }
+
+    /// Set the permission bits of `path`
+    pub fn set_mode(path: impl Into<PathBuf>, mode: u32) -> Self {
+        Self::SetMode {
+            path: path.into(),
+            mode,
+        }
+    }
+
+    /// Mount `source` on `target` as `fstype`
+    pub fn mount(
+        source: impl Into<PathBuf>,
+        target: impl Into<PathBuf>,
+        fstype: impl Into<String>,
+    ) -> Self {
+        Self::Mount {
+            source: source.into(),
+            target: target.into(),
+            fstype: fstype.into(),
+        }
+    }
+
+    /// Unmount the filesystem on `target`
+    pub fn unmount(target: impl Into<PathBuf>) -> Self {
+        Self::Unmount {
+            target: target.into(),
+        }
+    }
+
+    /// Make the change on the host
+    pub fn apply(&self) -> Result<()> {
+        match self {
+            Self::CreateDir { path } => fs::create_dir_all(path)?,
+            Self::Write { path, contents } => fs::write(path, contents)?,
+            Self::WriteBytes { path, contents } => fs::write(path, contents)?,
+            Self::Copy { from, to } => {
+                fs::copy(from, to)?;
+            }
+            Self::Symlink { target, link } => {
+                match fs::remove_file(link) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+                std::os::unix::fs::symlink(target, link)?;
+            }
+            Self::Remove { path } => {
+                let removed = match fs::symlink_metadata(path) {
+                    Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+                    _ => fs::remove_file(path),
+                };
+                match removed {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            Self::SetMode { path, mode } => {
+                fs::set_permissions(path, fs::Permissions::from_mode(*mode))?
+            }
+            Self::Mount {
+                source,
+                target,
+                fstype,
+            } => crate::disk::mount::mount(source, target, fstype)?,
+            Self::Unmount { target } => crate::disk::mount::unmount(target)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for FileOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CreateDir { path } => write!(f, "create directory: {}", path.display()),
+            Self::Write { path, .. } | Self::WriteBytes { path, .. } => {
+                write!(f, "write to: {}", path.display())
+            }
+            Self::Copy { from, to } => write!(f, "copy {} to {}", from.display(), to.display()),
+            Self::Symlink { target, link } => {
+                write!(f, "ln -sf {} {}", target.display(), link.display())
+            }
+            Self::Remove { path } => write!(f, "remove: {}", path.display()),
+            Self::SetMode { path, mode } => write!(f, "chmod {:o} {}", mode, path.display()),
+            Self::Mount {
+                source,
+                target,
+                fstype,
+            } => write!(
+                f,
+                "mount -t {} {} {}",
+                fstype,
+                source.display(),
+                target.display()
+            ),
+            Self::Unmount { target } => write!(f, "umount {}", target.display()),
+        }
+    }
+}
+
+/// Executes external commands and file changes
+pub trait CommandExecutor: Send + Sync {
+    /// Execute a command with explicit options, failing with `CommandFailed`
+    /// on a non-zero exit or timeout
+    fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult>;
+
+    /// Execute a command with default options
+    fn execute(&self, cmd: &mut Command) -> Result<ExecResult> {
+        self.execute_with(cmd, &ExecOptions::default())
+    }
+
+    /// Execute a command, reporting a failure through `wrap`
+    ///
+    /// `wrap` gets the command line and its stderr, so each module can report
+    /// failed commands as its own kind of error.
+    fn execute_as(
+        &self,
+        cmd: &mut Command,
+        options: &ExecOptions,
+        wrap: fn(String, String) -> InstallerError,
+    ) -> Result<ExecResult> {
+        self.execute_with(cmd, options).map_err(|e| match e {
+            InstallerError::CommandFailed { cmd, stderr, .. } => wrap(cmd, stderr),
+            other => other,
+        })
+    }
+
+    /// Make a change to the filesystem
+    fn apply(&self, op: FileOp) -> Result<()> {
+        op.apply()
+    }
+
+    /// Note something that is skipped rather than done, such as a hook in
+    /// dry-run mode; only executors that collect a plan keep it
+    fn plan(&self, _action: PlannedAction) {}
+}
+
+/// Executor that runs commands on the host system
+pub struct SystemExecutor {
+    /// Dry run mode - log commands instead of running them
+    dry_run: bool,
+}
+
+impl SystemExecutor {
+    /// Create a new system executor
+    pub fn new(dry_run: bool) -> Self {
+        Self { dry_run }
+    }
+}
+
+impl CommandExecutor for SystemExecutor {
+    fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+        let cmd_str = format!("{:?}", cmd);
+
+        if self.dry_run {
+            log::info!("[DRY RUN] Would execute: {}", cmd_str);
+            return Ok(ExecResult::Skipped {
+                argv: command_argv(cmd),
+            });
+        }
+
+        options
+            .retry
+            .run(&cmd_str, options.retry_sink.as_ref(), || {
+                log::debug!("Executing: {}", cmd_str);
+                let output = run_streaming(cmd, &cmd_str, options)?;
+
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(InstallerError::CommandFailed {
+                        cmd: cmd_str.clone(),
+                        code: output.status.code().unwrap_or(-1),
+                        stderr: stderr.to_string(),
+                    });
+                }
+
+                Ok(ExecResult::Ran {
+                    stdout: output.stdout,
+                    stderr: output.stderr,
+                    status: output.status,
+                })
+            })
+    }
+
+    fn apply(&self, op: FileOp) -> Result<()> {
+        if self.dry_run {
+            log::info!("[DRY RUN] Would {}", op);
+            return Ok(());
+        }
+
+        log::debug!("Applying: {}", op);
+        op.apply()
+    }
+}
+
+/// Something [`PlanExecutor`] was asked to do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum PlannedAction {
+    /// Run a command
+    Command {
+        /// Program followed by its arguments
+        argv: Vec<String>,
+        /// Stands in for input fed on stdin, which is never recorded
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stdin: Option<String>,
+    },
+    /// Change a file
+    File {
+        /// The change
+        #[serde(flatten)]
+        op: FileOp,
+    },
+    /// Run a hook script
+    Hook {
+        /// Hook point, e.g. `post-create-pool`
+        point: String,
+        /// The script
+        path: PathBuf,
+        /// Environment it would be started with
+        env: BTreeMap<String, String>,
+    },
+}
+
+impl std::fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Command { argv, stdin: None } => write!(f, "{}", argv.join(" ")),
+            Self::Command {
+                argv,
+                stdin: Some(stdin),
+            } => write!(f, "{} < {}", argv.join(" "), stdin),
+            Self::File { op } => write!(f, "{}", op),
+            Self::Hook { point, path, .. } => write!(f, "{} hook {}", point, path.display()),
+        }
+    }
+}
+
+/// Executor that collects a plan instead of touching the system
+///
+/// Commands are skipped as in dry-run mode, so callers behave exactly as
+/// they would in a dry run; every command and file change is kept, in order,
+/// until [`take`](Self::take) is called.
+#[derive(Default)]
+pub struct PlanExecutor {
+    actions: Mutex<Vec<PlannedAction>>,
+}
+
+impl PlanExecutor {
+    /// Create an empty plan executor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove and return the actions collected so far
+    pub fn take(&self) -> Vec<PlannedAction> {
+        std::mem::take(&mut *self.actions.lock().unwrap())
+    }
+}
+
+impl CommandExecutor for PlanExecutor {
+    fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+        let argv = command_argv(cmd);
+        log::info!("[DRY RUN] Would execute: {}", argv.join(" "));
+        self.actions.lock().unwrap().push(PlannedAction::Command {
+            argv: argv.clone(),
+            stdin: options.stdin.as_ref().map(|_| REDACTED.to_string()),
+        });
+        Ok(ExecResult::Skipped { argv })
+    }
+
+    fn apply(&self, op: FileOp) -> Result<()> {
+        log::info!("[DRY RUN] Would {}", op);
+        self.actions
+            .lock()
+            .unwrap()
+            .push(PlannedAction::File { op });
+        Ok(())
+    }
+
+    fn plan(&self, action: PlannedAction) {
+        self.actions.lock().unwrap().push(action);
+    }
+}
+
+/// Program followed by its arguments
+pub(crate) fn command_argv(cmd: &Command) -> Vec<String> {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|a| a.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Output collected from a running command
+struct Collected {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    tail: VecDeque<String>,
+}
+
+impl Collected {
+    fn push(&mut self, program: &str, stream: OutputStream, line: Vec<u8>, options: &ExecOptions) {
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end_matches(['\n', '\r']);
+
+        log::debug!("[{}] {}", program, text);
+        if let Some(sink) = &options.line_sink {
+            sink(stream, text);
+        }
+
+        if self.tail.len() == TAIL_LINES {
+            self.tail.pop_front();
+        }
+        self.tail.push_back(text.to_string());
+
+        match stream {
+            OutputStream::Stdout if !options.discard_stdout => self.stdout.extend_from_slice(&line),
+            OutputStream::Stdout => {}
+            OutputStream::Stderr => self.stderr.extend_from_slice(&line),
+        }
+    }
+}
+
+/// Forward lines from a pipe to the collecting thread
+fn spawn_reader(
+    pipe: impl Read + Send + 'static,
+    stream: OutputStream,
+    tx: Sender<(OutputStream, Vec<u8>)>,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let mut line = Vec::new();
+            match read_line_or_cr(&mut reader, &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if tx.send((stream, line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Read up to and including the next `\n`, `\r` or `\r\n`
+///
+/// Progress meters (rsync `--info=progress2`, curl) redraw a single line with
+/// bare carriage returns; splitting on them delivers each update as it happens.
+fn read_line_or_cr(reader: &mut impl BufRead, line: &mut Vec<u8>) -> std::io::Result<usize> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(line.len());
+        }
+
+        match buf.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            Some(i) => {
+                let cr = buf[i] == b'\r';
+                line.extend_from_slice(&buf[..=i]);
+                reader.consume(i + 1);
+                if cr && reader.fill_buf()?.first() == Some(&b'\n') {
+                    line.push(b'\n');
+                    reader.consume(1);
+                }
+                return Ok(line.len());
+            }
+            None => {
+                let len = buf.len();
+                line.extend_from_slice(buf);
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Kill every process in the group led by `child`, then reap it
+fn kill_group(child: &mut Child) {
+    // SAFETY: the child leads its own process group (see `run_streaming`)
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+    let _ = child.wait();
+}
+
+/// Spawn a command with piped output, streaming lines until it exits or times out
+fn run_streaming(cmd: &mut Command, cmd_str: &str, options: &ExecOptions) -> Result<Output> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let timeout = options.effective_timeout(&program);
+    let interruptible = options.effective_class(&program) == CommandClass::LongRunning;
+    let deadline = timeout.map(|t| Instant::now() + t);
+
+    let stdin = if options.stdin.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let mut child = cmd
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+    let _group = cancel::track_child_group(child.id() as i32);
+
+    // Write input from a thread so a full pipe can't block the output readers
+    if let (Some(mut pipe), Some(data)) = (child.stdin.take(), options.stdin.clone()) {
+        thread::spawn(move || {
+            let _ = pipe.write_all(&data);
+        });
+    }
+
+    let (tx, rx) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        spawn_reader(stdout, OutputStream::Stdout, tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_reader(stderr, OutputStream::Stderr, tx.clone());
+    }
+    drop(tx);
+
+    let mut collected = Collected {
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        tail: VecDeque::with_capacity(TAIL_LINES),
+    };
+
+    let mut exited_at: Option<Instant> = None;
+    let status = loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok((stream, line)) => collected.push(&program, stream, line, options),
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(status) = child.try_wait()? {
+                    if exited_at.get_or_insert_with(Instant::now).elapsed() >= EXIT_GRACE {
+                        break status;
+                    }
+                }
+            }
+            // Both pipes closed: the command has exited (or closed its output)
+            Err(RecvTimeoutError::Disconnected) => break child.wait()?,
+        }
+
+        if interruptible && cancel::signalled() {
+            kill_group(&mut child);
+            log::warn!("Interrupted, killed: {}", cmd_str);
+            return Err(InstallerError::UserCancelled);
+        }
+
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            kill_group(&mut child);
+            while let Ok((stream, line)) = rx.try_recv() {
+                collected.push(&program, stream, line, options);
+            }
+
+            let secs = timeout.unwrap_or_default().as_secs_f64();
+            let tail: Vec<String> = collected.tail.into_iter().collect();
+            log::error!("Command timed out after {:.1}s: {}", secs, cmd_str);
+            return Err(InstallerError::CommandFailed {
+                cmd: cmd_str.to_string(),
+                code: -1,
+                stderr: format!(
+                    "timed out after {:.1}s; last output:\n{}",
+                    secs,
+                    tail.join("\n")
+                ),
+            });
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: collected.stdout,
+        stderr: collected.stderr,
+    })
+}
+/// A command captured by [`RecordingExecutor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCommand {
+    /// Program name or path
+    pub program: String,
+    /// Arguments, in order
+    pub args: Vec<String>,
+    /// Environment variables explicitly set on the command
+    pub env: Vec<(String, String)>,
+    /// Data fed to standard input
+    pub stdin: Option<Vec<u8>>,
+}
+
+impl RecordedCommand {
+    fn from_command(cmd: &Command, options: &ExecOptions) -> Self {
+        Self {
+            program: cmd.get_program().to_string_lossy().to_string(),
+            args: cmd
+                .get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect(),
+            env: cmd
+                .get_envs()
+                .filter_map(|(k, v)| {
+                    v.map(|v| {
+                        (
+                            k.to_string_lossy().to_string(),
+                            v.to_string_lossy().to_string(),
+                        )
+                    })
+                })
+                .collect(),
+            stdin: options.stdin.as_ref().map(|data| data.to_vec()),
+        }
+    }
+
+    /// Program followed by its arguments
+    pub fn argv(&self) -> Vec<String> {
+        std::iter::once(self.program.clone())
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+
+    /// Look up an environment variable set on the command
+    pub fn env_var(&self, key: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A scripted failure for [`RecordingExecutor`]
+#[derive(Debug, Clone)]
+struct ScriptedFailure {
+    program: String,
+    code: i32,
+    stderr: String,
+    /// Remaining failures, or `None` to fail forever
+    remaining: Option<usize>,
+}
+
+/// Scripted output for [`RecordingExecutor`]
+#[derive(Debug, Clone)]
+struct ScriptedResponse {
+    program: String,
+    stdout: String,
+    /// Remaining responses, or `None` to respond forever
+    remaining: Option<usize>,
+}
+
+/// Executor that records commands instead of running them
+///
+/// Every command succeeds with empty output unless a failure has been scripted
+/// for its program with [`RecordingExecutor::fail_program`] or
+/// [`RecordingExecutor::fail_program_times`], or its output with
+/// [`RecordingExecutor::respond_program`] or
+/// [`RecordingExecutor::respond_program_times`]. Retry policies are honoured, so
+/// each retry is recorded as a separate command. File changes are recorded
+/// and never applied.
+#[derive(Default)]
+pub struct RecordingExecutor {
+    commands: Mutex<Vec<RecordedCommand>>,
+    file_ops: Mutex<Vec<FileOp>>,
+    failures: Mutex<Vec<ScriptedFailure>>,
+    responses: Mutex<Vec<ScriptedResponse>>,
+}
+
+impl RecordingExecutor {
+    /// Create a new recording executor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make every command whose program matches `program` fail
+    pub fn fail_program(&self, program: impl Into<String>, code: i32, stderr: impl Into<String>) {
+        self.failures.lock().unwrap().push(ScriptedFailure {
+            program: program.into(),
+            code,
+            stderr: stderr.into(),
+            remaining: None,
+        });
+    }
+
+    /// Make the next `times` commands whose program matches `program` fail
+    pub fn fail_program_times(
+        &self,
+        program: impl Into<String>,
+        times: usize,
+        code: i32,
+        stderr: impl Into<String>,
+    ) {
+        self.failures.lock().unwrap().push(ScriptedFailure {
+            program: program.into(),
+            code,
+            stderr: stderr.into(),
+            remaining: Some(times),
+        });
+    }
+
+    /// Make every successful command whose program matches `program` print `stdout`
+    pub fn respond_program(&self, program: impl Into<String>, stdout: impl Into<String>) {
+        self.responses.lock().unwrap().push(ScriptedResponse {
+            program: program.into(),
+            stdout: stdout.into(),
+            remaining: None,
+        });
+    }
+
+    /// Make the next `times` successful commands whose program matches `program` print `stdout`
+    ///
+    /// Responses are used in the order they were scripted, so a sequence of
+    /// outputs can be set up with one call per step.
+    pub fn respond_program_times(
+        &self,
+        program: impl Into<String>,
+        times: usize,
+        stdout: impl Into<String>,
+    ) {
+        self.responses.lock().unwrap().push(ScriptedResponse {
+            program: program.into(),
+            stdout: stdout.into(),
+            remaining: Some(times),
+        });
+    }
+
+    /// Record a command and return its scripted outcome
+    fn run_once(&self, cmd: &Command, options: &ExecOptions) -> Result<ExecResult> {
+        let recorded = RecordedCommand::from_command(cmd, options);
+        self.commands.lock().unwrap().push(recorded.clone());
+
+        let failure = {
+            let mut failures = self.failures.lock().unwrap();
+            failures
+                .iter_mut()
+                .find(|f| f.program == recorded.program && f.remaining != Some(0))
+                .map(|f| {
+                    if let Some(remaining) = f.remaining.as_mut() {
+                        *remaining -= 1;
+                    }
+                    f.clone()
+                })
+        };
+
+        if let Some(failure) = failure {
+            return Err(InstallerError::CommandFailed {
+                cmd: recorded.argv().join(" "),
+                code: failure.code,
+                stderr: failure.stderr,
+            });
+        }
+
+        let stdout = self
+            .responses
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|r| r.program == recorded.program && r.remaining != Some(0))
+            .map(|r| {
+                if let Some(remaining) = r.remaining.as_mut() {
+                    *remaining -= 1;
+                }
+                r.stdout.clone().into_bytes()
+            })
+            .unwrap_or_default();
+
+        Ok(ExecResult::Ran {
+            stdout,
+            stderr: Vec::new(),
+            status: ExitStatus::from_raw(0),
+        })
+    }
+
+    /// Commands recorded so far, in execution order
+    pub fn commands(&self) -> Vec<RecordedCommand> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    /// File changes recorded so far, in order
+    pub fn file_ops(&self) -> Vec<FileOp> {
+        self.file_ops.lock().unwrap().clone()
+    }
+
+    /// Contents last written to `path`, if any
+    pub fn written(&self, path: &Path) -> Option<String> {
+        self.file_ops
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find_map(|op| match op {
+                FileOp::Write { path: p, contents } if p == path => Some(contents.clone()),
+                _ => None,
+            })
+    }
+}
+
+impl CommandExecutor for RecordingExecutor {
+    fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+        let cmd_str = command_argv(cmd).join(" ");
+        options
+            .retry
+            .run(&cmd_str, options.retry_sink.as_ref(), || {
+                self.run_once(cmd, options)
+            })
+    }
+
+    fn apply(&self, op: FileOp) -> Result<()> {
+        self.file_ops.lock().unwrap().push(op);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_executor_dry_run_skips_command() {
+        let executor = SystemExecutor::new(true);
+        // Would fail if actually executed
+        let result = executor
+            .execute(Command::new("/nonexistent/binary").arg("--flag"))
+            .unwrap();
+        assert_eq!(
+            result,
+            ExecResult::Skipped {
+                argv: vec!["/nonexistent/binary".to_string(), "--flag".to_string()]
+            }
+        );
+        assert!(result.is_skipped());
+        assert_eq!(result.stdout(), None);
+    }
+
+    #[test]
+    fn test_system_executor_reports_failure() {
+        let executor = SystemExecutor::new(false);
+        let err = executor
+            .execute(Command::new("sh").arg("-c").arg("echo oops >&2; exit 4"))
+            .unwrap_err();
+        match err {
+            InstallerError::CommandFailed { code, stderr, .. } => {
+                assert_eq!(code, 4);
+                assert_eq!(stderr.trim(), "oops");
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_recording_executor_captures_argv_and_env() {
+        let executor = RecordingExecutor::new();
+        executor
+            .execute(Command::new("zpool").arg("list").env("ZBM_POOL", "zroot"))
+            .unwrap();
+
+        let commands = executor.commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].argv(), vec!["zpool", "list"]);
+        assert_eq!(commands[0].env_var("ZBM_POOL"), Some("zroot"));
+    }
+
+    #[test]
+    fn test_recording_executor_scripted_failure() {
+        let executor = RecordingExecutor::new();
+        executor.fail_program("sgdisk", 2, "device busy");
+
+        assert!(executor.execute(&mut Command::new("wipefs")).is_ok());
+        let err = executor.execute(&mut Command::new("sgdisk")).unwrap_err();
+        assert!(err.to_string().contains("device busy"));
+        assert_eq!(executor.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_command_class_defaults() {
+        assert_eq!(CommandClass::for_program("partprobe"), CommandClass::Quick);
+        assert_eq!(
+            CommandClass::for_program("/usr/sbin/zpool"),
+            CommandClass::Zfs
+        );
+        assert_eq!(
+            CommandClass::for_program("rsync"),
+            CommandClass::LongRunning
+        );
+        assert_eq!(
+            CommandClass::for_program("/hooks/pre"),
+            CommandClass::Standard
+        );
+        assert_eq!(CommandClass::LongRunning.default_timeout(), None);
+
+        let options = ExecOptions::new();
+        assert_eq!(
+            options.effective_timeout("sgdisk"),
+            Some(Duration::from_secs(120))
+        );
+        let options = ExecOptions::new().timeout(Duration::from_secs(5));
+        assert_eq!(
+            options.effective_timeout("rsync"),
+            Some(Duration::from_secs(5))
+        );
+        let options = ExecOptions::new().class(CommandClass::LongRunning);
+        assert_eq!(options.effective_timeout("sgdisk"), None);
+    }
+
+    #[test]
+    fn test_output_streamed_as_it_arrives() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let options = ExecOptions::new().on_line(move |stream, line| {
+            sink.lock()
+                .unwrap()
+                .push((stream, line.to_string(), Instant::now()));
+        });
+
+        let start = Instant::now();
+        let output = SystemExecutor::new(false)
+            .execute_with(
+                Command::new("sh")
+                    .arg("-c")
+                    .arg("echo first; echo warn >&2; sleep 0.5; echo second"),
+                &options,
+            )
+            .unwrap();
+        let finished = Instant::now();
+
+        let lines = lines.lock().unwrap();
+        let stdout: Vec<&(OutputStream, String, Instant)> = lines
+            .iter()
+            .filter(|(s, _, _)| *s == OutputStream::Stdout)
+            .collect();
+        assert_eq!(stdout.len(), 2);
+        assert_eq!(stdout[0].1, "first");
+        assert_eq!(stdout[1].1, "second");
+        assert!(lines
+            .iter()
+            .any(|(s, l, _)| *s == OutputStream::Stderr && l == "warn"));
+
+        // The first line was delivered well before the command finished
+        assert!(stdout[0].2.duration_since(start) < finished.duration_since(start));
+        assert!(finished.duration_since(stdout[0].2) >= Duration::from_millis(300));
+
+        match output {
+            ExecResult::Ran { stdout, stderr, .. } => {
+                assert_eq!(String::from_utf8_lossy(&stdout), "first\nsecond\n");
+                assert_eq!(String::from_utf8_lossy(&stderr), "warn\n");
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_timeout_kills_command_and_reports_tail() {
+        let options = ExecOptions::new().timeout(Duration::from_millis(300));
+
+        let start = Instant::now();
+        let err = SystemExecutor::new(false)
+            .execute_with(
+                Command::new("sh")
+                    .arg("-c")
+                    .arg("echo partprobe is stuck; exec sleep 30"),
+                &options,
+            )
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        match err {
+            InstallerError::CommandFailed { code, stderr, .. } => {
+                assert_eq!(code, -1);
+                assert!(stderr.contains("timed out after 0.3s"));
+                assert!(stderr.contains("partprobe is stuck"));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_discard_stdout_still_streams() {
+        let count = Arc::new(Mutex::new(0));
+        let sink = count.clone();
+        let options = ExecOptions::new()
+            .discard_stdout()
+            .on_line(move |_, _| *sink.lock().unwrap() += 1);
+
+        let output = SystemExecutor::new(false)
+            .execute_with(Command::new("sh").arg("-c").arg("seq 1 100"), &options)
+            .unwrap();
+        assert_eq!(output.stdout(), Some(&[][..]));
+        assert_eq!(*count.lock().unwrap(), 100);
+    }
+
+    #[test]
+    fn test_stdin_fed_without_appearing_in_argv() {
+        let options = ExecOptions::new().stdin("s3cret passphrase");
+
+        let output = SystemExecutor::new(false)
+            .execute_with(&mut Command::new("cat"), &options)
+            .unwrap();
+        assert_eq!(output.stdout_string().as_deref(), Some("s3cret passphrase"));
+
+        let recorder = RecordingExecutor::new();
+        recorder
+            .execute_with(Command::new("zpool").arg("create"), &options)
+            .unwrap();
+        let recorded = &recorder.commands()[0];
+        assert_eq!(recorded.argv(), vec!["zpool", "create"]);
+        assert_eq!(recorded.stdin.as_deref(), Some(&b"s3cret passphrase"[..]));
+    }
+
+    #[test]
+    fn test_carriage_returns_split_lines() {
+        let mut reader = std::io::Cursor::new(b"10%\r55%\r100%\r\ndone\n".to_vec());
+        let mut lines = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            if read_line_or_cr(&mut reader, &mut line).unwrap() == 0 {
+                break;
+            }
+            lines.push(String::from_utf8(line).unwrap());
+        }
+        assert_eq!(lines, vec!["10%\r", "55%\r", "100%\r\n", "done\n"]);
+    }
+
+    #[test]
+    fn test_transient_classification() {
+        let failed = |stderr: &str| InstallerError::CommandFailed {
+            cmd: "partprobe /dev/sda".to_string(),
+            code: 1,
+            stderr: stderr.to_string(),
+        };
+
+        assert!(is_transient(&failed(
+            "Error: Partition(s) 1 on /dev/sda have been written, but we have been unable \
+             to inform the kernel of the change, probably because it/they are in use. \
+             Device or resource busy"
+        )));
+        assert!(is_transient(&failed(
+            "cannot import 'zroot': no such pool available"
+        )));
+        assert!(is_transient(&failed("Resource temporarily unavailable")));
+        assert!(!is_transient(&failed("Invalid partition data!")));
+        assert!(!is_transient(&InstallerError::validation(
+            "Device or resource busy"
+        )));
+    }
+
+    #[test]
+    fn test_retry_succeeds_after_transient_failure() {
+        let executor = RecordingExecutor::new();
+        executor.fail_program_times("partprobe", 2, 1, "Device or resource busy");
+        let options = ExecOptions::new().retry(RetryPolicy::new(3, Duration::ZERO));
+
+        assert!(executor
+            .execute_with(&mut Command::new("partprobe"), &options)
+            .is_ok());
+        assert_eq!(executor.commands().len(), 3);
+    }
+
+    #[test]
+    fn test_retries_are_reported() {
+        let executor = RecordingExecutor::new();
+        executor.fail_program_times("wipefs", 2, 1, "Device or resource busy");
+        let retries = Arc::new(Mutex::new(Vec::new()));
+        let sink = retries.clone();
+        let options = ExecOptions::new()
+            .retry(RetryPolicy::new(3, Duration::ZERO))
+            .on_retry(move |message| sink.lock().unwrap().push(message.to_string()));
+
+        executor
+            .execute_with(Command::new("wipefs").arg("/dev/sdb"), &options)
+            .unwrap();
+        let retries = retries.lock().unwrap();
+        assert_eq!(retries.len(), 2);
+        assert!(
+            retries[0].starts_with("Attempt 1/3 of wipefs /dev/sdb failed"),
+            "{}",
+            retries[0]
+        );
+    }
+
+    #[test]
+    fn test_retry_reports_attempts_on_final_failure() {
+        let executor = RecordingExecutor::new();
+        executor.fail_program("bootctl", 1, "Device or resource busy");
+        let options = ExecOptions::new().retry(RetryPolicy::new(3, Duration::ZERO));
+
+        let err = executor
+            .execute_with(&mut Command::new("bootctl"), &options)
+            .unwrap_err();
+        assert!(err.to_string().contains("gave up after 3 attempts"));
+        assert_eq!(executor.commands().len(), 3);
+    }
+
+    #[test]
+    fn test_permanent_failure_not_retried() {
+        let executor = RecordingExecutor::new();
+        executor.fail_program("sgdisk", 2, "Invalid partition data!");
+        let options = ExecOptions::new().retry(RetryPolicy::new(3, Duration::ZERO));
+
+        let err = executor
+            .execute_with(&mut Command::new("sgdisk"), &options)
+            .unwrap_err();
+        assert!(!err.to_string().contains("attempts"));
+        assert_eq!(executor.commands().len(), 1);
+    }
+
+    #[test]
+    fn test_no_retry_by_default() {
+        let executor = RecordingExecutor::new();
+        executor.fail_program("partprobe", 1, "Device or resource busy");
+
+        assert!(executor.execute(&mut Command::new("partprobe")).is_err());
+        assert_eq!(executor.commands().len(), 1);
+    }
+
+    #[test]
+    fn test_system_executor_applies_file_ops_unless_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("etc/hostid.conf");
+
+        SystemExecutor::new(true)
+            .apply(FileOp::create_dir(dir.path().join("etc")))
+            .unwrap();
+        assert!(!dir.path().join("etc").exists());
+
+        let executor = SystemExecutor::new(false);
+        executor
+            .apply(FileOp::create_dir(dir.path().join("etc")))
+            .unwrap();
+        executor.apply(FileOp::write(&file, "8f3c2a1b\n")).unwrap();
+        executor.apply(FileOp::set_mode(&file, 0o600)).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "8f3c2a1b\n");
+        assert_eq!(
+            fs::metadata(&file).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+    }
+
+    #[test]
+    fn test_remove_deletes_files_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let zbm = dir.path().join("EFI/ZBM");
+        fs::create_dir_all(&zbm).unwrap();
+        fs::write(zbm.join("zfsbootmenu.EFI"), "image").unwrap();
+        fs::write(dir.path().join("startup.nsh"), "").unwrap();
+
+        FileOp::remove(&zbm).apply().unwrap();
+        FileOp::remove(dir.path().join("startup.nsh"))
+            .apply()
+            .unwrap();
+        FileOp::remove(dir.path().join("missing")).apply().unwrap();
+        assert!(!zbm.exists());
+        assert!(!dir.path().join("startup.nsh").exists());
+        assert!(dir.path().join("EFI").is_dir());
+    }
+
+    #[test]
+    fn test_symlink_replaces_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let localtime = dir.path().join("localtime");
+        fs::write(&localtime, "UTC").unwrap();
+
+        FileOp::symlink("/usr/share/zoneinfo/Europe/Berlin", &localtime)
+            .apply()
+            .unwrap();
+        assert_eq!(
+            fs::read_link(&localtime).unwrap(),
+            Path::new("/usr/share/zoneinfo/Europe/Berlin")
+        );
+    }
+
+    #[test]
+    fn test_plan_executor_collects_commands_and_file_ops() {
+        let executor = PlanExecutor::new();
+        let result = executor
+            .execute(Command::new("zpool").args(["set", "bootfs=zroot/ROOT/default", "zroot"]))
+            .unwrap();
+        assert!(result.is_skipped());
+        executor
+            .apply(FileOp::write(
+                "/mnt/etc/fstab",
+                "UUID=5D7E-0C41 /boot/efi vfat umask=0077 0 2\n",
+            ))
+            .unwrap();
+
+        let actions = executor.take();
+        assert_eq!(
+            actions[0].to_string(),
+            "zpool set bootfs=zroot/ROOT/default zroot"
+        );
+        let json = serde_json::to_value(&actions).unwrap();
+        assert_eq!(json[0]["kind"], "command");
+        assert!(json[0].get("stdin").is_none());
+        assert_eq!(json[1]["kind"], "file");
+        assert_eq!(json[1]["op"], "write");
+        assert_eq!(json[1]["path"], "/mnt/etc/fstab");
+        let back: Vec<PlannedAction> = serde_json::from_value(json).unwrap();
+        assert_eq!(back, actions);
+        assert!(executor.take().is_empty());
+    }
+
+    #[test]
+    fn test_plan_redacts_stdin() {
+        let executor = PlanExecutor::new();
+        executor
+            .execute_with(
+                Command::new("chpasswd").arg("-e"),
+                &ExecOptions::new().stdin("alice:$6$salt$hash\n"),
+            )
+            .unwrap();
+
+        let actions = executor.take();
+        assert_eq!(actions[0].to_string(), "chpasswd -e < <redacted>");
+        let json = serde_json::to_string(&actions).unwrap();
+        assert!(json.contains("\"stdin\":\"<redacted>\""));
+        assert!(!json.contains("$6$"));
+    }
+}
@@ -0,0 +1,155 @@
+//! German messages (partial; missing keys fall back to English)
+
+pub(super) const MESSAGES: &[(&str, &str)] = &[
+    // CLI
+    (
+        "cli.not_root",
+        "Fehler: Dieses Programm muss als root ausgeführt werden",
+    ),
+    ("cli.run_with_sudo", "Bitte ausführen: sudo {program}"),
+    ("cli.cancelled", "Installation abgebrochen"),
+    (
+        "cli.log_path",
+        "Vollständiges Installationsprotokoll: {path}",
+    ),
+    (
+        "cli.confirm.warning",
+        "⚠️  WARNUNG: Alle Daten auf den ausgewählten Laufwerken werden GELÖSCHT!",
+    ),
+    (
+        "cli.confirm.prompt",
+        "Zum Fortfahren den Poolnamen ({pool}) eingeben, oder --force zum Überspringen: ",
+    ),
+    (
+        "cli.destroy.warning",
+        "⚠️  WARNUNG: Folgendes wird entfernt; ein zerstörter Pool ist nicht wiederherstellbar:",
+    ),
+    (
+        "cli.destroy.prompt",
+        "Zur Bestätigung den Poolnamen ({pool}) eingeben: ",
+    ),
+    ("cli.passphrase.prompt", "Verschlüsselungs-Passphrase: "),
+    ("cli.passphrase.confirm", "Passphrase bestätigen: "),
+    ("cli.password.prompt", "Passwort für {name}: "),
+    ("cli.password.confirm", "Passwort bestätigen: "),
+    // Line prompts (--interactive)
+    (
+        "prompt.no_terminal",
+        "--interactive braucht ein Terminal für die Fragen; dort ausführen, oder mit --drives oder --answer-file installieren",
+    ),
+    ("prompt.choice", "Auswahl [{default}]: "),
+    ("prompt.choice_invalid", "Eine Zahl von 1 bis {count} eingeben"),
+    ("prompt.choices", "Auswahl (z.B. 1,3 oder 2-4): "),
+    ("prompt.yes", "ja"),
+    ("prompt.no", "nein"),
+    ("prompt.yes_no_default_yes", "[J/n]"),
+    ("prompt.yes_no_default_no", "[j/N]"),
+    ("prompt.yes_no_invalid", "Mit ja oder nein antworten"),
+    (
+        "wizard.encrypt",
+        "Den Pool mit nativer ZFS-Verschlüsselung verschlüsseln?",
+    ),
+    // Screen titles
+    ("ui.screen.welcome", "Willkommen beim ZFSBootMenu-Installer"),
+    ("ui.screen.mode_select", "Installationsmodus wählen"),
+    ("ui.screen.device_select", "Geräte auswählen"),
+    ("ui.screen.raid_config", "RAID-Konfiguration"),
+    ("ui.screen.encryption", "Verschlüsselung"),
+    ("ui.screen.settings", "Installationseinstellungen"),
+    ("ui.screen.confirmation", "Installation bestätigen"),
+    ("ui.screen.execution", "Installation läuft"),
+    ("ui.screen.completion", "Installation abgeschlossen"),
+    // Shared TUI text
+    ("ui.button.back", "Zurück"),
+    ("ui.button.cancel", "Abbrechen"),
+    ("ui.button.continue", "Weiter"),
+    ("ui.button.save", "Konfiguration speichern"),
+    ("ui.button.no", "Nein"),
+    ("ui.button.yes_exit", "Ja, beenden"),
+    ("ui.button.yes", "Ja"),
+    ("ui.button.view_log", "Protokoll anzeigen"),
+    ("ui.label.mode", "Modus"),
+    ("ui.label.pool_name", "Pool-Name"),
+    ("ui.label.devices", "Geräte"),
+    ("ui.label.compression", "Kompression"),
+    ("ui.label.boot_mode", "Boot-Modus"),
+    ("ui.label.efi_size", "EFI-Größe"),
+    ("ui.label.swap_size", "Swap-Größe"),
+    ("ui.label.usable_capacity", "Nutzbar"),
+    ("ui.label.kernel_cmdline", "Kernel-Befehlszeile"),
+    ("ui.label.hostname", "Hostname"),
+    ("ui.label.timezone", "Zeitzone"),
+    ("ui.label.locale", "Locale"),
+    ("ui.label.keymap", "Tastaturbelegung"),
+    ("ui.label.users", "Benutzer"),
+    ("ui.label.new_user", "Neuer Administrator"),
+    ("ui.label.password", "Passwort für {name}"),
+    ("ui.label.password_confirm", "Passwort bestätigen"),
+    ("ui.label.root_password", "Root-Passwort"),
+    ("ui.label.lock_root", "Root sperren"),
+    ("ui.label.wipe_mode", "Löschmodus"),
+    ("ui.label.encryption", "Verschlüsselung"),
+    (
+        "ui.devices.shown",
+        "{shown} von {total} Geräten angezeigt, {selected} ausgewählt",
+    ),
+    ("ui.devices.sort", "Sortierung: {sort}"),
+    ("ui.devices.filter", "Filter: {filter}"),
+    ("ui.settings.invalid", "Ungültiger Wert"),
+    ("ui.settings.password_set", "gesetzt"),
+    // Screens
+    ("ui.confirm.device_count", "{count} Gerät(e)"),
+    ("ui.confirm.selected_devices", "Ausgewählte Geräte:"),
+    (
+        "ui.confirm.type_pool",
+        "Zum Fortfahren den Poolnamen ({pool}) eingeben:",
+    ),
+    (
+        "ui.confirm.warning",
+        "⚠️  WARNUNG: Alle Daten auf den ausgewählten Laufwerken werden GELÖSCHT!",
+    ),
+    ("ui.exec.cancel_title", "Installation abbrechen"),
+    ("ui.exec.failed", "Installation fehlgeschlagen"),
+    (
+        "ui.exec.log_title",
+        "Protokoll (↑/↓ Bild↑/Bild↓: Blättern, Ende: Folgen, Esc: Schließen)",
+    ),
+    ("ui.complete.pool", "Pool {pool} ist bereit."),
+    (
+        "ui.complete.esps",
+        "Geschriebene EFI-Systempartitionen: {count}",
+    ),
+    ("ui.complete.bootloader", "Bootloader {name} installiert"),
+    (
+        "ui.complete.bootloader_failed",
+        "Bootloader {name} NICHT installiert (ignoriert): {error}",
+    ),
+    ("ui.complete.network", "Übernommenes Netzwerk: {summary}"),
+    ("ui.exit.title", "Installer beenden"),
+    (
+        "ui.exit.question",
+        "Möchten Sie den Installer wirklich beenden?",
+    ),
+    (
+        "ui.exit.no_changes",
+        "Es wurden keine Änderungen vorgenommen.",
+    ),
+    // Validation findings
+    (
+        "validation.not_root",
+        "Dieses Programm muss als root ausgeführt werden",
+    ),
+    (
+        "validation.not_uefi",
+        "Das System muss im UEFI-Modus gestartet sein",
+    ),
+    ("validation.config_error", "Konfigurationsfehler: {error}"),
+    (
+        "validation.zfs_missing",
+        "ZFS ist auf diesem System nicht verfügbar. Bitte zuerst ZFS installieren.",
+    ),
+    (
+        "validation.command_missing",
+        "Benötigter Befehl nicht gefunden: {command}",
+    ),
+];
@@ -0,0 +1,460 @@
+//! English messages (complete; the fallback for every other catalog)
+
+pub(super) const MESSAGES: &[(&str, &str)] = &[
+    // CLI
+    ("cli.not_root", "Error: This program must be run as root"),
+    ("cli.run_with_sudo", "Please run: sudo {program}"),
+    ("cli.cancelled", "Installation cancelled"),
+    ("cli.log_path", "Full install log: {path}"),
+    (
+        "cli.confirm.warning",
+        "⚠️  WARNING: This will DESTROY all data on the selected drives!",
+    ),
+    (
+        "cli.confirm.holders",
+        "{device} is in use by {holders}; they will be stopped and destroyed",
+    ),
+    (
+        "cli.confirm.prompt",
+        "Type the pool name ({pool}) to continue, or --force to skip this: ",
+    ),
+    (
+        "cli.destroy.warning",
+        "⚠️  WARNING: This will remove the following; a destroyed pool can't be recovered:",
+    ),
+    ("cli.destroy.prompt", "Type the pool name ({pool}) to confirm: "),
+    ("cli.validate.pass", "PASS"),
+    ("cli.validate.warn", "WARN"),
+    ("cli.validate.fail", "FAIL"),
+    ("cli.passphrase.prompt", "Encryption passphrase: "),
+    ("cli.passphrase.confirm", "Confirm passphrase: "),
+    ("cli.password.prompt", "Password for {name}: "),
+    ("cli.password.confirm", "Confirm password: "),
+    // Line prompts (--interactive)
+    (
+        "prompt.no_terminal",
+        "--interactive needs a terminal to ask on; run it in one, or install with --drives or --answer-file",
+    ),
+    ("prompt.choice", "Choice [{default}]: "),
+    ("prompt.choice_invalid", "Enter a number from 1 to {count}"),
+    ("prompt.choices", "Choices (e.g. 1,3 or 2-4): "),
+    (
+        "prompt.choices_invalid",
+        "Enter numbers from 1 to {count}, separated by commas, or a range like 2-4",
+    ),
+    ("prompt.yes", "yes"),
+    ("prompt.no", "no"),
+    ("prompt.yes_no_default_yes", "[Y/n]"),
+    ("prompt.yes_no_default_no", "[y/N]"),
+    ("prompt.yes_no_invalid", "Answer yes or no"),
+    (
+        "wizard.intro",
+        "ZFSBootMenu installation setup. Press Enter to keep the value in brackets or marked with *.",
+    ),
+    ("wizard.encrypt", "Encrypt the pool with native ZFS encryption?"),
+    // Screen titles
+    ("ui.screen.welcome", "Welcome to ZFSBootMenu Installer"),
+    ("ui.screen.mode_select", "Select Installation Mode"),
+    ("ui.screen.device_discovery", "Discovering Devices"),
+    ("ui.screen.device_select", "Select Devices"),
+    ("ui.screen.raid_config", "RAID Configuration"),
+    ("ui.screen.encryption", "Encryption"),
+    ("ui.screen.settings", "Installation Settings"),
+    ("ui.screen.preflight", "Pre-flight Checks"),
+    ("ui.screen.confirmation", "Confirm Installation"),
+    ("ui.screen.execution", "Installing"),
+    ("ui.screen.completion", "Installation Complete"),
+    // Shared TUI text
+    (
+        "ui.help",
+        "↑↓: Navigate | Enter: Select | Esc: Back | Q: Quit",
+    ),
+    ("ui.button.back", "Back"),
+    ("ui.button.ok", "OK"),
+    ("ui.button.cancel", "Cancel"),
+    ("ui.button.continue", "Continue"),
+    ("ui.button.save", "Save Config"),
+    ("ui.button.no", "No"),
+    ("ui.button.yes_exit", "Yes, Exit"),
+    ("ui.button.yes", "Yes"),
+    ("ui.button.view_log", "View Log"),
+    ("ui.label.mode", "Mode"),
+    ("ui.label.pool_name", "Pool Name"),
+    ("ui.label.raid_level", "RAID Level"),
+    ("ui.label.devices", "Devices"),
+    ("ui.label.compression", "Compression"),
+    ("ui.label.boot_mode", "Boot Mode"),
+    ("ui.label.efi_size", "EFI Size"),
+    ("ui.label.swap_size", "Swap Size"),
+    ("ui.label.arc_max", "ARC Max"),
+    ("ui.label.usable_capacity", "Usable Capacity"),
+    ("ui.label.kernel_cmdline", "Kernel Cmdline"),
+    ("ui.label.hostname", "Hostname"),
+    ("ui.label.timezone", "Timezone"),
+    ("ui.label.locale", "Locale"),
+    ("ui.label.keymap", "Keymap"),
+    ("ui.label.users", "Users"),
+    ("ui.label.new_user", "New admin user"),
+    ("ui.label.password", "Password for {name}"),
+    ("ui.label.password_confirm", "Confirm password"),
+    ("ui.label.root_password", "Root Password"),
+    ("ui.label.lock_root", "Lock Root"),
+    ("ui.label.wipe_mode", "Wipe Mode"),
+    ("ui.label.encryption", "Encryption"),
+    ("ui.label.key", "Key"),
+    ("ui.label.key_file", "Key file (absolute path)"),
+    ("ui.label.passphrase", "Encryption passphrase"),
+    ("ui.label.passphrase_confirm", "Confirm passphrase"),
+    // Screens
+    (
+        "ui.welcome.continue",
+        "Press ENTER to continue or Q to quit",
+    ),
+    ("ui.mode.prompt", "Select Installation Mode:"),
+    ("ui.mode.new", "New Installation"),
+    (
+        "ui.mode.new_description",
+        "Fresh ZFS installation on empty drives",
+    ),
+    ("ui.mode.existing", "Migrate Existing System"),
+    (
+        "ui.mode.existing_description",
+        "Move an existing system to ZFS",
+    ),
+    ("ui.devices.none_found", "No Devices Found"),
+    (
+        "ui.devices.none_found_detail",
+        "No suitable block devices were found.",
+    ),
+    (
+        "ui.devices.none_found_hint",
+        "Please ensure devices are connected.",
+    ),
+    ("ui.devices.prompt", "Select devices for installation:"),
+    (
+        "ui.devices.help",
+        "Space: Toggle | r: Role | i/→: Details | PgUp/PgDn: Page | Enter: Continue | Esc: Back",
+    ),
+    (
+        "ui.devices.help_list",
+        "/: Filter | s: Sort | a: Check all shown | A: Uncheck all shown",
+    ),
+    (
+        "ui.devices.shown",
+        "Showing {shown} of {total} devices, {selected} selected",
+    ),
+    ("ui.devices.sort", "Sort: {sort}"),
+    ("ui.devices.sort_name", "name"),
+    ("ui.devices.sort_size", "size"),
+    ("ui.devices.sort_media", "media type"),
+    ("ui.devices.filter", "Filter: {filter}"),
+    (
+        "ui.devices.filter_prompt",
+        "Filter by name, model or serial (empty: show all)",
+    ),
+    ("ui.devices.none_selected", "No Devices Selected"),
+    (
+        "ui.devices.none_selected_detail",
+        "Please select at least one device.",
+    ),
+    ("ui.devices.removed", "Selected Device Removed"),
+    (
+        "ui.devices.removed_detail",
+        "Unplugged and deselected: {devices}",
+    ),
+    ("ui.devices.boot_medium", "boot medium"),
+    ("ui.device_info.title", "Device details: {device}"),
+    (
+        "ui.device_info.help",
+        "↑/↓/PgUp/PgDn: Scroll | Esc/←: Back to device list",
+    ),
+    ("ui.raid.prompt", "Select RAID Level:"),
+    ("ui.raid.selected_devices", "Selected devices: {count}"),
+    ("ui.raid.usable", "about {size} usable"),
+    ("ui.settings.prompt", "Installation Settings:"),
+    ("ui.settings.continue", "Continue →"),
+    ("ui.settings.invalid", "Invalid Value"),
+    ("ui.settings.password_set", "set"),
+    ("ui.encryption.prompt", "Native ZFS Encryption:"),
+    ("ui.encryption.title", "Encryption"),
+    ("ui.encryption.passphrase", "passphrase, typed in at boot"),
+    ("ui.encryption.key_file", "key file {path}"),
+    (
+        "ui.encryption.passphrase_missing",
+        "Enter the passphrase, or turn encryption off, before continuing",
+    ),
+    ("ui.encryption.strength", "Strength: {strength}"),
+    ("ui.encryption.too_short", "too short, at least 8 characters"),
+    ("ui.encryption.weak", "weak"),
+    ("ui.encryption.fair", "fair"),
+    ("ui.encryption.strong", "strong"),
+    ("ui.preflight.running", "Running Pre-flight Checks..."),
+    (
+        "ui.preflight.passed",
+        "All checks passed! Press ENTER to continue",
+    ),
+    (
+        "ui.preflight.failed",
+        "Some checks failed. Fix the problems above before continuing.",
+    ),
+    (
+        "ui.preflight.warnings",
+        "Review the warnings above, then press A to acknowledge them",
+    ),
+    (
+        "ui.preflight.acknowledged",
+        "Warnings acknowledged. Press ENTER to continue",
+    ),
+    (
+        "ui.preflight.help",
+        "↑/↓: Scroll | A: Acknowledge warnings | R: Run again | Esc: Back",
+    ),
+    ("ui.preflight.error", "Pre-flight Checks Failed to Run"),
+    ("ui.confirm.device_count", "{count} device(s)"),
+    ("ui.confirm.usable", "about {size} ({redundancy})"),
+    ("ui.confirm.selected_devices", "Selected devices:"),
+    ("ui.confirm.holders", "stops and destroys {holders}"),
+    ("ui.confirm.encryption_off", "disabled"),
+    ("ui.confirm.encryption_passphrase", "enabled (passphrase)"),
+    ("ui.confirm.encryption_key_file", "enabled (key file {path})"),
+    ("ui.confirm.arc_default", "ZFS default"),
+    ("ui.confirm.type_pool", "Type the pool name ({pool}) to continue:"),
+    ("ui.confirm.arc_suggested", "ZFS default (suggested: {size})"),
+    (
+        "ui.confirm.warning",
+        "⚠️  WARNING: All data on selected drives will be DESTROYED!",
+    ),
+    ("ui.exec.help", "Esc: Cancel installation  L: Show log"),
+    ("ui.exec.cancel_title", "Cancel Installation"),
+    (
+        "ui.exec.cancel_question",
+        "Stop after the current step and roll back?",
+    ),
+    ("ui.exec.cancelling", "Cancelling after the current step..."),
+    ("ui.exec.failed", "Installation Failed"),
+    (
+        "ui.exec.log_title",
+        "Log (↑/↓ PgUp/PgDn: Scroll, End: Follow, Esc: Close)",
+    ),
+    ("ui.complete.pool", "Pool {pool} is ready."),
+    ("ui.complete.datasets", "Datasets ({count}):"),
+    ("ui.complete.esps", "EFI system partitions written: {count}"),
+    ("ui.complete.bootloader", "Bootloader {name} installed"),
+    (
+        "ui.complete.bootloader_failed",
+        "Bootloader {name} FAILED to install (ignored): {error}",
+    ),
+    ("ui.complete.network", "Network carried over: {summary}"),
+    ("ui.complete.exit", "L: Show log, any other key: Exit"),
+    ("ui.save.prompt", "Save configuration to:"),
+    ("ui.save.saved", "Configuration Saved"),
+    (
+        "ui.save.saved_detail",
+        "Replay it with: zbm-installer --config {path}",
+    ),
+    ("ui.save.failed", "Save Failed"),
+    ("ui.exit.title", "Exit Installer"),
+    ("ui.exit.question", "Are you sure you want to exit?"),
+    ("ui.exit.no_changes", "No changes have been made."),
+    // Validation findings
+    ("validation.check.root", "Root privileges"),
+    ("validation.check.firmware", "Boot firmware"),
+    ("validation.check.secure_boot", "Secure Boot"),
+    ("validation.check.config", "Configuration"),
+    ("validation.check.timezone", "Timezone"),
+    ("validation.check.network", "Network"),
+    ("validation.check.artifacts", "Offline artifacts"),
+    ("validation.check.zfs", "ZFS tools"),
+    ("validation.check.zfs_module", "ZFS kernel module"),
+    ("validation.check.existing_pool", "Existing pool"),
+    ("validation.check.compatibility", "Pool compatibility"),
+    ("validation.check.swap", "Swap"),
+    ("validation.check.device", "Device {device}"),
+    ("validation.check.distinct_devices", "Distinct devices"),
+    ("validation.check.smart", "Drive health (SMART)"),
+    ("validation.check.sizes", "Matched device sizes"),
+    ("validation.check.media", "Matched device types"),
+    ("validation.check.vdevs", "Matched vdev layouts"),
+    ("validation.check.memory", "Memory"),
+    ("validation.check.command", "Command {command}"),
+    ("validation.not_root", "This program must be run as root"),
+    (
+        "validation.size_mismatch",
+        "Device sizes differ by more than {percent}%: {smallest} vs {largest}. Usable capacity will be limited by the smallest, {usable} per device",
+    ),
+    (
+        "validation.mixed_media",
+        "SSDs ({ssds}) and HDDs ({hdds}) are mixed in one {vdev} vdev; it will perform like the HDDs",
+    ),
+    (
+        "validation.uneven_vdevs",
+        "Top-level vdevs differ in type or width ({layout}); writes and redundancy will be uneven across them",
+    ),
+    (
+        "validation.duplicate_device",
+        "{device} is listed more than once",
+    ),
+    (
+        "validation.same_device",
+        "{first} and {second} are the same device ({device})",
+    ),
+    (
+        "validation.partition_of_selected",
+        "{partition} is a partition of {disk}, which is also selected",
+    ),
+    (
+        "validation.not_uefi",
+        "System must be booted in UEFI mode (use --firmware bios for a legacy BIOS install)",
+    ),
+    (
+        "validation.bios_firmware",
+        "System is booted in legacy BIOS mode; no UEFI boot entry will be registered",
+    ),
+    (
+        "validation.bios_from_uefi",
+        "System is booted in UEFI mode; the legacy BIOS install only boots if the firmware offers legacy (CSM) boot",
+    ),
+    (
+        "validation.secure_boot",
+        "Secure Boot is enabled and would refuse the unsigned ZFSBootMenu image (use --secure-boot shim or sign-local, or disable Secure Boot)",
+    ),
+    (
+        "validation.secure_boot_forced",
+        "Secure Boot is enabled; the unsigned ZFSBootMenu image only boots once it is disabled",
+    ),
+    ("validation.config_error", "Configuration error: {error}"),
+    (
+        "validation.unknown_timezone",
+        "Timezone {timezone} is not in {dir}",
+    ),
+    (
+        "validation.no_pkg_dir",
+        "Package directory {dir} does not exist",
+    ),
+    (
+        "validation.missing_artifact",
+        "The artifact directory lacks ZFSBootMenu: {error}",
+    ),
+    (
+        "validation.no_network",
+        "Can't reach {host}, which the bootstrap downloads from; check the network",
+    ),
+    (
+        "validation.zfs_missing",
+        "ZFS is not available on this system. Please install ZFS first.",
+    ),
+    (
+        "validation.zfs_check_failed",
+        "Failed to check ZFS availability: {error}",
+    ),
+    ("validation.zfs_module", "ZFS kernel module unusable: {error}"),
+    (
+        "validation.devices_failed",
+        "Device validation failed: {error}",
+    ),
+    (
+        "validation.smartctl_missing",
+        "smartctl not found (install smartmontools); drive health was not checked",
+    ),
+    (
+        "validation.smart_failed",
+        "{device} failed its SMART health assessment and may lose data soon (use --force to install anyway)",
+    ),
+    (
+        "validation.smart_failed_forced",
+        "{device} failed its SMART health assessment but --force was specified",
+    ),
+    (
+        "validation.smart_degraded",
+        "{device} shows signs of wear: {problems}",
+    ),
+    (
+        "validation.smart_unreadable",
+        "Could not read SMART data of {device}: {error}",
+    ),
+    (
+        "validation.boot_medium",
+        "The running system was booted from this device; it can't be installed to",
+    ),
+    (
+        "validation.multipath_path",
+        "This is one path of the multipath map {map}; select /dev/mapper/{map} instead",
+    ),
+    (
+        "validation.removable_forced",
+        "{controller} device {device} is removable but --force was specified",
+    ),
+    (
+        "validation.zvol_swap_hibernation",
+        "Swap on a zvol does not support hibernation (suspend to disk)",
+    ),
+    (
+        "validation.encrypted_swap_hibernation",
+        "Swap encrypted with a random key does not support hibernation (suspend to disk)",
+    ),
+    (
+        "validation.pool_not_found",
+        "Pool '{pool}' is neither imported nor found by zpool import",
+    ),
+    (
+        "validation.pool_no_bootfs",
+        "Pool '{pool}' is a legacy pool too old for the bootfs property (run zpool upgrade)",
+    ),
+    (
+        "validation.pool_no_compatibility",
+        "Pool '{pool}' has no compatibility property set; GRUB may not be able to read it (set compatibility=grub2)",
+    ),
+    (
+        "validation.unknown_compatibility",
+        "Unknown pool compatibility '{name}'; available: {available}",
+    ),
+    ("validation.no_compatibility_files", "none installed"),
+    (
+        "validation.zfs_member",
+        "Device belongs to ZFS pool '{pool}' (use --force to override)",
+    ),
+    (
+        "validation.zfs_member_forced",
+        "Device {device} belongs to ZFS pool '{pool}' but --force was specified",
+    ),
+    (
+        "validation.holders",
+        "Device is in use by {holders}, which would be destroyed (stop them, or use --force)",
+    ),
+    (
+        "validation.holders_released",
+        "{holders} on {device} will be stopped and destroyed",
+    ),
+    (
+        "validation.stacked_members",
+        "{members} on {device} will be destroyed",
+    ),
+    (
+        "validation.nvme_4k_format",
+        "{device} ({model}) runs 512-byte sectors but supports 4K; reformat the namespace first (nvme format --lbaf) for better performance",
+    ),
+    (
+        "validation.automounted",
+        "{mounts} on {device} will be unmounted",
+    ),
+    (
+        "validation.free_space",
+        "Largest free region is too small ({free}, need at least {min_size})",
+    ),
+    (
+        "validation.free_space_every_disk",
+        "Largest free region is too small ({free}, need at least {min_size}); installing into free space needs room on every disk of the pool",
+    ),
+    (
+        "validation.low_memory",
+        "System has only {gb}GB of RAM. ZFS recommends at least 2GB.",
+    ),
+    (
+        "validation.arc_max_too_large",
+        "arc_max ({arc_max}) is larger than the {memory} of RAM",
+    ),
+    (
+        "validation.command_missing",
+        "Required command not found: {command}",
+    ),
+];
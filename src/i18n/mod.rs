@@ -0,0 +1,228 @@
+//! Message catalogs for user-facing strings
+//!
+//! Strings are looked up by key in the active language's catalog, falling
+//! back to English for keys that haven't been translated yet. Placeholders are
+//! written as `{name}` and filled in by [`tr_args`].
+
+mod de;
+mod en;
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// A supported interface language
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    /// English (complete, used as the fallback)
+    #[default]
+    En,
+    /// German
+    De,
+}
+
+impl Language {
+    /// All supported languages
+    pub const ALL: [Language; 2] = [Self::En, Self::De];
+
+    /// ISO 639-1 code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+        }
+    }
+
+    /// Parse a language code or locale name ("de", "de_DE.UTF-8", "de-AT")
+    pub fn from_code(code: &str) -> Option<Self> {
+        let lang = code
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        match lang.as_str() {
+            "c" | "posix" => Some(Self::En),
+            _ => Self::ALL.into_iter().find(|l| l.code() == lang),
+        }
+    }
+
+    /// Language from LC_ALL, LC_MESSAGES or LANG, English if unset or unsupported
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_code(&value))
+            .unwrap_or_default()
+    }
+
+    /// Key → message pairs for this language
+    fn catalog(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::En => en::MESSAGES,
+            Self::De => de::MESSAGES,
+        }
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Active language, stored as an index into [`Language::ALL`]
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+/// Set the language used by [`tr`] and [`tr_args`]
+pub fn set_language(language: Language) {
+    let index = Language::ALL
+        .iter()
+        .position(|l| *l == language)
+        .unwrap_or(0);
+    CURRENT.store(index as u8, Ordering::Relaxed);
+}
+
+/// The active language
+pub fn language() -> Language {
+    Language::ALL
+        .get(CURRENT.load(Ordering::Relaxed) as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Look up a message in one language's catalog, without fallback
+pub fn lookup(language: Language, key: &str) -> Option<&'static str> {
+    language
+        .catalog()
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, message)| *message)
+}
+
+/// Message for `key` in `language`, falling back to English, then to the key
+pub fn tr_in(language: Language, key: &'static str) -> &'static str {
+    lookup(language, key)
+        .or_else(|| lookup(Language::En, key))
+        .unwrap_or(key)
+}
+
+/// Message for `key` in the active language
+pub fn tr(key: &'static str) -> &'static str {
+    tr_in(language(), key)
+}
+
+/// Message for `key` with `{name}` placeholders filled in
+pub fn tr_args(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    fill(tr(key), args)
+}
+
+/// Replace `{name}` placeholders in a message
+fn fill(message: &str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(message.to_string(), |out, (name, value)| {
+        out.replace(&format!("{{{}}}", name), &value.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    /// Placeholder names used in a message
+    fn placeholders(message: &str) -> BTreeSet<String> {
+        regex::Regex::new(r"\{([a-z_]+)\}")
+            .unwrap()
+            .captures_iter(message)
+            .map(|c| c[1].to_string())
+            .collect()
+    }
+
+    /// Every key passed to tr/tr_args/tr_in anywhere under `dir`
+    fn referenced_keys(dir: &Path, keys: &mut BTreeSet<String>) {
+        let call = regex::Regex::new(r#"\btr(?:_args|_in)?\(\s*(?:[\w:]+,\s*)?"([^"]+)""#).unwrap();
+
+        for entry in std::fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                referenced_keys(&path, keys);
+            } else if path.extension().is_some_and(|e| e == "rs") {
+                let source = std::fs::read_to_string(&path).unwrap();
+                keys.extend(call.captures_iter(&source).map(|c| c[1].to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_code() {
+        assert_eq!(Language::from_code("de"), Some(Language::De));
+        assert_eq!(Language::from_code("de_DE.UTF-8"), Some(Language::De));
+        assert_eq!(Language::from_code("de-AT"), Some(Language::De));
+        assert_eq!(Language::from_code("en_US.UTF-8"), Some(Language::En));
+        assert_eq!(Language::from_code("C.UTF-8"), Some(Language::En));
+        assert_eq!(Language::from_code("ja_JP"), None);
+    }
+
+    #[test]
+    fn test_fallback_to_english() {
+        assert_eq!(
+            tr_in(Language::De, "cli.confirm.prompt"),
+            "Zum Fortfahren den Poolnamen ({pool}) eingeben, oder --force zum Überspringen: "
+        );
+
+        // Keys missing from the German catalog come from English
+        let untranslated = en::MESSAGES
+            .iter()
+            .find(|(key, _)| lookup(Language::De, key).is_none())
+            .expect("German catalog is partial");
+        assert_eq!(tr_in(Language::De, untranslated.0), untranslated.1);
+
+        assert_eq!(tr_in(Language::De, "no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn test_placeholders_filled() {
+        assert_eq!(
+            fill(
+                tr_in(Language::En, "validation.command_missing"),
+                &[("command", &"sgdisk")]
+            ),
+            "Required command not found: sgdisk"
+        );
+    }
+
+    #[test]
+    fn test_every_referenced_key_exists_in_english() {
+        let mut keys = BTreeSet::new();
+        referenced_keys(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut keys,
+        );
+        keys.remove("no.such.key");
+        assert!(keys.len() > 20, "key scan found too few keys: {keys:?}");
+
+        let missing: Vec<&String> = keys
+            .iter()
+            .filter(|k| lookup(Language::En, k).is_none())
+            .collect();
+        assert!(missing.is_empty(), "keys missing from en: {missing:?}");
+    }
+
+    #[test]
+    fn test_translations_match_english() {
+        for language in Language::ALL {
+            let mut seen = BTreeSet::new();
+            for (key, message) in language.catalog() {
+                assert!(seen.insert(*key), "{language}: duplicate key {key}");
+                let english = lookup(Language::En, key)
+                    .unwrap_or_else(|| panic!("{language}: {key} is not in en"));
+                assert_eq!(
+                    placeholders(message),
+                    placeholders(english),
+                    "{language}: placeholders differ for {key}"
+                );
+            }
+        }
+    }
+}
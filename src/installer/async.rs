@@ -0,0 +1,212 @@
+//! Async wrapper for embedding the installer in a tokio service
+//!
+//! Enabled with the `async` feature. The blocking installer runs on tokio's
+//! blocking thread pool; progress events are forwarded into a tokio channel and
+//! a caller-supplied future is mapped onto the installer's [`CancelToken`].
+
+use crate::config::Config;
+use crate::error::{InstallerError, Result};
+use crate::installer::{CancelToken, InstallEvent, InstallReport, Installer, ProgressReporter};
+use std::future::Future;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Forwards events into a tokio channel from the installer's blocking thread
+///
+/// Waits for room in the channel, so the installer stalls until the
+/// receiver catches up.
+struct BlockingEventForwarder {
+    tx: mpsc::Sender<InstallEvent>,
+}
+
+impl ProgressReporter for BlockingEventForwarder {
+    fn report(&self, event: InstallEvent) {
+        // A receiver that has gone away must not abort the installation
+        let _ = self.tx.blocking_send(event);
+    }
+}
+
+/// Run an installation without blocking the runtime
+///
+/// Events are sent to `progress_tx` as they happen; when `cancel` completes the
+/// installation stops at the next step with `UserCancelled`. Must be
+/// called from within a tokio runtime.
+///
+/// No event is dropped: once `progress_tx` is full the installation waits
+/// for it to be read, so keep draining the receiver (or drop it) until the
+/// returned handle completes.
+pub fn install_async<F>(
+    config: Config,
+    progress_tx: mpsc::Sender<InstallEvent>,
+    cancel: F,
+) -> JoinHandle<Result<InstallReport>>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    match Installer::new(config) {
+        Ok(installer) => spawn_install(installer, progress_tx, cancel),
+        Err(e) => tokio::spawn(async move { Err(e) }),
+    }
+}
+
+/// Like [`install_async`], for an already constructed installer
+pub fn spawn_install<F>(
+    installer: Installer,
+    progress_tx: mpsc::Sender<InstallEvent>,
+    cancel: F,
+) -> JoinHandle<Result<InstallReport>>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let token = CancelToken::new();
+    let watcher = {
+        let token = token.clone();
+        tokio::spawn(async move {
+            cancel.await;
+            token.cancel();
+        })
+    };
+
+    let mut installer = installer.with_cancel_token(token);
+    let install = tokio::task::spawn_blocking(move || {
+        let reporter = BlockingEventForwarder { tx: progress_tx };
+        installer.install_with_progress(&reporter)
+    });
+
+    tokio::spawn(async move {
+        let result = install.await.unwrap_or_else(|e| {
+            Err(InstallerError::Other(format!(
+                "Installer task failed: {}",
+                e
+            )))
+        });
+        watcher.abort();
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::SysRoot;
+    use crate::exec::{CommandExecutor, ExecOptions, ExecResult, RecordingExecutor};
+    use crate::installer::{HookPoint, HookSpec, Phase};
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    fn config() -> Config {
+        Config {
+            devices: vec![PathBuf::from("/dev/zbm-missing")],
+            skip_preflight: true,
+            dry_run: true,
+            ..Default::default()
+        }
+    }
+
+    /// Executor that blocks every command until the test releases it
+    struct GateExecutor {
+        release: Mutex<std_mpsc::Receiver<()>>,
+        inner: RecordingExecutor,
+    }
+
+    impl CommandExecutor for GateExecutor {
+        fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+            let _ = self.release.lock().unwrap().recv();
+            self.inner.execute_with(cmd, options)
+        }
+    }
+
+    async fn collect(mut rx: mpsc::Receiver<InstallEvent>) -> Vec<InstallEvent> {
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_events_forwarded_in_order() {
+        let installer = Installer::new(config())
+            .unwrap()
+            .with_executor(Arc::new(RecordingExecutor::new()));
+        let (tx, rx) = mpsc::channel(1);
+
+        let handle = spawn_install(installer, tx, std::future::pending());
+        let events = collect(rx).await;
+        let result = handle.await.unwrap();
+
+        // The missing device fails disk preparation before it starts
+        assert!(matches!(
+            result.unwrap_err().root_cause(),
+            InstallerError::MultiDevice(_)
+        ));
+        assert!(matches!(
+            events.as_slice(),
+            [
+                InstallEvent::PhaseStarted {
+                    phase: Phase::Validate,
+                    total_steps: 0
+                },
+                InstallEvent::PhaseFinished {
+                    phase: Phase::Validate,
+                    ..
+                },
+                InstallEvent::PhaseStarted {
+                    phase: Phase::PrepareDisks,
+                    total_steps: 3
+                },
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_future_stops_at_next_step() {
+        // Hooks only go through the executor outside dry-run mode
+        let mut config = Config {
+            dry_run: false,
+            ..config()
+        };
+        config.hooks.insert(
+            HookPoint::Pre(Phase::Validate),
+            vec![HookSpec::new("/hooks/slow")],
+        );
+
+        let (release_tx, release_rx) = std_mpsc::channel();
+        let installer = Installer::new(config)
+            .unwrap()
+            .with_executor(Arc::new(GateExecutor {
+                release: Mutex::new(release_rx),
+                inner: RecordingExecutor::new(),
+            }))
+            .with_sys_root(SysRoot::fixture());
+        let (tx, mut rx) = mpsc::channel(8);
+        let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel::<()>();
+
+        let handle = spawn_install(installer, tx, async move {
+            let _ = cancel_rx.await;
+        });
+
+        // Validate has started and is blocked in its pre hook
+        assert_eq!(
+            rx.recv().await,
+            Some(InstallEvent::PhaseStarted {
+                phase: Phase::Validate,
+                // Checking the kernel module
+                total_steps: 1
+            })
+        );
+        cancel_tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        release_tx.send(()).unwrap();
+        // Let the module check through too
+        drop(release_tx);
+
+        let events = collect(rx).await;
+        let err = handle.await.unwrap().unwrap_err();
+        assert!(err.is_cancelled());
+        // The module check never starts, and no other phase does either
+        assert_eq!(events, vec![]);
+    }
+}
@@ -0,0 +1,304 @@
+//! Cooperative cancellation
+//!
+//! A [`CancelToken`] is checked before every step, so a cancelled
+//! installation stops at the next safe point and rolls back like any other
+//! failure. [`cancel_on_signals`] ties a token to SIGINT and SIGTERM: the
+//! first signal cancels it and kills whatever long-running command (rsync,
+//! dracut) is in progress; a second one kills every running command and
+//! exits immediately, after putting back the terminal settings saved by
+//! [`save_terminal`].
+//!
+//! Commands run in their own process group so a Ctrl-C on the terminal
+//! reaches only the installer, which decides what to stop.
+
+use crate::error::{InstallerError, Result};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Exit status used when a second signal forces the installer to quit
+const FORCED_EXIT: i32 = 130;
+
+/// SIGINT/SIGTERM received since the handlers were installed
+static SIGNALS: SignalState = SignalState::new();
+
+/// Most commands whose process groups are tracked at once
+const MAX_CHILD_GROUPS: usize = 64;
+
+/// Process groups of the commands running right now
+static CHILD_GROUPS: ChildGroups = ChildGroups::new();
+
+/// Terminal settings to restore on a forced exit
+static SAVED_TERMINAL: OnceLock<libc::termios> = OnceLock::new();
+
+/// Leaves the alternate screen and shows the cursor again
+const RESET_SCREEN: &[u8] = b"\x1b[?1049l\x1b[?25h\n";
+
+/// Shared flag used to ask a running installation to stop
+///
+/// The installer checks the token between phases and steps; clones share
+/// the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a token that has not been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Fail with `UserCancelled` if cancellation has been requested
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(InstallerError::UserCancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Cancel `token` on the first SIGINT or SIGTERM and exit on the second
+///
+/// Can be called once per process; later calls fail.
+pub fn cancel_on_signals(token: &CancelToken) -> Result<()> {
+    SIGNALS
+        .token
+        .set(token.clone())
+        .map_err(|_| InstallerError::Other("signal handlers are already installed".to_string()))?;
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: the handler only touches atomics and calls async-signal-safe
+        // functions (killpg, _exit)
+        let installed = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut())
+        };
+        if installed != 0 {
+            return Err(InstallerError::Other(format!(
+                "Failed to install signal handler: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Remember the terminal settings, to be restored if a second signal forces
+/// an exit
+///
+/// The TUI calls this before taking over the terminal: a forced exit skips
+/// its normal teardown. Does nothing if standard input isn't a terminal.
+pub fn save_terminal() {
+    // SAFETY: tcgetattr only writes to the termios it is given
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+            let _ = SAVED_TERMINAL.set(termios);
+        }
+    }
+}
+
+/// Whether SIGINT or SIGTERM has been received
+pub(crate) fn signalled() -> bool {
+    SIGNALS.signalled()
+}
+
+/// Signals received and the token the first one cancels
+///
+/// Kept apart from the handler, so the counting can be tested without
+/// installing it.
+struct SignalState {
+    /// Number of signals received
+    count: AtomicUsize,
+    /// Token cancelled by the first signal
+    token: OnceLock<CancelToken>,
+}
+
+impl SignalState {
+    const fn new() -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            token: OnceLock::new(),
+        }
+    }
+
+    /// Count a signal, cancelling the token on the first; `true` for any
+    /// later one, which forces an exit
+    fn receive(&self) -> bool {
+        if self.count.fetch_add(1, Ordering::SeqCst) > 0 {
+            return true;
+        }
+        if let Some(token) = self.token.get() {
+            token.cancel();
+        }
+        false
+    }
+
+    fn signalled(&self) -> bool {
+        self.count.load(Ordering::SeqCst) > 0
+    }
+}
+
+/// Track the process group of a command being waited for, so a forced
+/// exit kills it, until the returned guard is dropped
+pub(crate) fn track_child_group(group: i32) -> ChildGroup<'static> {
+    CHILD_GROUPS.track(group)
+}
+
+/// Process groups of running commands, 0 in free slots
+///
+/// Devices are prepared in parallel, so several commands can be running
+/// when a forced exit has to kill them. The slots are atomics because the
+/// signal handler reads them, where no lock can be taken.
+struct ChildGroups([AtomicI32; MAX_CHILD_GROUPS]);
+
+impl ChildGroups {
+    const fn new() -> Self {
+        Self([const { AtomicI32::new(0) }; MAX_CHILD_GROUPS])
+    }
+
+    fn track(&self, group: i32) -> ChildGroup<'_> {
+        let slot = self.0.iter().position(|slot| {
+            slot.compare_exchange(0, group, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+        if slot.is_none() {
+            log::warn!(
+                "Too many commands running to kill process group {} on a forced exit",
+                group
+            );
+        }
+        ChildGroup { groups: self, slot }
+    }
+
+    /// Kill every tracked group; async-signal-safe
+    fn kill_all(&self) {
+        for slot in &self.0 {
+            let group = slot.load(Ordering::SeqCst);
+            if group > 0 {
+                // SAFETY: killpg is async-signal-safe
+                unsafe {
+                    libc::killpg(group, libc::SIGKILL);
+                }
+            }
+        }
+    }
+}
+
+/// A tracked process group, forgotten when dropped
+pub(crate) struct ChildGroup<'a> {
+    groups: &'a ChildGroups,
+    slot: Option<usize>,
+}
+
+impl Drop for ChildGroup<'_> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            self.groups.0[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+extern "C" fn on_signal(_signal: libc::c_int) {
+    if !SIGNALS.receive() {
+        return;
+    }
+
+    // Second signal: the user doesn't want to wait for the cleanup
+    CHILD_GROUPS.kill_all();
+    // SAFETY: tcsetattr, write and _exit are async-signal-safe
+    unsafe {
+        if let Some(termios) = SAVED_TERMINAL.get() {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+            libc::write(
+                libc::STDOUT_FILENO,
+                RESET_SCREEN.as_ptr().cast(),
+                RESET_SCREEN.len(),
+            );
+        }
+        libc::_exit(FORCED_EXIT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_flag() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(InstallerError::UserCancelled)));
+    }
+
+    #[test]
+    fn test_first_signal_cancels_token() {
+        let state = SignalState::new();
+        let token = CancelToken::new();
+        state.token.set(token.clone()).unwrap();
+        assert!(!state.signalled());
+
+        assert!(!state.receive());
+        assert!(state.signalled());
+        assert!(token.is_cancelled());
+
+        // The second one forces the exit
+        assert!(state.receive());
+    }
+
+    #[test]
+    fn test_forced_exit_kills_every_tracked_group() {
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+        use std::process::Command;
+
+        let groups = ChildGroups::new();
+        let mut children: Vec<_> = (0..2)
+            .map(|_| {
+                Command::new("sleep")
+                    .arg("30")
+                    .process_group(0)
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        let tracked: Vec<_> = children
+            .iter()
+            .map(|child| groups.track(child.id() as i32))
+            .collect();
+
+        // A finished command frees its slot without forgetting the others
+        let finished = groups.track(i32::MAX);
+        drop(finished);
+        assert_eq!(
+            groups
+                .0
+                .iter()
+                .filter(|slot| slot.load(Ordering::SeqCst) != 0)
+                .count(),
+            2
+        );
+
+        groups.kill_all();
+        for child in &mut children {
+            assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGKILL));
+        }
+        drop(tracked);
+        assert!(groups.0.iter().all(|slot| slot.load(Ordering::SeqCst) == 0));
+    }
+}
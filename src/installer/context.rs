@@ -0,0 +1,82 @@
+//! Artifacts carried between installation phases
+
+use crate::disk::{BlockDevice, ZbmPartitions};
+use crate::installer::phase::Phase;
+use crate::installer::report::{NetworkReport, PhaseTiming};
+use crate::installer::rollback::{UndoAction, UndoLog};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// State produced by earlier phases and consumed by later ones
+///
+/// Available through [`Installer::context`](super::Installer::context) while
+/// driving the phases one at a time.
+#[derive(Debug, Default)]
+pub struct InstallContext {
+    pub(super) started_at: Option<String>,
+    pub(super) completed: Vec<Phase>,
+    pub(super) devices: Vec<BlockDevice>,
+    pub(super) partitions: Vec<ZbmPartitions>,
+    pub(super) partuuids: BTreeMap<PathBuf, String>,
+    pub(super) pool_guid: Option<String>,
+    pub(super) mount_point: Option<PathBuf>,
+    pub(super) zbm_version: Option<String>,
+    pub(super) bootloader_error: Option<String>,
+    pub(super) network: Option<NetworkReport>,
+    pub(super) timings: Vec<PhaseTiming>,
+    pub(super) undo: UndoLog,
+}
+
+impl InstallContext {
+    /// Phases that have completed, in order
+    pub fn completed_phases(&self) -> &[Phase] {
+        &self.completed
+    }
+
+    /// Whether `phase` has completed
+    pub fn is_completed(&self, phase: Phase) -> bool {
+        self.completed.contains(&phase)
+    }
+
+    /// Devices prepared by [`Phase::PrepareDisks`]
+    pub fn devices(&self) -> &[BlockDevice] {
+        &self.devices
+    }
+
+    /// Partitions created by [`Phase::PrepareDisks`], one entry per device
+    pub fn partitions(&self) -> &[ZbmPartitions] {
+        &self.partitions
+    }
+
+    /// Where [`Phase::MountFilesystem`] mounted the boot environment
+    pub fn mount_point(&self) -> Option<&Path> {
+        self.mount_point.as_deref()
+    }
+
+    /// ZFSBootMenu version installed by [`Phase::InstallBootloader`]
+    pub fn zbm_version(&self) -> Option<&str> {
+        self.zbm_version.as_deref()
+    }
+
+    /// Why the bootloader failed to install, when
+    /// `ignore_bootloader_errors` let [`Phase::InstallBootloader`] finish
+    pub fn bootloader_error(&self) -> Option<&str> {
+        self.bootloader_error.as_deref()
+    }
+
+    /// Network configuration [`Phase::MigrateSystem`] carried over
+    pub fn network(&self) -> Option<&NetworkReport> {
+        self.network.as_ref()
+    }
+
+    /// Duration of each completed phase
+    pub fn timings(&self) -> &[PhaseTiming] {
+        &self.timings
+    }
+
+    /// Changes that [`Installer::rollback`](super::Installer::rollback) would
+    /// undo, oldest first
+    pub fn pending_undo(&self) -> Vec<UndoAction> {
+        self.undo.actions()
+    }
+}
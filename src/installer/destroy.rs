@@ -0,0 +1,442 @@
+//! Undoing an installation
+//!
+//! [`Installer::destroy`] removes what an install left behind: the
+//! ZFSBootMenu boot entries of the pool's disks, the ZFSBootMenu files on
+//! their ESPs,
+//! the pool itself and, if asked, the signatures on the pool's partitions.
+//! Everything is worked out before anything is removed, so a dry run lists
+//! exactly what a real run removes.
+
+use super::{Installer, TARGET_MOUNTPOINT};
+use crate::bootloader::efi;
+use crate::config::Config;
+use crate::disk::block_device::{read_mounts, resolve_dev_path};
+use crate::disk::{DeviceDiscovery, MountGuard, SysRoot};
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, FileOp, SystemExecutor};
+use crate::zfs::status::imported_members;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// Label the installer's firmware boot entries start with
+const BOOT_ENTRY_LABEL: &str = "ZFSBootMenu";
+
+/// Directory on an ESP holding the ZFSBootMenu images
+const ZBM_DIR: &str = "EFI/ZBM";
+
+/// Where each ESP is mounted while its ZFSBootMenu files are removed
+const ESP_MOUNTPOINT: &str = "/run/zbm-installer/esp";
+
+/// What [`Installer::destroy`] does with the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DestroyOptions {
+    /// Export the pool instead of destroying it, keeping its data
+    pub keep_pool: bool,
+    /// Remove the ZFS signatures from the pool's partitions once it is destroyed
+    pub wipe_partitions: bool,
+}
+
+/// Something [`Installer::destroy`] removes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Removal {
+    /// A firmware boot entry
+    BootEntry {
+        /// Four-digit hex boot number
+        bootnum: String,
+        /// Entry label
+        label: String,
+    },
+    /// The ZFSBootMenu directory on an ESP
+    EspFiles {
+        /// The ESP
+        partition: PathBuf,
+        /// Its filesystem UUID, if blkid found one
+        uuid: Option<String>,
+    },
+    /// The pool, imported first if it isn't
+    Pool {
+        /// Pool name
+        name: String,
+        /// Whether the pool is imported now
+        imported: bool,
+    },
+    /// The pool, exported but kept
+    ExportPool {
+        /// Pool name
+        name: String,
+    },
+    /// The signatures on one of the pool's partitions
+    Partition {
+        /// The partition
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for Removal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BootEntry { bootnum, label } => {
+                write!(f, "boot entry Boot{} ({})", bootnum, label)
+            }
+            Self::EspFiles { partition, uuid } => {
+                write!(f, "/{} on {}", ZBM_DIR, partition.display())?;
+                match uuid {
+                    Some(uuid) => write!(f, " (UUID {})", uuid),
+                    None => Ok(()),
+                }
+            }
+            Self::Pool { name, .. } => write!(f, "pool {}", name),
+            Self::ExportPool { name } => write!(f, "pool {} (exported, data kept)", name),
+            Self::Partition { path } => write!(f, "ZFS signatures on {}", path.display()),
+        }
+    }
+}
+
+impl Installer {
+    /// Create an installer for [`destroy`](Self::destroy)
+    ///
+    /// Only the pool name has to be valid: nothing is installed, so devices
+    /// and the rest of the configuration are ignored.
+    pub fn for_pool(config: Config) -> Result<Self> {
+        Config::validate_pool_name(&config.pool_name)?;
+        Ok(Self::unchecked(config))
+    }
+
+    /// Remove what an installation onto the configured pool left behind
+    ///
+    /// Boot entries labelled `ZFSBootMenu (<disk>)` for the pool's disks and
+    /// `/EFI/ZBM` on their ESPs go first, then the pool is destroyed (or only exported), and
+    /// finally its partitions are wiped if asked. Refuses to touch the pool
+    /// the running system booted from. In dry-run mode nothing is removed;
+    /// the returned list is what would be.
+    pub fn destroy(&self, options: DestroyOptions) -> Result<Vec<Removal>> {
+        let removals = self.removals(options)?;
+
+        for removal in &removals {
+            if self.config.dry_run {
+                log::info!("[DRY RUN] Would remove {}", removal);
+                continue;
+            }
+            self.cancel.check()?;
+            log::info!("Removing {}", removal);
+            self.remove(removal)?;
+        }
+        Ok(removals)
+    }
+
+    /// Work out everything [`destroy`](Self::destroy) removes, in order,
+    /// without removing anything
+    pub fn removals(&self, options: DestroyOptions) -> Result<Vec<Removal>> {
+        let pool = &self.config.pool_name;
+        if let Some(source) = booted_from(&self.sys_root, pool) {
+            return Err(InstallerError::validation(format!(
+                "The running system's root filesystem ({}) is on pool {}; \
+                 boot from other media to remove it",
+                source.display(),
+                pool
+            )));
+        }
+
+        let query = self.query_executor();
+        let members: Vec<_> = imported_members(query.as_ref())?;
+        let imported = members.iter().any(|member| &member.pool == pool);
+        let discovery = DeviceDiscovery::new()?
+            .with_sys_root(self.sys_root.clone())
+            .with_probe_executor(query.clone());
+        let disks: Vec<_> = discovery
+            .scan_devices()?
+            .into_iter()
+            .filter(|disk| disk.zfs_pool_in(&self.sys_root, &members).as_ref() == Some(pool))
+            .collect();
+        if !imported && disks.is_empty() {
+            return Err(InstallerError::zfs(
+                format!("find pool {}", pool),
+                "no imported pool or disk with its label exists".to_string(),
+            ));
+        }
+
+        // Firmware without EFI variables (legacy BIOS) has no entries to remove.
+        // Entries of other pools' disks share the label prefix and are kept.
+        let entries = efi::list(query.as_ref()).unwrap_or_else(|e| {
+            log::warn!("Could not read the firmware boot entries: {}", e);
+            Default::default()
+        });
+        let disk_names: Vec<String> = disks
+            .iter()
+            .filter_map(|disk| disk.path.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect();
+        let mut removals: Vec<Removal> = entries
+            .entries
+            .into_iter()
+            .filter(|(_, label)| is_entry_for(label, &disk_names))
+            .map(|(bootnum, label)| Removal::BootEntry { bootnum, label })
+            .collect();
+
+        removals.extend(disks.iter().flat_map(|disk| {
+            disk.partitions
+                .iter()
+                .filter(|p| p.fstype.as_deref() == Some("vfat"))
+                .map(|p| Removal::EspFiles {
+                    partition: p.path.clone(),
+                    uuid: p.uuid.clone(),
+                })
+        }));
+
+        if options.keep_pool {
+            if imported {
+                removals.push(Removal::ExportPool { name: pool.clone() });
+            }
+            return Ok(removals);
+        }
+        removals.push(Removal::Pool {
+            name: pool.clone(),
+            imported,
+        });
+
+        if options.wipe_partitions {
+            let vdevs: Vec<PathBuf> = members
+                .iter()
+                .filter(|member| &member.pool == pool)
+                .map(|member| resolve_dev_path(&self.sys_root, &member.device))
+                .collect();
+            removals.extend(disks.iter().flat_map(|disk| {
+                disk.partitions
+                    .iter()
+                    .filter(|p| {
+                        vdevs.contains(&p.path)
+                            || (p.fstype.as_deref() == Some("zfs_member")
+                                && p.label.as_ref() == Some(pool))
+                    })
+                    .map(|p| Removal::Partition {
+                        path: p.path.clone(),
+                    })
+            }));
+        }
+        Ok(removals)
+    }
+
+    /// Remove one thing found by [`removals`](Self::removals)
+    fn remove(&self, removal: &Removal) -> Result<()> {
+        match removal {
+            Removal::BootEntry { bootnum, .. } => efi::delete(self.executor.as_ref(), bootnum),
+            Removal::EspFiles { partition, .. } => {
+                let esp = MountGuard::mount(
+                    self.executor.clone(),
+                    partition,
+                    Path::new(ESP_MOUNTPOINT),
+                    "vfat",
+                )?;
+                self.executor
+                    .apply(FileOp::remove(esp.target().join(ZBM_DIR)))
+            }
+            Removal::Pool { imported, .. } => {
+                let pool = self.existing_pool();
+                if !imported {
+                    pool.import_at(Path::new(TARGET_MOUNTPOINT))?;
+                }
+                pool.destroy()
+            }
+            Removal::ExportPool { .. } => self.existing_pool().export(),
+            Removal::Partition { path } => {
+                self.executor
+                    .execute(Command::new("wipefs").arg("-a").arg(path))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Executor for finding what to remove
+    ///
+    /// A dry run still has to look at the live system to list anything, so
+    /// its queries run for real unless a synthetic sys root is in use.
+    fn query_executor(&self) -> Arc<dyn CommandExecutor> {
+        if self.config.dry_run && self.sys_root == SysRoot::default() {
+            Arc::new(SystemExecutor::new(false))
+        } else {
+            self.executor.clone()
+        }
+    }
+}
+
+/// Whether `label` is one of the installer's boot entries for a disk in
+/// `disk_names`, as labelled by [`entry_label`](crate::bootloader::entry_label)
+fn is_entry_for(label: &str, disk_names: &[String]) -> bool {
+    label.starts_with(BOOT_ENTRY_LABEL)
+        && disk_names
+            .iter()
+            .any(|name| label.ends_with(&format!(" ({})", name)))
+}
+
+/// Source of the root filesystem, if it is a dataset of `pool`
+fn booted_from(root: &SysRoot, pool: &str) -> Option<PathBuf> {
+    read_mounts(root)
+        .into_iter()
+        .find(|(source, target)| {
+            target == Path::new("/")
+                && source
+                    .to_str()
+                    .is_some_and(|s| s == pool || s.starts_with(&format!("{}/", pool)))
+        })
+        .map(|(source, _)| source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    const BLKID: &str = "DEVNAME=/dev/sda1\nUUID=7A3C-19F2\nTYPE=vfat\nLABEL=EFI\n\n\
+                         DEVNAME=/dev/sda2\nLABEL=zroot\nUUID=1234567890\nTYPE=zfs_member\n";
+
+    const STATUS: &str = "  pool: zroot
+ state: ONLINE
+config:
+
+\tNAME         STATE     READ WRITE CKSUM
+\tzroot        ONLINE       0     0     0
+\t  /dev/sda2  ONLINE       0     0     0
+";
+
+    const EFIBOOTMGR: &str = "BootCurrent: 0001
+BootOrder: 0003,0004,0001
+Boot0001* Windows Boot Manager
+Boot0003* ZFSBootMenu (sda)
+Boot0004* ZFSBootMenu Backup (sda)
+Boot0005* ZFSBootMenu (sdb)
+";
+
+    fn destroyer(executor: Arc<RecordingExecutor>, dry_run: bool) -> Installer {
+        let config = Config {
+            dry_run,
+            ..Default::default()
+        };
+        Installer::for_pool(config)
+            .unwrap()
+            .with_executor(executor)
+            .with_sys_root(SysRoot::fixture())
+    }
+
+    #[test]
+    fn test_dry_run_lists_removals_without_removing() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("zpool", STATUS);
+        executor.respond_program("blkid", BLKID);
+        executor.respond_program("efibootmgr", EFIBOOTMGR);
+
+        let options = DestroyOptions {
+            wipe_partitions: true,
+            ..Default::default()
+        };
+        let removals: Vec<String> = destroyer(executor.clone(), true)
+            .destroy(options)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            removals,
+            vec![
+                "boot entry Boot0003 (ZFSBootMenu (sda))",
+                "boot entry Boot0004 (ZFSBootMenu Backup (sda))",
+                "/EFI/ZBM on /dev/sda1 (UUID 7A3C-19F2)",
+                "pool zroot",
+                "ZFS signatures on /dev/sda2",
+            ]
+        );
+        assert!(executor.file_ops().is_empty());
+        assert!(!executor.commands().iter().any(|c| c
+            .args
+            .iter()
+            .any(|a| a == "--delete-bootnum" || a == "destroy")));
+    }
+
+    #[test]
+    fn test_destroy_removes_entries_files_and_pool() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("zpool", STATUS);
+        executor.respond_program("blkid", BLKID);
+        executor.respond_program("efibootmgr", EFIBOOTMGR);
+
+        destroyer(executor.clone(), false)
+            .destroy(DestroyOptions::default())
+            .unwrap();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| format!("{} {}", c.program, c.args.join(" ")))
+            .collect();
+        assert!(commands.contains(&"efibootmgr --bootnum 0003 --delete-bootnum".to_string()));
+        assert!(commands.contains(&"efibootmgr --bootnum 0004 --delete-bootnum".to_string()));
+        assert!(!commands.iter().any(|c| c.contains("0001 --delete")));
+        // sdb belongs to another pool
+        assert!(!commands.iter().any(|c| c.contains("0005 --delete")));
+        assert!(commands.iter().any(|c| c.starts_with("zpool destroy")));
+        assert!(!commands.iter().any(|c| c.starts_with("wipefs")));
+        assert!(executor
+            .file_ops()
+            .contains(&FileOp::remove(Path::new(ESP_MOUNTPOINT).join(ZBM_DIR))));
+    }
+
+    #[test]
+    fn test_entries_of_other_disks_are_kept() {
+        let disks = ["sda".to_string(), "nvme0n1".to_string()];
+        assert!(is_entry_for("ZFSBootMenu (sda)", &disks));
+        assert!(is_entry_for("ZFSBootMenu Backup (nvme0n1)", &disks));
+        assert!(!is_entry_for("ZFSBootMenu (sdb)", &disks));
+        assert!(!is_entry_for("ZFSBootMenu (sdaa)", &disks));
+        assert!(!is_entry_for("Windows Boot Manager (sda)", &disks));
+        assert!(!is_entry_for("ZFSBootMenu", &disks));
+    }
+
+    #[test]
+    fn test_keep_pool_exports_it() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("zpool", STATUS);
+        executor.respond_program("blkid", BLKID);
+
+        let options = DestroyOptions {
+            keep_pool: true,
+            wipe_partitions: false,
+        };
+        let removals = destroyer(executor, true).destroy(options).unwrap();
+        assert_eq!(
+            removals.last(),
+            Some(&Removal::ExportPool {
+                name: "zroot".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_refuses_booted_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("proc")).unwrap();
+        std::fs::write(
+            dir.path().join("proc/mounts"),
+            "zroot/ROOT/default / zfs rw,relatime,xattr,posixacl 0 0\n",
+        )
+        .unwrap();
+        let executor = Arc::new(RecordingExecutor::new());
+
+        let err = destroyer(executor.clone(), false)
+            .with_sys_root(SysRoot::new(dir.path()))
+            .destroy(DestroyOptions::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("zroot/ROOT/default"));
+        assert!(executor.commands().is_empty());
+    }
+
+    #[test]
+    fn test_missing_pool_is_an_error() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let err = destroyer(executor, false)
+            .destroy(DestroyOptions::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("find pool zroot"));
+    }
+}
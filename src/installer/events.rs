@@ -0,0 +1,326 @@
+//! Machine-readable event stream
+//!
+//! Automation (Ansible, a provisioning web UI) follows an installation as
+//! one JSON object per line instead of scraping the log. An [`EventSink`]
+//! set with [`Installer::with_event_sink`](super::Installer::with_event_sink)
+//! receives phase transitions and steps, every command with its duration and
+//! exit status, the pre-flight results and a final summary. Steps of a phase
+//! that works on several disks at once name the disk they are on.
+//!
+//! A dry run of a single-disk install starts and ends like this:
+//!
+//! ```
+//! use zbm_installer::installer::{BootloaderReport, Event, InstallSummary, Phase};
+//! use zbm_installer::{Check, CheckResult};
+//!
+//! let events = [
+//!     Event::PhaseStarted { phase: Phase::Validate, total_steps: 1 },
+//!     Event::Check(CheckResult::ok(Check::RootPrivileges)),
+//!     Event::PhaseFinished { phase: Phase::Validate, duration_ms: 12 },
+//!     Event::PhaseStarted { phase: Phase::PrepareDisks, total_steps: 3 },
+//!     Event::Step {
+//!         phase: Phase::PrepareDisks,
+//!         device: Some("/dev/sda".into()),
+//!         label: "Wiping /dev/sda (signatures)".into(),
+//!         percent: 0,
+//!     },
+//!     Event::Command {
+//!         argv: vec!["wipefs".into(), "-a".into(), "/dev/sda".into()],
+//!         duration_ms: 0,
+//!         exit_code: None,
+//!         skipped: true,
+//!     },
+//!     Event::Finished(InstallSummary {
+//!         dry_run: true,
+//!         pool: "zroot".into(),
+//!         pool_guid: None,
+//!         datasets: vec!["zroot/ROOT".into(), "zroot/ROOT/default".into()],
+//!         esp_partuuids: vec![],
+//!         bootloader: Some(BootloaderReport { name: "systemd-boot".into(), error: None }),
+//!     }),
+//! ];
+//! let lines: Vec<String> = events
+//!     .iter()
+//!     .map(|event| serde_json::to_string(event).unwrap())
+//!     .collect();
+//! assert_eq!(
+//!     lines,
+//!     [
+//!         r#"{"event":"phase-started","phase":"Validate","total_steps":1}"#,
+//!         r#"{"event":"check","check":"root-privileges","severity":"ok"}"#,
+//!         r#"{"event":"phase-finished","phase":"Validate","duration_ms":12}"#,
+//!         r#"{"event":"phase-started","phase":"PrepareDisks","total_steps":3}"#,
+//!         r#"{"event":"step","phase":"PrepareDisks","device":"/dev/sda","label":"Wiping /dev/sda (signatures)","percent":0}"#,
+//!         r#"{"event":"command","argv":["wipefs","-a","/dev/sda"],"duration_ms":0,"exit_code":null,"skipped":true}"#,
+//!         r#"{"event":"finished","dry_run":true,"pool":"zroot","pool_guid":null,"datasets":["zroot/ROOT","zroot/ROOT/default"],"esp_partuuids":[],"bootloader":{"name":"systemd-boot","error":null}}"#,
+//!     ]
+//! );
+//! ```
+
+use crate::error::{InstallerError, Result};
+use crate::exec::{command_argv, CommandExecutor, ExecOptions, ExecResult, FileOp, PlannedAction};
+use crate::installer::progress::{InstallEvent, ProgressReporter};
+use crate::installer::{BootloaderReport, InstallReport, Phase};
+use crate::validation::CheckResult;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Something that happened, as automation sees it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A phase is about to run
+    PhaseStarted {
+        /// The phase
+        phase: Phase,
+        /// Number of steps the phase will report
+        total_steps: usize,
+    },
+    /// A phase moved on to a new piece of work
+    Step {
+        /// The phase
+        phase: Phase,
+        /// Device the work is on, when a phase works on several at once
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        device: Option<PathBuf>,
+        /// What is being done
+        label: String,
+        /// Completion of the phase, 0-100
+        percent: u8,
+    },
+    /// Something a phase did on the side
+    Notice {
+        /// The phase
+        phase: Phase,
+        /// What happened
+        message: String,
+    },
+    /// A phase completed successfully
+    PhaseFinished {
+        /// The phase
+        phase: Phase,
+        /// Time the phase took, including its hooks
+        duration_ms: u64,
+    },
+    /// A command ran, failed or was skipped
+    Command {
+        /// Program followed by its arguments
+        argv: Vec<String>,
+        /// Time it took, retries included
+        duration_ms: u64,
+        /// Exit code; `None` if it was skipped, killed or couldn't start
+        exit_code: Option<i32>,
+        /// Skipped because this is a dry run
+        skipped: bool,
+    },
+    /// A pre-flight check completed
+    Check(CheckResult),
+    /// The installation completed
+    Finished(InstallSummary),
+    /// The installation failed (after rolling back, if enabled)
+    Failed {
+        /// What went wrong
+        error: String,
+    },
+}
+
+impl From<InstallEvent> for Event {
+    fn from(event: InstallEvent) -> Self {
+        match event {
+            InstallEvent::PhaseStarted { phase, total_steps } => {
+                Self::PhaseStarted { phase, total_steps }
+            }
+            InstallEvent::Step {
+                phase,
+                device,
+                label,
+                percent,
+            } => Self::Step {
+                phase,
+                device,
+                label,
+                percent,
+            },
+            InstallEvent::Notice { phase, message } => Self::Notice { phase, message },
+            InstallEvent::PhaseFinished { phase, duration_ms } => {
+                Self::PhaseFinished { phase, duration_ms }
+            }
+        }
+    }
+}
+
+/// What a finished installation produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallSummary {
+    /// Whether this was a dry run
+    pub dry_run: bool,
+    /// Pool name
+    pub pool: String,
+    /// Pool GUID, if it could be read
+    pub pool_guid: Option<String>,
+    /// Datasets in the pool
+    pub datasets: Vec<String>,
+    /// PARTUUIDs of the EFI system partitions
+    pub esp_partuuids: Vec<String>,
+    /// Whether the bootloader was installed
+    pub bootloader: Option<BootloaderReport>,
+}
+
+impl From<&InstallReport> for InstallSummary {
+    fn from(report: &InstallReport) -> Self {
+        Self {
+            dry_run: report.dry_run,
+            pool: report.pool.name.clone(),
+            pool_guid: report.pool.guid.clone(),
+            datasets: report.pool.datasets.clone(),
+            esp_partuuids: report
+                .devices
+                .iter()
+                .flat_map(|device| &device.partitions)
+                .filter(|partition| partition.role == "efi")
+                .filter_map(|partition| partition.partuuid.clone())
+                .collect(),
+            bootloader: report.bootloader.clone(),
+        }
+    }
+}
+
+/// Receives installation events
+pub trait EventSink: Send + Sync {
+    /// Handle an event
+    fn emit(&self, event: &Event);
+}
+
+/// Sink that writes each event as a line of JSON
+///
+/// Write errors are ignored, so a reader going away doesn't stop the
+/// installation.
+pub struct JsonLinesSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesSink {
+    /// Write events to `writer`
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Write events to standard output
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+
+    /// Write events to a file or named pipe
+    ///
+    /// Opening a pipe blocks until a reader opens it too.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| {
+                InstallerError::Other(format!(
+                    "Failed to open event output {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        Ok(Self::new(file))
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn emit(&self, event: &Event) {
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{}", line).and_then(|()| writer.flush());
+    }
+}
+
+/// Sink that keeps every event, for tests
+#[derive(Default)]
+pub struct RecordingSink {
+    events: Mutex<Vec<Event>>,
+}
+
+impl RecordingSink {
+    /// Create an empty recording sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events received so far, in order
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl EventSink for RecordingSink {
+    fn emit(&self, event: &Event) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
+
+/// Reporter that passes progress on to a sink as well as to another reporter
+pub(super) struct SinkReporter<'a> {
+    pub(super) sink: &'a dyn EventSink,
+    pub(super) inner: &'a dyn ProgressReporter,
+}
+
+impl ProgressReporter for SinkReporter<'_> {
+    fn report(&self, event: InstallEvent) {
+        self.sink.emit(&Event::from(event.clone()));
+        self.inner.report(event);
+    }
+}
+
+/// Executor that reports every command it runs to a sink
+pub(super) struct SinkExecutor {
+    inner: Arc<dyn CommandExecutor>,
+    sink: Arc<dyn EventSink>,
+}
+
+impl SinkExecutor {
+    pub(super) fn new(inner: Arc<dyn CommandExecutor>, sink: Arc<dyn EventSink>) -> Self {
+        Self { inner, sink }
+    }
+}
+
+impl CommandExecutor for SinkExecutor {
+    fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+        let argv = command_argv(cmd);
+        let start = Instant::now();
+        let result = self.inner.execute_with(cmd, options);
+        let (exit_code, skipped) = match &result {
+            Ok(ExecResult::Skipped { .. }) => (None, true),
+            Ok(ExecResult::Ran { status, .. }) => (status.code(), false),
+            Err(InstallerError::CommandFailed { code, .. }) => (Some(*code), false),
+            Err(_) => (None, false),
+        };
+        self.sink.emit(&Event::Command {
+            argv,
+            duration_ms: start.elapsed().as_millis() as u64,
+            exit_code,
+            skipped,
+        });
+        result
+    }
+
+    fn apply(&self, op: FileOp) -> Result<()> {
+        self.inner.apply(op)
+    }
+
+    fn plan(&self, action: PlannedAction) {
+        self.inner.plan(action)
+    }
+}
@@ -0,0 +1,522 @@
+//! Pre/post phase hook scripts
+//!
+//! Hooks are executables configured per [`HookPoint`] and run by the installer
+//! immediately before or after the matching phase. Each hook is started with
+//! the following environment:
+//!
+//! | Variable         | Value                                          |
+//! |------------------|------------------------------------------------|
+//! | `ZBM_POOL`       | Name of the pool being installed               |
+//! | `ZBM_MOUNTPOINT` | Directory the target system is mounted on      |
+//! | `ZBM_PHASE`      | Phase identifier, e.g. `prepare-disks`         |
+//! | `ZBM_HOOK_POINT` | Hook point, e.g. `post-prepare-disks`          |
+//! | `ZBM_DRY_RUN`    | `1` when running a dry run, `0` otherwise      |
+//! | `ZBM_DEVICES`    | Data devices, separated by spaces              |
+//! | `ZBM_ESP_PATHS`  | EFI system partitions, once the disks are prepared |
+//!
+//! Besides `pre-<phase>` and `post-<phase>`, three points have names of their
+//! own: `pre-install` (before anything else), `post-pool` (once the pool
+//! exists) and `post-install` (with the finished system still mounted).
+//!
+//! A non-zero exit aborts the installation unless the hook is marked optional
+//! (`ignore_failure` in a config file). In dry-run mode hooks are only logged,
+//! and recorded in the plan, unless `run_hooks_in_dry_run` is set.
+
+use crate::config::Config;
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, PlannedAction};
+use crate::installer::Phase;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Point in the pipeline at which hooks run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HookPoint {
+    /// Before the phase starts
+    Pre(Phase),
+    /// After the phase completed successfully
+    Post(Phase),
+}
+
+impl HookPoint {
+    /// The phase this hook point is attached to
+    pub fn phase(&self) -> Phase {
+        match self {
+            Self::Pre(phase) | Self::Post(phase) => *phase,
+        }
+    }
+}
+
+impl std::fmt::Display for HookPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pre(phase) => write!(f, "pre-{}", phase.id()),
+            Self::Post(phase) => write!(f, "post-{}", phase.id()),
+        }
+    }
+}
+
+impl FromStr for HookPoint {
+    type Err = InstallerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Aliases for the points unattended installs hook into most
+        let parsed = match s.replace('_', "-").as_str() {
+            "pre-install" => Some(Self::Pre(Phase::Validate)),
+            "post-pool" => Some(Self::Post(Phase::CreatePool)),
+            "post-install" => Some(Self::Post(Phase::Finalize)),
+            _ => None,
+        }
+        .or_else(|| {
+            if let Some(id) = s.strip_prefix("pre-") {
+                Phase::from_id(id).map(Self::Pre)
+            } else if let Some(id) = s.strip_prefix("post-") {
+                Phase::from_id(id).map(Self::Post)
+            } else {
+                None
+            }
+        });
+
+        parsed.ok_or_else(|| {
+            InstallerError::config(format!(
+                "Invalid hook point '{}' (expected pre-<phase>, post-<phase>, \
+                 pre-install, post-pool or post-install)",
+                s
+            ))
+        })
+    }
+}
+
+impl Serialize for HookPoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for HookPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Read a `[hooks]` table, appending the lists of keys that name the same
+/// point (`pre_install` and `pre-validate`) instead of keeping only one
+pub(crate) fn deserialize_hooks<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<HashMap<HookPoint, Vec<HookSpec>>, D::Error> {
+    struct HooksVisitor;
+
+    impl<'de> Visitor<'de> for HooksVisitor {
+        type Value = HashMap<HookPoint, Vec<HookSpec>>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("a table of hook points")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut hooks = Self::Value::new();
+            while let Some((point, specs)) = map.next_entry::<HookPoint, Vec<HookSpec>>()? {
+                hooks.entry(point).or_default().extend(specs);
+            }
+            Ok(hooks)
+        }
+    }
+
+    deserializer.deserialize_map(HooksVisitor)
+}
+
+/// A single hook executable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "HookSpecRepr")]
+pub struct HookSpec {
+    /// Path to the executable
+    pub path: PathBuf,
+    /// Continue the installation if the hook fails
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl HookSpec {
+    /// Create a required hook
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            optional: false,
+        }
+    }
+
+    /// Mark the hook as optional
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+}
+
+/// Hooks may be given as a bare path or as a table with options
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HookSpecRepr {
+    Path(PathBuf),
+    Full {
+        path: PathBuf,
+        #[serde(default, alias = "ignore_failure")]
+        optional: bool,
+    },
+}
+
+impl From<HookSpecRepr> for HookSpec {
+    fn from(repr: HookSpecRepr) -> Self {
+        match repr {
+            HookSpecRepr::Path(path) => Self::new(path),
+            HookSpecRepr::Full { path, optional } => Self { path, optional },
+        }
+    }
+}
+
+/// Runs the hooks configured for each hook point
+pub struct HookRunner {
+    hooks: HashMap<HookPoint, Vec<HookSpec>>,
+    pool_name: String,
+    devices: Vec<PathBuf>,
+    esp_paths: Vec<PathBuf>,
+    dry_run: bool,
+    run_in_dry_run: bool,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl HookRunner {
+    /// Create a hook runner for the given configuration
+    ///
+    /// The executor must actually run commands when hooks are enabled in dry-run mode.
+    pub fn new(config: &Config, executor: Arc<dyn CommandExecutor>) -> Self {
+        Self {
+            hooks: config.hooks.clone(),
+            pool_name: config.pool_name.clone(),
+            devices: config
+                .data_devices()
+                .into_iter()
+                .map(Path::to_path_buf)
+                .collect(),
+            esp_paths: Vec::new(),
+            dry_run: config.dry_run,
+            run_in_dry_run: config.run_hooks_in_dry_run,
+            executor,
+        }
+    }
+
+    /// EFI system partitions to pass to hooks, once they are known
+    pub fn with_esp_paths(mut self, esp_paths: Vec<PathBuf>) -> Self {
+        self.esp_paths = esp_paths;
+        self
+    }
+
+    /// Environment passed to hooks at the given point
+    pub fn environment(&self, point: HookPoint, mountpoint: &Path) -> Vec<(String, String)> {
+        let join = |paths: &[PathBuf]| {
+            paths
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        vec![
+            ("ZBM_POOL".to_string(), self.pool_name.clone()),
+            (
+                "ZBM_MOUNTPOINT".to_string(),
+                mountpoint.display().to_string(),
+            ),
+            ("ZBM_PHASE".to_string(), point.phase().id().to_string()),
+            ("ZBM_HOOK_POINT".to_string(), point.to_string()),
+            (
+                "ZBM_DRY_RUN".to_string(),
+                if self.dry_run { "1" } else { "0" }.to_string(),
+            ),
+            ("ZBM_DEVICES".to_string(), join(&self.devices)),
+            ("ZBM_ESP_PATHS".to_string(), join(&self.esp_paths)),
+        ]
+    }
+
+    /// Run every hook registered for `point`, in configuration order
+    pub fn run(&self, point: HookPoint, mountpoint: &Path) -> Result<()> {
+        let hooks = match self.hooks.get(&point) {
+            Some(hooks) if !hooks.is_empty() => hooks,
+            _ => return Ok(()),
+        };
+
+        let env = self.environment(point, mountpoint);
+
+        for hook in hooks {
+            if self.dry_run && !self.run_in_dry_run {
+                log::info!(
+                    "[DRY RUN] Would run {} hook: {}",
+                    point,
+                    hook.path.display()
+                );
+                self.executor.plan(PlannedAction::Hook {
+                    point: point.to_string(),
+                    path: hook.path.clone(),
+                    env: env.iter().cloned().collect(),
+                });
+                continue;
+            }
+
+            log::info!("Running {} hook: {}", point, hook.path.display());
+
+            let mut cmd = Command::new(&hook.path);
+            cmd.envs(env.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+
+            if let Err(e) = self.executor.execute(&mut cmd) {
+                if hook.optional {
+                    log::warn!(
+                        "Optional {} hook {} failed: {}",
+                        point,
+                        hook.path.display(),
+                        e
+                    );
+                    continue;
+                }
+
+                return Err(InstallerError::HookFailed {
+                    hook: hook.path.clone(),
+                    point: point.to_string(),
+                    details: e.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    fn config_with_hooks(hooks: Vec<(HookPoint, Vec<HookSpec>)>) -> Config {
+        Config {
+            pool_name: "tank".to_string(),
+            hooks: hooks.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hook_point_round_trip() {
+        let point = HookPoint::Post(Phase::PrepareDisks);
+        assert_eq!(point.to_string(), "post-prepare-disks");
+        assert_eq!("post-prepare-disks".parse::<HookPoint>().unwrap(), point);
+        assert!("during-validate".parse::<HookPoint>().is_err());
+        assert!("pre-nothing".parse::<HookPoint>().is_err());
+
+        // Aliases parse to the phase boundaries they stand for
+        for (alias, point) in [
+            ("pre_install", HookPoint::Pre(Phase::Validate)),
+            ("post-pool", HookPoint::Post(Phase::CreatePool)),
+            ("post_install", HookPoint::Post(Phase::Finalize)),
+        ] {
+            assert_eq!(alias.parse::<HookPoint>().unwrap(), point);
+        }
+    }
+
+    #[test]
+    fn test_hook_environment() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let config = config_with_hooks(vec![(
+            HookPoint::Pre(Phase::InstallBootloader),
+            vec![HookSpec::new("/etc/zbm/hooks/keys.sh")],
+        )]);
+        let runner = HookRunner::new(&config, executor.clone());
+
+        runner
+            .run(HookPoint::Pre(Phase::InstallBootloader), Path::new("/mnt"))
+            .unwrap();
+
+        let commands = executor.commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].program, "/etc/zbm/hooks/keys.sh");
+        assert_eq!(commands[0].env_var("ZBM_POOL"), Some("tank"));
+        assert_eq!(commands[0].env_var("ZBM_MOUNTPOINT"), Some("/mnt"));
+        assert_eq!(commands[0].env_var("ZBM_PHASE"), Some("install-bootloader"));
+        assert_eq!(
+            commands[0].env_var("ZBM_HOOK_POINT"),
+            Some("pre-install-bootloader")
+        );
+        assert_eq!(commands[0].env_var("ZBM_DRY_RUN"), Some("0"));
+        assert_eq!(commands[0].env_var("ZBM_ESP_PATHS"), Some(""));
+    }
+
+    #[test]
+    fn test_only_matching_hook_point_runs() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let config = config_with_hooks(vec![
+            (
+                HookPoint::Pre(Phase::CreatePool),
+                vec![HookSpec::new("/hooks/pre")],
+            ),
+            (
+                HookPoint::Post(Phase::CreatePool),
+                vec![
+                    HookSpec::new("/hooks/post-a"),
+                    HookSpec::new("/hooks/post-b"),
+                ],
+            ),
+        ]);
+        let runner = HookRunner::new(&config, executor.clone());
+
+        runner
+            .run(HookPoint::Post(Phase::CreatePool), Path::new("/mnt"))
+            .unwrap();
+
+        let programs: Vec<String> = executor.commands().into_iter().map(|c| c.program).collect();
+        assert_eq!(programs, vec!["/hooks/post-a", "/hooks/post-b"]);
+    }
+
+    #[test]
+    fn test_required_hook_failure_aborts() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("/hooks/fail", 1, "boom");
+        let config = config_with_hooks(vec![(
+            HookPoint::Post(Phase::Validate),
+            vec![HookSpec::new("/hooks/fail"), HookSpec::new("/hooks/next")],
+        )]);
+        let runner = HookRunner::new(&config, executor.clone());
+
+        let err = runner
+            .run(HookPoint::Post(Phase::Validate), Path::new("/mnt"))
+            .unwrap_err();
+
+        assert!(matches!(err, InstallerError::HookFailed { .. }));
+        assert!(err.to_string().contains("post-validate"));
+        // Later hooks must not run once a required hook failed
+        assert_eq!(executor.commands().len(), 1);
+    }
+
+    #[test]
+    fn test_optional_hook_failure_continues() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("/hooks/fail", 1, "boom");
+        let config = config_with_hooks(vec![(
+            HookPoint::Post(Phase::Validate),
+            vec![
+                HookSpec::new("/hooks/fail").optional(),
+                HookSpec::new("/hooks/next"),
+            ],
+        )]);
+        let runner = HookRunner::new(&config, executor.clone());
+
+        runner
+            .run(HookPoint::Post(Phase::Validate), Path::new("/mnt"))
+            .unwrap();
+        assert_eq!(executor.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_dry_run_skips_hooks() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let mut config = config_with_hooks(vec![(
+            HookPoint::Pre(Phase::Finalize),
+            vec![HookSpec::new("/hooks/pre-finalize")],
+        )]);
+        config.dry_run = true;
+
+        let runner = HookRunner::new(&config, executor.clone());
+        runner
+            .run(HookPoint::Pre(Phase::Finalize), Path::new("/mnt"))
+            .unwrap();
+        assert!(executor.commands().is_empty());
+
+        config.run_hooks_in_dry_run = true;
+        let runner = HookRunner::new(&config, executor.clone());
+        runner
+            .run(HookPoint::Pre(Phase::Finalize), Path::new("/mnt"))
+            .unwrap();
+        let commands = executor.commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].env_var("ZBM_DRY_RUN"), Some("1"));
+    }
+
+    #[test]
+    fn test_hook_spec_deserialize_forms() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            hooks: HashMap<HookPoint, Vec<HookSpec>>,
+        }
+
+        let parsed: Wrapper = toml::from_str(
+            r#"
+            [hooks]
+            "post-create-pool" = ["/hooks/a", { path = "/hooks/b", optional = true }]
+            "#,
+        )
+        .unwrap();
+
+        let hooks = &parsed.hooks[&HookPoint::Post(Phase::CreatePool)];
+        assert_eq!(hooks[0], HookSpec::new("/hooks/a"));
+        assert_eq!(hooks[1], HookSpec::new("/hooks/b").optional());
+    }
+
+    #[test]
+    fn test_aliases_merge_with_point_names() {
+        let parsed = Config::from_toml(
+            r#"
+            [hooks]
+            pre_install = ["/hooks/a"]
+            "pre-validate" = ["/hooks/b"]
+            post_install = ["/hooks/c"]
+            "#,
+        )
+        .unwrap();
+
+        let mut pre = parsed.hooks[&HookPoint::Pre(Phase::Validate)].clone();
+        pre.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(pre, [HookSpec::new("/hooks/a"), HookSpec::new("/hooks/b")]);
+        assert_eq!(
+            parsed.hooks[&HookPoint::Post(Phase::Finalize)],
+            [HookSpec::new("/hooks/c")]
+        );
+    }
+
+    #[test]
+    fn test_top_level_hook_lists() {
+        let parsed = Config::from_toml(
+            r#"
+            pre_install = ["/hooks/a"]
+            post_pool = [{ path = "/hooks/b", ignore_failure = true }]
+            post_install = ["/hooks/c"]
+
+            [hooks]
+            "pre-validate" = ["/hooks/d"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            parsed.hooks[&HookPoint::Pre(Phase::Validate)],
+            [HookSpec::new("/hooks/d"), HookSpec::new("/hooks/a")]
+        );
+        assert_eq!(
+            parsed.hooks[&HookPoint::Post(Phase::CreatePool)],
+            [HookSpec::new("/hooks/b").optional()]
+        );
+        assert_eq!(
+            parsed.hooks[&HookPoint::Post(Phase::Finalize)],
+            [HookSpec::new("/hooks/c")]
+        );
+        assert!(parsed.pre_install.is_empty());
+
+        // Written back under [hooks] only
+        let written = parsed.to_toml().unwrap();
+        assert!(!written.contains("\npre_install"), "{written}");
+        assert_eq!(Config::from_toml(&written).unwrap(), parsed);
+    }
+}
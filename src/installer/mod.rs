@@ -0,0 +1,3980 @@
+//! Installation orchestration
+//!
+//! The [`Installer`] drives the pipeline one [`Phase`] at a time, running any
+//! configured hooks around each phase, and finishes by writing an
+//! [`InstallReport`] to the target system.
+//!
+//! Integrators that need to pause between phases can call
+//! [`Installer::run_phase`] themselves. Phases must run in [`Phase::ALL`]
+//! order; phases that don't apply to the configuration may be skipped, and
+//! [`Installer::finish`] writes the report once [`Phase::Finalize`] is done.
+
+#[cfg(feature = "async")]
+pub mod r#async;
+pub mod cancel;
+pub mod context;
+pub mod destroy;
+pub mod events;
+pub mod hooks;
+pub(crate) mod parallel;
+pub mod phase;
+pub mod plan;
+pub mod progress;
+pub mod report;
+pub mod rollback;
+pub mod state;
+
+pub use cancel::{cancel_on_signals, CancelToken};
+pub use context::InstallContext;
+pub use destroy::{DestroyOptions, Removal};
+pub use events::{Event, EventSink, InstallSummary, JsonLinesSink, RecordingSink};
+pub use hooks::{HookPoint, HookRunner, HookSpec};
+pub use phase::{Phase, PhaseOutcome};
+pub use plan::{DeviceTable, InstallPlan, PhasePlan};
+pub use progress::{
+    ChannelReporter, InstallEvent, LoggingReporter, NullReporter, PhaseState, ProgressReporter,
+    ProgressTracker, RecordingReporter,
+};
+
+pub use report::{BootloaderReport, InstallReport, NetworkReport};
+pub use rollback::{UndoAction, UndoLog};
+pub use state::{InstallState, STATE_PATH};
+
+use crate::bootloader::syslinux::BOOT_MOUNTPOINT;
+use crate::bootloader::zbm::{COMMANDLINE_PROPERTY, KEYSOURCE_PROPERTY, ZBM_VERSION};
+use crate::bootloader::{
+    BootEntryManager, EfiStub, Grub, GrubBios, SecureBoot, Syslinux, SystemdBoot, ZbmInstaller,
+};
+use crate::config::{
+    Bootloader, Config, FirmwareMode, InstallMode, PartitioningMode, SecureBootMode, VdevSpec,
+};
+use crate::disk::{
+    holders, stable_path_in, BlockDevice, BootLayout, DeviceDiscovery, DiskOperations, MountGuard,
+    PartitionRole, PartitionTable, SysRoot, ZbmPartitions, DEVICE_RETRY_BACKOFF,
+};
+use crate::error::{InstallerError, MultiDeviceError, Result, ResultExt};
+use crate::exec::{CommandExecutor, FileOp, PlanExecutor, RetryPolicy, SystemExecutor};
+use crate::log_sink::{LogSink, TranscriptExecutor, TARGET_LOG};
+use crate::system::{
+    self, network, Bootstrap, ChrootEnv, Distro, DistroInfo, FstabGenerator, NetworkCarryOver,
+    SystemIdentity, SystemMigration, UserAccounts,
+};
+use crate::validation::{self, CheckResult, ValidationResult, Validator};
+use crate::zfs::{self, DatasetManager, DatasetProperty, SupportVdevs, ZfsBackend, ZfsPool};
+use events::{SinkExecutor, SinkReporter};
+use progress::PhaseSteps;
+use report::{ConfigSummary, DeviceReport, PartitionReport, PhaseTiming, PoolReport};
+use state::DeviceState;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Directory the target system is mounted on during installation
+const TARGET_MOUNTPOINT: &str = "/mnt";
+
+/// Main installer orchestrator
+pub struct Installer {
+    config: Config,
+    executor: Arc<dyn CommandExecutor>,
+    cancel: CancelToken,
+    sys_root: SysRoot,
+    context: InstallContext,
+    events: Option<Arc<dyn EventSink>>,
+    log: Option<LogSink>,
+    state_file: Option<PathBuf>,
+}
+
+impl Installer {
+    /// Create a new installer with the given configuration
+    pub fn new(config: Config) -> Result<Self> {
+        // Validate configuration
+        config.validate()?;
+
+        Ok(Self::unchecked(config))
+    }
+
+    /// Create an installer without validating the configuration
+    fn unchecked(config: Config) -> Self {
+        let executor = Arc::new(SystemExecutor::new(config.dry_run));
+        Self {
+            config,
+            executor,
+            cancel: CancelToken::new(),
+            sys_root: SysRoot::default(),
+            context: InstallContext::default(),
+            events: None,
+            log: None,
+            state_file: None,
+        }
+    }
+
+    /// Create an installer that only runs the pre-flight checks
+    ///
+    /// The configuration isn't checked up front: [`validate_only`](Self::validate_only)
+    /// reports what is wrong with it along with everything else.
+    pub fn for_validation(config: Config) -> Self {
+        Self::unchecked(config)
+    }
+
+    /// Use a custom command executor (e.g. a recording executor in tests)
+    pub fn with_executor(mut self, mut executor: Arc<dyn CommandExecutor>) -> Self {
+        if let Some(log) = &self.log {
+            executor = Arc::new(TranscriptExecutor::new(executor, log.clone()));
+        }
+        if let Some(sink) = &self.events {
+            executor = Arc::new(SinkExecutor::new(executor, sink.clone()));
+        }
+        self.executor = executor;
+        self
+    }
+
+    /// Send every event, command and check result to `sink` as well
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.executor = Arc::new(SinkExecutor::new(self.executor.clone(), sink.clone()));
+        self.events = Some(sink);
+        self
+    }
+
+    /// Record every command in `log`, and copy it into the target system
+    /// during finalize
+    ///
+    /// The encryption passphrase, if any, is redacted from the log.
+    pub fn with_log_sink(mut self, log: LogSink) -> Self {
+        if let Some(passphrase) = self
+            .config
+            .encryption
+            .as_ref()
+            .and_then(|encryption| encryption.passphrase.as_ref())
+        {
+            log.redact(passphrase.expose());
+        }
+        self.executor = Arc::new(TranscriptExecutor::new(self.executor.clone(), log.clone()));
+        self.log = Some(log);
+        self
+    }
+
+    /// Stop at the next step once `cancel` is cancelled
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Record progress in `path` after every phase, so a failed installation
+    /// can be [resumed](Self::resume)
+    ///
+    /// Nothing is recorded in dry-run mode.
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Discover devices under a different sysfs/procfs root
+    pub fn with_sys_root(mut self, sys_root: SysRoot) -> Self {
+        self.sys_root = sys_root;
+        self
+    }
+
+    /// Artifacts produced by the phases run so far
+    pub fn context(&self) -> &InstallContext {
+        &self.context
+    }
+
+    /// Run the installation, logging progress
+    pub fn install(&mut self) -> Result<InstallReport> {
+        self.install_with_progress(&LoggingReporter)
+    }
+
+    /// Run the installation, reporting progress to `reporter`
+    pub fn install_with_progress(
+        &mut self,
+        reporter: &dyn ProgressReporter,
+    ) -> Result<InstallReport> {
+        log::info!("Starting ZFSBootMenu installation");
+        log::info!("Mode: {}", self.config.mode);
+        log::info!("Pool: {}", self.config.pool_name);
+        log::info!("RAID: {}", self.config.raid_summary());
+        log::info!("Devices: {}", self.config.data_devices().len());
+
+        // Check if dry run
+        if self.config.dry_run {
+            log::warn!("DRY RUN MODE - No changes will be made");
+        }
+
+        self.context = InstallContext::default();
+        self.run_and_report(reporter)
+    }
+
+    /// Continue the installation recorded in the state file, logging progress
+    pub fn resume(&mut self) -> Result<InstallReport> {
+        self.resume_with_progress(&LoggingReporter)
+    }
+
+    /// Continue the installation recorded in the state file, reporting
+    /// progress to `reporter`
+    ///
+    /// Fails with [`InstallerError::ResumeMismatch`] if there is no state,
+    /// the configuration differs from the one it was written for, or the
+    /// partitions or pool it describes have changed. Phases that completed
+    /// are not run again.
+    pub fn resume_with_progress(
+        &mut self,
+        reporter: &dyn ProgressReporter,
+    ) -> Result<InstallReport> {
+        let path = self.state_file.clone().ok_or_else(|| {
+            InstallerError::ResumeMismatch("no state file is configured".to_string())
+        })?;
+        let state = InstallState::load(&path)?;
+        state.check_config(&self.config)?;
+
+        self.context = InstallContext::default();
+        self.restore(&state)?;
+        if let Some(phase) = state.next_phase() {
+            log::info!(
+                "Resuming the installation at phase {}: {}",
+                phase.number(),
+                phase.description()
+            );
+        }
+        self.run_and_report(reporter)
+    }
+
+    /// Run the remaining phases and tell the event sink how it went
+    fn run_and_report(&mut self, reporter: &dyn ProgressReporter) -> Result<InstallReport> {
+        let result = self.run_all_phases(reporter);
+        if let Some(sink) = &self.events {
+            sink.emit(&match &result {
+                Ok(report) => Event::Finished(InstallSummary::from(report)),
+                Err(e) => Event::Failed {
+                    error: e.to_string(),
+                },
+            });
+        }
+        result
+    }
+
+    /// Run every phase and finish, rolling back on failure
+    fn run_all_phases(&mut self, reporter: &dyn ProgressReporter) -> Result<InstallReport> {
+        for phase in Phase::ALL {
+            // Done by the run being resumed
+            if self.context.is_completed(phase) {
+                continue;
+            }
+            if let Err(e) = self.run_phase_with_progress(phase, reporter) {
+                if self.config.rollback {
+                    self.rollback();
+                } else {
+                    log::warn!("Rollback disabled, leaving the partial installation in place");
+                }
+                self.save_state_after_failure();
+                return Err(e);
+            }
+        }
+
+        let report = self.finish()?;
+        if let Some(path) = &self.state_file {
+            if !self.config.dry_run {
+                self.executor.apply(FileOp::remove(path))?;
+            }
+        }
+        log::info!("Installation completed successfully!");
+        Ok(report)
+    }
+
+    /// Collect everything the installation would do, without doing it
+    ///
+    /// Runs every applicable phase in dry-run mode against a
+    /// [`PlanExecutor`], so the plan is exactly what a dry run of the same
+    /// configuration would do. Hook scripts are never run while planning;
+    /// the plan lists them with the environment they would get.
+    pub fn plan(mut self) -> Result<InstallPlan> {
+        self.config.dry_run = true;
+        self.config.run_hooks_in_dry_run = false;
+        let planner = Arc::new(PlanExecutor::new());
+        self.executor = planner.clone();
+        self.context = InstallContext::default();
+
+        let mut plan = InstallPlan::new();
+        plan.partition_tables = self.partition_tables()?;
+        for phase in self.applicable_phases() {
+            self.run_phase(phase)?;
+            if phase == Phase::Finalize {
+                self.finish()?;
+            }
+            plan.push(phase, planner.take());
+        }
+        Ok(plan)
+    }
+
+    /// Run a single phase, including its hooks
+    ///
+    /// Phases must run in [`Phase::ALL`] order. Running a phase before the
+    /// phases it depends on, or running it twice, fails with
+    /// [`InstallerError::PhaseOrder`]. Phases that don't apply to the
+    /// configuration return [`PhaseOutcome::Skipped`].
+    pub fn run_phase(&mut self, phase: Phase) -> Result<PhaseOutcome> {
+        self.run_phase_with_progress(phase, &NullReporter)
+    }
+
+    /// Run a single phase, reporting progress to `reporter`
+    pub fn run_phase_with_progress(
+        &mut self,
+        phase: Phase,
+        reporter: &dyn ProgressReporter,
+    ) -> Result<PhaseOutcome> {
+        self.check_phase_order(phase)?;
+        if !self.phase_applies(phase) {
+            return Ok(PhaseOutcome::Skipped);
+        }
+
+        self.cancel.check().in_phase(phase)?;
+        self.context.started_at.get_or_insert_with(timestamp);
+        let sink = self.events.clone();
+        let tee;
+        let reporter = match &sink {
+            Some(sink) => {
+                tee = SinkReporter {
+                    sink: sink.as_ref(),
+                    inner: reporter,
+                };
+                &tee as &dyn ProgressReporter
+            }
+            None => reporter,
+        };
+        let steps = PhaseSteps::new(reporter, phase, self.total_steps(phase))
+            .with_cancel_token(self.cancel.clone());
+        reporter.phase_started(phase, self.total_steps(phase));
+
+        let esp_paths = self.context.partitions.iter().filter_map(|p| p.efi.clone());
+        let hooks =
+            HookRunner::new(&self.config, self.hook_executor()).with_esp_paths(esp_paths.collect());
+        let mountpoint = Path::new(TARGET_MOUNTPOINT);
+        let start = Instant::now();
+        hooks
+            .run(HookPoint::Pre(phase), mountpoint)
+            .in_phase(phase)?;
+        self.execute_phase(phase, &steps).in_phase(phase)?;
+        hooks
+            .run(HookPoint::Post(phase), mountpoint)
+            .in_phase(phase)?;
+        let duration_ms = start.elapsed().as_millis() as u64;
+
+        self.context.completed.push(phase);
+        if let Err(e) = self.save_state() {
+            log::warn!("Failed to record progress: {}", e);
+        }
+        if phase == Phase::Finalize {
+            // The installation is complete; nothing should undo it now
+            self.context.undo.drain_newest_first();
+        }
+        self.context
+            .timings
+            .push(PhaseTiming { phase, duration_ms });
+        reporter.phase_finished(phase, duration_ms);
+        Ok(PhaseOutcome::Completed { duration_ms })
+    }
+
+    /// Undo the changes made by the phases run so far, newest first
+    ///
+    /// Best effort: every action is attempted and failures are logged, so the
+    /// error that caused the rollback stays the one reported. Called
+    /// automatically by [`install`](Self::install) unless `rollback` is off in
+    /// the configuration.
+    pub fn rollback(&mut self) {
+        let actions = self.context.undo.drain_newest_first();
+        if actions.is_empty() {
+            return;
+        }
+
+        log::warn!(
+            "Installation failed, rolling back {} change(s)",
+            actions.len()
+        );
+        for action in actions {
+            log::info!("Undoing: {}", action);
+            if let Err(e) = self.undo(&action) {
+                log::error!("Failed to undo {}: {}", action, e);
+            }
+
+            // Phases whose results are gone have to run again on resume
+            let lost = match action {
+                UndoAction::PoolCreated { .. } => Some(Phase::CreatePool),
+                UndoAction::Partitioned { .. } | UndoAction::PartitionsAdded { .. }
+                    if self.config.wipe_on_rollback =>
+                {
+                    Some(Phase::PrepareDisks)
+                }
+                _ => None,
+            };
+            if let Some(lost) = lost {
+                self.context.completed.retain(|phase| *phase < lost);
+            }
+        }
+    }
+
+    /// Reverse a single recorded change
+    fn undo(&self, action: &UndoAction) -> Result<()> {
+        match action {
+            UndoAction::Mounted { path } => {
+                self.executor.apply(FileOp::unmount(path))?;
+            }
+            UndoAction::DatasetMounted { dataset } => {
+                self.dataset_manager().unmount(dataset)?;
+            }
+            UndoAction::PoolCreated { pool } => {
+                let backend = self.zfs_backend();
+                if let Err(e) = backend.pool_destroy(pool) {
+                    // At least release the disks so they can be reused
+                    log::warn!("Could not destroy pool {} ({}), exporting it", pool, e);
+                    backend.pool_export(pool)?;
+                }
+            }
+            UndoAction::PoolImported { pool } => {
+                self.zfs_backend().pool_export(pool)?;
+            }
+            UndoAction::Partitioned { device } => {
+                if self.config.wipe_on_rollback {
+                    self.disk_ops().wipe_device(device)?;
+                } else {
+                    log::info!("Leaving the new partitions on {}", device.path.display());
+                }
+            }
+            UndoAction::PartitionsAdded { device, numbers } => {
+                if self.config.wipe_on_rollback {
+                    self.disk_ops().delete_partitions(device, numbers)?;
+                } else {
+                    log::info!("Leaving the new partitions on {}", device.path.display());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the phases completed so far in the state file
+    fn save_state(&mut self) -> Result<()> {
+        let Some(path) = self.state_file.clone() else {
+            return Ok(());
+        };
+        if self.config.dry_run {
+            return Ok(());
+        }
+
+        // Read once the artifacts exist, then kept for every later save
+        let prepared = self.context.is_completed(Phase::PrepareDisks);
+        if prepared && self.context.partuuids.is_empty() {
+            let disk_ops = self.disk_ops();
+            for partition in self
+                .context
+                .partitions
+                .iter()
+                .flat_map(ZbmPartitions::paths)
+            {
+                if let Some(partuuid) = disk_ops.partuuid(partition)? {
+                    self.context
+                        .partuuids
+                        .insert(partition.to_path_buf(), partuuid);
+                }
+            }
+        }
+        let pool_created = self.context.is_completed(Phase::CreatePool);
+        if pool_created && self.context.pool_guid.is_none() {
+            self.context.pool_guid = self.existing_pool().guid()?;
+        }
+
+        let devices = if prepared {
+            self.context
+                .devices
+                .iter()
+                .zip(&self.context.partitions)
+                .map(|(device, partitions)| DeviceState {
+                    device: device.path.clone(),
+                    partitions: partitions.clone(),
+                    partuuids: partitions
+                        .paths()
+                        .filter_map(|path| {
+                            let partuuid = self.context.partuuids.get(path)?;
+                            Some((path.to_path_buf(), partuuid.clone()))
+                        })
+                        .collect(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let state = InstallState {
+            config_hash: state::config_hash(&self.config)?,
+            completed: self.context.completed.clone(),
+            devices,
+            pool_guid: self.context.pool_guid.clone().filter(|_| pool_created),
+            zbm_version: self.context.zbm_version.clone(),
+            bootloader_error: self.context.bootloader_error.clone(),
+            network: self.context.network.clone(),
+            boot_environment: Some(self.config.boot_environment.clone()),
+            ..Default::default()
+        };
+        if let Some(dir) = path.parent() {
+            self.executor.apply(FileOp::create_dir(dir))?;
+        }
+        self.executor.apply(FileOp::write(&path, state.to_json()?))
+    }
+
+    /// Keep what survived a failure for a resumed run, or forget the run if
+    /// the disks have to be prepared again anyway
+    fn save_state_after_failure(&mut self) {
+        let Some(path) = self.state_file.clone() else {
+            return;
+        };
+        if self.config.dry_run {
+            return;
+        }
+
+        let resumable = self.context.is_completed(Phase::PrepareDisks);
+        let result = if resumable {
+            self.save_state()
+        } else {
+            self.executor.apply(FileOp::remove(&path))
+        };
+        match result {
+            Ok(()) if resumable => {
+                log::info!(
+                    "The installation can be resumed; its progress is in {}",
+                    path.display()
+                )
+            }
+            Ok(()) => {}
+            Err(e) => log::warn!("Failed to record progress: {}", e),
+        }
+    }
+
+    /// Check that what `state` describes is still in place, and carry on
+    /// from there
+    ///
+    /// A pool that was exported since is imported again, and the boot
+    /// environment and ESPs are mounted again if the phases that mounted
+    /// them had completed.
+    fn restore(&mut self, state: &InstallState) -> Result<()> {
+        let discovery = DeviceDiscovery::new()?.with_sys_root(self.sys_root.clone());
+        let disk_ops = self.disk_ops();
+        for recorded in &state.devices {
+            let name = recorded
+                .device
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let device = discovery.find_device(&name).map_err(|_| {
+                InstallerError::ResumeMismatch(format!(
+                    "{} is no longer present",
+                    recorded.device.display()
+                ))
+            })?;
+            for (partition, partuuid) in &recorded.partuuids {
+                if disk_ops.partuuid(partition).ok().flatten().as_ref() != Some(partuuid) {
+                    return Err(InstallerError::ResumeMismatch(format!(
+                        "partition {} has changed (expected PARTUUID {})",
+                        partition.display(),
+                        partuuid
+                    )));
+                }
+            }
+            self.context.devices.push(device);
+            self.context.partitions.push(recorded.partitions.clone());
+            self.context.partuuids.extend(recorded.partuuids.clone());
+        }
+        self.context.completed = state.completed.clone();
+
+        let mut imported = false;
+        if self.context.is_completed(Phase::CreatePool) {
+            let name = self.config.pool_name.clone();
+            let pool = self.existing_pool();
+            if !pool.is_imported() {
+                pool.import_at(Path::new(TARGET_MOUNTPOINT)).map_err(|e| {
+                    InstallerError::ResumeMismatch(format!(
+                        "pool {} can't be imported: {}",
+                        name, e
+                    ))
+                })?;
+                self.context
+                    .undo
+                    .record(UndoAction::PoolImported { pool: name.clone() });
+                imported = true;
+                // The export left the key unloaded
+                pool.load_key()?;
+            }
+            let guid = pool.guid()?;
+            if state.pool_guid.is_some() && guid != state.pool_guid {
+                return Err(InstallerError::ResumeMismatch(format!(
+                    "pool {} is not the pool created by the interrupted run",
+                    name
+                )));
+            }
+            self.context.pool_guid = guid;
+        }
+
+        if self.context.is_completed(Phase::MountFilesystem) {
+            self.context.mount_point = Some(if imported {
+                self.mount_filesystem()?
+            } else {
+                PathBuf::from(TARGET_MOUNTPOINT)
+            });
+        }
+        if self.context.is_completed(Phase::MigrateSystem) {
+            self.context.network = state.network.clone();
+        }
+        if self.context.is_completed(Phase::InstallBootloader) {
+            self.context.zbm_version = state.zbm_version.clone();
+            self.context.bootloader_error = state.bootloader_error.clone();
+            if imported {
+                for mount in self.mount_loaders(&self.context.partitions)? {
+                    let path = mount.persist();
+                    self.context.undo.record(UndoAction::Mounted { path });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The configured pool, for operations on it once it exists
+    fn existing_pool(&self) -> ZfsPool {
+        ZfsPool::new(
+            self.config.pool_name.clone(),
+            self.config.raid_level,
+            Vec::new(),
+            None,
+            self.config.compression,
+            self.config.dry_run,
+        )
+        .with_encryption(self.config.encryption.clone())
+        .with_backend(self.zfs_backend())
+    }
+
+    /// Build and write the install report once every phase has run
+    pub fn finish(&mut self) -> Result<InstallReport> {
+        if !self.context.is_completed(Phase::Finalize) {
+            return Err(InstallerError::PhaseOrder {
+                phase: Phase::Finalize,
+                reason: "the report is only available once this phase has completed".to_string(),
+            });
+        }
+
+        let root = self
+            .context
+            .mount_point
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(TARGET_MOUNTPOINT));
+        let report = self.build_report();
+        report.write(&root, &*self.executor)?;
+        self.teardown(&root)?;
+        Ok(report)
+    }
+
+    /// Unmount the target and export the pool so the first boot imports it cleanly
+    ///
+    /// Runs after the post-finalize hooks and the report, which both need
+    /// the target mounted. With `keep_mounted` the pool is imported again
+    /// under the target mountpoint to chroot into.
+    fn teardown(&self, root: &Path) -> Result<()> {
+        log::info!(
+            "Unmounting {} and exporting pool {}",
+            root.display(),
+            self.config.pool_name
+        );
+
+        // The ESPs are mounted inside the boot environment
+        let esps = self.loader_mountpoints(self.context.partitions.len().max(1));
+        for mountpoint in esps.iter().rev() {
+            self.executor.apply(FileOp::unmount(mountpoint))?;
+        }
+
+        let dataset_manager = self.dataset_manager();
+        dataset_manager.unmount_all()?;
+
+        let pool = self.existing_pool();
+        pool.export()?;
+
+        if self.config.keep_mounted {
+            pool.import_at(Path::new(TARGET_MOUNTPOINT))?;
+            pool.load_key()?;
+            dataset_manager.mount_all()?;
+            log::info!("Pool imported under {} for chrooting", TARGET_MOUNTPOINT);
+        }
+        Ok(())
+    }
+
+    /// Ensure every applicable phase before `phase` has completed
+    fn check_phase_order(&self, phase: Phase) -> Result<()> {
+        if self.context.is_completed(phase) {
+            return Err(InstallerError::PhaseOrder {
+                phase,
+                reason: "phase has already completed".to_string(),
+            });
+        }
+
+        let missing = Phase::ALL
+            .into_iter()
+            .take_while(|p| *p != phase)
+            .find(|p| self.phase_applies(*p) && !self.context.is_completed(*p));
+        match missing {
+            Some(missing) => Err(InstallerError::PhaseOrder {
+                phase,
+                reason: format!("{} must complete first", missing),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// ZFS backend for pool and dataset operations
+    fn zfs_backend(&self) -> Arc<dyn ZfsBackend> {
+        zfs::backend::select(self.executor.clone(), self.config.dry_run)
+    }
+
+    /// Dataset manager for the configured pool and layout
+    fn dataset_manager(&self) -> DatasetManager {
+        DatasetManager::new(self.config.pool_name.clone(), self.config.dry_run)
+            .with_backend(self.zfs_backend())
+            .with_layout(self.config.dataset_layout())
+    }
+
+    /// Executor used for hooks, which may run for real even in dry-run mode
+    fn hook_executor(&self) -> Arc<dyn CommandExecutor> {
+        if self.config.dry_run && self.config.run_hooks_in_dry_run {
+            Arc::new(SystemExecutor::new(false))
+        } else {
+            self.executor.clone()
+        }
+    }
+
+    /// Phases [`install`](Self::install) runs for the current configuration, in order
+    pub fn applicable_phases(&self) -> Vec<Phase> {
+        Phase::ALL
+            .into_iter()
+            .filter(|phase| self.phase_applies(*phase))
+            .collect()
+    }
+
+    /// Whether a phase runs for the current configuration
+    fn phase_applies(&self, phase: Phase) -> bool {
+        match phase {
+            Phase::MigrateSystem => self.config.mode == InstallMode::Existing,
+            Phase::BootstrapSystem => self.config.bootstrap.is_some(),
+            // Empty new pools have no system to configure
+            Phase::ConfigureSystem => self.config.installs_system(),
+            _ => true,
+        }
+    }
+
+    /// Number of steps a phase reports for the current configuration
+    fn total_steps(&self, phase: Phase) -> usize {
+        match phase {
+            // Pre-flight checks, or just the kernel module check
+            Phase::Validate => usize::from(!self.config.skip_preflight || !self.config.dry_run),
+            // Wipe, partition and format each device, or find its partitions
+            Phase::PrepareDisks if self.config.use_existing_pool => {
+                self.config.data_devices().len()
+            }
+            Phase::PrepareDisks => 3 * self.config.data_devices().len(),
+            Phase::CreatePool => {
+                2 + usize::from(self.config.use_existing_pool)
+                    + usize::from(self.config.swap_zvol_size().is_some())
+            }
+            Phase::MountFilesystem => 1,
+            Phase::MigrateSystem => 1,
+            Phase::BootstrapSystem => 1,
+            Phase::InstallBootloader => match self.config.boot_layout() {
+                BootLayout::Bios => 3,
+                layout => {
+                    3 + usize::from(layout == BootLayout::Hybrid)
+                        + usize::from(self.config.secure_boot.signs())
+                }
+            },
+            Phase::ConfigureSystem => {
+                3 + usize::from(self.config.sets_identity())
+                    + usize::from(self.config.sets_accounts())
+            }
+            Phase::Finalize => {
+                4 + usize::from(self.config.initial_snapshot.is_some())
+                    + usize::from(!self.phase_applies(Phase::ConfigureSystem))
+                    + usize::from(self.config.cachefile.is_some())
+                    + usize::from(self.log.is_some())
+            }
+        }
+    }
+
+    /// Run the work of a single phase, recording its artifacts
+    fn execute_phase(&mut self, phase: Phase, steps: &PhaseSteps) -> Result<()> {
+        log::info!("Phase {}: {}", phase.number(), phase.description());
+
+        match phase {
+            Phase::Validate => self.validate(steps),
+            Phase::PrepareDisks => {
+                let prepared = self.prepare_disks(steps)?;
+                (self.context.devices, self.context.partitions) = prepared.into_iter().unzip();
+                Ok(())
+            }
+            Phase::CreatePool if self.config.use_existing_pool => self.import_pool(steps),
+            Phase::CreatePool => self.create_zfs(&self.context.partitions, steps),
+            Phase::MountFilesystem => {
+                steps.step("Mounting the boot environment")?;
+                self.context.mount_point = Some(self.mount_filesystem()?);
+                Ok(())
+            }
+            Phase::MigrateSystem => {
+                let mount_point = self
+                    .context
+                    .mount_point
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(TARGET_MOUNTPOINT));
+                let reporter = steps.reporter();
+                self.migrate_system(&mount_point, &|percent| {
+                    reporter.step(phase, "Copying the system", percent)
+                })?;
+                reporter.step(phase, "Carrying over the network configuration", 100);
+                self.context.network = Some(self.carry_over_network(&mount_point)?);
+                Ok(())
+            }
+            Phase::BootstrapSystem => {
+                steps.step("Installing the base system")?;
+                let mount_point = self
+                    .context
+                    .mount_point
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(TARGET_MOUNTPOINT));
+                let reporter = steps.reporter();
+                self.bootstrap_system(&mount_point, &|line| reporter.step(phase, line, 0))
+            }
+            Phase::InstallBootloader => {
+                let (version, error) = self.install_bootloader(&self.context.partitions, steps)?;
+                self.context.zbm_version = Some(version);
+                self.context.bootloader_error = error;
+                Ok(())
+            }
+            Phase::ConfigureSystem => self.configure_system(steps),
+            Phase::Finalize => self.finalize(steps),
+        }
+    }
+
+    /// Run every pre-flight check without installing anything
+    ///
+    /// The Validate phase, `zbm-installer validate` and the TUI's pre-flight
+    /// screen all check through here, so they can't disagree.
+    pub fn validate_only(&self) -> Result<ValidationResult> {
+        self.validate_only_with(&mut |_| {})
+    }
+
+    /// Run every pre-flight check, passing each result to `on_check` as it completes
+    pub fn validate_only_with(
+        &self,
+        on_check: &mut dyn FnMut(&CheckResult),
+    ) -> Result<ValidationResult> {
+        let validator = Validator::new(self.config.clone()).with_sys_root(self.sys_root.clone());
+        validator.validate_with(&mut |check| {
+            if let Some(sink) = &self.events {
+                sink.emit(&Event::Check(check.clone()));
+            }
+            on_check(check);
+        })
+    }
+
+    /// Validate configuration and system
+    ///
+    /// Skipping the pre-flight checks still checks the ZFS kernel module,
+    /// unless nothing is going to be written anyway.
+    fn validate(&self, steps: &PhaseSteps) -> Result<()> {
+        if !self.config.skip_preflight {
+            steps.step("Running pre-flight checks")?;
+            let result = self.validate_only()?;
+
+            for warning in result.warnings() {
+                log::warn!("Warning: {}", warning);
+            }
+
+            if !result.is_ok() {
+                for error in result.errors() {
+                    log::error!("Error: {}", error);
+                }
+                return Err(InstallerError::validation("Pre-flight checks failed"));
+            }
+        } else if !self.config.dry_run {
+            // Even unchecked, no disk is touched without a working module
+            steps.step("Checking the ZFS kernel module")?;
+            let version = zfs::ensure_module_loaded(self.executor.as_ref(), &self.sys_root)?;
+            log::info!("ZFS kernel module {} loaded", version);
+        }
+
+        Ok(())
+    }
+
+    /// Prepare disks (partition, format)
+    ///
+    /// With an existing pool the disks are left as they are and only their
+    /// partitions are looked up.
+    fn prepare_disks(&self, steps: &PhaseSteps) -> Result<Vec<(BlockDevice, ZbmPartitions)>> {
+        let discovery = DeviceDiscovery::new()?.with_sys_root(self.sys_root.clone());
+
+        // Find every device before touching any, reporting all missing or
+        // unsuitable ones; skipped pre-flight checks don't bypass the policy
+        let policy = self.config.suitability_policy();
+        let mut devices = Vec::new();
+        let mut failures = MultiDeviceError::new();
+        for device_path in self.config.data_devices() {
+            let device = match device_path.file_name() {
+                Some(name) => discovery.find_device(&name.to_string_lossy()),
+                None => Err(InstallerError::DeviceNotFound(device_path.to_path_buf())),
+            }
+            .and_then(|device| device.is_suitable(&policy).map(|()| device));
+            match device {
+                Ok(device) => devices.push(device),
+                Err(e) => {
+                    log::error!("Device {}: {}", device_path.display(), e);
+                    failures.push(device_path.to_path_buf(), e);
+                }
+            }
+        }
+        failures.into_result()?;
+
+        // A dry run goes one device at a time, so the plan lists each
+        // device's commands together and in order
+        let jobs = if self.config.dry_run {
+            1
+        } else {
+            self.config.prepare_jobs()
+        };
+        if jobs > 1 {
+            log::info!("Preparing {} devices, {} at a time", devices.len(), jobs);
+        }
+        let results = parallel::map_bounded(&devices, jobs, |device| {
+            let steps = steps.for_device(&device.path);
+            let result = self.prepare_one(device, &steps);
+            if result.is_err() {
+                // The others stop once their current step is done
+                steps.halt_devices();
+            }
+            result
+        });
+        self.cancel.check()?;
+
+        let mut prepared = Vec::new();
+        let mut failures = MultiDeviceError::new();
+        for (device, result) in devices.into_iter().zip(results) {
+            match result {
+                Ok(partitions) => prepared.push((device, partitions)),
+                Err(e) if matches!(e.root_cause(), InstallerError::AnotherDeviceFailed) => {
+                    log::warn!(
+                        "Stopped preparing {} because another device failed",
+                        device.path.display()
+                    );
+                }
+                Err(e) => {
+                    log::error!("{}", e);
+                    failures.push(device.path.clone(), e);
+                }
+            }
+        }
+        failures.into_result()?;
+        Ok(prepared)
+    }
+
+    /// Prepare one device for the pool, or find the partitions of an
+    /// existing pool's device
+    fn prepare_one(&self, device: &BlockDevice, steps: &PhaseSteps) -> Result<ZbmPartitions> {
+        // Each device has its own, so notices aren't mixed up
+        let disk_ops = self.disk_ops().with_wipe_mode(self.config.wipe_mode);
+        let path = device.path.display();
+        let prepared = if self.config.use_existing_pool {
+            steps.step(&format!("Finding partitions on {}", path))?;
+            disk_ops.find_zbm_partitions(
+                device,
+                self.config.swap_partition_size().0 > 0,
+                self.config.boot_layout(),
+            )
+        } else {
+            if self.config.partitioning.keeps_partitions() {
+                steps.step(&format!("Reading the partition table of {}", path))?;
+            } else {
+                steps.step(&format!("Wiping {} ({})", path, self.config.wipe_mode))?;
+            }
+            log::info!("Preparing device: {}", device.display_name());
+            // Free-space installs record the partitions they add instead
+            if !self.config.partitioning.keeps_partitions() {
+                self.context.undo.record(UndoAction::Partitioned {
+                    device: Box::new(device.clone()),
+                });
+            }
+            self.prepare_device(&disk_ops, device, steps)
+        };
+
+        for notice in disk_ops.take_notices() {
+            steps.notice(&notice);
+        }
+        prepared.on_device(device_label(device))
+    }
+
+    /// Partition and format a single device
+    fn prepare_device(
+        &self,
+        disk_ops: &DiskOperations,
+        device: &BlockDevice,
+        steps: &PhaseSteps,
+    ) -> Result<ZbmPartitions> {
+        // Make sure the layout fits before anything is destroyed
+        let table = self.partition_table(device)?;
+        log::info!("Partition table for {}:\n{}", device.path.display(), table);
+
+        if table.keep_existing {
+            disk_ops.unmount_automounts(device)?;
+            let numbers: Vec<u32> = table.new_partitions().map(|p| p.number).collect();
+            self.context.undo.record(UndoAction::PartitionsAdded {
+                device: Box::new(device.clone()),
+                numbers,
+            });
+        } else {
+            // Validation lists these; without consent they are left alone
+            if !device.holders.is_empty() {
+                if !(self.config.force || self.config.release_holders) {
+                    return Err(InstallerError::InvalidDevice {
+                        path: device.path.clone(),
+                        reason: format!(
+                            "in use by {} (use --force to stop them)",
+                            holders::describe(&self.sys_root, &device.holders)
+                        ),
+                    });
+                }
+                disk_ops.release_device(device)?;
+            }
+            disk_ops.wipe_device(device)?;
+        }
+
+        steps.step(&format!("Partitioning {}", device.path.display()))?;
+        let partitions = disk_ops.create_zbm_partitions(device, &table)?;
+
+        // Format EFI partition, unless it is one already on the disk
+        if let Some(efi) = &partitions.efi {
+            if table.get(PartitionRole::Efi).is_some_and(|p| p.existing) {
+                log::info!("Using the existing ESP {}", efi.display());
+            } else {
+                steps.step(&format!("Formatting {}", efi.display()))?;
+                disk_ops.format_efi(efi)?;
+            }
+        }
+        if let Some(boot) = &partitions.boot {
+            steps.step(&format!("Formatting {}", boot.display()))?;
+            disk_ops.format_boot(boot)?;
+        }
+
+        // Create swap if enabled; encrypted swap is set up at every boot
+        if let Some(ref swap) = partitions.swap {
+            if !self.config.encrypts_swap() {
+                disk_ops.create_swap(swap)?;
+            }
+        }
+
+        Ok(partitions)
+    }
+
+    /// Partition table the configuration gives `device`
+    ///
+    /// Installing into free space reads the table already on the disk.
+    fn partition_table(&self, device: &BlockDevice) -> Result<PartitionTable> {
+        let efi_size = self.config.efi_size;
+        let swap_size = self.config.swap_partition_size();
+        let layout = self.config.boot_layout();
+        let table = match self.config.partitioning {
+            PartitioningMode::WholeDisk => {
+                PartitionTable::plan(device, efi_size, swap_size, layout)?
+            }
+            PartitioningMode::UseFreeSpace { reuse_esp } => {
+                let gpt = self.disk_ops().read_gpt(device)?;
+                PartitionTable::plan_free_space(
+                    device, &gpt, efi_size, swap_size, layout, reuse_esp,
+                )?
+            }
+        };
+        Ok(table.with_encrypted_swap(self.config.encrypts_swap()))
+    }
+
+    /// Partition table each data disk would get, in device order
+    ///
+    /// Empty when installing onto an existing pool, which leaves the disks
+    /// as they are.
+    pub fn partition_tables(&self) -> Result<Vec<DeviceTable>> {
+        if self.config.use_existing_pool {
+            return Ok(Vec::new());
+        }
+        let discovery = DeviceDiscovery::new()?.with_sys_root(self.sys_root.clone());
+        self.config
+            .data_devices()
+            .into_iter()
+            .map(|path| {
+                let name = path
+                    .file_name()
+                    .ok_or_else(|| InstallerError::DeviceNotFound(path.to_path_buf()))?;
+                let device = discovery.find_device(&name.to_string_lossy())?;
+                Ok(DeviceTable {
+                    device: path.to_path_buf(),
+                    table: self.partition_table(&device)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Disk operations running through the installer's executor
+    fn disk_ops(&self) -> DiskOperations {
+        DiskOperations::new(self.config.dry_run)
+            .with_executor(self.executor.clone())
+            .with_sys_root(self.sys_root.clone())
+            .with_device_timeout(Duration::from_secs(self.config.device_timeout))
+            .with_retry(RetryPolicy::new(
+                self.config.device_retries + 1,
+                DEVICE_RETRY_BACKOFF,
+            ))
+    }
+
+    /// Create ZFS pool and datasets
+    fn create_zfs(&self, partitions: &[ZbmPartitions], steps: &PhaseSteps) -> Result<()> {
+        // The pool is created from the /dev/disk links, which udev may still
+        // be making
+        let zfs_devices: Vec<PathBuf> = partitions
+            .iter()
+            .map(|p| self.pool_device_path(&p.zfs))
+            .collect();
+        let disk_ops = self.disk_ops();
+        for device in &zfs_devices {
+            disk_ops.wait_for_device(device, Duration::from_secs(self.config.device_timeout))?;
+        }
+
+        // Group them like the configured vdevs
+        let mut zfs_devices = zfs_devices.into_iter();
+        let vdevs = self
+            .config
+            .vdev_groups()
+            .into_iter()
+            .map(|vdev| {
+                let devices = zfs_devices.by_ref().take(vdev.devices.len()).collect();
+                VdevSpec::new(vdev.raid, devices)
+            })
+            .collect();
+
+        // Use the configured ashift, or the best fit for the prepared disks
+        let ashift = self
+            .config
+            .ashift
+            .or_else(|| ZfsPool::detect_ashift(&self.context.devices));
+
+        let autotrim = self
+            .config
+            .autotrim
+            .unwrap_or_else(|| ZfsPool::detect_autotrim(&self.context.devices));
+        let compatibility = self.config.compatibility();
+        if let (None, Some(features)) = (&self.config.pool_compatibility, &compatibility) {
+            log::info!(
+                "GRUB selected, limiting the pool to the {} feature set",
+                features
+            );
+        }
+
+        // Create pool
+        let pool = ZfsPool::new(
+            self.config.pool_name.clone(),
+            self.config.raid_level,
+            Vec::new(),
+            ashift,
+            self.config.compression,
+            self.config.dry_run,
+        )
+        .with_vdevs(vdevs)
+        .with_backend(self.zfs_backend())
+        .with_support_vdevs(self.support_vdevs())
+        .with_encryption(self.config.encryption.clone())
+        .with_cachefile(self.config.cachefile.clone())
+        .with_altroot(Some(PathBuf::from(TARGET_MOUNTPOINT)))
+        .with_compatibility(compatibility)
+        .with_autotrim(autotrim)
+        .with_properties(
+            self.config.pool_options.clone(),
+            self.config.filesystem_properties.clone(),
+        );
+
+        steps.step(&format!("Creating pool {}", self.config.pool_name))?;
+        pool.create()?;
+        self.context.undo.record(UndoAction::PoolCreated {
+            pool: self.config.pool_name.clone(),
+        });
+
+        self.create_datasets(&[], steps)
+    }
+
+    /// Import the existing pool and add the layout's datasets to it
+    ///
+    /// The pool is imported under the target mount point without mounting
+    /// anything. Layout datasets that already exist are only kept with
+    /// `adopt_datasets`, so an installation is never made over a system by
+    /// accident.
+    fn import_pool(&self, steps: &PhaseSteps) -> Result<()> {
+        let name = &self.config.pool_name;
+        let pool = ZfsPool::new(
+            name.clone(),
+            self.config.raid_level,
+            Vec::new(),
+            None,
+            self.config.compression,
+            self.config.dry_run,
+        )
+        .with_backend(self.zfs_backend());
+
+        steps.step(&format!("Importing pool {}", name))?;
+        if pool.is_imported() {
+            log::info!("Pool {} is already imported", name);
+        } else {
+            pool.import_at(Path::new(TARGET_MOUNTPOINT))?;
+            self.context
+                .undo
+                .record(UndoAction::PoolImported { pool: name.clone() });
+        }
+        for warning in validation::existing_pool_warnings(&pool, name, self.config.bootloader)? {
+            log::warn!("Warning: {}", warning);
+        }
+
+        steps.step("Checking for existing datasets")?;
+        let wanted = self.dataset_manager().zbm_dataset_names();
+        let existing: Vec<String> = self
+            .dataset_manager()
+            .list()?
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|dataset| wanted.contains(dataset))
+            .collect();
+        if !existing.is_empty() && !self.config.adopt_datasets {
+            return Err(InstallerError::zfs(
+                format!("check datasets of pool {}", name),
+                format!(
+                    "{} already exist (use --adopt-datasets to keep them)",
+                    existing.join(", ")
+                ),
+            ));
+        }
+
+        self.create_datasets(&existing, steps)
+    }
+
+    /// Create the layout's datasets not among `existing`, and the swap zvol
+    fn create_datasets(&self, existing: &[String], steps: &PhaseSteps) -> Result<()> {
+        let dataset_manager = self
+            .dataset_manager()
+            .with_encryption(self.config.encryption.is_some());
+        steps.step("Creating datasets")?;
+        dataset_manager.create_missing_zbm_datasets(existing)?;
+
+        if let Some(size) = self.config.swap_zvol_size() {
+            steps.step("Creating swap zvol")?;
+            let zvol = dataset_manager.create_swap_zvol(size.as_u64())?;
+            let disk_ops = self.disk_ops();
+            // The /dev/zvol link is created by udev
+            disk_ops.settle_udev()?;
+            disk_ops.create_swap(&zvol)?;
+        }
+
+        Ok(())
+    }
+
+    /// Log, cache and spare devices, which are added to the pool whole
+    fn support_vdevs(&self) -> SupportVdevs {
+        let paths = |devices: &[PathBuf]| -> Vec<PathBuf> {
+            devices.iter().map(|d| self.pool_device_path(d)).collect()
+        };
+        SupportVdevs {
+            log: paths(&self.config.log_devices),
+            mirror_log: self.config.mirror_log,
+            cache: paths(&self.config.cache_devices),
+            spares: paths(&self.config.spare_devices),
+        }
+    }
+
+    /// Path to hand to `zpool create` for a partition
+    ///
+    /// Kernel names like /dev/sda3 can change between boots, so the most
+    /// stable persistent link is used unless `use_by_id` is off.
+    fn pool_device_path(&self, partition: &Path) -> PathBuf {
+        if !self.config.use_by_id {
+            return partition.to_path_buf();
+        }
+
+        match stable_path_in(&self.sys_root, partition) {
+            Some(path) => {
+                log::info!("Using {} for {}", path.display(), partition.display());
+                path
+            }
+            None => {
+                log::warn!(
+                    "No persistent /dev/disk link for {}, using the kernel name",
+                    partition.display()
+                );
+                partition.to_path_buf()
+            }
+        }
+    }
+
+    /// Mount filesystem
+    fn mount_filesystem(&self) -> Result<PathBuf> {
+        let mount_point = PathBuf::from(TARGET_MOUNTPOINT);
+
+        if !self.config.dry_run {
+            // `zfs create` mounted each dataset under the altroot as it was
+            // made, before the boot environment they belong inside
+            let dataset_manager = self.dataset_manager();
+            dataset_manager.unmount_all()?;
+
+            // The boot environment first, then the rest in mountpoint order
+            dataset_manager.mount_all()?;
+            let layout = self.config.dataset_layout();
+            self.context.undo.record(UndoAction::DatasetMounted {
+                dataset: layout.root_name().to_string(),
+            });
+        }
+
+        Ok(mount_point)
+    }
+
+    /// Migrate existing system
+    fn migrate_system(&self, mount_point: &Path, on_progress: &(dyn Fn(u8) + Sync)) -> Result<()> {
+        SystemMigration::new(&self.config.source_root, mount_point, self.config.dry_run)
+            .with_excludes(&self.config.exclude_paths)
+            .with_copy_home(self.config.copy_home)
+            .with_executor(self.executor.clone())
+            .run(on_progress)
+    }
+
+    /// Copy the source's network configuration into the migrated system
+    /// again, and pin the interface names if asked to
+    fn carry_over_network(&self, mount_point: &Path) -> Result<NetworkReport> {
+        let pinned = if self.config.pin_interface_names {
+            network::detect_interfaces(Path::new(network::SYS_NET_DIR))
+        } else {
+            Vec::new()
+        };
+        let summary =
+            NetworkCarryOver::new(&self.config.source_root, mount_point, self.config.dry_run)
+                .with_pinned_interfaces(pinned)
+                .with_executor(self.executor.clone())
+                .run()?;
+        Ok(NetworkReport {
+            stacks: summary.stacks.iter().map(ToString::to_string).collect(),
+            files: summary.files,
+            pinned_interfaces: summary
+                .pinned
+                .into_iter()
+                .map(|interface| (interface.name, interface.mac))
+                .collect(),
+        })
+    }
+
+    /// Install the configured base system into the mounted boot environment
+    fn bootstrap_system(
+        &self,
+        mount_point: &Path,
+        on_output: &(dyn Fn(&str) + Sync),
+    ) -> Result<()> {
+        let Some(spec) = &self.config.bootstrap else {
+            return Ok(());
+        };
+        Bootstrap::new(spec.clone(), mount_point, self.config.dry_run)
+            .with_executor(self.executor.clone())
+            .run(on_output)
+    }
+
+    /// Mount points for each disk's ESP: `/mnt/boot/efi`, then `/mnt/boot/efi2`, ...
+    fn esp_mountpoints(&self, count: usize) -> Vec<PathBuf> {
+        let boot = Path::new(TARGET_MOUNTPOINT).join("boot");
+        (0..count)
+            .map(|i| match i {
+                0 => boot.join("efi"),
+                _ => boot.join(format!("efi{}", i + 1)),
+            })
+            .collect()
+    }
+
+    /// Where each disk's ZFSBootMenu partition is mounted: the ESPs, or the
+    /// boot partitions of a BIOS layout on `/boot/syslinux`, `/boot/syslinux2`
+    /// and so on
+    fn loader_mountpoints(&self, count: usize) -> Vec<PathBuf> {
+        if self.config.boot_layout() != BootLayout::Bios {
+            return self.esp_mountpoints(count);
+        }
+        let boot = Path::new(TARGET_MOUNTPOINT).join(BOOT_MOUNTPOINT.trim_start_matches('/'));
+        (0..count)
+            .map(|i| match i {
+                0 => boot.clone(),
+                _ => PathBuf::from(format!("{}{}", boot.display(), i + 1)),
+            })
+            .collect()
+    }
+
+    /// Mount an ESP for the bootloader phase, unmounted again if the phase fails
+    fn mount_esp(&self, partition: &Path, mountpoint: &Path) -> Result<MountGuard> {
+        MountGuard::mount(self.executor.clone(), partition, mountpoint, "vfat")
+    }
+
+    /// Mount every disk's ESP or boot partition on its
+    /// [`loader_mountpoints`](Self::loader_mountpoints)
+    fn mount_loaders(&self, partitions: &[ZbmPartitions]) -> Result<Vec<MountGuard>> {
+        let mountpoints = self.loader_mountpoints(partitions.len().max(1));
+        partitions
+            .iter()
+            .zip(&mountpoints)
+            .map(|(parts, mountpoint)| match (&parts.efi, &parts.boot) {
+                (Some(efi), _) => self.mount_esp(efi, mountpoint),
+                (None, Some(boot)) => {
+                    MountGuard::mount(self.executor.clone(), boot, mountpoint, "ext4")
+                }
+                (None, None) => Err(InstallerError::BootloaderError(format!(
+                    "No ESP or boot partition on {}",
+                    parts.zfs.display()
+                ))),
+            })
+            .collect()
+    }
+
+    /// The configured bootloader, managing the ESP mounted at `esp`
+    fn boot_entry_manager(&self, esp: &Path) -> Box<dyn BootEntryManager> {
+        let esp = esp.to_path_buf();
+        let dry_run = self.config.dry_run;
+        let shim = self.config.secure_boot == SecureBootMode::Shim;
+        match self.config.bootloader {
+            Bootloader::SystemdBoot => Box::new(
+                SystemdBoot::new(esp, dry_run)
+                    .with_executor(self.executor.clone())
+                    .with_shim(shim),
+            ),
+            Bootloader::Grub => {
+                let root = self
+                    .context
+                    .mount_point
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(TARGET_MOUNTPOINT));
+                Box::new(
+                    Grub::new(esp, root, dry_run)
+                        .with_executor(self.executor.clone())
+                        .with_shim(shim),
+                )
+            }
+            Bootloader::EfiStub => Box::new(
+                EfiStub::new(dry_run)
+                    .with_executor(self.executor.clone())
+                    .with_shim(shim),
+            ),
+        }
+    }
+
+    /// The Secure Boot signer, if the configuration signs anything
+    fn secure_boot(&self) -> Option<SecureBoot> {
+        if !self.config.secure_boot.signs() {
+            return None;
+        }
+        let key = self.config.secure_boot_key.clone()?;
+        let cert = self.config.secure_boot_cert.clone()?;
+        Some(SecureBoot::new(key, cert, self.config.dry_run).with_executor(self.executor.clone()))
+    }
+
+    /// Install bootloader
+    ///
+    /// ZFSBootMenu and the configured bootloader go onto every disk's ESP,
+    /// each with its own firmware boot entry, so the system still boots after
+    /// losing any one disk of a redundant pool. BIOS layouts get syslinux on
+    /// every disk's boot partition instead.
+    ///
+    /// Returns the ZFSBootMenu version installed.
+    fn install_bootloader(
+        &self,
+        partitions: &[ZbmPartitions],
+        steps: &PhaseSteps,
+    ) -> Result<(String, Option<String>)> {
+        let mountpoints = self.loader_mountpoints(partitions.len().max(1));
+        let mounts = self.mount_loaders(partitions)?;
+        let installed = match self.config.boot_layout() {
+            BootLayout::Bios => self.install_syslinux(partitions, &mountpoints, steps)?,
+            layout => self.install_efi(partitions, &mountpoints, layout, steps)?,
+        };
+
+        // Later phases write to the ESPs too; the teardown unmounts them
+        for mount in mounts {
+            let path = mount.persist();
+            self.context.undo.record(UndoAction::Mounted { path });
+        }
+        Ok(installed)
+    }
+
+    /// Install ZFSBootMenu and the configured bootloader on the ESPs mounted
+    /// at `mountpoints`, returning the ZFSBootMenu version and the ignored
+    /// bootloader failure, if any
+    fn install_efi(
+        &self,
+        partitions: &[ZbmPartitions],
+        mountpoints: &[PathBuf],
+        layout: BootLayout,
+        steps: &PhaseSteps,
+    ) -> Result<(String, Option<String>)> {
+        let efi_mount = mountpoints[0].clone();
+
+        // Install ZFSBootMenu on the primary ESP, then copy it to the others
+        steps.step("Installing ZFSBootMenu")?;
+        let zbm_installer = ZbmInstaller::new(
+            self.config.pool_name.clone(),
+            efi_mount.clone(),
+            self.config.dry_run,
+        )
+        .with_executor(self.executor.clone())
+        .with_source(self.config.zbm_source.clone())
+        .with_release_fallback(self.config.zbm_release_fallback)
+        .with_artifact_dir(self.config.artifact_dir.clone());
+        let image = zbm_installer.install()?;
+        // Signed once, so the mirrors get the signed images
+        let secure_boot = self.secure_boot();
+        if let Some(secure_boot) = &secure_boot {
+            secure_boot.sign(&ZbmInstaller::image_path(&efi_mount))?;
+            secure_boot.sign(&ZbmInstaller::backup_path(&efi_mount))?;
+        }
+        for mirror in mountpoints.iter().skip(1) {
+            zbm_installer.install_mirror(mirror)?;
+        }
+        if self.config.install_fallback {
+            for mountpoint in mountpoints {
+                zbm_installer.install_fallback(mountpoint)?;
+            }
+        }
+
+        // Install the bootloader on every ESP
+        steps.step(&format!("Installing {}", self.config.bootloader))?;
+        let managers: Vec<Box<dyn BootEntryManager>> = mountpoints
+            .iter()
+            .map(|mountpoint| self.boot_entry_manager(mountpoint))
+            .collect();
+        let error = self.install_managers(managers.iter().map(Box::as_ref))?;
+
+        if let Some(secure_boot) = &secure_boot {
+            steps.step("Setting up Secure Boot")?;
+            self.setup_secure_boot(secure_boot, mountpoints)?;
+        }
+
+        // One firmware entry per disk
+        steps.step("Registering boot entries")?;
+        self.add_boot_entries(partitions, managers.iter().map(Box::as_ref))?;
+
+        // Hybrid layouts also get a legacy BIOS boot path on every disk
+        if layout == BootLayout::Hybrid {
+            steps.step("Installing GRUB for legacy BIOS")?;
+            GrubBios::new(efi_mount, self.config.dry_run)
+                .with_executor(self.executor.clone())
+                .install(&self.device_paths())?;
+        }
+
+        Ok((image.version, error))
+    }
+
+    /// Make the ESPs mounted at `mountpoints` boot with Secure Boot enforced
+    ///
+    /// ZFSBootMenu is signed already. GRUB, which the firmware starts in its
+    /// place, is signed on every ESP; systemd-boot only backs up the entries
+    /// starting ZFSBootMenu and stays unsigned. Shim mode puts shim next to
+    /// ZFSBootMenu on every ESP and enrolls the signing key once.
+    fn setup_secure_boot(&self, secure_boot: &SecureBoot, mountpoints: &[PathBuf]) -> Result<()> {
+        if self.config.bootloader == Bootloader::Grub {
+            for esp in mountpoints {
+                secure_boot.sign(&Grub::image_path(esp))?;
+            }
+        }
+        if self.config.secure_boot == SecureBootMode::Shim {
+            for esp in mountpoints {
+                secure_boot.install_shim(esp)?;
+            }
+            secure_boot.enroll(&mountpoints[0])?;
+        }
+        Ok(())
+    }
+
+    /// Install syslinux with the ZFSBootMenu components on the boot
+    /// partitions mounted at `mountpoints`
+    ///
+    /// The components are built on the first disk's boot partition and
+    /// copied to the others; every disk gets its own boot code.
+    fn install_syslinux(
+        &self,
+        partitions: &[ZbmPartitions],
+        mountpoints: &[PathBuf],
+        steps: &PhaseSteps,
+    ) -> Result<(String, Option<String>)> {
+        let managers: Vec<Syslinux> = mountpoints
+            .iter()
+            .map(|mountpoint| {
+                Syslinux::new(mountpoint.clone(), self.config.dry_run)
+                    .with_executor(self.executor.clone())
+            })
+            .collect();
+
+        steps.step("Building the ZFSBootMenu components")?;
+        managers[0].generate_components()?;
+        for mirror in managers.iter().skip(1) {
+            mirror.copy_components(&mountpoints[0])?;
+        }
+
+        steps.step("Installing syslinux")?;
+        let error = self.install_managers(managers.iter().map(|m| m as &dyn BootEntryManager))?;
+
+        steps.step("Writing boot code")?;
+        let managers = managers.iter().map(|m| m as &dyn BootEntryManager);
+        self.add_boot_entries(partitions, managers)?;
+
+        // Built by the host's generate-zbm rather than a release download
+        Ok(("local".to_string(), error))
+    }
+
+    /// Install the bootloader with each of `managers`
+    ///
+    /// With `ignore_bootloader_errors` a failure is logged and returned
+    /// instead, for the summary, and the installation carries on.
+    fn install_managers<'a>(
+        &self,
+        managers: impl IntoIterator<Item = &'a dyn BootEntryManager>,
+    ) -> Result<Option<String>> {
+        let mut ignored = None;
+        for manager in managers {
+            match manager.install() {
+                Ok(()) => {}
+                Err(InstallerError::BootloaderError(message))
+                    if self.config.ignore_bootloader_errors =>
+                {
+                    log::warn!(
+                        "{} failed to install, continuing as configured: {}",
+                        self.bootloader_name(),
+                        message
+                    );
+                    ignored.get_or_insert(message);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(ignored)
+    }
+
+    /// The bootloader being installed, as the report names it
+    fn bootloader_name(&self) -> String {
+        match self.config.boot_layout() {
+            BootLayout::Bios => "syslinux".to_string(),
+            _ => self.config.bootloader.to_string(),
+        }
+    }
+
+    /// Have each disk's manager add the disk's boot entry
+    fn add_boot_entries<'a>(
+        &self,
+        partitions: &[ZbmPartitions],
+        managers: impl IntoIterator<Item = &'a dyn BootEntryManager>,
+    ) -> Result<()> {
+        for (parts, manager) in partitions.iter().zip(managers) {
+            match parts.efi_location() {
+                Some((disk, number)) => manager.add_entry(&disk, number)?,
+                None => log::warn!(
+                    "Can't tell the disk and partition number of {}, skipping its boot entry",
+                    parts.loader().unwrap_or(&parts.zfs).display()
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths of the devices being installed to
+    fn device_paths(&self) -> Vec<PathBuf> {
+        self.context
+            .devices
+            .iter()
+            .map(|d| d.path.clone())
+            .collect()
+    }
+
+    /// Make the migrated or bootstrapped system bootable from the pool
+    ///
+    /// Runs inside the target: writes the host ID the pool was created
+    /// under, rebuilds the initramfs so it can import the pool, and enables
+    /// the services that import and mount it after boot.
+    fn configure_system(&self, steps: &PhaseSteps) -> Result<()> {
+        let root = self
+            .context
+            .mount_point
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(TARGET_MOUNTPOINT));
+        let mut env = ChrootEnv::new(&root, self.config.dry_run)
+            .with_esp(&self.esp_mountpoints(1)[0])
+            .with_executor(self.executor.clone());
+        env.mount()?;
+
+        // The pool was last imported under the host's ID; if the target's
+        // differs, it refuses to import it without forcing
+        steps.step("Setting the host ID")?;
+        let hostid = self.host_id()?;
+        let mut zgenhostid = vec!["zgenhostid", "-f"];
+        zgenhostid.extend(hostid.as_deref());
+        env.run(&zgenhostid)?;
+
+        // Nothing is copied or bootstrapped in dry-run mode, so fall back to
+        // the source system or the one the bootstrap would install
+        let info = match DistroInfo::detect_in(&root)? {
+            info if info.distro == Distro::Unknown => match &self.config.bootstrap {
+                Some(spec) => DistroInfo::parse(&format!("ID={}\n", spec.method.distro_id())),
+                None => DistroInfo::detect_in(&self.config.source_root)?,
+            },
+            info => info,
+        };
+        log::info!("Target system: {}", info);
+
+        // Before the initramfs, which may include the keymap
+        if self.config.sets_identity() {
+            steps.step("Setting the hostname, timezone, locale and keymap")?;
+            SystemIdentity::new(self.config.dry_run)
+                .with_executor(self.executor.clone())
+                .with_hostname(self.config.hostname.clone())
+                .with_timezone(self.config.timezone.clone())
+                .with_locale(self.config.locale.clone())
+                .with_keymap(self.config.keymap.clone())
+                .apply(&env, info.distro)?;
+        }
+
+        if self.config.sets_accounts() {
+            steps.step("Setting up user accounts")?;
+            UserAccounts::new(self.config.dry_run)
+                .with_executor(self.executor.clone())
+                .with_users(self.config.users.clone())
+                .with_root_password(self.config.root_password_hash.clone())
+                .with_lock_root(self.config.lock_root)
+                .apply(&env, info.distro)?;
+        }
+
+        // Also before the initramfs, which loads the module with its options
+        if let Some(arc_max) = self.config.arc_max {
+            steps.step("Limiting the ARC size")?;
+            log::info!("Limiting the ARC to {}", arc_max);
+            let conf = root.join(zfs::MODPROBE_CONF);
+            let existing = std::fs::read_to_string(&conf).unwrap_or_default();
+            if let Some(dir) = conf.parent() {
+                self.executor.apply(FileOp::create_dir(dir))?;
+            }
+            self.executor
+                .apply(FileOp::write(&conf, zfs::set_arc_max(&existing, arc_max)))?;
+        }
+
+        steps.step("Regenerating the initramfs")?;
+        match info.distro.initramfs_command() {
+            Some(command) => env.run(&command)?,
+            None => log::warn!(
+                "Don't know how to regenerate the initramfs on this distribution, skipping"
+            ),
+        }
+
+        steps.step("Enabling ZFS services")?;
+        match info.distro.enable_services_command() {
+            Some(commands) => {
+                for command in commands {
+                    env.run(&command)?;
+                }
+            }
+            None => log::warn!(
+                "Don't know how to enable services on this distribution, skipping"
+            ),
+        }
+
+        env.close()
+    }
+
+    /// Collect the install report
+    ///
+    /// Queries are best effort: anything that can't be read (or isn't run in
+    /// dry-run mode) is left empty rather than failing a finished install.
+    fn build_report(&self) -> InstallReport {
+        let state = &self.context;
+        let disk_ops = self.disk_ops();
+        let partuuid = |path: &Path| disk_ops.partuuid(path).ok().flatten();
+
+        let devices = state
+            .devices
+            .iter()
+            .zip(&state.partitions)
+            .map(|(device, parts)| {
+                let mut partitions: Vec<(&str, &PathBuf)> = parts
+                    .bios_boot
+                    .as_ref()
+                    .map(|bios| ("bios", bios))
+                    .into_iter()
+                    .collect();
+                partitions.extend(parts.efi.as_ref().map(|efi| ("efi", efi)));
+                partitions.extend(parts.boot.as_ref().map(|boot| ("boot", boot)));
+                partitions.extend(parts.swap.as_ref().map(|swap| ("swap", swap)));
+                partitions.push(("zfs", &parts.zfs));
+
+                DeviceReport {
+                    path: device.path.clone(),
+                    model: device.model.as_ref().map(|m| m.trim().to_string()),
+                    serial: device.serial.as_ref().map(|s| s.trim().to_string()),
+                    size: device.size,
+                    partitions: partitions
+                        .into_iter()
+                        .map(|(role, path)| PartitionReport {
+                            role: role.to_string(),
+                            path: path.clone(),
+                            partuuid: partuuid(path),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let pool = ZfsPool::new(
+            self.config.pool_name.clone(),
+            self.config.raid_level,
+            Vec::new(),
+            None,
+            self.config.compression,
+            self.config.dry_run,
+        )
+        .with_backend(self.zfs_backend());
+        let dataset_manager = self.dataset_manager();
+        let datasets = dataset_manager
+            .list()
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| dataset_manager.zbm_dataset_names());
+
+        let mut versions = BTreeMap::from([
+            (
+                "installer".to_string(),
+                env!("CARGO_PKG_VERSION").to_string(),
+            ),
+            (
+                "zfsbootmenu".to_string(),
+                state
+                    .zbm_version
+                    .clone()
+                    .unwrap_or_else(|| ZBM_VERSION.to_string()),
+            ),
+        ]);
+        if let Some(zfs) = self
+            .executor
+            .execute(Command::new("zpool").arg("version"))
+            .ok()
+            .and_then(|r| r.stdout_string())
+            .and_then(|s| s.lines().next().map(str::to_string))
+        {
+            versions.insert("zfs".to_string(), zfs);
+        }
+
+        InstallReport {
+            schema_version: report::REPORT_SCHEMA_VERSION,
+            started_at: state.started_at.clone().unwrap_or_else(timestamp),
+            finished_at: timestamp(),
+            dry_run: self.config.dry_run,
+            config: ConfigSummary {
+                mode: self.config.mode.to_string(),
+                raid_level: self.config.raid_summary(),
+                boot_mode: match self.config.boot_layout() {
+                    BootLayout::Bios => FirmwareMode::Bios.to_string(),
+                    _ => self.config.boot_mode.to_string(),
+                },
+                compression: self.config.compression.to_string(),
+                encryption: self
+                    .config
+                    .encryption
+                    .as_ref()
+                    .map(|e| format!("{}, {} key ({})", e.algorithm, e.keyformat, e.keylocation())),
+                ashift: self.config.ashift,
+                efi_size: self.config.efi_size.to_string(),
+                swap_size: self.config.swap_size.to_string(),
+                hostname: self.config.hostname.clone(),
+            },
+            devices,
+            pool: PoolReport {
+                name: self.config.pool_name.clone(),
+                guid: pool.guid().ok().flatten(),
+                datasets,
+            },
+            versions,
+            bootloader: Some(BootloaderReport {
+                name: self.bootloader_name(),
+                error: state.bootloader_error.clone(),
+            }),
+            network: state.network.clone(),
+            phases: state.timings.clone(),
+            rollback_journal: None,
+        }
+    }
+
+    /// Finalize installation
+    fn finalize(&self, steps: &PhaseSteps) -> Result<()> {
+        // Set bootfs property
+        steps.step("Setting bootfs")?;
+        let pool = ZfsPool::new(
+            self.config.pool_name.clone(),
+            self.config.raid_level,
+            Vec::new(),
+            None,
+            self.config.compression,
+            self.config.dry_run,
+        )
+        .with_backend(self.zfs_backend());
+        let layout = self.config.dataset_layout();
+        pool.set_bootfs(layout.root_name())?;
+
+        // ZFSBootMenu reads how to boot the environment from its properties
+        steps.step("Setting ZFSBootMenu properties")?;
+        let dataset_manager = self.dataset_manager();
+        if !self.config.kernel_cmdline.is_empty() {
+            dataset_manager.set_property(
+                layout.root_name(),
+                &DatasetProperty {
+                    key: COMMANDLINE_PROPERTY.to_string(),
+                    value: self.config.kernel_cmdline.clone(),
+                },
+            )?;
+        }
+        // A key file lives in the boot environment, which ZFSBootMenu has to
+        // mount to find it
+        if self
+            .config
+            .encryption
+            .as_ref()
+            .is_some_and(|encryption| !encryption.uses_prompt())
+        {
+            dataset_manager.set_root_property(&DatasetProperty {
+                key: KEYSOURCE_PROPERTY.to_string(),
+                value: format!("{}/{}", self.config.pool_name, layout.root_name()),
+            })?;
+        }
+
+        // A recursive snapshot is taken of the pool root, so every dataset
+        // shares the same baseline
+        let now = chrono::Local::now().naive_local();
+        if let Some(snapshot) = self.config.initial_snapshot_name(now)? {
+            steps.step("Creating initial snapshot")?;
+            if self.config.recursive_snapshot {
+                dataset_manager.snapshot("", &snapshot, true)?;
+            } else {
+                dataset_manager.snapshot(layout.root_name(), &snapshot, false)?;
+            }
+        }
+
+        // The ESPs and swap aren't ZFS, so they need fstab entries to come back after reboot
+        steps.step("Writing /etc/fstab")?;
+        let root = self
+            .context
+            .mount_point
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(TARGET_MOUNTPOINT));
+        FstabGenerator::new(self.config.dry_run)
+            .with_executor(self.executor.clone())
+            .with_swap_zvol(
+                self.config
+                    .swap_zvol_size()
+                    .map(|_| dataset_manager.swap_zvol_path()),
+            )
+            .with_zfs_mountpoints(dataset_manager.zbm_mountpoints())
+            .with_encrypted_swap(self.config.encrypts_swap())
+            .with_tmpfs_tmp(self.config.tmpfs_tmp)
+            .write(&root, &self.context.partitions)?;
+
+        // Existing systems got their host ID before the initramfs was rebuilt
+        if !self.phase_applies(Phase::ConfigureSystem) {
+            steps.step("Writing /etc/hostid")?;
+            self.write_hostid(&root)?;
+        }
+
+        // Without the cachefile the pool is only found by scanning at boot
+        if let Some(cachefile) = &self.config.cachefile {
+            steps.step("Copying the pool cachefile")?;
+            pool.set_cachefile(cachefile)?;
+            let target = root.join(cachefile.strip_prefix("/").unwrap_or(cachefile));
+            if let Some(dir) = target.parent() {
+                self.executor.apply(FileOp::create_dir(dir))?;
+            }
+            self.executor.apply(FileOp::copy(cachefile, &target))?;
+        }
+
+        // Keep the log with the system it describes
+        if let Some(log) = &self.log {
+            steps.step("Copying the install log")?;
+            let target = root.join(TARGET_LOG.trim_start_matches('/'));
+            if let Some(dir) = target.parent() {
+                self.executor.apply(FileOp::create_dir(dir))?;
+            }
+            self.executor.apply(FileOp::copy(log.path(), &target))?;
+        }
+
+        // Sync
+        steps.step("Syncing filesystems")?;
+        system::sync()?;
+
+        log::info!("Installation finalized");
+        Ok(())
+    }
+
+    /// The host ID the pool was created under (`None` in dry-run mode)
+    fn host_id(&self) -> Result<Option<String>> {
+        Ok(self
+            .executor
+            .execute(&mut Command::new("hostid"))?
+            .stdout_string()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty()))
+    }
+
+    /// Write the host ID to `<root>/etc/hostid`
+    ///
+    /// Uses the host's `zgenhostid` if it has one, otherwise writes the
+    /// 4-byte file itself.
+    fn write_hostid(&self, root: &Path) -> Result<()> {
+        let hostid = self.host_id()?;
+        let path = root.join("etc/hostid");
+        self.executor.apply(FileOp::create_dir(root.join("etc")))?;
+
+        let mut zgenhostid = Command::new("zgenhostid");
+        zgenhostid
+            .arg("-f")
+            .arg("-o")
+            .arg(&path)
+            .args(hostid.as_deref());
+        match self.executor.execute(&mut zgenhostid) {
+            Err(InstallerError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("zgenhostid not found, writing {} directly", path.display());
+            }
+            result => return result.map(drop),
+        }
+
+        let bytes = hostid.as_deref().and_then(hostid_bytes).ok_or_else(|| {
+            InstallerError::SystemError(format!(
+                "Can't write {} without zgenhostid: the host ID is unknown",
+                path.display()
+            ))
+        })?;
+        self.executor.apply(FileOp::write_bytes(&path, bytes))
+    }
+}
+
+/// Contents of /etc/hostid for a host ID as printed by `hostid`
+///
+/// The file holds the ID as a native-endian 32-bit integer.
+fn hostid_bytes(hostid: &str) -> Option<[u8; 4]> {
+    u32::from_str_radix(hostid.trim(), 16)
+        .ok()
+        .map(u32::to_ne_bytes)
+}
+
+/// Current time as an RFC 3339 timestamp
+fn timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Device description used in error context, e.g. "/dev/sdc (Samsung SSD 870)"
+fn device_label(device: &BlockDevice) -> String {
+    match &device.model {
+        Some(model) => format!("{} ({})", device.path.display(), model.trim()),
+        None => device.path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        EncryptionConfig, KeyFormat, Passphrase, PasswordHash, RaidLevel, SwapMode, UserSpec,
+    };
+    use crate::disk::PartitionRole;
+    use crate::exec::{ExecOptions, ExecResult, PlannedAction, RecordingExecutor};
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    #[test]
+    fn test_installer_creation_requires_valid_config() {
+        let config = Config::default(); // Empty devices
+        let result = Installer::new(config);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_installer_with_dry_run() {
+        let mut config = Config::default();
+        config.devices = vec![PathBuf::from("/dev/sda")];
+        config.dry_run = true;
+
+        let result = Installer::new(config);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_failing_pre_hook_stops_before_phase() {
+        let mut config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            skip_preflight: true,
+            ..Default::default()
+        };
+        config.hooks.insert(
+            HookPoint::Post(Phase::Validate),
+            vec![HookSpec::new("/hooks/post-validate")],
+        );
+        config.hooks.insert(
+            HookPoint::Pre(Phase::PrepareDisks),
+            vec![HookSpec::new("/hooks/pre-prepare")],
+        );
+
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("/hooks/pre-prepare", 1, "refusing");
+        let mut installer = Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture());
+
+        let err = installer.install().unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            InstallerError::HookFailed { .. }
+        ));
+        assert!(err.to_string().starts_with("Phase PrepareDisks: "));
+
+        let programs: Vec<String> = executor.commands().into_iter().map(|c| c.program).collect();
+        // The kernel module check asks for the version of the tools
+        assert_eq!(
+            programs,
+            vec!["zpool", "/hooks/post-validate", "/hooks/pre-prepare"]
+        );
+    }
+
+    #[test]
+    fn test_migrate_phase_only_in_existing_mode() {
+        let mut config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            ..Default::default()
+        };
+        let installer = Installer::new(config.clone()).unwrap();
+        assert!(!installer.phase_applies(Phase::MigrateSystem));
+        assert!(!installer.phase_applies(Phase::ConfigureSystem));
+        assert!(installer.phase_applies(Phase::PrepareDisks));
+
+        config.mode = InstallMode::Existing;
+        let installer = Installer::new(config).unwrap();
+        assert!(installer.phase_applies(Phase::MigrateSystem));
+        assert!(!installer.phase_applies(Phase::BootstrapSystem));
+        assert!(installer.phase_applies(Phase::ConfigureSystem));
+    }
+
+    #[test]
+    fn test_new_mode_bootstraps_and_configures_target() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("hostid", "8f3c2a1b\n");
+        let mut installer = Installer::new(Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            bootstrap: Some("pacstrap".parse().unwrap()),
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_sys_root(SysRoot::fixture());
+        assert!(!installer.phase_applies(Phase::MigrateSystem));
+        assert!(installer.phase_applies(Phase::BootstrapSystem));
+        assert!(installer.phase_applies(Phase::ConfigureSystem));
+        installer.install().unwrap();
+
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        let position = |command: &str| argv.iter().position(|a| a.starts_with(command)).unwrap();
+        // Into the new boot environment, before the bootloader and the chroot
+        assert!(position("zfs create") < position("pacstrap /mnt base linux"));
+        assert!(position("pacstrap") < position("bootctl"));
+        assert!(position("bootctl") < position("chroot /mnt mkinitcpio -P"));
+    }
+
+    #[test]
+    fn test_existing_mode_migrates_with_rsync() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let mut installer = Installer::new(Config {
+            mode: InstallMode::Existing,
+            devices: vec![PathBuf::from("/dev/sda")],
+            exclude_paths: vec![PathBuf::from("/var/cache")],
+            copy_home: false,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_sys_root(SysRoot::fixture());
+        installer.install().unwrap();
+
+        let rsync: Vec<_> = executor
+            .commands()
+            .into_iter()
+            .filter(|c| c.program == "rsync")
+            .collect();
+        assert_eq!(rsync.len(), 1);
+        let args = &rsync[0].args;
+        assert!(args.contains(&"--exclude=/var/cache".to_string()));
+        assert!(args.contains(&"--exclude=/home/*".to_string()));
+        assert!(args.contains(&"--exclude=/mnt".to_string()));
+        assert_eq!(&args[args.len() - 2..], ["/", "/mnt/"]);
+    }
+
+    #[test]
+    fn test_existing_mode_configures_target_in_chroot() {
+        let source = tempfile::tempdir().unwrap();
+        fs::create_dir(source.path().join("etc")).unwrap();
+        fs::write(source.path().join("etc/os-release"), "ID=debian\n").unwrap();
+        fs::create_dir(source.path().join("etc/netplan")).unwrap();
+        fs::write(
+            source.path().join("etc/netplan/01-netcfg.yaml"),
+            "network: {}\n",
+        )
+        .unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("hostid", "8f3c2a1b\n");
+        let report = Installer::new(Config {
+            mode: InstallMode::Existing,
+            source_root: source.path().to_path_buf(),
+            devices: vec![PathBuf::from("/dev/sda")],
+            hostname: Some("nas".to_string()),
+            locale: Some("de_DE.UTF-8".to_string()),
+            users: vec![UserSpec {
+                admin: true,
+                password_hash: Some(PasswordHash::new("$6$salt$hash").unwrap()),
+                ..UserSpec::new("alice")
+            }],
+            lock_root: true,
+            arc_max: Some(bytesize::ByteSize::gib(4)),
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_sys_root(SysRoot::fixture())
+        .install()
+        .unwrap();
+
+        let commands = executor.commands();
+        let chroot: Vec<String> = commands
+            .iter()
+            .filter(|c| c.program == "chroot")
+            .map(|c| c.args.join(" "))
+            .collect();
+        assert_eq!(
+            chroot,
+            vec![
+                "/mnt zgenhostid -f 8f3c2a1b",
+                "/mnt locale-gen",
+                "/mnt useradd -m -G sudo alice",
+                "/mnt chpasswd -e",
+                "/mnt passwd -l root",
+                "/mnt update-initramfs -u -k all",
+                "/mnt systemctl enable zfs-import-cache.service zfs-mount.service",
+            ]
+        );
+        assert_eq!(
+            executor.written(Path::new("/mnt/etc/hostname")).as_deref(),
+            Some("nas\n")
+        );
+        assert_eq!(
+            executor
+                .written(Path::new("/mnt/etc/modprobe.d/zfs.conf"))
+                .as_deref(),
+            Some("options zfs zfs_arc_max=4294967296\n")
+        );
+        // Dry run prints the plan without bind-mounting anything
+        assert!(!commands
+            .iter()
+            .any(|c| c.program == "mount" && c.args.contains(&"--rbind".to_string())));
+
+        let network = report.network.unwrap();
+        assert_eq!(network.stacks, ["netplan"]);
+        assert_eq!(
+            network.files,
+            [PathBuf::from("/etc/netplan/01-netcfg.yaml")]
+        );
+        assert!(executor.file_ops().contains(&FileOp::copy(
+            source.path().join("etc/netplan/01-netcfg.yaml"),
+            "/mnt/etc/netplan/01-netcfg.yaml"
+        )));
+    }
+
+    #[test]
+    fn test_existing_mode_enables_services_with_target_init() {
+        let source = tempfile::tempdir().unwrap();
+        fs::create_dir(source.path().join("etc")).unwrap();
+        fs::write(source.path().join("etc/os-release"), "ID=alpine\n").unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("hostid", "8f3c2a1b\n");
+        Installer::new(Config {
+            mode: InstallMode::Existing,
+            source_root: source.path().to_path_buf(),
+            devices: vec![PathBuf::from("/dev/sda")],
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_sys_root(SysRoot::fixture())
+        .install()
+        .unwrap();
+
+        let commands = executor.commands();
+        let chroot: Vec<String> = commands
+            .iter()
+            .filter(|c| c.program == "chroot")
+            .map(|c| c.args.join(" "))
+            .collect();
+        assert_eq!(
+            chroot,
+            vec![
+                "/mnt zgenhostid -f 8f3c2a1b",
+                "/mnt mkinitfs",
+                "/mnt rc-update add zfs-import boot",
+                "/mnt rc-update add zfs-mount boot",
+            ]
+        );
+        assert!(!commands.iter().any(|c| c.args.iter().any(|a| a == "systemctl")));
+    }
+
+    #[test]
+    fn test_prepare_disks_reports_all_devices_and_stops() {
+        let config = Config {
+            devices: vec![
+                PathBuf::from("/dev/zbm-missing-a"),
+                PathBuf::from("/dev/zbm-missing-b"),
+            ],
+            skip_preflight: true,
+            ..Default::default()
+        };
+
+        let executor = Arc::new(RecordingExecutor::new());
+        let mut installer = Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture());
+
+        let err = installer.install().unwrap_err();
+        match err.root_cause() {
+            InstallerError::MultiDevice(failures) => assert_eq!(failures.failures.len(), 2),
+            other => panic!("unexpected error: {other}"),
+        }
+        assert!(err.to_string().contains("2 of the selected devices failed"));
+
+        // Nothing reached the pool phase
+        assert!(executor
+            .commands()
+            .iter()
+            .all(|c| c.argv()[..2] != ["zpool", "create"]));
+    }
+
+    /// Executor that records how many commands run at once
+    #[derive(Default)]
+    struct ConcurrencyExecutor {
+        inner: RecordingExecutor,
+        running: std::sync::atomic::AtomicUsize,
+        most: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CommandExecutor for ConcurrencyExecutor {
+        fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+            use std::sync::atomic::Ordering;
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            let result = self.inner.execute_with(cmd, options);
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            result
+        }
+
+        fn apply(&self, op: FileOp) -> Result<()> {
+            self.inner.apply(op)
+        }
+    }
+
+    fn mirror_config() -> Config {
+        Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            raid_level: RaidLevel::Mirror,
+            // sdb is a 16 GB stick
+            swap_size: bytesize::ByteSize::b(0),
+            allow_removable: true,
+            skip_preflight: true,
+            device_timeout: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prepare_disks_runs_devices_in_parallel() {
+        let executor = Arc::new(ConcurrencyExecutor::default());
+        let reporter = RecordingReporter::new();
+        let mut installer = Installer::new(mirror_config())
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture());
+        installer.run_phase(Phase::Validate).unwrap();
+        installer
+            .run_phase_with_progress(Phase::PrepareDisks, &reporter)
+            .unwrap();
+
+        assert_eq!(executor.most.load(std::sync::atomic::Ordering::SeqCst), 2);
+        // Results stay in device order, whichever finished first
+        let names: Vec<&str> = installer
+            .context()
+            .devices()
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(names, ["sda", "sdb"]);
+
+        let mut steps: Vec<(PathBuf, u8)> = reporter
+            .events()
+            .into_iter()
+            .filter_map(|event| match event {
+                InstallEvent::Step {
+                    device, percent, ..
+                } => Some((device.unwrap(), percent)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(steps.len(), 6);
+        steps.sort_by_key(|(_, percent)| *percent);
+        let percents: Vec<u8> = steps.iter().map(|(_, percent)| *percent).collect();
+        assert_eq!(percents, [0, 16, 33, 50, 66, 83]);
+        for device in ["/dev/sda", "/dev/sdb"] {
+            assert_eq!(
+                steps.iter().filter(|(d, _)| d == Path::new(device)).count(),
+                3
+            );
+        }
+
+        // One job prepares the disks one after the other
+        let executor = Arc::new(ConcurrencyExecutor::default());
+        let mut installer = Installer::new(Config {
+            jobs: Some(1),
+            ..mirror_config()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_sys_root(SysRoot::fixture());
+        installer.run_phase(Phase::Validate).unwrap();
+        installer.run_phase(Phase::PrepareDisks).unwrap();
+        assert_eq!(executor.most.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_prepare_disks_fails_phase_when_one_device_fails() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program_times("mkfs.vfat", 1, 1, "mkfs.vfat: unable to open");
+        let mut installer = Installer::new(mirror_config())
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture());
+        installer.run_phase(Phase::Validate).unwrap();
+        let err = installer.run_phase(Phase::PrepareDisks).unwrap_err();
+
+        // The other device stopped or finished, but didn't fail
+        match err.root_cause() {
+            InstallerError::MultiDevice(failures) => assert_eq!(failures.failures.len(), 1),
+            other => panic!("unexpected error: {other}"),
+        }
+        assert!(!installer.context().is_completed(Phase::PrepareDisks));
+        assert!(installer
+            .context()
+            .pending_undo()
+            .iter()
+            .any(|action| matches!(action, UndoAction::Partitioned { .. })));
+    }
+
+    #[test]
+    fn test_dry_run_plan_lists_devices_in_order() {
+        let plan = Installer::new(Config {
+            dry_run: true,
+            ..mirror_config()
+        })
+        .unwrap()
+        .with_sys_root(SysRoot::fixture())
+        .plan()
+        .unwrap();
+        let actions: Vec<String> = plan.actions().map(ToString::to_string).collect();
+        let pool = actions
+            .iter()
+            .position(|a| a.starts_with("zpool create"))
+            .unwrap();
+        let prepare = &actions[..pool];
+        let last_sda = prepare
+            .iter()
+            .rposition(|a| a.contains("/dev/sda"))
+            .unwrap();
+        let first_sdb = prepare.iter().position(|a| a.contains("/dev/sdb")).unwrap();
+        assert!(last_sda < first_sdb, "{prepare:#?}");
+    }
+
+    #[test]
+    fn test_prepare_disks_on_fixture_tree() {
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            skip_preflight: true,
+            ..Default::default()
+        };
+
+        let executor = Arc::new(RecordingExecutor::new());
+        let installer = Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture());
+
+        let steps = PhaseSteps::new(&NullReporter, Phase::PrepareDisks, 2);
+        let prepared = installer.prepare_disks(&steps).unwrap();
+        assert_eq!(prepared.len(), 1);
+        assert_eq!(
+            prepared[0].0.model.as_deref(),
+            Some("Samsung SSD 870 EVO 500GB")
+        );
+        assert!(executor
+            .commands()
+            .iter()
+            .any(|c| c.program == "sgdisk" && c.args.iter().any(|a| a == "/dev/sda")));
+    }
+
+    #[test]
+    fn test_prepare_disks_applies_the_suitability_policy() {
+        let prepare = |config: Config| {
+            let installer = Installer::new(config)
+                .unwrap()
+                .with_executor(Arc::new(RecordingExecutor::new()))
+                .with_sys_root(SysRoot::fixture());
+            let steps = PhaseSteps::new(&NullReporter, Phase::PrepareDisks, 2);
+            installer
+                .prepare_disks(&steps)
+                .map(|prepared| prepared.len())
+        };
+
+        // The stick is refused even with the pre-flight checks skipped
+        let err = prepare(Config {
+            allow_removable: false,
+            ..mirror_config()
+        })
+        .unwrap_err();
+        match err {
+            InstallerError::MultiDevice(failures) => {
+                assert_eq!(failures.failures.len(), 1);
+                assert_eq!(failures.failures[0].0, PathBuf::from("/dev/sdb"));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+
+        assert_eq!(prepare(mirror_config()).unwrap(), 2);
+        assert_eq!(
+            prepare(Config {
+                allow_removable: false,
+                force: true,
+                ..mirror_config()
+            })
+            .unwrap(),
+            2
+        );
+
+        // A swap partition no longer fits on the stick, whatever the flags
+        assert!(prepare(Config {
+            swap_size: bytesize::ByteSize::gib(8),
+            force: true,
+            ..mirror_config()
+        })
+        .is_err());
+    }
+
+    fn fixture_installer(executor: Arc<RecordingExecutor>) -> Installer {
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor)
+            .with_sys_root(SysRoot::fixture())
+    }
+
+    #[test]
+    fn test_phases_must_run_in_order() {
+        let mut installer = fixture_installer(Arc::new(RecordingExecutor::new()));
+
+        let err = installer.run_phase(Phase::CreatePool).unwrap_err();
+        assert!(matches!(
+            err,
+            InstallerError::PhaseOrder {
+                phase: Phase::CreatePool,
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("Validate must complete first"));
+        assert!(installer.finish().is_err());
+
+        installer.run_phase(Phase::Validate).unwrap();
+        let err = installer.run_phase(Phase::Validate).unwrap_err();
+        assert!(err.to_string().contains("already completed"));
+
+        let err = installer.run_phase(Phase::CreatePool).unwrap_err();
+        assert!(err.to_string().contains("PrepareDisks must complete first"));
+        assert!(installer.context().partitions().is_empty());
+    }
+
+    #[test]
+    fn test_plan_groups_commands_and_file_writes_by_phase() {
+        let plan = fixture_installer(Arc::new(RecordingExecutor::new()))
+            .plan()
+            .unwrap();
+
+        let phases: Vec<Phase> = plan.phases.iter().map(|p| p.phase).collect();
+        assert_eq!(
+            phases,
+            vec![
+                Phase::Validate,
+                Phase::PrepareDisks,
+                Phase::CreatePool,
+                Phase::MountFilesystem,
+                Phase::InstallBootloader,
+                Phase::Finalize,
+            ]
+        );
+
+        let prepare = &plan.phases[1].actions;
+        assert_eq!(prepare[0].to_string(), "wipefs -a /dev/sda");
+        let finalize = &plan.phases[5].actions;
+        assert_eq!(
+            finalize[0].to_string(),
+            "zpool set bootfs=zroot/ROOT/default zroot"
+        );
+        assert!(finalize.iter().any(|a| matches!(
+            a,
+            PlannedAction::File {
+                op: FileOp::Write { path, .. }
+            } if path == Path::new("/mnt/etc/fstab")
+        )));
+        assert!(plan
+            .actions()
+            .any(|a| a.to_string() == "write to: /mnt/var/lib/zbm-installer/install-report.json"));
+
+        // The layout the sgdisk calls above will write
+        assert_eq!(plan.partition_tables.len(), 1);
+        let table = &plan.partition_tables[0];
+        assert_eq!(table.device, PathBuf::from("/dev/sda"));
+        let zfs = table.table.get(PartitionRole::Zfs).unwrap();
+        assert!(prepare.iter().any(|a| a
+            .to_string()
+            .contains(&format!("--new={}:{}:{}", zfs.number, zfs.start, zfs.end))));
+
+        let json = plan.to_json().unwrap();
+        assert_eq!(serde_json::from_str::<InstallPlan>(&json).unwrap(), plan);
+    }
+
+    #[test]
+    fn test_event_sink_sees_phases_commands_and_summary() {
+        let sink = Arc::new(RecordingSink::new());
+        fixture_installer(Arc::new(RecordingExecutor::new()))
+            .with_event_sink(sink.clone())
+            .install()
+            .unwrap();
+
+        let events = sink.events();
+        assert_eq!(
+            events[0],
+            Event::PhaseStarted {
+                phase: Phase::Validate,
+                total_steps: 0
+            }
+        );
+        assert!(events.iter().any(|e| matches!(
+            e,
+            Event::Command { argv, exit_code: Some(0), skipped: false, .. }
+                if argv.join(" ") == "wipefs -a /dev/sda"
+        )));
+        match events.last().unwrap() {
+            Event::Finished(summary) => {
+                assert!(summary.dry_run);
+                assert_eq!(summary.pool, "zroot");
+            }
+            other => panic!("unexpected last event: {:?}", other),
+        }
+
+        // A failure ends the stream instead
+        let sink = Arc::new(RecordingSink::new());
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("wipefs", 1, "busy");
+        let _ = fixture_installer(executor)
+            .with_event_sink(sink.clone())
+            .install()
+            .unwrap_err();
+        assert!(matches!(
+            sink.events().last(),
+            Some(Event::Failed { error }) if error.contains("wipefs")
+        ));
+    }
+
+    #[test]
+    fn test_validate_only_reports_an_invalid_configuration() {
+        let sink = Arc::new(RecordingSink::new());
+        let result = Installer::for_validation(Config::default())
+            .with_sys_root(SysRoot::fixture())
+            .with_event_sink(sink.clone())
+            .validate_only()
+            .unwrap();
+
+        assert!(!result.is_ok());
+        assert!(result
+            .checks
+            .iter()
+            .any(|c| c.check == validation::Check::Configuration
+                && c.severity == validation::Severity::Error));
+        let checks = sink
+            .events()
+            .iter()
+            .filter(|e| matches!(e, Event::Check(_)))
+            .count();
+        assert_eq!(checks, result.checks.len());
+    }
+
+    #[test]
+    fn test_plan_records_hooks_with_environment() {
+        let config = Config::from_toml(
+            r#"devices = ["/dev/sda"]
+dry_run = true
+skip_preflight = true
+
+[hooks]
+pre_install = ["/hooks/announce"]
+post_pool = [{ path = "/hooks/tune", ignore_failure = true }]
+post_install = ["/hooks/enroll"]
+"#,
+        )
+        .unwrap();
+        assert!(config.hooks[&HookPoint::Post(Phase::CreatePool)][0].optional);
+
+        let plan = Installer::new(config)
+            .unwrap()
+            .with_sys_root(SysRoot::fixture())
+            .plan()
+            .unwrap();
+        let hooks: Vec<(Phase, &str, &BTreeMap<String, String>)> = plan
+            .phases
+            .iter()
+            .flat_map(|p| p.actions.iter().map(move |a| (p.phase, a)))
+            .filter_map(|(phase, action)| match action {
+                PlannedAction::Hook { point, env, .. } => Some((phase, point.as_str(), env)),
+                _ => None,
+            })
+            .collect();
+        let points: Vec<(Phase, &str)> = hooks
+            .iter()
+            .map(|(phase, point, _)| (*phase, *point))
+            .collect();
+        assert_eq!(
+            points,
+            vec![
+                (Phase::Validate, "pre-validate"),
+                (Phase::CreatePool, "post-create-pool"),
+                (Phase::Finalize, "post-finalize"),
+            ]
+        );
+
+        // The ESP is only known once the disks are prepared
+        let (_, _, env) = hooks[0];
+        assert_eq!(env["ZBM_DEVICES"], "/dev/sda");
+        assert_eq!(env["ZBM_ESP_PATHS"], "");
+        let (_, _, env) = hooks[1];
+        assert_eq!(env["ZBM_POOL"], "zroot");
+        assert_eq!(env["ZBM_MOUNTPOINT"], "/mnt");
+        assert_eq!(env["ZBM_ESP_PATHS"], "/dev/sda1");
+    }
+
+    #[test]
+    fn test_bootfs_points_at_named_boot_environment() {
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            boot_environment: "void".to_string(),
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        let executor = Arc::new(RecordingExecutor::new());
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture())
+            .install()
+            .unwrap();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        for expected in [
+            "zfs create -o canmount=noauto -o mountpoint=/ zroot/ROOT/void",
+            "zpool set bootfs=zroot/ROOT/void zroot",
+            "zfs snapshot zroot/ROOT/void@initial",
+        ] {
+            assert!(commands.iter().any(|c| c == expected), "{}", expected);
+        }
+        assert!(!commands.iter().any(|c| c.contains("ROOT/default")));
+    }
+
+    #[test]
+    fn test_zfsbootmenu_properties() {
+        let key = tempfile::NamedTempFile::new().unwrap();
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            kernel_cmdline: "rw loglevel=3".to_string(),
+            encryption: Some(EncryptionConfig {
+                keyformat: KeyFormat::Raw,
+                keylocation: Some(format!("file://{}", key.path().display())),
+                ..Default::default()
+            }),
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        let executor = Arc::new(RecordingExecutor::new());
+        // Every dataset reports the pool root as its encryption root
+        executor.respond_program("zfs", "zroot\n");
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture())
+            .install()
+            .unwrap();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        for expected in [
+            "zfs set org.zfsbootmenu:commandline=rw loglevel=3 zroot/ROOT/default",
+            "zfs set org.zfsbootmenu:keysource=zroot/ROOT/default zroot",
+        ] {
+            assert!(commands.iter().any(|c| c == expected), "{}", expected);
+        }
+    }
+
+    #[test]
+    fn test_initial_snapshot() {
+        let snapshots = |config: Config| {
+            let executor = Arc::new(RecordingExecutor::new());
+            Installer::new(config)
+                .unwrap()
+                .with_executor(executor.clone())
+                .with_sys_root(SysRoot::fixture())
+                .install()
+                .unwrap();
+            executor
+                .commands()
+                .iter()
+                .map(|c| c.argv().join(" "))
+                .filter(|c| c.starts_with("zfs snapshot"))
+                .collect::<Vec<_>>()
+        };
+        let config = || Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+
+        let today = chrono::Local::now().format("%Y%m%d").to_string();
+        assert_eq!(
+            snapshots(Config {
+                initial_snapshot: Some("install-%Y%m%d".to_string()),
+                recursive_snapshot: true,
+                ..config()
+            }),
+            vec![format!("zfs snapshot -r zroot@install-{}", today)]
+        );
+        assert!(snapshots(Config {
+            initial_snapshot: None,
+            ..config()
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn test_teardown_exports_pool() {
+        let install = |keep_mounted: bool| {
+            let executor = Arc::new(RecordingExecutor::new());
+            let config = Config {
+                devices: vec![PathBuf::from("/dev/sda")],
+                keep_mounted,
+                dry_run: true,
+                skip_preflight: true,
+                ..Default::default()
+            };
+            Installer::new(config)
+                .unwrap()
+                .with_executor(executor.clone())
+                .with_sys_root(SysRoot::fixture())
+                .install()
+                .unwrap();
+            let commands: Vec<String> = executor
+                .commands()
+                .iter()
+                .map(|c| c.argv().join(" "))
+                .collect();
+            let export = commands
+                .iter()
+                .position(|c| c == "zpool export zroot")
+                .unwrap();
+            commands[export..].to_vec()
+        };
+
+        assert_eq!(install(false), vec!["zpool export zroot"]);
+        assert_eq!(
+            install(true),
+            vec![
+                "zpool export zroot",
+                "zpool import -f -N -R /mnt zroot",
+                "zfs mount zroot/ROOT/default",
+                "zfs mount zroot/home",
+                "zfs mount zroot/opt",
+                "zfs mount zroot/home/root",
+                "zfs mount zroot/srv",
+                "zfs mount zroot/usr/local",
+                "zfs mount zroot/var/cache",
+                "zfs mount zroot/var/log",
+                "zfs mount zroot/var/tmp",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keep_mounted_loads_encryption_key() {
+        let executor = Arc::new(RecordingExecutor::new());
+        // Every dataset inherits its key from the pool root
+        executor.respond_program("zfs", "zroot\n");
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            encryption: Some(EncryptionConfig {
+                passphrase: Some(Passphrase::new("correct horse battery")),
+                ..Default::default()
+            }),
+            keep_mounted: true,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture())
+            .install()
+            .unwrap();
+
+        let commands = executor.commands();
+        let export = commands
+            .iter()
+            .position(|c| c.argv() == ["zpool", "export", "zroot"])
+            .unwrap();
+        let after: Vec<String> = commands[export..]
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            &after[..4],
+            [
+                "zpool export zroot",
+                "zpool import -f -N -R /mnt zroot",
+                "zfs load-key zroot",
+                "zfs mount zroot/ROOT/default",
+            ]
+        );
+        // The passphrase goes to stdin
+        assert_eq!(
+            commands[export + 2].stdin.as_deref(),
+            Some(&b"correct horse battery"[..])
+        );
+    }
+
+    fn existing_pool_config(dry_run: bool) -> Config {
+        Config {
+            use_existing_pool: true,
+            devices: vec![PathBuf::from("/dev/sda")],
+            swap_size: bytesize::ByteSize::b(0),
+            dry_run,
+            skip_preflight: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_existing_pool_is_imported_not_created() {
+        let plan = Installer::new(existing_pool_config(true))
+            .unwrap()
+            .with_sys_root(SysRoot::fixture())
+            .plan()
+            .unwrap();
+
+        let actions: Vec<String> = plan.actions().map(ToString::to_string).collect();
+        assert!(!actions.iter().any(|a| a.starts_with("sgdisk")
+            || a.starts_with("wipefs")
+            || a.starts_with("mkfs.vfat")
+            || a.starts_with("zpool create")));
+        assert!(actions.contains(&"zpool import -f -N -R /mnt zroot".to_string()));
+        assert!(
+            actions
+                .contains(&"zfs create -o canmount=off -o mountpoint=none zroot/ROOT".to_string()),
+            "{:?}",
+            actions
+        );
+        assert!(actions.contains(&"mount -t vfat /dev/sda1 /mnt/boot/efi".to_string()));
+    }
+
+    #[test]
+    fn test_existing_datasets_need_adopting() {
+        let run = |adopt_datasets| {
+            let executor = Arc::new(RecordingExecutor::new());
+            executor.respond_program(
+                "blkid",
+                "DEVNAME=/dev/sda1\nTYPE=vfat\n\nDEVNAME=/dev/sda2\nTYPE=zfs_member\n",
+            );
+            executor.respond_program("zfs", "zroot\nzroot/ROOT\n");
+            let config = Config {
+                adopt_datasets,
+                ..existing_pool_config(false)
+            };
+            let mut installer = Installer::new(config)
+                .unwrap()
+                .with_executor(executor.clone())
+                .with_sys_root(SysRoot::fixture());
+            installer.run_phase(Phase::Validate).unwrap();
+            installer.run_phase(Phase::PrepareDisks).unwrap();
+            let result = installer.run_phase(Phase::CreatePool);
+            (installer, executor, result)
+        };
+
+        let (mut installer, executor, result) = run(false);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("zroot/ROOT already exist"), "{}", err);
+        installer.rollback();
+        assert_eq!(
+            executor.commands().last().unwrap().argv(),
+            ["zpool", "export", "zroot"]
+        );
+
+        let (installer, executor, result) = run(true);
+        result.unwrap();
+        assert_eq!(
+            installer.context().partitions()[0].zfs,
+            PathBuf::from("/dev/sda2")
+        );
+        let created: Vec<String> = executor
+            .commands()
+            .iter()
+            .filter(|c| c.program == "zfs" && c.args[0] == "create")
+            .filter_map(|c| c.args.last().cloned())
+            .collect();
+        assert!(!created.contains(&"zroot/ROOT".to_string()));
+        assert!(
+            created.contains(&"zroot/ROOT/default".to_string()),
+            "{:?}",
+            created
+        );
+    }
+
+    #[test]
+    fn test_finalize_plans_hostid_and_cachefile() {
+        let plan = fixture_installer(Arc::new(RecordingExecutor::new()))
+            .plan()
+            .unwrap();
+
+        let create = plan.phases[2].actions[0].to_string();
+        assert!(
+            create.contains("-o cachefile=/etc/zfs/zpool.cache"),
+            "{}",
+            create
+        );
+        let finalize: Vec<String> = plan.phases[5]
+            .actions
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        for expected in [
+            "hostid",
+            "create directory: /mnt/etc",
+            "zgenhostid -f -o /mnt/etc/hostid",
+            "zpool set cachefile=/etc/zfs/zpool.cache zroot",
+            "create directory: /mnt/etc/zfs",
+            "copy /etc/zfs/zpool.cache to /mnt/etc/zfs/zpool.cache",
+        ] {
+            assert!(finalize.iter().any(|a| a == expected), "{}", expected);
+        }
+
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            cachefile: None,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        let plan = Installer::new(config)
+            .unwrap()
+            .with_sys_root(SysRoot::fixture())
+            .plan()
+            .unwrap();
+        assert!(!plan.actions().any(|a| a.to_string().contains("cachefile")));
+    }
+
+    #[test]
+    fn test_finalize_copies_install_log() {
+        let log = LogSink::new(
+            std::io::sink(),
+            "/var/log/zbm-installer/20261015-120000.log",
+        );
+        let plan = fixture_installer(Arc::new(RecordingExecutor::new()))
+            .with_log_sink(log)
+            .plan()
+            .unwrap();
+
+        let finalize: Vec<String> = plan.phases[5]
+            .actions
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let copy = finalize
+            .iter()
+            .position(|a| {
+                a == "copy /var/log/zbm-installer/20261015-120000.log to /mnt/var/log/zbm-install.log"
+            })
+            .unwrap();
+        assert_eq!(finalize[copy - 1], "create directory: /mnt/var/log");
+    }
+
+    #[test]
+    fn test_hostid_bytes() {
+        assert_eq!(
+            hostid_bytes("8f3c2a1b\n"),
+            Some(0x8f3c2a1b_u32.to_ne_bytes())
+        );
+        assert_eq!(hostid_bytes("not-hex"), None);
+    }
+
+    #[test]
+    fn test_phase_by_phase_matches_one_shot() {
+        let one_shot = Arc::new(RecordingExecutor::new());
+        fixture_installer(one_shot.clone()).install().unwrap();
+
+        let stepped = Arc::new(RecordingExecutor::new());
+        let mut installer = fixture_installer(stepped.clone());
+
+        installer.run_phase(Phase::Validate).unwrap();
+        installer.run_phase(Phase::PrepareDisks).unwrap();
+        assert_eq!(installer.context().devices()[0].name, "sda");
+        assert_eq!(
+            installer.context().partitions()[0].efi,
+            Some(PathBuf::from("/dev/sda1"))
+        );
+
+        installer.run_phase(Phase::CreatePool).unwrap();
+        installer.run_phase(Phase::MountFilesystem).unwrap();
+        assert_eq!(installer.context().mount_point(), Some(Path::new("/mnt")));
+
+        assert_eq!(
+            installer.run_phase(Phase::MigrateSystem).unwrap(),
+            PhaseOutcome::Skipped
+        );
+        installer.run_phase(Phase::InstallBootloader).unwrap();
+        installer.run_phase(Phase::Finalize).unwrap();
+        let report = installer.finish().unwrap();
+
+        assert_eq!(report.phases.len(), 6);
+        assert_eq!(
+            installer.context().completed_phases().last(),
+            Some(&Phase::Finalize)
+        );
+
+        let argv = |e: &RecordingExecutor| -> Vec<Vec<String>> {
+            e.commands().iter().map(|c| c.argv()).collect()
+        };
+        assert!(!argv(&one_shot).is_empty());
+        assert_eq!(argv(&stepped), argv(&one_shot));
+    }
+
+    #[test]
+    fn test_striped_mirrors_create_one_vdev_per_group() {
+        // Four disks big enough for the default layout
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["sdc", "sdd", "sde", "sdf"] {
+            let queue = dir.path().join("sys/block").join(name).join("queue");
+            std::fs::create_dir_all(&queue).unwrap();
+            for (attr, value) in [
+                ("logical_block_size", "512\n"),
+                ("physical_block_size", "4096\n"),
+                ("rotational", "0\n"),
+            ] {
+                std::fs::write(queue.join(attr), value).unwrap();
+            }
+            std::fs::write(queue.parent().unwrap().join("size"), "134217728\n").unwrap();
+        }
+
+        let executor = Arc::new(RecordingExecutor::new());
+        let config = Config {
+            vdevs: vec![
+                "mirror:/dev/sdc,/dev/sdd".parse().unwrap(),
+                "mirror:/dev/sde,/dev/sdf".parse().unwrap(),
+            ],
+            use_by_id: false,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        let report = Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::new(dir.path()))
+            .install()
+            .unwrap();
+
+        let create = executor
+            .commands()
+            .into_iter()
+            .find(|c| c.program == "zpool" && c.args[0] == "create")
+            .unwrap();
+        let argv = create.argv().join(" ");
+        assert!(
+            argv.contains(" zroot mirror /dev/sdc3 /dev/sdd3 mirror /dev/sde3 /dev/sdf3"),
+            "{}",
+            argv
+        );
+        assert_eq!(report.config.raid_level, "2 x mirror");
+    }
+
+    #[test]
+    fn test_mirror_installs_bootloader_on_every_esp() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            raid_level: crate::config::RaidLevel::Mirror,
+            // sdb is a 16 GB stick
+            swap_size: bytesize::ByteSize::gib(2),
+            allow_removable: true,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture())
+            .install()
+            .unwrap();
+
+        // sdb3 has no persistent link in the fixture tree
+        let create = executor
+            .commands()
+            .into_iter()
+            .find(|c| c.program == "zpool" && c.args[0] == "create")
+            .unwrap();
+        assert!(create
+            .argv()
+            .join(" ")
+            .ends_with("zroot mirror /dev/disk/by-id/wwn-0x5002538f42a1b2c3-part3 /dev/sdb3"));
+
+        let mounts: Vec<String> = executor
+            .file_ops()
+            .iter()
+            .map(|op| op.to_string())
+            .filter(|op| op.starts_with("mount"))
+            .collect();
+        assert_eq!(
+            mounts,
+            vec![
+                "mount -t vfat /dev/sda1 /mnt/boot/efi",
+                "mount -t vfat /dev/sdb1 /mnt/boot/efi2",
+            ]
+        );
+
+        let plan: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .filter(|c| ["bootctl", "efibootmgr"].iter().any(|p| c.starts_with(p)))
+            .collect();
+        assert_eq!(
+            plan,
+            vec![
+                "bootctl --path /mnt/boot/efi install",
+                "bootctl --path /mnt/boot/efi2 install",
+                "efibootmgr --create --disk /dev/sda --part 1 --label ZFSBootMenu (sda) --loader \\EFI\\ZBM\\zfsbootmenu.EFI",
+                "efibootmgr --create --disk /dev/sdb --part 1 --label ZFSBootMenu (sdb) --loader \\EFI\\ZBM\\zfsbootmenu.EFI",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bios_mirror_installs_syslinux_on_every_disk() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            raid_level: crate::config::RaidLevel::Mirror,
+            firmware: FirmwareMode::Bios,
+            // sdb is a 16 GB stick
+            swap_size: bytesize::ByteSize::gib(2),
+            allow_removable: true,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture())
+            .install()
+            .unwrap();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert!(
+            commands.contains(
+                &"sgdisk /dev/sda --new=2:4096:2101247 --typecode=2:8300 --change-name=2:boot"
+                    .to_string()
+            ),
+            "{commands:#?}"
+        );
+        assert!(commands.contains(&"mkfs.ext4 -F -O ^64bit -L boot /dev/sdb2".to_string()));
+        assert!(!commands.iter().any(|c| c.starts_with("mkfs.vfat")
+            || c.starts_with("efibootmgr")
+            || c.starts_with("curl")));
+
+        let mounts: Vec<String> = executor
+            .file_ops()
+            .iter()
+            .map(|op| op.to_string())
+            .filter(|op| op.starts_with("mount"))
+            .collect();
+        assert_eq!(
+            mounts,
+            vec![
+                "mount -t ext4 /dev/sda2 /mnt/boot/syslinux",
+                "mount -t ext4 /dev/sdb2 /mnt/boot/syslinux2",
+            ]
+        );
+
+        let plan: Vec<&String> = commands
+            .iter()
+            .filter(|c| {
+                ["generate-zbm", "extlinux", "dd", "sgdisk --attributes"]
+                    .iter()
+                    .any(|p| c.starts_with(p))
+            })
+            .collect();
+        assert_eq!(plan.len(), 7, "{plan:#?}");
+        assert_eq!(
+            plan[0],
+            "generate-zbm --config /etc/zfsbootmenu/syslinux.yaml"
+        );
+        assert_eq!(plan[1], "extlinux --install /mnt/boot/syslinux");
+        assert_eq!(plan[2], "extlinux --install /mnt/boot/syslinux2");
+        assert_eq!(plan[3], "sgdisk --attributes=2:set:2 /dev/sda");
+        assert_eq!(plan[5], "sgdisk --attributes=2:set:2 /dev/sdb");
+    }
+
+    #[test]
+    fn test_shim_mirror_signs_zbm_and_chains_through_shim() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            raid_level: crate::config::RaidLevel::Mirror,
+            bootloader: Bootloader::EfiStub,
+            secure_boot: SecureBootMode::Shim,
+            secure_boot_key: Some(PathBuf::from("/etc/secureboot/db.key")),
+            secure_boot_cert: Some(PathBuf::from("/etc/secureboot/db.crt")),
+            install_fallback: true,
+            swap_size: bytesize::ByteSize::gib(2),
+            allow_removable: true,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture())
+            .install()
+            .unwrap();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        let signed: Vec<&String> = commands
+            .iter()
+            .filter(|c| c.starts_with("sbsign"))
+            .collect();
+        assert_eq!(signed.len(), 2, "{signed:#?}");
+        assert!(signed[0].ends_with("/mnt/boot/efi/EFI/ZBM/zfsbootmenu.EFI"));
+        assert!(signed[1].ends_with("/mnt/boot/efi/EFI/ZBM/zfsbootmenu-backup.EFI"));
+        assert!(commands
+            .iter()
+            .any(|c| c.starts_with("mokutil --import /mnt/boot/efi/EFI/ZBM/MOK.cer")));
+
+        // Every ESP gets shim
+        let ops: Vec<String> = executor
+            .file_ops()
+            .iter()
+            .map(|op| op.to_string())
+            .collect();
+        assert!(ops
+            .iter()
+            .any(|op| op.contains("/mnt/boot/efi2/EFI/ZBM/shimx64.efi")));
+        // And the signed image as its fallback loader
+        for esp in ["/mnt/boot/efi", "/mnt/boot/efi2"] {
+            assert!(ops
+                .iter()
+                .any(|op| op.contains(&format!("{esp}/EFI/BOOT/BOOTX64.EFI"))));
+        }
+
+        let entries: Vec<&String> = commands
+            .iter()
+            .filter(|c| c.starts_with("efibootmgr --create"))
+            .collect();
+        assert_eq!(entries.len(), 4, "{entries:#?}");
+        assert!(entries
+            .iter()
+            .all(|c| c
+                .contains("--loader \\EFI\\ZBM\\shimx64.efi --unicode \\EFI\\ZBM\\zfsbootmenu")));
+    }
+
+    #[test]
+    fn test_bootloader_backends_plan_distinct_commands() {
+        let plan = |bootloader| {
+            let executor = Arc::new(RecordingExecutor::new());
+            let mut installer = fixture_installer(executor.clone());
+            installer.config.bootloader = bootloader;
+            installer.install().unwrap();
+            executor
+                .commands()
+                .iter()
+                .map(|c| c.argv().join(" "))
+                .filter(|c| {
+                    ["bootctl", "grub-install", "efibootmgr"]
+                        .iter()
+                        .any(|p| c.starts_with(p))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            plan(Bootloader::Grub),
+            vec![
+                "grub-install --target=x86_64-efi --efi-directory=/mnt/boot/efi --boot-directory=/mnt/boot/efi/boot --bootloader-id=GRUB --no-nvram",
+                "efibootmgr --create --disk /dev/sda --part 1 --label GRUB (sda) --loader \\EFI\\GRUB\\grubx64.efi",
+            ]
+        );
+        assert_eq!(
+            plan(Bootloader::EfiStub),
+            vec![
+                "efibootmgr",
+                "efibootmgr --create --disk /dev/sda --part 1 --label ZFSBootMenu (sda) --loader \\EFI\\ZBM\\zfsbootmenu.EFI",
+                "efibootmgr --create --disk /dev/sda --part 1 --label ZFSBootMenu Backup (sda) --loader \\EFI\\ZBM\\zfsbootmenu-backup.EFI",
+            ]
+        );
+        assert_eq!(
+            plan(Bootloader::SystemdBoot)[0],
+            "bootctl --path /mnt/boot/efi install"
+        );
+    }
+
+    #[test]
+    fn test_bootloader_failure_fails_the_install_unless_ignored() {
+        let run = |ignore| {
+            let executor = Arc::new(RecordingExecutor::new());
+            executor.fail_program(
+                "bootctl",
+                1,
+                "Failed to write file: No space left on device",
+            );
+            let mut installer = fixture_installer(executor);
+            installer.config.bootloader = Bootloader::SystemdBoot;
+            installer.config.ignore_bootloader_errors = ignore;
+            installer.install()
+        };
+
+        let err = run(false).unwrap_err();
+        assert!(err.to_string().contains("No space left on device"), "{err}");
+        assert!(matches!(
+            err.root_cause(),
+            InstallerError::BootloaderError(_)
+        ));
+
+        let report = run(true).unwrap();
+        let bootloader = report.bootloader.unwrap();
+        assert_eq!(bootloader.name, "systemd-boot");
+        assert!(bootloader
+            .error
+            .as_deref()
+            .is_some_and(|e| e.contains("No space left on device")));
+        assert!(bootloader
+            .to_string()
+            .starts_with("systemd-boot FAILED (ignored)"));
+    }
+
+    #[test]
+    fn test_swap_zvol_replaces_swap_partitions() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let mut installer = fixture_installer(executor.clone());
+        installer.config.swap_mode = SwapMode::Zvol;
+        installer.install().unwrap();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert!(
+            !commands.iter().any(|c| c.contains(":8200")),
+            "{commands:#?}"
+        );
+        assert!(commands
+            .iter()
+            .any(|c| c.starts_with("zfs create -V 8589934592 ") && c.ends_with(" zroot/swap")));
+        assert!(commands.contains(&"mkswap /dev/zvol/zroot/swap".to_string()));
+    }
+
+    #[test]
+    fn test_dry_run_progress_sequence() {
+        let reporter = RecordingReporter::new();
+        fixture_installer(Arc::new(RecordingExecutor::new()))
+            .install_with_progress(&reporter)
+            .unwrap();
+
+        let lines: Vec<String> = reporter
+            .events()
+            .into_iter()
+            .map(|event| match event {
+                InstallEvent::PhaseStarted { phase, total_steps } => {
+                    format!("start {} ({})", phase, total_steps)
+                }
+                InstallEvent::Step { label, percent, .. } => format!("  {:>3}% {}", percent, label),
+                InstallEvent::Notice { message, .. } => format!("       {}", message),
+                InstallEvent::PhaseFinished { phase, .. } => format!("end {}", phase),
+            })
+            .collect();
+        let expected = "\
+start Validate (0)
+end Validate
+start PrepareDisks (3)
+    0% Wiping /dev/sda (signatures)
+   33% Partitioning /dev/sda
+   66% Formatting /dev/sda1
+end PrepareDisks
+start CreatePool (2)
+    0% Creating pool zroot
+   50% Creating datasets
+end CreatePool
+start MountFilesystem (1)
+    0% Mounting the boot environment
+end MountFilesystem
+start InstallBootloader (3)
+    0% Installing ZFSBootMenu
+   33% Installing systemd-boot
+   66% Registering boot entries
+end InstallBootloader
+start Finalize (7)
+    0% Setting bootfs
+   14% Setting ZFSBootMenu properties
+   28% Creating initial snapshot
+   42% Writing /etc/fstab
+   57% Writing /etc/hostid
+   71% Copying the pool cachefile
+   85% Syncing filesystems
+end Finalize
+";
+        assert_eq!(lines.join("\n") + "\n", expected);
+    }
+
+    fn failing_dataset_install(rollback: bool) -> Vec<String> {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("zfs", 1, "out of space");
+        let mut installer = Installer::new(Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            raid_level: crate::config::RaidLevel::Mirror,
+            // sdb is a 16 GB stick
+            swap_size: bytesize::ByteSize::gib(2),
+            allow_removable: true,
+            dry_run: true,
+            skip_preflight: true,
+            rollback,
+            wipe_on_rollback: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_sys_root(SysRoot::fixture());
+
+        let err = installer.install().unwrap_err();
+        assert!(err.to_string().contains("out of space"));
+        assert_eq!(installer.context().pending_undo().is_empty(), rollback);
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        let failed = commands
+            .iter()
+            .position(|c| c.starts_with("zfs create"))
+            .unwrap();
+        commands[failed + 1..].to_vec()
+    }
+
+    #[test]
+    fn test_failed_install_rolls_back_newest_first() {
+        let undo = failing_dataset_install(true);
+        let expected = "\
+zpool destroy -f zroot
+wipefs -a /dev/sdb
+sgdisk --zap-all /dev/sdb
+partprobe /dev/sdb
+udevadm settle
+wipefs -a /dev/sda
+sgdisk --zap-all /dev/sda
+partprobe /dev/sda
+udevadm settle";
+        assert_eq!(undo.join("\n"), expected);
+    }
+
+    /// Cancels a token as soon as the pool is being created
+    struct CancelAtPool(CancelToken);
+
+    impl ProgressReporter for CancelAtPool {
+        fn report(&self, event: InstallEvent) {
+            if let InstallEvent::Step { label, .. } = event {
+                if label.starts_with("Creating pool") {
+                    self.0.cancel();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancel_mid_install_stops_at_next_step_and_rolls_back() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let token = CancelToken::new();
+        let mut installer = Installer::new(Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_cancel_token(token.clone())
+        .with_sys_root(SysRoot::fixture());
+
+        let err = installer
+            .install_with_progress(&CancelAtPool(token))
+            .unwrap_err();
+        assert!(err.is_cancelled());
+        assert!(err.to_string().starts_with("Phase CreatePool:"), "{err}");
+
+        // The pool that was being created is destroyed again
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert!(!commands.iter().any(|c| c.starts_with("zfs create")));
+        assert_eq!(commands.last().unwrap(), "zpool destroy -f zroot");
+    }
+
+    /// Contents of the last state file written through `executor`
+    fn written_state(executor: &RecordingExecutor, path: &Path) -> InstallState {
+        let json = executor
+            .file_ops()
+            .into_iter()
+            .rev()
+            .find_map(|op| match op {
+                FileOp::Write { path: p, contents } if p == path => Some(contents),
+                _ => None,
+            })
+            .unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_resume_after_failed_download_skips_disk_preparation() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            skip_preflight: true,
+            device_timeout: 0,
+            ..Default::default()
+        };
+        let installer = |executor: &Arc<RecordingExecutor>, config: &Config| {
+            executor.respond_program("blkid", "6e3f4c1a-01\n");
+            Installer::new(config.clone())
+                .unwrap()
+                .with_executor(executor.clone())
+                .with_state_file(&state_path)
+                .with_sys_root(SysRoot::fixture())
+        };
+
+        // The download fails; rolling back destroys the pool but leaves
+        // the partitions
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("curl", 6, "Could not resolve host");
+        installer(&executor, &config).install().unwrap_err();
+        let state = written_state(&executor, &state_path);
+        assert_eq!(state.completed, vec![Phase::Validate, Phase::PrepareDisks]);
+        assert_eq!(state.devices[0].partuuids.len(), 3);
+        fs::write(&state_path, state.to_json().unwrap()).unwrap();
+
+        // A changed configuration is refused
+        let other = Config {
+            pool_name: "tank".to_string(),
+            ..config.clone()
+        };
+        let err = installer(&Arc::new(RecordingExecutor::new()), &other)
+            .resume()
+            .unwrap_err();
+        assert!(matches!(err, InstallerError::ResumeMismatch(_)), "{err}");
+
+        // So is a disk whose partitions were replaced
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program_times("blkid", 1, "0badc0de-01\n");
+        let err = installer(&executor, &config).resume().unwrap_err();
+        assert!(err.to_string().contains("/dev/sda"), "{err}");
+
+        // Resuming creates the pool on the partitions already there
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("curl", 6, "Could not resolve host");
+        installer(&executor, &config).resume().unwrap_err();
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert!(
+            !commands
+                .iter()
+                .any(|c| c.starts_with("wipefs") || c.starts_with("sgdisk")),
+            "{commands:?}"
+        );
+        assert!(commands.iter().any(|c| c.starts_with("zpool create")));
+        assert_eq!(written_state(&executor, &state_path), state);
+
+        // Once the last phase is done the state is deleted
+        let state = InstallState {
+            completed: Phase::ALL[..Phase::ALL.len() - 1].to_vec(),
+            zbm_version: Some("2.3.0".to_string()),
+            ..state
+        };
+        fs::write(&state_path, state.to_json().unwrap()).unwrap();
+        let executor = Arc::new(RecordingExecutor::new());
+        installer(&executor, &config).resume().unwrap();
+        assert_eq!(
+            executor.file_ops().last(),
+            Some(&FileOp::remove(&state_path))
+        );
+    }
+
+    #[test]
+    fn test_resume_loads_encryption_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            encryption: Some(EncryptionConfig {
+                passphrase: Some(Passphrase::new("correct horse battery")),
+                ..Default::default()
+            }),
+            skip_preflight: true,
+            device_timeout: 0,
+            ..Default::default()
+        };
+        let installer = |executor: &Arc<RecordingExecutor>| {
+            executor.respond_program("blkid", "6e3f4c1a-01\n");
+            Installer::new(config.clone())
+                .unwrap()
+                .with_executor(executor.clone())
+                .with_state_file(&state_path)
+                .with_sys_root(SysRoot::fixture())
+        };
+
+        // Interrupted during the download, after the pool was exported
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("curl", 6, "Could not resolve host");
+        installer(&executor).install().unwrap_err();
+        let state = InstallState {
+            completed: Phase::ALL[..Phase::ALL.len() - 3].to_vec(),
+            ..written_state(&executor, &state_path)
+        };
+        fs::write(&state_path, state.to_json().unwrap()).unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("curl", 6, "Could not resolve host");
+        installer(&executor).resume().unwrap_err();
+        let commands = executor.commands();
+        let argv: Vec<String> = commands.iter().map(|c| c.argv().join(" ")).collect();
+        let import = argv
+            .iter()
+            .position(|c| c == "zpool import -f -N -R /mnt zroot")
+            .unwrap();
+        let load_key = argv.iter().position(|c| c == "zfs load-key zroot").unwrap();
+        let mount = argv.iter().position(|c| c.starts_with("zfs mount")).unwrap();
+        assert!(import < load_key && load_key < mount, "{argv:?}");
+        assert_eq!(
+            commands[load_key].stdin.as_deref(),
+            Some(&b"correct horse battery"[..])
+        );
+    }
+
+    #[test]
+    fn test_free_space_install_keeps_existing_partitions() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("sgdisk", crate::disk::gpt::SAMPLE_PRINT);
+        executor.fail_program("zfs", 1, "out of space");
+        let mut installer = Installer::new(Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            swap_size: bytesize::ByteSize::b(0),
+            partitioning: PartitioningMode::UseFreeSpace { reuse_esp: true },
+            dry_run: true,
+            skip_preflight: true,
+            wipe_on_rollback: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_executor(executor.clone())
+        .with_sys_root(SysRoot::fixture());
+        installer.install().unwrap_err();
+
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        // Nothing wiped or formatted, only the pool partition added
+        assert!(
+            !commands
+                .iter()
+                .any(|c| c.starts_with("wipefs") || c.starts_with("mkfs.vfat")),
+            "{commands:?}"
+        );
+        assert!(commands.contains(
+            &"sgdisk /dev/sda --new=3:410650624:976773119 --typecode=3:BF00 --change-name=3:zfs"
+                .to_string()
+        ));
+        // and removed again on rollback
+        let failed = commands
+            .iter()
+            .position(|c| c.starts_with("zfs create"))
+            .unwrap();
+        assert_eq!(
+            commands[failed + 1..].join("\n"),
+            "zpool destroy -f zroot\nsgdisk --delete=3 /dev/sda\npartprobe /dev/sda\nudevadm settle"
+        );
+    }
+
+    #[test]
+    fn test_rollback_can_be_disabled() {
+        assert!(failing_dataset_install(false).is_empty());
+    }
+
+    #[test]
+    fn test_by_id_paths_can_be_disabled() {
+        let mut installer = fixture_installer(Arc::new(RecordingExecutor::new()));
+        let sda3 = Path::new("/dev/sda3");
+        assert_eq!(
+            installer.pool_device_path(sda3),
+            PathBuf::from("/dev/disk/by-id/wwn-0x5002538f42a1b2c3-part3")
+        );
+
+        installer.config.use_by_id = false;
+        assert_eq!(installer.pool_device_path(sda3), sda3);
+    }
+
+    #[test]
+    fn test_hybrid_dry_run_plan() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            swap_size: bytesize::ByteSize::b(0),
+            boot_mode: crate::config::BootMode::Hybrid,
+            dry_run: true,
+            skip_preflight: true,
+            ..Default::default()
+        };
+        Installer::new(config)
+            .unwrap()
+            .with_executor(executor.clone())
+            .with_sys_root(SysRoot::fixture())
+            .install()
+            .unwrap();
+
+        let plan: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        let expected = "\
+wipefs -a /dev/sda
+sgdisk --zap-all /dev/sda
+partprobe /dev/sda
+udevadm settle
+sgdisk --clear /dev/sda
+sgdisk /dev/sda --new=1:2048:4095 --typecode=1:EF02 --change-name=1:BIOS
+partprobe /dev/sda
+udevadm settle
+sgdisk /dev/sda --new=2:4096:2101247 --typecode=2:EF00 --change-name=2:EFI
+partprobe /dev/sda
+udevadm settle
+sgdisk /dev/sda --new=3:2101248:976773119 --typecode=3:BF00 --change-name=3:zfs
+partprobe /dev/sda
+udevadm settle
+mkfs.vfat -F32 -n EFI /dev/sda2
+zpool create -f -m none -R /mnt -o ashift=12 -o cachefile=/etc/zfs/zpool.cache -o autotrim=on -O acltype=posixacl -O xattr=sa -O dnodesize=auto -O compression=zstd -O normalization=formD -O relatime=on -O canmount=off zroot /dev/disk/by-id/wwn-0x5002538f42a1b2c3-part3
+zfs create -o canmount=off -o mountpoint=none zroot/ROOT
+zfs create -o canmount=noauto -o mountpoint=/ zroot/ROOT/default
+zfs create -o mountpoint=/home zroot/home
+zfs create -o mountpoint=/root zroot/home/root
+zfs create -o canmount=off -o mountpoint=none zroot/var
+zfs create -o mountpoint=/var/log -o acltype=posixacl -o xattr=sa zroot/var/log
+zfs create -o mountpoint=/var/cache -o com.sun:auto-snapshot=false zroot/var/cache
+zfs create -o mountpoint=/var/tmp -o com.sun:auto-snapshot=false zroot/var/tmp
+zfs create -o mountpoint=/opt zroot/opt
+zfs create -o mountpoint=/srv zroot/srv
+zfs create -o canmount=off -o mountpoint=none zroot/usr
+zfs create -o mountpoint=/usr/local zroot/usr/local
+bootctl --path /mnt/boot/efi install
+efibootmgr --create --disk /dev/sda --part 2 --label ZFSBootMenu (sda) --loader \\EFI\\ZBM\\zfsbootmenu.EFI
+grub-install --target=i386-pc --boot-directory=/mnt/boot/efi/boot --modules=part_gpt fat /dev/sda
+zpool set bootfs=zroot/ROOT/default zroot
+zfs set org.zfsbootmenu:commandline=rw quiet zroot/ROOT/default
+zfs snapshot zroot/ROOT/default@initial
+blkid -s UUID -o value /dev/sda2
+hostid
+zgenhostid -f -o /mnt/etc/hostid
+zpool set cachefile=/etc/zfs/zpool.cache zroot
+blkid -s PARTUUID -o value /dev/sda1
+blkid -s PARTUUID -o value /dev/sda2
+blkid -s PARTUUID -o value /dev/sda3
+zfs list -H -o name -r zroot
+zpool version
+zpool get -H -o value guid zroot
+zfs list -H -o name,mountpoint,mounted -r zroot
+zpool export zroot
+";
+        assert_eq!(plan.join("\n") + "\n", expected);
+    }
+}
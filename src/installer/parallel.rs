@@ -0,0 +1,68 @@
+//! Working on several devices at once
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Apply `work` to every item, on up to `jobs` threads
+///
+/// Results come back in the order of `items`, whichever finishes first.
+/// With a single job everything runs in order on the calling thread.
+pub(crate) fn map_bounded<T, R, F>(items: &[T], jobs: usize, work: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if jobs <= 1 || items.len() <= 1 {
+        return items.iter().map(work).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = work(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is worked on"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_map_bounded_keeps_order_and_limit() {
+        let items: Vec<u64> = (0..8).collect();
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+
+        let results = map_bounded(&items, 3, |item| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            // Later items finish first
+            thread::sleep(Duration::from_millis(40 - 5 * item));
+            running.fetch_sub(1, Ordering::SeqCst);
+            item * 10
+        });
+
+        assert_eq!(results, vec![0, 10, 20, 30, 40, 50, 60, 70]);
+        assert!(most.load(Ordering::SeqCst) <= 3);
+        assert!(most.load(Ordering::SeqCst) > 1);
+    }
+}
@@ -0,0 +1,130 @@
+//! Installation phases
+
+use serde::{Deserialize, Serialize};
+
+/// A phase of the installation pipeline, in execution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Phase {
+    /// Pre-flight validation
+    Validate,
+    /// Wipe, partition and format the selected disks
+    PrepareDisks,
+    /// Create the ZFS pool and datasets
+    CreatePool,
+    /// Mount the boot environment
+    MountFilesystem,
+    /// Copy the running system (existing mode only)
+    MigrateSystem,
+    /// Install a minimal system into the pool (new mode with a bootstrap only)
+    BootstrapSystem,
+    /// Install ZFSBootMenu and the boot manager
+    InstallBootloader,
+    /// Configure the installed system from a chroot (migrated or bootstrapped systems only)
+    ConfigureSystem,
+    /// Set bootfs, snapshot and sync
+    Finalize,
+}
+
+impl Phase {
+    /// All phases in execution order
+    pub const ALL: [Phase; 9] = [
+        Self::Validate,
+        Self::PrepareDisks,
+        Self::CreatePool,
+        Self::MountFilesystem,
+        Self::MigrateSystem,
+        Self::BootstrapSystem,
+        Self::InstallBootloader,
+        Self::ConfigureSystem,
+        Self::Finalize,
+    ];
+
+    /// Stable identifier used in hook points and environment variables
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Validate => "validate",
+            Self::PrepareDisks => "prepare-disks",
+            Self::CreatePool => "create-pool",
+            Self::MountFilesystem => "mount-filesystem",
+            Self::MigrateSystem => "migrate-system",
+            Self::BootstrapSystem => "bootstrap-system",
+            Self::InstallBootloader => "install-bootloader",
+            Self::ConfigureSystem => "configure-system",
+            Self::Finalize => "finalize",
+        }
+    }
+
+    /// Parse a phase from its identifier
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|p| p.id() == id)
+    }
+
+    /// One-based position in the pipeline
+    pub fn number(&self) -> usize {
+        Self::ALL.iter().position(|p| p == self).unwrap_or(0) + 1
+    }
+
+    /// Human-readable description for log output
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Validate => "Validation",
+            Self::PrepareDisks => "Preparing disks",
+            Self::CreatePool => "Creating ZFS pool",
+            Self::MountFilesystem => "Mounting filesystem",
+            Self::MigrateSystem => "Migrating existing system",
+            Self::BootstrapSystem => "Bootstrapping a new system",
+            Self::InstallBootloader => "Installing bootloader",
+            Self::ConfigureSystem => "Configuring the installed system",
+            Self::Finalize => "Finalizing",
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Validate => write!(f, "Validate"),
+            Self::PrepareDisks => write!(f, "PrepareDisks"),
+            Self::CreatePool => write!(f, "CreatePool"),
+            Self::MountFilesystem => write!(f, "MountFilesystem"),
+            Self::MigrateSystem => write!(f, "MigrateSystem"),
+            Self::BootstrapSystem => write!(f, "BootstrapSystem"),
+            Self::InstallBootloader => write!(f, "InstallBootloader"),
+            Self::ConfigureSystem => write!(f, "ConfigureSystem"),
+            Self::Finalize => write!(f, "Finalize"),
+        }
+    }
+}
+
+/// Result of running a single phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseOutcome {
+    /// The phase ran to completion
+    Completed {
+        /// How long the phase took
+        duration_ms: u64,
+    },
+    /// The phase does not apply to this configuration
+    Skipped,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_id_round_trip() {
+        for phase in Phase::ALL {
+            assert_eq!(Phase::from_id(phase.id()), Some(phase));
+        }
+        assert_eq!(Phase::from_id("bogus"), None);
+    }
+
+    #[test]
+    fn test_phase_numbering() {
+        assert_eq!(Phase::Validate.number(), 1);
+        assert_eq!(Phase::BootstrapSystem.number(), 6);
+        assert_eq!(Phase::ConfigureSystem.number(), 8);
+        assert_eq!(Phase::Finalize.number(), 9);
+    }
+}
@@ -0,0 +1,79 @@
+//! Machine-readable dry-run plans
+//!
+//! [`Installer::plan`](super::Installer::plan) runs every phase against a
+//! [`PlanExecutor`](crate::exec::PlanExecutor) and groups what it collected
+//! by phase, so a plan can be saved, diffed between runs, or checked by a
+//! script before anything touches the disks.
+
+use crate::disk::PartitionTable;
+use crate::error::{InstallerError, Result};
+use crate::exec::PlannedAction;
+use crate::installer::Phase;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Version of the plan layout; bump on incompatible changes
+pub const PLAN_SCHEMA_VERSION: u32 = 1;
+
+/// Everything an installation would do, phase by phase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallPlan {
+    /// Layout version, see [`PLAN_SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// Partition table each disk would get
+    #[serde(default)]
+    pub partition_tables: Vec<DeviceTable>,
+    /// Phases that would run, in order
+    pub phases: Vec<PhasePlan>,
+}
+
+/// The partition table planned for one disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceTable {
+    /// The disk, as configured
+    pub device: PathBuf,
+    /// Its layout
+    pub table: PartitionTable,
+}
+
+/// What one phase would do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhasePlan {
+    /// The phase
+    pub phase: Phase,
+    /// Commands and file changes, in order
+    pub actions: Vec<PlannedAction>,
+}
+
+impl Default for InstallPlan {
+    fn default() -> Self {
+        Self {
+            schema_version: PLAN_SCHEMA_VERSION,
+            partition_tables: Vec::new(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl InstallPlan {
+    /// Create an empty plan
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the actions of `phase`
+    pub fn push(&mut self, phase: Phase, actions: Vec<PlannedAction>) {
+        self.phases.push(PhasePlan { phase, actions });
+    }
+
+    /// Every action, in order
+    pub fn actions(&self) -> impl Iterator<Item = &PlannedAction> {
+        self.phases.iter().flat_map(|p| p.actions.iter())
+    }
+
+    /// Serialize to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| InstallerError::Other(format!("Failed to serialize install plan: {}", e)))
+    }
+}
@@ -0,0 +1,443 @@
+//! Installation progress events
+//!
+//! Every phase reports how many steps it will take when it starts, then one
+//! [`InstallEvent::Step`] per unit of work, so frontends can draw a per-phase
+//! progress bar without knowing what each phase does.
+
+use crate::error::{InstallerError, Result};
+use crate::installer::{CancelToken, Phase};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+/// Something that happened during an installation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallEvent {
+    /// A phase is about to run
+    PhaseStarted {
+        /// The phase
+        phase: Phase,
+        /// Number of steps the phase will report
+        total_steps: usize,
+    },
+    /// A phase moved on to a new piece of work, or made progress on one
+    Step {
+        /// The phase
+        phase: Phase,
+        /// Device the work is on, when a phase works on several at once
+        device: Option<PathBuf>,
+        /// What is being done, e.g. "Partitioning /dev/sda"
+        label: String,
+        /// Completion of the phase, 0-100
+        percent: u8,
+    },
+    /// Something a phase did on the side, such as retrying a command on a
+    /// busy device or unmounting an automounted filesystem
+    Notice {
+        /// The phase
+        phase: Phase,
+        /// What happened
+        message: String,
+    },
+    /// A phase completed successfully
+    PhaseFinished {
+        /// The phase
+        phase: Phase,
+        /// Time the phase took, including its hooks
+        duration_ms: u64,
+    },
+}
+
+/// Receives progress events from the installer
+///
+/// Implementors only need [`report`](Self::report); the other methods are
+/// conveniences the installer uses to build events.
+pub trait ProgressReporter: Send + Sync {
+    /// Handle an event
+    fn report(&self, event: InstallEvent);
+
+    /// A phase is about to run `total_steps` steps
+    fn phase_started(&self, phase: Phase, total_steps: usize) {
+        self.report(InstallEvent::PhaseStarted { phase, total_steps });
+    }
+
+    /// A phase reached `label`, `percent` of the way through
+    fn step(&self, phase: Phase, label: &str, percent: u8) {
+        self.report(InstallEvent::Step {
+            phase,
+            device: None,
+            label: label.to_string(),
+            percent,
+        });
+    }
+
+    /// A phase completed
+    fn phase_finished(&self, phase: Phase, duration_ms: u64) {
+        self.report(InstallEvent::PhaseFinished { phase, duration_ms });
+    }
+}
+
+/// Reporter that ignores every event
+pub struct NullReporter;
+
+impl ProgressReporter for NullReporter {
+    fn report(&self, _event: InstallEvent) {}
+}
+
+/// Reporter that writes every event to the log
+pub struct LoggingReporter;
+
+impl ProgressReporter for LoggingReporter {
+    fn report(&self, event: InstallEvent) {
+        match event {
+            InstallEvent::PhaseStarted { phase, total_steps } => {
+                log::debug!("{} started ({} steps)", phase, total_steps)
+            }
+            InstallEvent::Step { label, percent, .. } => log::info!("[{:>3}%] {}", percent, label),
+            // Already logged where it happened
+            InstallEvent::Notice { message, .. } => log::debug!("{}", message),
+            InstallEvent::PhaseFinished { phase, duration_ms } => {
+                log::debug!("{} finished in {} ms", phase, duration_ms)
+            }
+        }
+    }
+}
+
+/// Reporter that keeps every event, for tests and scripted frontends
+#[derive(Default)]
+pub struct RecordingReporter {
+    events: Mutex<Vec<InstallEvent>>,
+}
+
+impl RecordingReporter {
+    /// Create an empty recording reporter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events received so far, in order
+    pub fn events(&self) -> Vec<InstallEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl ProgressReporter for RecordingReporter {
+    fn report(&self, event: InstallEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+/// Reporter that forwards every event over a channel
+///
+/// Lets a frontend run the installer on a worker thread and draw progress on
+/// its own. Events sent after the receiver is gone are dropped.
+pub struct ChannelReporter {
+    sender: Mutex<Sender<InstallEvent>>,
+}
+
+impl ChannelReporter {
+    /// Forward events to `sender`
+    pub fn new(sender: Sender<InstallEvent>) -> Self {
+        Self {
+            sender: Mutex::new(sender),
+        }
+    }
+}
+
+impl ProgressReporter for ChannelReporter {
+    fn report(&self, event: InstallEvent) {
+        let _ = self.sender.lock().unwrap().send(event);
+    }
+}
+
+/// Where a phase is, as far as its events tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseState {
+    /// Not started yet
+    Pending,
+    /// Running, `percent` of the way through
+    Running {
+        /// Completion of the phase, 0-100
+        percent: u8,
+    },
+    /// Completed successfully
+    Done,
+    /// Stopped by an error or cancellation
+    Failed,
+}
+
+/// Folds progress events into per-phase state and overall completion
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    phases: Vec<(Phase, PhaseState)>,
+    label: String,
+    devices: Vec<(PathBuf, String)>,
+}
+
+impl ProgressTracker {
+    /// Track the phases that will run, in order
+    pub fn new(phases: Vec<Phase>) -> Self {
+        Self {
+            phases: phases
+                .into_iter()
+                .map(|phase| (phase, PhaseState::Pending))
+                .collect(),
+            label: String::new(),
+            devices: Vec::new(),
+        }
+    }
+
+    /// Update the state from an event
+    pub fn apply(&mut self, event: &InstallEvent) {
+        match event {
+            InstallEvent::PhaseStarted { phase, .. } => {
+                self.set(*phase, PhaseState::Running { percent: 0 });
+                self.devices.clear();
+            }
+            InstallEvent::Step {
+                phase,
+                device,
+                label,
+                percent,
+            } => {
+                self.set(*phase, PhaseState::Running { percent: *percent });
+                self.label = label.clone();
+                if let Some(device) = device {
+                    match self.devices.iter_mut().find(|(d, _)| d == device) {
+                        Some((_, latest)) => *latest = label.clone(),
+                        None => self.devices.push((device.clone(), label.clone())),
+                    }
+                }
+            }
+            InstallEvent::Notice { message, .. } => self.label = message.clone(),
+            InstallEvent::PhaseFinished { phase, .. } => self.set(*phase, PhaseState::Done),
+        }
+    }
+
+    /// Mark the running phase as failed
+    pub fn fail_running(&mut self) {
+        for (_, state) in &mut self.phases {
+            if matches!(state, PhaseState::Running { .. }) {
+                *state = PhaseState::Failed;
+            }
+        }
+    }
+
+    /// Every tracked phase with its state
+    pub fn phases(&self) -> &[(Phase, PhaseState)] {
+        &self.phases
+    }
+
+    /// Label of the latest step
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Latest step on each device the running phase works on, in the order
+    /// the devices were started
+    pub fn devices(&self) -> &[(PathBuf, String)] {
+        &self.devices
+    }
+
+    /// Completion of the whole installation, 0.0-1.0
+    pub fn overall(&self) -> f32 {
+        let done: f32 = self
+            .phases
+            .iter()
+            .map(|(_, state)| match state {
+                PhaseState::Done => 1.0,
+                PhaseState::Running { percent } => f32::from(*percent) / 100.0,
+                PhaseState::Pending | PhaseState::Failed => 0.0,
+            })
+            .sum();
+        done / self.phases.len().max(1) as f32
+    }
+
+    fn set(&mut self, phase: Phase, new_state: PhaseState) {
+        if let Some((_, state)) = self.phases.iter_mut().find(|(p, _)| *p == phase) {
+            *state = new_state;
+        }
+    }
+}
+
+/// Numbers the steps of one phase
+///
+/// Each step is reported with the share of the phase completed before it.
+/// Starting a step is also where a cancelled installation stops.
+///
+/// A phase working on several devices at once gives each its own
+/// [`for_device`](Self::for_device) view; the views share the step count.
+pub(crate) struct PhaseSteps<'a> {
+    reporter: &'a dyn ProgressReporter,
+    phase: Phase,
+    total: usize,
+    done: Arc<AtomicUsize>,
+    cancel: CancelToken,
+    device: Option<PathBuf>,
+    halted: Arc<AtomicBool>,
+}
+
+impl<'a> PhaseSteps<'a> {
+    pub(crate) fn new(reporter: &'a dyn ProgressReporter, phase: Phase, total: usize) -> Self {
+        Self {
+            reporter,
+            phase,
+            total,
+            done: Arc::default(),
+            cancel: CancelToken::new(),
+            device: None,
+            halted: Arc::default(),
+        }
+    }
+
+    /// Stop at the next step once `cancel` is cancelled
+    pub(crate) fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Steps of the work on `device`, counted with the rest of the phase
+    pub(crate) fn for_device(&self, device: &Path) -> Self {
+        Self {
+            reporter: self.reporter,
+            phase: self.phase,
+            total: self.total,
+            done: self.done.clone(),
+            cancel: self.cancel.clone(),
+            device: Some(device.to_path_buf()),
+            halted: self.halted.clone(),
+        }
+    }
+
+    /// Stop the work on every other device at its next step
+    pub(crate) fn halt_devices(&self) {
+        self.halted.store(true, Ordering::SeqCst);
+    }
+
+    /// Report the next step, or fail if the installation was cancelled (or,
+    /// on a device, if another device failed)
+    pub(crate) fn step(&self, label: &str) -> Result<()> {
+        self.cancel.check()?;
+        if self.device.is_some() && self.halted.load(Ordering::SeqCst) {
+            return Err(InstallerError::AnotherDeviceFailed);
+        }
+        let done = self.done.fetch_add(1, Ordering::SeqCst);
+        self.reporter.report(InstallEvent::Step {
+            phase: self.phase,
+            device: self.device.clone(),
+            label: label.to_string(),
+            percent: percent_of(done, self.total),
+        });
+        Ok(())
+    }
+
+    /// Report something done on the side, without moving the phase on
+    pub(crate) fn notice(&self, message: &str) {
+        self.reporter.report(InstallEvent::Notice {
+            phase: self.phase,
+            message: message.to_string(),
+        });
+    }
+
+    /// The reporter, for work that reports its own progress
+    pub(crate) fn reporter(&self) -> &'a dyn ProgressReporter {
+        self.reporter
+    }
+}
+
+/// `done` out of `total` as a percentage, clamped to 0-100
+fn percent_of(done: usize, total: usize) -> u8 {
+    (done * 100 / total.max(1)).min(100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_are_numbered() {
+        let reporter = RecordingReporter::new();
+        reporter.phase_started(Phase::CreatePool, 2);
+        let steps = PhaseSteps::new(&reporter, Phase::CreatePool, 2);
+        steps.step("Creating pool").unwrap();
+        steps.step("Creating datasets").unwrap();
+        reporter.phase_finished(Phase::CreatePool, 5);
+
+        let percents: Vec<u8> = reporter
+            .events()
+            .iter()
+            .filter_map(|e| match e {
+                InstallEvent::Step { percent, .. } => Some(*percent),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(percents, vec![0, 50]);
+        assert_eq!(reporter.events().len(), 4);
+    }
+
+    #[test]
+    fn test_device_steps_share_the_count() {
+        let reporter = RecordingReporter::new();
+        let steps = PhaseSteps::new(&reporter, Phase::PrepareDisks, 4);
+        let sda = steps.for_device(Path::new("/dev/sda"));
+        let sdb = steps.for_device(Path::new("/dev/sdb"));
+        sda.step("Wiping /dev/sda").unwrap();
+        sdb.step("Wiping /dev/sdb").unwrap();
+        sda.step("Partitioning /dev/sda").unwrap();
+
+        let mut tracker = ProgressTracker::new(vec![Phase::PrepareDisks]);
+        for event in reporter.events() {
+            tracker.apply(&event);
+        }
+        assert_eq!(tracker.phases()[0].1, PhaseState::Running { percent: 50 });
+        assert_eq!(
+            tracker.devices(),
+            [
+                (
+                    PathBuf::from("/dev/sda"),
+                    "Partitioning /dev/sda".to_string()
+                ),
+                (PathBuf::from("/dev/sdb"), "Wiping /dev/sdb".to_string()),
+            ]
+        );
+
+        // Once one device fails, the others stop at their next step
+        sdb.halt_devices();
+        assert!(matches!(
+            sda.step("Formatting /dev/sda1"),
+            Err(InstallerError::AnotherDeviceFailed)
+        ));
+        steps.step("Done").unwrap();
+    }
+
+    #[test]
+    fn test_tracker_follows_channel_events() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let reporter = ChannelReporter::new(tx);
+        reporter.phase_started(Phase::Validate, 1);
+        reporter.phase_finished(Phase::Validate, 1);
+        reporter.phase_started(Phase::CreatePool, 2);
+        reporter.step(Phase::CreatePool, "Creating datasets", 50);
+        drop(reporter);
+
+        let mut tracker = ProgressTracker::new(vec![
+            Phase::Validate,
+            Phase::CreatePool,
+            Phase::Finalize,
+            Phase::InstallBootloader,
+        ]);
+        for event in rx {
+            tracker.apply(&event);
+        }
+
+        assert_eq!(tracker.label(), "Creating datasets");
+        assert_eq!(tracker.phases()[0].1, PhaseState::Done);
+        assert_eq!(tracker.phases()[1].1, PhaseState::Running { percent: 50 });
+        assert_eq!(tracker.phases()[2].1, PhaseState::Pending);
+        assert!((tracker.overall() - 1.5 / 4.0).abs() < f32::EPSILON);
+
+        tracker.fail_running();
+        assert_eq!(tracker.phases()[1].1, PhaseState::Failed);
+    }
+}
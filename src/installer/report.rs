@@ -0,0 +1,524 @@
+//! Installation report
+//!
+//! At the end of an installation an [`InstallReport`] is written to the target
+//! system as `/var/lib/zbm-installer/install-report.json`, with a human-readable
+//! `install-report.txt` next to it. Later tooling (cleanup, resume) reads the
+//! JSON file back, so its layout is versioned with [`REPORT_SCHEMA_VERSION`].
+
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, FileOp};
+use crate::installer::Phase;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the report layout; bump on incompatible changes
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Directory the report is written to, relative to the target root
+pub const REPORT_DIR: &str = "var/lib/zbm-installer";
+
+/// File name of the JSON report
+pub const REPORT_JSON: &str = "install-report.json";
+
+/// File name of the text report
+pub const REPORT_TEXT: &str = "install-report.txt";
+
+/// Summary of the configuration the installation ran with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigSummary {
+    /// Installation mode
+    pub mode: String,
+    /// RAID level
+    pub raid_level: String,
+    /// Boot mode ("uefi" or "hybrid")
+    #[serde(default = "default_boot_mode")]
+    pub boot_mode: String,
+    /// Compression algorithm
+    pub compression: String,
+    /// Encryption ("aes-256-gcm, passphrase key"), None if unencrypted
+    #[serde(default)]
+    pub encryption: Option<String>,
+    /// ashift (None means auto-detected)
+    pub ashift: Option<u8>,
+    /// EFI partition size
+    pub efi_size: String,
+    /// Swap partition size
+    pub swap_size: String,
+    /// Hostname of the new system
+    pub hostname: Option<String>,
+}
+
+/// Boot mode recorded by reports written before it was tracked
+fn default_boot_mode() -> String {
+    "uefi".to_string()
+}
+
+/// A partition created on a device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionReport {
+    /// What the partition is for ("bios", "efi", "swap" or "zfs")
+    pub role: String,
+    /// Partition device path
+    pub path: PathBuf,
+    /// PARTUUID, if it could be read
+    pub partuuid: Option<String>,
+}
+
+/// A device the installer prepared
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceReport {
+    /// Device path
+    pub path: PathBuf,
+    /// Device model
+    pub model: Option<String>,
+    /// Device serial number
+    pub serial: Option<String>,
+    /// Device size in bytes
+    pub size: u64,
+    /// Partitions created on the device
+    pub partitions: Vec<PartitionReport>,
+}
+
+/// The pool that was created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolReport {
+    /// Pool name
+    pub name: String,
+    /// Pool GUID, if it could be read
+    pub guid: Option<String>,
+    /// Datasets in the pool
+    pub datasets: Vec<String>,
+}
+
+/// How long a phase took
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    /// The phase
+    pub phase: Phase,
+    /// Duration in milliseconds
+    pub duration_ms: u64,
+}
+
+/// How the bootloader installation went
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootloaderReport {
+    /// The bootloader ("systemd-boot", "grub", "efi-stub" or "syslinux")
+    pub name: String,
+    /// Why it failed to install, when `ignore_bootloader_errors` let the
+    /// installation finish anyway
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for BootloaderReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error {
+            None => write!(f, "{} installed", self.name),
+            Some(error) => write!(f, "{} FAILED (ignored): {}", self.name, error),
+        }
+    }
+}
+
+/// Network configuration carried over to a migrated system
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkReport {
+    /// Network stacks found on the source ("netplan", "NetworkManager", ...)
+    pub stacks: Vec<String>,
+    /// Configuration files copied, as paths in the installed system
+    pub files: Vec<PathBuf>,
+    /// MAC address each pinned interface name is matched by
+    pub pinned_interfaces: BTreeMap<String, String>,
+}
+
+impl std::fmt::Display for NetworkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.stacks.is_empty() {
+            write!(f, "no configuration found")?;
+        } else {
+            write!(f, "{} ({} files)", self.stacks.join(", "), self.files.len())?;
+        }
+        if !self.pinned_interfaces.is_empty() {
+            write!(
+                f,
+                ", {} interface names pinned",
+                self.pinned_interfaces.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Record of a completed installation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallReport {
+    /// Report layout version
+    pub schema_version: u32,
+    /// When the installation started (RFC 3339)
+    pub started_at: String,
+    /// When the installation finished (RFC 3339)
+    pub finished_at: String,
+    /// Whether this was a dry run
+    pub dry_run: bool,
+    /// Configuration summary
+    pub config: ConfigSummary,
+    /// Prepared devices
+    pub devices: Vec<DeviceReport>,
+    /// Created pool
+    pub pool: PoolReport,
+    /// Resolved component versions (installer, zfs, zfsbootmenu, ...)
+    pub versions: BTreeMap<String, String>,
+    /// Bootloader status; reports written before it was tracked have none
+    #[serde(default)]
+    pub bootloader: Option<BootloaderReport>,
+    /// Network configuration carried over, for migrations
+    #[serde(default)]
+    pub network: Option<NetworkReport>,
+    /// Time spent in each phase, in execution order
+    pub phases: Vec<PhaseTiming>,
+    /// Location of the rollback journal, if one was kept
+    pub rollback_journal: Option<PathBuf>,
+}
+
+impl InstallReport {
+    /// Serialize to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| InstallerError::Other(format!("Failed to serialize report: {}", e)))
+    }
+
+    /// Parse a JSON report, rejecting layouts newer than this build understands
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| InstallerError::ParseError(format!("Invalid install report: {}", e)))?;
+
+        let version = value
+            .get("schema_version")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| {
+                InstallerError::ParseError("Install report has no schema_version".to_string())
+            })?;
+        if version > u64::from(REPORT_SCHEMA_VERSION) {
+            return Err(InstallerError::Unsupported(format!(
+                "Install report schema version {} is newer than supported version {}",
+                version, REPORT_SCHEMA_VERSION
+            )));
+        }
+
+        serde_json::from_value(value)
+            .map_err(|e| InstallerError::ParseError(format!("Invalid install report: {}", e)))
+    }
+
+    /// Path of the JSON report under a target root
+    pub fn json_path(root: &Path) -> PathBuf {
+        root.join(REPORT_DIR).join(REPORT_JSON)
+    }
+
+    /// Write the JSON and text reports under `root` through `executor`
+    pub fn write(&self, root: &Path, executor: &dyn CommandExecutor) -> Result<PathBuf> {
+        let dir = root.join(REPORT_DIR);
+        let json_path = dir.join(REPORT_JSON);
+
+        executor.apply(FileOp::create_dir(&dir))?;
+        executor.apply(FileOp::write(&json_path, self.to_json()?))?;
+        executor.apply(FileOp::write(dir.join(REPORT_TEXT), self.render_text()))?;
+
+        log::info!("Install report: {}", json_path.display());
+        Ok(json_path)
+    }
+
+    /// Read the JSON report from under `root`
+    pub fn read(root: &Path) -> Result<Self> {
+        let json = fs::read_to_string(Self::json_path(root))?;
+        Self::from_json(&json)
+    }
+
+    /// Render the human-readable text report
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        let or_dash = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+
+        let _ = writeln!(out, "ZFSBootMenu installation report");
+        let _ = writeln!(out, "===============================");
+        let _ = writeln!(out, "Schema version: {}", self.schema_version);
+        let _ = writeln!(out, "Started:        {}", self.started_at);
+        let _ = writeln!(out, "Finished:       {}", self.finished_at);
+        let _ = writeln!(
+            out,
+            "Dry run:        {}",
+            if self.dry_run { "yes" } else { "no" }
+        );
+
+        let _ = writeln!(out, "\nConfiguration");
+        let _ = writeln!(out, "  Mode:        {}", self.config.mode);
+        let _ = writeln!(out, "  RAID:        {}", self.config.raid_level);
+        let _ = writeln!(out, "  Boot mode:   {}", self.config.boot_mode);
+        let _ = writeln!(out, "  Compression: {}", self.config.compression);
+        if let Some(ref encryption) = self.config.encryption {
+            let _ = writeln!(out, "  Encryption:  {}", encryption);
+        }
+        let _ = writeln!(
+            out,
+            "  ashift:      {}",
+            self.config
+                .ashift
+                .map(|a| a.to_string())
+                .unwrap_or_else(|| "auto".to_string())
+        );
+        let _ = writeln!(out, "  EFI size:    {}", self.config.efi_size);
+        let _ = writeln!(out, "  Swap size:   {}", self.config.swap_size);
+        let _ = writeln!(out, "  Hostname:    {}", or_dash(&self.config.hostname));
+
+        let _ = writeln!(out, "\nDevices");
+        for device in &self.devices {
+            let _ = writeln!(
+                out,
+                "  {} (model {}, serial {}, {} bytes)",
+                device.path.display(),
+                or_dash(&device.model),
+                or_dash(&device.serial),
+                device.size
+            );
+            for part in &device.partitions {
+                let _ = writeln!(
+                    out,
+                    "    {:<5} {}  PARTUUID {}",
+                    part.role,
+                    part.path.display(),
+                    or_dash(&part.partuuid)
+                );
+            }
+        }
+
+        let _ = writeln!(out, "\nPool");
+        let _ = writeln!(out, "  Name: {}", self.pool.name);
+        let _ = writeln!(out, "  GUID: {}", or_dash(&self.pool.guid));
+        let _ = writeln!(out, "  Datasets:");
+        for dataset in &self.pool.datasets {
+            let _ = writeln!(out, "    {}", dataset);
+        }
+
+        let _ = writeln!(out, "\nVersions");
+        for (component, version) in &self.versions {
+            let _ = writeln!(out, "  {:<12} {}", component, version);
+        }
+
+        if let Some(ref bootloader) = self.bootloader {
+            let _ = writeln!(out, "\nBootloader: {}", bootloader);
+        }
+
+        if let Some(ref network) = self.network {
+            let _ = writeln!(out, "\nNetwork: {}", network);
+            for file in &network.files {
+                let _ = writeln!(out, "  {}", file.display());
+            }
+            for (name, mac) in &network.pinned_interfaces {
+                let _ = writeln!(out, "  {} pinned to {}", name, mac);
+            }
+        }
+
+        let _ = writeln!(out, "\nPhase timings");
+        for timing in &self.phases {
+            let _ = writeln!(
+                out,
+                "  {:<18} {:.1}s",
+                timing.phase.to_string(),
+                timing.duration_ms as f64 / 1000.0
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "\nRollback journal: {}",
+            self.rollback_journal
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "none".to_string())
+        );
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::SystemExecutor;
+
+    fn sample_report() -> InstallReport {
+        InstallReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            started_at: "2026-01-02T03:04:05Z".to_string(),
+            finished_at: "2026-01-02T03:10:00Z".to_string(),
+            dry_run: false,
+            config: ConfigSummary {
+                mode: "new".to_string(),
+                raid_level: "mirror".to_string(),
+                boot_mode: "uefi".to_string(),
+                compression: "zstd".to_string(),
+                encryption: None,
+                ashift: Some(12),
+                efi_size: "1.0 GiB".to_string(),
+                swap_size: "0 B".to_string(),
+                hostname: Some("tank".to_string()),
+            },
+            devices: vec![DeviceReport {
+                path: PathBuf::from("/dev/sda"),
+                model: Some("Samsung SSD 870".to_string()),
+                serial: Some("S5Y1NX0R".to_string()),
+                size: 500107862016,
+                partitions: vec![
+                    PartitionReport {
+                        role: "efi".to_string(),
+                        path: PathBuf::from("/dev/sda1"),
+                        partuuid: Some("0b5c6a1e-01".to_string()),
+                    },
+                    PartitionReport {
+                        role: "zfs".to_string(),
+                        path: PathBuf::from("/dev/sda2"),
+                        partuuid: None,
+                    },
+                ],
+            }],
+            pool: PoolReport {
+                name: "zroot".to_string(),
+                guid: Some("1234567890".to_string()),
+                datasets: vec!["zroot/ROOT".to_string(), "zroot/ROOT/default".to_string()],
+            },
+            versions: BTreeMap::from([
+                ("installer".to_string(), "0.1.0".to_string()),
+                ("zfsbootmenu".to_string(), "2.3.0".to_string()),
+            ]),
+            bootloader: Some(BootloaderReport {
+                name: "systemd-boot".to_string(),
+                error: None,
+            }),
+            network: None,
+            phases: vec![
+                PhaseTiming {
+                    phase: Phase::Validate,
+                    duration_ms: 120,
+                },
+                PhaseTiming {
+                    phase: Phase::PrepareDisks,
+                    duration_ms: 4500,
+                },
+            ],
+            rollback_journal: None,
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let report = sample_report();
+        let json = report.to_json().unwrap();
+        assert!(json.contains("\"schema_version\": 1"));
+        assert_eq!(InstallReport::from_json(&json).unwrap(), report);
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let mut value = serde_json::to_value(sample_report()).unwrap();
+        value["schema_version"] = serde_json::json!(REPORT_SCHEMA_VERSION + 1);
+
+        let err = InstallReport::from_json(&value.to_string()).unwrap_err();
+        assert!(matches!(err, InstallerError::Unsupported(_)));
+    }
+
+    #[test]
+    fn test_text_rendering() {
+        let expected = "\
+ZFSBootMenu installation report
+===============================
+Schema version: 1
+Started:        2026-01-02T03:04:05Z
+Finished:       2026-01-02T03:10:00Z
+Dry run:        no
+
+Configuration
+  Mode:        new
+  RAID:        mirror
+  Boot mode:   uefi
+  Compression: zstd
+  ashift:      12
+  EFI size:    1.0 GiB
+  Swap size:   0 B
+  Hostname:    tank
+
+Devices
+  /dev/sda (model Samsung SSD 870, serial S5Y1NX0R, 500107862016 bytes)
+    efi   /dev/sda1  PARTUUID 0b5c6a1e-01
+    zfs   /dev/sda2  PARTUUID -
+
+Pool
+  Name: zroot
+  GUID: 1234567890
+  Datasets:
+    zroot/ROOT
+    zroot/ROOT/default
+
+Versions
+  installer    0.1.0
+  zfsbootmenu  2.3.0
+
+Bootloader: systemd-boot installed
+
+Phase timings
+  Validate           0.1s
+  PrepareDisks       4.5s
+
+Rollback journal: none
+";
+        assert_eq!(sample_report().render_text(), expected);
+    }
+
+    #[test]
+    fn test_network_rendering() {
+        let report = InstallReport {
+            network: Some(NetworkReport {
+                stacks: vec!["netplan".to_string()],
+                files: vec![PathBuf::from("/etc/netplan/01-netcfg.yaml")],
+                pinned_interfaces: BTreeMap::from([(
+                    "enp3s0".to_string(),
+                    "52:54:00:12:34:56".to_string(),
+                )]),
+            }),
+            ..sample_report()
+        };
+        assert!(report.render_text().contains(
+            "\nNetwork: netplan (1 files), 1 interface names pinned\n  \
+             /etc/netplan/01-netcfg.yaml\n  enp3s0 pinned to 52:54:00:12:34:56\n"
+        ));
+
+        // Reports from before networks were tracked still read
+        let mut value = serde_json::to_value(sample_report()).unwrap();
+        value.as_object_mut().unwrap().remove("network");
+        assert_eq!(
+            InstallReport::from_json(&value.to_string()).unwrap(),
+            sample_report()
+        );
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let root = tempfile::tempdir().unwrap();
+        let report = sample_report();
+
+        let path = report
+            .write(root.path(), &SystemExecutor::new(false))
+            .unwrap();
+        assert_eq!(path, InstallReport::json_path(root.path()));
+        assert!(root.path().join(REPORT_DIR).join(REPORT_TEXT).exists());
+        assert_eq!(InstallReport::read(root.path()).unwrap(), report);
+    }
+
+    #[test]
+    fn test_write_respects_dry_run() {
+        let root = tempfile::tempdir().unwrap();
+        sample_report()
+            .write(root.path(), &SystemExecutor::new(true))
+            .unwrap();
+        assert!(!root.path().join(REPORT_DIR).exists());
+    }
+}
@@ -0,0 +1,95 @@
+//! Undoing a failed installation
+//!
+//! Phases record each change to the system as they make it. If the
+//! installation fails, [`Installer::rollback`](super::Installer::rollback)
+//! reverses them newest first, so the disks aren't left with a half-built
+//! pool and stray mounts.
+
+use crate::disk::BlockDevice;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A change to the system that can be undone
+#[derive(Debug, Clone)]
+pub enum UndoAction {
+    /// A disk was wiped and repartitioned
+    Partitioned {
+        /// The disk
+        device: Box<BlockDevice>,
+    },
+    /// Partitions were added to a disk's existing table
+    PartitionsAdded {
+        /// The disk
+        device: Box<BlockDevice>,
+        /// Numbers of the new partitions
+        numbers: Vec<u32>,
+    },
+    /// The pool was created (its datasets go with it)
+    PoolCreated {
+        /// Pool name
+        pool: String,
+    },
+    /// An existing pool was imported
+    PoolImported {
+        /// Pool name
+        pool: String,
+    },
+    /// A dataset was mounted
+    DatasetMounted {
+        /// Dataset name relative to the pool (`ROOT/default`)
+        dataset: String,
+    },
+    /// A filesystem was mounted
+    Mounted {
+        /// Mount point
+        path: PathBuf,
+    },
+}
+
+impl std::fmt::Display for UndoAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Partitioned { device } => write!(f, "partitioned {}", device.path.display()),
+            Self::PartitionsAdded { device, numbers } => write!(
+                f,
+                "added partitions {} to {}",
+                numbers
+                    .iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                device.path.display()
+            ),
+            Self::PoolCreated { pool } => write!(f, "created pool {}", pool),
+            Self::PoolImported { pool } => write!(f, "imported pool {}", pool),
+            Self::DatasetMounted { dataset } => write!(f, "mounted dataset {}", dataset),
+            Self::Mounted { path } => write!(f, "mounted {}", path.display()),
+        }
+    }
+}
+
+/// Changes made so far, oldest first
+#[derive(Debug, Default)]
+pub struct UndoLog {
+    actions: Mutex<Vec<UndoAction>>,
+}
+
+impl UndoLog {
+    /// Record a change
+    pub fn record(&self, action: UndoAction) {
+        log::debug!("Recorded for rollback: {}", action);
+        self.actions.lock().unwrap().push(action);
+    }
+
+    /// Changes recorded so far, oldest first
+    pub fn actions(&self) -> Vec<UndoAction> {
+        self.actions.lock().unwrap().clone()
+    }
+
+    /// Remove and return every change, newest first
+    pub(super) fn drain_newest_first(&self) -> Vec<UndoAction> {
+        let mut actions = std::mem::take(&mut *self.actions.lock().unwrap());
+        actions.reverse();
+        actions
+    }
+}
@@ -0,0 +1,239 @@
+//! Resuming an interrupted installation
+//!
+//! With a state file set (see
+//! [`Installer::with_state_file`](super::Installer::with_state_file)), the
+//! installer records after every phase which phases have completed and what
+//! they produced. When an installation fails part-way, say because the
+//! network dropped during the ZFSBootMenu download,
+//! [`Installer::resume`](super::Installer::resume) continues from the first
+//! incomplete phase instead of wiping the disks again.
+//!
+//! The state is only trusted if the configuration is unchanged and the
+//! partitions and pool it describes are still there. It is deleted once the
+//! installation succeeds.
+
+use crate::config::Config;
+use crate::disk::ZbmPartitions;
+use crate::error::{InstallerError, Result};
+use crate::installer::report::NetworkReport;
+use crate::installer::Phase;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the CLI keeps the state of the current installation
+pub const STATE_PATH: &str = "/var/lib/zbm-installer/state.json";
+
+/// Format version of the state file
+const STATE_VERSION: u32 = 1;
+
+/// Progress of an installation, as persisted between runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallState {
+    /// Format version
+    pub version: u32,
+    /// [`config_hash`] of the configuration being installed
+    pub config_hash: String,
+    /// Phases whose results are still in place, in order
+    pub completed: Vec<Phase>,
+    /// Prepared disks and their partitions
+    pub devices: Vec<DeviceState>,
+    /// GUID of the pool, once created
+    pub pool_guid: Option<String>,
+    /// ZFSBootMenu version, once installed
+    pub zbm_version: Option<String>,
+    /// Ignored bootloader failure, once installed
+    #[serde(default)]
+    pub bootloader_error: Option<String>,
+    /// Network configuration carried over, once migrated
+    #[serde(default)]
+    pub network: Option<NetworkReport>,
+    /// Name of the boot environment being installed
+    #[serde(default)]
+    pub boot_environment: Option<String>,
+}
+
+/// A prepared disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceState {
+    /// The disk
+    pub device: PathBuf,
+    /// Partitions made on it
+    pub partitions: ZbmPartitions,
+    /// PARTUUID of each partition, to tell it is still the same one
+    pub partuuids: BTreeMap<PathBuf, String>,
+}
+
+impl InstallState {
+    /// Read the state left by an earlier run
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path).map_err(|e| {
+            InstallerError::ResumeMismatch(format!(
+                "no interrupted installation found ({}: {})",
+                path.display(),
+                e
+            ))
+        })?;
+        let state: Self = serde_json::from_str(&json).map_err(|e| {
+            InstallerError::ResumeMismatch(format!("{} is unreadable: {}", path.display(), e))
+        })?;
+        if state.version != STATE_VERSION {
+            return Err(InstallerError::ResumeMismatch(format!(
+                "{} was written by an incompatible version of the installer",
+                path.display()
+            )));
+        }
+        Ok(state)
+    }
+
+    /// Serialize for writing to the state file
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| InstallerError::Other(format!("Failed to serialize state: {}", e)))
+    }
+
+    /// Fail unless the state was written for `config`
+    pub fn check_config(&self, config: &Config) -> Result<()> {
+        if self.config_hash != config_hash(config)? {
+            return Err(InstallerError::ResumeMismatch(
+                "the configuration has changed since the interrupted run".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// First phase that still has to run
+    pub fn next_phase(&self) -> Option<Phase> {
+        Phase::ALL
+            .into_iter()
+            .find(|phase| !self.completed.contains(phase))
+    }
+}
+
+impl Default for InstallState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            config_hash: String::new(),
+            completed: Vec::new(),
+            devices: Vec::new(),
+            pool_guid: None,
+            zbm_version: None,
+            bootloader_error: None,
+            network: None,
+            boot_environment: None,
+        }
+    }
+}
+
+/// Fingerprint of what `config` installs
+///
+/// Settings that only change how the installer runs (dry run, confirmation,
+/// rollback, pre-flight checks, releasing stacked holders, parallelism) are
+/// left out, so a resumed run may differ in those.
+pub fn config_hash(config: &Config) -> Result<String> {
+    let config = Config {
+        dry_run: false,
+        force: false,
+        unattended: false,
+        skip_preflight: false,
+        rollback: false,
+        wipe_on_rollback: false,
+        release_holders: false,
+        jobs: None,
+        ..config.clone()
+    };
+    // Converting to a value sorts the keys, hooks included
+    let json = serde_json::to_value(&config)
+        .map(|value| value.to_string())
+        .map_err(|e| InstallerError::Other(format!("Failed to serialize config: {}", e)))?;
+    Ok(format!("{:016x}", fnv1a(json.as_bytes())))
+}
+
+/// 64-bit FNV-1a; stable across builds, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_hash_ignores_how_the_installer_runs() {
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            ..Default::default()
+        };
+        let hash = config_hash(&config).unwrap();
+        assert_eq!(hash.len(), 16);
+        assert_eq!(config_hash(&config.clone()).unwrap(), hash);
+
+        let rerun = Config {
+            force: true,
+            rollback: false,
+            ..config.clone()
+        };
+        assert_eq!(config_hash(&rerun).unwrap(), hash);
+
+        // The first run stopped the holders, so a resume finds none
+        let released = Config {
+            release_holders: true,
+            ..config.clone()
+        };
+        assert_eq!(config_hash(&released).unwrap(), hash);
+
+        let other_pool = Config {
+            pool_name: "tank".to_string(),
+            ..config
+        };
+        assert_ne!(config_hash(&other_pool).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_load_round_trip_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let err = InstallState::load(&path).unwrap_err();
+        assert!(
+            err.to_string().contains("no interrupted installation"),
+            "{err}"
+        );
+
+        let state = InstallState {
+            config_hash: "0123456789abcdef".to_string(),
+            completed: vec![Phase::Validate, Phase::PrepareDisks],
+            devices: vec![DeviceState {
+                device: PathBuf::from("/dev/sda"),
+                partitions: ZbmPartitions {
+                    bios_boot: None,
+                    efi: Some(PathBuf::from("/dev/sda1")),
+                    boot: None,
+                    swap: None,
+                    zfs: PathBuf::from("/dev/sda2"),
+                },
+                partuuids: BTreeMap::from([(
+                    PathBuf::from("/dev/sda1"),
+                    "6e3f4c1a-01".to_string(),
+                )]),
+            }],
+            ..Default::default()
+        };
+        fs::write(&path, state.to_json().unwrap()).unwrap();
+        let loaded = InstallState::load(&path).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded.next_phase(), Some(Phase::CreatePool));
+
+        let err = loaded.check_config(&Config::default()).unwrap_err();
+        assert!(
+            err.to_string().contains("Start a fresh installation"),
+            "{err}"
+        );
+
+        fs::write(&path, r#"{"version": 99}"#).unwrap();
+        assert!(InstallState::load(&path).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Persistent install log
+//!
+//! Everything logged during an installation is also written to a file under
+//! [`LOG_DIR`], together with a transcript of every command the installer
+//! runs: its argv, output and exit code. The file outlives the terminal
+//! session for post-mortem debugging and is copied into the installed system
+//! (at [`TARGET_LOG`]) during finalize.
+//!
+//! Secrets registered with [`LogSink::redact`] (encryption passphrases) are
+//! replaced before anything reaches the file.
+
+use crate::error::{InstallerError, Result};
+use crate::exec::{command_argv, CommandExecutor, ExecOptions, ExecResult, FileOp, PlannedAction};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Directory install logs are written to by default
+pub const LOG_DIR: &str = "/var/log/zbm-installer";
+
+/// Where the log is copied to in the installed system
+pub const TARGET_LOG: &str = "/var/log/zbm-install.log";
+
+/// What a secret is replaced with
+pub(crate) const REDACTED: &str = "<redacted>";
+
+struct SinkState {
+    writer: Box<dyn Write + Send>,
+    secrets: Vec<String>,
+    partial: String,
+}
+
+impl SinkState {
+    fn write_line(&mut self, line: &str) {
+        let mut line = line.to_string();
+        for secret in &self.secrets {
+            line = line.replace(secret.as_str(), REDACTED);
+        }
+        // A full disk mustn't stop the installation
+        let _ = writeln!(self.writer, "{}", line);
+    }
+}
+
+/// Log file shared by the logger and the command transcript
+///
+/// Clones write to the same file. Writes go through line by line, so a
+/// secret is redacted even if the logger hands it over in pieces.
+#[derive(Clone)]
+pub struct LogSink {
+    state: Arc<Mutex<SinkState>>,
+    path: PathBuf,
+}
+
+impl LogSink {
+    /// Default location: a file named after the current time in [`LOG_DIR`]
+    pub fn default_path() -> PathBuf {
+        let now = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        Path::new(LOG_DIR).join(format!("{}.log", now))
+    }
+
+    /// Create (or truncate) the log file at `path`, and its directory
+    pub fn create(path: &Path) -> Result<Self> {
+        let open = || -> io::Result<File> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            File::create(path)
+        };
+        let file = open().map_err(|e| {
+            InstallerError::Other(format!(
+                "Failed to create log file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(Self::new(file, path))
+    }
+
+    /// Write to `writer`, reporting `path` as the location
+    pub fn new(writer: impl Write + Send + 'static, path: impl Into<PathBuf>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SinkState {
+                writer: Box::new(writer),
+                secrets: Vec::new(),
+                partial: String::new(),
+            })),
+            path: path.into(),
+        }
+    }
+
+    /// Where the log is written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Never write `secret`; replace it wherever it appears
+    pub fn redact(&self, secret: &str) {
+        if secret.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if !state.secrets.iter().any(|s| s == secret) {
+            state.secrets.push(secret.to_string());
+        }
+    }
+
+    /// Record a command and how it went
+    pub fn record_command(&self, argv: &[String], result: &Result<ExecResult>, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.write_line(&format!("$ {}", argv.join(" ")));
+
+        let secs = elapsed.as_secs_f64();
+        match result {
+            Ok(ExecResult::Skipped { .. }) => state.write_line("  (dry run, not executed)"),
+            Ok(ExecResult::Ran {
+                stdout,
+                stderr,
+                status,
+            }) => {
+                write_output(&mut state, "stdout", stdout);
+                write_output(&mut state, "stderr", stderr);
+                let code = status
+                    .code()
+                    .map_or_else(|| "none".to_string(), |c| c.to_string());
+                state.write_line(&format!("  exit code {} after {:.2}s", code, secs));
+            }
+            Err(InstallerError::CommandFailed { code, stderr, .. }) => {
+                write_output(&mut state, "stderr", stderr.as_bytes());
+                state.write_line(&format!("  exit code {} after {:.2}s", code, secs));
+            }
+            Err(e) => state.write_line(&format!("  failed after {:.2}s: {}", secs, e)),
+        }
+        let _ = state.writer.flush();
+    }
+}
+
+/// Write each line of a command's output, prefixed with its stream
+fn write_output(state: &mut SinkState, stream: &str, output: &[u8]) {
+    for line in String::from_utf8_lossy(output).lines() {
+        state.write_line(&format!("  [{}] {}", stream, line));
+    }
+}
+
+impl Write for LogSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        state.partial.push_str(&String::from_utf8_lossy(buf));
+        while let Some(end) = state.partial.find('\n') {
+            let line: String = state.partial.drain(..=end).collect();
+            state.write_line(line.trim_end_matches('\n'));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.lock().unwrap().writer.flush()
+    }
+}
+
+/// Writer that sends everything to two writers
+///
+/// Used as the logger's target to keep the terminal (or the TUI's log tail)
+/// and the log file in step.
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Write, B: Write> Tee<A, B> {
+    /// Write to `first`, then `second`
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.first.write_all(buf)?;
+        self.second.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
+/// Executor that records every command it runs in a log file
+pub(crate) struct TranscriptExecutor {
+    inner: Arc<dyn CommandExecutor>,
+    sink: LogSink,
+}
+
+impl TranscriptExecutor {
+    pub(crate) fn new(inner: Arc<dyn CommandExecutor>, sink: LogSink) -> Self {
+        Self { inner, sink }
+    }
+}
+
+impl CommandExecutor for TranscriptExecutor {
+    fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+        let argv = command_argv(cmd);
+        let start = Instant::now();
+        let result = self.inner.execute_with(cmd, options);
+        self.sink.record_command(&argv, &result, start.elapsed());
+        result
+    }
+
+    fn apply(&self, op: FileOp) -> Result<()> {
+        self.inner.apply(op)
+    }
+
+    fn plan(&self, action: PlannedAction) {
+        self.inner.plan(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    /// Shared buffer standing in for the log file
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_transcript_records_commands_and_redacts_secrets() {
+        let buffer = Buffer::default();
+        let sink = LogSink::new(buffer.clone(), "/var/log/zbm-installer/test.log");
+        sink.redact("correct horse");
+
+        let recording = Arc::new(RecordingExecutor::new());
+        recording.respond_program("zpool", "pool: zroot\nkey correct horse\n");
+        recording.fail_program("zfs", 1, "dataset already exists");
+        let executor = TranscriptExecutor::new(recording, sink.clone());
+        executor
+            .execute(Command::new("zpool").arg("status"))
+            .unwrap();
+        executor
+            .execute(Command::new("zfs").args(["create", "zroot/ROOT"]))
+            .unwrap_err();
+
+        // Log records may arrive in pieces
+        let mut logger = sink.clone();
+        write!(logger, "INFO passphrase is correct").unwrap();
+        writeln!(logger, " horse").unwrap();
+
+        let text = buffer.text();
+        assert!(!text.contains("correct horse"), "{text}");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "$ zpool status");
+        assert_eq!(lines[1], "  [stdout] pool: zroot");
+        assert_eq!(lines[2], "  [stdout] key <redacted>");
+        assert!(lines[3].starts_with("  exit code 0 after "), "{text}");
+        assert_eq!(lines[4], "$ zfs create zroot/ROOT");
+        assert_eq!(lines[5], "  [stderr] dataset already exists");
+        assert!(lines[6].starts_with("  exit code 1 after "), "{text}");
+        assert_eq!(lines[7], "INFO passphrase is <redacted>");
+    }
+}
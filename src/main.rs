@@ -2,11 +2,26 @@
 //!
 //! CLI and TUI installer for ZFSBootMenu with RAID support.
 
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
+use zbm_installer::config::{confirms, parse_size};
+use zbm_installer::installer::{cancel_on_signals, CancelToken, STATE_PATH};
+use zbm_installer::log_sink::Tee;
 use zbm_installer::*;
 
+/// Exit code when the installation fails
+const EXIT_FAILURE: i32 = 1;
+
+/// Exit code of `validate --strict` when checks only warned
+const EXIT_WARNINGS: i32 = 2;
+
+/// Exit code when the user cancels the installation
+const EXIT_CANCELLED: i32 = 3;
+
 /// ZFSBootMenu Installer
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -22,13 +37,74 @@ EXAMPLES:
     zbm-installer --mode new --drives /dev/sda,/dev/sdb,/dev/sdc \\
         --raid raidz1 --pool-name mytank --compression lz4
 
+    # Mirror bootable from both UEFI and legacy BIOS firmware
+    zbm-installer --mode new --drives /dev/sda,/dev/sdb --raid mirror --boot-mode hybrid
+
+    # Encrypted pool, passphrase entered at the prompt
+    zbm-installer --mode new --drives /dev/sda --encrypt
+
+    # Install from a saved configuration, overriding the drives
+    zbm-installer --config install.toml --drives /dev/sda,/dev/sdb
+
+    # Unattended install that never prompts, e.g. when provisioning machines
+    zbm-installer --answer-file machine.toml
+
     # Dry run (recommended for testing)
     zbm-installer --mode new --drives /dev/sda,/dev/sdb --raid mirror --dry-run
 
     # Interactive TUI mode
     zbm-installer --tui
+
+    # Interactive setup with plain prompts, e.g. over a serial console
+    zbm-installer --interactive
+
+    # Check a configuration would install cleanly, without touching any disk
+    zbm-installer validate --config install.toml --strict
+
+    # Remove a previous installation's boot entries, ZFSBootMenu files and pool
+    zbm-installer destroy --pool zroot
+
+    # List the disks the installer can see
+    zbm-installer list-devices
+
+    # Install without network access, from ZFSBootMenu fetched on a connected machine
+    zbm-installer fetch-artifacts --dest /media/usb/artifacts
+    zbm-installer --mode new --drives /dev/sda --offline --artifact-dir /media/usb/artifacts
 ")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Subcommands>,
+
+    /// Flags given without a subcommand install, like `install`
+    #[command(flatten)]
+    install: InstallArgs,
+
+    /// Verbose output
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Install log with every command's output [default: /var/log/zbm-installer/<time>.log]
+    #[arg(short, long, value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Interface language (e.g. en, de); defaults to LC_ALL/LC_MESSAGES/LANG
+    #[arg(long, global = true)]
+    lang: Option<String>,
+}
+
+/// Installation settings
+#[derive(clap::Args, Debug)]
+struct InstallArgs {
+    /// Load settings from a TOML file; flags given on the command line override it
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Install unattended from a TOML file: like --config, but never prompts
+    /// and fails instead of asking for confirmation (set force = true in it)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["config", "tui"])]
+    answer_file: Option<PathBuf>,
+
     /// Installation mode: new or existing
     #[arg(short, long, value_enum)]
     mode: Option<InstallModeArg>,
@@ -37,10 +113,45 @@ struct Args {
     #[arg(short, long, value_delimiter = ',')]
     drives: Vec<PathBuf>,
 
+    /// Top-level vdev as TYPE:DEVICES (e.g. mirror:/dev/sda,/dev/sdb); repeat to stripe
+    /// several, instead of --drives and --raid
+    #[arg(long = "vdev", value_name = "TYPE:DEVICES")]
+    vdevs: Vec<VdevSpec>,
+
+    /// Comma-separated separate intent log (SLOG) devices, used whole
+    #[arg(long, value_delimiter = ',', value_name = "DEVICES")]
+    log: Vec<PathBuf>,
+
+    /// Mirror the log devices instead of striping them
+    #[arg(long)]
+    mirror_log: bool,
+
+    /// Comma-separated L2ARC cache devices, used whole
+    #[arg(long, value_delimiter = ',', value_name = "DEVICES")]
+    cache: Vec<PathBuf>,
+
+    /// Comma-separated hot spare devices, used whole
+    #[arg(long, value_delimiter = ',', value_name = "DEVICES")]
+    spare: Vec<PathBuf>,
+
     /// ZFS pool name
     #[arg(short, long, default_value = "zroot")]
     pool_name: String,
 
+    /// Limit the pool to these compatibility feature sets, e.g. openzfs-2.1-linux
+    /// (defaults to grub2 with --bootloader grub)
+    #[arg(long, value_name = "NAME")]
+    compatibility: Option<String>,
+
+    /// Install onto the existing pool named by --pool-name instead of creating one;
+    /// --drives then name the disks holding its ESPs, which are left as they are
+    #[arg(long)]
+    existing_pool: bool,
+
+    /// Keep layout datasets that already exist in the existing pool
+    #[arg(long)]
+    adopt_datasets: bool,
+
     /// RAID level
     #[arg(short, long, value_enum, default_value = "none")]
     raid: RaidLevelArg,
@@ -49,14 +160,55 @@ struct Args {
     #[arg(short, long, default_value = "1G")]
     efi_size: String,
 
-    /// Swap partition size (0 to disable, e.g., 8G, 16G)
+    /// Swap size (0 to disable, e.g., 8G, 16G); per disk for swap partitions
     #[arg(short, long, default_value = "8G")]
     swap_size: String,
 
+    /// Where to put swap: a partition on every disk, a zvol on the pool, or none
+    #[arg(long, value_enum, default_value = "partition")]
+    swap_mode: SwapModeArg,
+
+    /// Encrypt the swap partitions with a new random key at every boot (no hibernation)
+    #[arg(long)]
+    encrypt_swap: bool,
+
+    /// Largest the ARC of the installed system may grow (e.g., 4G); defaults to half the RAM
+    #[arg(long)]
+    arc_max: Option<String>,
+
+    /// How to wipe the disks: remove signatures, discard all blocks (SSD/NVMe), or zero the ZFS labels
+    #[arg(long, value_enum, default_value = "signatures")]
+    wipe_mode: WipeModeArg,
+
+    /// Keep the disks' partitions and install into the largest free region
+    #[arg(long)]
+    use_free_space: bool,
+
+    /// Use each disk's EFI system partition instead of adding one (implies --use-free-space)
+    #[arg(long)]
+    reuse_esp: bool,
+
+    /// Seconds to wait for new partitions to show up in /dev (default: 30)
+    #[arg(long, value_name = "SECS")]
+    device_timeout: Option<u64>,
+
+    /// Times to retry a disk command that fails because the device is busy (default: 3)
+    #[arg(long, value_name = "N")]
+    device_retries: Option<u32>,
+
+    /// Disks to wipe, partition and format at once (default: one per disk, at most 4)
+    #[arg(long, value_name = "N")]
+    jobs: Option<usize>,
+
     /// ZFS ashift value (9-16, auto-detect if not specified)
     #[arg(short, long)]
     ashift: Option<u8>,
 
+    /// Trim freed blocks automatically: on or off (auto-detect if not specified:
+    /// on only when every drive is an SSD)
+    #[arg(long, value_name = "on|off", value_parser = clap::builder::BoolishValueParser::new())]
+    autotrim: Option<bool>,
+
     /// ZFS compression algorithm
     #[arg(short, long, value_enum, default_value = "zstd")]
     compression: CompressionArg,
@@ -65,6 +217,50 @@ struct Args {
     #[arg(short = 'H', long)]
     hostname: Option<String>,
 
+    /// Timezone of the installed system, e.g. Europe/Berlin
+    #[arg(long, value_name = "ZONE")]
+    timezone: Option<String>,
+
+    /// Locale of the installed system, e.g. en_US.UTF-8
+    #[arg(long)]
+    locale: Option<String>,
+
+    /// Console keymap of the installed system, e.g. us
+    #[arg(long)]
+    keymap: Option<String>,
+
+    /// Create an admin account in the installed system and ask for its password (repeatable)
+    #[arg(long = "user", value_name = "NAME")]
+    users: Vec<String>,
+
+    /// Ask for a new root password for the installed system
+    #[arg(long)]
+    root_password: bool,
+
+    /// Lock the root account of the installed system (needs --user)
+    #[arg(long)]
+    lock_root: bool,
+
+    /// Boot environment name under ROOT (default: "default", or <distro>-<date> with --hostname)
+    #[arg(long)]
+    boot_environment: Option<String>,
+
+    /// Kernel command line ZFSBootMenu boots the environment with
+    #[arg(long, value_name = "ARGS")]
+    cmdline: Option<String>,
+
+    /// Snapshot taken after install, with strftime placeholders like install-%Y%m%d ("none" to skip)
+    #[arg(long, value_name = "NAME")]
+    initial_snapshot: Option<String>,
+
+    /// Snapshot every dataset in the pool, not just the boot environment
+    #[arg(long)]
+    recursive_snapshot: bool,
+
+    /// Re-import the pool under /mnt after installing, to chroot into the new system
+    #[arg(long)]
+    keep_mounted: bool,
+
     /// Source root for existing mode
     #[arg(long, default_value = "/")]
     source_root: PathBuf,
@@ -77,25 +273,228 @@ struct Args {
     #[arg(long)]
     no_copy_home: bool,
 
+    /// Keep the network interface names in existing mode, pinned to their MAC addresses
+    #[arg(long)]
+    pin_interface_names: bool,
+
+    /// Install a minimal system in new mode as METHOD[:RELEASE] (e.g. debootstrap:bookworm,
+    /// dnf:40, pacstrap)
+    #[arg(long, value_name = "METHOD[:RELEASE]")]
+    bootstrap: Option<BootstrapSpec>,
+
+    /// Package mirror for --bootstrap debootstrap
+    #[arg(long, value_name = "URL")]
+    bootstrap_mirror: Option<String>,
+
+    /// Extra package for --bootstrap, e.g. a kernel or ZFS (can be used multiple times)
+    #[arg(long = "bootstrap-package", value_name = "PACKAGE")]
+    bootstrap_packages: Vec<String>,
+
     /// Dry run - show what would be done without making changes
     #[arg(short = 'n', long)]
     dry_run: bool,
 
+    /// Write the dry-run plan to this file as JSON and exit (implies --dry-run)
+    #[arg(long, value_name = "FILE")]
+    plan_json: Option<PathBuf>,
+
+    /// Run the pre-flight checks, write their results to this file as JSON and exit
+    #[arg(long, value_name = "FILE")]
+    preflight_json: Option<PathBuf>,
+
     /// Force mode - skip confirmations
     #[arg(short, long)]
     force: bool,
 
-    /// Verbose output
-    #[arg(short, long)]
-    verbose: bool,
+    /// Accept removable devices (USB sticks, SD cards) without --force
+    #[arg(long)]
+    allow_removable: bool,
+
+    /// Progress output: text log, or json for one event object per line on stdout
+    #[arg(long, value_enum, default_value = "text", conflicts_with = "tui")]
+    log_format: LogFormatArg,
+
+    /// With --log-format json, write the events to this file or named pipe instead
+    #[arg(long, value_name = "PATH")]
+    event_output: Option<PathBuf>,
 
     /// Skip pre-flight system checks
     #[arg(short = 'S', long)]
     skip_preflight: bool,
 
+    /// Keep the partial state of a failed installation instead of rolling it back
+    #[arg(long)]
+    no_rollback: bool,
+
+    /// Continue a failed installation from its first incomplete phase
+    #[arg(long, conflicts_with_all = ["tui", "dry_run", "plan_json", "preflight_json"])]
+    resume: bool,
+
+    /// Execute configured hook scripts even in dry-run mode
+    #[arg(long)]
+    run_hooks_in_dry_run: bool,
+
+    /// Encrypt the pool with native ZFS encryption (prompts for a passphrase)
+    #[arg(long)]
+    encrypt: bool,
+
+    /// Read the encryption key from this file instead of prompting (implies --encrypt)
+    #[arg(long, value_name = "PATH")]
+    keyfile: Option<PathBuf>,
+
+    /// Firmware to install for: uefi, bios (syslinux), or auto to match the running system
+    #[arg(long, value_enum, default_value = "uefi")]
+    firmware: FirmwareArg,
+
+    /// Boot paths to install: uefi, or hybrid (UEFI plus legacy BIOS)
+    #[arg(long, value_enum, default_value = "uefi")]
+    boot_mode: BootModeArg,
+
+    /// Bootloader the UEFI boot entries start
+    #[arg(long, value_enum, default_value = "systemd-boot")]
+    bootloader: BootloaderArg,
+
+    /// ZFSBootMenu release to install (e.g. 2.3.0); defaults to the latest
+    #[arg(long, value_name = "VERSION")]
+    zbm_version: Option<String>,
+
+    /// Install this ZFSBootMenu EFI image instead of downloading one
+    #[arg(long, value_name = "PATH", conflicts_with = "zbm_version")]
+    zbm_image: Option<PathBuf>,
+
+    /// Build ZFSBootMenu with the host's generate-zbm instead of downloading a release
+    #[arg(long, conflicts_with_all = ["zbm_version", "zbm_image"])]
+    zbm_local: bool,
+
+    /// Install the latest release if a local build finds no generate-zbm
+    #[arg(long)]
+    zbm_release_fallback: bool,
+
+    /// Install without network access, taking ZFSBootMenu from --artifact-dir
+    #[arg(long)]
+    offline: bool,
+
+    /// Directory filled by fetch-artifacts to take the ZFSBootMenu release from
+    #[arg(long, value_name = "DIR")]
+    artifact_dir: Option<PathBuf>,
+
+    /// Local package repository to install packages from (apt, dnf or pacman)
+    #[arg(long, value_name = "DIR")]
+    pkg_dir: Option<PathBuf>,
+
+    /// Also install ZFSBootMenu as the fallback loader EFI/BOOT/BOOTX64.EFI
+    #[arg(long)]
+    install_fallback: bool,
+
+    /// Finish the installation even if the bootloader fails to install
+    #[arg(long)]
+    ignore_bootloader_errors: bool,
+
+    /// Sign for Secure Boot: off, shim (enrolls the key as a MOK), or sign-local
+    #[arg(long, value_enum, default_value = "off")]
+    secure_boot: SecureBootArg,
+
+    /// Private key (PEM) signing the EFI images for Secure Boot
+    #[arg(long, value_name = "PATH")]
+    secure_boot_key: Option<PathBuf>,
+
+    /// Certificate (PEM) of the Secure Boot signing key
+    #[arg(long, value_name = "PATH")]
+    secure_boot_cert: Option<PathBuf>,
+
+    /// Mount a tmpfs on /tmp in the installed system
+    #[arg(long)]
+    tmpfs_tmp: bool,
+
+    /// Pool cachefile to create and copy into the target ("none" to skip)
+    #[arg(long, value_name = "PATH")]
+    cachefile: Option<PathBuf>,
+
     /// Launch interactive TUI
     #[arg(short, long)]
     tui: bool,
+
+    /// Ask for the settings with plain prompts on stdin/stdout, for terminals the TUI can't use
+    #[arg(
+        long,
+        conflicts_with_all = ["tui", "answer_file", "resume", "plan_json", "preflight_json"]
+    )]
+    interactive: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Subcommands {
+    /// Install ZFSBootMenu (the default when no subcommand is given)
+    Install(Box<InstallArgs>),
+    /// List the disks the installer can see
+    ListDevices {
+        /// Show everything known about each disk and its partitions
+        #[arg(long)]
+        details: bool,
+    },
+    /// Run every pre-flight check for a configuration file and report the results
+    Validate(ValidateArgs),
+    /// Undo an installation: remove its boot entries, its files on the ESPs and its pool
+    Destroy(DestroyArgs),
+    /// Download what an offline install needs into a directory, on a connected machine
+    FetchArtifacts(FetchArtifactsArgs),
+    /// Print a shell completion script
+    #[command(hide = true)]
+    Completions {
+        /// Shell to complete in
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page in roff format
+    GenerateManpage,
+}
+
+#[derive(clap::Args, Debug)]
+struct ValidateArgs {
+    /// Configuration to check
+    #[arg(long, value_name = "FILE")]
+    config: PathBuf,
+
+    /// Exit with status 2 when checks warn but none fails
+    #[arg(long)]
+    strict: bool,
+
+    /// Print the results as JSON instead of one line per check
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct FetchArtifactsArgs {
+    /// Directory to fill, given to the offline install as --artifact-dir
+    #[arg(long, value_name = "DIR")]
+    dest: PathBuf,
+
+    /// ZFSBootMenu release to fetch (e.g. 2.3.0); defaults to the latest
+    #[arg(long, value_name = "VERSION")]
+    zbm_version: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct DestroyArgs {
+    /// Pool to remove
+    #[arg(long, default_value = "zroot")]
+    pool: String,
+
+    /// Export the pool instead of destroying it, keeping its data
+    #[arg(long)]
+    keep_pool: bool,
+
+    /// Also remove the ZFS signatures from the pool's partitions
+    #[arg(long, conflicts_with = "keep_pool")]
+    wipe: bool,
+
+    /// List what would be removed without removing it
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Skip typing the pool name to confirm
+    #[arg(short, long)]
+    force: bool,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -134,6 +533,112 @@ impl From<RaidLevelArg> for RaidLevel {
     }
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FirmwareArg {
+    Uefi,
+    Bios,
+    Auto,
+}
+
+impl From<FirmwareArg> for FirmwareMode {
+    fn from(mode: FirmwareArg) -> Self {
+        match mode {
+            FirmwareArg::Uefi => FirmwareMode::Uefi,
+            FirmwareArg::Bios => FirmwareMode::Bios,
+            FirmwareArg::Auto => FirmwareMode::Auto,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BootModeArg {
+    Uefi,
+    Hybrid,
+}
+
+impl From<BootModeArg> for BootMode {
+    fn from(mode: BootModeArg) -> Self {
+        match mode {
+            BootModeArg::Uefi => BootMode::Uefi,
+            BootModeArg::Hybrid => BootMode::Hybrid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BootloaderArg {
+    SystemdBoot,
+    Grub,
+    EfiStub,
+}
+
+impl From<BootloaderArg> for Bootloader {
+    fn from(bootloader: BootloaderArg) -> Self {
+        match bootloader {
+            BootloaderArg::SystemdBoot => Bootloader::SystemdBoot,
+            BootloaderArg::Grub => Bootloader::Grub,
+            BootloaderArg::EfiStub => Bootloader::EfiStub,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SecureBootArg {
+    Off,
+    Shim,
+    SignLocal,
+}
+
+impl From<SecureBootArg> for SecureBootMode {
+    fn from(mode: SecureBootArg) -> Self {
+        match mode {
+            SecureBootArg::Off => SecureBootMode::Off,
+            SecureBootArg::Shim => SecureBootMode::Shim,
+            SecureBootArg::SignLocal => SecureBootMode::SignLocal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormatArg {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SwapModeArg {
+    Partition,
+    Zvol,
+    None,
+}
+
+impl From<SwapModeArg> for SwapMode {
+    fn from(mode: SwapModeArg) -> Self {
+        match mode {
+            SwapModeArg::Partition => SwapMode::Partition,
+            SwapModeArg::Zvol => SwapMode::Zvol,
+            SwapModeArg::None => SwapMode::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum WipeModeArg {
+    Signatures,
+    Discard,
+    ZeroLabels,
+}
+
+impl From<WipeModeArg> for WipeMode {
+    fn from(mode: WipeModeArg) -> Self {
+        match mode {
+            WipeModeArg::Signatures => WipeMode::Signatures,
+            WipeModeArg::Discard => WipeMode::Discard,
+            WipeModeArg::ZeroLabels => WipeMode::ZeroLabels,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum CompressionArg {
     Off,
@@ -155,145 +660,1198 @@ impl From<CompressionArg> for Compression {
     }
 }
 
-fn parse_size(size_str: &str) -> Result<bytesize::ByteSize> {
-    size_str
-        .parse()
-        .map_err(|e| InstallerError::ParseError(format!("Invalid size '{}': {}", size_str, e)))
+/// Map the installer result to a process exit code
+fn exit_code(result: &Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) if e.is_cancelled() => EXIT_CANCELLED,
+        Err(_) => EXIT_FAILURE,
+    }
+}
+
+/// Map pre-flight results to a process exit code
+///
+/// Warnings only fail the run with `strict`.
+fn validation_exit_code(result: &ValidationResult, strict: bool) -> i32 {
+    if !result.is_ok() {
+        EXIT_FAILURE
+    } else if strict && result.warnings().next().is_some() {
+        EXIT_WARNINGS
+    } else {
+        0
+    }
 }
 
 fn main() {
-    // Parse arguments
-    let args = Args::parse();
+    // Parse arguments, keeping the matches to tell explicit flags from defaults
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Bare flags install, as they did before there were subcommands
+    let command = args
+        .command
+        .unwrap_or(Subcommands::Install(Box::new(args.install)));
+    let install_matches = matches.subcommand_matches("install").unwrap_or(&matches);
+
+    // Completions and the man page only print, before anything is set up
+    match &command {
+        Subcommands::Completions { shell } => {
+            let mut cmd = Args::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(*shell, &mut cmd, name, &mut std::io::stdout());
+            return;
+        }
+        Subcommands::GenerateManpage => {
+            // Section 8: administration commands
+            let result = clap_mangen::Man::new(Args::command())
+                .section("8")
+                .render(&mut std::io::stdout())
+                .map_err(InstallerError::from);
+            process::exit(exit_code(&result));
+        }
+        _ => {}
+    }
 
     // Initialize logging
     let log_level = if args.verbose { "debug" } else { "info" };
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
+    // Everything logged also goes to the install log, if it can be created;
+    // checking and listing change nothing, so they don't keep one
+    let read_only = matches!(
+        command,
+        Subcommands::Validate(_) | Subcommands::ListDevices { .. } | Subcommands::FetchArtifacts(_)
+    );
+    let log_path = args.log_file.clone().unwrap_or_else(LogSink::default_path);
+    let log_sink = (!read_only).then(|| LogSink::create(&log_path));
+
+    // The TUI owns the terminal, so its log goes to a buffer it can show instead
+    let log_tail = ui::LogTail::default();
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level));
+    let terminal: Option<Box<dyn Write + Send>> = match &command {
+        Subcommands::Install(install) if install.tui => Some(Box::new(log_tail.clone())),
+        _ => None,
+    };
+    match (&log_sink, terminal) {
+        (Some(Ok(sink)), terminal) => {
+            let terminal = terminal.unwrap_or_else(|| Box::new(std::io::stderr()));
+            logger.target(env_logger::Target::Pipe(Box::new(Tee::new(
+                terminal,
+                sink.clone(),
+            ))));
+        }
+        (_, Some(terminal)) => {
+            logger.target(env_logger::Target::Pipe(terminal));
+        }
+        (_, None) => {}
+    }
+    logger.init();
+
+    // Select the interface language
+    let language = match args.lang.as_deref() {
+        Some(code) => i18n::Language::from_code(code).unwrap_or_else(|| {
+            log::warn!("Unsupported language '{}', using English", code);
+            i18n::Language::En
+        }),
+        None => i18n::Language::from_env(),
+    };
+    i18n::set_language(language);
+
+    // Checking needs no root: not being root is reported like any other failure
+    match &command {
+        Subcommands::Validate(validate) => process::exit(run_validate(validate)),
+        Subcommands::ListDevices { details } => process::exit(exit_code(&list_devices(*details))),
+        Subcommands::FetchArtifacts(fetch) => process::exit(exit_code(&fetch_artifacts(fetch))),
+        _ => {}
+    }
 
     // Check root privileges
     if !system::is_root() {
-        eprintln!("Error: This program must be run as root");
-        eprintln!("Please run: sudo {}", std::env::args().next().unwrap());
+        eprintln!("{}", i18n::tr("cli.not_root"));
+        eprintln!(
+            "{}",
+            i18n::tr_args(
+                "cli.run_with_sudo",
+                &[("program", &std::env::args().next().unwrap())]
+            )
+        );
         process::exit(1);
     }
+    let log_sink = log_sink.and_then(|sink| {
+        sink.inspect_err(|e| log::warn!("Continuing without an install log: {}", e))
+            .ok()
+    });
 
     // Run installer
-    let result = if args.tui {
-        run_tui(args)
-    } else {
-        run_cli(args)
+    let destroying = matches!(command, Subcommands::Destroy(_));
+    let result = match command {
+        Subcommands::Destroy(destroy) => run_destroy(destroy, log_sink.clone()),
+        Subcommands::Install(install) if install.tui => {
+            run_tui(*install, install_matches, log_tail, log_sink.clone())
+        }
+        Subcommands::Install(install) if install.interactive => {
+            run_interactive(*install, install_matches, log_sink.clone())
+        }
+        Subcommands::Install(install) => run_cli(*install, install_matches, log_sink.clone()),
+        Subcommands::ListDevices { .. }
+        | Subcommands::Validate(_)
+        | Subcommands::FetchArtifacts(_)
+        | Subcommands::Completions { .. }
+        | Subcommands::GenerateManpage => unreachable!("handled before installing"),
     };
 
     // Handle result
-    match result {
-        Ok(()) => {
-            log::info!("Installation completed successfully!");
-            process::exit(0);
+    match &result {
+        Ok(()) if destroying => log::info!("Removal completed successfully!"),
+        Ok(()) => log::info!("Installation completed successfully!"),
+        Err(e) if e.is_cancelled() => println!("{}", i18n::tr("cli.cancelled")),
+        Err(e) if destroying => log::error!("Removal failed: {}", e),
+        Err(e) => log::error!("Installation failed: {}", e),
+    }
+    if let Some(sink) = &log_sink {
+        eprintln!(
+            "{}",
+            i18n::tr_args("cli.log_path", &[("path", &sink.path().display())])
+        );
+    }
+
+    process::exit(exit_code(&result));
+}
+
+/// Build the configuration from `--config` (if given) and the command-line flags
+///
+/// Without a config file every flag applies, defaults included. With one, only
+/// flags given explicitly on the command line override values from the file.
+fn build_config(args: InstallArgs, matches: &ArgMatches) -> Result<Config> {
+    let file = args.config.as_ref().or(args.answer_file.as_ref());
+    let from_file = file.is_some();
+    let mut config = match file {
+        Some(path) => Config::from_file(path)?,
+        None => Config::new(),
+    };
+    config.unattended |= args.answer_file.is_some();
+    let given = |id: &str| !from_file || matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    if let Some(mode) = args.mode {
+        config.mode = mode.into();
+    }
+    if !args.drives.is_empty() {
+        config.devices = args.drives;
+    }
+    if !args.vdevs.is_empty() {
+        config.vdevs = args.vdevs;
+    }
+    if !args.log.is_empty() {
+        config.log_devices = args.log;
+    }
+    if args.mirror_log {
+        config.mirror_log = true;
+    }
+    if !args.cache.is_empty() {
+        config.cache_devices = args.cache;
+    }
+    if !args.spare.is_empty() {
+        config.spare_devices = args.spare;
+    }
+    if given("pool_name") {
+        config.pool_name = args.pool_name;
+    }
+    if args.compatibility.is_some() {
+        config.pool_compatibility = args.compatibility;
+    }
+    if args.existing_pool {
+        config.use_existing_pool = true;
+    }
+    if args.adopt_datasets {
+        config.adopt_datasets = true;
+    }
+    if given("raid") {
+        config.raid_level = args.raid.into();
+    }
+    if given("efi_size") {
+        config.efi_size = parse_size(&args.efi_size)?;
+    }
+    if given("swap_size") {
+        config.swap_size = parse_size(&args.swap_size)?;
+    }
+    if given("swap_mode") {
+        config.swap_mode = args.swap_mode.into();
+    }
+    if args.encrypt_swap {
+        config.encrypt_swap = true;
+    }
+    if let Some(ref arc_max) = args.arc_max {
+        config.arc_max = Some(parse_size(arc_max)?);
+    }
+    if given("wipe_mode") {
+        config.wipe_mode = args.wipe_mode.into();
+    }
+    if args.use_free_space || args.reuse_esp {
+        config.partitioning = PartitioningMode::UseFreeSpace {
+            reuse_esp: args.reuse_esp,
+        };
+    }
+    if let Some(timeout) = args.device_timeout {
+        config.device_timeout = timeout;
+    }
+    if let Some(retries) = args.device_retries {
+        config.device_retries = retries;
+    }
+    if args.jobs.is_some() {
+        config.jobs = args.jobs;
+    }
+    if args.ashift.is_some() {
+        config.ashift = args.ashift;
+    }
+    if args.autotrim.is_some() {
+        config.autotrim = args.autotrim;
+    }
+    if given("compression") {
+        config.compression = args.compression.into();
+    }
+    if given("firmware") {
+        config.firmware = args.firmware.into();
+    }
+    if given("boot_mode") {
+        config.boot_mode = args.boot_mode.into();
+    }
+    if given("bootloader") {
+        config.bootloader = args.bootloader.into();
+    }
+    if let Some(version) = args.zbm_version {
+        config.zbm_source = ZbmSource::Release {
+            version: Some(version),
+        };
+    }
+    if let Some(path) = args.zbm_image {
+        config.zbm_source = ZbmSource::CustomImage { path };
+    }
+    if args.zbm_local {
+        config.zbm_source = ZbmSource::Local;
+    }
+    if args.zbm_release_fallback {
+        config.zbm_release_fallback = true;
+    }
+    config.offline |= args.offline;
+    if args.artifact_dir.is_some() {
+        config.artifact_dir = args.artifact_dir;
+    }
+    if args.pkg_dir.is_some() {
+        config.pkg_dir = args.pkg_dir;
+    }
+    if args.install_fallback {
+        config.install_fallback = true;
+    }
+    if args.ignore_bootloader_errors {
+        config.ignore_bootloader_errors = true;
+    }
+    if given("secure_boot") {
+        config.secure_boot = args.secure_boot.into();
+    }
+    if args.secure_boot_key.is_some() {
+        config.secure_boot_key = args.secure_boot_key;
+    }
+    if args.secure_boot_cert.is_some() {
+        config.secure_boot_cert = args.secure_boot_cert;
+    }
+    if args.tmpfs_tmp {
+        config.tmpfs_tmp = true;
+    }
+    if let Some(cachefile) = args.cachefile {
+        config.cachefile = (cachefile != Path::new("none")).then_some(cachefile);
+    }
+    if args.hostname.is_some() {
+        config.hostname = args.hostname;
+    }
+    if args.timezone.is_some() {
+        config.timezone = args.timezone;
+    }
+    if args.locale.is_some() {
+        config.locale = args.locale;
+    }
+    if args.keymap.is_some() {
+        config.keymap = args.keymap;
+    }
+    for name in args.users {
+        if !config.users.iter().any(|user| user.name == name) {
+            config.users.push(UserSpec {
+                admin: true,
+                ..UserSpec::new(name)
+            });
         }
-        Err(e) => {
-            log::error!("Installation failed: {}", e);
-            process::exit(1);
+    }
+    config.lock_root |= args.lock_root;
+    if given("source_root") {
+        config.source_root = args.source_root;
+    }
+    if let Some(name) = args.boot_environment {
+        config.boot_environment = name;
+    } else {
+        let distro =
+            system::Distro::detect_in(&config.source_root).unwrap_or(system::Distro::Unknown);
+        let today = chrono::Local::now().date_naive();
+        let state_file = args.resume.then_some(Path::new(STATE_PATH));
+        if let Some(name) = default_boot_environment(&config, distro, today, state_file) {
+            log::info!("Naming the boot environment ROOT/{}", name);
+            config.boot_environment = name;
+        }
+    }
+    if let Some(cmdline) = args.cmdline {
+        config.kernel_cmdline = cmdline;
+    }
+    if let Some(snapshot) = args.initial_snapshot {
+        config.initial_snapshot = (snapshot != "none").then_some(snapshot);
+    }
+    if args.recursive_snapshot {
+        config.recursive_snapshot = true;
+    }
+    if args.keep_mounted {
+        config.keep_mounted = true;
+    }
+    if !args.exclude.is_empty() {
+        config.exclude_paths = args.exclude;
+    }
+    if args.no_copy_home {
+        config.copy_home = false;
+    }
+    config.pin_interface_names |= args.pin_interface_names;
+    if let Some(spec) = args.bootstrap {
+        config.bootstrap = Some(spec);
+    }
+    if args.bootstrap_mirror.is_some() || !args.bootstrap_packages.is_empty() {
+        let Some(spec) = config.bootstrap.as_mut() else {
+            return Err(InstallerError::config(
+                "--bootstrap-mirror and --bootstrap-package need --bootstrap",
+            ));
+        };
+        if args.bootstrap_mirror.is_some() {
+            spec.mirror = args.bootstrap_mirror;
+        }
+        spec.extra_packages.extend(args.bootstrap_packages);
+    }
+    if args.encrypt || args.keyfile.is_some() {
+        let encryption = config
+            .encryption
+            .get_or_insert_with(EncryptionConfig::default);
+        if let Some(keyfile) = args.keyfile {
+            encryption.keylocation = Some(format!("file://{}", keyfile.display()));
+        }
+    }
+
+    // Switches can only turn behaviour on
+    config.dry_run |= args.dry_run;
+    config.force |= args.force;
+    config.allow_removable |= args.allow_removable;
+    config.skip_preflight |= args.skip_preflight;
+    if args.no_rollback {
+        config.rollback = false;
+    }
+    config.run_hooks_in_dry_run |= args.run_hooks_in_dry_run;
+
+    Ok(config)
+}
+
+/// Name for the boot environment when none is configured
+///
+/// A resumed run keeps the name the interrupted run saved in `state_file`,
+/// which may have been chosen on an earlier day.
+fn default_boot_environment(
+    config: &Config,
+    distro: system::Distro,
+    today: chrono::NaiveDate,
+    state_file: Option<&Path>,
+) -> Option<String> {
+    let suggested = config.suggested_boot_environment(distro, today)?;
+    let saved = state_file
+        .and_then(|path| installer::InstallState::load(path).ok())
+        .and_then(|state| state.boot_environment);
+    Some(saved.unwrap_or(suggested))
+}
+
+/// Ask for the encryption passphrase twice, without echo, if one is needed
+///
+/// Unattended installs fail instead.
+fn prompt_for_passphrase(config: &mut Config) -> Result<()> {
+    let unattended = config.unattended;
+    let Some(encryption) = config.encryption.as_mut() else {
+        return Ok(());
+    };
+    if !encryption.uses_prompt() || encryption.passphrase.is_some() {
+        return Ok(());
+    }
+    if unattended {
+        return Err(InstallerError::config(
+            "An unattended install can't prompt for the encryption passphrase; \
+             give a key file with keylocation",
+        ));
+    }
+
+    let first = system::read_secret(i18n::tr("cli.passphrase.prompt"))?;
+    let second = system::read_secret(i18n::tr("cli.passphrase.confirm"))?;
+    encryption.passphrase = Some(Passphrase::confirmed(&first, &second)?);
+
+    Ok(())
+}
+
+/// Ask for the passwords of users that have none, and for root's with
+/// `--root-password`, twice each without echo
+///
+/// Unattended installs fail instead.
+fn prompt_for_passwords(config: &mut Config, root_password: bool) -> Result<()> {
+    let unattended = config.unattended;
+    let mut names: Vec<String> = config
+        .users
+        .iter()
+        .filter(|user| user.password_hash.is_none())
+        .map(|user| user.name.clone())
+        .collect();
+    if root_password && config.root_password_hash.is_none() {
+        names.push("root".to_string());
+    }
+    if names.is_empty() {
+        return Ok(());
+    }
+    if unattended {
+        return Err(InstallerError::config(format!(
+            "An unattended install can't prompt for the password of {}; \
+             give a password_hash",
+            names[0]
+        )));
+    }
+
+    for name in names {
+        let first = system::read_secret(&i18n::tr_args("cli.password.prompt", &[("name", &name)]))?;
+        let second = system::read_secret(i18n::tr("cli.password.confirm"))?;
+        let hash = system::users::hash_password(&Passphrase::confirmed(&first, &second)?)?;
+        match config.users.iter_mut().find(|user| user.name == name) {
+            Some(user) => user.password_hash = Some(hash),
+            None => config.root_password_hash = Some(hash),
         }
     }
+
+    Ok(())
 }
 
-fn run_cli(args: Args) -> Result<()> {
+fn run_cli(args: InstallArgs, matches: &ArgMatches, log_sink: Option<LogSink>) -> Result<()> {
     log::info!("ZFSBootMenu Installer - CLI Mode");
 
     // Validate required arguments
-    if args.mode.is_none() {
+    if args.mode.is_none() && args.config.is_none() && args.answer_file.is_none() {
         return Err(InstallerError::config(
             "Installation mode is required. Use --mode new or --mode existing",
         ));
     }
 
-    if args.drives.is_empty() {
+    // Build configuration
+    let plan_json = args.plan_json.clone();
+    let resume = args.resume;
+    let preflight_json = args.preflight_json.clone();
+    let events = event_sink(&args)?;
+    let root_password = args.root_password;
+    let mut config = build_config(args, matches)?;
+    config.dry_run |= plan_json.is_some();
+    prompt_for_passphrase(&mut config)?;
+    prompt_for_passwords(&mut config, root_password)?;
+
+    if config.data_devices().is_empty() {
         return Err(InstallerError::config(
-            "At least one drive must be specified with --drives",
+            "At least one drive must be specified with --drives or --vdev",
         ));
     }
 
-    // Build configuration
-    let mut config = Config::new();
-    config.mode = args.mode.unwrap().into();
-    config.devices = args.drives;
-    config.pool_name = args.pool_name;
-    config.raid_level = args.raid.into();
-    config.efi_size = parse_size(&args.efi_size)?;
-    config.swap_size = parse_size(&args.swap_size)?;
-    config.ashift = args.ashift;
-    config.compression = args.compression.into();
-    config.hostname = args.hostname;
-    config.dry_run = args.dry_run;
-    config.force = args.force;
-    config.source_root = args.source_root;
-    config.exclude_paths = args.exclude;
-    config.copy_home = !args.no_copy_home;
-    config.skip_preflight = args.skip_preflight;
-
-    // Display configuration
+    show_config(&config);
+
+    if let Some(path) = preflight_json {
+        let result = Installer::for_validation(config).validate_only()?;
+        std::fs::write(&path, result.to_json()?)?;
+        log::info!("Pre-flight results written to {}", path.display());
+        if !result.is_ok() {
+            return Err(InstallerError::validation("Pre-flight checks failed"));
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = plan_json {
+        let plan = Installer::new(config)?.plan()?;
+        std::fs::write(&path, plan.to_json()?)?;
+        log::info!("Install plan written to {}", path.display());
+        return Ok(());
+    }
+
+    confirm_install(&mut config)?;
+
+    run_installer(config, resume, events, log_sink)
+}
+
+/// Ask for the settings on the terminal with plain prompts, then install
+///
+/// Refuses without a terminal rather than waiting for answers.
+fn run_interactive(
+    args: InstallArgs,
+    matches: &ArgMatches,
+    log_sink: Option<LogSink>,
+) -> Result<()> {
+    let mut prompter = ui::prompt::Prompter::stdio()?;
+    log::info!("ZFSBootMenu Installer - Interactive Mode");
+
+    // Flags and --config give the starting values
+    let events = event_sink(&args)?;
+    let root_password = args.root_password;
+    let config = build_config(args, matches)?;
+    let devices = DeviceDiscovery::new()?.scan_devices()?;
+    let mut config = ui::wizard::configure(&mut prompter, config, &devices)?;
+
+    // The prompts below read stdin themselves
+    drop(prompter);
+    prompt_for_passwords(&mut config, root_password)?;
+
+    show_config(&config);
+    confirm_install(&mut config)?;
+    run_installer(config, false, events, log_sink)
+}
+
+/// Log the settings the installation will use
+fn show_config(config: &Config) {
     log::info!("Configuration:");
     log::info!("  Mode: {}", config.mode);
-    log::info!("  Pool: {}", config.pool_name);
-    log::info!(
-        "  RAID: {} ({})",
-        config.raid_level,
-        config.raid_level.description()
-    );
-    log::info!("  Devices: {}", config.devices.len());
-    for device in &config.devices {
-        log::info!("    - {}", device.display());
+    if config.use_existing_pool {
+        log::info!("  Pool: {} (existing)", config.pool_name);
+    } else {
+        log::info!("  Pool: {}", config.pool_name);
+    }
+    if config.vdevs.is_empty() {
+        log::info!(
+            "  RAID: {} ({})",
+            config.raid_level,
+            config.raid_level.description()
+        );
+    } else {
+        log::info!("  RAID: {}", config.raid_summary());
+    }
+    if let Some(capacity) = config.usable_capacity(device_size) {
+        log::info!(
+            "  Usable capacity: about {} ({})",
+            capacity,
+            config.redundancy_summary()
+        );
+    }
+    log::info!("  Devices: {}", config.data_devices().len());
+    for (role, device) in config.devices_by_role() {
+        match role {
+            DeviceRole::Data => log::info!("    - {}", device.display()),
+            _ => log::info!("    - {} ({})", device.display(), role),
+        }
     }
     log::info!("  EFI size: {}", config.efi_size);
-    log::info!("  Swap size: {}", config.swap_size);
+    if config.encrypts_swap() {
+        log::info!(
+            "  Swap: {} ({}, encrypted with a random key)",
+            config.swap_size,
+            config.swap_mode
+        );
+    } else {
+        log::info!("  Swap: {} ({})", config.swap_size, config.swap_mode);
+    }
+    if config.installs_system() {
+        match (config.arc_max, system::get_system_memory_kb()) {
+            (Some(arc_max), _) => log::info!("  ARC max: {}", arc_max),
+            (None, Ok(mem_kb)) if mem_kb > 0 => log::info!(
+                "  ARC max: ZFS default (suggested: {}, set with --arc-max)",
+                zfs::suggested_arc_max(bytesize::ByteSize::kib(mem_kb))
+            ),
+            _ => {}
+        }
+    }
+    log::info!("  Partitioning: {}", config.partitioning);
+    if !config.partitioning.keeps_partitions() {
+        log::info!(
+            "  Wipe: {} ({})",
+            config.wipe_mode,
+            config.wipe_mode.description()
+        );
+    }
     log::info!("  Compression: {}", config.compression);
+    if let Some(ref encryption) = config.encryption {
+        log::info!(
+            "  Encryption: {} ({} key, keylocation={})",
+            encryption.algorithm,
+            encryption.keyformat,
+            encryption.keylocation()
+        );
+    }
+    log::info!(
+        "  Firmware: {} ({})",
+        config.firmware,
+        config.firmware.description()
+    );
+    log::info!(
+        "  Boot mode: {} ({})",
+        config.boot_mode,
+        config.boot_mode.description()
+    );
+    log::info!(
+        "  Bootloader: {} ({})",
+        config.bootloader,
+        config.bootloader.description()
+    );
+    log::info!("  ZFSBootMenu: {}", config.zbm_source);
+    log::info!(
+        "  Secure Boot: {} ({})",
+        config.secure_boot,
+        config.secure_boot.description()
+    );
     if config.dry_run {
         log::warn!("  DRY RUN MODE - No changes will be made");
     }
+}
+
+/// Have the pool name typed before wiping the disks, unless forced or a dry run
+///
+/// Unattended installs fail instead.
+fn confirm_install(config: &mut Config) -> Result<()> {
+    if config.force || config.dry_run {
+        return Ok(());
+    }
+    if config.unattended {
+        return Err(InstallerError::config(
+            "An unattended install won't wipe disks without confirmation; \
+             set force = true in the answer file or pass --force",
+        ));
+    }
+    println!("\n{}", i18n::tr("cli.confirm.warning"));
+    let stacked = stacked_devices(config);
+    for (device, holders) in &stacked {
+        println!(
+            "{}",
+            i18n::tr_args(
+                "cli.confirm.holders",
+                &[("device", &device.display()), ("holders", holders)]
+            )
+        );
+    }
+    println!(
+        "{}",
+        i18n::tr_args("cli.confirm.prompt", &[("pool", &config.pool_name)])
+    );
 
-    // Confirm unless force mode
-    if !config.force && !config.dry_run {
-        println!("\n⚠️  WARNING: This will DESTROY all data on the selected drives!");
-        println!("Continue? (yes/no): ");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    if !confirms(&input, &config.pool_name) {
+        return Err(InstallerError::UserCancelled);
+    }
+    // The user saw what will be stopped
+    config.release_holders |= !stacked.is_empty();
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
+    Ok(())
+}
+
+/// Install (or with `resume`, continue installing) `config`, stopping on Ctrl-C
+fn run_installer(
+    config: Config,
+    resume: bool,
+    events: Option<Arc<dyn EventSink>>,
+    log_sink: Option<LogSink>,
+) -> Result<()> {
+    // From here on, Ctrl-C stops at the next step and rolls back
+    let cancel = CancelToken::new();
+    cancel_on_signals(&cancel)?;
 
-        if input.trim().to_lowercase() != "yes" {
-            println!("Installation cancelled.");
-            return Ok(());
+    // Create and run installer
+    let mut installer = Installer::new(config)?
+        .with_cancel_token(cancel)
+        .with_state_file(STATE_PATH);
+    if let Some(sink) = events {
+        installer = installer.with_event_sink(sink);
+    }
+    if let Some(sink) = log_sink {
+        installer = installer.with_log_sink(sink);
+    }
+    let report = if resume {
+        installer.resume()?
+    } else {
+        installer.install()?
+    };
+
+    match &report.bootloader {
+        Some(bootloader) if bootloader.error.is_some() => log::warn!("Bootloader: {}", bootloader),
+        Some(bootloader) => log::info!("Bootloader: {}", bootloader),
+        None => {}
+    }
+    if let Some(network) = &report.network {
+        log::info!("Network: {}", network);
+    }
+    Ok(())
+}
+
+/// Download the ZFSBootMenu release and its checksums for offline installs
+fn fetch_artifacts(args: &FetchArtifactsArgs) -> Result<()> {
+    let image = bootloader::zbm::fetch_artifacts(&args.dest, args.zbm_version.clone())?;
+    println!(
+        "ZFSBootMenu {} is in {}; install with --offline --artifact-dir {}",
+        image.version,
+        image.path.display(),
+        args.dest.display()
+    );
+    Ok(())
+}
+
+/// Print the disks device discovery finds, with the pool each belongs to
+fn list_devices(details: bool) -> Result<()> {
+    let devices = DeviceDiscovery::new()?.scan_devices()?;
+    let members =
+        zfs::status::imported_members(&exec::SystemExecutor::new(false)).unwrap_or_default();
+
+    for device in &devices {
+        let pool = device.zfs_pool(&members);
+        if details {
+            for line in device.details(pool.as_deref()) {
+                println!("{}", line);
+            }
+            println!();
+            continue;
+        }
+        match pool {
+            Some(pool) => println!(
+                "{}  {}  [pool {}]",
+                device.path.display(),
+                device.display_name(),
+                pool
+            ),
+            None => println!("{}  {}", device.path.display(), device.display_name()),
         }
     }
+    Ok(())
+}
 
-    // Create and run installer
-    let installer = Installer::new(config)?;
-    installer.install()?;
+/// Run the pre-flight checks for `--config` and print each result
+///
+/// Returns the exit code: 0 when every check passed (or with `--strict`,
+/// passed without warnings).
+fn run_validate(args: &ValidateArgs) -> i32 {
+    let result = Config::from_file(&args.config)
+        .and_then(|config| Installer::for_validation(config).validate_only());
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            log::error!("Validation failed: {}", e);
+            return EXIT_FAILURE;
+        }
+    };
+
+    if args.json {
+        match result.to_json() {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                log::error!("{}", e);
+                return EXIT_FAILURE;
+            }
+        }
+    } else {
+        for check in &result.checks {
+            let status = match check.severity {
+                Severity::Ok => i18n::tr("cli.validate.pass"),
+                Severity::Warning => i18n::tr("cli.validate.warn"),
+                Severity::Error => i18n::tr("cli.validate.fail"),
+            };
+            match &check.message {
+                Some(message) => println!("[{}] {}: {}", status, check.check, message),
+                None => println!("[{}] {}", status, check.check),
+            }
+        }
+    }
+
+    validation_exit_code(&result, args.strict)
+}
+
+/// Remove a previous installation, after the pool name is typed to confirm
+fn run_destroy(args: DestroyArgs, log_sink: Option<LogSink>) -> Result<()> {
+    log::info!("ZFSBootMenu Installer - Destroy Mode");
 
+    let options = DestroyOptions {
+        keep_pool: args.keep_pool,
+        wipe_partitions: args.wipe,
+    };
+    let config = Config {
+        pool_name: args.pool.clone(),
+        dry_run: args.dry_run,
+        ..Default::default()
+    };
+    let mut installer = Installer::for_pool(config)?;
+    if let Some(sink) = log_sink {
+        installer = installer.with_log_sink(sink);
+    }
+
+    if !args.force && !args.dry_run {
+        println!("\n{}", i18n::tr("cli.destroy.warning"));
+        for removal in installer.removals(options)? {
+            println!("  - {}", removal);
+        }
+        println!(
+            "{}",
+            i18n::tr_args("cli.destroy.prompt", &[("pool", &args.pool)])
+        );
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !confirms(&input, &args.pool) {
+            return Err(InstallerError::UserCancelled);
+        }
+    }
+
+    // From here on, Ctrl-C stops before the next removal
+    let cancel = CancelToken::new();
+    cancel_on_signals(&cancel)?;
+    installer.with_cancel_token(cancel).destroy(options)?;
     Ok(())
 }
 
-fn run_tui(args: Args) -> Result<()> {
+/// Where JSON events go, if `--log-format json` was given
+fn event_sink(args: &InstallArgs) -> Result<Option<Arc<dyn EventSink>>> {
+    if args.log_format != LogFormatArg::Json {
+        if args.event_output.is_some() {
+            log::warn!("--event-output has no effect without --log-format json");
+        }
+        return Ok(None);
+    }
+    let sink = match &args.event_output {
+        Some(path) => JsonLinesSink::open(path)?,
+        None => JsonLinesSink::stdout(),
+    };
+    Ok(Some(Arc::new(sink)))
+}
+
+/// Size of a disk, named directly or by a /dev/disk/by-id link, if it can be read
+fn device_size(path: &Path) -> Option<u64> {
+    let path = std::fs::canonicalize(path).ok()?;
+    let device = BlockDevice::from_name(&path.file_name()?.to_string_lossy()).ok()?;
+    Some(device.size)
+}
+
+/// Selected disks with md arrays, LVM volumes or LUKS mappings on them
+fn stacked_devices(config: &Config) -> Vec<(PathBuf, String)> {
+    let Ok(discovery) = DeviceDiscovery::new() else {
+        return Vec::new();
+    };
+    config
+        .data_devices()
+        .into_iter()
+        .filter_map(|path| {
+            let device = discovery
+                .find_device(&path.file_name()?.to_string_lossy())
+                .ok()?;
+            let holders = disk::holders::describe(discovery.sys_root(), &device.holders);
+            (!device.holders.is_empty()).then(|| (path.to_path_buf(), holders))
+        })
+        .collect()
+}
+
+fn run_tui(
+    args: InstallArgs,
+    matches: &ArgMatches,
+    log_tail: ui::LogTail,
+    log_sink: Option<LogSink>,
+) -> Result<()> {
     log::info!("ZFSBootMenu Installer - TUI Mode");
 
-    // Build base configuration from CLI args (if any)
-    let mut config = Config::new();
-    if let Some(mode) = args.mode {
-        config.mode = mode.into();
+    // Build base configuration from the config file and CLI args (if any)
+    let root_password = args.root_password;
+    let mut config = build_config(args, matches)?;
+    prompt_for_passphrase(&mut config)?;
+    prompt_for_passwords(&mut config, root_password)?;
+
+    let cancel = CancelToken::new();
+    cancel_on_signals(&cancel)?;
+
+    // Launch TUI; it runs the installation once the user confirms
+    let mut ui = ui::UiManager::new(config)
+        .with_log_tail(log_tail)
+        .with_cancel_token(cancel);
+    if let Some(sink) = log_sink {
+        ui = ui.with_log_sink(sink);
     }
-    if !args.drives.is_empty() {
-        config.devices = args.drives;
+    ui.run()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code_mapping() {
+        assert_eq!(exit_code(&Ok(())), 0);
+        assert_eq!(
+            exit_code(&Err(InstallerError::UserCancelled)),
+            EXIT_CANCELLED
+        );
+        assert_eq!(
+            exit_code(&Err(InstallerError::UserCancelled.in_phase(Phase::Validate))),
+            EXIT_CANCELLED
+        );
+        assert_eq!(
+            exit_code(&Err(InstallerError::config("no drives"))),
+            EXIT_FAILURE
+        );
     }
-    config.pool_name = args.pool_name;
-    config.raid_level = args.raid.into();
-    config.dry_run = args.dry_run;
 
-    // Launch TUI
-    let mut ui = ui::UiManager::new(config);
-    let final_config = ui.run()?;
+    #[test]
+    fn test_validation_exit_code() {
+        let mut result = ValidationResult::new();
+        result.push(CheckResult::ok(Check::MinimumMemory));
+        assert_eq!(validation_exit_code(&result, true), 0);
 
-    // Run installation with TUI-configured settings
-    let installer = Installer::new(final_config)?;
-    installer.install()?;
+        result.push(CheckResult::warning(
+            Check::MatchedSizes,
+            "sizes differ".to_string(),
+        ));
+        assert_eq!(validation_exit_code(&result, false), 0);
+        assert_eq!(validation_exit_code(&result, true), EXIT_WARNINGS);
 
-    Ok(())
+        result.push(CheckResult::error(
+            Check::RootPrivileges,
+            "not root".to_string(),
+        ));
+        assert_eq!(validation_exit_code(&result, false), EXIT_FAILURE);
+        assert_eq!(validation_exit_code(&result, true), EXIT_FAILURE);
+    }
+
+    /// Parse a command line the way main() does
+    fn parse(argv: &[&str]) -> (Args, ArgMatches) {
+        let matches = Args::command()
+            .try_get_matches_from(std::iter::once("zbm-installer").chain(argv.iter().copied()))
+            .unwrap();
+        (Args::from_arg_matches(&matches).unwrap(), matches)
+    }
+
+    #[test]
+    fn test_cli_flags_override_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("install.toml");
+        std::fs::write(
+            &path,
+            "pool_name = \"tank\"\nraid_level = \"mirror\"\ndevices = [\"/dev/sda\", \"/dev/sdb\"]\nswap_size = \"4 GiB\"\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        // Defaults of flags not given don't clobber the file
+        let (args, matches) = parse(&["--config", path]);
+        let config = build_config(args.install, &matches).unwrap();
+        assert_eq!(config.pool_name, "tank");
+        assert_eq!(config.raid_level, RaidLevel::Mirror);
+        assert_eq!(config.swap_size, bytesize::ByteSize::gib(4));
+
+        // Explicit flags win
+        let (args, matches) = parse(&["--config", path, "--pool-name", "rpool", "-n"]);
+        let config = build_config(args.install, &matches).unwrap();
+        assert_eq!(config.pool_name, "rpool");
+        assert_eq!(config.devices.len(), 2);
+        assert!(config.dry_run);
+
+        // Without a file, flag defaults apply as before
+        let (args, matches) = parse(&["--mode", "new", "--drives", "/dev/sda"]);
+        let config = build_config(args.install, &matches).unwrap();
+        assert_eq!(config.swap_size, parse_size("8G").unwrap());
+    }
+
+    #[test]
+    fn test_resume_keeps_the_saved_boot_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state.json");
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            hostname: Some("box".to_string()),
+            ..Default::default()
+        };
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        let named = |today, state_file| {
+            let name = default_boot_environment(&config, system::Distro::Debian, today, state_file);
+            Config {
+                boot_environment: name.unwrap(),
+                ..config.clone()
+            }
+        };
+
+        // Interrupted the day before
+        let first = named(day(14), None);
+        assert_eq!(first.boot_environment, "debian-2026-10-14");
+        let state = installer::InstallState {
+            config_hash: installer::state::config_hash(&first).unwrap(),
+            boot_environment: Some(first.boot_environment.clone()),
+            ..Default::default()
+        };
+        std::fs::write(&state_file, state.to_json().unwrap()).unwrap();
+
+        let resumed = named(day(15), Some(&state_file));
+        assert_eq!(resumed.boot_environment, "debian-2026-10-14");
+        state.check_config(&resumed).unwrap();
+
+        // Without a state to resume, today's date is used
+        let missing = dir.path().join("missing.json");
+        assert_eq!(
+            named(day(15), Some(&missing)).boot_environment,
+            "debian-2026-10-15"
+        );
+    }
+
+    #[test]
+    fn test_repeated_vdev_flags_stripe_mirrors() {
+        let (args, matches) = parse(&[
+            "--vdev",
+            "mirror:/dev/sda,/dev/sdb",
+            "--vdev",
+            "mirror:/dev/sdc,/dev/sdd",
+        ]);
+        let config = build_config(args.install, &matches).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.vdevs.len(), 2);
+        assert_eq!(config.vdevs[1].devices[1], PathBuf::from("/dev/sdd"));
+        assert_eq!(config.raid_summary(), "2 x mirror");
+
+        assert!(Args::command()
+            .try_get_matches_from(["zbm-installer", "--vdev", "raid10:/dev/sda"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_bootstrap_flags() {
+        let (args, matches) = parse(&[
+            "--drives",
+            "/dev/sda",
+            "--bootstrap",
+            "debootstrap:noble",
+            "--bootstrap-mirror",
+            "http://archive.ubuntu.com/ubuntu",
+            "--bootstrap-package",
+            "linux-generic",
+            "--bootstrap-package",
+            "zfs-initramfs",
+        ]);
+        let config = build_config(args.install, &matches).unwrap();
+        config.validate().unwrap();
+        let spec = config.bootstrap.unwrap();
+        assert_eq!(spec.method, BootstrapMethod::Debootstrap);
+        assert_eq!(spec.release.as_deref(), Some("noble"));
+        assert_eq!(spec.extra_packages, ["linux-generic", "zfs-initramfs"]);
+
+        let (args, matches) = parse(&["--bootstrap-package", "linux-generic"]);
+        assert!(build_config(args.install, &matches).is_err());
+    }
+
+    #[test]
+    fn test_install_subcommand_matches_bare_flags() {
+        Args::command().debug_assert();
+
+        let argv = [
+            "--mode",
+            "new",
+            "--drives",
+            "/dev/sda",
+            "--pool-name",
+            "tank",
+        ];
+        let (args, matches) = parse(&argv);
+        assert!(args.command.is_none());
+        let bare = build_config(args.install, &matches).unwrap();
+
+        let (args, matches) = parse(&[&["install"][..], &argv[..]].concat());
+        let Some(Subcommands::Install(install)) = args.command else {
+            panic!("expected the install subcommand");
+        };
+        let config =
+            build_config(*install, matches.subcommand_matches("install").unwrap()).unwrap();
+        assert_eq!(config, bare);
+        assert_eq!(config.pool_name, "tank");
+    }
+
+    #[test]
+    fn test_completions_offer_value_enum_variants() {
+        let mut script = Vec::new();
+        clap_complete::generate(
+            clap_complete::Shell::Bash,
+            &mut Args::command(),
+            "zbm-installer",
+            &mut script,
+        );
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("--skip-preflight"));
+        assert!(script.contains("raidz2"));
+        assert!(script.contains("zstd"));
+        assert!(script.contains("existing"));
+    }
+
+    #[test]
+    fn test_manpage_includes_examples() {
+        let mut page = Vec::new();
+        clap_mangen::Man::new(Args::command())
+            .render(&mut page)
+            .unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains("EXAMPLES:"));
+        assert!(page.contains("list\\-devices"));
+    }
+
+    #[test]
+    fn test_validate_subcommand() {
+        let (args, _) = parse(&["validate", "--config", "install.toml", "--strict"]);
+        let Some(Subcommands::Validate(validate)) = args.command else {
+            panic!("expected the validate subcommand");
+        };
+        assert_eq!(validate.config, PathBuf::from("install.toml"));
+        assert!(validate.strict && !validate.json);
+
+        assert!(Args::command()
+            .try_get_matches_from(["zbm-installer", "validate"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_destroy_subcommand() {
+        let (args, _) = parse(&["destroy", "--pool", "tank", "--wipe", "-n", "-v"]);
+        assert!(args.verbose);
+        let Some(Subcommands::Destroy(destroy)) = args.command else {
+            panic!("expected the destroy subcommand");
+        };
+        assert_eq!(destroy.pool, "tank");
+        assert!(destroy.wipe && destroy.dry_run && !destroy.force);
+
+        for argv in [
+            &["zbm-installer", "destroy", "--wipe", "--keep-pool"][..],
+            &["zbm-installer", "--drives", "/dev/sda", "destroy"][..],
+        ] {
+            assert!(Args::command().try_get_matches_from(argv).is_err());
+        }
+    }
+
+    #[test]
+    fn test_offline_flags() {
+        let (args, _) = parse(&[
+            "fetch-artifacts",
+            "--dest",
+            "/media/usb",
+            "--zbm-version",
+            "2.3.0",
+        ]);
+        let Some(Subcommands::FetchArtifacts(fetch)) = args.command else {
+            panic!("expected the fetch-artifacts subcommand");
+        };
+        assert_eq!(fetch.dest, PathBuf::from("/media/usb"));
+        assert_eq!(fetch.zbm_version.as_deref(), Some("2.3.0"));
+
+        let (args, matches) = parse(&[
+            "--drives",
+            "/dev/sda",
+            "--offline",
+            "--artifact-dir",
+            "/media/usb",
+            "--pkg-dir",
+            "/media/usb/packages",
+        ]);
+        let config = build_config(args.install, &matches).unwrap();
+        config.validate().unwrap();
+        assert!(config.offline);
+        assert_eq!(config.artifact_dir, Some(PathBuf::from("/media/usb")));
+        assert_eq!(config.pkg_dir, Some(PathBuf::from("/media/usb/packages")));
+    }
 }
@@ -0,0 +1,167 @@
+//! Installing a minimal system into a new pool
+//!
+//! New mode otherwise leaves an empty boot environment with nothing to boot.
+//! The distribution's own bootstrap tool installs a base system into it,
+//! which the chroot configuration then makes bootable from the pool like a
+//! migrated one.
+
+use crate::config::{BootstrapMethod, BootstrapSpec};
+use crate::error::Result;
+use crate::exec::{CommandExecutor, ExecOptions, OutputStream, SystemExecutor};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Installs a base system with debootstrap, dnf or pacstrap
+pub struct Bootstrap {
+    spec: BootstrapSpec,
+    target: PathBuf,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl Bootstrap {
+    /// Bootstrap `spec` into `target`
+    pub fn new(spec: BootstrapSpec, target: impl Into<PathBuf>, dry_run: bool) -> Self {
+        Self {
+            spec,
+            target: target.into(),
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Run the bootstrap, passing each line the tool prints to `on_output`
+    ///
+    /// In dry-run mode the commands are only logged.
+    pub fn run(&self, on_output: &(dyn Fn(&str) + Sync)) -> Result<()> {
+        log::info!(
+            "Bootstrapping {} into {} with {}",
+            self.spec.release.as_deref().unwrap_or("a new system"),
+            self.target.display(),
+            self.spec.method
+        );
+
+        for mut cmd in self.commands() {
+            let (tx, rx) = mpsc::channel::<String>();
+            thread::scope(|scope| {
+                scope.spawn(move || {
+                    for line in rx {
+                        on_output(&line);
+                    }
+                });
+
+                // The sink owns the sender; dropping the options ends the loop above
+                let options = ExecOptions::new()
+                    .discard_stdout()
+                    .on_line(move |stream, line| {
+                        if stream == OutputStream::Stdout && !line.trim().is_empty() {
+                            let _ = tx.send(line.trim().to_string());
+                        }
+                    });
+                self.executor.execute_with(&mut cmd, &options)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// The commands installing the base system and the extra packages
+    fn commands(&self) -> Vec<Command> {
+        let target = self.target.as_path();
+        let release = self.spec.release.as_deref().unwrap_or_default();
+        let extra = &self.spec.extra_packages;
+
+        match self.spec.method {
+            BootstrapMethod::Debootstrap => {
+                let mut cmd = Command::new("debootstrap");
+                if !extra.is_empty() {
+                    cmd.arg(format!("--include={}", extra.join(",")));
+                }
+                cmd.arg(release).arg(target);
+                cmd.args(self.spec.mirror.as_deref());
+                vec![cmd]
+            }
+            BootstrapMethod::Dnf => {
+                let mut commands = vec![dnf(target, release, "groupinstall", ["core"])];
+                if !extra.is_empty() {
+                    commands.push(dnf(target, release, "install", extra));
+                }
+                commands
+            }
+            BootstrapMethod::Pacstrap => {
+                let mut cmd = Command::new("pacstrap");
+                cmd.arg(target).args(["base", "linux"]).args(extra);
+                vec![cmd]
+            }
+        }
+    }
+}
+
+/// `dnf` working on the system below `target`
+fn dnf<I, S>(target: &Path, release: &str, operation: &str, args: I) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    let mut cmd = Command::new("dnf");
+    cmd.arg(format!("--installroot={}", target.display()))
+        .arg(format!("--releasever={}", release))
+        .arg("--assumeyes")
+        .arg(operation)
+        .args(args);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    fn bootstrap(spec: BootstrapSpec) -> Vec<String> {
+        let executor = Arc::new(RecordingExecutor::new());
+        Bootstrap::new(spec, "/mnt", true)
+            .with_executor(executor.clone())
+            .run(&|_| {})
+            .unwrap();
+        executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn test_each_method_installs_a_base_system() {
+        let spec = |s: &str| s.parse::<BootstrapSpec>().unwrap();
+
+        assert_eq!(
+            bootstrap(BootstrapSpec {
+                mirror: Some("http://archive.ubuntu.com/ubuntu".to_string()),
+                extra_packages: vec!["linux-generic".to_string(), "zfs-initramfs".to_string()],
+                ..spec("debootstrap:noble")
+            }),
+            vec![
+                "debootstrap --include=linux-generic,zfs-initramfs noble /mnt http://archive.ubuntu.com/ubuntu"
+            ]
+        );
+        assert_eq!(
+            bootstrap(BootstrapSpec {
+                extra_packages: vec!["kernel".to_string()],
+                ..spec("dnf:40")
+            }),
+            vec![
+                "dnf --installroot=/mnt --releasever=40 --assumeyes groupinstall core",
+                "dnf --installroot=/mnt --releasever=40 --assumeyes install kernel",
+            ]
+        );
+        assert_eq!(
+            bootstrap(spec("pacstrap")),
+            vec!["pacstrap /mnt base linux"]
+        );
+    }
+}
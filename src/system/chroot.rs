@@ -0,0 +1,212 @@
+//! Running commands inside the installed system
+//!
+//! Tools like `update-initramfs` and `systemctl enable` have to run against
+//! the target's own files, so they're run through `chroot` with the host's
+//! `/dev`, `/proc` and `/sys` bind-mounted in. The mounts are torn down when
+//! the environment is closed or dropped, whether or not the commands worked.
+
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, FileOp, SystemExecutor};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// Host filesystems every chroot needs
+const API_FILESYSTEMS: &[&str] = &["/dev", "/proc", "/sys"];
+
+/// Where the ESP is expected inside the target
+const ESP_MOUNTPOINT: &str = "boot/efi";
+
+/// A target root prepared for `chroot`
+///
+/// Call [`mount`](Self::mount) before [`run`](Self::run). Mounts are undone
+/// newest first by [`close`](Self::close), or on drop if `close` wasn't
+/// called.
+pub struct ChrootEnv {
+    root: PathBuf,
+    binds: Vec<(PathBuf, PathBuf)>,
+    mounted: Vec<PathBuf>,
+    dry_run: bool,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl ChrootEnv {
+    /// Prepare `root` with the host's `/dev`, `/proc` and `/sys`
+    pub fn new(root: impl Into<PathBuf>, dry_run: bool) -> Self {
+        let root = root.into();
+        let binds = API_FILESYSTEMS
+            .iter()
+            .map(|dir| (PathBuf::from(dir), root.join(&dir[1..])))
+            .collect();
+        Self {
+            root,
+            binds,
+            mounted: Vec::new(),
+            dry_run,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Also make the ESP mounted at `esp` visible as `/boot/efi`
+    ///
+    /// Nothing extra is mounted if `esp` is already inside the root.
+    pub fn with_esp(mut self, esp: impl Into<PathBuf>) -> Self {
+        let esp = esp.into();
+        if !esp.starts_with(&self.root) {
+            let target = self.root.join(ESP_MOUNTPOINT);
+            self.binds.push((esp, target));
+        }
+        self
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// The target root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Bind-mount the host filesystems into the root
+    ///
+    /// If a mount fails, the ones already made are left for
+    /// [`close`](Self::close) or drop to undo.
+    pub fn mount(&mut self) -> Result<()> {
+        for (source, target) in self.binds.clone() {
+            if self.dry_run {
+                log::info!(
+                    "[DRY RUN] Would bind-mount {} on {}",
+                    source.display(),
+                    target.display()
+                );
+                continue;
+            }
+
+            self.executor.apply(FileOp::create_dir(&target))?;
+            self.executor.execute(
+                Command::new("mount")
+                    .arg("--rbind")
+                    .arg(&source)
+                    .arg(&target),
+            )?;
+            self.mounted.push(target.clone());
+            // Keep unmounts inside the chroot from propagating back to the host
+            self.executor
+                .execute(Command::new("mount").arg("--make-rslave").arg(&target))?;
+        }
+        Ok(())
+    }
+
+    /// Run a command inside the root
+    pub fn run(&self, args: &[&str]) -> Result<()> {
+        self.run_with(args, &ExecOptions::default())
+    }
+
+    /// Run a command inside the root with custom options, e.g. input on stdin
+    pub fn run_with(&self, args: &[&str], options: &ExecOptions) -> Result<()> {
+        let Some((program, rest)) = args.split_first() else {
+            return Err(InstallerError::SystemError(
+                "Empty command for chroot".to_string(),
+            ));
+        };
+        log::info!("Running in {}: {}", self.root.display(), args.join(" "));
+        self.executor.execute_with(
+            Command::new("chroot")
+                .arg(&self.root)
+                .arg(program)
+                .args(rest),
+            options,
+        )?;
+        Ok(())
+    }
+
+    /// Undo the mounts, newest first
+    ///
+    /// Every mount is attempted; the first failure is returned.
+    pub fn close(mut self) -> Result<()> {
+        self.unmount_all()
+    }
+
+    fn unmount_all(&mut self) -> Result<()> {
+        let mut first_error = None;
+        while let Some(target) = self.mounted.pop() {
+            let result = self
+                .executor
+                .execute(Command::new("umount").arg("-R").arg(&target));
+            if let Err(e) = result {
+                log::warn!("Failed to unmount {}: {}", target.display(), e);
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+impl Drop for ChrootEnv {
+    fn drop(&mut self) {
+        if !self.mounted.is_empty() {
+            let _ = self.unmount_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    fn plan(executor: &RecordingExecutor) -> Vec<String> {
+        executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn test_dry_run_runs_commands_without_mounting() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let mut env = ChrootEnv::new("/mnt", true)
+            .with_esp("/mnt/boot/efi")
+            .with_executor(executor.clone());
+        env.mount().unwrap();
+        env.run(&["zgenhostid", "-f"]).unwrap();
+        env.close().unwrap();
+
+        assert_eq!(plan(&executor), vec!["chroot /mnt zgenhostid -f"]);
+    }
+
+    #[test]
+    fn test_mounts_are_undone_on_drop_after_failure() {
+        let root = tempfile::tempdir().unwrap();
+        let esp = tempfile::tempdir().unwrap();
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("chroot", 1, "update-initramfs: not found");
+        {
+            let mut env = ChrootEnv::new(root.path(), false)
+                .with_esp(esp.path())
+                .with_executor(executor.clone());
+            env.mount().unwrap();
+            assert!(env.run(&["update-initramfs", "-u"]).is_err());
+        }
+
+        let root = root.path().display();
+        let umounts: Vec<String> = plan(&executor)
+            .into_iter()
+            .filter(|c| c.starts_with("umount"))
+            .collect();
+        assert_eq!(
+            umounts,
+            vec![
+                format!("umount -R {}/boot/efi", root),
+                format!("umount -R {}/sys", root),
+                format!("umount -R {}/proc", root),
+                format!("umount -R {}/dev", root),
+            ]
+        );
+        assert!(plan(&executor).contains(&format!("mount --rbind /dev {}/dev", root)));
+    }
+}
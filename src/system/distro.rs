@@ -1,16 +1,37 @@
 //! Distribution detection and support
+//!
+//! Distributions are told apart by `/etc/os-release`. Derivatives the
+//! installer doesn't know by name (Linux Mint, Rocky, EndeavourOS) are
+//! resolved through `ID_LIKE` to the distribution whose tools they share.
 
-use crate::error::Result;
+use crate::error::{InstallerError, Result};
 use std::fs;
+use std::path::Path;
 
 /// Supported Linux distributions
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Distro {
+    /// Fedora
     Fedora,
+    /// Debian
     Debian,
+    /// Ubuntu
     Ubuntu,
+    /// MX Linux
     MxLinux,
+    /// Arch Linux
     Arch,
+    /// Void Linux
+    Void,
+    /// openSUSE Leap and Tumbleweed
+    OpenSuse,
+    /// Gentoo
+    Gentoo,
+    /// Alpine Linux
+    Alpine,
+    /// Detected, but packages are only installed through the system configuration
+    NixOs,
+    /// Not recognised
     Unknown,
 }
 
@@ -22,6 +43,11 @@ impl std::fmt::Display for Distro {
             Self::Ubuntu => write!(f, "Ubuntu"),
             Self::MxLinux => write!(f, "MX Linux"),
             Self::Arch => write!(f, "Arch Linux"),
+            Self::Void => write!(f, "Void Linux"),
+            Self::OpenSuse => write!(f, "openSUSE"),
+            Self::Gentoo => write!(f, "Gentoo"),
+            Self::Alpine => write!(f, "Alpine Linux"),
+            Self::NixOs => write!(f, "NixOS"),
             Self::Unknown => write!(f, "Unknown"),
         }
     }
@@ -30,35 +56,46 @@ impl std::fmt::Display for Distro {
 impl Distro {
     /// Detect the current distribution
     pub fn detect() -> Result<Self> {
-        // Try /etc/os-release first (standard)
-        if let Ok(content) = fs::read_to_string("/etc/os-release") {
-            for line in content.lines() {
-                if line.starts_with("ID=") {
-                    let id = line.trim_start_matches("ID=").trim_matches('"');
-                    return Ok(match id {
-                        "fedora" => Self::Fedora,
-                        "debian" => Self::Debian,
-                        "ubuntu" => Self::Ubuntu,
-                        "mx" => Self::MxLinux,
-                        "arch" => Self::Arch,
-                        _ => Self::Unknown,
-                    });
-                }
-            }
-        }
+        Self::detect_in(Path::new("/"))
+    }
 
-        // Fallback to checking specific files
-        if fs::metadata("/etc/fedora-release").is_ok() {
-            return Ok(Self::Fedora);
-        }
-        if fs::metadata("/etc/debian_version").is_ok() {
-            return Ok(Self::Debian);
-        }
-        if fs::metadata("/etc/arch-release").is_ok() {
-            return Ok(Self::Arch);
-        }
+    /// Detect the distribution installed below `root`
+    pub fn detect_in(root: &Path) -> Result<Self> {
+        Ok(DistroInfo::detect_in(root)?.distro)
+    }
 
-        Ok(Self::Unknown)
+    /// The distribution an os-release `ID` names, if it is a known one
+    pub fn from_id(id: &str) -> Option<Self> {
+        Some(match id {
+            "fedora" => Self::Fedora,
+            "debian" => Self::Debian,
+            "ubuntu" => Self::Ubuntu,
+            "mx" => Self::MxLinux,
+            "arch" => Self::Arch,
+            "void" => Self::Void,
+            "gentoo" => Self::Gentoo,
+            "alpine" => Self::Alpine,
+            "nixos" => Self::NixOs,
+            _ if id == "suse" || id == "sles" || id.starts_with("opensuse") => Self::OpenSuse,
+            _ => return None,
+        })
+    }
+
+    /// The `ID` this distribution uses in `/etc/os-release`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Fedora => "fedora",
+            Self::Debian => "debian",
+            Self::Ubuntu => "ubuntu",
+            Self::MxLinux => "mx",
+            Self::Arch => "arch",
+            Self::Void => "void",
+            Self::OpenSuse => "opensuse",
+            Self::Gentoo => "gentoo",
+            Self::Alpine => "alpine",
+            Self::NixOs => "nixos",
+            Self::Unknown => "unknown",
+        }
     }
 
     /// Check if distribution is supported
@@ -72,6 +109,11 @@ impl Distro {
             Self::Fedora => "dnf",
             Self::Debian | Self::Ubuntu | Self::MxLinux => "apt-get",
             Self::Arch => "pacman",
+            Self::Void => "xbps-install",
+            Self::OpenSuse => "zypper",
+            Self::Gentoo => "emerge",
+            Self::Alpine => "apk",
+            Self::NixOs => "nixos-rebuild",
             Self::Unknown => "unknown",
         }
     }
@@ -82,30 +124,123 @@ impl Distro {
             Self::Fedora => vec!["zfs"],
             Self::Debian | Self::Ubuntu | Self::MxLinux => vec!["zfsutils-linux", "zfs-dkms"],
             Self::Arch => vec!["zfs-linux", "zfs-utils"],
-            Self::Unknown => vec![],
+            Self::Void => vec!["zfs"],
+            Self::OpenSuse => vec!["zfs", "zfs-kmp-default"],
+            Self::Gentoo => vec!["sys-fs/zfs"],
+            Self::Alpine => vec!["zfs", "zfs-lts"],
+            Self::NixOs | Self::Unknown => vec![],
         }
     }
 
-    /// Get required packages for ZFSBootMenu
-    pub fn zbm_packages(&self) -> Vec<&'static str> {
+    /// Whether the ZFS module is built by DKMS when installed, which needs
+    /// the headers of the running kernel
+    pub fn builds_zfs_with_dkms(&self) -> bool {
+        matches!(
+            self,
+            Self::Fedora | Self::Debian | Self::Ubuntu | Self::MxLinux
+        )
+    }
+
+    /// Package with the headers for kernel `release` (as `uname -r` prints
+    /// it), on distributions that build ZFS with DKMS
+    pub fn kernel_headers_package(&self, release: &str) -> Option<String> {
         match self {
-            Self::Fedora => vec![
-                "kernel-devel",
-                "dracut",
-                "efibootmgr",
-                "gdisk",
-                "util-linux",
-            ],
+            Self::Fedora => Some(format!("kernel-devel-{}", release)),
+            Self::Debian | Self::Ubuntu | Self::MxLinux => {
+                Some(format!("linux-headers-{}", release))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get required packages for ZFSBootMenu on a host running kernel
+    /// `release`
+    pub fn zbm_packages(&self, release: &str) -> Vec<String> {
+        let packages = match self {
+            Self::Fedora => vec!["dracut", "efibootmgr", "gdisk", "util-linux"],
             Self::Debian | Self::Ubuntu | Self::MxLinux => {
                 vec!["dracut-core", "efibootmgr", "gdisk", "util-linux"]
             }
-            Self::Arch => vec!["dracut", "efibootmgr", "gptfdisk", "util-linux"],
-            Self::Unknown => vec![],
+            Self::Arch | Self::Void | Self::OpenSuse => {
+                vec!["dracut", "efibootmgr", "gptfdisk", "util-linux"]
+            }
+            Self::Gentoo => vec![
+                "sys-kernel/dracut",
+                "sys-boot/efibootmgr",
+                "sys-apps/gptfdisk",
+                "sys-apps/util-linux",
+            ],
+            Self::Alpine => vec!["efibootmgr", "sgdisk", "util-linux"],
+            Self::NixOs | Self::Unknown => vec![],
+        };
+        self.kernel_headers_package(release)
+            .into_iter()
+            .chain(packages.into_iter().map(str::to_string))
+            .collect()
+    }
+
+    /// Group whose members may use sudo
+    pub fn admin_group(&self) -> &'static str {
+        match self {
+            Self::Debian | Self::Ubuntu | Self::MxLinux => "sudo",
+            _ => "wheel",
+        }
+    }
+
+    /// Command that regenerates the initramfs for every installed kernel
+    pub fn initramfs_command(&self) -> Option<Vec<&'static str>> {
+        match self {
+            Self::Fedora => Some(vec!["dracut", "--force", "--regenerate-all"]),
+            Self::Debian | Self::Ubuntu | Self::MxLinux => {
+                Some(vec!["update-initramfs", "-u", "-k", "all"])
+            }
+            Self::Arch => Some(vec!["mkinitcpio", "-P"]),
+            Self::OpenSuse | Self::Gentoo => Some(vec!["dracut", "--force", "--regenerate-all"]),
+            // Reconfiguring the kernel packages runs their dracut hooks
+            Self::Void => Some(vec!["xbps-reconfigure", "-fa"]),
+            Self::Alpine => Some(vec!["mkinitfs"]),
+            // nixos-rebuild builds the initrd with the rest of the system
+            Self::NixOs | Self::Unknown => None,
+        }
+    }
+
+    /// Commands that enable the services importing and mounting pools at
+    /// boot, with the target's init system
+    ///
+    /// Empty where nothing needs enabling, `None` where the init system
+    /// isn't known.
+    pub fn enable_services_command(&self) -> Option<Vec<Vec<&'static str>>> {
+        match self {
+            Self::Fedora | Self::Debian | Self::Ubuntu | Self::Arch | Self::OpenSuse => {
+                Some(vec![vec![
+                    "systemctl",
+                    "enable",
+                    "zfs-import-cache.service",
+                    "zfs-mount.service",
+                ]])
+            }
+            // sysvinit by default
+            Self::MxLinux => Some(vec![
+                vec!["update-rc.d", "zfs-import", "defaults"],
+                vec!["update-rc.d", "zfs-mount", "defaults"],
+            ]),
+            // OpenRC
+            Self::Gentoo | Self::Alpine => Some(vec![
+                vec!["rc-update", "add", "zfs-import", "boot"],
+                vec!["rc-update", "add", "zfs-mount", "boot"],
+            ]),
+            // runit's core services import and mount pools themselves
+            Self::Void => Some(vec![]),
+            // Services are part of the NixOS configuration
+            Self::NixOs | Self::Unknown => None,
         }
     }
 
     /// Get install command for packages
-    pub fn install_command(&self, packages: &[&str]) -> Vec<String> {
+    ///
+    /// NixOS installs packages only through its configuration, so it gets
+    /// an error saying what to add there instead.
+    pub fn install_command(&self, packages: &[&str]) -> Result<Vec<String>> {
         let mut cmd = vec![self.package_manager().to_string()];
 
         match self {
@@ -121,11 +256,140 @@ impl Distro {
                 cmd.push("-S".to_string());
                 cmd.push("--noconfirm".to_string());
             }
-            Self::Unknown => {}
+            Self::Void => cmd.push("-Sy".to_string()),
+            Self::OpenSuse => {
+                cmd.push("--non-interactive".to_string());
+                cmd.push("install".to_string());
+            }
+            Self::Gentoo => {
+                cmd.push("--ask=n".to_string());
+                cmd.push("--noreplace".to_string());
+            }
+            Self::Alpine => cmd.push("add".to_string()),
+            Self::NixOs => {
+                return Err(InstallerError::Unsupported(format!(
+                    "NixOS installs packages declaratively; add {} to environment.systemPackages \
+                     (and boot.supportedFilesystems = [ \"zfs\" ]) in configuration.nix and run \
+                     nixos-rebuild switch",
+                    packages.join(", ")
+                )));
+            }
+            Self::Unknown => {
+                return Err(InstallerError::Unsupported(
+                    "Don't know how to install packages on this distribution".to_string(),
+                ));
+            }
         }
 
         cmd.extend(packages.iter().map(|s| s.to_string()));
-        cmd
+        Ok(cmd)
+    }
+}
+
+/// What `/etc/os-release` says about a distribution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistroInfo {
+    /// The distribution whose tools the system uses
+    pub distro: Distro,
+    /// `ID`, e.g. `linuxmint`
+    pub id: String,
+    /// `ID_LIKE`, closest relative first
+    pub id_like: Vec<String>,
+    /// `VERSION_ID`, e.g. `21.3` (rolling releases often have none)
+    pub version_id: Option<String>,
+    /// `PRETTY_NAME`, e.g. `Linux Mint 21.3`
+    pub pretty_name: Option<String>,
+}
+
+impl DistroInfo {
+    /// Read the os-release of the system installed below `root`
+    ///
+    /// Falls back to the release files older systems have instead, and to
+    /// [`Distro::Unknown`] if there are none.
+    pub fn detect_in(root: &Path) -> Result<Self> {
+        for path in ["etc/os-release", "usr/lib/os-release"] {
+            if let Ok(content) = fs::read_to_string(root.join(path)) {
+                return Ok(Self::parse(&content));
+            }
+        }
+
+        let distro = [
+            ("etc/fedora-release", Distro::Fedora),
+            ("etc/debian_version", Distro::Debian),
+            ("etc/arch-release", Distro::Arch),
+            ("etc/gentoo-release", Distro::Gentoo),
+            ("etc/alpine-release", Distro::Alpine),
+            ("etc/SuSE-release", Distro::OpenSuse),
+        ]
+        .into_iter()
+        .find(|(file, _)| root.join(file).exists())
+        .map_or(Distro::Unknown, |(_, distro)| distro);
+        Ok(Self {
+            distro,
+            id: distro.id().to_string(),
+            id_like: Vec::new(),
+            version_id: None,
+            pretty_name: None,
+        })
+    }
+
+    /// Parse the contents of an os-release file
+    pub fn parse(content: &str) -> Self {
+        let mut id = String::new();
+        let mut id_like = Vec::new();
+        let mut version_id = None;
+        let mut pretty_name = None;
+        for line in content.lines() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let value = unquote(value);
+            match key {
+                "ID" => id = value,
+                "ID_LIKE" => id_like = value.split_whitespace().map(str::to_string).collect(),
+                "VERSION_ID" => version_id = Some(value),
+                "PRETTY_NAME" => pretty_name = Some(value),
+                _ => {}
+            }
+        }
+
+        let distro = std::iter::once(&id)
+            .chain(&id_like)
+            .find_map(|id| Distro::from_id(id))
+            .unwrap_or(Distro::Unknown);
+        Self {
+            distro,
+            id,
+            id_like,
+            version_id,
+            pretty_name,
+        }
+    }
+}
+
+impl std::fmt::Display for DistroInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.pretty_name, &self.version_id) {
+            (Some(name), _) => write!(f, "{}", name)?,
+            (None, Some(version)) => write!(f, "{} {}", self.distro, version)?,
+            (None, None) => write!(f, "{}", self.distro)?,
+        }
+        // Say which toolchain a derivative gets
+        if Distro::from_id(&self.id).is_none() && self.distro != Distro::Unknown {
+            write!(f, " ({})", self.distro)?;
+        }
+        Ok(())
+    }
+}
+
+/// An os-release value with its shell quoting removed
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let quoted = |q: char| value.len() >= 2 && value.starts_with(q) && value.ends_with(q);
+    if quoted('"') || quoted('\'') {
+        value[1..value.len() - 1].replace("\\\"", "\"")
+    } else {
+        value.to_string()
     }
 }
 
@@ -161,4 +425,125 @@ mod tests {
         let debian_packages = Distro::Debian.zfs_packages();
         assert!(debian_packages.contains(&"zfsutils-linux"));
     }
+
+    #[test]
+    fn test_zbm_packages_include_kernel_headers() {
+        let release = "6.1.0-18-amd64";
+        assert!(Distro::Debian
+            .zbm_packages(release)
+            .contains(&"linux-headers-6.1.0-18-amd64".to_string()));
+        assert!(Distro::Fedora
+            .zbm_packages("6.8.5-301.fc40.x86_64")
+            .contains(&"kernel-devel-6.8.5-301.fc40.x86_64".to_string()));
+        assert!(!Distro::Arch.builds_zfs_with_dkms());
+        assert_eq!(Distro::Arch.kernel_headers_package(release), None);
+        assert!(!Distro::Arch
+            .zbm_packages(release)
+            .iter()
+            .any(|package| package.contains("headers")));
+    }
+
+    #[test]
+    fn test_detect_in_target_root() {
+        let root = tempfile::tempdir().unwrap();
+        assert_eq!(Distro::detect_in(root.path()).unwrap(), Distro::Unknown);
+
+        fs::create_dir(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join("etc/os-release"),
+            "NAME=\"Arch Linux\"\nID=arch\n",
+        )
+        .unwrap();
+        let distro = Distro::detect_in(root.path()).unwrap();
+        assert_eq!(distro, Distro::Arch);
+        assert_eq!(distro.initramfs_command(), Some(vec!["mkinitcpio", "-P"]));
+    }
+
+    #[test]
+    fn test_os_release_samples() {
+        let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/os-release");
+        let parse =
+            |name: &str| DistroInfo::parse(&fs::read_to_string(samples.join(name)).unwrap());
+
+        let cases = [
+            (
+                "linuxmint",
+                Distro::Ubuntu,
+                Some("21.3"),
+                "Linux Mint 21.3 (Ubuntu)",
+            ),
+            ("mx", Distro::MxLinux, Some("23.2"), "MX 23.2 Libretto"),
+            (
+                "rocky",
+                Distro::Fedora,
+                Some("9.3"),
+                "Rocky Linux 9.3 (Blue Onyx) (Fedora)",
+            ),
+            ("void", Distro::Void, None, "Void Linux"),
+            (
+                "opensuse-tumbleweed",
+                Distro::OpenSuse,
+                Some("20240510"),
+                "openSUSE Tumbleweed",
+            ),
+            ("gentoo", Distro::Gentoo, Some("2.15"), "Gentoo Linux"),
+            (
+                "alpine",
+                Distro::Alpine,
+                Some("3.19.1"),
+                "Alpine Linux v3.19",
+            ),
+            ("nixos", Distro::NixOs, Some("23.11"), "NixOS 23.11 (Tapir)"),
+        ];
+        for (name, distro, version, display) in cases {
+            let info = parse(name);
+            assert_eq!(info.distro, distro, "{name}");
+            assert_eq!(info.version_id.as_deref(), version, "{name}");
+            assert_eq!(info.to_string(), display, "{name}");
+        }
+        assert_eq!(parse("linuxmint").id_like, vec!["ubuntu", "debian"]);
+        assert_eq!(DistroInfo::parse("ID=plan9\n").distro, Distro::Unknown);
+    }
+
+    #[test]
+    fn test_enable_services_commands() {
+        let argv = |distro: Distro| {
+            distro
+                .enable_services_command()
+                .map(|commands| commands.iter().map(|c| c.join(" ")).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            argv(Distro::Debian).unwrap(),
+            ["systemctl enable zfs-import-cache.service zfs-mount.service"]
+        );
+        assert_eq!(
+            argv(Distro::Alpine).unwrap(),
+            [
+                "rc-update add zfs-import boot",
+                "rc-update add zfs-mount boot"
+            ]
+        );
+        assert_eq!(argv(Distro::Gentoo), argv(Distro::Alpine));
+        assert!(argv(Distro::MxLinux).unwrap()[0].starts_with("update-rc.d"));
+        assert_eq!(argv(Distro::Void).unwrap(), Vec::<String>::new());
+        assert_eq!(argv(Distro::Unknown), None);
+    }
+
+    #[test]
+    fn test_install_commands() {
+        let argv = |distro: Distro| distro.install_command(&["zfs"]).unwrap().join(" ");
+        assert_eq!(argv(Distro::Void), "xbps-install -Sy zfs");
+        assert_eq!(
+            argv(Distro::OpenSuse),
+            "zypper --non-interactive install zfs"
+        );
+        assert_eq!(argv(Distro::Gentoo), "emerge --ask=n --noreplace zfs");
+        assert_eq!(argv(Distro::Alpine), "apk add zfs");
+
+        let err = Distro::NixOs.install_command(&["zfs"]).unwrap_err();
+        assert!(matches!(err, InstallerError::Unsupported(_)));
+        assert!(err.to_string().contains("configuration.nix"), "{err}");
+        assert!(Distro::NixOs.is_supported());
+        assert!(Distro::NixOs.zfs_packages().is_empty());
+    }
 }
@@ -0,0 +1,549 @@
+//! `/etc/fstab` for the installed system
+//!
+//! ZFS datasets mount themselves through their `mountpoint` property, so the
+//! fstab only lists what ZFS doesn't manage: the ESPs, swap, and optionally a
+//! tmpfs on `/tmp`. Swap partitions encrypted with a random key also get
+//! `/etc/crypttab` lines, and fstab swaps on their mappings.
+
+use crate::disk::ZbmPartitions;
+use crate::error::Result;
+use crate::exec::{CommandExecutor, FileOp, SystemExecutor};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// Header of a generated fstab
+const HEADER: &str = "# /etc/fstab: static file system information
+#
+# ZFS datasets are mounted by ZFS itself and are not listed here.
+#
+# <file system> <mount point> <type> <options> <dump> <pass>
+";
+
+/// Prefix of lines commented out when merging into an existing fstab
+const SUPERSEDED: &str = "# superseded by zbm-installer: ";
+
+/// crypttab options of swap encrypted with a new random key at every boot
+const CRYPTSWAP_OPTIONS: &str = "swap,cipher=aes-xts-plain64,size=256,nofail";
+
+/// One line of an fstab
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FstabEntry {
+    /// Device, e.g. `UUID=5D7E-0C41`
+    pub spec: String,
+    /// Mount point, or `none` for swap
+    pub file: String,
+    /// Filesystem type
+    pub vfstype: String,
+    /// Mount options
+    pub options: String,
+    /// fsck pass number
+    pub passno: u8,
+}
+
+impl FstabEntry {
+    fn new(spec: impl Into<String>, file: &str, vfstype: &str, options: &str, passno: u8) -> Self {
+        Self {
+            spec: spec.into(),
+            file: file.to_string(),
+            vfstype: vfstype.to_string(),
+            options: options.to_string(),
+            passno,
+        }
+    }
+}
+
+impl std::fmt::Display for FstabEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} 0 {}",
+            self.spec, self.file, self.vfstype, self.options, self.passno
+        )
+    }
+}
+
+/// Builds and writes the fstab of the installed system
+pub struct FstabGenerator {
+    dry_run: bool,
+    executor: Arc<dyn CommandExecutor>,
+    swap_zvol: Option<PathBuf>,
+    encrypted_swap: bool,
+    zfs_mountpoints: Vec<PathBuf>,
+    tmpfs_tmp: bool,
+}
+
+impl FstabGenerator {
+    /// Create a new fstab generator
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+            swap_zvol: None,
+            encrypted_swap: false,
+            zfs_mountpoints: Vec::new(),
+            tmpfs_tmp: false,
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Swap on this zvol (`/dev/zvol/<pool>/swap`)
+    pub fn with_swap_zvol(mut self, swap_zvol: Option<PathBuf>) -> Self {
+        self.swap_zvol = swap_zvol;
+        self
+    }
+
+    /// Encrypt the swap partitions with a random key through `/etc/crypttab`
+    pub fn with_encrypted_swap(mut self, encrypted_swap: bool) -> Self {
+        self.encrypted_swap = encrypted_swap;
+        self
+    }
+
+    /// Mount points now provided by ZFS datasets
+    ///
+    /// Lines of an existing fstab mounting anything here are commented out.
+    pub fn with_zfs_mountpoints(mut self, mountpoints: Vec<PathBuf>) -> Self {
+        self.zfs_mountpoints = mountpoints;
+        self
+    }
+
+    /// Mount a tmpfs on `/tmp`
+    pub fn with_tmpfs_tmp(mut self, tmpfs_tmp: bool) -> Self {
+        self.tmpfs_tmp = tmpfs_tmp;
+        self
+    }
+
+    /// Entries for the installed system
+    ///
+    /// The first disk's ESP is mounted on `/boot/efi`, the others on
+    /// `/boot/efi2` and so on, matching the layout used during installation;
+    /// the boot partitions of BIOS layouts likewise on `/boot/syslinux`.
+    /// Mirror ESPs, mirror boot partitions and swap partitions are `nofail`
+    /// so a missing disk doesn't stop the boot.
+    pub fn entries(&self, partitions: &[ZbmPartitions]) -> Result<Vec<FstabEntry>> {
+        let mut entries = Vec::new();
+
+        for (i, parts) in partitions.iter().enumerate() {
+            let suffix = match i {
+                0 => String::new(),
+                _ => (i + 1).to_string(),
+            };
+            let nofail = if i == 0 { "" } else { ",nofail" };
+            if let Some(efi) = &parts.efi {
+                entries.push(FstabEntry::new(
+                    self.spec(efi)?,
+                    &format!("/boot/efi{}", suffix),
+                    "vfat",
+                    &format!("umask=0077{}", nofail),
+                    2,
+                ));
+            }
+            if let Some(boot) = &parts.boot {
+                entries.push(FstabEntry::new(
+                    self.spec(boot)?,
+                    &format!("/boot/syslinux{}", suffix),
+                    "ext4",
+                    &format!("defaults{}", nofail),
+                    2,
+                ));
+            }
+        }
+
+        for (i, swap) in partitions
+            .iter()
+            .filter_map(|p| p.swap.as_ref())
+            .enumerate()
+        {
+            let spec = if self.encrypted_swap {
+                format!("/dev/mapper/{}", cryptswap_name(i))
+            } else {
+                self.spec(swap)?
+            };
+            entries.push(FstabEntry::new(spec, "none", "swap", "defaults,nofail", 0));
+        }
+        if let Some(zvol) = &self.swap_zvol {
+            entries.push(FstabEntry::new(
+                zvol.display().to_string(),
+                "none",
+                "swap",
+                "defaults",
+                0,
+            ));
+        }
+
+        if self.tmpfs_tmp {
+            entries.push(FstabEntry::new(
+                "tmpfs",
+                "/tmp",
+                "tmpfs",
+                "defaults,nosuid,nodev,mode=1777",
+                0,
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    /// crypttab lines mapping each swap partition, by PARTUUID since the
+    /// partitions have no filesystem UUID
+    ///
+    /// Empty unless swap is encrypted.
+    pub fn crypttab_entries(&self, partitions: &[ZbmPartitions]) -> Result<Vec<String>> {
+        if !self.encrypted_swap {
+            return Ok(Vec::new());
+        }
+        partitions
+            .iter()
+            .filter_map(|p| p.swap.as_ref())
+            .enumerate()
+            .map(|(i, swap)| {
+                Ok(format!(
+                    "{} {} /dev/urandom {}",
+                    cryptswap_name(i),
+                    self.tagged_spec("PARTUUID", swap)?,
+                    CRYPTSWAP_OPTIONS
+                ))
+            })
+            .collect()
+    }
+
+    /// `UUID=` spec of a partition, or its path when the UUID can't be read
+    fn spec(&self, partition: &Path) -> Result<String> {
+        self.tagged_spec("UUID", partition)
+    }
+
+    /// `<tag>=` spec of a partition as blkid reads it, or its path when
+    /// the value can't be read
+    fn tagged_spec(&self, tag: &str, partition: &Path) -> Result<String> {
+        let result = self.executor.execute(
+            Command::new("blkid")
+                .arg("-s")
+                .arg(tag)
+                .arg("-o")
+                .arg("value")
+                .arg(partition),
+        )?;
+
+        match result.stdout_string().map(|s| s.trim().to_string()) {
+            Some(value) if !value.is_empty() => Ok(format!("{}={}", tag, value)),
+            _ => {
+                if !self.dry_run {
+                    log::warn!(
+                        "No {} for {}, using the device path",
+                        tag,
+                        partition.display()
+                    );
+                }
+                Ok(partition.display().to_string())
+            }
+        }
+    }
+
+    /// A new fstab holding `entries`
+    pub fn render(entries: &[FstabEntry]) -> String {
+        let mut out = HEADER.to_string();
+        for entry in entries {
+            out.push_str(&entry.to_string());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// `existing` with `entries` appended
+    ///
+    /// Lines that mount something an entry replaces (the same mount point,
+    /// any old swap when swap is configured, or a mount point now provided
+    /// by a ZFS dataset) are commented out rather than deleted.
+    pub fn merge(&self, existing: &str, entries: &[FstabEntry]) -> String {
+        let has_swap = entries.iter().any(|e| e.vfstype == "swap");
+        let mut out = String::new();
+
+        for line in existing.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let superseded = match fields.as_slice() {
+                [spec, file, vfstype, ..] if !spec.starts_with('#') => {
+                    entries.iter().any(|e| e.file != "none" && e.file == *file)
+                        || (has_swap && *vfstype == "swap")
+                        || self.zfs_mountpoints.iter().any(|m| m == Path::new(file))
+                }
+                _ => false,
+            };
+
+            if superseded {
+                out.push_str(SUPERSEDED);
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        out.push_str("\n# Added by zbm-installer\n");
+        for entry in entries {
+            out.push_str(&entry.to_string());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// `existing` crypttab with `entries` appended
+    ///
+    /// Lines mapping the same names, or swap of an earlier setup, are
+    /// commented out like superseded fstab lines.
+    pub fn merge_crypttab(existing: &str, entries: &[String]) -> String {
+        let names: Vec<&str> = entries
+            .iter()
+            .filter_map(|entry| entry.split_whitespace().next())
+            .collect();
+        let mut out = String::new();
+
+        for line in existing.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let superseded = match fields.as_slice() {
+                [name, ..] if name.starts_with('#') => false,
+                [name, _, _, options, ..] => {
+                    names.contains(name) || options.split(',').any(|o| o == "swap")
+                }
+                [name, ..] => names.contains(name),
+                [] => false,
+            };
+
+            if superseded {
+                out.push_str(SUPERSEDED);
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("# Added by zbm-installer\n");
+        for entry in entries {
+            out.push_str(entry);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Write `<root>/etc/fstab`, merging into an existing one, and
+    /// `<root>/etc/crypttab` for encrypted swap
+    pub fn write(&self, root: &Path, partitions: &[ZbmPartitions]) -> Result<()> {
+        let crypttab = self.crypttab_entries(partitions)?;
+        if !crypttab.is_empty() {
+            let path = root.join("etc").join("crypttab");
+            let existing = fs::read_to_string(&path).unwrap_or_default();
+            self.executor.apply(FileOp::create_dir(root.join("etc")))?;
+            self.executor.apply(FileOp::write(
+                &path,
+                Self::merge_crypttab(&existing, &crypttab),
+            ))?;
+        }
+
+        let entries = self.entries(partitions)?;
+        let path = root.join("etc").join("fstab");
+
+        let content = match fs::read_to_string(&path) {
+            Ok(existing) => {
+                log::info!("Merging into existing {}", path.display());
+                self.merge(&existing, &entries)
+            }
+            Err(_) => Self::render(&entries),
+        };
+
+        self.executor.apply(FileOp::create_dir(root.join("etc")))?;
+        self.executor.apply(FileOp::write(&path, content))?;
+        if self.dry_run {
+            for entry in &entries {
+                log::info!("[DRY RUN]   {}", entry);
+            }
+        } else {
+            log::info!("Wrote {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Name of the mapping of the `i`th swap partition: `swap`, then `swap2`
+/// and so on, like the ESP mount points
+fn cryptswap_name(i: usize) -> String {
+    match i {
+        0 => "swap".to_string(),
+        _ => format!("swap{}", i + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    fn partitions(disk: &str, swap: bool) -> ZbmPartitions {
+        ZbmPartitions {
+            bios_boot: None,
+            efi: Some(PathBuf::from(format!("/dev/{}1", disk))),
+            boot: None,
+            swap: swap.then(|| PathBuf::from(format!("/dev/{}2", disk))),
+            zfs: PathBuf::from(format!("/dev/{}3", disk)),
+        }
+    }
+
+    fn generator(uuid: &str) -> FstabGenerator {
+        generator_with(Arc::new(RecordingExecutor::new()), uuid)
+    }
+
+    fn generator_with(executor: Arc<RecordingExecutor>, uuid: &str) -> FstabGenerator {
+        executor.respond_program("blkid", format!("{}\n", uuid));
+        FstabGenerator::new(false).with_executor(executor)
+    }
+
+    #[test]
+    fn test_single_disk_with_swap_partition() {
+        let entries = generator("5D7E-0C41")
+            .entries(&[partitions("sda", true)])
+            .unwrap();
+
+        assert_eq!(
+            FstabGenerator::render(&entries),
+            "# /etc/fstab: static file system information
+#
+# ZFS datasets are mounted by ZFS itself and are not listed here.
+#
+# <file system> <mount point> <type> <options> <dump> <pass>
+UUID=5D7E-0C41 /boot/efi vfat umask=0077 0 2
+UUID=5D7E-0C41 none swap defaults,nofail 0 0
+"
+        );
+    }
+
+    #[test]
+    fn test_mirror_with_swap_zvol_and_tmpfs() {
+        let entries = generator("")
+            .with_swap_zvol(Some(PathBuf::from("/dev/zvol/zroot/swap")))
+            .with_tmpfs_tmp(true)
+            .entries(&[partitions("sda", false), partitions("sdb", false)])
+            .unwrap();
+
+        let lines: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "/dev/sda1 /boot/efi vfat umask=0077 0 2",
+                "/dev/sdb1 /boot/efi2 vfat umask=0077,nofail 0 2",
+                "/dev/zvol/zroot/swap none swap defaults 0 0",
+                "tmpfs /tmp tmpfs defaults,nosuid,nodev,mode=1777 0 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encrypted_swap_maps_each_partition() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join("etc/crypttab"),
+            "# <name> <device> <password> <options>\ncryptswap1 UUID=old /dev/urandom swap,cipher=aes-xts-plain64\nhome UUID=77aa none luks\n",
+        )
+        .unwrap();
+
+        generator_with(executor.clone(), "0f2d9a6e-02")
+            .with_encrypted_swap(true)
+            .write(
+                root.path(),
+                &[partitions("sda", true), partitions("sdb", true)],
+            )
+            .unwrap();
+
+        assert_eq!(
+            executor
+                .written(&root.path().join("etc/crypttab"))
+                .as_deref(),
+            Some(
+                "# <name> <device> <password> <options>
+# superseded by zbm-installer: cryptswap1 UUID=old /dev/urandom swap,cipher=aes-xts-plain64
+home UUID=77aa none luks
+
+# Added by zbm-installer
+swap PARTUUID=0f2d9a6e-02 /dev/urandom swap,cipher=aes-xts-plain64,size=256,nofail
+swap2 PARTUUID=0f2d9a6e-02 /dev/urandom swap,cipher=aes-xts-plain64,size=256,nofail
+"
+            )
+        );
+        let fstab = executor.written(&root.path().join("etc/fstab")).unwrap();
+        assert!(fstab.contains("/dev/mapper/swap none swap defaults,nofail 0 0\n"));
+        assert!(fstab.contains("/dev/mapper/swap2 none swap defaults,nofail 0 0\n"));
+    }
+
+    #[test]
+    fn test_bios_mirror_mounts_the_boot_partitions() {
+        let bios = |disk: &str| ZbmPartitions {
+            efi: None,
+            boot: Some(PathBuf::from(format!("/dev/{}2", disk))),
+            ..partitions(disk, false)
+        };
+        let entries = generator("").entries(&[bios("sda"), bios("sdb")]).unwrap();
+
+        let lines: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "/dev/sda2 /boot/syslinux ext4 defaults 0 2",
+                "/dev/sdb2 /boot/syslinux2 ext4 defaults,nofail 0 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_comments_out_superseded_lines() {
+        let generator = generator("5D7E-0C41")
+            .with_zfs_mountpoints(vec![PathBuf::from("/"), PathBuf::from("/home")]);
+        let entries = generator.entries(&[partitions("sda", true)]).unwrap();
+        let existing = "# <file system> <mount point> <type> <options> <dump> <pass>
+UUID=0a1b2c3d / ext4 errors=remount-ro 0 1
+UUID=9F2E-11AA /boot/efi vfat umask=0077 0 1
+UUID=77aa /home ext4 defaults 0 2
+/swapfile none swap sw 0 0
+server:/srv/media /media nfs defaults 0 0
+";
+
+        assert_eq!(
+            generator.merge(existing, &entries),
+            "# <file system> <mount point> <type> <options> <dump> <pass>
+# superseded by zbm-installer: UUID=0a1b2c3d / ext4 errors=remount-ro 0 1
+# superseded by zbm-installer: UUID=9F2E-11AA /boot/efi vfat umask=0077 0 1
+# superseded by zbm-installer: UUID=77aa /home ext4 defaults 0 2
+# superseded by zbm-installer: /swapfile none swap sw 0 0
+server:/srv/media /media nfs defaults 0 0
+
+# Added by zbm-installer
+UUID=5D7E-0C41 /boot/efi vfat umask=0077 0 2
+UUID=5D7E-0C41 none swap defaults,nofail 0 0
+"
+        );
+    }
+
+    #[test]
+    fn test_write_merges_existing_file() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join("etc/fstab"),
+            "UUID=old /boot/efi vfat defaults 0 1\n",
+        )
+        .unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        generator_with(executor.clone(), "5D7E-0C41")
+            .write(root.path(), &[partitions("sda", false)])
+            .unwrap();
+
+        let fstab = executor.written(&root.path().join("etc/fstab")).unwrap();
+        assert!(fstab.starts_with("# superseded by zbm-installer: UUID=old /boot/efi"));
+        assert!(fstab.ends_with("UUID=5D7E-0C41 /boot/efi vfat umask=0077 0 2\n"));
+    }
+}
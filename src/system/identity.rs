@@ -0,0 +1,296 @@
+//! Hostname, timezone, locale and keymap of the installed system
+//!
+//! A migrated system would otherwise keep the live image's identity. Every
+//! file is written below the target root; `locale-gen`, where the
+//! distribution generates locales, runs inside it.
+
+use super::chroot::ChrootEnv;
+use super::distro::Distro;
+use crate::error::Result;
+use crate::exec::{CommandExecutor, FileOp, SystemExecutor};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Where timezones are, in the target as on the host
+pub const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// Address Debian-style `/etc/hosts` maps the machine's own name to
+const HOSTNAME_ADDRESS: &str = "127.0.1.1";
+
+/// Writes the identity of the installed system
+pub struct SystemIdentity {
+    hostname: Option<String>,
+    timezone: Option<String>,
+    locale: Option<String>,
+    keymap: Option<String>,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl SystemIdentity {
+    /// Create a new identity writer that changes nothing until given values
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            hostname: None,
+            timezone: None,
+            locale: None,
+            keymap: None,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Set the hostname
+    pub fn with_hostname(mut self, hostname: Option<String>) -> Self {
+        self.hostname = hostname;
+        self
+    }
+
+    /// Set the timezone, e.g. `Europe/Berlin`
+    pub fn with_timezone(mut self, timezone: Option<String>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Set the locale, e.g. `de_DE.UTF-8`
+    pub fn with_locale(mut self, locale: Option<String>) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Set the console keymap, e.g. `de-latin1`
+    pub fn with_keymap(mut self, keymap: Option<String>) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Write what was given into the system at `env`'s root
+    ///
+    /// `distro` decides where the locale goes: Debian-likes generate it
+    /// from `/etc/locale.gen` and read `/etc/default/locale`, the others
+    /// read `/etc/locale.conf`.
+    pub fn apply(&self, env: &ChrootEnv, distro: Distro) -> Result<()> {
+        let etc = env.root().join("etc");
+
+        if let Some(hostname) = &self.hostname {
+            log::info!("Setting the hostname to {}", hostname);
+            self.executor.apply(FileOp::write(
+                etc.join("hostname"),
+                format!("{}\n", hostname),
+            ))?;
+            let hosts = etc.join("hosts");
+            let existing = fs::read_to_string(&hosts).unwrap_or_default();
+            self.executor
+                .apply(FileOp::write(&hosts, update_hosts(&existing, hostname)))?;
+        }
+
+        if let Some(timezone) = &self.timezone {
+            log::info!("Setting the timezone to {}", timezone);
+            self.executor.apply(FileOp::symlink(
+                Path::new(ZONEINFO_DIR).join(timezone),
+                etc.join("localtime"),
+            ))?;
+            if is_debian_like(distro) {
+                self.executor.apply(FileOp::write(
+                    etc.join("timezone"),
+                    format!("{}\n", timezone),
+                ))?;
+            }
+        }
+
+        if let Some(locale) = &self.locale {
+            log::info!("Setting the locale to {}", locale);
+            let lang = format!("LANG={}\n", locale);
+            if is_debian_like(distro) {
+                let locale_gen = etc.join("locale.gen");
+                let existing = fs::read_to_string(&locale_gen).unwrap_or_default();
+                self.executor.apply(FileOp::write(
+                    &locale_gen,
+                    update_locale_gen(&existing, locale),
+                ))?;
+                env.run(&["locale-gen"])?;
+                self.executor
+                    .apply(FileOp::write(etc.join("default").join("locale"), lang))?;
+            } else {
+                self.executor
+                    .apply(FileOp::write(etc.join("locale.conf"), lang))?;
+            }
+        }
+
+        if let Some(keymap) = &self.keymap {
+            log::info!("Setting the console keymap to {}", keymap);
+            self.executor.apply(FileOp::write(
+                etc.join("vconsole.conf"),
+                format!("KEYMAP={}\n", keymap),
+            ))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Distributions that generate locales from `/etc/locale.gen`
+fn is_debian_like(distro: Distro) -> bool {
+    matches!(distro, Distro::Debian | Distro::Ubuntu | Distro::MxLinux)
+}
+
+/// `/etc/hosts` with the 127.0.1.1 line naming `hostname`, replacing the
+/// live image's name or added after the loopback lines
+fn update_hosts(existing: &str, hostname: &str) -> String {
+    let entry = format!("{}\t{}", HOSTNAME_ADDRESS, hostname);
+    let mut lines: Vec<String> = existing.lines().map(str::to_string).collect();
+
+    match lines
+        .iter()
+        .position(|line| line.split_whitespace().next() == Some(HOSTNAME_ADDRESS))
+    {
+        Some(i) => lines[i] = entry,
+        None => {
+            if lines.is_empty() {
+                lines.push("127.0.0.1\tlocalhost".to_string());
+            }
+            let after = lines
+                .iter()
+                .rposition(|line| line.starts_with("127."))
+                .map_or(0, |i| i + 1);
+            lines.insert(after, entry);
+        }
+    }
+
+    let mut hosts = lines.join("\n");
+    hosts.push('\n');
+    hosts
+}
+
+/// `/etc/locale.gen` with `locale` enabled: its commented-out line
+/// uncommented, or a new line added
+///
+/// A locale without a charset (`de_DE`) is generated as ISO-8859-1, like
+/// glibc names it.
+fn update_locale_gen(existing: &str, locale: &str) -> String {
+    let charset = locale.split_once('.').map_or("ISO-8859-1", |(_, charset)| {
+        charset.split('@').next().unwrap_or(charset)
+    });
+    let entry = format!("{} {}", locale, charset);
+
+    let mut found = false;
+    let mut lines: Vec<String> = existing
+        .lines()
+        .map(|line| {
+            let uncommented = line.trim_start_matches('#').trim();
+            if uncommented == entry {
+                found = true;
+                entry.clone()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(entry);
+    }
+
+    let mut locale_gen = lines.join("\n");
+    locale_gen.push('\n');
+    locale_gen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    #[test]
+    fn test_hosts_line_is_replaced_or_added() {
+        assert_eq!(
+            update_hosts(
+                "127.0.0.1\tlocalhost\n127.0.1.1\tdebian\n::1\tlocalhost\n",
+                "nas"
+            ),
+            "127.0.0.1\tlocalhost\n127.0.1.1\tnas\n::1\tlocalhost\n"
+        );
+        assert_eq!(
+            update_hosts("127.0.0.1 localhost\n::1 localhost\n", "nas"),
+            "127.0.0.1 localhost\n127.0.1.1\tnas\n::1 localhost\n"
+        );
+        assert_eq!(
+            update_hosts("", "nas"),
+            "127.0.0.1\tlocalhost\n127.0.1.1\tnas\n"
+        );
+    }
+
+    #[test]
+    fn test_locale_gen_enables_the_locale() {
+        let existing = "# en_US.UTF-8 UTF-8\n# de_DE.UTF-8 UTF-8\n";
+        assert_eq!(
+            update_locale_gen(existing, "de_DE.UTF-8"),
+            "# en_US.UTF-8 UTF-8\nde_DE.UTF-8 UTF-8\n"
+        );
+        assert_eq!(update_locale_gen("", "de_DE"), "de_DE ISO-8859-1\n");
+    }
+
+    #[test]
+    fn test_apply_writes_below_the_root() {
+        let identity = |distro| {
+            let executor = Arc::new(RecordingExecutor::new());
+            let env = ChrootEnv::new("/mnt/target", true).with_executor(executor.clone());
+            SystemIdentity::new(true)
+                .with_executor(executor.clone())
+                .with_hostname(Some("nas".to_string()))
+                .with_timezone(Some("Europe/Berlin".to_string()))
+                .with_locale(Some("de_DE.UTF-8".to_string()))
+                .with_keymap(Some("de-latin1".to_string()))
+                .apply(&env, distro)
+                .unwrap();
+            executor
+        };
+
+        let executor = identity(Distro::Debian);
+        assert_eq!(
+            executor
+                .written(Path::new("/mnt/target/etc/hostname"))
+                .as_deref(),
+            Some("nas\n")
+        );
+        assert!(executor.file_ops().contains(&FileOp::symlink(
+            "/usr/share/zoneinfo/Europe/Berlin",
+            "/mnt/target/etc/localtime",
+        )));
+        assert_eq!(
+            executor
+                .written(Path::new("/mnt/target/etc/timezone"))
+                .as_deref(),
+            Some("Europe/Berlin\n")
+        );
+        assert_eq!(
+            executor
+                .written(Path::new("/mnt/target/etc/default/locale"))
+                .as_deref(),
+            Some("LANG=de_DE.UTF-8\n")
+        );
+        assert_eq!(
+            executor.commands()[0].argv().join(" "),
+            "chroot /mnt/target locale-gen"
+        );
+        assert_eq!(
+            executor
+                .written(Path::new("/mnt/target/etc/vconsole.conf"))
+                .as_deref(),
+            Some("KEYMAP=de-latin1\n")
+        );
+
+        let executor = identity(Distro::Fedora);
+        assert_eq!(
+            executor
+                .written(Path::new("/mnt/target/etc/locale.conf"))
+                .as_deref(),
+            Some("LANG=de_DE.UTF-8\n")
+        );
+        assert!(executor.commands().is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+//! Copying an existing system onto the new pool
+
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, OutputStream, SystemExecutor};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Pseudo and volatile filesystems whose contents are never copied
+///
+/// Only their contents are excluded so the directories still exist as mount
+/// points on the new system.
+const VOLATILE_DIRS: &[&str] = &["/proc", "/sys", "/dev", "/run", "/tmp"];
+
+/// rsync exit code for "some files vanished before they could be transferred"
+const RSYNC_VANISHED: i32 = 24;
+
+/// Copies a running system into the mounted boot environment with rsync
+pub struct SystemMigration {
+    source: PathBuf,
+    target: PathBuf,
+    exclude_paths: Vec<PathBuf>,
+    copy_home: bool,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl SystemMigration {
+    /// Copy `source` into `target`
+    pub fn new(source: impl Into<PathBuf>, target: impl Into<PathBuf>, dry_run: bool) -> Self {
+        Self {
+            source: source.into(),
+            target: target.into(),
+            exclude_paths: Vec::new(),
+            copy_home: true,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Skip these paths (absolute on the source system, or below `source`)
+    pub fn with_excludes(mut self, paths: &[PathBuf]) -> Self {
+        self.exclude_paths = paths.to_vec();
+        self
+    }
+
+    /// Whether to copy the contents of `/home`
+    pub fn with_copy_home(mut self, copy_home: bool) -> Self {
+        self.copy_home = copy_home;
+        self
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// rsync exclude patterns, anchored at the source root
+    pub fn excludes(&self) -> Vec<String> {
+        let mut patterns: Vec<String> = VOLATILE_DIRS
+            .iter()
+            .map(|dir| format!("{}/*", dir))
+            .collect();
+
+        // Never copy the target into itself
+        if let Ok(rel) = self.target.strip_prefix(&self.source) {
+            if !rel.as_os_str().is_empty() {
+                patterns.push(anchored(rel));
+            }
+        }
+
+        if !self.copy_home {
+            patterns.push("/home/*".to_string());
+        }
+
+        for path in &self.exclude_paths {
+            patterns.push(anchored(path.strip_prefix(&self.source).unwrap_or(path)));
+        }
+
+        patterns
+    }
+
+    /// The rsync invocation
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new("rsync");
+        cmd.args([
+            "--archive",
+            "--hard-links",
+            "--acls",
+            "--xattrs",
+            "--numeric-ids",
+            // Scan the whole tree up front so the percentage is meaningful
+            "--no-inc-recursive",
+            "--info=progress2",
+        ]);
+        for pattern in self.excludes() {
+            cmd.arg(format!("--exclude={}", pattern));
+        }
+        cmd.arg(dir_arg(&self.source)).arg(dir_arg(&self.target));
+        cmd
+    }
+
+    /// Run the copy, calling `on_progress` with each new overall percentage
+    ///
+    /// In dry-run mode the command is only logged.
+    pub fn run(&self, on_progress: &(dyn Fn(u8) + Sync)) -> Result<()> {
+        log::info!(
+            "Copying {} to {}",
+            self.source.display(),
+            self.target.display()
+        );
+
+        let (tx, rx) = mpsc::channel();
+        let result = thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut last = None;
+                for percent in rx {
+                    if last != Some(percent) {
+                        last = Some(percent);
+                        on_progress(percent);
+                    }
+                }
+            });
+
+            // The sink owns the sender; dropping the options ends the loop above
+            let options = ExecOptions::new()
+                .discard_stdout()
+                .on_line(move |stream, line| {
+                    if let Some(percent) = parse_progress(stream, line) {
+                        let _ = tx.send(percent);
+                    }
+                });
+            self.executor.execute_with(&mut self.command(), &options)
+        });
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(InstallerError::CommandFailed { code, stderr, .. }) if code == RSYNC_VANISHED => {
+                log::warn!("Some files vanished during the copy: {}", stderr.trim_end());
+                Ok(())
+            }
+            Err(InstallerError::CommandFailed { cmd, code, stderr }) => {
+                Err(InstallerError::SystemError(format!(
+                    "System migration failed (rsync exit {}): {}\n{}",
+                    code,
+                    cmd,
+                    stderr.trim_end()
+                )))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Overall percentage from an rsync `--info=progress2` line
+///
+/// e.g. `  1,238,099,968  45%  123.45MB/s    0:00:10 (xfr#123, to-chk=10/200)`
+pub fn parse_progress(stream: OutputStream, line: &str) -> Option<u8> {
+    if stream != OutputStream::Stdout {
+        return None;
+    }
+
+    line.split_whitespace()
+        .nth(1)
+        .and_then(|field| field.strip_suffix('%'))
+        .and_then(|percent| percent.parse::<u8>().ok())
+        .map(|percent| percent.min(100))
+}
+
+/// `path` as an exclude pattern anchored at the transfer root
+fn anchored(path: &Path) -> String {
+    let rel = path.strip_prefix("/").unwrap_or(path);
+    format!("/{}", rel.display())
+}
+
+/// Directory argument with a trailing slash, so rsync copies its contents
+fn dir_arg(path: &Path) -> String {
+    let path = path.display().to_string();
+    if path.ends_with('/') {
+        path
+    } else {
+        format!("{}/", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_command_excludes() {
+        let migration = SystemMigration::new("/", "/mnt", true)
+            .with_excludes(&[PathBuf::from("/var/cache"), PathBuf::from("srv/scratch")])
+            .with_copy_home(false);
+        let args: Vec<String> = migration
+            .command()
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect();
+
+        for flag in [
+            "--archive",
+            "--hard-links",
+            "--acls",
+            "--xattrs",
+            "--numeric-ids",
+            "--info=progress2",
+        ] {
+            assert!(args.iter().any(|a| a == flag), "missing {flag}");
+        }
+        for exclude in [
+            "/proc/*",
+            "/sys/*",
+            "/dev/*",
+            "/run/*",
+            "/tmp/*",
+            "/mnt",
+            "/home/*",
+            "/var/cache",
+            "/srv/scratch",
+        ] {
+            assert!(
+                args.contains(&format!("--exclude={}", exclude)),
+                "missing exclude {exclude}: {args:?}"
+            );
+        }
+        assert_eq!(&args[args.len() - 2..], ["/", "/mnt/"]);
+    }
+
+    #[test]
+    fn test_excludes_relative_to_source_root() {
+        let migration = SystemMigration::new("/mnt/old", "/mnt/new", true)
+            .with_excludes(&[PathBuf::from("/mnt/old/var/log")]);
+        let excludes = migration.excludes();
+
+        assert!(excludes.contains(&"/var/log".to_string()));
+        // The target lies outside the source, so there's nothing to exclude for it
+        assert!(!excludes.iter().any(|e| e.contains("new")));
+        assert!(!excludes.contains(&"/home/*".to_string()));
+    }
+
+    #[test]
+    fn test_parse_progress() {
+        let line = "  1,238,099,968  45%  123.45MB/s    0:00:10 (xfr#123, to-chk=10/200)";
+        assert_eq!(parse_progress(OutputStream::Stdout, line), Some(45));
+        assert_eq!(
+            parse_progress(
+                OutputStream::Stdout,
+                "          0   0%    0.00kB/s    0:00:00"
+            ),
+            Some(0)
+        );
+        assert_eq!(parse_progress(OutputStream::Stderr, line), None);
+        assert_eq!(
+            parse_progress(OutputStream::Stdout, "sending incremental file list"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dry_run_runs_nothing() {
+        let seen = Mutex::new(Vec::new());
+        SystemMigration::new("/nonexistent/source", "/nonexistent/target", true)
+            .run(&|percent| seen.lock().unwrap().push(percent))
+            .unwrap();
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failure_surfaces_stderr() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program(
+            "rsync",
+            23,
+            "rsync: write failed: No space left on device (28)",
+        );
+
+        let err = SystemMigration::new("/", "/mnt", false)
+            .with_executor(executor.clone())
+            .run(&|_| {})
+            .unwrap_err();
+        assert!(err.to_string().contains("No space left on device"));
+        assert!(err.to_string().contains("exit 23"));
+        assert_eq!(executor.commands()[0].program, "rsync");
+    }
+
+    #[test]
+    fn test_vanished_files_tolerated() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("rsync", RSYNC_VANISHED, "file has vanished: \"/var/tmp/x\"");
+
+        SystemMigration::new("/", "/mnt", false)
+            .with_executor(executor)
+            .run(&|_| {})
+            .unwrap();
+    }
+}
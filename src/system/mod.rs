@@ -1,10 +1,24 @@
 //! System utilities: distribution detection, package management, etc.
 
+pub mod bootstrap;
+pub mod chroot;
 pub mod distro;
+pub mod fstab;
+pub mod identity;
+pub mod migrate;
+pub mod network;
 pub mod packages;
+pub mod users;
 
-pub use distro::Distro;
+pub use bootstrap::Bootstrap;
+pub use chroot::ChrootEnv;
+pub use distro::{Distro, DistroInfo};
+pub use fstab::FstabGenerator;
+pub use identity::SystemIdentity;
+pub use migrate::SystemMigration;
+pub use network::NetworkCarryOver;
 pub use packages::PackageInstaller;
+pub use users::UserAccounts;
 
 use crate::error::Result;
 use std::process::Command;
@@ -19,6 +33,25 @@ pub fn is_uefi() -> bool {
     std::path::Path::new("/sys/firmware/efi").exists()
 }
 
+/// The `SecureBoot` EFI variable in efivarfs
+pub const SECURE_BOOT_VARIABLE: &str =
+    "/sys/firmware/efi/efivars/SecureBoot-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+
+/// Check if the firmware enforces Secure Boot
+///
+/// None when it can't be told: booted without UEFI, or efivarfs not mounted.
+pub fn secure_boot_enabled() -> Option<bool> {
+    std::fs::read(SECURE_BOOT_VARIABLE)
+        .ok()
+        .and_then(|data| parse_secure_boot(&data))
+}
+
+/// Parse the `SecureBoot` variable: four attribute bytes, then one byte
+/// that is 1 when Secure Boot is enabled
+fn parse_secure_boot(data: &[u8]) -> Option<bool> {
+    data.get(4).map(|&value| value == 1)
+}
+
 /// Get system memory in KB
 pub fn get_system_memory_kb() -> Result<u64> {
     let meminfo = std::fs::read_to_string("/proc/meminfo")?;
@@ -35,6 +68,49 @@ pub fn get_system_memory_kb() -> Result<u64> {
     Ok(0)
 }
 
+/// Read a line from the terminal without echoing it
+///
+/// Falls back to a plain read when stdin is not a terminal (e.g. piped input).
+pub fn read_secret(prompt: &str) -> Result<String> {
+    use std::io::{BufRead, Write};
+
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+
+    let mut line = String::new();
+    without_echo(|| std::io::stdin().lock().read_line(&mut line))?;
+    Ok(line.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Run `read` with the terminal on stdin not echoing what's typed
+///
+/// The newline that ends the line is still echoed. Runs `read` as it is when
+/// stdin is not a terminal.
+pub fn without_echo<T>(read: impl FnOnce() -> T) -> T {
+    let fd = libc::STDIN_FILENO;
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    // SAFETY: tcgetattr only writes to the termios struct it is given
+    let original = (unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } == 0)
+        .then(|| unsafe { termios.assume_init() });
+
+    if let Some(original) = original {
+        let mut silent = original;
+        silent.c_lflag &= !libc::ECHO;
+        silent.c_lflag |= libc::ECHONL;
+        // SAFETY: silent is a valid termios derived from the current settings
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &silent) };
+    }
+
+    let result = read();
+
+    if let Some(original) = original {
+        // SAFETY: restores the settings read above
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    }
+
+    result
+}
+
 /// Sync filesystems
 pub fn sync() -> Result<()> {
     Command::new("sync").status()?;
@@ -51,6 +127,13 @@ mod tests {
         let _ = is_uefi();
     }
 
+    #[test]
+    fn test_parse_secure_boot() {
+        assert_eq!(parse_secure_boot(&[6, 0, 0, 0, 1]), Some(true));
+        assert_eq!(parse_secure_boot(&[6, 0, 0, 0, 0]), Some(false));
+        assert_eq!(parse_secure_boot(&[6, 0, 0, 0]), None);
+    }
+
     #[test]
     fn test_get_system_memory() {
         let result = get_system_memory_kb();
@@ -0,0 +1,327 @@
+//! Carrying the network configuration over to a migrated system
+//!
+//! Without it a migration can come up with no network: the configuration
+//! was excluded from the copy, or the interfaces are named differently on
+//! the next boot. The configuration of every network stack the source uses
+//! is copied again with its permissions (NetworkManager keyfiles hold
+//! secrets and must stay 0600), and interface names can be pinned to the
+//! current MAC addresses with systemd link files.
+
+use crate::error::Result;
+use crate::exec::{CommandExecutor, FileOp, SystemExecutor};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where the kernel lists network interfaces
+pub const SYS_NET_DIR: &str = "/sys/class/net";
+
+/// Directory the link files pinning interface names are written to
+const LINK_DIR: &str = "etc/systemd/network";
+
+/// A network configuration system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkStack {
+    /// Ubuntu's netplan
+    Netplan,
+    /// NetworkManager keyfiles
+    NetworkManager,
+    /// Debian's ifupdown
+    Ifupdown,
+    /// systemd-networkd units
+    Networkd,
+}
+
+impl NetworkStack {
+    /// Every stack, in the order they are carried over
+    pub const ALL: [NetworkStack; 4] = [
+        NetworkStack::Netplan,
+        NetworkStack::NetworkManager,
+        NetworkStack::Ifupdown,
+        NetworkStack::Networkd,
+    ];
+
+    /// Files and directories holding the configuration, relative to the root
+    pub fn config_paths(&self) -> &'static [&'static str] {
+        match self {
+            Self::Netplan => &["etc/netplan"],
+            Self::NetworkManager => &["etc/NetworkManager/system-connections"],
+            Self::Ifupdown => &["etc/network/interfaces", "etc/network/interfaces.d"],
+            Self::Networkd => &["etc/systemd/network"],
+        }
+    }
+}
+
+impl std::fmt::Display for NetworkStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Netplan => write!(f, "netplan"),
+            Self::NetworkManager => write!(f, "NetworkManager"),
+            Self::Ifupdown => write!(f, "ifupdown"),
+            Self::Networkd => write!(f, "systemd-networkd"),
+        }
+    }
+}
+
+/// A physical network interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    /// Kernel name, e.g. `enp3s0`
+    pub name: String,
+    /// MAC address
+    pub mac: String,
+}
+
+/// The physical interfaces below `net_dir`, usually [`SYS_NET_DIR`], by name
+///
+/// Virtual interfaces (loopback, bridges, VPNs) have no `device` link and
+/// are left out, as are interfaces without a MAC address.
+pub fn detect_interfaces(net_dir: &Path) -> Vec<Interface> {
+    let Ok(entries) = fs::read_dir(net_dir) else {
+        return Vec::new();
+    };
+    let mut interfaces: Vec<Interface> = entries
+        .flatten()
+        .filter(|entry| entry.path().join("device").exists())
+        .filter_map(|entry| {
+            let mac = fs::read_to_string(entry.path().join("address")).ok()?;
+            let mac = mac.trim();
+            (!mac.is_empty() && mac != "00:00:00:00:00:00").then(|| Interface {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                mac: mac.to_string(),
+            })
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// What [`NetworkCarryOver::run`] carried over
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkSummary {
+    /// Stacks found on the source
+    pub stacks: Vec<NetworkStack>,
+    /// Files copied, as absolute paths in the installed system
+    pub files: Vec<PathBuf>,
+    /// Interfaces whose names were pinned
+    pub pinned: Vec<Interface>,
+}
+
+/// Copies the source's network configuration into the target
+pub struct NetworkCarryOver {
+    source: PathBuf,
+    target: PathBuf,
+    pinned: Vec<Interface>,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl NetworkCarryOver {
+    /// Carry the configuration of the system at `source` over to `target`
+    pub fn new(source: impl Into<PathBuf>, target: impl Into<PathBuf>, dry_run: bool) -> Self {
+        Self {
+            source: source.into(),
+            target: target.into(),
+            pinned: Vec::new(),
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Keep these interfaces' names on the installed system
+    pub fn with_pinned_interfaces(mut self, interfaces: Vec<Interface>) -> Self {
+        self.pinned = interfaces;
+        self
+    }
+
+    /// The stacks the source has configuration for
+    pub fn detect(&self) -> Vec<NetworkStack> {
+        NetworkStack::ALL
+            .into_iter()
+            .filter(|stack| !self.files(*stack).is_empty())
+            .collect()
+    }
+
+    /// Copy the configuration and write the link files
+    ///
+    /// In dry-run mode the changes are only logged.
+    pub fn run(&self) -> Result<NetworkSummary> {
+        let mut summary = NetworkSummary::default();
+
+        for stack in self.detect() {
+            log::info!("Carrying over the {} configuration", stack);
+            for rel in self.files(stack) {
+                let from = self.source.join(&rel);
+                let to = self.target.join(&rel);
+                if let Some(parent) = to.parent() {
+                    self.executor.apply(FileOp::create_dir(parent))?;
+                }
+                self.executor.apply(FileOp::copy(&from, &to))?;
+                let mode = fs::metadata(&from)?.permissions().mode() & 0o7777;
+                self.executor.apply(FileOp::set_mode(&to, mode))?;
+                summary.files.push(Path::new("/").join(rel));
+            }
+            summary.stacks.push(stack);
+        }
+        if summary.stacks.is_empty() {
+            log::warn!(
+                "No network configuration found in {}",
+                self.source.display()
+            );
+        }
+
+        if !self.pinned.is_empty() {
+            let dir = self.target.join(LINK_DIR);
+            self.executor.apply(FileOp::create_dir(&dir))?;
+            for interface in &self.pinned {
+                log::info!("Pinning {} to {}", interface.name, interface.mac);
+                self.executor.apply(FileOp::write(
+                    dir.join(format!("10-zbm-{}.link", interface.name)),
+                    link_file(interface),
+                ))?;
+            }
+            summary.pinned = self.pinned.clone();
+        }
+
+        Ok(summary)
+    }
+
+    /// Regular files of `stack`'s configuration, relative to the source root
+    fn files(&self, stack: NetworkStack) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for path in stack.config_paths() {
+            collect_files(&self.source, Path::new(path), &mut files);
+        }
+        files
+    }
+}
+
+/// Add the regular files at or below `rel` in `root` to `files`, sorted
+/// within each directory
+fn collect_files(root: &Path, rel: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(metadata) = fs::symlink_metadata(root.join(rel)) else {
+        return;
+    };
+    if metadata.is_file() {
+        files.push(rel.to_path_buf());
+    } else if metadata.is_dir() {
+        let Ok(entries) = fs::read_dir(root.join(rel)) else {
+            return;
+        };
+        let mut names: Vec<_> = entries.flatten().map(|entry| entry.file_name()).collect();
+        names.sort();
+        for name in names {
+            collect_files(root, &rel.join(name), files);
+        }
+    }
+}
+
+/// systemd link file giving the interface with `interface`'s MAC its name
+fn link_file(interface: &Interface) -> String {
+    format!(
+        "# Written by zbm-installer to keep the name from before the migration\n\
+         [Match]\n\
+         MACAddress={}\n\
+         \n\
+         [Link]\n\
+         Name={}\n",
+        interface.mac, interface.name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    fn write(root: &Path, rel: &str, contents: &str, mode: u32) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_configuration_is_copied_with_its_mode() {
+        let source = tempfile::tempdir().unwrap();
+        write(
+            source.path(),
+            "etc/netplan/01-netcfg.yaml",
+            "network: {}\n",
+            0o600,
+        );
+        write(
+            source.path(),
+            "etc/NetworkManager/system-connections/home.nmconnection",
+            "[wifi-security]\npsk=secret\n",
+            0o600,
+        );
+        fs::create_dir_all(source.path().join("etc/systemd/network")).unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        let summary = NetworkCarryOver::new(source.path(), "/mnt", true)
+            .with_executor(executor.clone())
+            .run()
+            .unwrap();
+
+        assert_eq!(
+            summary.stacks,
+            [NetworkStack::Netplan, NetworkStack::NetworkManager]
+        );
+        assert_eq!(
+            summary.files,
+            [
+                PathBuf::from("/etc/netplan/01-netcfg.yaml"),
+                PathBuf::from("/etc/NetworkManager/system-connections/home.nmconnection"),
+            ]
+        );
+        let keyfile = Path::new("/mnt/etc/NetworkManager/system-connections/home.nmconnection");
+        let ops = executor.file_ops();
+        assert!(ops.contains(&FileOp::copy(
+            source
+                .path()
+                .join("etc/NetworkManager/system-connections/home.nmconnection"),
+            keyfile
+        )));
+        assert!(ops.contains(&FileOp::set_mode(keyfile, 0o600)));
+        assert!(summary.pinned.is_empty());
+    }
+
+    #[test]
+    fn test_interface_names_are_pinned() {
+        let net = tempfile::tempdir().unwrap();
+        write(net.path(), "enp3s0/address", "52:54:00:12:34:56\n", 0o644);
+        fs::create_dir(net.path().join("enp3s0/device")).unwrap();
+        write(net.path(), "lo/address", "00:00:00:00:00:00\n", 0o644);
+        write(net.path(), "br0/address", "52:54:00:ab:cd:ef\n", 0o644);
+
+        let interfaces = detect_interfaces(net.path());
+        assert_eq!(
+            interfaces,
+            [Interface {
+                name: "enp3s0".to_string(),
+                mac: "52:54:00:12:34:56".to_string(),
+            }]
+        );
+
+        let executor = Arc::new(RecordingExecutor::new());
+        let summary = NetworkCarryOver::new("/nonexistent", "/mnt", true)
+            .with_executor(executor.clone())
+            .with_pinned_interfaces(interfaces.clone())
+            .run()
+            .unwrap();
+        assert!(summary.stacks.is_empty());
+        assert_eq!(summary.pinned, interfaces);
+
+        let link = executor
+            .written(Path::new("/mnt/etc/systemd/network/10-zbm-enp3s0.link"))
+            .unwrap();
+        assert!(link.contains("[Match]\nMACAddress=52:54:00:12:34:56\n"));
+        assert!(link.contains("[Link]\nName=enp3s0\n"));
+    }
+}
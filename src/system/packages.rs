@@ -1,13 +1,39 @@
 //! Package installation management
 
 use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, ExecResult, FileOp, SystemExecutor};
 use crate::system::distro::Distro;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+
+/// Where the kernel's release is read from, as `uname -r` prints it
+const OSRELEASE_PATH: &str = "/proc/sys/kernel/osrelease";
+
+/// Where the modules and the headers (`build`) of each kernel are
+const MODULES_DIR: &str = "/lib/modules";
+
+/// Where DKMS keeps its builds
+const DKMS_DIR: &str = "/var/lib/dkms";
+
+/// Lines of a failed DKMS build log quoted in the error
+const MAKE_LOG_TAIL: usize = 20;
+
+/// Name of the local repository in the package manager's configuration
+const LOCAL_REPO: &str = "zbm-installer-local";
+
+/// apt source list pointing at the local repository while it is in use
+const LOCAL_APT_SOURCE: &str = "/etc/apt/sources.list.d/zbm-installer-local.list";
 
 /// Package installer
 pub struct PackageInstaller {
     distro: Distro,
+    kernel_release: String,
+    local_repo: Option<PathBuf>,
+    #[allow(dead_code)] // Enforced by the executor
     dry_run: bool,
+    executor: Arc<dyn CommandExecutor>,
 }
 
 impl PackageInstaller {
@@ -22,38 +48,58 @@ impl PackageInstaller {
             )));
         }
 
-        Ok(Self { distro, dry_run })
+        Ok(Self {
+            distro,
+            kernel_release: fs::read_to_string(OSRELEASE_PATH)?.trim().to_string(),
+            local_repo: None,
+            dry_run,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        })
     }
 
-    /// Execute a command
-    fn execute(&self, cmd: &mut Command) -> Result<std::process::Output> {
-        let cmd_str = format!("{:?}", cmd);
-
-        if self.dry_run {
-            log::info!("[DRY RUN] Would execute: {}", cmd_str);
-            return Ok(std::process::Output {
-                status: std::process::ExitStatus::default(),
-                stdout: Vec::new(),
-                stderr: Vec::new(),
-            });
+    /// Install for `distro` running kernel `release`, instead of the host's
+    pub fn for_distro(distro: Distro, release: impl Into<String>, dry_run: bool) -> Self {
+        Self {
+            distro,
+            kernel_release: release.into(),
+            local_repo: None,
+            dry_run,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
         }
+    }
 
-        log::debug!("Executing: {}", cmd_str);
-        let output = cmd.output()?;
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(InstallerError::SystemError(format!(
-                "Package command failed: {}\n{}",
-                cmd_str, stderr
-            )));
-        }
+    /// Install from the repository in `dir` instead of the network: a
+    /// flat apt repository, an rpm repository or a pacman package cache
+    ///
+    /// It is only configured while packages are installed.
+    pub fn with_local_repo(mut self, dir: Option<PathBuf>) -> Self {
+        self.local_repo = dir;
+        self
+    }
 
-        Ok(output)
+    /// Execute a command, reporting failures as system errors
+    fn execute(&self, cmd: &mut Command) -> Result<ExecResult> {
+        self.executor.execute_as(
+            cmd,
+            &ExecOptions::default(),
+            InstallerError::package_command,
+        )
     }
 
     /// Update package database
+    ///
+    /// Nothing is updated with a local repository: there is no network, and
+    /// installing reads the repository's index.
     pub fn update(&self) -> Result<()> {
+        if self.local_repo.is_some() {
+            return Ok(());
+        }
         log::info!("Updating package database");
 
         match self.distro {
@@ -63,8 +109,21 @@ impl PackageInstaller {
             Distro::Arch => {
                 self.execute(Command::new("pacman").arg("-Sy"))?;
             }
+            Distro::Void => {
+                self.execute(Command::new("xbps-install").arg("-S"))?;
+            }
+            Distro::OpenSuse => {
+                self.execute(
+                    Command::new("zypper")
+                        .arg("--non-interactive")
+                        .arg("refresh"),
+                )?;
+            }
+            Distro::Alpine => {
+                self.execute(Command::new("apk").arg("update"))?;
+            }
             _ => {
-                // Fedora and others don't need explicit update
+                // Fedora and Gentoo refresh as they install; NixOS doesn't install here
             }
         }
 
@@ -79,23 +138,80 @@ impl PackageInstaller {
 
         log::info!("Installing packages: {}", packages.join(", "));
 
-        let cmd_parts = self.distro.install_command(packages);
-        let mut cmd = Command::new(&cmd_parts[0]);
-        for arg in &cmd_parts[1..] {
-            cmd.arg(arg);
-        }
+        let mut cmd_parts = self.distro.install_command(packages)?;
+        let Some(dir) = &self.local_repo else {
+            self.execute(Command::new(&cmd_parts[0]).args(&cmd_parts[1..]))?;
+            return Ok(());
+        };
 
-        self.execute(&mut cmd)?;
+        log::info!("Installing from the local repository {}", dir.display());
+        let url = format!("file://{}", dir.display());
+        match self.distro {
+            Distro::Debian | Distro::Ubuntu | Distro::MxLinux => {
+                self.executor.apply(FileOp::write(
+                    LOCAL_APT_SOURCE,
+                    format!("deb [trusted=yes] {} ./\n", url),
+                ))?;
+                // Read only the local repository's index; the others are
+                // unreachable
+                let result = self
+                    .execute(Command::new("apt-get").args([
+                        "update",
+                        "-o",
+                        &format!("Dir::Etc::sourcelist={}", LOCAL_APT_SOURCE),
+                        "-o",
+                        "Dir::Etc::sourceparts=-",
+                        "-o",
+                        "APT::Get::List-Cleanup=0",
+                    ]))
+                    .and_then(|_| self.execute(Command::new(&cmd_parts[0]).args(&cmd_parts[1..])));
+                self.executor.apply(FileOp::remove(LOCAL_APT_SOURCE))?;
+                result?;
+            }
+            Distro::Fedora => {
+                cmd_parts.splice(
+                    1..1,
+                    [
+                        format!("--repofrompath={},{}", LOCAL_REPO, url),
+                        format!("--repo={}", LOCAL_REPO),
+                        "--nogpgcheck".to_string(),
+                    ],
+                );
+                self.execute(Command::new(&cmd_parts[0]).args(&cmd_parts[1..]))?;
+            }
+            Distro::Arch => {
+                cmd_parts.splice(1..1, ["--cachedir".to_string(), dir.display().to_string()]);
+                self.execute(Command::new(&cmd_parts[0]).args(&cmd_parts[1..]))?;
+            }
+            _ => {
+                return Err(InstallerError::Unsupported(format!(
+                    "Installing from a local repository is not supported on {}",
+                    self.distro
+                )))
+            }
+        }
         Ok(())
     }
 
     /// Install ZFS packages
+    ///
+    /// Where DKMS builds the module, the running kernel's headers are
+    /// installed first and the module is checked to load afterwards: a
+    /// failed DKMS build doesn't fail the package install.
     pub fn install_zfs(&self) -> Result<()> {
+        if self.distro.builds_zfs_with_dkms() {
+            self.ensure_kernel_headers()?;
+        }
+
         log::info!("Installing ZFS packages");
 
         let packages = self.distro.zfs_packages();
         self.install(&packages)?;
 
+        if self.distro.builds_zfs_with_dkms() {
+            self.verify_zfs_module()?;
+        }
+
         Ok(())
     }
 
@@ -103,12 +219,89 @@ impl PackageInstaller {
     pub fn install_zbm_deps(&self) -> Result<()> {
         log::info!("Installing ZFSBootMenu dependencies");
 
-        let packages = self.distro.zbm_packages();
+        let packages = self.distro.zbm_packages(&self.kernel_release);
+        let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
         self.install(&packages)?;
 
         Ok(())
     }
 
+    /// Install the headers of the running kernel unless they are there
+    fn ensure_kernel_headers(&self) -> Result<()> {
+        let build = Path::new(MODULES_DIR)
+            .join(&self.kernel_release)
+            .join("build");
+        if build.exists() {
+            return Ok(());
+        }
+        let Some(package) = self.distro.kernel_headers_package(&self.kernel_release) else {
+            return Ok(());
+        };
+
+        log::info!(
+            "Installing the headers of kernel {} for DKMS",
+            self.kernel_release
+        );
+        self.install(&[&package])
+    }
+
+    /// Check DKMS built the ZFS module for the running kernel and that it
+    /// loads
+    ///
+    /// Nothing is checked in dry-run mode, where nothing was built.
+    fn verify_zfs_module(&self) -> Result<()> {
+        log::info!(
+            "Checking the ZFS module was built for {}",
+            self.kernel_release
+        );
+
+        let status = self
+            .executor
+            .execute(Command::new("dkms").args(["status", "zfs"]))
+            .ok();
+        if status.as_ref().is_some_and(ExecResult::is_skipped) {
+            return Ok(());
+        }
+        let built = status
+            .and_then(|status| status.stdout_string())
+            .is_some_and(|status| dkms_built(&status, &self.kernel_release))
+            || has_zfs_module(&Path::new(MODULES_DIR).join(&self.kernel_release));
+        if !built {
+            return Err(self.module_error(format!(
+                "DKMS did not build the ZFS module for kernel {}",
+                self.kernel_release
+            )));
+        }
+
+        self.executor
+            .execute(Command::new("modprobe").arg("zfs"))
+            .map_err(|e| self.module_error(format!("The ZFS module does not load: {}", e)))?;
+        Ok(())
+    }
+
+    /// Error for a ZFS module that didn't build or load, ending with the
+    /// DKMS build log
+    fn module_error(&self, message: String) -> InstallerError {
+        let log = latest_make_log(&Path::new(DKMS_DIR).join("zfs"));
+        let tail = log
+            .as_deref()
+            .and_then(|log| fs::read_to_string(log).ok())
+            .map(|contents| log_tail(&contents, MAKE_LOG_TAIL));
+        InstallerError::SystemError(match (log, tail) {
+            (Some(log), Some(tail)) => format!(
+                "{}; are the headers for kernel {} installed? End of {}:\n{}",
+                message,
+                self.kernel_release,
+                log.display(),
+                tail
+            ),
+            _ => format!(
+                "{}; are the headers for kernel {} installed?",
+                message, self.kernel_release
+            ),
+        })
+    }
+
     /// Check if a package is installed
     pub fn is_installed(&self, package: &str) -> bool {
         let result = match self.distro {
@@ -117,16 +310,80 @@ impl PackageInstaller {
                 Command::new("dpkg").arg("-s").arg(package).output()
             }
             Distro::Arch => Command::new("pacman").arg("-Q").arg(package).output(),
-            Distro::Unknown => return false,
+            Distro::Void => Command::new("xbps-query").arg(package).output(),
+            Distro::OpenSuse => Command::new("rpm").arg("-q").arg(package).output(),
+            Distro::Gentoo => Command::new("portageq")
+                .arg("has_version")
+                .arg("/")
+                .arg(package)
+                .output(),
+            Distro::Alpine => Command::new("apk")
+                .arg("info")
+                .arg("-e")
+                .arg(package)
+                .output(),
+            Distro::NixOs | Distro::Unknown => return false,
         };
 
         result.map(|o| o.status.success()).unwrap_or(false)
     }
 }
 
+/// Whether `dkms status` output lists the ZFS module as installed for
+/// kernel `release`
+///
+/// e.g. `zfs/2.2.2, 6.1.0-18-amd64, x86_64: installed`
+fn dkms_built(status: &str, release: &str) -> bool {
+    status.lines().any(|line| {
+        line.split_once(':').is_some_and(|(module, state)| {
+            module.split(',').any(|field| field.trim() == release)
+                && state.trim().starts_with("installed")
+        })
+    })
+}
+
+/// Whether a ZFS module, compressed or not, is below `modules_dir`
+fn has_zfs_module(modules_dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(modules_dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let path = entry.path();
+        if path.is_symlink() {
+            return false;
+        }
+        if path.is_dir() {
+            return has_zfs_module(&path);
+        }
+        entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name == "zfs.ko" || name.starts_with("zfs.ko."))
+    })
+}
+
+/// The most recently written `make.log` of the module's DKMS builds in
+/// `module_dir`, one directory per version
+fn latest_make_log(module_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(module_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path().join("build").join("make.log"))
+        .filter_map(|log| Some((fs::metadata(&log).ok()?.modified().ok()?, log)))
+        .max()
+        .map(|(_, log)| log)
+}
+
+/// The last `lines` lines of `contents`
+fn log_tail(contents: &str, lines: usize) -> String {
+    let all: Vec<&str> = contents.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exec::RecordingExecutor;
 
     #[test]
     fn test_package_installer_creation() {
@@ -137,4 +394,122 @@ mod tests {
             assert!(installer.dry_run);
         }
     }
+
+    #[test]
+    fn test_dkms_build_is_verified() {
+        let release = "0.0.0-zbm-test";
+        let status = format!("zfs/2.2.2, {}, x86_64: installed\n", release);
+        assert!(dkms_built(&status, release));
+        assert!(!dkms_built(
+            "zfs/2.2.2, 6.1.0-18-amd64, x86_64: installed\n",
+            release
+        ));
+        assert!(!dkms_built(
+            &format!("zfs/2.2.2, {}, x86_64: built\n", release),
+            release
+        ));
+
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("dkms", status);
+        PackageInstaller::for_distro(Distro::Debian, release, false)
+            .with_executor(executor.clone())
+            .install_zfs()
+            .unwrap();
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            argv,
+            [
+                format!("apt-get install -y linux-headers-{}", release),
+                "apt-get install -y zfsutils-linux zfs-dkms".to_string(),
+                "dkms status zfs".to_string(),
+                "modprobe zfs".to_string(),
+            ]
+        );
+
+        // A failed build is caught before anything is wiped
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program("dkms", "");
+        let err = PackageInstaller::for_distro(Distro::Ubuntu, release, false)
+            .with_executor(executor)
+            .install_zfs()
+            .unwrap_err();
+        assert!(err.to_string().contains("did not build"), "{err}");
+    }
+
+    #[test]
+    fn test_local_repository() {
+        let argv = |distro: Distro| {
+            let executor = Arc::new(RecordingExecutor::new());
+            PackageInstaller::for_distro(distro, "6.1.0", false)
+                .with_executor(executor.clone())
+                .with_local_repo(Some(PathBuf::from("/srv/packages")))
+                .install(&["zfs"])
+                .unwrap();
+            let commands: Vec<String> = executor
+                .commands()
+                .iter()
+                .map(|c| c.argv().join(" "))
+                .collect();
+            (commands, executor.file_ops())
+        };
+
+        let (commands, ops) = argv(Distro::Debian);
+        assert_eq!(
+            commands,
+            [
+                "apt-get update -o Dir::Etc::sourcelist=/etc/apt/sources.list.d/zbm-installer-local.list \
+                 -o Dir::Etc::sourceparts=- -o APT::Get::List-Cleanup=0",
+                "apt-get install -y zfs",
+            ]
+        );
+        // The source is gone again afterwards
+        assert_eq!(
+            ops,
+            [
+                FileOp::write(
+                    LOCAL_APT_SOURCE,
+                    "deb [trusted=yes] file:///srv/packages ./\n"
+                ),
+                FileOp::remove(LOCAL_APT_SOURCE),
+            ]
+        );
+
+        assert_eq!(
+            argv(Distro::Fedora).0,
+            [
+                "dnf --repofrompath=zbm-installer-local,file:///srv/packages \
+              --repo=zbm-installer-local --nogpgcheck install -y zfs"
+            ]
+        );
+        assert_eq!(
+            argv(Distro::Arch).0,
+            ["pacman --cachedir /srv/packages -S --noconfirm zfs"]
+        );
+    }
+
+    #[test]
+    fn test_module_files_and_build_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let modules = dir.path().join("modules");
+        fs::create_dir_all(modules.join("updates/dkms")).unwrap();
+        assert!(!has_zfs_module(&modules));
+        fs::write(modules.join("updates/dkms/zfs.ko.zst"), "").unwrap();
+        assert!(has_zfs_module(&modules));
+
+        let dkms = dir.path().join("dkms/zfs");
+        assert_eq!(latest_make_log(&dkms), None);
+        fs::create_dir_all(dkms.join("2.2.2/build")).unwrap();
+        fs::write(dkms.join("2.2.2/build/make.log"), "").unwrap();
+        assert_eq!(
+            latest_make_log(&dkms),
+            Some(dkms.join("2.2.2/build/make.log"))
+        );
+
+        assert_eq!(log_tail("a\nb\nc\n", 2), "b\nc");
+        assert_eq!(log_tail("a\n", 5), "a");
+    }
 }
@@ -0,0 +1,252 @@
+//! Login accounts of the installed system
+//!
+//! A migrated system keeps the source's accounts; these add the ones the
+//! installation was asked for and set their passwords. Passwords are only
+//! handled as crypt(3) hashes: [`hash_password`] hashes what was typed at a
+//! prompt right away, and the hashes reach `chpasswd -e` on stdin, never a
+//! command line, the log or a dry-run plan.
+
+use super::chroot::ChrootEnv;
+use super::distro::Distro;
+use crate::config::{Passphrase, PasswordHash, UserSpec};
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, SystemExecutor};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Hash a password as SHA-512 crypt with a random salt
+///
+/// Runs `openssl passwd` on the host, also in dry runs: it only computes
+/// the hash. The password goes to its stdin.
+pub fn hash_password(password: &Passphrase) -> Result<PasswordHash> {
+    let mut child = Command::new("openssl")
+        .args(["passwd", "-6", "-stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| InstallerError::SystemError(format!("Failed to run openssl: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", password.expose())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(InstallerError::SystemError(format!(
+            "openssl passwd failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    PasswordHash::new(String::from_utf8_lossy(&output.stdout).trim())
+}
+
+/// Creates accounts and sets passwords in the installed system
+pub struct UserAccounts {
+    users: Vec<UserSpec>,
+    root_password: Option<PasswordHash>,
+    lock_root: bool,
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl UserAccounts {
+    /// Create a new account writer that changes nothing until given accounts
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            users: Vec::new(),
+            root_password: None,
+            lock_root: false,
+            executor: Arc::new(SystemExecutor::new(dry_run)),
+        }
+    }
+
+    /// Use a custom command executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Set the accounts to create
+    pub fn with_users(mut self, users: Vec<UserSpec>) -> Self {
+        self.users = users;
+        self
+    }
+
+    /// Set a new root password
+    pub fn with_root_password(mut self, hash: Option<PasswordHash>) -> Self {
+        self.root_password = hash;
+        self
+    }
+
+    /// Lock the root account
+    pub fn with_lock_root(mut self, lock_root: bool) -> Self {
+        self.lock_root = lock_root;
+        self
+    }
+
+    /// Create the accounts in the system at `env`'s root
+    ///
+    /// Accounts the system already has, such as the source's on a
+    /// migration, are added to the groups and given the shell instead.
+    /// `distro` decides the administrators' group.
+    pub fn apply(&self, env: &ChrootEnv, distro: Distro) -> Result<()> {
+        let existing = existing_users(env.root());
+
+        for user in &self.users {
+            let mut groups = user.groups.clone();
+            if user.admin {
+                groups.push(distro.admin_group().to_string());
+            }
+            let groups = groups.join(",");
+            let shell = user.shell.as_ref().map(|shell| shell.to_string_lossy());
+
+            let mut args = Vec::new();
+            if existing.contains(&user.name) {
+                log::info!("User {} already exists, updating it", user.name);
+                if !groups.is_empty() {
+                    args.extend(["-a", "-G", &groups]);
+                }
+                if let Some(shell) = &shell {
+                    args.extend(["-s", shell]);
+                }
+                if !args.is_empty() {
+                    args.insert(0, "usermod");
+                    args.push(&user.name);
+                    env.run(&args)?;
+                }
+            } else {
+                log::info!("Creating user {}", user.name);
+                args.extend(["useradd", "-m"]);
+                if !groups.is_empty() {
+                    args.extend(["-G", &groups]);
+                }
+                if let Some(shell) = &shell {
+                    args.extend(["-s", shell]);
+                }
+                args.push(&user.name);
+                env.run(&args)?;
+            }
+            if user.password_hash.is_none() {
+                log::warn!("User {} has no password and can't log in yet", user.name);
+            }
+        }
+
+        let passwords: String = self
+            .users
+            .iter()
+            .filter_map(|user| Some((user.name.as_str(), user.password_hash.as_ref()?)))
+            .chain(self.root_password.as_ref().map(|hash| ("root", hash)))
+            .map(|(name, hash)| format!("{}:{}\n", name, hash.expose()))
+            .collect();
+        if !passwords.is_empty() {
+            log::info!("Setting passwords");
+            env.run_with(&["chpasswd", "-e"], &ExecOptions::new().stdin(passwords))?;
+        }
+
+        if self.lock_root {
+            log::info!("Locking the root account");
+            env.run(&["passwd", "-l", "root"])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Names of the accounts in `/etc/passwd` below `root`; none if it can't
+/// be read, as in a dry run
+fn existing_users(root: &Path) -> HashSet<String> {
+    fs::read_to_string(root.join("etc").join("passwd"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split(':').next())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_accounts_are_created_with_hashed_passwords() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("etc")).unwrap();
+        fs::write(
+            root.path().join("etc/passwd"),
+            "root:x:0:0:root:/root:/bin/bash\nbob:x:1000:1000::/home/bob:/bin/bash\n",
+        )
+        .unwrap();
+
+        let executor = Arc::new(RecordingExecutor::new());
+        let env = ChrootEnv::new(root.path(), true).with_executor(executor.clone());
+        UserAccounts::new(true)
+            .with_executor(executor.clone())
+            .with_users(vec![
+                UserSpec {
+                    groups: vec!["video".to_string()],
+                    shell: Some(PathBuf::from("/bin/zsh")),
+                    admin: true,
+                    password_hash: Some(PasswordHash::new("$6$salt$alice").unwrap()),
+                    ..UserSpec::new("alice")
+                },
+                UserSpec {
+                    admin: true,
+                    ..UserSpec::new("bob")
+                },
+            ])
+            .with_root_password(Some(PasswordHash::new("$6$salt$root").unwrap()))
+            .with_lock_root(false)
+            .apply(&env, Distro::Debian)
+            .unwrap();
+
+        let root = root.path().display();
+        let commands = executor.commands();
+        let argv: Vec<String> = commands.iter().map(|c| c.argv().join(" ")).collect();
+        assert_eq!(
+            argv,
+            vec![
+                format!("chroot {} useradd -m -G video,sudo -s /bin/zsh alice", root),
+                format!("chroot {} usermod -a -G sudo bob", root),
+                format!("chroot {} chpasswd -e", root),
+            ]
+        );
+        assert_eq!(
+            commands[2].stdin.as_deref(),
+            Some(&b"alice:$6$salt$alice\nroot:$6$salt$root\n"[..])
+        );
+    }
+
+    #[test]
+    fn test_root_is_locked() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let env = ChrootEnv::new("/mnt/target", true).with_executor(executor.clone());
+        UserAccounts::new(true)
+            .with_executor(executor.clone())
+            .with_users(vec![UserSpec {
+                admin: true,
+                ..UserSpec::new("alice")
+            }])
+            .with_lock_root(true)
+            .apply(&env, Distro::Fedora)
+            .unwrap();
+
+        let argv: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            argv,
+            vec![
+                "chroot /mnt/target useradd -m -G wheel alice",
+                "chroot /mnt/target passwd -l root",
+            ]
+        );
+    }
+}
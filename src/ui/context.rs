@@ -1,23 +1,37 @@
 //! Notcurses context wrapper - safe Rust interface to libnotcurses
 
-#[cfg(feature = "tui")]
-use libnotcurses_sys::{Nc, NcInput, NcPlane, NcReceived};
+use libnotcurses_sys::{Nc, NcFlag, NcInput, NcPlane, NcReceived};
 
 use crate::error::{InstallerError, Result};
+use super::layout;
 
 /// Notcurses context wrapper
-#[cfg(feature = "tui")]
 pub struct NotcursesContext {
     nc: &'static mut Nc,
     rows: u32,
     cols: u32,
+    overlay: Option<&'static mut NcPlane>,
+    on_overlay: bool,
+}
+
+/// Channels of a progress bar's filled and empty parts
+#[derive(Debug, Clone, Copy)]
+pub struct BarStyle {
+    /// Channels of the filled part and the label
+    pub filled: u64,
+    /// Channels of the empty part
+    pub empty: u64,
 }
 
-#[cfg(feature = "tui")]
 impl NotcursesContext {
     /// Initialize notcurses
+    ///
+    /// SIGINT and SIGTERM are left to the installer's own handlers, which
+    /// cancel the installation and let this context be dropped normally.
     pub fn init() -> Result<Self> {
-        let nc = unsafe { Nc::new() }.map_err(|e| {
+        crate::installer::cancel::save_terminal();
+        let flags = NcFlag::SuppressBanners | NcFlag::NoQuitSigHandlers;
+        let nc = unsafe { Nc::with_flags(flags) }.map_err(|e| {
             InstallerError::UiError(format!("Failed to initialize notcurses: {:?}", e))
         })?;
 
@@ -28,6 +42,8 @@ impl NotcursesContext {
             nc,
             rows,
             cols,
+            overlay: None,
+            on_overlay: false,
         })
     }
 
@@ -41,6 +57,49 @@ impl NotcursesContext {
         unsafe { self.nc.stdplane() }
     }
 
+    /// The plane drawing goes to: the overlay inside [`Self::draw_overlay`]
+    fn target(&mut self) -> &mut NcPlane {
+        match &mut self.overlay {
+            Some(overlay) if self.on_overlay => overlay,
+            _ => unsafe { self.nc.stdplane() },
+        }
+    }
+
+    /// Open an opaque plane over the screen, replacing any open one
+    ///
+    /// The screen underneath is left as it was and shows again once the
+    /// overlay is closed.
+    pub fn open_overlay(&mut self, y: u32, x: u32, height: u32, width: u32) -> Result<()> {
+        self.close_overlay()?;
+        let stdplane = unsafe { self.nc.stdplane() };
+        let overlay = NcPlane::new_child_sized(stdplane, y as i32, x as i32, height.max(1), width.max(1))
+            .map_err(|e| InstallerError::UiError(format!("Failed to create overlay: {:?}", e)))?;
+        let _ = overlay.set_base(" ", 0, channels::WHITE_ON_BLACK);
+        self.overlay = Some(overlay);
+        Ok(())
+    }
+
+    /// Close the overlay, if one is open
+    pub fn close_overlay(&mut self) -> Result<()> {
+        if let Some(overlay) = self.overlay.take() {
+            overlay.destroy().map_err(|e| {
+                InstallerError::UiError(format!("Failed to close overlay: {:?}", e))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Draw on the open overlay instead of the screen, at coordinates relative to it
+    pub fn draw_overlay(&mut self, draw: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.overlay.is_none() {
+            return Ok(());
+        }
+        self.on_overlay = true;
+        let result = draw(self);
+        self.on_overlay = false;
+        result
+    }
+
     /// Clear the screen
     pub fn clear(&mut self) -> Result<()> {
         let plane = unsafe { self.nc.stdplane() };
@@ -85,7 +144,7 @@ impl NotcursesContext {
         text: &str,
         channels: u64,
     ) -> Result<()> {
-        let plane = unsafe { self.nc.stdplane() };
+        let plane = self.target();
         plane.set_channels(channels);
         let _ = plane.cursor_move_yx(y, x);
         let _ = plane.putstr_yx(Some(y), Some(x), text);
@@ -102,6 +161,10 @@ impl NotcursesContext {
         title: Option<&str>,
         channels: u64,
     ) -> Result<()> {
+        if width < 2 || height < 2 {
+            return Ok(());
+        }
+
         // Draw corners and edges
         let ul = "┌";
         let ur = "┐";
@@ -119,10 +182,13 @@ impl NotcursesContext {
 
         // Title (if provided)
         if let Some(title) = title {
-            let title_x = x + (width - title.len() as u32) / 2;
-            self.putstr_yx(y, title_x - 1, " ", channels)?;
-            self.putstr_yx(y, title_x, title, channels)?;
-            self.putstr_yx(y, title_x + title.len() as u32, " ", channels)?;
+            let title = layout::truncate(title, width.saturating_sub(4));
+            if !title.is_empty() {
+                let title_x = x + layout::center_text(width, &title);
+                self.putstr_yx(y, title_x - 1, " ", channels)?;
+                self.putstr_yx(y, title_x, &title, channels)?;
+                self.putstr_yx(y, title_x + layout::display_width(&title), " ", channels)?;
+            }
         }
 
         // Sides
@@ -149,53 +215,38 @@ impl NotcursesContext {
         width: u32,
         progress: f32,
         label: Option<&str>,
-        fg_channels: u64,
-        bg_channels: u64,
+        style: BarStyle,
     ) -> Result<()> {
         let filled = (width as f32 * progress.clamp(0.0, 1.0)) as u32;
 
         // Draw filled portion
         for i in 0..filled {
-            self.putstr_yx(y, x + i, "█", fg_channels)?;
+            self.putstr_yx(y, x + i, "█", style.filled)?;
         }
 
         // Draw empty portion
         for i in filled..width {
-            self.putstr_yx(y, x + i, "░", bg_channels)?;
+            self.putstr_yx(y, x + i, "░", style.empty)?;
         }
 
         // Draw label if provided
         if let Some(label) = label {
-            let label_x = x + (width - label.len() as u32) / 2;
-            self.putstr_yx(y, label_x, label, fg_channels)?;
+            let label = layout::truncate(label, width);
+            let label_x = x + layout::center_text(width, &label);
+            self.putstr_yx(y, label_x, &label, style.filled)?;
         }
 
         Ok(())
     }
 }
 
-#[cfg(feature = "tui")]
 impl Drop for NotcursesContext {
     fn drop(&mut self) {
         let _ = unsafe { self.nc.stop() };
     }
 }
 
-// Mock implementation when tui feature is disabled
-#[cfg(not(feature = "tui"))]
-pub struct NotcursesContext;
-
-#[cfg(not(feature = "tui"))]
-impl NotcursesContext {
-    pub fn init() -> Result<Self> {
-        Err(InstallerError::UiError(
-            "TUI support not compiled in. Rebuild with --features tui".into(),
-        ))
-    }
-}
-
 /// Helper functions for creating channel values (color pairs)
-#[cfg(feature = "tui")]
 pub mod channels {
     /// Create a channel with RGB colors
     pub fn from_rgb(fg_r: u8, fg_g: u8, fg_b: u8, bg_r: u8, bg_g: u8, bg_b: u8) -> u64 {
@@ -222,19 +273,3 @@ pub mod channels {
     pub const CYAN_ON_BLACK: u64 = 0x4000FFFF_40000000;
     pub const MAGENTA_ON_BLACK: u64 = 0x40FF00FF_40000000;
 }
-
-#[cfg(not(feature = "tui"))]
-pub mod channels {
-    pub fn from_rgb(_fg_r: u8, _fg_g: u8, _fg_b: u8, _bg_r: u8, _bg_g: u8, _bg_b: u8) -> u64 {
-        0
-    }
-
-    pub const WHITE_ON_BLACK: u64 = 0;
-    pub const BLACK_ON_WHITE: u64 = 0;
-    pub const GREEN_ON_BLACK: u64 = 0;
-    pub const RED_ON_BLACK: u64 = 0;
-    pub const YELLOW_ON_BLACK: u64 = 0;
-    pub const BLUE_ON_BLACK: u64 = 0;
-    pub const CYAN_ON_BLACK: u64 = 0;
-    pub const MAGENTA_ON_BLACK: u64 = 0;
-}
@@ -0,0 +1,143 @@
+//! Terminal layout helpers
+//!
+//! Widths here are display columns, not bytes: the box-drawing art, emoji and
+//! CJK labels all take more bytes than columns, and CJK and most emoji take two
+//! columns per character. Every subtraction saturates, so text wider than the
+//! terminal is pinned to the left edge instead of underflowing.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Marks text cut short by [`truncate`]
+pub const ELLIPSIS: &str = "…";
+
+/// Columns `text` takes on the terminal
+pub fn display_width(text: &str) -> u32 {
+    u32::try_from(text.width()).unwrap_or(u32::MAX)
+}
+
+/// Offset that centers `width` columns within `total`, 0 if it doesn't fit
+pub fn center(total: u32, width: u32) -> u32 {
+    total.saturating_sub(width) / 2
+}
+
+/// Column where `text` starts when centered on a line of `cols` columns
+pub fn center_text(cols: u32, text: &str) -> u32 {
+    center(cols, display_width(text))
+}
+
+/// Cut `text` to at most `width` columns, ending with [`ELLIPSIS`] if anything was cut
+pub fn truncate(text: &str, width: u32) -> String {
+    if display_width(text) <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut out = take_columns(text, width - 1).to_string();
+    out.push_str(ELLIPSIS);
+    out
+}
+
+/// The longest prefix of `text` that fits in `width` columns
+///
+/// Never splits a character, so a double-width character that would straddle
+/// the edge is left out.
+pub fn take_columns(text: &str, width: u32) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += char_width(c);
+        if used > width {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+/// The longest suffix of `text` that fits in `width` columns
+pub fn tail_columns(text: &str, width: u32) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices().rev() {
+        used += char_width(c);
+        if used > width {
+            return &text[i + c.len_utf8()..];
+        }
+    }
+    text
+}
+
+fn char_width(c: char) -> u32 {
+    c.width().unwrap_or(0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("ZFSBootMenu"), 11);
+        assert_eq!(display_width("╔══╗"), 4);
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("💾 disk"), 7);
+        assert_eq!(display_width(""), 0);
+    }
+
+    #[test]
+    fn test_center() {
+        assert_eq!(center(80, 20), 30);
+        assert_eq!(center(20, 20), 0);
+        // Wider than the terminal: pinned left instead of underflowing
+        assert_eq!(center(20, 60), 0);
+        assert_eq!(center(0, 1), 0);
+    }
+
+    #[test]
+    fn test_center_text_counts_columns_not_bytes() {
+        // 12 columns but 36 bytes
+        let art = "╔══════════╗";
+        assert_eq!(center_text(20, art), 4);
+        assert_eq!(center_text(20, "日本語テキスト"), 3);
+        assert_eq!(center_text(20, "🔒🔒"), 8);
+        assert_eq!(center_text(20, &"x".repeat(40)), 0);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 20), "short");
+        assert_eq!(truncate("twelve chars", 12), "twelve chars");
+        assert_eq!(truncate("a long line of text", 10), "a long li…");
+        assert_eq!(truncate("anything", 1), "…");
+        assert_eq!(truncate("anything", 0), "");
+    }
+
+    #[test]
+    fn test_truncate_multibyte() {
+        // Byte slicing would panic in the middle of these
+        assert_eq!(truncate("╔════════╗", 5), "╔═══…");
+        assert_eq!(truncate("💾💾💾💾", 5), "💾💾…");
+        // A wide character that would straddle the edge is dropped
+        assert_eq!(truncate("日本語テキスト", 6), "日本…");
+        for width in 0..20 {
+            assert!(display_width(&truncate("日本語 and 💾 mixed text", width)) <= width);
+        }
+    }
+
+    #[test]
+    fn test_truncate_to_narrow_terminal() {
+        let line = "Select the target devices for the ZFS pool (Space to toggle)";
+        let cut = truncate(line, 20 - 4);
+        assert_eq!(display_width(&cut), 16);
+        assert!(cut.ends_with(ELLIPSIS));
+    }
+
+    #[test]
+    fn test_take_and_tail_columns() {
+        assert_eq!(take_columns("abcdef", 3), "abc");
+        assert_eq!(take_columns("日本語", 3), "日");
+        assert_eq!(take_columns("ab", 10), "ab");
+        assert_eq!(tail_columns("abcdef", 3), "def");
+        assert_eq!(tail_columns("日本語", 5), "本語");
+        assert_eq!(tail_columns("pass💾word", 6), "💾word");
+        assert_eq!(tail_columns("ab", 0), "");
+    }
+}
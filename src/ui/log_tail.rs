@@ -0,0 +1,86 @@
+//! Recent log output, kept while the TUI owns the terminal
+//!
+//! Log lines written to stderr would corrupt the notcurses display, so in TUI
+//! mode the logger writes here instead and the UI shows the tail on demand.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Lines kept by default
+pub const DEFAULT_CAPACITY: usize = 200;
+
+#[derive(Default)]
+struct Buffer {
+    lines: VecDeque<String>,
+    partial: String,
+    subscribers: Vec<Sender<String>>,
+}
+
+/// A bounded, shareable buffer of the most recent log lines
+///
+/// Clones share the same buffer, so one clone can be handed to the logger as
+/// its output and another kept by the UI.
+#[derive(Clone)]
+pub struct LogTail {
+    buffer: Arc<Mutex<Buffer>>,
+    capacity: usize,
+}
+
+impl LogTail {
+    /// Keep at most `capacity` lines
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Arc::default(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// The last `count` complete lines, oldest first
+    pub fn last(&self, count: usize) -> Vec<String> {
+        let buffer = self.buffer.lock().unwrap();
+        let skip = buffer.lines.len().saturating_sub(count);
+        buffer.lines.iter().skip(skip).cloned().collect()
+    }
+
+    /// Receive every complete line written from now on
+    ///
+    /// A subscriber that has been dropped stops being sent lines.
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        self.buffer.lock().unwrap().subscribers.push(tx);
+        rx
+    }
+}
+
+impl Default for LogTail {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Write for LogTail {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.partial.push_str(&String::from_utf8_lossy(buf));
+
+        while let Some(end) = buffer.partial.find('\n') {
+            let line: String = buffer.partial.drain(..=end).collect();
+            let line = line.trim_end().to_string();
+            buffer
+                .subscribers
+                .retain(|tx| tx.send(line.clone()).is_ok());
+            buffer.lines.push_back(line);
+            if buffer.lines.len() > self.capacity {
+                buffer.lines.pop_front();
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,35 +1,93 @@
 //! TUI framework using Notcurses
 //!
-//! This module provides a text user interface for the ZBM installer.
+//! This module provides a text user interface for the ZBM installer. The
+//! notcurses screens are only built with the `tui` feature; the plain
+//! prompts of `--interactive` are always available.
 
+#[cfg(feature = "tui")]
 pub mod context;
+pub mod layout;
+pub mod log_tail;
+pub mod prompt;
+#[cfg(feature = "tui")]
 pub mod runner;
 pub mod screens;
+#[cfg(feature = "tui")]
 pub mod widgets;
+pub mod wizard;
 
+#[cfg(feature = "tui")]
 pub use context::NotcursesContext;
+pub use log_tail::LogTail;
+#[cfg(feature = "tui")]
 pub use runner::UiRunner;
 pub use screens::Screen;
 
 use crate::config::Config;
+#[cfg(not(feature = "tui"))]
+use crate::error::InstallerError;
 use crate::error::Result;
+use crate::installer::{CancelToken, InstallReport};
+use crate::log_sink::LogSink;
 
 /// UI manager
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
 pub struct UiManager {
     config: Config,
+    log_tail: LogTail,
+    cancel: CancelToken,
+    log_sink: Option<LogSink>,
 }
 
 impl UiManager {
     /// Create a new UI manager
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            log_tail: LogTail::default(),
+            cancel: CancelToken::new(),
+            log_sink: None,
+        }
     }
 
-    /// Run the interactive TUI
-    pub fn run(&mut self) -> Result<Config> {
-        let mut runner = UiRunner::new(self.config.clone());
+    /// Show `log_tail` when the installation fails
+    pub fn with_log_tail(mut self, log_tail: LogTail) -> Self {
+        self.log_tail = log_tail;
+        self
+    }
+
+    /// Stop once `cancel` is cancelled (e.g. by SIGINT)
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Record the installation in `log_sink`
+    pub fn with_log_sink(mut self, log_sink: LogSink) -> Self {
+        self.log_sink = Some(log_sink);
+        self
+    }
+
+    /// Run the interactive TUI, including the installation itself
+    #[cfg(feature = "tui")]
+    pub fn run(&mut self) -> Result<InstallReport> {
+        let mut runner = UiRunner::new(self.config.clone())
+            .with_log_tail(self.log_tail.clone())
+            .with_cancel_token(self.cancel.clone());
+        if let Some(log_sink) = &self.log_sink {
+            runner = runner.with_log_sink(log_sink.clone());
+        }
         runner.run()
     }
+
+    /// Fail: the TUI isn't compiled in
+    #[cfg(not(feature = "tui"))]
+    pub fn run(&mut self) -> Result<InstallReport> {
+        Err(InstallerError::UiError(
+            "TUI support not compiled in. Use --interactive, CLI mode or rebuild with --features tui"
+                .into(),
+        ))
+    }
 }
 
 // NOTE: Full Notcurses implementation would include:
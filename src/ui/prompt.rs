@@ -0,0 +1,346 @@
+//! Line-based prompts, for terminals the TUI can't run on
+//!
+//! Serial consoles and minimal SSH sessions get numbered lists and typed
+//! answers on stdin/stdout instead of notcurses. An invalid answer is
+//! explained and asked again; the end of input cancels.
+
+use crate::error::{InstallerError, Result};
+use crate::i18n::{tr, tr_args};
+use crate::system;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Asks questions on `output` and reads the answers from `input`
+pub struct Prompter<R, W> {
+    input: R,
+    output: W,
+    /// Whether `input` is the terminal, whose echo secrets are read without
+    terminal: bool,
+}
+
+impl Prompter<io::StdinLock<'static>, io::Stdout> {
+    /// Prompt on the terminal, refusing if stdin or stdout isn't one
+    ///
+    /// Without a terminal there may be nobody to answer, and waiting for
+    /// one would hang.
+    pub fn stdio() -> Result<Self> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err(InstallerError::config(tr("prompt.no_terminal")));
+        }
+        Ok(Self {
+            terminal: true,
+            ..Self::new(io::stdin().lock(), io::stdout())
+        })
+    }
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    /// Read answers from `input`, writing questions to `output`
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            terminal: false,
+        }
+    }
+
+    /// Print a line of information between questions
+    pub fn say(&mut self, text: &str) -> Result<()> {
+        writeln!(self.output, "{}", text)?;
+        Ok(())
+    }
+
+    /// Ask `question` and read one line, without its line ending
+    fn ask(&mut self, question: &str) -> Result<String> {
+        self.read(question, false)
+    }
+
+    /// Ask for a secret, not echoed when typed on the terminal
+    pub fn secret(&mut self, question: &str) -> Result<String> {
+        self.read(question, true)
+    }
+
+    fn read(&mut self, question: &str, hidden: bool) -> Result<String> {
+        write!(self.output, "{}", question)?;
+        self.output.flush()?;
+
+        let mut line = String::new();
+        let input = &mut self.input;
+        let read = if hidden && self.terminal {
+            system::without_echo(|| input.read_line(&mut line))?
+        } else {
+            input.read_line(&mut line)?
+        };
+        if read == 0 {
+            writeln!(self.output)?;
+            return Err(InstallerError::UserCancelled);
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Explain why an answer was rejected
+    pub fn reject(&mut self, reason: &str) -> Result<()> {
+        writeln!(self.output, "  {}", reason)?;
+        Ok(())
+    }
+
+    /// Print `options` numbered from 1, marking those in `marked`
+    fn list(&mut self, question: &str, options: &[String], marked: &[usize]) -> Result<()> {
+        writeln!(self.output, "\n{}", question)?;
+        for (i, option) in options.iter().enumerate() {
+            let mark = if marked.contains(&i) { '*' } else { ' ' };
+            writeln!(self.output, "{} {:>2}) {}", mark, i + 1, option)?;
+        }
+        Ok(())
+    }
+
+    /// Pick one of `options` by number; an empty answer picks `default`
+    pub fn select(&mut self, question: &str, options: &[String], default: usize) -> Result<usize> {
+        let default = default.min(options.len().saturating_sub(1));
+        self.list(question, options, &[default])?;
+        loop {
+            let answer = self.ask(&tr_args("prompt.choice", &[("default", &(default + 1))]))?;
+            if answer.trim().is_empty() {
+                return Ok(default);
+            }
+            match parse_choices(&answer, options.len()).as_deref() {
+                Some(&[choice]) => return Ok(choice),
+                _ => self.reject(&tr_args(
+                    "prompt.choice_invalid",
+                    &[("count", &options.len())],
+                ))?,
+            }
+        }
+    }
+
+    /// Pick at least one of `options` by number ("1,3", "2-4"); an empty
+    /// answer keeps `checked`, if that isn't empty
+    pub fn multi_select(
+        &mut self,
+        question: &str,
+        options: &[String],
+        checked: &[usize],
+    ) -> Result<Vec<usize>> {
+        self.list(question, options, checked)?;
+        loop {
+            let answer = self.ask(tr("prompt.choices"))?;
+            if answer.trim().is_empty() && !checked.is_empty() {
+                return Ok(checked.to_vec());
+            }
+            match parse_choices(&answer, options.len()) {
+                Some(choices) if !choices.is_empty() => return Ok(choices),
+                _ => self.reject(&tr_args(
+                    "prompt.choices_invalid",
+                    &[("count", &options.len())],
+                ))?,
+            }
+        }
+    }
+
+    /// Ask a yes/no question; an empty answer gives `default`
+    pub fn yes_no(&mut self, question: &str, default: bool) -> Result<bool> {
+        let hint = tr(if default {
+            "prompt.yes_no_default_yes"
+        } else {
+            "prompt.yes_no_default_no"
+        });
+        loop {
+            let answer = self.ask(&format!("{} {} ", question, hint))?;
+            match parse_yes_no(&answer, default) {
+                Some(yes) => return Ok(yes),
+                None => self.reject(tr("prompt.yes_no_invalid"))?,
+            }
+        }
+    }
+
+    /// Read text until `parse` accepts it; an empty answer gives `default`
+    pub fn text<T>(
+        &mut self,
+        question: &str,
+        default: &str,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<T> {
+        loop {
+            let answer = if default.is_empty() {
+                self.ask(&format!("{}: ", question))?
+            } else {
+                self.ask(&format!("{} [{}]: ", question, default))?
+            };
+            let answer = answer.trim();
+            let answer = if answer.is_empty() { default } else { answer };
+            match parse(answer) {
+                Ok(value) => return Ok(value),
+                Err(e) => self.reject(&e.to_string())?,
+            }
+        }
+    }
+}
+
+/// Zero-based indices from a list of 1-based numbers and ranges ("1,3",
+/// "2-4", "1 5"), in order and without repeats; `None` if any is invalid
+fn parse_choices(answer: &str, count: usize) -> Option<Vec<usize>> {
+    let number = |text: &str| {
+        text.trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| (1..=count).contains(&n))
+    };
+
+    let mut choices = Vec::new();
+    for part in answer
+        .split([',', ' '])
+        .filter(|part| !part.trim().is_empty())
+    {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (number(first)?, number(last)?),
+            None => (number(part)?, number(part)?),
+        };
+        if first > last {
+            return None;
+        }
+        for n in first..=last {
+            if !choices.contains(&(n - 1)) {
+                choices.push(n - 1);
+            }
+        }
+    }
+    Some(choices)
+}
+
+/// Read a yes/no answer, in English or the active language
+fn parse_yes_no(answer: &str, default: bool) -> Option<bool> {
+    let answer = answer.trim().to_lowercase();
+    if answer.is_empty() {
+        return Some(default);
+    }
+    let matches = |key: &'static str, english: &str| {
+        let word = tr(key).to_lowercase();
+        [english, &english[..1]].contains(&answer.as_str())
+            || answer == word
+            || word.chars().next().is_some_and(|c| answer == c.to_string())
+    };
+    if matches("prompt.yes", "yes") {
+        Some(true)
+    } else if matches("prompt.no", "no") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn prompter(input: &str) -> Prompter<Cursor<Vec<u8>>, Vec<u8>> {
+        Prompter::new(Cursor::new(input.as_bytes().to_vec()), Vec::new())
+    }
+
+    fn options(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_choices() {
+        assert_eq!(parse_choices("1", 3), Some(vec![0]));
+        assert_eq!(parse_choices("3,1", 3), Some(vec![2, 0]));
+        assert_eq!(parse_choices(" 1-3 ", 5), Some(vec![0, 1, 2]));
+        assert_eq!(parse_choices("1 2,2", 3), Some(vec![0, 1]));
+        assert_eq!(parse_choices("", 3), Some(vec![]));
+
+        assert_eq!(parse_choices("0", 3), None);
+        assert_eq!(parse_choices("4", 3), None);
+        assert_eq!(parse_choices("3-1", 3), None);
+        assert_eq!(parse_choices("two", 3), None);
+        assert_eq!(parse_choices("1,x", 3), None);
+    }
+
+    #[test]
+    fn test_parse_yes_no() {
+        assert_eq!(parse_yes_no("y", false), Some(true));
+        assert_eq!(parse_yes_no("YES", false), Some(true));
+        assert_eq!(parse_yes_no("n", true), Some(false));
+        assert_eq!(parse_yes_no("No", true), Some(false));
+        assert_eq!(parse_yes_no("", true), Some(true));
+        assert_eq!(parse_yes_no("  ", false), Some(false));
+        assert_eq!(parse_yes_no("maybe", true), None);
+    }
+
+    #[test]
+    fn test_select() {
+        let names = options(&["new", "existing"]);
+
+        let mut p = prompter("2\n");
+        assert_eq!(p.select("Mode?", &names, 0).unwrap(), 1);
+
+        // Empty takes the default; invalid answers are asked again
+        let mut p = prompter("\n");
+        assert_eq!(p.select("Mode?", &names, 1).unwrap(), 1);
+        let mut p = prompter("3\n1,2\nx\n1\n");
+        assert_eq!(p.select("Mode?", &names, 1).unwrap(), 0);
+
+        let output = String::from_utf8(p.output).unwrap();
+        assert!(output.contains(" 1) new"));
+        assert!(output.contains("*  2) existing"));
+    }
+
+    #[test]
+    fn test_end_of_input_cancels() {
+        let names = options(&["a", "b"]);
+        assert!(matches!(
+            prompter("").select("?", &names, 0),
+            Err(InstallerError::UserCancelled)
+        ));
+        assert!(matches!(
+            prompter("9\n").multi_select("?", &names, &[]),
+            Err(InstallerError::UserCancelled)
+        ));
+        assert!(matches!(
+            prompter("").yes_no("?", true),
+            Err(InstallerError::UserCancelled)
+        ));
+    }
+
+    #[test]
+    fn test_multi_select() {
+        let names = options(&["sda", "sdb", "sdc"]);
+
+        let mut p = prompter("1,3\n");
+        assert_eq!(p.multi_select("Devices?", &names, &[]).unwrap(), vec![0, 2]);
+
+        // Empty keeps what was checked, but something has to be chosen
+        let mut p = prompter("\n");
+        assert_eq!(p.multi_select("Devices?", &names, &[1]).unwrap(), vec![1]);
+        let mut p = prompter("\n2-3\n");
+        assert_eq!(p.multi_select("Devices?", &names, &[]).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_yes_no() {
+        assert!(prompter("y\n").yes_no("Encrypt?", false).unwrap());
+        assert!(!prompter("\n").yes_no("Encrypt?", false).unwrap());
+        assert!(prompter("what\nyes\n").yes_no("Encrypt?", false).unwrap());
+    }
+
+    #[test]
+    fn test_text_validates() {
+        let parse = |value: &str| {
+            if value.contains(' ') {
+                Err(InstallerError::validation("no spaces"))
+            } else {
+                Ok(value.to_string())
+            }
+        };
+
+        let mut p = prompter("my pool\n  tank  \n");
+        assert_eq!(p.text("Pool name", "zroot", parse).unwrap(), "tank");
+        let output = String::from_utf8(p.output).unwrap();
+        assert!(output.contains("Pool name [zroot]: "));
+        assert!(output.contains("no spaces"));
+
+        assert_eq!(
+            prompter("\n").text("Pool name", "zroot", parse).unwrap(),
+            "zroot"
+        );
+    }
+}
@@ -1,22 +1,66 @@
 //! UI runner - orchestrates screen transitions and user interaction
 
-use super::context::{channels, NotcursesContext};
+use super::context::{channels, BarStyle, NotcursesContext};
+use super::layout;
+use super::log_tail::LogTail;
 use super::screens::Screen;
-use super::widgets::{CheckList, Dialog, Menu, MenuItem};
-use crate::config::{Config, InstallMode, RaidLevel};
-use crate::disk::discovery::DeviceDiscovery;
+use super::widgets::{scrub, CheckList, Dialog, InputField, LogView, Menu, MenuItem};
+use crate::config::{
+    confirms, parse_size, Compression, Config, DeviceRole, EncryptionConfig, InstallMode, Passphrase,
+    PassphraseStrength, PasswordHash, RaidLevel, UserSpec,
+};
+use crate::disk::discovery::{DeviceDiscovery, DeviceEvent};
+use crate::disk::{holders, BlockDevice};
 use crate::error::{InstallerError, Result};
+use crate::exec::SystemExecutor;
+use crate::system::users::hash_password;
+use crate::i18n::{tr, tr_args};
+use crate::installer::{
+    CancelToken, ChannelReporter, InstallReport, Installer, PhaseState, ProgressTracker,
+};
+use crate::log_sink::LogSink;
+use crate::validation::Severity;
+use crate::zfs;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
 
-#[cfg(feature = "tui")]
 use libnotcurses_sys::c_api::{
-    NCKEY_DOWN, NCKEY_ENTER, NCKEY_ESC, NCKEY_LEFT, NCKEY_RIGHT, NCKEY_SPACE, NCKEY_TAB, NCKEY_UP,
+    NCKEY_BACKSPACE, NCKEY_DOWN, NCKEY_END, NCKEY_ENTER, NCKEY_ESC, NCKEY_HOME, NCKEY_LEFT,
+    NCKEY_PGDOWN, NCKEY_PGUP, NCKEY_RIGHT, NCKEY_SPACE, NCKEY_TAB, NCKEY_UP,
 };
 
+/// Suggested location for a saved configuration
+const DEFAULT_SAVE_PATH: &str = "/root/zbm-install.toml";
+
+/// How often the execution screen polls for progress and keys
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Log lines kept for the log view
+const LOG_VIEW_LINES: usize = 2000;
+
+/// Where a screen was left, restored when it's shown again
+#[derive(Debug, Clone, Default)]
+struct ScreenState {
+    /// Highlighted item
+    selected: usize,
+    /// Checked devices by name, with the role each was given
+    devices: HashMap<String, DeviceRole>,
+}
+
 /// UI runner
 pub struct UiRunner {
     current_screen: Screen,
+    screen_state: HashMap<Screen, ScreenState>,
     config: Config,
+    /// Sizes of the devices last listed for selection, by path
+    device_sizes: HashMap<PathBuf, u64>,
+    log_view: LogView,
+    log_lines: Option<mpsc::Receiver<String>>,
+    report: Option<InstallReport>,
+    cancel: CancelToken,
+    log_sink: Option<LogSink>,
 }
 
 impl UiRunner {
@@ -24,54 +68,72 @@ impl UiRunner {
     pub fn new(config: Config) -> Self {
         Self {
             current_screen: Screen::Welcome,
+            screen_state: HashMap::new(),
             config,
+            device_sizes: HashMap::new(),
+            log_view: LogView::new(tr("ui.exec.log_title"), LOG_VIEW_LINES),
+            log_lines: None,
+            report: None,
+            cancel: CancelToken::new(),
+            log_sink: None,
         }
     }
 
-    /// Run the TUI workflow
-    pub fn run(&mut self) -> Result<Config> {
-        #[cfg(not(feature = "tui"))]
-        {
-            return Err(InstallerError::UiError(
-                "TUI support not compiled. Use CLI mode or rebuild with --features tui".into(),
-            ));
-        }
-
-        #[cfg(feature = "tui")]
-        {
-            let mut ctx = NotcursesContext::init()?;
-
-            loop {
-                ctx.clear()?;
-                self.draw_header(&mut ctx)?;
-
-                let action = match self.current_screen {
-                    Screen::Welcome => self.show_welcome(&mut ctx)?,
-                    Screen::ModeSelect => self.show_mode_select(&mut ctx)?,
-                    Screen::DeviceDiscovery => self.show_device_discovery(&mut ctx)?,
-                    Screen::DeviceSelect => self.show_device_select(&mut ctx)?,
-                    Screen::RaidConfig => self.show_raid_config(&mut ctx)?,
-                    Screen::Settings => self.show_settings(&mut ctx)?,
-                    Screen::PreflightCheck => self.show_preflight(&mut ctx)?,
-                    Screen::Confirmation => self.show_confirmation(&mut ctx)?,
-                    Screen::Execution => {
-                        // Don't actually execute in TUI, just show completion
-                        return Ok(self.config.clone());
-                    }
-                    Screen::Completion => return Ok(self.config.clone()),
-                };
+    /// Show what is logged to `log_tail` in the log view
+    pub fn with_log_tail(mut self, log_tail: LogTail) -> Self {
+        self.log_lines = Some(log_tail.subscribe());
+        for line in log_tail.last(LOG_VIEW_LINES) {
+            self.log_view.push(line);
+        }
+        self
+    }
 
-                match action {
-                    ScreenAction::Next => self.next_screen(),
-                    ScreenAction::Previous => self.previous_screen(),
-                    ScreenAction::Exit => {
-                        self.show_exit_dialog(&mut ctx)?;
-                        return Err(InstallerError::UserCancelled);
-                    }
-                }
+    /// Stop once `cancel` is cancelled (e.g. by SIGINT)
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
 
-                ctx.render()?;
+    /// Record the installation in `log_sink`
+    pub fn with_log_sink(mut self, log_sink: LogSink) -> Self {
+        self.log_sink = Some(log_sink);
+        self
+    }
+
+    /// Run the TUI workflow, then the installation, returning its report
+    pub fn run(&mut self) -> Result<InstallReport> {
+        let mut ctx = NotcursesContext::init()?;
+
+        loop {
+            ctx.clear()?;
+            self.draw_header(&mut ctx)?;
+
+            let action = match self.current_screen {
+                Screen::Welcome => self.show_welcome(&mut ctx)?,
+                Screen::ModeSelect => self.show_mode_select(&mut ctx)?,
+                Screen::DeviceDiscovery => self.show_device_discovery(&mut ctx)?,
+                Screen::DeviceSelect => self.show_device_select(&mut ctx)?,
+                Screen::RaidConfig => self.show_raid_config(&mut ctx)?,
+                Screen::Encryption => self.show_encryption(&mut ctx)?,
+                Screen::Settings => self.show_settings(&mut ctx)?,
+                Screen::PreflightCheck => self.show_preflight(&mut ctx)?,
+                Screen::Confirmation => self.show_confirmation(&mut ctx)?,
+                Screen::Execution => self.show_execution(&mut ctx)?,
+                Screen::Completion => return self.show_completion(&mut ctx),
+            };
+
+            match action {
+                ScreenAction::Next => self.next_screen(),
+                ScreenAction::Previous => self.previous_screen(),
+                ScreenAction::Stay => {}
+                ScreenAction::Exit => {
+                    self.show_exit_dialog(&mut ctx)?;
+                    return Err(InstallerError::UserCancelled);
+                }
             }
+            self.cancel.check()?;
+
+            ctx.render()?;
         }
     }
 
@@ -79,14 +141,14 @@ impl UiRunner {
         let (rows, cols) = ctx.dimensions();
 
         // Draw title bar
-        let title = "═══ ZFSBootMenu Installer ═══";
-        let title_x = (cols - title.len() as u32) / 2;
-        ctx.putstr_yx(0, title_x, title, channels::from_rgb(0, 255, 255, 0, 30, 50))?;
+        let title = layout::truncate("═══ ZFSBootMenu Installer ═══", cols);
+        let title_x = layout::center_text(cols, &title);
+        ctx.putstr_yx(0, title_x, &title, channels::from_rgb(0, 255, 255, 0, 30, 50))?;
 
         // Draw current screen indicator
         let screen_name = self.current_screen.title();
-        let subtitle = format!("[ {} ]", screen_name);
-        let subtitle_x = (cols - subtitle.len() as u32) / 2;
+        let subtitle = layout::truncate(&format!("[ {} ]", screen_name), cols);
+        let subtitle_x = layout::center_text(cols, &subtitle);
         ctx.putstr_yx(1, subtitle_x, &subtitle, channels::CYAN_ON_BLACK)?;
 
         // Draw separator line
@@ -94,9 +156,9 @@ impl UiRunner {
         ctx.putstr_yx(2, 0, &separator, channels::from_rgb(100, 100, 150, 0, 0, 0))?;
 
         // Draw footer with help
-        let help = "↑↓: Navigate | Enter: Select | Esc: Back | Q: Quit";
-        let help_x = (cols - help.len() as u32) / 2;
-        ctx.putstr_yx(rows - 1, help_x, help, channels::from_rgb(200, 200, 0, 0, 0, 0))?;
+        let help = layout::truncate(tr("ui.help"), cols);
+        let help_x = layout::center_text(cols, &help);
+        ctx.putstr_yx(rows.saturating_sub(1), help_x, &help, channels::from_rgb(200, 200, 0, 0, 0, 0))?;
 
         Ok(())
     }
@@ -109,10 +171,10 @@ impl UiRunner {
         let messages = vec![
             "╔═══════════════════════════════════════════════════════════╗",
             "║                                                           ║",
-            "║         Welcome to ZFSBootMenu Installer!                ║",
+            "║         Welcome to ZFSBootMenu Installer!                 ║",
             "║                                                           ║",
-            "║  This installer will help you set up ZFS on Linux        ║",
-            "║  with ZFSBootMenu for a powerful, modern boot system.    ║",
+            "║  This installer will help you set up ZFS on Linux         ║",
+            "║  with ZFSBootMenu for a powerful, modern boot system.     ║",
             "║                                                           ║",
             "╚═══════════════════════════════════════════════════════════╝",
             "",
@@ -129,12 +191,12 @@ impl UiRunner {
             "   ✓ Boot environment management",
             "",
             "",
-            "Press ENTER to continue or Q to quit",
+            tr("ui.welcome.continue"),
         ];
 
         let mut y = start_y;
         for msg in &messages {
-            let x = (cols - msg.len() as u32) / 2;
+            let x = layout::center_text(cols, msg);
             let color = if msg.contains("WARNING") {
                 channels::RED_ON_BLACK
             } else if msg.contains("Features") {
@@ -148,7 +210,7 @@ impl UiRunner {
             } else {
                 channels::WHITE_ON_BLACK
             };
-            ctx.putstr_yx(y, x, msg, color)?;
+            ctx.putstr_yx(y, x, &layout::truncate(msg, cols), color)?;
             y += 1;
         }
 
@@ -175,23 +237,28 @@ impl UiRunner {
         let (_rows, cols) = ctx.dimensions();
 
         // Draw prompt
-        let prompt = "Select Installation Mode:";
-        ctx.putstr_yx(5, (cols - prompt.len() as u32) / 2, prompt, channels::CYAN_ON_BLACK)?;
+        let prompt = tr("ui.mode.prompt");
+        ctx.putstr_yx(5, layout::center_text(cols, prompt), prompt, channels::CYAN_ON_BLACK)?;
 
         // Create menu items
         let items = vec![
-            MenuItem::new("New Installation")
-                .with_description("Fresh ZFS installation on empty drives"),
-            MenuItem::new("Migrate Existing System")
-                .with_description("Move an existing system to ZFS"),
+            MenuItem::new(tr("ui.mode.new"))
+                .with_description(tr("ui.mode.new_description")),
+            MenuItem::new(tr("ui.mode.existing"))
+                .with_description(tr("ui.mode.existing_description")),
         ];
 
-        let mut menu = Menu::new(items, 8, (cols - 50) / 2, 50);
+        let current = match self.config.mode {
+            InstallMode::New => 0,
+            InstallMode::Existing => 1,
+        };
+        let mut menu = Menu::new(items, 8, layout::center(cols, 50), 50).with_selected(self.remembered_selected(current));
 
         ctx.render()?;
 
         // Handle input
         loop {
+            self.remember_selected(menu.selected());
             menu.render(ctx)?;
             ctx.render()?;
 
@@ -233,9 +300,9 @@ impl UiRunner {
             "  ✓ Virtual devices",
         ];
 
-        let start_y = (rows - messages.len() as u32) / 2;
+        let start_y = layout::center(rows, messages.len() as u32);
         for (i, msg) in messages.iter().enumerate() {
-            let x = (cols - msg.len() as u32) / 2;
+            let x = layout::center_text(cols, msg);
             let color = if msg.contains("✓") {
                 channels::GREEN_ON_BLACK
             } else {
@@ -247,12 +314,14 @@ impl UiRunner {
         // Draw a progress bar
         ctx.draw_progress_bar(
             start_y + messages.len() as u32 + 2,
-            cols / 2 - 20,
+            layout::center(cols, 40),
             40,
             1.0,
             Some("100%"),
-            channels::GREEN_ON_BLACK,
-            channels::from_rgb(50, 50, 50, 0, 0, 0),
+            BarStyle {
+                filled: channels::GREEN_ON_BLACK,
+                empty: channels::from_rgb(50, 50, 50, 0, 0, 0),
+            },
         )?;
 
         ctx.render()?;
@@ -266,18 +335,21 @@ impl UiRunner {
     fn show_device_select(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
         let (rows, cols) = ctx.dimensions();
 
-        // Discover devices
-        let discovery = DeviceDiscovery::new()?;
-        let devices = discovery.scan_devices()?;
+        // Discover devices, then keep watching for hot-plugged ones
+        let mut discovery = DeviceDiscovery::new()?;
+        if let Err(e) = discovery.enable_hotplug_detection() {
+            log::warn!("Hot-plug detection unavailable: {}", e);
+        }
+        let mut devices = discovery.scan_devices()?;
 
         if devices.is_empty() {
             let mut dialog = Dialog::new(
-                "No Devices Found",
+                tr("ui.devices.none_found"),
                 vec![
-                    "No suitable block devices were found.".to_string(),
-                    "Please ensure devices are connected.".to_string(),
+                    tr("ui.devices.none_found_detail").to_string(),
+                    tr("ui.devices.none_found_hint").to_string(),
                 ],
-                vec!["Back".to_string()],
+                vec![tr("ui.button.back").to_string()],
             );
             dialog.center(rows, cols);
             dialog.render(ctx)?;
@@ -286,43 +358,118 @@ impl UiRunner {
             return Ok(ScreenAction::Previous);
         }
 
-        // Create device list
+        // Create device list; checked devices hold data unless given another role.
+        // Devices checked when the screen was left (or configured) start checked.
+        let checked = self.remembered_devices();
+        let mut roles: HashMap<String, DeviceRole> = checked
+            .iter()
+            .filter(|(_, &role)| role != DeviceRole::Data)
+            .map(|(name, &role)| (name.clone(), role))
+            .collect();
         let device_strings: Vec<String> = devices
             .iter()
-            .map(|d| format!("{:<10} {:<12} {:<8} {}", d.name, d.display_name(),
-                if d.rotational { "HDD" } else { "SSD" }, d.controller_type))
+            .map(|d| device_row(d, roles.get(&d.name).copied().unwrap_or_default()))
             .collect();
-
-        let mut checklist = CheckList::new(device_strings, 6, 5, rows - 12);
-
-        // Draw instructions
-        ctx.putstr_yx(4, 5, "Select devices for installation:", channels::CYAN_ON_BLACK)?;
-        ctx.putstr_yx(
-            rows - 4,
-            5,
-            "Space: Toggle | Enter: Continue | Esc: Back",
-            channels::YELLOW_ON_BLACK,
-        )?;
-
-        ctx.render()?;
-
-        // Handle input
+        let is_checked = devices.iter().map(|d| checked.contains_key(&d.name)).collect();
+        let mut checklist = CheckList::new(Vec::new(), 6, 5, cols.saturating_sub(11), rows.saturating_sub(12));
+        checklist.set_items(device_strings, is_checked, self.remembered_selected(0));
+        disable_boot_media(&mut checklist, &devices);
+        roles.retain(|name, _| {
+            devices
+                .iter()
+                .position(|d| &d.name == name)
+                .is_some_and(|i| checklist.is_checked(i))
+        });
+
+        // Only devices matching the filter are listed, in the chosen order
+        let mut filter = String::new();
+        let mut sort = DeviceSort::default();
+        checklist.set_view(device_view(&devices, &filter, sort));
+
+        self.draw_device_select(ctx)?;
+
+        // Handle input, merging device changes while no key is pressed
         loop {
+            self.remember_devices(&devices, &checklist, &roles);
             checklist.render(ctx)?;
+            let position = format!("{:>9}", checklist.position());
+            ctx.putstr_yx(4, cols.saturating_sub(15), &position, channels::from_rgb(150, 150, 150, 0, 0, 0))?;
+            let mut status = tr_args(
+                "ui.devices.shown",
+                &[("shown", &checklist.shown()), ("total", &checklist.len()), ("selected", &checklist.checked_indices().len())],
+            );
+            status = format!("{} | {}", status, tr_args("ui.devices.sort", &[("sort", &sort.label())]));
+            if !filter.is_empty() {
+                status = format!("{} | {}", status, tr_args("ui.devices.filter", &[("filter", &filter)]));
+            }
+            let width = cols.saturating_sub(10);
+            let status = format!("{:<width$}", layout::truncate(&status, width), width = width as usize);
+            ctx.putstr_yx(rows.saturating_sub(5), 5, &status, channels::from_rgb(150, 150, 150, 0, 0, 0))?;
             ctx.render()?;
 
-            let input = ctx.get_blocking()?;
+            let Some(input) = ctx.get_nonblocking()? else {
+                let events = discovery.poll_events().unwrap_or_else(|e| {
+                    log::warn!("Failed to read device events: {}", e);
+                    Vec::new()
+                });
+                if !events.is_empty() {
+                    let removed = merge_device_events(&discovery, &mut devices, &mut checklist, &mut roles, &events);
+                    checklist.set_view(device_view(&devices, &filter, sort));
+                    self.draw_device_select(ctx)?;
+                    if !removed.is_empty() {
+                        let mut dialog = Dialog::new(
+                            tr("ui.devices.removed"),
+                            vec![tr_args("ui.devices.removed_detail", &[("devices", &removed.join(", "))])],
+                            vec![tr("ui.button.ok").to_string()],
+                        );
+                        dialog.center(rows, cols);
+                        dialog.render(ctx)?;
+                        ctx.render()?;
+                        ctx.get_blocking()?;
+                        self.draw_device_select(ctx)?;
+                    }
+                }
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+
             match input.id {
                 NCKEY_UP => checklist.select_prev(),
                 NCKEY_DOWN => checklist.select_next(),
-                NCKEY_SPACE => checklist.toggle_selected(),
+                NCKEY_PGUP => checklist.page_up(),
+                NCKEY_PGDOWN => checklist.page_down(),
+                NCKEY_HOME => checklist.select_first(),
+                NCKEY_END => checklist.select_last(),
+                NCKEY_SPACE => {
+                    checklist.toggle_selected();
+                    let index = checklist.selected();
+                    if let Some(device) = devices.get(index) {
+                        if !checklist.is_checked(index) && roles.remove(&device.name).is_some() {
+                            checklist.set_item(index, device_row(device, DeviceRole::Data));
+                        }
+                    }
+                }
+                NCKEY_RIGHT => {
+                    if let Some(device) = devices.get(checklist.selected()) {
+                        self.show_device_details(ctx, device)?;
+                        self.draw_device_select(ctx)?;
+                    }
+                }
                 NCKEY_ENTER => {
                     let selected = checklist.checked_indices();
-                    if selected.is_empty() {
+                    let role_of = |i: usize| roles.get(&devices[i].name).copied().unwrap_or_default();
+                    let with_role = |role: DeviceRole| -> Vec<PathBuf> {
+                        selected
+                            .iter()
+                            .filter(|&&i| role_of(i) == role)
+                            .map(|&i| PathBuf::from(format!("/dev/{}", devices[i].name)))
+                            .collect()
+                    };
+                    if with_role(DeviceRole::Data).is_empty() {
                         let mut dialog = Dialog::new(
-                            "No Devices Selected",
-                            vec!["Please select at least one device.".to_string()],
-                            vec!["OK".to_string()],
+                            tr("ui.devices.none_selected"),
+                            vec![tr("ui.devices.none_selected_detail").to_string()],
+                            vec![tr("ui.button.ok").to_string()],
                         );
                         dialog.center(rows, cols);
                         dialog.render(ctx)?;
@@ -331,76 +478,172 @@ impl UiRunner {
                         continue;
                     }
 
-                    self.config.devices = selected
+                    // The TUI builds a single data vdev, configured on the next screen
+                    self.device_sizes = devices
                         .iter()
-                        .map(|&i| PathBuf::from(format!("/dev/{}", devices[i].name)))
+                        .map(|d| (PathBuf::from(format!("/dev/{}", d.name)), d.size))
                         .collect();
+                    self.config.vdevs.clear();
+                    self.config.devices = with_role(DeviceRole::Data);
+                    self.config.log_devices = with_role(DeviceRole::Log);
+                    self.config.mirror_log = self.config.log_devices.len() > 1;
+                    self.config.cache_devices = with_role(DeviceRole::Cache);
+                    self.config.spare_devices = with_role(DeviceRole::Spare);
+
+                    // Keep the RAID level unless there are now too few devices for it
+                    if self.config.raid_level.min_drives() > self.config.devices.len() {
+                        self.config.raid_level = RaidLevel::None;
+                        self.screen_state.remove(&Screen::RaidConfig);
+                    }
 
                     return Ok(ScreenAction::Next);
                 }
                 NCKEY_ESC => return Ok(ScreenAction::Previous),
-                _ => {
-                    if let Some(ch) = char::from_u32(input.id) {
-                        if ch == 'q' || ch == 'Q' {
-                            return Ok(ScreenAction::Exit);
+                _ => match char::from_u32(input.id) {
+                    Some('q' | 'Q') => return Ok(ScreenAction::Exit),
+                    Some('i' | 'I') => {
+                        if let Some(device) = devices.get(checklist.selected()) {
+                            self.show_device_details(ctx, device)?;
+                            self.draw_device_select(ctx)?;
                         }
                     }
-                }
+                    Some('/') => {
+                        if let Some(text) = self.read_input(ctx, tr("ui.devices.filter_prompt"), &filter)? {
+                            filter = text.trim().to_string();
+                            checklist.set_view(device_view(&devices, &filter, sort));
+                        }
+                        self.draw_device_select(ctx)?;
+                    }
+                    Some('s' | 'S') => {
+                        sort = sort.next();
+                        checklist.set_view(device_view(&devices, &filter, sort));
+                    }
+                    // Check every device shown, or uncheck them and drop their roles
+                    Some('a') => checklist.check_shown(true),
+                    Some('A') => {
+                        checklist.check_shown(false);
+                        for (index, device) in devices.iter().enumerate() {
+                            if !checklist.is_checked(index) && roles.remove(&device.name).is_some() {
+                                checklist.set_item(index, device_row(device, DeviceRole::Data));
+                            }
+                        }
+                    }
+                    Some('r' | 'R') => {
+                        let index = checklist.selected();
+                        if let Some(device) = devices.get(index).filter(|_| checklist.is_checked(index)) {
+                            let role = roles.get(&device.name).copied().unwrap_or_default().next();
+                            if role == DeviceRole::Data {
+                                roles.remove(&device.name);
+                            } else {
+                                roles.insert(device.name.clone(), role);
+                            }
+                            checklist.set_item(index, device_row(device, role));
+                        }
+                    }
+                    _ => {}
+                },
             }
         }
     }
 
-    fn show_raid_config(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
-        let (_rows, cols) = ctx.dimensions();
+    /// Show everything known about one device until Esc or Left is pressed
+    fn show_device_details(&self, ctx: &mut NotcursesContext, device: &BlockDevice) -> Result<()> {
+        let (rows, cols) = ctx.dimensions();
 
-        ctx.putstr_yx(5, (cols - 30) / 2, "Select RAID Level:", channels::CYAN_ON_BLACK)?;
+        let members = zfs::status::imported_members(&SystemExecutor::new(false)).unwrap_or_else(|e| {
+            log::debug!("Could not list imported pools: {}", e);
+            Vec::new()
+        });
+        let pool = device.zfs_pool(&members);
+        let mut details = CheckList::new(device.details(pool.as_deref()), 6, 5, cols.saturating_sub(11), rows.saturating_sub(12)).without_checkboxes();
 
-        let device_count = self.config.devices.len();
+        ctx.clear()?;
+        self.draw_header(ctx)?;
+        let title = tr_args("ui.device_info.title", &[("device", &device.name)]);
+        ctx.putstr_yx(4, 5, &title, channels::CYAN_ON_BLACK)?;
+        ctx.putstr_yx(rows.saturating_sub(4), 5, tr("ui.device_info.help"), channels::YELLOW_ON_BLACK)?;
 
-        // Create menu with RAID options
-        let mut items = vec![
-            MenuItem::new("None (Striped or Single)")
-                .with_description("No redundancy - maximum capacity"),
-        ];
+        loop {
+            details.render(ctx)?;
+            ctx.render()?;
 
-        if device_count >= 2 {
-            items.push(
-                MenuItem::new("Mirror (RAID1)")
-                    .with_description("Can lose N-1 drives - 50% capacity"),
-            );
+            let input = ctx.get_blocking()?;
+            match input.id {
+                NCKEY_UP => details.select_prev(),
+                NCKEY_DOWN => details.select_next(),
+                NCKEY_PGUP => details.page_up(),
+                NCKEY_PGDOWN => details.page_down(),
+                NCKEY_HOME => details.select_first(),
+                NCKEY_END => details.select_last(),
+                NCKEY_ESC | NCKEY_LEFT => return Ok(()),
+                _ => {}
+            }
         }
+    }
 
-        if device_count >= 3 {
-            items.push(
-                MenuItem::new("RAIDZ1 (RAID5)")
-                    .with_description("Can lose 1 drive - (N-1)/N capacity"),
-            );
-        }
+    /// Redraw the device selection screen around the list
+    fn draw_device_select(&self, ctx: &mut NotcursesContext) -> Result<()> {
+        let (rows, _cols) = ctx.dimensions();
+        ctx.clear()?;
+        self.draw_header(ctx)?;
+        ctx.putstr_yx(4, 5, tr("ui.devices.prompt"), channels::CYAN_ON_BLACK)?;
+        ctx.putstr_yx(
+            rows.saturating_sub(4),
+            5,
+            tr("ui.devices.help"),
+            channels::YELLOW_ON_BLACK,
+        )?;
+        ctx.putstr_yx(
+            rows.saturating_sub(3),
+            5,
+            tr("ui.devices.help_list"),
+            channels::YELLOW_ON_BLACK,
+        )?;
+        Ok(())
+    }
 
-        if device_count >= 4 {
-            items.push(
-                MenuItem::new("RAIDZ2 (RAID6)")
-                    .with_description("Can lose 2 drives - (N-2)/N capacity"),
-            );
-        }
+    fn show_raid_config(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
+        let (_rows, cols) = ctx.dimensions();
 
-        if device_count >= 5 {
-            items.push(
-                MenuItem::new("RAIDZ3")
-                    .with_description("Can lose 3 drives - (N-3)/N capacity"),
-            );
-        }
+        ctx.putstr_yx(5, layout::center(cols, 30), tr("ui.raid.prompt"), channels::CYAN_ON_BLACK)?;
+
+        let device_count = self.config.devices.len();
 
-        let mut menu = Menu::new(items, 8, (cols - 60) / 2, 60);
+        // Create menu with the RAID options there are enough devices for,
+        // with the space each would leave
+        let options = [
+            (RaidLevel::None, "None (Striped or Single)", "No redundancy - maximum capacity"),
+            (RaidLevel::Mirror, "Mirror (RAID1)", "Can lose N-1 drives - 50% capacity"),
+            (RaidLevel::Raidz1, "RAIDZ1 (RAID5)", "Can lose 1 drive - (N-1)/N capacity"),
+            (RaidLevel::Raidz2, "RAIDZ2 (RAID6)", "Can lose 2 drives - (N-2)/N capacity"),
+            (RaidLevel::Raidz3, "RAIDZ3", "Can lose 3 drives - (N-3)/N capacity"),
+        ];
+        let items: Vec<MenuItem> = options
+            .iter()
+            .filter(|(level, ..)| *level == RaidLevel::None || level.min_drives() <= device_count)
+            .map(|&(level, name, description)| {
+                let description = match self.usable_capacity(level) {
+                    Some(size) => format!("{}, {}", description, tr_args("ui.raid.usable", &[("size", &size)])),
+                    None => description.to_string(),
+                };
+                MenuItem::new(name).with_description(description)
+            })
+            .collect();
+
+        // Highlight where the screen was left, else the level already chosen
+        let current = RaidLevel::ALL.iter().position(|&level| level == self.config.raid_level).unwrap_or(0);
+        let selected = Some(self.remembered_selected(current)).filter(|&i| i < items.len()).unwrap_or(current);
+        let mut menu = Menu::new(items, 8, layout::center(cols, 60), 60).with_selected(selected);
 
         // Show device count
-        let dev_info = format!("Selected devices: {}", device_count);
-        ctx.putstr_yx(7, (cols - dev_info.len() as u32) / 2, &dev_info, channels::from_rgb(150, 150, 150, 0, 0, 0))?;
+        let dev_info = tr_args("ui.raid.selected_devices", &[("count", &device_count)]);
+        ctx.putstr_yx(7, layout::center_text(cols, &dev_info), &dev_info, channels::from_rgb(150, 150, 150, 0, 0, 0))?;
 
         ctx.render()?;
 
         // Handle input
         loop {
+            self.remember_selected(menu.selected());
             menu.render(ctx)?;
             ctx.render()?;
 
@@ -409,14 +652,7 @@ impl UiRunner {
                 NCKEY_UP => menu.select_prev(),
                 NCKEY_DOWN => menu.select_next(),
                 NCKEY_ENTER => {
-                    self.config.raid_level = match menu.selected() {
-                        0 => RaidLevel::None,
-                        1 => RaidLevel::Mirror,
-                        2 => RaidLevel::Raidz1,
-                        3 => RaidLevel::Raidz2,
-                        4 => RaidLevel::Raidz3,
-                        _ => RaidLevel::None,
-                    };
+                    self.config.raid_level = RaidLevel::ALL.get(menu.selected()).copied().unwrap_or(RaidLevel::None);
                     return Ok(ScreenAction::Next);
                 }
                 NCKEY_ESC => return Ok(ScreenAction::Previous),
@@ -434,23 +670,15 @@ impl UiRunner {
     fn show_settings(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
         let (_rows, cols) = ctx.dimensions();
 
-        ctx.putstr_yx(4, (cols - 30) / 2, "Installation Settings:", channels::CYAN_ON_BLACK)?;
-
-        // Create menu for settings
-        let items = vec![
-            MenuItem::new(format!("Pool Name: {}", self.config.pool_name)),
-            MenuItem::new(format!("Compression: {}", self.config.compression)),
-            MenuItem::new(format!("EFI Size: {}", self.config.efi_size)),
-            MenuItem::new(format!("Swap Size: {}", self.config.swap_size)),
-            MenuItem::new("Continue →"),
-        ];
-
-        let mut menu = Menu::new(items, 7, (cols - 50) / 2, 50);
-
-        ctx.render()?;
+        let mut menu = Menu::new(self.settings_items(), 7, layout::center(cols, 50), 50).with_selected(self.remembered_selected(0));
 
         // Handle input
         loop {
+            self.remember_selected(menu.selected());
+            // Editors draw over the screen, so redraw it all each time
+            ctx.clear()?;
+            self.draw_header(ctx)?;
+            ctx.putstr_yx(4, layout::center(cols, 30), tr("ui.settings.prompt"), channels::CYAN_ON_BLACK)?;
             menu.render(ctx)?;
             ctx.render()?;
 
@@ -460,12 +688,104 @@ impl UiRunner {
                 NCKEY_DOWN => menu.select_next(),
                 NCKEY_ENTER => {
                     match menu.selected() {
-                        4 => return Ok(ScreenAction::Next), // Continue
-                        _ => {
-                            // Could implement editing here
-                            // For now, just continue
+                        0 => {
+                            let label = tr("ui.label.pool_name");
+                            let current = self.config.pool_name.clone();
+                            if let Some(name) = self.edit_value(ctx, label, &current, |value| {
+                                Config::validate_pool_name(value).map(|()| value.to_string())
+                            })? {
+                                self.config.pool_name = name;
+                            }
+                        }
+                        1 => {
+                            if let Some(compression) = self.choose_compression(ctx)? {
+                                self.config.compression = compression;
+                            }
                         }
+                        2 => {
+                            let label = tr("ui.label.efi_size");
+                            let current = self.config.efi_size.to_string();
+                            if let Some(size) = self.edit_value(ctx, label, &current, |value| {
+                                let size = parse_size(value)?;
+                                Config::validate_efi_size(size).map(|()| size)
+                            })? {
+                                self.config.efi_size = size;
+                            }
+                        }
+                        3 => {
+                            let label = tr("ui.label.swap_size");
+                            let current = self.config.swap_size.to_string();
+                            if let Some(size) = self.edit_value(ctx, label, &current, parse_size)? {
+                                self.config.swap_size = size;
+                            }
+                        }
+                        4 => {
+                            let label = tr("ui.label.kernel_cmdline");
+                            let current = self.config.kernel_cmdline.clone();
+                            if let Some(cmdline) = self.edit_value(ctx, label, &current, |value| {
+                                Config::validate_kernel_cmdline(value).map(|()| value.to_string())
+                            })? {
+                                self.config.kernel_cmdline = cmdline;
+                            }
+                        }
+                        // Clearing the field leaves the value as it is on the source system
+                        5 => {
+                            let current = self.config.hostname.clone().unwrap_or_default();
+                            if let Some(hostname) = self.edit_value(ctx, tr("ui.label.hostname"), &current, |value| {
+                                optional(value, Config::validate_hostname)
+                            })? {
+                                self.config.hostname = hostname;
+                            }
+                        }
+                        6 => {
+                            let current = self.config.timezone.clone().unwrap_or_default();
+                            if let Some(timezone) = self.edit_value(ctx, tr("ui.label.timezone"), &current, |value| {
+                                optional(value, Config::validate_timezone)
+                            })? {
+                                self.config.timezone = timezone;
+                            }
+                        }
+                        7 => {
+                            let current = self.config.locale.clone().unwrap_or_default();
+                            if let Some(locale) = self.edit_value(ctx, tr("ui.label.locale"), &current, |value| {
+                                optional(value, |name| Config::validate_locale_name("locale", name))
+                            })? {
+                                self.config.locale = locale;
+                            }
+                        }
+                        8 => {
+                            let current = self.config.keymap.clone().unwrap_or_default();
+                            if let Some(keymap) = self.edit_value(ctx, tr("ui.label.keymap"), &current, |value| {
+                                optional(value, |name| Config::validate_locale_name("keymap", name))
+                            })? {
+                                self.config.keymap = keymap;
+                            }
+                        }
+                        // An admin account, asked for by name and then password
+                        9 => {
+                            if let Some(name) = self.edit_value(ctx, tr("ui.label.new_user"), "", |value| {
+                                Config::validate_account_name("user", value).map(|()| value.to_string())
+                            })? {
+                                if let Some(hash) = self.read_password(ctx, &name)? {
+                                    self.config.users.retain(|user| user.name != name);
+                                    self.config.users.push(UserSpec {
+                                        admin: true,
+                                        password_hash: Some(hash),
+                                        ..UserSpec::new(name)
+                                    });
+                                }
+                            }
+                        }
+                        10 => {
+                            if let Some(hash) = self.read_password(ctx, "root")? {
+                                self.config.root_password_hash = Some(hash);
+                                self.config.lock_root = false;
+                            }
+                        }
+                        11 => self.config.lock_root = !self.config.lock_root,
+                        _ => return Ok(ScreenAction::Next), // Continue
                     }
+                    menu.set_items(self.settings_items());
                 }
                 NCKEY_ESC => return Ok(ScreenAction::Previous),
                 _ => {
@@ -479,81 +799,449 @@ impl UiRunner {
         }
     }
 
-    fn show_preflight(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
+    /// Turn native encryption on or off and choose how the key is given
+    ///
+    /// A passphrase has to be entered before continuing with encryption on;
+    /// a key file is checked by the pre-flight checks.
+    fn show_encryption(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
         let (_rows, cols) = ctx.dimensions();
 
-        let start_y = 5;
+        let mut menu = Menu::new(self.encryption_items(), 7, layout::center(cols, 50), 50).with_selected(self.remembered_selected(0));
+
+        loop {
+            self.remember_selected(menu.selected());
+            ctx.clear()?;
+            self.draw_header(ctx)?;
+            ctx.putstr_yx(4, layout::center(cols, 30), tr("ui.encryption.prompt"), channels::CYAN_ON_BLACK)?;
+            menu.render(ctx)?;
+            ctx.render()?;
 
-        ctx.putstr_yx(start_y, (cols - 30) / 2, "Running Pre-flight Checks...", channels::CYAN_ON_BLACK)?;
+            let input = ctx.get_blocking()?;
+            match input.id {
+                NCKEY_UP => menu.select_prev(),
+                NCKEY_DOWN => menu.select_next(),
+                NCKEY_ENTER => {
+                    // None while off, else whether the passphrase is prompted for
+                    let prompt = self.config.encryption.as_ref().map(EncryptionConfig::uses_prompt);
+                    let passphrase_set = self.config.encryption.as_ref().is_some_and(|e| e.passphrase.is_some());
+                    match (menu.selected(), prompt) {
+                        (0, Some(_)) => self.config.encryption = None,
+                        (0, None) => self.config.encryption = Some(EncryptionConfig::default()),
+                        // Switching between a passphrase and a key file
+                        (1, Some(true)) => {
+                            let label = tr("ui.label.key_file");
+                            if let Some(path) = self.edit_value(ctx, label, "", |value| {
+                                if value.starts_with('/') {
+                                    Ok(value.to_string())
+                                } else {
+                                    Err(InstallerError::validation("The key file must be an absolute path"))
+                                }
+                            })? {
+                                let encryption = self.config.encryption.get_or_insert_with(Default::default);
+                                encryption.keylocation = Some(format!("file://{}", path));
+                                encryption.passphrase = None;
+                            }
+                        }
+                        (1, Some(false)) => {
+                            if let Some(encryption) = self.config.encryption.as_mut() {
+                                encryption.keylocation = None;
+                            }
+                        }
+                        (2, Some(true)) => {
+                            if let Some(passphrase) = self.read_passphrase(ctx)? {
+                                if let Some(encryption) = self.config.encryption.as_mut() {
+                                    encryption.passphrase = Some(passphrase);
+                                }
+                            }
+                        }
+                        (_, Some(true)) if !passphrase_set => {
+                            self.show_message(ctx, tr("ui.encryption.title"), tr("ui.encryption.passphrase_missing"))?;
+                        }
+                        _ => return Ok(ScreenAction::Next),
+                    }
+                    menu.set_items(self.encryption_items());
+                }
+                NCKEY_ESC => return Ok(ScreenAction::Previous),
+                _ => {
+                    if let Some(ch) = char::from_u32(input.id) {
+                        if ch == 'q' || ch == 'Q' {
+                            return Ok(ScreenAction::Exit);
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-        let checks = vec![
-            ("Checking root privileges", true),
-            ("Verifying ZFS modules", true),
-            ("Checking disk availability", true),
-            ("Verifying partition alignment", true),
-            ("Checking available space", true),
+    /// Encryption menu entries; the key entries only while encryption is on
+    fn encryption_items(&self) -> Vec<MenuItem> {
+        let Some(encryption) = &self.config.encryption else {
+            return vec![
+                MenuItem::new(format!("{}: {}", tr("ui.label.encryption"), tr("ui.button.no"))),
+                MenuItem::new(tr("ui.settings.continue")),
+            ];
+        };
+        let key = if encryption.uses_prompt() {
+            tr("ui.encryption.passphrase").to_string()
+        } else {
+            tr_args("ui.encryption.key_file", &[("path", &encryption.keylocation())])
+        };
+        let mut items = vec![
+            MenuItem::new(format!("{}: {}", tr("ui.label.encryption"), tr("ui.button.yes"))),
+            MenuItem::new(format!("{}: {}", tr("ui.label.key"), key)),
         ];
+        if encryption.uses_prompt() {
+            let passphrase = match encryption.passphrase {
+                Some(_) => tr("ui.settings.password_set"),
+                None => "-",
+            };
+            items.push(MenuItem::new(format!("{}: {}", tr("ui.label.passphrase"), passphrase)));
+        }
+        items.push(MenuItem::new(tr("ui.settings.continue")));
+        items
+    }
 
-        for (i, (check, passed)) in checks.iter().enumerate() {
-            let y = start_y + 2 + i as u32;
-            let status = if *passed { "✓" } else { "✗" };
-            let color = if *passed {
-                channels::GREEN_ON_BLACK
-            } else {
-                channels::RED_ON_BLACK
+    /// Ask for the encryption passphrase twice, masked, showing how strong
+    /// it is while it's typed; `None` if cancelled
+    ///
+    /// Mismatched or too short entries are explained and asked for again.
+    /// Whatever was typed is scrubbed from memory once it's been used.
+    fn read_passphrase(&mut self, ctx: &mut NotcursesContext) -> Result<Option<Passphrase>> {
+        let (rows, cols) = ctx.dimensions();
+        let (y, x) = ((rows / 2).saturating_sub(2), layout::center(cols, 60));
+
+        loop {
+            let field = InputField::new(tr("ui.label.passphrase"), "", y, x, 60).masked();
+            let Some(mut first) = self.run_input_with(ctx, field, &|value| Some(strength_hint(value)))? else {
+                return Ok(None);
+            };
+            let field = InputField::new(tr("ui.label.passphrase_confirm"), "", y, x, 60).masked();
+            let Some(mut second) = self.run_input(ctx, field)? else {
+                scrub(&mut first);
+                return Ok(None);
             };
+            let confirmed = Passphrase::confirmed(&first, &second);
+            scrub(&mut first);
+            scrub(&mut second);
+            match confirmed {
+                Ok(passphrase) => return Ok(Some(passphrase)),
+                Err(e) => self.show_message(ctx, tr("ui.settings.invalid"), &e.to_string())?,
+            }
+        }
+    }
 
-            ctx.putstr_yx(y, (cols - 50) / 2, &format!("  {} {}", status, check), color)?;
+    /// Settings menu entries, showing the current values
+    fn settings_items(&self) -> Vec<MenuItem> {
+        let names: Vec<&str> = self.config.users.iter().map(|user| user.name.as_str()).collect();
+        let users = if names.is_empty() { "-".to_string() } else { names.join(", ") };
+        let root_password = match self.config.root_password_hash {
+            Some(_) => tr("ui.settings.password_set"),
+            None => "-",
+        };
+        let lock_root = tr(if self.config.lock_root { "ui.button.yes" } else { "ui.button.no" });
+
+        vec![
+            MenuItem::new(format!("{}: {}", tr("ui.label.pool_name"), self.config.pool_name)),
+            MenuItem::new(format!("{}: {}", tr("ui.label.compression"), self.config.compression)),
+            MenuItem::new(format!("{}: {}", tr("ui.label.efi_size"), self.config.efi_size)),
+            MenuItem::new(format!("{}: {}", tr("ui.label.swap_size"), self.config.swap_size)),
+            MenuItem::new(format!("{}: {}", tr("ui.label.kernel_cmdline"), self.config.kernel_cmdline)),
+            MenuItem::new(format!("{}: {}", tr("ui.label.hostname"), or_unchanged(&self.config.hostname))),
+            MenuItem::new(format!("{}: {}", tr("ui.label.timezone"), or_unchanged(&self.config.timezone))),
+            MenuItem::new(format!("{}: {}", tr("ui.label.locale"), or_unchanged(&self.config.locale))),
+            MenuItem::new(format!("{}: {}", tr("ui.label.keymap"), or_unchanged(&self.config.keymap))),
+            MenuItem::new(format!("{}: {}", tr("ui.label.users"), users)),
+            MenuItem::new(format!("{}: {}", tr("ui.label.root_password"), root_password)),
+            MenuItem::new(format!("{}: {}", tr("ui.label.lock_root"), lock_root)),
+            MenuItem::new(tr("ui.settings.continue")),
+        ]
+    }
+
+    /// Edit a value until `parse` accepts it; `None` if the edit was cancelled
+    ///
+    /// Rejected input is explained in a dialog and left in the field to fix.
+    fn edit_value<T>(
+        &mut self,
+        ctx: &mut NotcursesContext,
+        label: &str,
+        current: &str,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let mut value = current.to_string();
 
+        loop {
+            let Some(entered) = self.read_input(ctx, label, &value)? else {
+                return Ok(None);
+            };
+            match parse(entered.trim()) {
+                Ok(parsed) => return Ok(Some(parsed)),
+                Err(e) => {
+                    self.show_message(ctx, tr("ui.settings.invalid"), &e.to_string())?;
+                    value = entered;
+                }
+            }
+        }
+    }
+
+    /// Pick a compression algorithm from a menu; `None` if cancelled
+    fn choose_compression(&mut self, ctx: &mut NotcursesContext) -> Result<Option<Compression>> {
+        let (rows, cols) = ctx.dimensions();
+
+        let items = Compression::ALL.iter().map(|c| MenuItem::new(c.to_string())).collect();
+        let current = Compression::ALL.iter().position(|c| *c == self.config.compression).unwrap_or(0);
+        let mut menu = Menu::new(items, (rows / 2).saturating_sub(2), layout::center(cols, 20), 20).with_selected(current);
+
+        loop {
+            ctx.clear()?;
+            self.draw_header(ctx)?;
+            ctx.putstr_yx((rows / 2).saturating_sub(4), layout::center(cols, 20), tr("ui.label.compression"), channels::CYAN_ON_BLACK)?;
+            menu.render(ctx)?;
             ctx.render()?;
-            std::thread::sleep(std::time::Duration::from_millis(300));
+
+            let input = ctx.get_blocking()?;
+            match input.id {
+                NCKEY_UP => menu.select_prev(),
+                NCKEY_DOWN => menu.select_next(),
+                NCKEY_ENTER => return Ok(Some(Compression::ALL[menu.selected()])),
+                NCKEY_ESC => return Ok(None),
+                _ => {}
+            }
         }
+    }
 
-        ctx.putstr_yx(
-            start_y + 2 + checks.len() as u32 + 2,
-            (cols - 40) / 2,
-            "All checks passed! Press ENTER to continue",
-            channels::GREEN_ON_BLACK,
-        )?;
+    /// Read a line of text in an input field; `None` if cancelled with Esc
+    fn read_input(&mut self, ctx: &mut NotcursesContext, label: &str, initial: &str) -> Result<Option<String>> {
+        let (rows, cols) = ctx.dimensions();
+        let field = InputField::new(label, initial, (rows / 2).saturating_sub(2), layout::center(cols, 60), 60);
+        self.run_input(ctx, field)
+    }
 
-        ctx.render()?;
+    /// Ask for a password twice, masked, and hash it; `None` if cancelled
+    ///
+    /// Mismatched or too short entries are explained and asked for again.
+    fn read_password(&mut self, ctx: &mut NotcursesContext, name: &str) -> Result<Option<PasswordHash>> {
+        let (rows, cols) = ctx.dimensions();
+        let label = tr_args("ui.label.password", &[("name", &name)]);
 
-        // Wait for input
         loop {
+            let field = InputField::new(&label, "", (rows / 2).saturating_sub(2), layout::center(cols, 60), 60).masked();
+            let Some(first) = self.run_input(ctx, field)? else {
+                return Ok(None);
+            };
+            let field = InputField::new(tr("ui.label.password_confirm"), "", (rows / 2).saturating_sub(2), layout::center(cols, 60), 60).masked();
+            let Some(second) = self.run_input(ctx, field)? else {
+                return Ok(None);
+            };
+            match Passphrase::confirmed(&first, &second).and_then(|password| hash_password(&password)) {
+                Ok(hash) => return Ok(Some(hash)),
+                Err(e) => self.show_message(ctx, tr("ui.settings.invalid"), &e.to_string())?,
+            }
+        }
+    }
+
+    /// Let the user edit `field`; `None` if cancelled
+    fn run_input(&mut self, ctx: &mut NotcursesContext, field: InputField) -> Result<Option<String>> {
+        self.run_input_with(ctx, field, &|_| None)
+    }
+
+    /// Let the user edit `field`, showing what `hint` says about the value
+    /// below it; `None` if cancelled
+    fn run_input_with(
+        &mut self,
+        ctx: &mut NotcursesContext,
+        mut field: InputField,
+        hint: &dyn Fn(&str) -> Option<(String, u64)>,
+    ) -> Result<Option<String>> {
+        let (y, x) = field.position();
+        loop {
+            ctx.clear()?;
+            self.draw_header(ctx)?;
+            field.render(ctx)?;
+            if let Some((text, color)) = hint(field.value()) {
+                ctx.putstr_yx(y + 4, x, &text, color)?;
+            }
+            ctx.render()?;
+
             let input = ctx.get_blocking()?;
             match input.id {
-                NCKEY_ENTER => return Ok(ScreenAction::Next),
-                NCKEY_ESC => return Ok(ScreenAction::Previous),
+                NCKEY_ENTER => return Ok(Some(field.value().to_string())),
+                NCKEY_ESC => return Ok(None),
+                NCKEY_BACKSPACE => field.backspace(),
+                NCKEY_LEFT => field.move_cursor_left(),
+                NCKEY_RIGHT => field.move_cursor_right(),
                 _ => {
-                    if let Some(ch) = char::from_u32(input.id) {
-                        if ch == 'q' || ch == 'Q' {
-                            return Ok(ScreenAction::Exit);
-                        }
+                    if let Some(ch) = char::from_u32(input.id).filter(|c| !c.is_control()) {
+                        field.insert_char(ch);
                     }
                 }
             }
         }
     }
 
+    /// Show a message in a dialog and wait for a key
+    fn show_message(&self, ctx: &mut NotcursesContext, title: &str, message: &str) -> Result<()> {
+        let (rows, cols) = ctx.dimensions();
+
+        let mut dialog = Dialog::new(title, vec![message.to_string()], vec![tr("ui.button.ok").to_string()]);
+        dialog.center(rows, cols);
+        dialog.render(ctx)?;
+        ctx.render()?;
+        ctx.get_blocking()?;
+
+        Ok(())
+    }
+
+    /// Run the pre-flight checks, showing each result as it completes
+    ///
+    /// Failed checks block Continue; warnings have to be acknowledged first.
+    fn show_preflight(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
+        let (rows, cols) = ctx.dimensions();
+        let start_y = 5;
+        let x = 5;
+
+        ctx.putstr_yx(start_y, layout::center(cols, 30), tr("ui.preflight.running"), channels::CYAN_ON_BLACK)?;
+        ctx.render()?;
+
+        // The same checks the installer's Validate phase runs
+        let installer = Installer::for_validation(self.config.clone());
+        let (tx, rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            installer.validate_only_with(&mut |check| {
+                let _ = tx.send(check.clone());
+            })
+        });
+
+        let mut shown = 0;
+        loop {
+            for check in rx.try_iter() {
+                let (mark, color) = check_mark(check.severity);
+                let line = format!("{} {}", mark, check.check);
+                ctx.putstr_yx(start_y + 2 + shown, x, &line, color)?;
+                shown += 1;
+            }
+            ctx.render()?;
+            if handle.is_finished() {
+                break;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        let result = handle
+            .join()
+            .map_err(|_| InstallerError::UiError("Pre-flight check thread panicked".into()))?;
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.show_message(ctx, tr("ui.preflight.error"), &e.to_string())?;
+                return Ok(ScreenAction::Previous);
+            }
+        };
+
+        // Every check, with the full detail of warnings and failures below it
+        let width = cols.saturating_sub(2 * x + 4).max(20) as usize;
+        let mut lines = Vec::new();
+        for check in &result.checks {
+            let (mark, color) = check_mark(check.severity);
+            lines.push((format!("{} {}", mark, check.check), color));
+            if let Some(message) = &check.message {
+                for line in wrap_text(message, width) {
+                    lines.push((format!("    {}", line), color));
+                }
+            }
+        }
+
+        let height = rows.saturating_sub(start_y + 8).max(1) as usize;
+        let blocked = !result.is_ok();
+        let mut acknowledged = result.warnings().next().is_none();
+        let mut offset = 0;
+
+        loop {
+            ctx.clear()?;
+            self.draw_header(ctx)?;
+            ctx.putstr_yx(start_y, x, tr("ui.screen.preflight"), channels::CYAN_ON_BLACK)?;
+            for (i, (line, color)) in lines.iter().skip(offset).take(height).enumerate() {
+                ctx.putstr_yx(start_y + 2 + i as u32, x, line, *color)?;
+            }
+
+            let (footer, color) = if blocked {
+                (tr("ui.preflight.failed"), channels::RED_ON_BLACK)
+            } else if !acknowledged {
+                (tr("ui.preflight.warnings"), channels::YELLOW_ON_BLACK)
+            } else if result.warnings().next().is_some() {
+                (tr("ui.preflight.acknowledged"), channels::GREEN_ON_BLACK)
+            } else {
+                (tr("ui.preflight.passed"), channels::GREEN_ON_BLACK)
+            };
+            ctx.putstr_yx(rows.saturating_sub(5), x, footer, color)?;
+            ctx.putstr_yx(rows.saturating_sub(4), x, tr("ui.preflight.help"), channels::YELLOW_ON_BLACK)?;
+            ctx.render()?;
+
+            let input = ctx.get_blocking()?;
+            match input.id {
+                NCKEY_UP => offset = offset.saturating_sub(1),
+                NCKEY_DOWN => {
+                    if offset + height < lines.len() {
+                        offset += 1;
+                    }
+                }
+                NCKEY_ENTER if !blocked && acknowledged => return Ok(ScreenAction::Next),
+                NCKEY_ESC => return Ok(ScreenAction::Previous),
+                _ => match char::from_u32(input.id) {
+                    Some('a' | 'A') => acknowledged = true,
+                    Some('r' | 'R') => return Ok(ScreenAction::Stay),
+                    Some('q' | 'Q') => return Ok(ScreenAction::Exit),
+                    _ => {}
+                },
+            }
+        }
+    }
+
     fn show_confirmation(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
         let (rows, cols) = ctx.dimensions();
 
         // Draw confirmation details
         let start_y = 4;
-        ctx.putstr_yx(start_y, (cols - 40) / 2, "═══ Confirm Installation ═══", channels::CYAN_ON_BLACK)?;
+        ctx.putstr_yx(start_y, layout::center(cols, 40), &format!("═══ {} ═══", tr("ui.screen.confirmation")), channels::CYAN_ON_BLACK)?;
 
         let mut y = start_y + 2;
-        let x = (cols - 60) / 2;
-
-        let details = vec![
-            ("Mode", format!("{}", self.config.mode)),
-            ("Pool Name", self.config.pool_name.clone()),
-            ("RAID Level", format!("{} ({})", self.config.raid_level, self.config.raid_level.description())),
-            ("Devices", format!("{} device(s)", self.config.devices.len())),
-            ("Compression", format!("{}", self.config.compression)),
-            ("EFI Size", format!("{}", self.config.efi_size)),
-            ("Swap Size", format!("{}", self.config.swap_size)),
+        let x = layout::center(cols, 60);
+
+        let mut details = vec![
+            (tr("ui.label.mode"), format!("{}", self.config.mode)),
+            (tr("ui.label.pool_name"), self.config.pool_name.clone()),
+            (tr("ui.label.raid_level"), format!("{} ({})", self.config.raid_level, self.config.raid_level.description())),
         ];
+        if let Some(size) = self.usable_capacity(self.config.raid_level) {
+            let redundancy = self.config.redundancy_summary();
+            details.push((tr("ui.label.usable_capacity"), tr_args("ui.confirm.usable", &[("size", &size), ("redundancy", &redundancy)])));
+        }
+        details.extend([
+            (tr("ui.label.devices"), tr_args("ui.confirm.device_count", &[("count", &self.config.data_devices().len())])),
+            (tr("ui.label.compression"), format!("{}", self.config.compression)),
+            // Only how the key is given, never the passphrase itself
+            (tr("ui.label.encryption"), match &self.config.encryption {
+                None => tr("ui.confirm.encryption_off").to_string(),
+                Some(encryption) if encryption.uses_prompt() => tr("ui.confirm.encryption_passphrase").to_string(),
+                Some(encryption) => tr_args("ui.confirm.encryption_key_file", &[("path", &encryption.keylocation())]),
+            }),
+            (tr("ui.label.boot_mode"), format!("{} ({})", self.config.boot_mode, self.config.boot_mode.description())),
+            (tr("ui.label.efi_size"), format!("{}", self.config.efi_size)),
+            (tr("ui.label.swap_size"), format!("{}", self.config.swap_size)),
+            (tr("ui.label.wipe_mode"), format!("{} ({})", self.config.wipe_mode, self.config.wipe_mode.description())),
+            (tr("ui.label.kernel_cmdline"), self.config.kernel_cmdline.clone()),
+        ]);
+        if self.config.installs_system() {
+            let arc_max = match self.config.arc_max {
+                Some(arc_max) => arc_max.to_string(),
+                None => crate::system::get_system_memory_kb()
+                    .ok()
+                    .filter(|&mem_kb| mem_kb > 0)
+                    .map(|mem_kb| zfs::suggested_arc_max(bytesize::ByteSize::kib(mem_kb)))
+                    .map(|size| tr_args("ui.confirm.arc_suggested", &[("size", &size)]))
+                    .unwrap_or_else(|| tr("ui.confirm.arc_default").to_string()),
+            };
+            details.push((tr("ui.label.arc_max"), arc_max));
+        }
 
         for (label, value) in details {
             ctx.putstr_yx(y, x, &format!("{:<15}: ", label), channels::from_rgb(150, 150, 150, 0, 0, 0))?;
@@ -562,19 +1250,46 @@ impl UiRunner {
         }
 
         y += 1;
-        ctx.putstr_yx(y, x, "Selected devices:", channels::CYAN_ON_BLACK)?;
+        ctx.putstr_yx(y, x, tr("ui.confirm.selected_devices"), channels::CYAN_ON_BLACK)?;
         y += 1;
 
-        for device in &self.config.devices {
-            ctx.putstr_yx(y, x + 2, &format!("• {}", device.display()), channels::WHITE_ON_BLACK)?;
+        // md arrays, LVM volumes and LUKS mappings the installer would stop
+        let discovery = DeviceDiscovery::new()?;
+        let mut stacked = false;
+        for (role, device) in self.config.devices_by_role() {
+            let line = match role {
+                DeviceRole::Data => format!("• {}", device.display()),
+                _ => format!("• {} ({})", device.display(), role),
+            };
+            ctx.putstr_yx(y, x + 2, &line, channels::WHITE_ON_BLACK)?;
             y += 1;
+
+            // Support devices are used whole and never partitioned
+            let names = device
+                .file_name()
+                .filter(|_| role == DeviceRole::Data)
+                .and_then(|name| discovery.find_device(&name.to_string_lossy()).ok())
+                .map(|d| d.holders)
+                .unwrap_or_default();
+            if !names.is_empty() {
+                let holders = holders::describe(discovery.sys_root(), &names);
+                ctx.putstr_yx(y, x + 4, &tr_args("ui.confirm.holders", &[("holders", &holders)]), channels::RED_ON_BLACK)?;
+                stacked = true;
+                y += 1;
+            }
         }
 
         y += 2;
-        ctx.putstr_yx(y, x, "⚠️  WARNING: All data on selected drives will be DESTROYED!", channels::RED_ON_BLACK)?;
+        ctx.putstr_yx(y, x, tr("ui.confirm.warning"), channels::RED_ON_BLACK)?;
+
+        // Erasing disks takes typing the pool name, not just pressing a button
+        let pool = self.config.pool_name.clone();
+        let prompt = tr_args("ui.confirm.type_pool", &[("pool", &pool)]);
+        let mut field = InputField::new(prompt, "", rows.saturating_sub(9), layout::center(cols, 40), 40);
 
         // Draw buttons
-        let buttons = vec!["Cancel".to_string(), "Continue".to_string()];
+        let labels = [tr("ui.button.cancel"), tr("ui.button.save"), tr("ui.button.continue")];
+        let buttons = labels.iter().map(|l| l.to_string()).collect();
         let mut dialog = Dialog::new("", vec![], buttons);
         dialog.center(rows, cols);
 
@@ -584,19 +1299,23 @@ impl UiRunner {
 
         // Handle input
         loop {
+            let confirmed = confirms(field.value(), &pool);
+            field.render(ctx)?;
+
             // Draw simple button bar
-            let button_y = rows - 5;
-            let button_x = (cols - 30) / 2;
+            let button_y = rows.saturating_sub(5);
+            let button_x = layout::center(cols, 45);
 
-            for i in 0..2 {
-                let label = if i == 0 { "Cancel" } else { "Continue" };
-                let color = if i == selected_button {
+            for (i, label) in labels.iter().enumerate() {
+                let color = if i == 2 && !confirmed {
+                    channels::from_rgb(100, 100, 100, 50, 50, 50) // Gray until the pool name is typed
+                } else if i == selected_button {
                     channels::from_rgb(255, 255, 255, 0, 150, 0)
                 } else {
                     channels::from_rgb(200, 200, 200, 50, 50, 50)
                 };
 
-                let btn_x = button_x + i * 15;
+                let btn_x = button_x + i as u32 * 15;
                 ctx.putstr_yx(button_y, btn_x, &format!("[ {} ]", label), color)?;
             }
 
@@ -604,37 +1323,354 @@ impl UiRunner {
 
             let input = ctx.get_blocking()?;
             match input.id {
-                NCKEY_LEFT => selected_button = 0,
-                NCKEY_RIGHT | NCKEY_TAB => selected_button = 1,
-                NCKEY_ENTER => {
-                    if selected_button == 0 {
-                        return Ok(ScreenAction::Previous);
-                    } else {
+                NCKEY_LEFT => selected_button = selected_button.saturating_sub(1),
+                NCKEY_RIGHT | NCKEY_TAB => selected_button = (selected_button + 1).min(labels.len() - 1),
+                NCKEY_BACKSPACE => field.backspace(),
+                NCKEY_ENTER => match selected_button {
+                    0 => return Ok(ScreenAction::Previous),
+                    1 => {
+                        self.save_config(ctx)?;
+                        return Ok(ScreenAction::Stay);
+                    }
+                    _ if !confirmed => {}
+                    _ => {
+                        // Confirming agrees to stop what was listed
+                        self.config.release_holders |= stacked;
                         return Ok(ScreenAction::Next);
                     }
-                }
+                },
                 NCKEY_ESC => return Ok(ScreenAction::Previous),
+                // Every other key types into the field, so q can't quit here
                 _ => {
-                    if let Some(ch) = char::from_u32(input.id) {
-                        if ch == 'q' || ch == 'Q' {
-                            return Ok(ScreenAction::Exit);
-                        }
+                    if let Some(ch) = char::from_u32(input.id).filter(|c| !c.is_control()) {
+                        field.insert_char(ch);
                     }
                 }
             }
         }
     }
 
+    /// Run the installer on a worker thread, drawing its progress as it goes
+    ///
+    /// The only input accepted is a confirmed cancel, which stops the
+    /// installer at the next step.
+    fn show_execution(&mut self, ctx: &mut NotcursesContext) -> Result<ScreenAction> {
+        let token = self.cancel.clone();
+        let mut installer = Installer::new(self.config.clone())?.with_cancel_token(token.clone());
+        if let Some(log_sink) = &self.log_sink {
+            installer = installer.with_log_sink(log_sink.clone());
+        }
+        let mut tracker = ProgressTracker::new(installer.applicable_phases());
+
+        let (tx, rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let reporter = ChannelReporter::new(tx);
+            installer.install_with_progress(&reporter)
+        });
+
+        let mut log_open = false;
+        loop {
+            while let Ok(event) = rx.try_recv() {
+                tracker.apply(&event);
+            }
+            if handle.is_finished() {
+                break;
+            }
+
+            self.draw_execution(ctx, &tracker, token.is_cancelled())?;
+            if log_open {
+                self.draw_log_view(ctx)?;
+            }
+
+            if let Some(input) = ctx.get_nonblocking()? {
+                let quit = char::from_u32(input.id).is_some_and(|ch| ch == 'q' || ch == 'Q');
+                if log_open {
+                    log_open = self.log_view_key(ctx, input.id)?;
+                } else if is_log_key(input.id) {
+                    self.open_log_view(ctx)?;
+                    log_open = true;
+                } else if (input.id == NCKEY_ESC || quit) && !token.is_cancelled() && self.confirm_cancel(ctx)? {
+                    log::warn!("Cancellation requested from the TUI");
+                    token.cancel();
+                }
+            }
+
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        if log_open {
+            ctx.close_overlay()?;
+        }
+
+        let result = handle
+            .join()
+            .map_err(|_| InstallerError::UiError("Installer thread panicked".into()))?;
+        for event in rx.try_iter() {
+            tracker.apply(&event);
+        }
+
+        match result {
+            Ok(report) => {
+                self.draw_execution(ctx, &tracker, false)?;
+                self.report = Some(report);
+                Ok(ScreenAction::Next)
+            }
+            Err(e) => {
+                tracker.fail_running();
+                self.draw_execution(ctx, &tracker, false)?;
+                if !e.is_cancelled() {
+                    self.show_failure(ctx, &e.to_string())?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Draw the phase checklist and overall progress
+    fn draw_execution(&self, ctx: &mut NotcursesContext, tracker: &ProgressTracker, cancelling: bool) -> Result<()> {
+        let (rows, cols) = ctx.dimensions();
+        ctx.clear()?;
+        self.draw_header(ctx)?;
+
+        let x = layout::center(cols, 50);
+        let mut y = 5;
+
+        for (phase, state) in tracker.phases() {
+            let (mark, color) = match state {
+                PhaseState::Pending => ("[ ]     ".to_string(), channels::from_rgb(150, 150, 150, 0, 0, 0)),
+                PhaseState::Running { percent } => (format!("[▶] {:>3}%", percent), channels::CYAN_ON_BLACK),
+                PhaseState::Done => ("[✓]     ".to_string(), channels::GREEN_ON_BLACK),
+                PhaseState::Failed => ("[✗]     ".to_string(), channels::RED_ON_BLACK),
+            };
+            ctx.putstr_yx(y, x, &format!("{} {}", mark, phase.description()), color)?;
+            y += 1;
+        }
+
+        y += 1;
+        let percent = format!("{:.0}%", tracker.overall() * 100.0);
+        ctx.draw_progress_bar(
+            y,
+            x,
+            50,
+            tracker.overall(),
+            Some(&percent),
+            BarStyle {
+                filled: channels::GREEN_ON_BLACK,
+                empty: channels::from_rgb(100, 100, 100, 0, 0, 0),
+            },
+        )?;
+
+        y += 2;
+        // Disks prepared at once get a line each
+        let labels: Vec<&str> = if tracker.devices().is_empty() {
+            vec![tracker.label()]
+        } else {
+            tracker.devices().iter().map(|(_, label)| label.as_str()).collect()
+        };
+        for label in labels {
+            let label = layout::truncate(label, cols.saturating_sub(x));
+            ctx.putstr_yx(y, x, &label, channels::WHITE_ON_BLACK)?;
+            y += 1;
+        }
+
+        if cancelling {
+            ctx.putstr_yx(y + 1, x, tr("ui.exec.cancelling"), channels::RED_ON_BLACK)?;
+        }
+
+        let help = tr("ui.exec.help");
+        ctx.putstr_yx(rows.saturating_sub(2), layout::center_text(cols, help), help, channels::from_rgb(200, 200, 0, 0, 0, 0))?;
+
+        ctx.render()
+    }
+
+    /// Ask whether to cancel the running installation
+    fn confirm_cancel(&self, ctx: &mut NotcursesContext) -> Result<bool> {
+        let (rows, cols) = ctx.dimensions();
+
+        let mut dialog = Dialog::new(
+            tr("ui.exec.cancel_title"),
+            vec![tr("ui.exec.cancel_question").to_string()],
+            vec![tr("ui.button.no").to_string(), tr("ui.button.yes").to_string()],
+        );
+        dialog.center(rows, cols);
+
+        loop {
+            dialog.render(ctx)?;
+            ctx.render()?;
+
+            let input = ctx.get_blocking()?;
+            match input.id {
+                NCKEY_LEFT => dialog.select_prev_button(),
+                NCKEY_RIGHT | NCKEY_TAB => dialog.select_next_button(),
+                NCKEY_ENTER => return Ok(dialog.selected_button() == 1),
+                NCKEY_ESC => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+
+    /// Show why the installation failed, offering the recent log
+    fn show_failure(&mut self, ctx: &mut NotcursesContext, error: &str) -> Result<()> {
+        let (rows, cols) = ctx.dimensions();
+
+        let mut dialog = Dialog::new(
+            tr("ui.exec.failed"),
+            vec![error.to_string()],
+            vec![tr("ui.button.ok").to_string(), tr("ui.button.view_log").to_string()],
+        );
+        dialog.center(rows, cols);
+
+        loop {
+            dialog.render(ctx)?;
+            ctx.render()?;
+
+            let input = ctx.get_blocking()?;
+            match input.id {
+                NCKEY_LEFT => dialog.select_prev_button(),
+                NCKEY_RIGHT | NCKEY_TAB => dialog.select_next_button(),
+                NCKEY_ENTER if dialog.selected_button() == 1 => self.show_log_view(ctx)?,
+                NCKEY_ENTER | NCKEY_ESC => return Ok(()),
+                key if is_log_key(key) => self.show_log_view(ctx)?,
+                _ => {}
+            }
+        }
+    }
+
+    /// Show the log over the current screen until it's closed
+    fn show_log_view(&mut self, ctx: &mut NotcursesContext) -> Result<()> {
+        self.open_log_view(ctx)?;
+        loop {
+            self.draw_log_view(ctx)?;
+            if let Some(input) = ctx.get_nonblocking()? {
+                if !self.log_view_key(ctx, input.id)? {
+                    return ctx.render();
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Open the log view over the screen, following the newest lines
+    fn open_log_view(&mut self, ctx: &mut NotcursesContext) -> Result<()> {
+        let (rows, cols) = ctx.dimensions();
+        let (height, width) = (rows.saturating_sub(4), cols.saturating_sub(4));
+        ctx.open_overlay(2, 2, height, width)?;
+        self.log_view.resize(height, width);
+        self.log_view.follow();
+        Ok(())
+    }
+
+    /// Take in newly logged lines and draw the log view
+    fn draw_log_view(&mut self, ctx: &mut NotcursesContext) -> Result<()> {
+        if let Some(lines) = &self.log_lines {
+            for line in lines.try_iter() {
+                self.log_view.push(line);
+            }
+        }
+        ctx.draw_overlay(|ctx| self.log_view.render(ctx))?;
+        ctx.render()
+    }
+
+    /// Scroll the log view, returning false once it has been closed
+    fn log_view_key(&mut self, ctx: &mut NotcursesContext, key: u32) -> Result<bool> {
+        match key {
+            NCKEY_UP => self.log_view.scroll_up(1),
+            NCKEY_DOWN => self.log_view.scroll_down(1),
+            NCKEY_PGUP => self.log_view.page_up(),
+            NCKEY_PGDOWN => self.log_view.page_down(),
+            NCKEY_HOME => self.log_view.scroll_to_top(),
+            NCKEY_END => self.log_view.follow(),
+            key if key == NCKEY_ESC || is_log_key(key) => {
+                ctx.close_overlay()?;
+                return Ok(false);
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Summarize what was installed and wait for a key
+    fn show_completion(&mut self, ctx: &mut NotcursesContext) -> Result<InstallReport> {
+        let report = self
+            .report
+            .take()
+            .ok_or_else(|| InstallerError::UiError("No installation report".into()))?;
+        let (rows, cols) = ctx.dimensions();
+
+        let x = layout::center(cols, 60);
+        let mut y = 5;
+
+        ctx.putstr_yx(y, x, &tr_args("ui.complete.pool", &[("pool", &report.pool.name)]), channels::GREEN_ON_BLACK)?;
+        y += 2;
+
+        ctx.putstr_yx(y, x, &tr_args("ui.complete.datasets", &[("count", &report.pool.datasets.len())]), channels::CYAN_ON_BLACK)?;
+        y += 1;
+        for dataset in report.pool.datasets.iter().take((rows as usize).saturating_sub(14)) {
+            ctx.putstr_yx(y, x + 2, &format!("• {}", dataset), channels::WHITE_ON_BLACK)?;
+            y += 1;
+        }
+
+        let esps = report
+            .devices
+            .iter()
+            .flat_map(|device| &device.partitions)
+            .filter(|part| part.role == "efi")
+            .count();
+        y += 1;
+        ctx.putstr_yx(y, x, &tr_args("ui.complete.esps", &[("count", &esps)]), channels::WHITE_ON_BLACK)?;
+        if let Some(bootloader) = &report.bootloader {
+            y += 1;
+            match &bootloader.error {
+                None => ctx.putstr_yx(y, x, &tr_args("ui.complete.bootloader", &[("name", &bootloader.name)]), channels::WHITE_ON_BLACK)?,
+                Some(error) => ctx.putstr_yx(
+                    y,
+                    x,
+                    &tr_args("ui.complete.bootloader_failed", &[("name", &bootloader.name), ("error", &error.lines().next().unwrap_or_default())]),
+                    channels::RED_ON_BLACK,
+                )?,
+            }
+        }
+        if let Some(network) = &report.network {
+            y += 1;
+            ctx.putstr_yx(y, x, &tr_args("ui.complete.network", &[("summary", network)]), channels::WHITE_ON_BLACK)?;
+        }
+
+        ctx.putstr_yx(rows.saturating_sub(3), x, tr("ui.complete.exit"), channels::from_rgb(200, 200, 0, 0, 0, 0))?;
+        ctx.render()?;
+        while is_log_key(ctx.get_blocking()?.id) {
+            self.show_log_view(ctx)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Ask for a path and save the configuration so it can be replayed with --config
+    fn save_config(&mut self, ctx: &mut NotcursesContext) -> Result<()> {
+        let Some(path) = self.read_input(ctx, tr("ui.save.prompt"), DEFAULT_SAVE_PATH)? else {
+            return Ok(());
+        };
+
+        let path = PathBuf::from(path);
+        let (title, message) = match self.config.to_file(&path) {
+            Ok(()) => (
+                tr("ui.save.saved"),
+                tr_args("ui.save.saved_detail", &[("path", &path.display())]),
+            ),
+            Err(e) => (tr("ui.save.failed"), e.to_string()),
+        };
+
+        self.show_message(ctx, title, &message)
+    }
+
     fn show_exit_dialog(&self, ctx: &mut NotcursesContext) -> Result<()> {
         let (rows, cols) = ctx.dimensions();
 
         let mut dialog = Dialog::new(
-            "Exit Installer",
+            tr("ui.exit.title"),
             vec![
-                "Are you sure you want to exit?".to_string(),
-                "No changes have been made.".to_string(),
+                tr("ui.exit.question").to_string(),
+                tr("ui.exit.no_changes").to_string(),
             ],
-            vec!["No".to_string(), "Yes, Exit".to_string()],
+            vec![tr("ui.button.no").to_string(), tr("ui.button.yes_exit").to_string()],
         );
         dialog.center(rows, cols);
         dialog.render(ctx)?;
@@ -643,6 +1679,52 @@ impl UiRunner {
         Ok(())
     }
 
+    /// Remember the highlighted item of the current screen
+    fn remember_selected(&mut self, selected: usize) {
+        self.screen_state.entry(self.current_screen).or_default().selected = selected;
+    }
+
+    /// The item to highlight on the current screen: where it was left, else `default`
+    fn remembered_selected(&self, default: usize) -> usize {
+        self.screen_state.get(&self.current_screen).map_or(default, |state| state.selected)
+    }
+
+    /// Remember which devices are checked, and in which role, by name
+    fn remember_devices(&mut self, devices: &[BlockDevice], checklist: &CheckList, roles: &HashMap<String, DeviceRole>) {
+        let state = self.screen_state.entry(self.current_screen).or_default();
+        state.selected = checklist.selected();
+        state.devices = checklist
+            .checked_indices()
+            .into_iter()
+            .filter_map(|i| devices.get(i))
+            .map(|d| (d.name.clone(), roles.get(&d.name).copied().unwrap_or_default()))
+            .collect();
+    }
+
+    /// Devices to check on the device screen: those checked when it was left,
+    /// else the configured ones
+    fn remembered_devices(&self) -> HashMap<String, DeviceRole> {
+        if let Some(state) = self.screen_state.get(&self.current_screen) {
+            return state.devices.clone();
+        }
+        self.config
+            .devices_by_role()
+            .into_iter()
+            .filter_map(|(role, path)| {
+                // Configured paths may be /dev/disk/by-id links
+                let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+                Some((path.file_name()?.to_string_lossy().into_owned(), role))
+            })
+            .collect()
+    }
+
+    /// Estimated pool capacity with the selected devices at `raid_level`,
+    /// if their sizes are known
+    fn usable_capacity(&self, raid_level: RaidLevel) -> Option<bytesize::ByteSize> {
+        let config = Config { raid_level, ..self.config.clone() };
+        config.usable_capacity(|path| self.device_sizes.get(path).copied())
+    }
+
     fn next_screen(&mut self) {
         if let Some(next) = self.current_screen.next() {
             self.current_screen = next;
@@ -656,9 +1738,264 @@ impl UiRunner {
     }
 }
 
+/// What the strength estimate says about a passphrase being typed, in the
+/// color it's shown in
+fn strength_hint(passphrase: &str) -> (String, u64) {
+    let (key, color) = match Passphrase::strength(passphrase) {
+        PassphraseStrength::TooShort => ("ui.encryption.too_short", channels::RED_ON_BLACK),
+        PassphraseStrength::Weak => ("ui.encryption.weak", channels::RED_ON_BLACK),
+        PassphraseStrength::Fair => ("ui.encryption.fair", channels::YELLOW_ON_BLACK),
+        PassphraseStrength::Strong => ("ui.encryption.strong", channels::GREEN_ON_BLACK),
+    };
+    (tr_args("ui.encryption.strength", &[("strength", &tr(key))]), color)
+}
+
 /// Screen navigation action
 enum ScreenAction {
     Next,
     Previous,
+    /// Redraw the current screen
+    Stay,
     Exit,
 }
+
+/// Order of the device selection list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum DeviceSort {
+    #[default]
+    Name,
+    /// Largest first
+    Size,
+    /// SSDs before HDDs
+    Media,
+}
+
+impl DeviceSort {
+    /// The order `s` switches to next
+    fn next(self) -> Self {
+        match self {
+            Self::Name => Self::Size,
+            Self::Size => Self::Media,
+            Self::Media => Self::Name,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Name => tr("ui.devices.sort_name"),
+            Self::Size => tr("ui.devices.sort_size"),
+            Self::Media => tr("ui.devices.sort_media"),
+        }
+    }
+}
+
+/// Indices of the devices whose name, model or serial contains `filter`
+/// (ignoring case), in `sort` order; ties go by name
+fn device_view(devices: &[BlockDevice], filter: &str, sort: DeviceSort) -> Vec<usize> {
+    let filter = filter.to_lowercase();
+    let matches = |device: &BlockDevice| {
+        [Some(&device.name), device.model.as_ref(), device.serial.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&filter))
+    };
+    let mut view: Vec<usize> = (0..devices.len()).filter(|&i| matches(&devices[i])).collect();
+    view.sort_by(|&a, &b| {
+        let (a, b) = (&devices[a], &devices[b]);
+        let order = match sort {
+            DeviceSort::Name => std::cmp::Ordering::Equal,
+            DeviceSort::Size => b.size.cmp(&a.size),
+            DeviceSort::Media => a.rotational.cmp(&b.rotational),
+        };
+        order.then_with(|| a.name.cmp(&b.name))
+    });
+    view
+}
+
+/// One line of the device selection list, tagged with any role besides data
+fn device_row(device: &BlockDevice, role: DeviceRole) -> String {
+    let mut row = format!("{:<10} {:<12} {:<8} {}", device.name, device.display_name(),
+        if device.rotational { "HDD" } else { "SSD" }, device.controller_type);
+    if device.is_boot_medium {
+        row = format!("{} ({})", row, tr("ui.devices.boot_medium"));
+    }
+    match role {
+        DeviceRole::Data => row,
+        _ => format!("{} [{}]", row, role),
+    }
+}
+
+/// Apply hot-plug events to the device list, keeping check state and roles by device name
+///
+/// Returns the names of checked devices that were removed; they stay unchecked
+/// if they come back.
+fn merge_device_events(
+    discovery: &DeviceDiscovery,
+    devices: &mut Vec<BlockDevice>,
+    checklist: &mut CheckList,
+    roles: &mut HashMap<String, DeviceRole>,
+    events: &[DeviceEvent],
+) -> Vec<String> {
+    let mut checked: HashSet<String> = checklist
+        .checked_indices()
+        .iter()
+        .map(|&i| devices[i].name.clone())
+        .collect();
+    let selected = devices.get(checklist.selected()).map(|d| d.name.clone());
+    let mut removed_checked = Vec::new();
+
+    for event in events {
+        match event {
+            DeviceEvent::Added(name) => {
+                if devices.iter().any(|d| &d.name == name) {
+                    continue;
+                }
+                if let Some(device) = discovery.read_device(name) {
+                    log::info!("Device {} was plugged in", name);
+                    devices.push(device);
+                }
+            }
+            DeviceEvent::Removed(name) => {
+                let before = devices.len();
+                devices.retain(|d| &d.name != name);
+                if devices.len() < before {
+                    log::info!("Device {} was removed", name);
+                }
+                if checked.remove(name) {
+                    removed_checked.push(name.clone());
+                }
+                roles.remove(name);
+            }
+        }
+    }
+
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    let items = devices
+        .iter()
+        .map(|d| device_row(d, roles.get(&d.name).copied().unwrap_or_default()))
+        .collect();
+    let checked = devices.iter().map(|d| checked.contains(&d.name)).collect();
+    let selected = selected
+        .and_then(|name| devices.iter().position(|d| d.name == name))
+        .unwrap_or_else(|| checklist.selected());
+    checklist.set_items(items, checked, selected);
+    disable_boot_media(checklist, devices);
+    removed_checked
+}
+
+/// An optional setting typed in: empty clears it, anything else has to pass `validate`
+fn optional(value: &str, validate: impl Fn(&str) -> Result<()>) -> Result<Option<String>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    validate(value)?;
+    Ok(Some(value.to_string()))
+}
+
+/// An optional setting as shown in the settings menu
+fn or_unchanged(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("-")
+}
+
+/// Grey out the disks the running system was booted from
+fn disable_boot_media(checklist: &mut CheckList, devices: &[BlockDevice]) {
+    for (index, device) in devices.iter().enumerate() {
+        if device.is_boot_medium {
+            checklist.set_enabled(index, false);
+        }
+    }
+}
+
+/// Status mark and color for a pre-flight check
+fn check_mark(severity: Severity) -> (&'static str, u64) {
+    match severity {
+        Severity::Ok => ("✓", channels::GREEN_ON_BLACK),
+        Severity::Warning => ("!", channels::YELLOW_ON_BLACK),
+        Severity::Error => ("✗", channels::RED_ON_BLACK),
+    }
+}
+
+/// `L` opens and closes the log view
+fn is_log_key(key: u32) -> bool {
+    char::from_u32(key).is_some_and(|ch| ch == 'l' || ch == 'L')
+}
+
+/// Split text into lines of at most `width` characters, breaking at spaces
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::ControllerType;
+
+    fn device(name: &str, size: u64, rotational: bool, model: Option<&str>) -> BlockDevice {
+        BlockDevice {
+            name: name.to_string(),
+            path: PathBuf::from(format!("/dev/{}", name)),
+            sys_path: PathBuf::from(format!("/sys/block/{}", name)),
+            controller_type: ControllerType::Sata,
+            size,
+            logical_block_size: 512,
+            physical_block_size: 4096,
+            optimal_io_size: 0,
+            model: model.map(String::from),
+            serial: Some(format!("SN-{}", name.to_uppercase())),
+            vendor: None,
+            removable: false,
+            readonly: false,
+            rotational,
+            mountpoint: None,
+            partitions: Vec::new(),
+            holders: Vec::new(),
+            is_boot_medium: false,
+            dm_name: None,
+            slaves: Vec::new(),
+            smart: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_device_view_sorts() {
+        let devices = [
+            device("sdb", 4_000, true, None),
+            device("nvme0n1", 1_000, false, None),
+            device("sda", 8_000, true, None),
+            device("sdc", 4_000, false, None),
+        ];
+        assert_eq!(device_view(&devices, "", DeviceSort::Name), vec![1, 2, 0, 3]);
+        // Equal sizes and media types go by name
+        assert_eq!(device_view(&devices, "", DeviceSort::Size), vec![2, 0, 3, 1]);
+        assert_eq!(device_view(&devices, "", DeviceSort::Media), vec![1, 3, 2, 0]);
+        assert_eq!(DeviceSort::Media.next(), DeviceSort::Name);
+    }
+
+    #[test]
+    fn test_device_view_filters() {
+        let devices = [
+            device("sda", 1_000, true, Some("WDC WD40EFRX")),
+            device("sdb", 1_000, true, Some("ST4000VN008")),
+            device("nvme0n1", 1_000, false, Some("Samsung SSD 980")),
+        ];
+        assert_eq!(device_view(&devices, "SDA", DeviceSort::Name), vec![0]);
+        assert_eq!(device_view(&devices, "st4000", DeviceSort::Name), vec![1]);
+        assert_eq!(device_view(&devices, "samsung", DeviceSort::Name), vec![2]);
+        assert_eq!(device_view(&devices, "sn-sdb", DeviceSort::Name), vec![1]);
+        assert!(device_view(&devices, "toshiba", DeviceSort::Name).is_empty());
+    }
+}
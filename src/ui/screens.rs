@@ -1,34 +1,49 @@
 //! Screen definitions for the TUI
 
+use crate::i18n::tr;
+
 /// Screens in the installer flow
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Screen {
+    /// Introduction
     Welcome,
+    /// New pool or existing pool
     ModeSelect,
+    /// Scanning for disks
     DeviceDiscovery,
+    /// Choosing the target disks
     DeviceSelect,
+    /// Choosing the pool layout
     RaidConfig,
+    /// Native encryption and its passphrase
+    Encryption,
+    /// Pool name, sizes and other settings
     Settings,
+    /// Results of the pre-flight checks
     PreflightCheck,
+    /// Summary to confirm before anything is written
     Confirmation,
+    /// Installation progress
     Execution,
+    /// Outcome of the installation
     Completion,
 }
 
 impl Screen {
-    /// Get the title for this screen
+    /// Get the title for this screen, in the active language
     pub fn title(&self) -> &'static str {
         match self {
-            Self::Welcome => "Welcome to ZFSBootMenu Installer",
-            Self::ModeSelect => "Select Installation Mode",
-            Self::DeviceDiscovery => "Discovering Devices",
-            Self::DeviceSelect => "Select Devices",
-            Self::RaidConfig => "RAID Configuration",
-            Self::Settings => "Installation Settings",
-            Self::PreflightCheck => "Pre-flight Checks",
-            Self::Confirmation => "Confirm Installation",
-            Self::Execution => "Installing",
-            Self::Completion => "Installation Complete",
+            Self::Welcome => tr("ui.screen.welcome"),
+            Self::ModeSelect => tr("ui.screen.mode_select"),
+            Self::DeviceDiscovery => tr("ui.screen.device_discovery"),
+            Self::DeviceSelect => tr("ui.screen.device_select"),
+            Self::RaidConfig => tr("ui.screen.raid_config"),
+            Self::Encryption => tr("ui.screen.encryption"),
+            Self::Settings => tr("ui.screen.settings"),
+            Self::PreflightCheck => tr("ui.screen.preflight"),
+            Self::Confirmation => tr("ui.screen.confirmation"),
+            Self::Execution => tr("ui.screen.execution"),
+            Self::Completion => tr("ui.screen.completion"),
         }
     }
 
@@ -39,7 +54,8 @@ impl Screen {
             Self::ModeSelect => Some(Self::DeviceDiscovery),
             Self::DeviceDiscovery => Some(Self::DeviceSelect),
             Self::DeviceSelect => Some(Self::RaidConfig),
-            Self::RaidConfig => Some(Self::Settings),
+            Self::RaidConfig => Some(Self::Encryption),
+            Self::Encryption => Some(Self::Settings),
             Self::Settings => Some(Self::PreflightCheck),
             Self::PreflightCheck => Some(Self::Confirmation),
             Self::Confirmation => Some(Self::Execution),
@@ -56,7 +72,8 @@ impl Screen {
             Self::DeviceDiscovery => Some(Self::ModeSelect),
             Self::DeviceSelect => Some(Self::DeviceDiscovery),
             Self::RaidConfig => Some(Self::DeviceSelect),
-            Self::Settings => Some(Self::RaidConfig),
+            Self::Encryption => Some(Self::RaidConfig),
+            Self::Settings => Some(Self::Encryption),
             Self::PreflightCheck => Some(Self::Settings),
             Self::Confirmation => Some(Self::PreflightCheck),
             Self::Execution => None, // Can't go back during execution
@@ -1,7 +1,9 @@
 //! UI widgets for the notcurses interface
 
 use super::context::{channels, NotcursesContext};
+use super::layout;
 use crate::error::Result;
+use std::collections::VecDeque;
 
 /// A selectable menu item
 #[derive(Debug, Clone)]
@@ -55,8 +57,20 @@ impl Menu {
         self.selected
     }
 
+    /// Start with `index` selected
+    pub fn with_selected(mut self, index: usize) -> Self {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+        self
+    }
+
+    /// Replace the items (e.g. after a value changed), keeping the selection
+    pub fn set_items(&mut self, items: Vec<MenuItem>) {
+        self.items = items;
+        self.selected = self.selected.min(self.items.len().saturating_sub(1));
+    }
+
     pub fn select_next(&mut self) {
-        if self.selected < self.items.len() - 1 {
+        if self.selected + 1 < self.items.len() {
             self.selected += 1;
             // Skip disabled items
             while self.selected < self.items.len() && !self.items[self.selected].enabled {
@@ -94,9 +108,10 @@ impl Menu {
 
             // Draw selection marker
             let marker = if is_selected { "▶ " } else { "  " };
-            let text = format!("{}{}", marker, item.label);
+            let text = layout::truncate(&format!("{}{}", marker, item.label), self.width);
+            let padding = self.width.saturating_sub(layout::display_width(&text)) as usize;
 
-            ctx.putstr_yx(y, self.x, &format!("{:<width$}", text, width = self.width as usize), channels)?;
+            ctx.putstr_yx(y, self.x, &format!("{}{:padding$}", text, "", padding = padding), channels)?;
 
             // Draw description if available
             if let Some(desc) = &item.description {
@@ -115,44 +130,96 @@ impl Menu {
 }
 
 /// A checkbox list widget
+///
+/// Shows a view of its items: a subset in any order, set with
+/// [`set_view`](Self::set_view). Indices taken and returned are those of the
+/// items, so check state stays with an item whether it's shown or not.
 pub struct CheckList {
     items: Vec<String>,
     checked: Vec<bool>,
+    enabled: Vec<bool>,
+    /// Items shown, in order
+    view: Vec<usize>,
+    /// Position in `view`
     selected: usize,
     y: u32,
     x: u32,
+    /// Columns for the rows; the scrollbar goes in the column after them
+    width: u32,
     height: u32,
     scroll_offset: usize,
+    checkboxes: bool,
 }
 
 impl CheckList {
-    pub fn new(items: Vec<String>, y: u32, x: u32, height: u32) -> Self {
+    pub fn new(items: Vec<String>, y: u32, x: u32, width: u32, height: u32) -> Self {
         let checked = vec![false; items.len()];
+        let enabled = vec![true; items.len()];
+        let view = (0..items.len()).collect();
         Self {
             items,
             checked,
+            enabled,
+            view,
             selected: 0,
             y,
             x,
+            width,
             height,
             scroll_offset: 0,
+            checkboxes: true,
         }
     }
 
+    /// Show the items as a plain scrolling list that can't be checked
+    pub fn without_checkboxes(mut self) -> Self {
+        self.checkboxes = false;
+        self
+    }
+
+    /// The item under the cursor, or the number of items if none is shown
     pub fn selected(&self) -> usize {
-        self.selected
+        self.view.get(self.selected).copied().unwrap_or(self.items.len())
     }
 
     pub fn is_checked(&self, index: usize) -> bool {
         self.checked.get(index).copied().unwrap_or(false)
     }
 
+    /// Grey an item out so it can't be checked or selected, like [`MenuItem::disabled`]
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(item) = self.enabled.get_mut(index) {
+            *item = enabled;
+            if !enabled {
+                self.checked[index] = false;
+            }
+        }
+    }
+
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.enabled.get(index).copied().unwrap_or(false)
+    }
+
     pub fn toggle_selected(&mut self) {
-        if self.selected < self.checked.len() {
-            self.checked[self.selected] = !self.checked[self.selected];
+        let index = self.selected();
+        if self.checkboxes && self.is_enabled(index) {
+            self.checked[index] = !self.checked[index];
+        }
+    }
+
+    /// Check or uncheck every enabled item that is shown
+    pub fn check_shown(&mut self, checked: bool) {
+        if !self.checkboxes {
+            return;
+        }
+        for &index in &self.view {
+            if self.enabled[index] {
+                self.checked[index] = checked;
+            }
         }
     }
 
+    /// Checked items, shown or not
     pub fn checked_indices(&self) -> Vec<usize> {
         self.checked
             .iter()
@@ -162,50 +229,137 @@ impl CheckList {
             .collect()
     }
 
+    /// Number of items shown
+    pub fn shown(&self) -> usize {
+        self.view.len()
+    }
+
+    /// Number of items, shown or not
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether there are no items at all
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Show only `view`, in that order, keeping the cursor on the same item if
+    /// it's still shown
+    ///
+    /// Indices past the last item are left out.
+    pub fn set_view(&mut self, view: Vec<usize>) {
+        let current = self.selected();
+        self.view = view.into_iter().filter(|&i| i < self.items.len()).collect();
+        let position = self.view.iter().position(|&i| i == current).unwrap_or(0);
+        self.scroll_offset = self.scroll_offset.min(self.view.len().saturating_sub(1));
+        self.select_position(position);
+    }
+
     pub fn select_next(&mut self) {
-        if self.selected < self.items.len() - 1 {
-            self.selected += 1;
-            // Adjust scroll if needed
-            if self.selected >= self.scroll_offset + self.height as usize {
-                self.scroll_offset = self.selected - self.height as usize + 1;
-            }
+        // Skip disabled items
+        if let Some(next) = (self.selected + 1..self.view.len()).find(|&i| self.enabled[self.view[i]]) {
+            self.select_position(next);
         }
     }
 
     pub fn select_prev(&mut self) {
-        if self.selected > 0 {
-            self.selected -= 1;
-            // Adjust scroll if needed
-            if self.selected < self.scroll_offset {
-                self.scroll_offset = self.selected;
-            }
+        // Skip disabled items
+        if let Some(prev) = (0..self.selected).rev().find(|&i| self.enabled[self.view[i]]) {
+            self.select_position(prev);
+        }
+    }
+
+    /// Move the selection down one page
+    pub fn page_down(&mut self) {
+        let last = self.view.len().saturating_sub(1);
+        self.select_position((self.selected + self.height as usize).min(last));
+    }
+
+    /// Move the selection up one page
+    pub fn page_up(&mut self) {
+        self.select_position(self.selected.saturating_sub(self.height as usize));
+    }
+
+    /// Select the first item
+    pub fn select_first(&mut self) {
+        self.select_position(0);
+    }
+
+    /// Select the last item
+    pub fn select_last(&mut self) {
+        self.select_position(self.view.len().saturating_sub(1));
+    }
+
+    /// Move the cursor to a position in the view, scrolling just enough to keep it visible
+    fn select_position(&mut self, position: usize) {
+        let height = (self.height as usize).max(1);
+        self.selected = position;
+        if position < self.scroll_offset {
+            self.scroll_offset = position;
+        } else if position >= self.scroll_offset + height {
+            self.scroll_offset = position + 1 - height;
         }
     }
 
+    /// Replace the label of one item
+    pub fn set_item(&mut self, index: usize, item: String) {
+        if let Some(label) = self.items.get_mut(index) {
+            *label = item;
+        }
+    }
+
+    /// Replace the items and their check state, showing them all, and select
+    /// item `selected` (kept in range)
+    pub fn set_items(&mut self, items: Vec<String>, checked: Vec<bool>, selected: usize) {
+        self.items = items;
+        self.checked = checked;
+        self.checked.resize(self.items.len(), false);
+        self.enabled = vec![true; self.items.len()];
+        self.view = (0..self.items.len()).collect();
+        self.scroll_offset = self.scroll_offset.min(self.items.len().saturating_sub(1));
+        self.select_position(selected.min(self.items.len().saturating_sub(1)));
+    }
+
+    /// "selected/shown" position indicator
+    pub fn position(&self) -> String {
+        format!("{}/{}", (self.selected + 1).min(self.view.len()), self.view.len())
+    }
+
+    /// Render only the rows currently scrolled into view
     pub fn render(&self, ctx: &mut NotcursesContext) -> Result<()> {
         let visible_items = self.height as usize;
-        let end = (self.scroll_offset + visible_items).min(self.items.len());
+        let end = (self.scroll_offset + visible_items).min(self.view.len());
 
-        for (i, item_idx) in (self.scroll_offset..end).enumerate() {
+        for (i, position) in (self.scroll_offset..end).enumerate() {
             let y = self.y + i as u32;
-            let is_selected = item_idx == self.selected;
+            let item_idx = self.view[position];
+            let is_selected = position == self.selected;
             let is_checked = self.checked[item_idx];
 
-            let channels = if is_selected {
+            let channels = if !self.enabled[item_idx] {
+                channels::from_rgb(100, 100, 100, 0, 0, 0) // Gray for disabled
+            } else if is_selected {
                 channels::from_rgb(0, 0, 0, 100, 100, 255) // Black on light blue
             } else {
                 channels::WHITE_ON_BLACK
             };
 
-            let checkbox = if is_checked { "[✓]" } else { "[ ]" };
             let marker = if is_selected { "▶" } else { " " };
-            let text = format!("{} {} {}", marker, checkbox, self.items[item_idx]);
+            let text = if self.checkboxes {
+                let checkbox = if is_checked { "[✓]" } else { "[ ]" };
+                format!("{} {} {}", marker, checkbox, self.items[item_idx])
+            } else {
+                format!("{} {}", marker, self.items[item_idx])
+            };
+            let text = layout::truncate(&text, self.width);
+            let padding = self.width.saturating_sub(layout::display_width(&text)) as usize;
 
-            ctx.putstr_yx(y, self.x, &text, channels)?;
+            ctx.putstr_yx(y, self.x, &format!("{}{:padding$}", text, "", padding = padding), channels)?;
         }
 
         // Draw scrollbar if needed
-        if self.items.len() > visible_items {
+        if self.view.len() > visible_items {
             self.draw_scrollbar(ctx)?;
         }
 
@@ -213,12 +367,10 @@ impl CheckList {
     }
 
     fn draw_scrollbar(&self, ctx: &mut NotcursesContext) -> Result<()> {
-        let scrollbar_x = self.x + 60; // Position on the right
-        let scrollbar_height = self.height;
-        let total_items = self.items.len();
+        let scrollbar_x = self.x + self.width;
 
         // Draw scrollbar track
-        for i in 0..scrollbar_height {
+        for i in 0..self.height {
             ctx.putstr_yx(
                 self.y + i,
                 scrollbar_x,
@@ -227,13 +379,8 @@ impl CheckList {
             )?;
         }
 
-        // Calculate thumb position and size
-        let thumb_size = (scrollbar_height as f32 * (scrollbar_height as f32 / total_items as f32))
-            .max(1.0) as u32;
-        let thumb_pos = (self.scroll_offset as f32
-            * (scrollbar_height as f32 / total_items as f32)) as u32;
-
         // Draw thumb
+        let (thumb_pos, thumb_size) = scrollbar_thumb(self.height, self.view.len(), self.scroll_offset);
         for i in 0..thumb_size {
             ctx.putstr_yx(
                 self.y + thumb_pos + i,
@@ -247,6 +394,22 @@ impl CheckList {
     }
 }
 
+/// Where the thumb of a scrollbar `height` rows tall starts and how many rows
+/// it covers, for `total` rows scrolled down by `offset`
+///
+/// The thumb is as much of the track as the rows shown are of all rows, but
+/// at least one row, and it reaches the bottom of the track on the last page.
+fn scrollbar_thumb(height: u32, total: usize, offset: usize) -> (u32, u32) {
+    let track = u64::from(height);
+    let total = (total as u64).max(1);
+    let size = (track * track / total).clamp(1.min(track), track);
+
+    let max_offset = total.saturating_sub(track);
+    let offset = (offset as u64).min(max_offset);
+    let pos = ((track - size) * offset).checked_div(max_offset).unwrap_or(0);
+    (pos as u32, size as u32)
+}
+
 /// A simple button widget
 pub struct Button {
     label: String,
@@ -278,9 +441,15 @@ impl Button {
             channels::from_rgb(200, 200, 200, 50, 50, 50) // Light gray on dark gray
         };
 
+        if self.width < 2 {
+            return Ok(());
+        }
+
         // Draw button
-        let padding = (self.width as usize - self.label.len()) / 2;
-        let text = format!("{:padding$}{}{:padding$}", "", self.label, "", padding = padding);
+        let inner = self.width - 2;
+        let label = layout::truncate(&self.label, inner);
+        let padding = layout::center_text(inner, &label) as usize;
+        let text = format!("{:padding$}{}{:padding$} ", "", label, "", padding = padding);
 
         ctx.putstr_yx(self.y, self.x, "┌", channels)?;
         for i in 1..self.width - 1 {
@@ -289,7 +458,7 @@ impl Button {
         ctx.putstr_yx(self.y, self.x + self.width - 1, "┐", channels)?;
 
         ctx.putstr_yx(self.y + 1, self.x, "│", channels)?;
-        ctx.putstr_yx(self.y + 1, self.x + 1, &text[..self.width as usize - 2], channels)?;
+        ctx.putstr_yx(self.y + 1, self.x + 1, layout::take_columns(&text, inner), channels)?;
         ctx.putstr_yx(self.y + 1, self.x + self.width - 1, "│", channels)?;
 
         ctx.putstr_yx(self.y + 2, self.x, "└", channels)?;
@@ -319,11 +488,11 @@ impl Dialog {
         let title_str: String = title.into();
         let width = message
             .iter()
-            .map(|s| s.len())
+            .map(|s| layout::display_width(s))
             .max()
             .unwrap_or(40)
-            .max(title_str.len() + 4)
-            .max(20) as u32
+            .max(layout::display_width(&title_str) + 4)
+            .max(20)
             + 4;
 
         let height = message.len() as u32 + buttons.len() as u32 + 6;
@@ -341,8 +510,9 @@ impl Dialog {
     }
 
     pub fn center(&mut self, screen_rows: u32, screen_cols: u32) {
-        self.y = (screen_rows - self.height) / 2;
-        self.x = (screen_cols - self.width) / 2;
+        self.y = layout::center(screen_rows, self.height);
+        self.x = layout::center(screen_cols, self.width);
+        self.width = self.width.min(screen_cols);
     }
 
     pub fn selected_button(&self) -> usize {
@@ -350,7 +520,7 @@ impl Dialog {
     }
 
     pub fn select_next_button(&mut self) {
-        if self.selected_button < self.buttons.len() - 1 {
+        if self.selected_button + 1 < self.buttons.len() {
             self.selected_button += 1;
         }
     }
@@ -375,8 +545,9 @@ impl Dialog {
         // Draw message lines
         let mut current_y = self.y + 2;
         for line in &self.message {
-            let line_x = self.x + (self.width - line.len() as u32) / 2;
-            ctx.putstr_yx(current_y, line_x, line, channels::WHITE_ON_BLACK)?;
+            let line = layout::truncate(line, self.width.saturating_sub(4));
+            let line_x = self.x + layout::center_text(self.width, &line);
+            ctx.putstr_yx(current_y, line_x, &line, channels::WHITE_ON_BLACK)?;
             current_y += 1;
         }
 
@@ -384,8 +555,8 @@ impl Dialog {
         current_y += 2;
         let button_width = 12u32;
         let total_button_width = self.buttons.len() as u32 * button_width
-            + (self.buttons.len() - 1) as u32 * 2;
-        let mut button_x = self.x + (self.width - total_button_width) / 2;
+            + self.buttons.len().saturating_sub(1) as u32 * 2;
+        let mut button_x = self.x + layout::center(self.width, total_button_width);
 
         for (i, button_label) in self.buttons.iter().enumerate() {
             let mut button = Button::new(button_label, current_y, button_x, button_width);
@@ -406,6 +577,7 @@ pub struct InputField {
     x: u32,
     width: u32,
     cursor_pos: usize,
+    masked: bool,
 }
 
 impl InputField {
@@ -419,22 +591,40 @@ impl InputField {
             x,
             width,
             cursor_pos,
+            masked: false,
         }
     }
 
+    /// Show `*` for each character instead of the value, for passwords
+    pub fn masked(mut self) -> Self {
+        self.masked = true;
+        self
+    }
+
     pub fn value(&self) -> &str {
         &self.value
     }
 
+    /// Row of the label and column of the field
+    pub fn position(&self) -> (u32, u32) {
+        (self.y, self.x)
+    }
+
+    /// Overwrite the value in memory and empty the field
+    pub fn scrub(&mut self) {
+        scrub(&mut self.value);
+        self.cursor_pos = 0;
+    }
+
     pub fn insert_char(&mut self, c: char) {
         self.value.insert(self.cursor_pos, c);
-        self.cursor_pos += 1;
+        self.cursor_pos += c.len_utf8();
     }
 
     pub fn backspace(&mut self) {
-        if self.cursor_pos > 0 {
-            self.value.remove(self.cursor_pos - 1);
-            self.cursor_pos -= 1;
+        if let Some(prev) = self.value[..self.cursor_pos].chars().next_back() {
+            self.cursor_pos -= prev.len_utf8();
+            self.value.remove(self.cursor_pos);
         }
     }
 
@@ -445,38 +635,396 @@ impl InputField {
     }
 
     pub fn move_cursor_left(&mut self) {
-        if self.cursor_pos > 0 {
-            self.cursor_pos -= 1;
+        if let Some(prev) = self.value[..self.cursor_pos].chars().next_back() {
+            self.cursor_pos -= prev.len_utf8();
         }
     }
 
     pub fn move_cursor_right(&mut self) {
-        if self.cursor_pos < self.value.len() {
-            self.cursor_pos += 1;
+        if let Some(next) = self.value[self.cursor_pos..].chars().next() {
+            self.cursor_pos += next.len_utf8();
         }
     }
 
     pub fn render(&self, ctx: &mut NotcursesContext) -> Result<()> {
         // Draw label
-        ctx.putstr_yx(self.y, self.x, &self.label, channels::CYAN_ON_BLACK)?;
+        ctx.putstr_yx(self.y, self.x, &layout::truncate(&self.label, self.width), channels::CYAN_ON_BLACK)?;
 
         // Draw input box
         let input_y = self.y + 1;
         ctx.draw_box(input_y, self.x, 3, self.width, None, channels::WHITE_ON_BLACK)?;
 
         // Draw value
-        let display_value = if self.value.len() as u32 > self.width - 4 {
-            &self.value[self.value.len() - (self.width - 4) as usize..]
+        // Show the text up to the cursor, scrolled so the cursor stays in view
+        let mask;
+        let before_cursor = if self.masked {
+            mask = "*".repeat(self.value[..self.cursor_pos].chars().count());
+            &mask
         } else {
-            &self.value
+            &self.value[..self.cursor_pos]
         };
+        let inner = self.width.saturating_sub(5);
+        let shown = layout::tail_columns(before_cursor, inner);
+        let after_mask;
+        let after_cursor = if self.masked {
+            after_mask = "*".repeat(self.value[self.cursor_pos..].chars().count());
+            &after_mask
+        } else {
+            &self.value[self.cursor_pos..]
+        };
+        let cursor_col = layout::display_width(shown);
+        let rest = layout::take_columns(after_cursor, inner - cursor_col);
 
-        ctx.putstr_yx(input_y + 1, self.x + 2, display_value, channels::WHITE_ON_BLACK)?;
+        // Pad to the full width to cover what was drawn before a deletion
+        let text = format!("{}{}", shown, rest);
+        let padding = (inner + 1).saturating_sub(layout::display_width(&text)) as usize;
+        ctx.putstr_yx(input_y + 1, self.x + 2, &format!("{}{:padding$}", text, "", padding = padding), channels::WHITE_ON_BLACK)?;
 
         // Draw cursor (if applicable)
-        let cursor_x = self.x + 2 + self.cursor_pos as u32;
+        let cursor_x = self.x + 2 + cursor_col;
         ctx.putstr_yx(input_y + 1, cursor_x, "_", channels::GREEN_ON_BLACK)?;
 
         Ok(())
     }
 }
+
+/// Masked fields hold secrets, which shouldn't linger in freed memory
+impl Drop for InputField {
+    fn drop(&mut self) {
+        if self.masked {
+            self.scrub();
+        }
+    }
+}
+
+/// Overwrite a string's bytes, then empty it
+///
+/// Best effort: copies left behind when the string grew aren't reached.
+pub fn scrub(value: &mut String) {
+    // SAFETY: NUL bytes keep the string valid UTF-8
+    unsafe { value.as_mut_vec().fill(0) };
+    value.clear();
+}
+
+/// A scrollable view of log lines, following the newest by default
+///
+/// Keeps at most `capacity` lines, dropping the oldest. Scrolling up stops
+/// following; scrolling back to the bottom (or [`LogView::follow`]) resumes it.
+pub struct LogView {
+    title: String,
+    lines: VecDeque<String>,
+    capacity: usize,
+    top: usize,
+    following: bool,
+    height: u32,
+    width: u32,
+}
+
+impl LogView {
+    /// An empty view titled `title`, keeping at most `capacity` lines
+    pub fn new(title: impl Into<String>, capacity: usize) -> Self {
+        Self {
+            title: title.into(),
+            lines: VecDeque::new(),
+            capacity: capacity.max(1),
+            top: 0,
+            following: true,
+            height: 3,
+            width: 20,
+        }
+    }
+
+    /// Set the size of the box, borders included
+    pub fn resize(&mut self, height: u32, width: u32) {
+        self.height = height;
+        self.width = width;
+        self.clamp();
+    }
+
+    /// Append a line, dropping the oldest once full
+    pub fn push(&mut self, line: impl Into<String>) {
+        self.lines.push_back(line.into());
+        if self.lines.len() > self.capacity {
+            self.lines.pop_front();
+            self.top = self.top.saturating_sub(1);
+        }
+        self.clamp();
+    }
+
+    /// Whether new lines scroll the view
+    pub fn is_following(&self) -> bool {
+        self.following
+    }
+
+    /// Rows of log shown at once
+    fn rows(&self) -> usize {
+        self.height.saturating_sub(2).max(1) as usize
+    }
+
+    fn bottom(&self) -> usize {
+        self.lines.len().saturating_sub(self.rows())
+    }
+
+    fn clamp(&mut self) {
+        if self.following {
+            self.top = self.bottom();
+        } else {
+            self.top = self.top.min(self.bottom());
+        }
+    }
+
+    /// Scroll back `count` lines
+    pub fn scroll_up(&mut self, count: usize) {
+        self.top = self.top.saturating_sub(count);
+        self.following = self.top >= self.bottom();
+    }
+
+    /// Scroll forward `count` lines
+    pub fn scroll_down(&mut self, count: usize) {
+        self.top = (self.top + count).min(self.bottom());
+        self.following = self.top >= self.bottom();
+    }
+
+    /// Scroll back one page
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.rows());
+    }
+
+    /// Scroll forward one page
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.rows());
+    }
+
+    /// Jump to the oldest line
+    pub fn scroll_to_top(&mut self) {
+        self.scroll_up(self.top);
+    }
+
+    /// Jump to the newest line and keep up with new ones
+    pub fn follow(&mut self) {
+        self.following = true;
+        self.clamp();
+    }
+
+    /// The lines currently scrolled into view
+    pub fn visible(&self) -> impl Iterator<Item = &String> {
+        self.lines.iter().skip(self.top).take(self.rows())
+    }
+
+    /// Whether `line` was logged at error level
+    ///
+    /// Log lines start with a `[timestamp LEVEL target]` header.
+    pub fn is_error(line: &str) -> bool {
+        line.starts_with('[')
+            && line
+                .split(']')
+                .next()
+                .is_some_and(|header| header.split_whitespace().any(|word| word == "ERROR"))
+    }
+
+    /// Draw the box at the origin, sized by [`LogView::resize`]
+    pub fn render(&self, ctx: &mut NotcursesContext) -> Result<()> {
+        ctx.draw_box(0, 0, self.height, self.width, Some(&self.title), channels::CYAN_ON_BLACK)?;
+
+        // Lines too long for the box are cut, ending in an ellipsis
+        let text_width = self.width.saturating_sub(4);
+        for (i, line) in self.visible().enumerate() {
+            let color = if Self::is_error(line) {
+                channels::RED_ON_BLACK
+            } else {
+                channels::WHITE_ON_BLACK
+            };
+            ctx.putstr_yx(1 + i as u32, 2, &layout::truncate(line, text_width), color)?;
+        }
+
+        let position = if self.lines.is_empty() {
+            "0/0".to_string()
+        } else {
+            let last = (self.top + self.rows()).min(self.lines.len());
+            format!("{}-{}/{}", self.top + 1, last, self.lines.len())
+        };
+        let position = format!(" {} ", position);
+        let x = self.width.saturating_sub(layout::display_width(&position) + 2);
+        ctx.putstr_yx(self.height.saturating_sub(1), x, &position, channels::CYAN_ON_BLACK)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(lines: usize) -> LogView {
+        let mut view = LogView::new("Log", 100);
+        view.resize(7, 40);
+        for i in 0..lines {
+            view.push(format!("line {}", i));
+        }
+        view
+    }
+
+    fn first_visible(view: &LogView) -> String {
+        view.visible().next().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_log_view_follows_the_tail() {
+        let mut view = view(20);
+        assert!(view.is_following());
+        assert_eq!(first_visible(&view), "line 15");
+        assert_eq!(view.visible().count(), 5);
+
+        view.push("line 20");
+        assert_eq!(first_visible(&view), "line 16");
+    }
+
+    #[test]
+    fn test_log_view_scrolling_stops_following() {
+        let mut view = view(20);
+        view.scroll_up(3);
+        assert!(!view.is_following());
+        assert_eq!(first_visible(&view), "line 12");
+
+        // New lines don't move a view scrolled back
+        view.push("line 20");
+        assert_eq!(first_visible(&view), "line 12");
+
+        view.page_down();
+        assert!(view.is_following());
+        assert_eq!(first_visible(&view), "line 16");
+
+        view.scroll_to_top();
+        assert_eq!(first_visible(&view), "line 0");
+        view.follow();
+        assert_eq!(first_visible(&view), "line 16");
+    }
+
+    #[test]
+    fn test_log_view_drops_the_oldest_lines() {
+        let mut view = LogView::new("Log", 10);
+        view.resize(7, 40);
+        for i in 0..15 {
+            view.push(format!("line {}", i));
+        }
+        view.scroll_to_top();
+        assert_eq!(first_visible(&view), "line 5");
+
+        // The view stays on the same line as older ones drop out
+        view.scroll_down(2);
+        view.push("line 15");
+        assert_eq!(first_visible(&view), "line 7");
+    }
+
+    #[test]
+    fn test_log_view_short_log() {
+        let mut view = view(2);
+        assert_eq!(view.visible().count(), 2);
+        view.scroll_up(10);
+        view.page_down();
+        assert!(view.is_following());
+        assert_eq!(first_visible(&view), "line 0");
+    }
+
+    #[test]
+    fn test_log_view_error_lines() {
+        assert!(LogView::is_error("[2026-10-15T12:00:00Z ERROR zbm_installer] Installation failed"));
+        assert!(!LogView::is_error("[2026-10-15T12:00:00Z INFO  zbm_installer] No ERROR here"));
+        assert!(!LogView::is_error("ERROR without a header"));
+    }
+
+    fn checklist(count: usize) -> CheckList {
+        let items = (0..count).map(|i| format!("sd{}", i)).collect();
+        CheckList::new(items, 0, 0, 40, 3)
+    }
+
+    #[test]
+    fn test_checklist_view_keeps_check_state() {
+        let mut list = checklist(5);
+        list.select_next();
+        list.toggle_selected();
+        assert_eq!(list.checked_indices(), vec![1]);
+
+        // Filtered out, item 1 stays checked
+        list.set_view(vec![4, 3, 0]);
+        assert_eq!(list.shown(), 3);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.selected(), 4);
+        list.select_next();
+        assert_eq!(list.selected(), 3);
+        list.toggle_selected();
+        assert_eq!(list.checked_indices(), vec![1, 3]);
+        assert_eq!(list.position(), "2/3");
+
+        // The cursor stays on its item when the view changes
+        list.set_view(vec![0, 1, 2, 3, 4]);
+        assert_eq!(list.selected(), 3);
+        assert!(list.is_checked(1));
+    }
+
+    #[test]
+    fn test_checklist_check_shown() {
+        let mut list = checklist(5);
+        list.set_enabled(2, false);
+        list.set_view(vec![1, 2, 3]);
+        list.check_shown(true);
+        assert_eq!(list.checked_indices(), vec![1, 3]);
+
+        list.set_view(vec![0, 1]);
+        list.check_shown(false);
+        assert_eq!(list.checked_indices(), vec![3]);
+    }
+
+    #[test]
+    fn test_checklist_empty_view() {
+        let mut list = checklist(3);
+        list.set_view(Vec::new());
+        // Nothing is under the cursor, and moving it does nothing
+        assert_eq!(list.selected(), 3);
+        list.select_next();
+        list.page_down();
+        list.select_last();
+        list.toggle_selected();
+        assert!(list.checked_indices().is_empty());
+        assert_eq!(list.position(), "0/0");
+
+        // Indices past the last item are dropped
+        list.set_view(vec![2, 7]);
+        assert_eq!(list.shown(), 1);
+        assert_eq!(list.selected(), 2);
+    }
+
+    #[test]
+    fn test_scrollbar_thumb_fits_the_track() {
+        for height in [5, 30] {
+            for total in [1usize, 10, 500] {
+                let max_offset = total.saturating_sub(height as usize);
+                for offset in 0..=max_offset {
+                    let (pos, size) = scrollbar_thumb(height, total, offset);
+                    assert!(size >= 1, "{height} rows, {total} items: empty thumb");
+                    assert!(size <= height, "{height} rows, {total} items: thumb {size}");
+                    assert!(pos + size <= height, "{height} rows, {total} items at {offset}: {pos}+{size}");
+                }
+                // The thumb spans the track from the first page to the last
+                assert_eq!(scrollbar_thumb(height, total, 0).0, 0);
+                let (pos, size) = scrollbar_thumb(height, total, max_offset);
+                assert!(total <= height as usize || pos + size == height);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scrollbar_thumb_size() {
+        // Everything fits: the thumb is the whole track
+        assert_eq!(scrollbar_thumb(5, 1, 0), (0, 5));
+        assert_eq!(scrollbar_thumb(30, 10, 0), (0, 30));
+        // Half the rows shown, half the track
+        assert_eq!(scrollbar_thumb(5, 10, 0), (0, 2));
+        assert_eq!(scrollbar_thumb(5, 10, 5), (3, 2));
+        // A long list still gets one row, and offsets past the end stay on the track
+        assert_eq!(scrollbar_thumb(5, 500, 0), (0, 1));
+        assert_eq!(scrollbar_thumb(5, 500, 495), (4, 1));
+        assert_eq!(scrollbar_thumb(5, 500, 9999), (4, 1));
+        assert_eq!(scrollbar_thumb(0, 500, 3), (0, 0));
+    }
+}
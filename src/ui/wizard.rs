@@ -0,0 +1,306 @@
+//! Interactive setup on plain stdin/stdout
+//!
+//! Walks the TUI's screens as questions: mode, devices, RAID level, settings
+//! and encryption. User passwords and the typed confirmation are left to the
+//! caller, as for the command line.
+
+use super::prompt::Prompter;
+use crate::config::{
+    parse_size, Compression, Config, EncryptionConfig, InstallMode, Passphrase, RaidLevel,
+};
+use crate::disk::BlockDevice;
+use crate::error::{InstallerError, Result};
+use crate::i18n::{tr, tr_args};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Ask for the settings the TUI would, starting from `config`, and return
+/// them validated
+///
+/// `devices` are the disks to choose from; the one the running system was
+/// booted from is left out.
+pub fn configure<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    mut config: Config,
+    devices: &[BlockDevice],
+) -> Result<Config> {
+    prompter.say(tr("wizard.intro"))?;
+
+    // Mode
+    let modes = [tr("ui.mode.new"), tr("ui.mode.existing")].map(String::from);
+    let current = match config.mode {
+        InstallMode::New => 0,
+        InstallMode::Existing => 1,
+    };
+    config.mode = match prompter.select(tr("ui.mode.prompt"), &modes, current)? {
+        0 => InstallMode::New,
+        _ => InstallMode::Existing,
+    };
+
+    // Devices, starting from those already configured
+    let devices: Vec<&BlockDevice> = devices.iter().filter(|d| !d.is_boot_medium).collect();
+    if devices.is_empty() {
+        return Err(InstallerError::config(tr("ui.devices.none_found_detail")));
+    }
+    let rows: Vec<String> = devices.iter().map(|d| device_row(d)).collect();
+    let checked: Vec<usize> = devices
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| config.devices.iter().any(|path| is_device(path, d)))
+        .map(|(i, _)| i)
+        .collect();
+    let selected = prompter.multi_select(tr("ui.devices.prompt"), &rows, &checked)?;
+    config.vdevs.clear();
+    config.devices = selected.iter().map(|&i| devices[i].path.clone()).collect();
+
+    // RAID level, from those the devices are enough for; an existing pool keeps its own
+    if config.use_existing_pool {
+        config.raid_level = RaidLevel::None;
+    } else {
+        let levels: Vec<RaidLevel> = RaidLevel::ALL
+            .into_iter()
+            .filter(|level| level.min_drives() <= config.devices.len())
+            .collect();
+        let names: Vec<String> = levels
+            .iter()
+            .map(|level| format!("{} ({})", level, level.description()))
+            .collect();
+        let current = levels
+            .iter()
+            .position(|&level| level == config.raid_level)
+            .unwrap_or(0);
+        let question = format!(
+            "{} {}",
+            tr("ui.raid.prompt"),
+            tr_args(
+                "ui.raid.selected_devices",
+                &[("count", &config.devices.len())]
+            )
+        );
+        config.raid_level = levels[prompter.select(&question, &names, current)?];
+    }
+
+    // Settings
+    config.pool_name = prompter.text(tr("ui.label.pool_name"), &config.pool_name, |value| {
+        Config::validate_pool_name(value).map(|()| value.to_string())
+    })?;
+    let names: Vec<String> = Compression::ALL
+        .iter()
+        .map(Compression::to_string)
+        .collect();
+    let current = Compression::ALL
+        .iter()
+        .position(|&c| c == config.compression)
+        .unwrap_or(0);
+    config.compression =
+        Compression::ALL[prompter.select(tr("ui.label.compression"), &names, current)?];
+    config.efi_size = prompter.text(
+        tr("ui.label.efi_size"),
+        &config.efi_size.to_string(),
+        |value| {
+            let size = parse_size(value)?;
+            Config::validate_efi_size(size).map(|()| size)
+        },
+    )?;
+    config.swap_size = prompter.text(
+        tr("ui.label.swap_size"),
+        &config.swap_size.to_string(),
+        parse_size,
+    )?;
+    let hostname = config.hostname.clone().unwrap_or_default();
+    config.hostname = prompter.text(tr("ui.label.hostname"), &hostname, |value| {
+        if value.is_empty() {
+            return Ok(None);
+        }
+        Config::validate_hostname(value).map(|()| Some(value.to_string()))
+    })?;
+
+    // Encryption, keeping a configured key file or passphrase
+    if !config.use_existing_pool {
+        let encrypt = prompter.yes_no(tr("wizard.encrypt"), config.encryption.is_some())?;
+        config.encryption = match (encrypt, config.encryption.take()) {
+            (false, _) => None,
+            (true, existing) => Some(existing.unwrap_or_else(EncryptionConfig::default)),
+        };
+    }
+    if let Some(encryption) = config.encryption.as_mut() {
+        if encryption.uses_prompt() && encryption.passphrase.is_none() {
+            encryption.passphrase = Some(read_passphrase(prompter)?);
+        }
+    }
+
+    config.validate()?;
+    Ok(config)
+}
+
+/// Ask for the encryption passphrase twice until both match and it's long enough
+fn read_passphrase<R: BufRead, W: Write>(prompter: &mut Prompter<R, W>) -> Result<Passphrase> {
+    loop {
+        let first = prompter.secret(tr("cli.passphrase.prompt"))?;
+        let second = prompter.secret(tr("cli.passphrase.confirm"))?;
+        match Passphrase::confirmed(&first, &second) {
+            Ok(passphrase) => return Ok(passphrase),
+            Err(e) => prompter.reject(&e.to_string())?,
+        }
+    }
+}
+
+/// One device in the list: name, size, model and kind
+fn device_row(device: &BlockDevice) -> String {
+    let kind = if device.rotational { "HDD" } else { "SSD" };
+    format!(
+        "{}  {} {}",
+        device.display_name(),
+        kind,
+        device.controller_type
+    )
+}
+
+/// Whether a configured path (possibly a /dev/disk/by-id link) names `device`
+fn is_device(path: &Path, device: &BlockDevice) -> bool {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    path == device.path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::ControllerType;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn device(name: &str) -> BlockDevice {
+        BlockDevice {
+            name: name.to_string(),
+            path: PathBuf::from(format!("/dev/{}", name)),
+            sys_path: PathBuf::from(format!("/sys/block/{}", name)),
+            controller_type: ControllerType::Sata,
+            size: 1_000_000_000_000,
+            logical_block_size: 512,
+            physical_block_size: 4096,
+            optimal_io_size: 0,
+            model: None,
+            serial: None,
+            vendor: None,
+            removable: false,
+            readonly: false,
+            rotational: false,
+            mountpoint: None,
+            partitions: Vec::new(),
+            holders: Vec::new(),
+            is_boot_medium: false,
+            dm_name: None,
+            slaves: Vec::new(),
+            smart: Default::default(),
+        }
+    }
+
+    fn run(input: &str, config: Config, devices: &[BlockDevice]) -> Result<Config> {
+        let mut prompter = Prompter::new(Cursor::new(input.as_bytes().to_vec()), Vec::new());
+        configure(&mut prompter, config, devices)
+    }
+
+    #[test]
+    fn test_defaults_keep_config() {
+        let devices = [device("sda"), device("sdb")];
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sdb")],
+            ..Config::default()
+        };
+        // Enter through every question
+        let config = run(&"\n".repeat(9), config, &devices).unwrap();
+        assert_eq!(config.mode, InstallMode::New);
+        assert_eq!(config.devices, vec![PathBuf::from("/dev/sdb")]);
+        assert_eq!(config.raid_level, RaidLevel::None);
+        assert_eq!(config.pool_name, "zroot");
+        assert!(config.encryption.is_none());
+    }
+
+    #[test]
+    fn test_mirror_with_settings() {
+        let devices = [device("sda"), device("sdb"), device("sdc")];
+        // Mode, devices, RAID, pool, compression, EFI, swap, hostname, encryption
+        let input = "1\n1,3\n2\ntank\n\n512M\n0\nnas\ny\nsecret passphrase\nsecret passphrase\n";
+        let config = run(input, Config::default(), &devices).unwrap();
+        assert_eq!(
+            config.devices,
+            vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdc")]
+        );
+        assert_eq!(config.raid_level, RaidLevel::Mirror);
+        assert_eq!(config.pool_name, "tank");
+        assert_eq!(config.efi_size, parse_size("512M").unwrap());
+        assert_eq!(config.swap_size, parse_size("0").unwrap());
+        assert_eq!(config.hostname.as_deref(), Some("nas"));
+        let encryption = config.encryption.unwrap();
+        assert_eq!(
+            encryption.passphrase.as_ref().map(Passphrase::expose),
+            Some("secret passphrase")
+        );
+    }
+
+    #[test]
+    fn test_passphrase_asked_until_confirmed() {
+        let devices = [device("sda")];
+        // Too short, then mistyped
+        let input = format!(
+            "\n1\n{}y\nshort\nshort\nsecret passphrase\nmistyped\nsecret passphrase\nsecret passphrase\n",
+            "\n".repeat(6)
+        );
+        let config = run(&input, Config::default(), &devices).unwrap();
+        assert!(config.encryption.unwrap().passphrase.is_some());
+    }
+
+    #[test]
+    fn test_raid_levels_need_enough_devices() {
+        let devices = [device("sda"), device("sdb")];
+        // raidz1 (3) isn't offered for two devices, so it's asked again
+        let input = "\n1-2\n3\n2\n\n\n\n\n\n\n";
+        let config = run(input, Config::default(), &devices).unwrap();
+        assert_eq!(config.raid_level, RaidLevel::Mirror);
+
+        // A configured level the devices aren't enough for isn't kept
+        let config = Config {
+            raid_level: RaidLevel::Raidz2,
+            ..Config::default()
+        };
+        let config = run("\n1\n\n\n\n\n\n\n\n", config, &devices).unwrap();
+        assert_eq!(config.raid_level, RaidLevel::None);
+    }
+
+    #[test]
+    fn test_boot_medium_not_offered() {
+        let mut boot = device("sda");
+        boot.is_boot_medium = true;
+        let devices = [boot, device("sdb")];
+        let config = run(
+            &format!("\n1\n{}", "\n".repeat(7)),
+            Config::default(),
+            &devices,
+        )
+        .unwrap();
+        assert_eq!(config.devices, vec![PathBuf::from("/dev/sdb")]);
+
+        let mut only = device("sda");
+        only.is_boot_medium = true;
+        assert!(run("\n", Config::default(), &[only]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_settings_asked_again() {
+        let devices = [device("sda")];
+        let input = "\n1\n\nmy pool\ntank\n\n1K\n1G\n\nbad_host!\n\n\n";
+        let config = run(input, Config::default(), &devices).unwrap();
+        assert_eq!(config.pool_name, "tank");
+        assert_eq!(config.efi_size, parse_size("1G").unwrap());
+        assert_eq!(config.hostname, None);
+    }
+
+    #[test]
+    fn test_end_of_input_cancels() {
+        let devices = [device("sda")];
+        assert!(matches!(
+            run("\n1\n", Config::default(), &devices),
+            Err(InstallerError::UserCancelled)
+        ));
+    }
+}
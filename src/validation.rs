@@ -1,175 +1,987 @@
 //! Pre-flight validation checks
 
-use crate::config::Config;
-use crate::disk::DeviceDiscovery;
+use crate::bootloader::zbm::artifact_image;
+use crate::config::{
+    Bootloader, Compression, Config, FirmwareMode, PartitioningMode, RaidLevel, SecureBootMode,
+    VdevSpec, ZbmSource,
+};
+use crate::disk::block_device::resolve_dev_path;
+use crate::disk::{
+    holders, parent_disk, BlockDevice, BootLayout, DeviceDiscovery, DiskOperations, PartitionTable,
+    SysRoot,
+};
 use crate::error::{InstallerError, Result};
-use crate::system::{is_root, is_uefi};
-use crate::zfs;
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::i18n::{tr, tr_args};
+use crate::system::identity::ZONEINFO_DIR;
+use crate::system::{is_root, is_uefi, secure_boot_enabled};
+use crate::zfs::{self, PoolMember, ZfsPool};
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long the network check waits for the mirror to answer
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest size difference within a vdev before warning, as a percentage of the largest device
+pub const MAX_SIZE_DIFFERENCE_PERCENT: u64 = 10;
+
+/// A single pre-flight check
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    /// Running as root
+    RootPrivileges,
+    /// Booted with firmware the boot mode can be installed from
+    UefiBoot,
+    /// Secure Boot won't refuse the installed images
+    SecureBoot,
+    /// The configuration is consistent
+    Configuration,
+    /// The configured timezone exists
+    Timezone,
+    /// The mirror a bootstrap downloads from can be reached
+    Network,
+    /// The artifact and package directories of an offline install are there
+    Artifacts,
+    /// The ZFS tools are available
+    ZfsAvailable,
+    /// The ZFS kernel module is loaded and matches the tools
+    ZfsModule,
+    /// The existing pool to install onto can be imported and booted from
+    ExistingPool,
+    /// The pool's compatibility feature sets are installed
+    PoolCompatibility,
+    /// Swap that can't be hibernated to: a zvol, or encrypted with a random key
+    SwapHibernation,
+    /// No device is selected twice, under another name or through a partition
+    DistinctDevices,
+    /// A selected device can be wiped and is large enough
+    DeviceSuitable(PathBuf),
+    /// No selected drive is failing, according to SMART
+    DriveHealth,
+    /// The devices of a redundant vdev are about the same size
+    MatchedSizes,
+    /// A redundant vdev doesn't mix SSDs and HDDs
+    MatchedMedia,
+    /// Striped top-level vdevs have the same layout
+    MatchedVdevs,
+    /// Enough memory for ZFS
+    MinimumMemory,
+    /// A command the installation runs is installed
+    RequiredCommand(String),
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RootPrivileges => write!(f, "{}", tr("validation.check.root")),
+            Self::UefiBoot => write!(f, "{}", tr("validation.check.firmware")),
+            Self::SecureBoot => write!(f, "{}", tr("validation.check.secure_boot")),
+            Self::Configuration => write!(f, "{}", tr("validation.check.config")),
+            Self::Timezone => write!(f, "{}", tr("validation.check.timezone")),
+            Self::Network => write!(f, "{}", tr("validation.check.network")),
+            Self::Artifacts => write!(f, "{}", tr("validation.check.artifacts")),
+            Self::ZfsAvailable => write!(f, "{}", tr("validation.check.zfs")),
+            Self::ZfsModule => write!(f, "{}", tr("validation.check.zfs_module")),
+            Self::ExistingPool => write!(f, "{}", tr("validation.check.existing_pool")),
+            Self::PoolCompatibility => write!(f, "{}", tr("validation.check.compatibility")),
+            Self::SwapHibernation => write!(f, "{}", tr("validation.check.swap")),
+            Self::DistinctDevices => write!(f, "{}", tr("validation.check.distinct_devices")),
+            Self::DeviceSuitable(path) => write!(
+                f,
+                "{}",
+                tr_args("validation.check.device", &[("device", &path.display())])
+            ),
+            Self::DriveHealth => write!(f, "{}", tr("validation.check.smart")),
+            Self::MatchedSizes => write!(f, "{}", tr("validation.check.sizes")),
+            Self::MatchedMedia => write!(f, "{}", tr("validation.check.media")),
+            Self::MatchedVdevs => write!(f, "{}", tr("validation.check.vdevs")),
+            Self::MinimumMemory => write!(f, "{}", tr("validation.check.memory")),
+            Self::RequiredCommand(command) => write!(
+                f,
+                "{}",
+                tr_args("validation.check.command", &[("command", command)])
+            ),
+        }
+    }
+}
+
+/// How a check turned out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Nothing to report
+    Ok,
+    /// Installation can go ahead once the user has seen the message
+    Warning,
+    /// Installation must not go ahead
+    Error,
+}
+
+/// The outcome of one check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    /// What was checked
+    pub check: Check,
+    /// How it turned out
+    pub severity: Severity,
+    /// Why the check warned or failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl CheckResult {
+    /// A check that passed
+    pub fn ok(check: Check) -> Self {
+        Self {
+            check,
+            severity: Severity::Ok,
+            message: None,
+        }
+    }
+
+    /// A check that found something to warn about
+    pub fn warning(check: Check, message: String) -> Self {
+        Self {
+            check,
+            severity: Severity::Warning,
+            message: Some(message),
+        }
+    }
+
+    /// A check that failed
+    pub fn error(check: Check, message: String) -> Self {
+        Self {
+            check,
+            severity: Severity::Error,
+            message: Some(message),
+        }
+    }
+}
 
 /// Validation result
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ValidationResult {
-    pub passed: bool,
-    pub errors: Vec<String>,
-    pub warnings: Vec<String>,
+    /// Every check, in the order it ran
+    pub checks: Vec<CheckResult>,
 }
 
 impl ValidationResult {
     /// Create a new validation result
     pub fn new() -> Self {
-        Self {
-            passed: true,
-            errors: Vec::new(),
-            warnings: Vec::new(),
-        }
+        Self::default()
+    }
+
+    /// Record the outcome of a check
+    pub fn push(&mut self, result: CheckResult) {
+        self.checks.push(result);
+    }
+
+    /// Messages of the failed checks
+    pub fn errors(&self) -> impl Iterator<Item = &str> {
+        self.messages(Severity::Error)
     }
 
-    /// Add an error
-    pub fn add_error(&mut self, msg: String) {
-        self.passed = false;
-        self.errors.push(msg);
+    /// Messages of the checks that warned
+    pub fn warnings(&self) -> impl Iterator<Item = &str> {
+        self.messages(Severity::Warning)
     }
 
-    /// Add a warning
-    pub fn add_warning(&mut self, msg: String) {
-        self.warnings.push(msg);
+    fn messages(&self, severity: Severity) -> impl Iterator<Item = &str> {
+        self.checks
+            .iter()
+            .filter(move |c| c.severity == severity)
+            .filter_map(|c| c.message.as_deref())
     }
 
     /// Check if validation passed
     pub fn is_ok(&self) -> bool {
-        self.passed
+        self.checks.iter().all(|c| c.severity != Severity::Error)
     }
-}
 
-impl Default for ValidationResult {
-    fn default() -> Self {
-        Self::new()
+    /// Serialize to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| {
+            InstallerError::Other(format!("Failed to serialize validation result: {}", e))
+        })
     }
 }
 
 /// System validator
 pub struct Validator {
     config: Config,
+    sys_root: SysRoot,
+    executor: Arc<dyn CommandExecutor>,
 }
 
 impl Validator {
     /// Create a new validator
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            sys_root: SysRoot::default(),
+            executor: Arc::new(SystemExecutor::new(false)),
+        }
+    }
+
+    /// Run read-only queries (`zpool status`) through a custom executor
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Inspect devices under a different sysfs/procfs root
+    pub fn with_sys_root(mut self, sys_root: SysRoot) -> Self {
+        self.sys_root = sys_root;
+        self
+    }
+
+    /// The checks [`validate`](Self::validate) runs for this configuration, in order
+    pub fn checks(&self) -> Vec<Check> {
+        let mut checks = vec![Check::RootPrivileges, Check::UefiBoot];
+        if self.config.boot_layout() != BootLayout::Bios {
+            checks.push(Check::SecureBoot);
+        }
+        checks.push(Check::Configuration);
+        if self.config.timezone.is_some() {
+            checks.push(Check::Timezone);
+        }
+        // Before anything is wiped, rather than when the bootstrap starts
+        if self.mirror_address().is_some() {
+            checks.push(Check::Network);
+        }
+        if self.config.artifact_dir.is_some() || self.config.pkg_dir.is_some() {
+            checks.push(Check::Artifacts);
+        }
+        checks.extend([Check::ZfsAvailable, Check::ZfsModule]);
+        if self.config.use_existing_pool {
+            checks.push(Check::ExistingPool);
+        } else if self.config.compatibility().is_some() {
+            checks.push(Check::PoolCompatibility);
+        }
+        checks.extend([Check::SwapHibernation, Check::DistinctDevices]);
+        checks.extend(
+            self.config
+                .data_devices()
+                .into_iter()
+                .map(|path| Check::DeviceSuitable(path.to_path_buf())),
+        );
+        // An existing pool's disks aren't chosen now
+        if !self.config.use_existing_pool {
+            checks.push(Check::DriveHealth);
+        }
+        if self.config.is_redundant() {
+            checks.extend([Check::MatchedSizes, Check::MatchedMedia]);
+        }
+        if self.config.vdev_groups().len() > 1 {
+            checks.push(Check::MatchedVdevs);
+        }
+        checks.push(Check::MinimumMemory);
+        checks.extend(
+            self.required_commands()
+                .into_iter()
+                .map(|cmd| Check::RequiredCommand(cmd.to_string())),
+        );
+        checks
     }
 
     /// Run all validation checks
     pub fn validate(&self) -> Result<ValidationResult> {
+        self.validate_with(&mut |_| {})
+    }
+
+    /// Run all validation checks, passing each result to `on_check` as it completes
+    pub fn validate_with(
+        &self,
+        on_check: &mut dyn FnMut(&CheckResult),
+    ) -> Result<ValidationResult> {
         let mut result = ValidationResult::new();
+        for check in self.checks() {
+            let outcome = self.run_check(check)?;
+            on_check(&outcome);
+            result.push(outcome);
+        }
+        Ok(result)
+    }
 
-        // Check root privileges
-        if !is_root() {
-            result.add_error("This program must be run as root".to_string());
+    /// Run one check, e.g. again after the user has fixed what it reported
+    pub fn run_check(&self, check: Check) -> Result<CheckResult> {
+        Ok(match check {
+            Check::RootPrivileges => {
+                if is_root() {
+                    CheckResult::ok(check)
+                } else {
+                    CheckResult::error(check, tr("validation.not_root").to_string())
+                }
+            }
+            Check::UefiBoot => self.check_firmware(is_uefi()),
+            Check::SecureBoot => self.check_secure_boot(secure_boot_enabled()),
+            Check::Configuration => match self.config.validate() {
+                Ok(()) => CheckResult::ok(check),
+                Err(e) => {
+                    CheckResult::error(check, tr_args("validation.config_error", &[("error", &e)]))
+                }
+            },
+            Check::Timezone => self.check_timezone(Path::new(ZONEINFO_DIR)),
+            Check::Network => {
+                let reachable = self
+                    .mirror_address()
+                    .is_some_and(|(host, port)| can_connect(&host, port));
+                self.check_network(reachable)
+            }
+            Check::Artifacts => self.check_artifacts(),
+            Check::ZfsAvailable => match zfs::check_zfs_available() {
+                Ok(true) => CheckResult::ok(check),
+                Ok(false) => CheckResult::error(check, tr("validation.zfs_missing").to_string()),
+                Err(e) => CheckResult::error(
+                    check,
+                    tr_args("validation.zfs_check_failed", &[("error", &e)]),
+                ),
+            },
+            Check::ZfsModule => {
+                match zfs::ensure_module_loaded(self.executor.as_ref(), &self.sys_root) {
+                    Ok(_) => CheckResult::ok(check),
+                    Err(e) => CheckResult::error(
+                        check,
+                        tr_args("validation.zfs_module", &[("error", &e)]),
+                    ),
+                }
+            }
+            Check::ExistingPool => match self.check_existing_pool() {
+                Err(InstallerError::ValidationError(message)) => CheckResult::error(check, message),
+                Err(e) => return Err(e),
+                Ok(warnings) if warnings.is_empty() => CheckResult::ok(check),
+                Ok(warnings) => CheckResult::warning(check, warnings.join("; ")),
+            },
+            Check::PoolCompatibility => {
+                let dir = self.sys_root.join(zfs::pool::COMPATIBILITY_DIR);
+                let unknown = self
+                    .config
+                    .compatibility()
+                    .and_then(|features| unknown_compatibility(&features, &dir));
+                match unknown {
+                    Some(message) => CheckResult::error(check, message),
+                    None => CheckResult::ok(check),
+                }
+            }
+            Check::SwapHibernation => {
+                if self.config.swap_zvol_size().is_some() {
+                    CheckResult::warning(check, tr("validation.zvol_swap_hibernation").to_string())
+                } else if self.config.encrypts_swap() {
+                    CheckResult::warning(
+                        check,
+                        tr("validation.encrypted_swap_hibernation").to_string(),
+                    )
+                } else {
+                    CheckResult::ok(check)
+                }
+            }
+            Check::DistinctDevices => match self.find_device_overlap() {
+                Some(message) => CheckResult::error(check, message),
+                None => CheckResult::ok(check),
+            },
+            Check::DeviceSuitable(ref path) => {
+                let mut warnings = Vec::new();
+                match self.check_device(path, &mut warnings) {
+                    Err(e) => CheckResult::error(check, e.to_string()),
+                    Ok(()) if warnings.is_empty() => CheckResult::ok(check),
+                    Ok(()) => CheckResult::warning(check, warnings.join("; ")),
+                }
+            }
+            Check::DriveHealth => self.check_drive_health(self.command_exists("smartctl"))?,
+            Check::MatchedSizes => {
+                let messages: Vec<String> = self
+                    .redundant_vdevs()?
+                    .iter()
+                    .filter_map(|(_, devices)| size_mismatch(devices))
+                    .collect();
+                if messages.is_empty() {
+                    CheckResult::ok(check)
+                } else {
+                    CheckResult::warning(check, messages.join("; "))
+                }
+            }
+            Check::MatchedMedia => {
+                let messages: Vec<String> = self
+                    .redundant_vdevs()?
+                    .iter()
+                    .filter_map(|(vdev, devices)| mixed_media(devices, vdev))
+                    .collect();
+                if messages.is_empty() {
+                    CheckResult::ok(check)
+                } else {
+                    CheckResult::warning(check, messages.join("; "))
+                }
+            }
+            Check::MatchedVdevs => match uneven_vdevs(&self.config.vdev_groups()) {
+                Some(message) => CheckResult::warning(check, message),
+                None => CheckResult::ok(check),
+            },
+            Check::MinimumMemory => {
+                let mem_kb = crate::system::get_system_memory_kb()?;
+                self.check_memory(ByteSize::kib(mem_kb))
+            }
+            Check::RequiredCommand(ref cmd) => {
+                if self.command_exists(cmd) {
+                    CheckResult::ok(check)
+                } else {
+                    let message = tr_args("validation.command_missing", &[("command", cmd)]);
+                    CheckResult::error(check, message)
+                }
+            }
+        })
+    }
+
+    /// Check there is enough RAM for ZFS and the ARC limit fits into it
+    ///
+    /// The limit is checked against this machine's RAM, which is the
+    /// installed system's too unless the disks move elsewhere.
+    fn check_memory(&self, memory: ByteSize) -> CheckResult {
+        if let Some(arc_max) = self.config.arc_max.filter(|&arc_max| arc_max > memory) {
+            return CheckResult::error(
+                Check::MinimumMemory,
+                tr_args(
+                    "validation.arc_max_too_large",
+                    &[("arc_max", &arc_max), ("memory", &memory)],
+                ),
+            );
+        }
+        // ZFS wants at least 2GB
+        let mem_gb = memory.as_u64() / ByteSize::gib(1).as_u64();
+        if mem_gb < 2 {
+            CheckResult::warning(
+                Check::MinimumMemory,
+                tr_args("validation.low_memory", &[("gb", &mem_gb)]),
+            )
+        } else {
+            CheckResult::ok(Check::MinimumMemory)
         }
+    }
 
-        // Check UEFI
-        if !is_uefi() {
-            result.add_error("System must be booted in UEFI mode".to_string());
+    /// Check the firmware the system was booted with suits the selected mode
+    ///
+    /// Hybrid layouts can be installed from either firmware. A BIOS install
+    /// made from a UEFI-booted system only boots where the machine can still
+    /// boot in legacy mode.
+    fn check_firmware(&self, uefi: bool) -> CheckResult {
+        match self.config.firmware.resolve(uefi) {
+            FirmwareMode::Bios if uefi => {
+                CheckResult::warning(Check::UefiBoot, tr("validation.bios_from_uefi").to_string())
+            }
+            FirmwareMode::Bios => CheckResult::ok(Check::UefiBoot),
+            _ if uefi => CheckResult::ok(Check::UefiBoot),
+            _ if self.config.boot_mode.has_bios() => {
+                CheckResult::warning(Check::UefiBoot, tr("validation.bios_firmware").to_string())
+            }
+            _ => CheckResult::error(Check::UefiBoot, tr("validation.not_uefi").to_string()),
         }
+    }
 
-        // Validate config
-        if let Err(e) = self.config.validate() {
-            result.add_error(format!("Configuration error: {}", e));
+    /// Check Secure Boot, if enforced, is met by a signing mode
+    ///
+    /// `enabled` is None when it can't be told, e.g. booted without UEFI.
+    /// The unsigned images don't boot with Secure Boot enforced, an error
+    /// unless `force` is set (Secure Boot may be disabled afterwards).
+    fn check_secure_boot(&self, enabled: Option<bool>) -> CheckResult {
+        if enabled != Some(true) || self.config.secure_boot.signs() {
+            return CheckResult::ok(Check::SecureBoot);
+        }
+        if self.config.force {
+            CheckResult::warning(
+                Check::SecureBoot,
+                tr("validation.secure_boot_forced").to_string(),
+            )
+        } else {
+            CheckResult::error(Check::SecureBoot, tr("validation.secure_boot").to_string())
         }
+    }
+
+    /// Check the configured timezone is one of the zones in `zoneinfo`
+    ///
+    /// The installed system is a copy of this one, so it has the same zones.
+    fn check_timezone(&self, zoneinfo: &Path) -> CheckResult {
+        match &self.config.timezone {
+            Some(timezone) if !zoneinfo.join(timezone).is_file() => CheckResult::error(
+                Check::Timezone,
+                tr_args(
+                    "validation.unknown_timezone",
+                    &[("timezone", timezone), ("dir", &zoneinfo.display())],
+                ),
+            ),
+            _ => CheckResult::ok(Check::Timezone),
+        }
+    }
+
+    /// Host and port of the mirror a bootstrap downloads from, if any
+    fn mirror_address(&self) -> Option<(String, u16)> {
+        self.config
+            .bootstrap
+            .as_ref()
+            .and_then(|spec| spec.mirror_address())
+    }
 
-        // Check ZFS availability
-        match zfs::check_zfs_available() {
-            Ok(true) => {}
-            Ok(false) => {
-                result.add_error(
-                    "ZFS is not available on this system. Please install ZFS first.".to_string(),
+    /// Check the bootstrap's mirror answered
+    fn check_network(&self, reachable: bool) -> CheckResult {
+        match self.mirror_address() {
+            Some((host, _)) if !reachable => CheckResult::error(
+                Check::Network,
+                tr_args("validation.no_network", &[("host", &host)]),
+            ),
+            _ => CheckResult::ok(Check::Network),
+        }
+    }
+
+    /// Check the artifact directory has the ZFSBootMenu release to install
+    /// and the package directory exists
+    fn check_artifacts(&self) -> CheckResult {
+        if let Some(dir) = &self.config.pkg_dir {
+            if !dir.is_dir() {
+                return CheckResult::error(
+                    Check::Artifacts,
+                    tr_args("validation.no_pkg_dir", &[("dir", &dir.display())]),
                 );
             }
-            Err(e) => {
-                result.add_error(format!("Failed to check ZFS availability: {}", e));
-            }
         }
+        let version = match &self.config.zbm_source {
+            ZbmSource::Release { version } => version.as_deref(),
+            ZbmSource::Local if self.config.zbm_release_fallback => None,
+            _ => return CheckResult::ok(Check::Artifacts),
+        };
+        match (&self.config.artifact_dir, version) {
+            (Some(dir), version) => match artifact_image(dir, version) {
+                Ok(_) => CheckResult::ok(Check::Artifacts),
+                Err(e) => CheckResult::error(
+                    Check::Artifacts,
+                    tr_args("validation.missing_artifact", &[("error", &e)]),
+                ),
+            },
+            (None, _) => CheckResult::ok(Check::Artifacts),
+        }
+    }
 
-        // Validate devices
-        if let Err(e) = self.validate_devices(&mut result) {
-            result.add_error(format!("Device validation failed: {}", e));
+    /// Check the pool to install onto is imported or can be imported
+    ///
+    /// Properties can only be read from an imported pool; the installer
+    /// checks them again once it has imported one.
+    fn check_existing_pool(&self) -> Result<Vec<String>> {
+        let name = &self.config.pool_name;
+        let imported = zfs::status::imported_members(self.executor.as_ref())?
+            .iter()
+            .any(|member| &member.pool == name);
+        if !imported {
+            return if zfs::status::importable_pools(self.executor.as_ref())?.contains(name) {
+                Ok(Vec::new())
+            } else {
+                Err(InstallerError::validation(tr_args(
+                    "validation.pool_not_found",
+                    &[("pool", name)],
+                )))
+            };
         }
 
-        // Check system requirements
-        self.check_system_requirements(&mut result)?;
+        let pool = ZfsPool::new(
+            name.clone(),
+            RaidLevel::None,
+            Vec::new(),
+            None,
+            Compression::default(),
+            false,
+        )
+        .with_executor(self.executor.clone());
+        existing_pool_warnings(&pool, name, self.config.bootloader)
+    }
 
-        Ok(result)
+    /// Describe the first pair of selected devices that overlap
+    ///
+    /// Paths are resolved through /dev/disk/by-* links, so the same disk
+    /// under two names is caught, as is a partition of another selected disk.
+    /// Log, cache and spare devices count too. Devices are named as they
+    /// were configured.
+    fn find_device_overlap(&self) -> Option<String> {
+        let resolved: Vec<(&Path, PathBuf, PathBuf)> = self
+            .config
+            .devices_by_role()
+            .into_iter()
+            .map(|(_, path)| {
+                let kernel = resolve_dev_path(&self.sys_root, path);
+                let is_partition = kernel
+                    .file_name()
+                    .is_some_and(|name| DeviceDiscovery::is_partition(&name.to_string_lossy()));
+                let disk = if is_partition {
+                    parent_disk(&kernel).unwrap_or_else(|| kernel.clone())
+                } else {
+                    kernel.clone()
+                };
+                (path, kernel, disk)
+            })
+            .collect();
+
+        for (i, (first, first_kernel, first_disk)) in resolved.iter().enumerate() {
+            for (second, second_kernel, second_disk) in &resolved[i + 1..] {
+                if first == second {
+                    return Some(tr_args(
+                        "validation.duplicate_device",
+                        &[("device", &first.display())],
+                    ));
+                }
+                if first_kernel == second_kernel {
+                    return Some(tr_args(
+                        "validation.same_device",
+                        &[
+                            ("first", &first.display()),
+                            ("second", &second.display()),
+                            ("device", &first_kernel.display()),
+                        ],
+                    ));
+                }
+                let (partition, disk) = if first_disk == second_kernel {
+                    (first, second)
+                } else if second_disk == first_kernel {
+                    (second, first)
+                } else {
+                    continue;
+                };
+                return Some(tr_args(
+                    "validation.partition_of_selected",
+                    &[
+                        ("partition", &partition.display()),
+                        ("disk", &disk.display()),
+                    ],
+                ));
+            }
+        }
+        None
     }
 
-    /// Validate selected devices
-    fn validate_devices(&self, result: &mut ValidationResult) -> Result<()> {
-        let discovery = DeviceDiscovery::new()?;
+    /// The readable devices of each redundant vdev, with its vdev type
+    ///
+    /// Devices that can't be read are left out; [`Check::DeviceSuitable`]
+    /// reports them.
+    fn redundant_vdevs(&self) -> Result<Vec<(&'static str, Vec<BlockDevice>)>> {
+        let discovery = DeviceDiscovery::new()?.with_sys_root(self.sys_root.clone());
+        Ok(self
+            .config
+            .vdev_groups()
+            .iter()
+            .filter_map(|vdev| {
+                let devices = vdev
+                    .devices
+                    .iter()
+                    .filter_map(|path| path.file_name())
+                    .filter_map(|name| discovery.find_device(&name.to_string_lossy()).ok())
+                    .collect();
+                Some((vdev.raid.vdev_type()?, devices))
+            })
+            .collect())
+    }
 
-        for device_path in &self.config.devices {
-            let device_name = device_path
+    /// Check the SMART health of every selected device
+    ///
+    /// A failed overall assessment is an error unless `force` is set;
+    /// worrying attributes are warnings. Without smartctl the check is
+    /// skipped with a single warning.
+    fn check_drive_health(&self, smartctl: bool) -> Result<CheckResult> {
+        let check = Check::DriveHealth;
+        if !smartctl {
+            return Ok(CheckResult::warning(
+                check,
+                tr("validation.smartctl_missing").to_string(),
+            ));
+        }
+
+        let discovery = DeviceDiscovery::new()?.with_sys_root(self.sys_root.clone());
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        for (_, path) in self.config.devices_by_role() {
+            // Unreadable devices are reported by Check::DeviceSuitable
+            let Some(device) = path
                 .file_name()
-                .ok_or_else(|| InstallerError::DeviceNotFound(device_path.clone()))?
-                .to_string_lossy()
-                .to_string();
-
-            match discovery.find_device(&device_name) {
-                Ok(device) => {
-                    // Check if device is suitable
-                    if let Err(e) = device.is_suitable() {
-                        if device.removable && self.config.force {
-                            result.add_warning(format!(
-                                "Device {} is removable but --force was specified",
-                                device.path.display()
-                            ));
-                        } else {
-                            result.add_error(format!("{}", e));
-                        }
-                    }
-
-                    // Check minimum size
-                    let min_size = self.config.min_device_size();
-                    if device.size < min_size.0 {
-                        result.add_error(format!(
-                            "Device {} is too small ({}, need at least {})",
-                            device.path.display(),
-                            device.size_human(),
-                            min_size
-                        ));
-                    }
-                }
+                .and_then(|name| discovery.find_device(&name.to_string_lossy()).ok())
+            else {
+                continue;
+            };
+            let device_name = device.path.display();
+            let status = match device.probe_smart(self.executor.as_ref()) {
+                Ok(Some(status)) => status,
+                Ok(None) => continue,
                 Err(e) => {
-                    result.add_error(format!("Device {}: {}", device_path.display(), e));
+                    warnings.push(tr_args(
+                        "validation.smart_unreadable",
+                        &[("device", &device_name), ("error", &e)],
+                    ));
+                    continue;
+                }
+            };
+
+            if !status.passed {
+                if self.config.force {
+                    warnings.push(tr_args(
+                        "validation.smart_failed_forced",
+                        &[("device", &device_name)],
+                    ));
+                } else {
+                    errors.push(tr_args(
+                        "validation.smart_failed",
+                        &[("device", &device_name)],
+                    ));
                 }
             }
+            let problems = status.problems();
+            if !problems.is_empty() {
+                warnings.push(tr_args(
+                    "validation.smart_degraded",
+                    &[("device", &device_name), ("problems", &problems.join(", "))],
+                ));
+            }
         }
 
-        Ok(())
+        Ok(if !errors.is_empty() {
+            errors.extend(warnings);
+            CheckResult::error(check, errors.join("; "))
+        } else if !warnings.is_empty() {
+            CheckResult::warning(check, warnings.join("; "))
+        } else {
+            CheckResult::ok(check)
+        })
     }
 
-    /// Check system requirements
-    fn check_system_requirements(&self, result: &mut ValidationResult) -> Result<()> {
-        // Check memory (minimum 2GB for ZFS)
-        let mem_kb = crate::system::get_system_memory_kb()?;
-        let mem_gb = mem_kb / (1024 * 1024);
+    /// Check one selected device, collecting warnings for what `force` overrides
+    fn check_device(&self, device_path: &Path, warnings: &mut Vec<String>) -> Result<()> {
+        let discovery = DeviceDiscovery::new()?.with_sys_root(self.sys_root.clone());
+        let members = zfs::status::imported_members(self.executor.as_ref()).unwrap_or_else(|e| {
+            log::warn!("Could not list imported pool devices: {}", e);
+            Vec::new()
+        });
+        self.validate_device(&discovery, &members, device_path, warnings)
+    }
 
-        if mem_gb < 2 {
-            result.add_warning(format!(
-                "System has only {}GB of RAM. ZFS recommends at least 2GB.",
-                mem_gb
+    /// Validate a single device
+    fn validate_device(
+        &self,
+        discovery: &DeviceDiscovery,
+        members: &[PoolMember],
+        device_path: &Path,
+        warnings: &mut Vec<String>,
+    ) -> Result<()> {
+        let device_name = device_path
+            .file_name()
+            .ok_or_else(|| InstallerError::DeviceNotFound(device_path.to_path_buf()))?
+            .to_string_lossy()
+            .to_string();
+
+        let device = discovery.find_device(&device_name)?;
+
+        // Not even --force wipes the system out from under itself
+        if device.is_boot_medium {
+            return Err(InstallerError::InvalidDevice {
+                path: device.path.clone(),
+                reason: tr("validation.boot_medium").to_string(),
+            });
+        }
+
+        // A path of a multipath map is only ever written through the map
+        let map = device
+            .holders
+            .iter()
+            .map(|name| holders::Holder::read(discovery.sys_root(), name))
+            .find(|holder| holder.kind == holders::HolderKind::Multipath);
+        if let Some(map) = map {
+            return Err(InstallerError::InvalidDevice {
+                path: device.path.clone(),
+                reason: tr_args("validation.multipath_path", &[("map", &map.display_name())]),
+            });
+        }
+
+        // The same policy decides again when the disks are prepared
+        let policy = self.config.suitability_policy();
+        device.is_suitable(&policy)?;
+        if policy.forces(&device) {
+            warnings.push(tr_args(
+                "validation.removable_forced",
+                &[
+                    ("device", &device.path.display()),
+                    ("controller", &device.controller_type),
+                ],
             ));
         }
 
-        // Check required commands
-        let required_commands = vec!["sgdisk", "mkfs.vfat", "zpool", "zfs"];
-        for cmd in required_commands {
-            if !self.command_exists(cmd) {
-                result.add_error(format!("Required command not found: {}", cmd));
+        // Nothing is wiped when installing onto an existing pool
+        if self.config.use_existing_pool {
+            return Ok(());
+        }
+        let automounts = device.automounts();
+        if !automounts.is_empty() {
+            let mounts: Vec<String> = automounts.iter().map(|m| m.display().to_string()).collect();
+            warnings.push(tr_args(
+                "validation.automounted",
+                &[
+                    ("device", &device.path.display()),
+                    ("mounts", &mounts.join(", ")),
+                ],
+            ));
+        }
+
+        // or into free space, which only has to be large enough
+        if let PartitioningMode::UseFreeSpace { reuse_esp } = self.config.partitioning {
+            return self.validate_free_space(discovery, &device, reuse_esp);
+        }
+
+        // Refuse to wipe a disk holding a pool, imported or not
+        if let Some(pool) = device.zfs_pool_in(discovery.sys_root(), members) {
+            let device_name = device.path.display();
+            if self.config.force {
+                warnings.push(tr_args(
+                    "validation.zfs_member_forced",
+                    &[("device", &device_name), ("pool", &pool)],
+                ));
+            } else {
+                return Err(InstallerError::InvalidDevice {
+                    path: device.path.clone(),
+                    reason: tr_args("validation.zfs_member", &[("pool", &pool)]),
+                });
             }
         }
 
+        // Everything stacked on the disk goes with it
+        if !device.holders.is_empty() {
+            let stacked = holders::describe(discovery.sys_root(), &device.holders);
+            if self.config.force || self.config.release_holders {
+                warnings.push(tr_args(
+                    "validation.holders_released",
+                    &[("device", &device.path.display()), ("holders", &stacked)],
+                ));
+            } else {
+                return Err(InstallerError::InvalidDevice {
+                    path: device.path.clone(),
+                    reason: tr_args("validation.holders", &[("holders", &stacked)]),
+                });
+            }
+        }
+        let members = device.stacked_members();
+        if !members.is_empty() {
+            warnings.push(tr_args(
+                "validation.stacked_members",
+                &[
+                    ("device", &device.path.display()),
+                    ("members", &members.join(", ")),
+                ],
+            ));
+        }
+        if device.offers_4k_format() {
+            warnings.push(tr_args(
+                "validation.nvme_4k_format",
+                &[
+                    ("device", &device.path.display()),
+                    ("model", &device.model.as_deref().unwrap_or_default().trim()),
+                ],
+            ));
+        }
+
+        // The exact layout, alignment and backup GPT included, has to fit too
+        PartitionTable::plan(
+            &device,
+            self.config.efi_size,
+            self.config.swap_partition_size(),
+            self.config.boot_layout(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Check the largest free region of `device` holds the ZBM partitions
+    ///
+    /// A reused ESP needs no room; BIOS layouts have none to reuse. Every disk of a multi-disk pool gets the
+    /// same partitions, so each has to pass on its own.
+    fn validate_free_space(
+        &self,
+        discovery: &DeviceDiscovery,
+        device: &BlockDevice,
+        reuse_esp: bool,
+    ) -> Result<()> {
+        let gpt = DiskOperations::new(false)
+            .with_executor(self.executor.clone())
+            .with_sys_root(discovery.sys_root().clone())
+            .read_gpt(device)?;
+
+        let mut min_size = self.config.min_device_size().0;
+        if reuse_esp && gpt.esp().is_some() && self.config.boot_layout() != BootLayout::Bios {
+            min_size = min_size.saturating_sub(self.config.efi_size.0);
+        }
+        let align = PartitionTable::alignment_for(device).max(gpt.sector_size) / gpt.sector_size;
+        let free = gpt
+            .largest_free_region(align)
+            .map_or(0, |(start, end)| (end + 1 - start) * gpt.sector_size);
+        if free < min_size {
+            let key = if self.config.data_devices().len() > 1 {
+                "validation.free_space_every_disk"
+            } else {
+                "validation.free_space"
+            };
+            return Err(InstallerError::InvalidDevice {
+                path: device.path.clone(),
+                reason: tr_args(
+                    key,
+                    &[("free", &ByteSize(free)), ("min_size", &ByteSize(min_size))],
+                ),
+            });
+        }
+
+        // Alignment and any BIOS boot partition have to fit too
+        PartitionTable::plan_free_space(
+            device,
+            &gpt,
+            self.config.efi_size,
+            self.config.swap_partition_size(),
+            self.config.boot_layout(),
+            reuse_esp,
+        )?;
         Ok(())
     }
 
+    /// Commands the installation runs
+    fn required_commands(&self) -> Vec<&'static str> {
+        let bios = self.config.boot_layout() == BootLayout::Bios;
+        let mut required_commands = if self.config.use_existing_pool {
+            vec!["zpool", "zfs"]
+        } else if bios {
+            vec!["sgdisk", "mkfs.ext4", "zpool", "zfs"]
+        } else {
+            vec!["sgdisk", "mkfs.vfat", "zpool", "zfs"]
+        };
+        if bios {
+            // The components are built, not downloaded
+            required_commands.extend(["generate-zbm", "extlinux"]);
+            return required_commands;
+        }
+        if self.config.bootloader == Bootloader::Grub {
+            required_commands.push("grub-install");
+        }
+        match self.config.zbm_source {
+            ZbmSource::Release { .. } if self.config.artifact_dir.is_none() => {
+                required_commands.push("curl")
+            }
+            // Without the fallback there is no other way to get an image
+            ZbmSource::Local if !self.config.zbm_release_fallback => {
+                required_commands.push("generate-zbm")
+            }
+            _ => {}
+        }
+        if self.config.secure_boot.signs() {
+            required_commands.extend(["sbsign", "sbverify"]);
+        }
+        if let Some(spec) = &self.config.bootstrap {
+            required_commands.push(spec.method.program());
+        }
+        if self.config.secure_boot == SecureBootMode::Shim {
+            required_commands.extend(["openssl", "mokutil"]);
+        }
+        required_commands
+    }
+
     /// Check if a command exists
     fn command_exists(&self, cmd: &str) -> bool {
         std::process::Command::new("which")
@@ -180,20 +992,189 @@ impl Validator {
     }
 }
 
+/// Whether a TCP connection to `host` on `port` opens within
+/// [`NETWORK_TIMEOUT`]
+fn can_connect(host: &str, port: u16) -> bool {
+    let Ok(addresses) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addresses
+        .into_iter()
+        .any(|address| TcpStream::connect_timeout(&address, NETWORK_TIMEOUT).is_ok())
+}
+
+/// Warnings about booting from an existing pool named `name`
+///
+/// A legacy pool too old for `bootfs` is an error. GRUB can only read pools
+/// whose features it knows, so it gets a warning unless the pool's
+/// `compatibility` property keeps to them. Nothing is queried in dry-run
+/// mode.
+pub fn existing_pool_warnings(
+    pool: &ZfsPool,
+    name: &str,
+    bootloader: Bootloader,
+) -> Result<Vec<String>> {
+    if pool.supports_bootfs()? == Some(false) {
+        return Err(InstallerError::validation(tr_args(
+            "validation.pool_no_bootfs",
+            &[("pool", &name)],
+        )));
+    }
+
+    let mut warnings = Vec::new();
+    if bootloader == Bootloader::Grub && pool.compatibility()?.as_deref() == Some("off") {
+        warnings.push(tr_args(
+            "validation.pool_no_compatibility",
+            &[("pool", &name)],
+        ));
+    }
+    Ok(warnings)
+}
+
+/// Describe the first feature set in `compatibility` that isn't a file in `dir`
+///
+/// `compatibility` is a comma-separated list of files, or `off` or `legacy`
+/// on their own.
+fn unknown_compatibility(compatibility: &str, dir: &Path) -> Option<String> {
+    if matches!(compatibility, "off" | "legacy") {
+        return None;
+    }
+
+    let unknown = compatibility
+        .split(',')
+        .map(str::trim)
+        .find(|name| name.is_empty() || name.contains('/') || !dir.join(name).is_file())?;
+    let mut available: Vec<String> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    available.sort();
+    let available = if available.is_empty() {
+        tr("validation.no_compatibility_files").to_string()
+    } else {
+        available.join(", ")
+    };
+    Some(tr_args(
+        "validation.unknown_compatibility",
+        &[("name", &unknown), ("available", &available)],
+    ))
+}
+
+/// Warn when the devices of one vdev differ in size by more than
+/// [`MAX_SIZE_DIFFERENCE_PERCENT`]
+fn size_mismatch(devices: &[BlockDevice]) -> Option<String> {
+    let smallest = devices.iter().min_by_key(|d| d.size)?;
+    let largest = devices.iter().max_by_key(|d| d.size)?;
+    let difference = u128::from(largest.size - smallest.size) * 100;
+    if difference <= u128::from(largest.size) * u128::from(MAX_SIZE_DIFFERENCE_PERCENT) {
+        return None;
+    }
+    Some(tr_args(
+        "validation.size_mismatch",
+        &[
+            ("percent", &MAX_SIZE_DIFFERENCE_PERCENT),
+            (
+                "smallest",
+                &format!("{} ({})", smallest.name, smallest.size_human()),
+            ),
+            (
+                "largest",
+                &format!("{} ({})", largest.name, largest.size_human()),
+            ),
+            ("usable", &smallest.size_human()),
+        ],
+    ))
+}
+
+/// Warn when striped top-level vdevs differ in type or width
+///
+/// ZFS spreads writes by free space, so an uneven layout fills and wears
+/// its vdevs unevenly and its redundancy is only as good as the weakest vdev.
+fn uneven_vdevs(vdevs: &[VdevSpec]) -> Option<String> {
+    let first = vdevs.first()?;
+    if vdevs
+        .iter()
+        .all(|vdev| vdev.raid == first.raid && vdev.devices.len() == first.devices.len())
+    {
+        return None;
+    }
+    let layout = vdevs
+        .iter()
+        .map(|vdev| format!("{} x {}", vdev.raid, vdev.devices.len()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(tr_args("validation.uneven_vdevs", &[("layout", &layout)]))
+}
+
+/// Warn when SSDs and HDDs are mixed in one vdev
+fn mixed_media(devices: &[BlockDevice], vdev: &str) -> Option<String> {
+    let names = |rotational: bool| {
+        devices
+            .iter()
+            .filter(|d| d.rotational == rotational)
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let (hdds, ssds) = (names(true), names(false));
+    if hdds.is_empty() || ssds.is_empty() {
+        return None;
+    }
+    Some(tr_args(
+        "validation.mixed_media",
+        &[("ssds", &ssds), ("hdds", &hdds), ("vdev", &vdev)],
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::BootstrapSpec;
+    use crate::exec::RecordingExecutor;
+    use std::fs;
+    use std::path::PathBuf;
 
     #[test]
     fn test_validation_result() {
         let mut result = ValidationResult::new();
         assert!(result.is_ok());
 
-        result.add_warning("Test warning".to_string());
+        result.push(CheckResult::ok(Check::RootPrivileges));
+        result.push(CheckResult::warning(
+            Check::MinimumMemory,
+            "Test warning".to_string(),
+        ));
         assert!(result.is_ok());
 
-        result.add_error("Test error".to_string());
+        result.push(CheckResult::error(
+            Check::ZfsAvailable,
+            "Test error".to_string(),
+        ));
         assert!(!result.is_ok());
+        assert_eq!(result.warnings().collect::<Vec<_>>(), vec!["Test warning"]);
+        assert_eq!(result.errors().collect::<Vec<_>>(), vec!["Test error"]);
+    }
+
+    #[test]
+    fn test_result_json_round_trip() {
+        let mut result = ValidationResult::new();
+        result.push(CheckResult::ok(Check::RootPrivileges));
+        result.push(CheckResult::error(
+            Check::DeviceSuitable(PathBuf::from("/dev/sda")),
+            "Device is read-only".to_string(),
+        ));
+
+        let json = result.to_json().unwrap();
+        assert!(json.contains("\"device-suitable\": \"/dev/sda\""), "{json}");
+        assert!(json.contains("\"severity\": \"error\""), "{json}");
+        assert_eq!(
+            serde_json::from_str::<ValidationResult>(&json).unwrap(),
+            result
+        );
     }
 
     #[test]
@@ -203,4 +1184,777 @@ mod tests {
         // Just test that it creates successfully
         assert_eq!(validator.config.pool_name, "zroot");
     }
+
+    #[test]
+    fn test_checks_follow_the_configuration() {
+        let validator = Validator::new(Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            bootloader: Bootloader::Grub,
+            zbm_source: ZbmSource::CustomImage {
+                path: PathBuf::from("/srv/zfsbootmenu.EFI"),
+            },
+            ..Default::default()
+        });
+        let checks = validator.checks();
+
+        assert_eq!(checks[0], Check::RootPrivileges);
+        assert!(checks.contains(&Check::DeviceSuitable(PathBuf::from("/dev/sdb"))));
+        assert!(checks.contains(&Check::RequiredCommand("grub-install".to_string())));
+        assert!(!checks.contains(&Check::RequiredCommand("curl".to_string())));
+        assert_eq!(
+            Check::DeviceSuitable(PathBuf::from("/dev/sda")).to_string(),
+            "Device /dev/sda"
+        );
+    }
+
+    #[test]
+    fn test_hybrid_accepts_either_firmware() {
+        let uefi_only = Validator::new(Config::default());
+        assert_eq!(uefi_only.check_firmware(false).severity, Severity::Error);
+
+        let hybrid = Validator::new(Config {
+            boot_mode: crate::config::BootMode::Hybrid,
+            ..Default::default()
+        });
+        assert_eq!(hybrid.check_firmware(true).severity, Severity::Ok);
+        assert_eq!(hybrid.check_firmware(false).severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_enforced_secure_boot_needs_signing() {
+        let secure_boot = |secure_boot, force, enabled| {
+            Validator::new(Config {
+                secure_boot,
+                force,
+                ..Default::default()
+            })
+            .check_secure_boot(enabled)
+            .severity
+        };
+        assert_eq!(secure_boot(SecureBootMode::Off, false, None), Severity::Ok);
+        assert_eq!(
+            secure_boot(SecureBootMode::Off, false, Some(false)),
+            Severity::Ok
+        );
+        assert_eq!(
+            secure_boot(SecureBootMode::Off, false, Some(true)),
+            Severity::Error
+        );
+        assert_eq!(
+            secure_boot(SecureBootMode::Off, true, Some(true)),
+            Severity::Warning
+        );
+        assert_eq!(
+            secure_boot(SecureBootMode::Shim, false, Some(true)),
+            Severity::Ok
+        );
+
+        let bios = Validator::new(Config {
+            firmware: FirmwareMode::Bios,
+            ..Default::default()
+        });
+        assert!(!bios.checks().contains(&Check::SecureBoot));
+    }
+
+    #[test]
+    fn test_timezone_must_exist() {
+        let zoneinfo = tempfile::tempdir().unwrap();
+        std::fs::create_dir(zoneinfo.path().join("Europe")).unwrap();
+        std::fs::write(zoneinfo.path().join("Europe/Berlin"), "TZif").unwrap();
+        let timezone = |timezone: &str| {
+            Validator::new(Config {
+                timezone: Some(timezone.to_string()),
+                ..Default::default()
+            })
+            .check_timezone(zoneinfo.path())
+            .severity
+        };
+
+        assert_eq!(timezone("Europe/Berlin"), Severity::Ok);
+        assert_eq!(timezone("Europe/Atlantis"), Severity::Error);
+        // A directory is a region, not a zone
+        assert_eq!(timezone("Europe"), Severity::Error);
+        assert!(!Validator::new(Config::default())
+            .checks()
+            .contains(&Check::Timezone));
+    }
+
+    #[test]
+    fn test_arc_max_must_fit_in_memory() {
+        let validator = |arc_max: Option<ByteSize>| {
+            Validator::new(Config {
+                arc_max,
+                ..Default::default()
+            })
+        };
+
+        let result = validator(Some(ByteSize::gib(16))).check_memory(ByteSize::gib(8));
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result
+            .message
+            .unwrap()
+            .contains(&ByteSize::gib(16).to_string()));
+        assert_eq!(
+            validator(Some(ByteSize::gib(4)))
+                .check_memory(ByteSize::gib(8))
+                .severity,
+            Severity::Ok
+        );
+        assert_eq!(
+            validator(None).check_memory(ByteSize::gib(1)).severity,
+            Severity::Warning
+        );
+    }
+
+    #[test]
+    fn test_offline_artifacts_must_be_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let validator = Validator::new(Config {
+            offline: true,
+            artifact_dir: Some(dir.path().to_path_buf()),
+            pkg_dir: Some(dir.path().join("packages")),
+            ..Default::default()
+        });
+        assert!(validator.checks().contains(&Check::Artifacts));
+        assert!(!validator.required_commands().contains(&"curl"));
+
+        let result = validator.check_artifacts();
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result.message.unwrap().contains("packages"));
+
+        fs::create_dir(dir.path().join("packages")).unwrap();
+        let result = validator.check_artifacts();
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result.message.unwrap().contains("fetch-artifacts"));
+
+        let release = dir.path().join("2.3.0");
+        fs::create_dir(&release).unwrap();
+        fs::write(
+            release.join("sha256.txt"),
+            "SHA256 (zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI) = 00\n",
+        )
+        .unwrap();
+        fs::write(
+            release.join("zfsbootmenu-release-x86_64-v2.3.0-linux6.1.EFI"),
+            "MZ",
+        )
+        .unwrap();
+        assert_eq!(validator.check_artifacts().severity, Severity::Ok);
+    }
+
+    #[test]
+    fn test_bootstrap_needs_the_mirror() {
+        let validator = |bootstrap: &str| {
+            Validator::new(Config {
+                bootstrap: Some(bootstrap.parse().unwrap()),
+                ..Default::default()
+            })
+        };
+
+        let debootstrap = validator("debootstrap:bookworm");
+        let checks = debootstrap.checks();
+        assert!(checks.contains(&Check::Network));
+        assert!(checks.contains(&Check::RequiredCommand("debootstrap".to_string())));
+        assert_eq!(debootstrap.check_network(true).severity, Severity::Ok);
+        let unreachable = debootstrap.check_network(false);
+        assert_eq!(unreachable.severity, Severity::Error);
+        assert!(unreachable.message.unwrap().contains("deb.debian.org"));
+
+        // A local mirror needs no network
+        let mut local = "debootstrap:bookworm".parse::<BootstrapSpec>().unwrap();
+        local.mirror = Some("file:///srv/mirror/debian".to_string());
+        assert!(!Validator::new(Config {
+            bootstrap: Some(local),
+            ..Default::default()
+        })
+        .checks()
+        .contains(&Check::Network));
+        assert!(!Validator::new(Config::default())
+            .checks()
+            .contains(&Check::Network));
+    }
+
+    #[test]
+    fn test_firmware_check_follows_the_selected_mode() {
+        let firmware = |firmware, uefi| {
+            Validator::new(Config {
+                firmware,
+                ..Default::default()
+            })
+            .check_firmware(uefi)
+            .severity
+        };
+        assert_eq!(firmware(FirmwareMode::Bios, false), Severity::Ok);
+        assert_eq!(firmware(FirmwareMode::Bios, true), Severity::Warning);
+        assert_eq!(firmware(FirmwareMode::Auto, false), Severity::Ok);
+        assert_eq!(firmware(FirmwareMode::Auto, true), Severity::Ok);
+        assert_eq!(firmware(FirmwareMode::Uefi, false), Severity::Error);
+
+        // BIOS installs build ZFSBootMenu with the host's generate-zbm
+        let checks = Validator::new(Config {
+            firmware: FirmwareMode::Bios,
+            bootloader: Bootloader::Grub,
+            ..Default::default()
+        })
+        .checks();
+        for command in ["mkfs.ext4", "generate-zbm", "extlinux"] {
+            assert!(checks.contains(&Check::RequiredCommand(command.to_string())));
+        }
+        for command in ["mkfs.vfat", "grub-install", "curl"] {
+            assert!(!checks.contains(&Check::RequiredCommand(command.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_every_device_is_checked() {
+        let config = Config {
+            devices: vec![
+                PathBuf::from("/dev/zbm-missing-a"),
+                PathBuf::from("/dev/zbm-missing-b"),
+            ],
+            ..Default::default()
+        };
+        let validator = Validator::new(config).with_executor(Arc::new(RecordingExecutor::new()));
+
+        let failed: Vec<Check> = validator
+            .checks()
+            .into_iter()
+            .filter(|c| matches!(c, Check::DeviceSuitable(_)))
+            .map(|c| validator.run_check(c).unwrap())
+            .filter(|r| r.severity == Severity::Error)
+            .map(|r| r.check)
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                Check::DeviceSuitable(PathBuf::from("/dev/zbm-missing-a")),
+                Check::DeviceSuitable(PathBuf::from("/dev/zbm-missing-b")),
+            ]
+        );
+    }
+
+    fn device(name: &str, size: u64, rotational: bool) -> BlockDevice {
+        BlockDevice {
+            name: name.to_string(),
+            path: PathBuf::from(format!("/dev/{}", name)),
+            sys_path: PathBuf::from(format!("/sys/block/{}", name)),
+            controller_type: crate::disk::ControllerType::Sata,
+            size,
+            logical_block_size: 512,
+            physical_block_size: 4096,
+            optimal_io_size: 0,
+            model: None,
+            serial: None,
+            vendor: None,
+            removable: false,
+            readonly: false,
+            rotational,
+            mountpoint: None,
+            partitions: Vec::new(),
+            holders: Vec::new(),
+            is_boot_medium: false,
+            dm_name: None,
+            slaves: Vec::new(),
+            smart: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_size_mismatch_threshold() {
+        let largest = 1_000_000_000_000;
+        // Exactly 10% smaller is still fine, a byte more is not
+        let at_limit = largest - largest * MAX_SIZE_DIFFERENCE_PERCENT / 100;
+        let devices = [
+            device("sda", largest, false),
+            device("sdb", at_limit, false),
+        ];
+        assert_eq!(size_mismatch(&devices), None);
+
+        let devices = [
+            device("sda", largest, false),
+            device("sdb", at_limit - 1, false),
+        ];
+        let message = size_mismatch(&devices).unwrap();
+        assert!(message.contains("sdb"), "{message}");
+        assert!(message.contains("limited by the smallest"), "{message}");
+
+        let devices = [
+            device("sda", 256_000_000_000, false),
+            device("sdb", 4_000_000_000_000, true),
+        ];
+        assert!(size_mismatch(&devices).is_some());
+        assert_eq!(size_mismatch(&[]), None);
+    }
+
+    #[test]
+    fn test_mixed_media() {
+        let ssds = [device("sda", 1, false), device("sdb", 1, false)];
+        assert_eq!(mixed_media(&ssds, "mirror"), None);
+        let hdds = [device("sda", 1, true), device("sdb", 1, true)];
+        assert_eq!(mixed_media(&hdds, "mirror"), None);
+
+        let mixed = [
+            device("sda", 1, false),
+            device("sdb", 1, true),
+            device("sdc", 1, false),
+        ];
+        let message = mixed_media(&mixed, "raidz1").unwrap();
+        assert!(message.contains("SSDs (sda, sdc)"), "{message}");
+        assert!(message.contains("HDDs (sdb)"), "{message}");
+        assert!(message.contains("raidz1"), "{message}");
+    }
+
+    #[test]
+    fn test_unknown_compatibility() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(unknown_compatibility("grub2", dir.path())
+            .unwrap()
+            .contains("available: none installed"));
+
+        for name in ["grub2", "openzfs-2.1-linux"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        assert_eq!(unknown_compatibility("grub2", dir.path()), None);
+        assert_eq!(
+            unknown_compatibility("openzfs-2.1-linux,grub2", dir.path()),
+            None
+        );
+        assert_eq!(unknown_compatibility("off", dir.path()), None);
+
+        let message = unknown_compatibility("grub2,zfs-9", dir.path()).unwrap();
+        assert!(message.contains("'zfs-9'"), "{message}");
+        assert!(message.contains("grub2, openzfs-2.1-linux"), "{message}");
+        assert!(unknown_compatibility("../grub2", dir.path()).is_some());
+    }
+
+    #[test]
+    fn test_uneven_vdevs() {
+        let vdev = |s: &str| s.parse::<VdevSpec>().unwrap();
+        let even = [
+            vdev("mirror:/dev/sda,/dev/sdb"),
+            vdev("mirror:/dev/sdc,/dev/sdd"),
+        ];
+        assert_eq!(uneven_vdevs(&even), None);
+
+        let uneven = [
+            vdev("mirror:/dev/sda,/dev/sdb"),
+            vdev("mirror:/dev/sdc,/dev/sdd,/dev/sde"),
+        ];
+        let message = uneven_vdevs(&uneven).unwrap();
+        assert!(message.contains("mirror x 2, mirror x 3"), "{message}");
+
+        let mixed = [
+            vdev("mirror:/dev/sda,/dev/sdb"),
+            vdev("none:/dev/sdc,/dev/sdd"),
+        ];
+        assert!(uneven_vdevs(&mixed).is_some());
+
+        let validator = Validator::new(Config {
+            vdevs: uneven.to_vec(),
+            ..Default::default()
+        });
+        assert!(validator.checks().contains(&Check::MatchedVdevs));
+        assert!(validator.checks().contains(&Check::MatchedSizes));
+    }
+
+    #[test]
+    fn test_vdev_checks_only_for_redundant_layouts() {
+        let devices = vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")];
+        let stripe = Validator::new(Config {
+            devices: devices.clone(),
+            ..Default::default()
+        });
+        assert!(!stripe.checks().contains(&Check::MatchedSizes));
+
+        let mirror = Validator::new(Config {
+            devices,
+            raid_level: crate::config::RaidLevel::Mirror,
+            ..Default::default()
+        })
+        .with_sys_root(SysRoot::fixture());
+        assert!(mirror.checks().contains(&Check::MatchedMedia));
+        // The fixture's sda is a 500GB SSD, sdb a 16GB USB stick
+        let result = mirror.run_check(Check::MatchedSizes).unwrap();
+        assert_eq!(result.severity, Severity::Warning);
+    }
+
+    /// Validator for `devices` over a /dev with by-id links to sda and sda1
+    fn overlap_validator(devices: &[&str]) -> (tempfile::TempDir, Validator) {
+        let dir = tempfile::tempdir().unwrap();
+        let by_id = dir.path().join("dev/disk/by-id");
+        std::fs::create_dir_all(&by_id).unwrap();
+        std::os::unix::fs::symlink("../../sda", by_id.join("ata-DISK_1")).unwrap();
+        std::os::unix::fs::symlink("../../sda1", by_id.join("ata-DISK_1-part1")).unwrap();
+
+        let config = Config {
+            devices: devices.iter().map(PathBuf::from).collect(),
+            ..Default::default()
+        };
+        let validator = Validator::new(config).with_sys_root(SysRoot::new(dir.path()));
+        (dir, validator)
+    }
+
+    fn overlap(devices: &[&str]) -> Option<String> {
+        let (_dir, validator) = overlap_validator(devices);
+        let result = validator.run_check(Check::DistinctDevices).unwrap();
+        assert_eq!(result.severity == Severity::Ok, result.message.is_none());
+        result.message
+    }
+
+    #[test]
+    fn test_distinct_devices_pass() {
+        assert_eq!(overlap(&["/dev/sda", "/dev/sdb"]), None);
+        assert_eq!(overlap(&["/dev/nvme0n1", "/dev/nvme1n1"]), None);
+        assert_eq!(overlap(&["/dev/disk/by-id/ata-DISK_1", "/dev/sdb1"]), None);
+    }
+
+    #[test]
+    fn test_duplicate_devices_are_rejected() {
+        let message = overlap(&["/dev/sda", "/dev/sdb", "/dev/sda"]).unwrap();
+        assert!(
+            message.contains("/dev/sda is listed more than once"),
+            "{message}"
+        );
+
+        // The same disk through a by-id link names both entries
+        let message = overlap(&["/dev/disk/by-id/ata-DISK_1", "/dev/sda"]).unwrap();
+        assert!(message.contains("/dev/disk/by-id/ata-DISK_1"), "{message}");
+        assert!(message.contains("/dev/sda"), "{message}");
+    }
+
+    #[test]
+    fn test_partition_of_selected_disk_is_rejected() {
+        let message =
+            overlap(&["/dev/disk/by-id/ata-DISK_1-part1", "/dev/sdb", "/dev/sda"]).unwrap();
+        assert!(
+            message.starts_with("/dev/disk/by-id/ata-DISK_1-part1 is a partition of /dev/sda"),
+            "{message}"
+        );
+
+        let message = overlap(&["/dev/nvme0n1", "/dev/nvme0n1p2"]).unwrap();
+        assert!(
+            message.starts_with("/dev/nvme0n1p2 is a partition of /dev/nvme0n1"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_support_device_overlapping_data_is_rejected() {
+        let (_dir, validator) = overlap_validator(&["/dev/sda"]);
+        let validator = Validator {
+            config: Config {
+                cache_devices: vec![PathBuf::from("/dev/disk/by-id/ata-DISK_1-part1")],
+                ..validator.config.clone()
+            },
+            ..validator
+        };
+        let result = validator.run_check(Check::DistinctDevices).unwrap();
+        assert_eq!(result.severity, Severity::Error);
+        let message = result.message.unwrap();
+        assert!(message.contains("is a partition of /dev/sda"), "{message}");
+    }
+
+    /// Validator for the fixture's sda, with /dev/sda3 in an imported pool
+    fn pool_member_validator(force: bool) -> Validator {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program(
+            "zpool",
+            "  pool: tank\n state: ONLINE\nconfig:\n\n\
+             \tNAME                                            STATE\n\
+             \ttank                                            ONLINE\n\
+             \t  /dev/disk/by-id/wwn-0x5002538f42a1b2c3-part2  ONLINE\n",
+        );
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            force,
+            ..Default::default()
+        };
+        Validator::new(config)
+            .with_sys_root(SysRoot::fixture())
+            .with_executor(executor)
+    }
+
+    fn check_sda(validator: &Validator) -> CheckResult {
+        validator
+            .run_check(Check::DeviceSuitable(PathBuf::from("/dev/sda")))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pool_member_is_refused() {
+        let result = check_sda(&pool_member_validator(false));
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result.message.unwrap().contains("tank"));
+    }
+
+    #[test]
+    fn test_pool_member_allowed_with_force() {
+        let result = check_sda(&pool_member_validator(true));
+        assert_eq!(result.severity, Severity::Warning);
+        assert!(result.message.unwrap().contains("tank"));
+    }
+
+    /// Validator installing into the free space of the fixture's sda (and
+    /// nvme0n1 with `mirror`), whose last partition ends at `end`
+    fn free_space_validator(end: u64, reuse_esp: bool, mirror: bool) -> Validator {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.respond_program(
+            "sgdisk",
+            crate::disk::gpt::SAMPLE_PRINT.replace("410650623", &end.to_string()),
+        );
+        let mut devices = vec![PathBuf::from("/dev/sda")];
+        if mirror {
+            devices.push(PathBuf::from("/dev/nvme0n1"));
+        }
+        let config = Config {
+            devices,
+            raid_level: if mirror {
+                RaidLevel::Mirror
+            } else {
+                RaidLevel::None
+            },
+            partitioning: PartitioningMode::UseFreeSpace { reuse_esp },
+            ..Default::default()
+        };
+        Validator::new(config)
+            .with_sys_root(SysRoot::fixture())
+            .with_executor(executor)
+    }
+
+    #[test]
+    fn test_free_space_must_hold_the_layout() {
+        assert_eq!(
+            check_sda(&free_space_validator(410650623, false, false)).severity,
+            Severity::Ok
+        );
+
+        // 18.5 GiB free: enough for 8 GiB of swap and the pool, not for an ESP too
+        let end = 937975807;
+        assert_eq!(
+            check_sda(&free_space_validator(end, true, false)).severity,
+            Severity::Ok
+        );
+        let result = check_sda(&free_space_validator(end, false, false));
+        assert_eq!(result.severity, Severity::Error);
+        let message = result.message.unwrap();
+        assert!(message.contains("need at least 20.4 GB"), "{message}");
+
+        let result = check_sda(&free_space_validator(end, false, true));
+        assert!(result.message.unwrap().contains("every disk"));
+    }
+
+    #[test]
+    fn test_stacked_devices_are_listed() {
+        let dir = crate::disk::holders::stacked_tree();
+        let check = |release_holders: bool| {
+            let config = Config {
+                devices: vec![PathBuf::from("/dev/sdc")],
+                release_holders,
+                ..Default::default()
+            };
+            Validator::new(config)
+                .with_sys_root(SysRoot::new(dir.path()))
+                .with_executor(Arc::new(RecordingExecutor::new()))
+                .run_check(Check::DeviceSuitable(PathBuf::from("/dev/sdc")))
+                .unwrap()
+        };
+
+        let refused = check(false);
+        assert_eq!(refused.severity, Severity::Error);
+        let message = refused.message.unwrap();
+        for stacked in ["data--vg-root", "data--vg-home", "luks-4f3c2a1e", "md127"] {
+            assert!(message.contains(stacked), "{message}");
+        }
+
+        let confirmed = check(true);
+        assert_eq!(confirmed.severity, Severity::Warning);
+        assert!(confirmed.message.unwrap().contains("md127 (md RAID array)"));
+    }
+
+    #[test]
+    fn test_boot_medium_is_refused_even_with_force() {
+        // The fixture's root filesystem is on nvme0n1
+        let device = PathBuf::from("/dev/nvme0n1");
+        let config = Config {
+            devices: vec![device.clone()],
+            force: true,
+            ..Default::default()
+        };
+        let result = Validator::new(config)
+            .with_sys_root(SysRoot::fixture())
+            .with_executor(Arc::new(RecordingExecutor::new()))
+            .run_check(Check::DeviceSuitable(device))
+            .unwrap();
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result.message.unwrap().contains("booted from this device"));
+    }
+
+    #[test]
+    fn test_multipath_paths_are_refused() {
+        let dir = crate::disk::discovery::multipath_tree();
+        let check = |device: &str| {
+            let device = PathBuf::from(device);
+            let config = Config {
+                devices: vec![device.clone()],
+                force: true,
+                ..Default::default()
+            };
+            Validator::new(config)
+                .with_sys_root(SysRoot::new(dir.path()))
+                .with_executor(Arc::new(RecordingExecutor::new()))
+                .run_check(Check::DeviceSuitable(device))
+                .unwrap()
+        };
+
+        let path = check("/dev/sdc");
+        assert_eq!(path.severity, Severity::Error);
+        assert!(path.message.unwrap().contains("/dev/mapper/mpatha"));
+        assert_eq!(check("/dev/mapper/mpatha").severity, Severity::Ok);
+    }
+
+    #[test]
+    fn test_nvme_with_4k_format_is_flagged() {
+        let dir = tempfile::tempdir().unwrap();
+        let block = dir.path().join("sys/block/nvme1n1");
+        for (attr, value) in [
+            ("size", "1953525168\n"),
+            ("queue/logical_block_size", "512\n"),
+            ("queue/physical_block_size", "512\n"),
+            ("device/model", "WD_BLACK SN850X 1000GB                  \n"),
+        ] {
+            let path = block.join(attr);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, value).unwrap();
+        }
+
+        let device = PathBuf::from("/dev/nvme1n1");
+        let config = Config {
+            devices: vec![device.clone()],
+            ..Default::default()
+        };
+        let result = Validator::new(config)
+            .with_sys_root(SysRoot::new(dir.path()))
+            .with_executor(Arc::new(RecordingExecutor::new()))
+            .run_check(Check::DeviceSuitable(device))
+            .unwrap();
+        assert_eq!(result.severity, Severity::Warning);
+        let message = result.message.unwrap();
+        assert!(
+            message.contains("/dev/nvme1n1 (WD_BLACK SN850X 1000GB) runs 512-byte sectors"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_existing_pool_member_is_allowed() {
+        let mut validator = pool_member_validator(false);
+        validator.config.use_existing_pool = true;
+        assert_eq!(check_sda(&validator).severity, Severity::Ok);
+    }
+
+    fn drive_health(smartctl: Option<&str>, failure: Option<i32>, force: bool) -> CheckResult {
+        let executor = Arc::new(RecordingExecutor::new());
+        if let Some(output) = smartctl {
+            executor.respond_program("smartctl", output);
+        }
+        if let Some(code) = failure {
+            executor.fail_program("smartctl", code, "");
+        }
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda")],
+            force,
+            ..Default::default()
+        };
+        Validator::new(config)
+            .with_sys_root(SysRoot::fixture())
+            .with_executor(executor)
+            .check_drive_health(true)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_drive_health() {
+        let healthy = r#"{"smart_status": {"passed": true}}"#;
+        assert_eq!(
+            drive_health(Some(healthy), None, false).severity,
+            Severity::Ok
+        );
+
+        let worn = r#"{"smart_status": {"passed": true},
+            "ata_smart_attributes": {"table": [{"id": 197, "raw": {"value": 3}}]}}"#;
+        let result = drive_health(Some(worn), None, false);
+        assert_eq!(result.severity, Severity::Warning);
+        assert!(result.message.unwrap().contains("3 pending sectors"));
+
+        // Bit 3 of smartctl's exit status: the health assessment failed
+        let result = drive_health(None, Some(8), false);
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result.message.unwrap().contains("/dev/sda"));
+        assert_eq!(
+            drive_health(None, Some(8), true).severity,
+            Severity::Warning
+        );
+
+        // No SMART support
+        assert_eq!(drive_health(None, Some(4), false).severity, Severity::Ok);
+    }
+
+    #[test]
+    fn test_missing_smartctl_warns_once() {
+        let config = Config {
+            devices: vec![PathBuf::from("/dev/sda"), PathBuf::from("/dev/sdb")],
+            ..Default::default()
+        };
+        let result = Validator::new(config)
+            .with_sys_root(SysRoot::fixture())
+            .check_drive_health(false)
+            .unwrap();
+        assert_eq!(result.severity, Severity::Warning);
+        assert_eq!(
+            result.message.as_deref(),
+            Some(tr("validation.smartctl_missing"))
+        );
+    }
+
+    /// Result of the existing pool check for `tank`, given the `zpool` outputs in order
+    fn check_existing_pool(bootloader: Bootloader, outputs: &[&str]) -> CheckResult {
+        let executor = Arc::new(RecordingExecutor::new());
+        for output in outputs {
+            executor.respond_program_times("zpool", 1, *output);
+        }
+        let config = Config {
+            pool_name: "tank".to_string(),
+            use_existing_pool: true,
+            devices: vec![PathBuf::from("/dev/sda")],
+            bootloader,
+            ..Default::default()
+        };
+        let validator = Validator::new(config).with_executor(executor);
+        assert!(validator.checks().contains(&Check::ExistingPool));
+        validator.run_check(Check::ExistingPool).unwrap()
+    }
+
+    #[test]
+    fn test_existing_pool_check() {
+        let imported = "  pool: tank\nconfig:\n\ttank  ONLINE\n\t  /dev/sda2  ONLINE\n";
+
+        // Exported, but importable: properties are checked after the import
+        let result = check_existing_pool(Bootloader::Grub, &["", "   pool: tank\n"]);
+        assert_eq!(result.severity, Severity::Ok);
+
+        let result = check_existing_pool(Bootloader::Grub, &["", "   pool: other\n"]);
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result.message.unwrap().contains("'tank'"));
+
+        let result = check_existing_pool(Bootloader::Grub, &[imported, "5\n"]);
+        assert_eq!(result.severity, Severity::Error);
+        assert!(result.message.unwrap().contains("bootfs"));
+
+        let result = check_existing_pool(Bootloader::Grub, &[imported, "-\n", "off\n"]);
+        assert_eq!(result.severity, Severity::Warning);
+        assert!(result.message.unwrap().contains("compatibility=grub2"));
+
+        let result = check_existing_pool(Bootloader::SystemdBoot, &[imported, "-\n", "off\n"]);
+        assert_eq!(result.severity, Severity::Ok);
+    }
 }
@@ -0,0 +1,373 @@
+//! Backend shelling out to the zpool and zfs commands
+
+use crate::config::Passphrase;
+use crate::error::{InstallerError, Result};
+use crate::exec::{CommandExecutor, ExecOptions, ExecResult, RetryPolicy};
+use crate::zfs::backend::{PoolSpec, ZfsBackend};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// ZFS backend running `zpool`/`zfs` through a command executor
+#[derive(Clone)]
+pub struct CliBackend {
+    executor: Arc<dyn CommandExecutor>,
+}
+
+impl CliBackend {
+    /// Create a CLI backend
+    pub fn new(executor: Arc<dyn CommandExecutor>) -> Self {
+        Self { executor }
+    }
+
+    /// Execute a command, reporting failures as ZFS errors
+    fn execute(&self, cmd: &mut Command) -> Result<ExecResult> {
+        self.execute_with(cmd, &ExecOptions::default())
+    }
+
+    /// Execute a command with explicit options, reporting failures as ZFS errors
+    fn execute_with(&self, cmd: &mut Command, options: &ExecOptions) -> Result<ExecResult> {
+        self.executor.execute_as(cmd, options, InstallerError::zfs)
+    }
+
+    /// Run a `get -H -o value` query
+    fn get(&self, program: &str, name: &str, property: &str) -> Result<Option<String>> {
+        let result = self.execute(
+            Command::new(program)
+                .arg("get")
+                .arg("-H")
+                .arg("-o")
+                .arg("value")
+                .arg(property)
+                .arg(name),
+        )?;
+
+        Ok(result.stdout_string().map(|s| s.trim().to_string()))
+    }
+}
+
+impl ZfsBackend for CliBackend {
+    fn name(&self) -> &'static str {
+        "cli"
+    }
+
+    fn pool_create(&self, spec: &PoolSpec) -> Result<()> {
+        let mut cmd = Command::new("zpool");
+        cmd.args(build_create_args(spec));
+
+        // The passphrase goes to stdin, never onto the command line
+        let mut options = ExecOptions::new();
+        if let Some(key) = &spec.key {
+            options = options.stdin(key.expose());
+        }
+
+        self.execute_with(&mut cmd, &options)?;
+        Ok(())
+    }
+
+    fn pool_destroy(&self, pool: &str) -> Result<()> {
+        self.execute(Command::new("zpool").arg("destroy").arg("-f").arg(pool))?;
+        Ok(())
+    }
+
+    fn pool_import(&self, pool: &str) -> Result<()> {
+        // Devices may not be visible yet right after partitioning
+        self.execute_with(
+            Command::new("zpool").arg("import").arg("-f").arg(pool),
+            &ExecOptions::new().retry(RetryPolicy::transient()),
+        )?;
+        Ok(())
+    }
+
+    fn pool_import_at(&self, pool: &str, altroot: &Path) -> Result<()> {
+        self.execute(
+            Command::new("zpool")
+                .arg("import")
+                .arg("-f")
+                .arg("-N")
+                .arg("-R")
+                .arg(altroot)
+                .arg(pool),
+        )?;
+        Ok(())
+    }
+
+    fn pool_export(&self, pool: &str) -> Result<()> {
+        self.execute(Command::new("zpool").arg("export").arg(pool))?;
+        Ok(())
+    }
+
+    fn pool_set(&self, pool: &str, property: &str, value: &str) -> Result<()> {
+        self.execute(
+            Command::new("zpool")
+                .arg("set")
+                .arg(format!("{}={}", property, value))
+                .arg(pool),
+        )?;
+        Ok(())
+    }
+
+    fn pool_get(&self, pool: &str, property: &str) -> Result<Option<String>> {
+        self.get("zpool", pool, property)
+    }
+
+    fn pool_status(&self, pool: &str) -> Result<Option<String>> {
+        let result = self.execute(Command::new("zpool").arg("status").arg(pool))?;
+        Ok(result.stdout_string())
+    }
+
+    fn dataset_create(&self, name: &str, properties: &[(String, String)]) -> Result<()> {
+        let mut cmd = Command::new("zfs");
+        cmd.arg("create");
+        for (key, value) in properties {
+            cmd.arg("-o").arg(format!("{}={}", key, value));
+        }
+        cmd.arg(name);
+
+        self.execute(&mut cmd)?;
+        Ok(())
+    }
+
+    fn volume_create(&self, name: &str, size: u64, properties: &[(String, String)]) -> Result<()> {
+        let mut cmd = Command::new("zfs");
+        cmd.arg("create").arg("-V").arg(size.to_string());
+        for (key, value) in properties {
+            cmd.arg("-o").arg(format!("{}={}", key, value));
+        }
+        cmd.arg(name);
+
+        self.execute(&mut cmd)?;
+        Ok(())
+    }
+
+    fn dataset_set(&self, name: &str, property: &str, value: &str) -> Result<()> {
+        self.execute(
+            Command::new("zfs")
+                .arg("set")
+                .arg(format!("{}={}", property, value))
+                .arg(name),
+        )?;
+        Ok(())
+    }
+
+    fn dataset_get(&self, name: &str, property: &str) -> Result<Option<String>> {
+        self.get("zfs", name, property)
+    }
+
+    fn dataset_mount(&self, name: &str) -> Result<()> {
+        self.execute(Command::new("zfs").arg("mount").arg(name))?;
+        Ok(())
+    }
+
+    fn dataset_unmount(&self, name: &str) -> Result<()> {
+        self.execute(Command::new("zfs").arg("unmount").arg(name))?;
+        Ok(())
+    }
+
+    fn dataset_load_key(&self, name: &str, passphrase: Option<&Passphrase>) -> Result<()> {
+        let mut options = ExecOptions::new();
+        if let Some(passphrase) = passphrase {
+            options = options.stdin(passphrase.expose());
+        }
+        self.execute_with(Command::new("zfs").arg("load-key").arg(name), &options)?;
+        Ok(())
+    }
+
+    fn dataset_snapshot(&self, snapshot: &str, recursive: bool) -> Result<()> {
+        let mut cmd = Command::new("zfs");
+        cmd.arg("snapshot");
+        if recursive {
+            cmd.arg("-r");
+        }
+        self.execute(cmd.arg(snapshot))?;
+        Ok(())
+    }
+
+    fn dataset_list(&self, root: &str) -> Result<Option<Vec<String>>> {
+        let result = self.execute(
+            Command::new("zfs")
+                .arg("list")
+                .arg("-H")
+                .arg("-o")
+                .arg("name")
+                .arg("-r")
+                .arg(root),
+        )?;
+
+        Ok(result
+            .stdout_string()
+            .map(|s| s.lines().map(|l| l.trim().to_string()).collect()))
+    }
+
+    fn dataset_mounts(&self, root: &str) -> Result<Option<Vec<(String, PathBuf)>>> {
+        let result = self.execute(
+            Command::new("zfs")
+                .arg("list")
+                .arg("-H")
+                .arg("-o")
+                .arg("name,mountpoint,mounted")
+                .arg("-r")
+                .arg(root),
+        )?;
+
+        Ok(result.stdout_string().map(|s| parse_mounts(&s)))
+    }
+}
+
+/// Parse `zfs list -H -o name,mountpoint,mounted` into the mounted datasets
+fn parse_mounts(output: &str) -> Vec<(String, PathBuf)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let (name, mountpoint, mounted) = (fields.next()?, fields.next()?, fields.next()?);
+            (mounted.trim() == "yes" && mountpoint.starts_with('/'))
+                .then(|| (name.to_string(), PathBuf::from(mountpoint)))
+        })
+        .collect()
+}
+
+/// Arguments to `zpool` that create the pool described by `spec`
+pub fn build_create_args(spec: &PoolSpec) -> Vec<String> {
+    let mut args = vec![
+        "create".to_string(),
+        "-f".to_string(), // Force
+        "-m".to_string(),
+        "none".to_string(), // Don't mount automatically
+    ];
+    if let Some(altroot) = &spec.altroot {
+        args.push("-R".to_string());
+        args.push(altroot.display().to_string());
+    }
+
+    for (key, value) in &spec.properties {
+        args.push("-o".to_string());
+        args.push(format!("{}={}", key, value));
+    }
+    for (key, value) in &spec.fs_properties {
+        args.push("-O".to_string());
+        args.push(format!("{}={}", key, value));
+    }
+
+    args.push(spec.name.clone());
+    for vdev in &spec.vdevs {
+        args.extend(vdev.vdev_type.clone());
+        args.extend(vdev.devices.iter().map(|d| d.display().to_string()));
+    }
+
+    // Support vdevs follow the data vdev
+    let log_type = (spec.mirror_log && spec.log_devices.len() > 1).then_some("mirror");
+    for (section, vdev_type, devices) in [
+        ("log", log_type, &spec.log_devices),
+        ("cache", None, &spec.cache_devices),
+        ("spare", None, &spec.spare_devices),
+    ] {
+        if devices.is_empty() {
+            continue;
+        }
+        args.push(section.to_string());
+        args.extend(vdev_type.map(str::to_string));
+        args.extend(devices.iter().map(|d| d.display().to_string()));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+    use crate::zfs::backend::Vdev;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_pool_create_command() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let backend = CliBackend::new(executor.clone());
+
+        backend
+            .pool_create(&PoolSpec {
+                name: "tank".to_string(),
+                vdevs: vec![Vdev {
+                    vdev_type: Some("mirror".to_string()),
+                    devices: vec![PathBuf::from("/dev/sda3"), PathBuf::from("/dev/sdb3")],
+                }],
+                properties: vec![("ashift".to_string(), "12".to_string())],
+                fs_properties: vec![("compression".to_string(), "zstd".to_string())],
+                altroot: Some(PathBuf::from("/mnt")),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(
+            executor.commands()[0].argv().join(" "),
+            "zpool create -f -m none -R /mnt -o ashift=12 -O compression=zstd tank mirror /dev/sda3 /dev/sdb3"
+        );
+    }
+
+    #[test]
+    fn test_support_vdevs_follow_data_vdev() {
+        let spec = PoolSpec {
+            name: "tank".to_string(),
+            vdevs: vec![Vdev {
+                vdev_type: Some("raidz1".to_string()),
+                devices: vec![
+                    PathBuf::from("/dev/sda3"),
+                    PathBuf::from("/dev/sdb3"),
+                    PathBuf::from("/dev/sdc3"),
+                ],
+            }],
+            log_devices: vec![PathBuf::from("/dev/nvme0n1"), PathBuf::from("/dev/nvme1n1")],
+            mirror_log: true,
+            cache_devices: vec![PathBuf::from("/dev/sdd")],
+            spare_devices: vec![PathBuf::from("/dev/sde")],
+            ..Default::default()
+        };
+        assert_eq!(
+            build_create_args(&spec)[4..].join(" "),
+            "tank raidz1 /dev/sda3 /dev/sdb3 /dev/sdc3 \
+             log mirror /dev/nvme0n1 /dev/nvme1n1 cache /dev/sdd spare /dev/sde"
+        );
+
+        let striped = PoolSpec {
+            mirror_log: false,
+            cache_devices: Vec::new(),
+            spare_devices: Vec::new(),
+            ..spec
+        };
+        assert!(build_create_args(&striped)
+            .join(" ")
+            .ends_with("/dev/sdc3 log /dev/nvme0n1 /dev/nvme1n1"));
+    }
+
+    #[test]
+    fn test_failures_reported_as_zfs_errors() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("zfs", 1, "dataset already exists");
+        let backend = CliBackend::new(executor);
+
+        match backend.dataset_create("tank/home", &[]).unwrap_err() {
+            InstallerError::ZfsError { operation, details } => {
+                assert!(operation.starts_with("zfs create"));
+                assert_eq!(details, "dataset already exists");
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_mounts() {
+        let output = "zroot\tnone\tno\n\
+                      zroot/ROOT\tnone\tno\n\
+                      zroot/ROOT/default\t/mnt\tyes\n\
+                      zroot/home\t/mnt/home\tyes\n\
+                      zroot/srv\t/mnt/srv\tno\n\
+                      zroot/swap\t-\t-\n";
+        assert_eq!(
+            parse_mounts(output),
+            vec![
+                ("zroot/ROOT/default".to_string(), PathBuf::from("/mnt")),
+                ("zroot/home".to_string(), PathBuf::from("/mnt/home")),
+            ]
+        );
+    }
+}
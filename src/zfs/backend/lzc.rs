@@ -0,0 +1,308 @@
+//! Backend using libzfs_core
+//!
+//! libzfs_core only covers dataset-level operations, so dataset creation and
+//! snapshots go through `lzc_*` calls and everything else (pool management,
+//! property changes, mounting, queries) is delegated to the CLI backend.
+//! Property values must be passed to the kernel in their native types; a
+//! create with properties this module can't encode also falls back to the CLI.
+
+use crate::config::Passphrase;
+use crate::error::{InstallerError, Result};
+use crate::zfs::backend::{CliBackend, PoolSpec, ZfsBackend};
+use libc::{c_char, c_int, c_uint};
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// Opaque nvpair list
+#[repr(C)]
+struct NvListRaw {
+    _private: [u8; 0],
+}
+
+/// `NV_UNIQUE_NAME` from sys/nvpair.h
+const NV_UNIQUE_NAME: c_uint = 0x1;
+
+/// `LZC_DATSET_TYPE_ZFS` from libzfs_core.h
+const LZC_DATSET_TYPE_ZFS: c_int = 2;
+
+#[link(name = "nvpair")]
+extern "C" {
+    fn nvlist_alloc(nvlp: *mut *mut NvListRaw, nvflag: c_uint, kmflag: c_int) -> c_int;
+    fn nvlist_free(nvl: *mut NvListRaw);
+    fn nvlist_add_boolean(nvl: *mut NvListRaw, name: *const c_char) -> c_int;
+    fn nvlist_add_string(nvl: *mut NvListRaw, name: *const c_char, val: *const c_char) -> c_int;
+    fn nvlist_add_uint64(nvl: *mut NvListRaw, name: *const c_char, val: u64) -> c_int;
+}
+
+#[link(name = "zfs_core")]
+extern "C" {
+    fn libzfs_core_init() -> c_int;
+    fn libzfs_core_fini();
+    fn lzc_create(
+        fsname: *const c_char,
+        dataset_type: c_int,
+        props: *mut NvListRaw,
+        wkeydata: *mut u8,
+        wkeylen: c_uint,
+    ) -> c_int;
+    fn lzc_snapshot(
+        snaps: *mut NvListRaw,
+        props: *mut NvListRaw,
+        errlist: *mut *mut NvListRaw,
+    ) -> c_int;
+}
+
+/// Owned nvlist, freed on drop
+struct NvList(*mut NvListRaw);
+
+impl NvList {
+    fn new() -> Result<Self> {
+        let mut raw = ptr::null_mut();
+        // SAFETY: nvlist_alloc initialises `raw` on success
+        check("nvlist_alloc", unsafe {
+            nvlist_alloc(&mut raw, NV_UNIQUE_NAME, 0)
+        })?;
+        Ok(Self(raw))
+    }
+
+    fn add_boolean(&mut self, name: &str) -> Result<()> {
+        let name = cstring(name)?;
+        // SAFETY: self.0 is a live nvlist and name is NUL-terminated
+        check("nvlist_add_boolean", unsafe {
+            nvlist_add_boolean(self.0, name.as_ptr())
+        })
+    }
+
+    fn add(&mut self, name: &str, value: &NativeValue) -> Result<()> {
+        let name = cstring(name)?;
+        // SAFETY: self.0 is a live nvlist; nvpair copies name and value
+        let rc = match value {
+            NativeValue::String(s) => {
+                let s = cstring(s)?;
+                unsafe { nvlist_add_string(self.0, name.as_ptr(), s.as_ptr()) }
+            }
+            NativeValue::Uint64(n) => unsafe { nvlist_add_uint64(self.0, name.as_ptr(), *n) },
+        };
+        check("nvlist_add", rc)
+    }
+}
+
+impl Drop for NvList {
+    fn drop(&mut self) {
+        // SAFETY: self.0 came from nvlist_alloc and is freed exactly once
+        unsafe { nvlist_free(self.0) }
+    }
+}
+
+/// A property value in the type the kernel expects
+#[derive(Debug, PartialEq, Eq)]
+enum NativeValue {
+    String(String),
+    Uint64(u64),
+}
+
+/// Encode a property for lzc_create, `None` if it needs libzfs' conversion
+fn encode_property(key: &str, value: &str) -> Option<NativeValue> {
+    // User properties are always strings
+    if key.contains(':') {
+        return Some(NativeValue::String(value.to_string()));
+    }
+
+    match key {
+        "mountpoint" => Some(NativeValue::String(value.to_string())),
+        "canmount" => match value {
+            "off" => Some(NativeValue::Uint64(0)),
+            "on" => Some(NativeValue::Uint64(1)),
+            "noauto" => Some(NativeValue::Uint64(2)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn cstring(s: &str) -> Result<CString> {
+    CString::new(s).map_err(|e| InstallerError::ParseError(e.to_string()))
+}
+
+/// Map an lzc/nvpair return code to an error
+fn check(operation: &str, rc: c_int) -> Result<()> {
+    if rc == 0 {
+        return Ok(());
+    }
+
+    Err(InstallerError::ZfsError {
+        operation: operation.to_string(),
+        details: std::io::Error::from_raw_os_error(rc).to_string(),
+    })
+}
+
+/// ZFS backend using libzfs_core, with the CLI for everything it can't do
+pub struct LzcBackend {
+    cli: CliBackend,
+}
+
+impl LzcBackend {
+    /// Initialise libzfs_core (fails without a usable /dev/zfs)
+    pub fn open(cli: CliBackend) -> Result<Self> {
+        // SAFETY: libzfs_core_init is reference counted and thread safe
+        check("libzfs_core_init", unsafe { libzfs_core_init() })?;
+        Ok(Self { cli })
+    }
+}
+
+impl Drop for LzcBackend {
+    fn drop(&mut self) {
+        // SAFETY: balances the successful libzfs_core_init in open()
+        unsafe { libzfs_core_fini() }
+    }
+}
+
+impl ZfsBackend for LzcBackend {
+    fn name(&self) -> &'static str {
+        "libzfs_core"
+    }
+
+    fn pool_create(&self, spec: &PoolSpec) -> Result<()> {
+        self.cli.pool_create(spec)
+    }
+
+    fn pool_destroy(&self, pool: &str) -> Result<()> {
+        self.cli.pool_destroy(pool)
+    }
+
+    fn pool_import(&self, pool: &str) -> Result<()> {
+        self.cli.pool_import(pool)
+    }
+
+    fn pool_import_at(&self, pool: &str, altroot: &Path) -> Result<()> {
+        self.cli.pool_import_at(pool, altroot)
+    }
+
+    fn pool_export(&self, pool: &str) -> Result<()> {
+        self.cli.pool_export(pool)
+    }
+
+    fn pool_set(&self, pool: &str, property: &str, value: &str) -> Result<()> {
+        self.cli.pool_set(pool, property, value)
+    }
+
+    fn pool_get(&self, pool: &str, property: &str) -> Result<Option<String>> {
+        self.cli.pool_get(pool, property)
+    }
+
+    fn pool_status(&self, pool: &str) -> Result<Option<String>> {
+        self.cli.pool_status(pool)
+    }
+
+    fn dataset_create(&self, name: &str, properties: &[(String, String)]) -> Result<()> {
+        let encoded: Option<Vec<(&str, NativeValue)>> = properties
+            .iter()
+            .map(|(k, v)| encode_property(k, v).map(|native| (k.as_str(), native)))
+            .collect();
+        let Some(encoded) = encoded else {
+            log::debug!("Creating {} via zfs create (unencodable properties)", name);
+            return self.cli.dataset_create(name, properties);
+        };
+
+        let mut props = NvList::new()?;
+        for (key, value) in &encoded {
+            props.add(key, value)?;
+        }
+
+        let fsname = cstring(name)?;
+        // SAFETY: fsname is NUL-terminated, props is a live nvlist and no
+        // wrapping key is passed
+        check(&format!("lzc_create {}", name), unsafe {
+            lzc_create(
+                fsname.as_ptr(),
+                LZC_DATSET_TYPE_ZFS,
+                props.0,
+                ptr::null_mut(),
+                0,
+            )
+        })
+    }
+
+    fn volume_create(&self, name: &str, size: u64, properties: &[(String, String)]) -> Result<()> {
+        // Volumes are rare enough that the CLI's volsize handling is worth reusing
+        self.cli.volume_create(name, size, properties)
+    }
+
+    fn dataset_set(&self, name: &str, property: &str, value: &str) -> Result<()> {
+        // libzfs_core has no property-setting call
+        self.cli.dataset_set(name, property, value)
+    }
+
+    fn dataset_get(&self, name: &str, property: &str) -> Result<Option<String>> {
+        self.cli.dataset_get(name, property)
+    }
+
+    fn dataset_mount(&self, name: &str) -> Result<()> {
+        self.cli.dataset_mount(name)
+    }
+
+    fn dataset_unmount(&self, name: &str) -> Result<()> {
+        self.cli.dataset_unmount(name)
+    }
+
+    fn dataset_load_key(&self, name: &str, passphrase: Option<&Passphrase>) -> Result<()> {
+        self.cli.dataset_load_key(name, passphrase)
+    }
+
+    fn dataset_snapshot(&self, snapshot: &str, recursive: bool) -> Result<()> {
+        let mut snaps = NvList::new()?;
+        match snapshot.split_once('@') {
+            // lzc_snapshot takes every snapshot of a recursive one in a
+            // single atomic call
+            Some((root, name)) if recursive => {
+                let datasets = self
+                    .cli
+                    .dataset_list(root)?
+                    .unwrap_or_else(|| vec![root.to_string()]);
+                for dataset in datasets {
+                    snaps.add_boolean(&format!("{}@{}", dataset, name))?;
+                }
+            }
+            _ => snaps.add_boolean(snapshot)?,
+        }
+
+        let mut errlist = ptr::null_mut();
+        // SAFETY: snaps is a live nvlist; errlist is freed below if set
+        let rc = unsafe { lzc_snapshot(snaps.0, ptr::null_mut(), &mut errlist) };
+        if !errlist.is_null() {
+            drop(NvList(errlist));
+        }
+        check(&format!("lzc_snapshot {}", snapshot), rc)
+    }
+
+    fn dataset_list(&self, root: &str) -> Result<Option<Vec<String>>> {
+        self.cli.dataset_list(root)
+    }
+
+    fn dataset_mounts(&self, root: &str) -> Result<Option<Vec<(String, PathBuf)>>> {
+        self.cli.dataset_mounts(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_property() {
+        assert_eq!(
+            encode_property("mountpoint", "/home"),
+            Some(NativeValue::String("/home".to_string()))
+        );
+        assert_eq!(
+            encode_property("canmount", "noauto"),
+            Some(NativeValue::Uint64(2))
+        );
+        assert_eq!(
+            encode_property("com.sun:auto-snapshot", "false"),
+            Some(NativeValue::String("false".to_string()))
+        );
+        assert_eq!(encode_property("compression", "zstd"), None);
+    }
+}
@@ -0,0 +1,293 @@
+//! Pluggable ZFS backends
+//!
+//! [`ZfsPool`](crate::zfs::ZfsPool) and [`DatasetManager`](crate::zfs::DatasetManager)
+//! talk to ZFS through a [`ZfsBackend`]. The default [`CliBackend`] shells out
+//! to `zpool`/`zfs`; with the `libzfs` feature, [`LzcBackend`] calls
+//! libzfs_core directly where it can and falls back to the CLI elsewhere.
+
+pub mod cli;
+#[cfg(feature = "libzfs")]
+pub mod lzc;
+
+pub use cli::CliBackend;
+#[cfg(feature = "libzfs")]
+pub use lzc::LzcBackend;
+
+use crate::config::Passphrase;
+use crate::error::Result;
+use crate::exec::CommandExecutor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// One top-level vdev
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Vdev {
+    /// vdev type ("mirror", "raidz1", ...), `None` for single or striped devices
+    pub vdev_type: Option<String>,
+    /// Devices making up the vdev
+    pub devices: Vec<PathBuf>,
+}
+
+/// Everything needed to create a pool
+#[derive(Debug, Clone, Default)]
+pub struct PoolSpec {
+    /// Pool name
+    pub name: String,
+    /// Top-level vdevs, striped together
+    pub vdevs: Vec<Vdev>,
+    /// Separate intent log devices
+    pub log_devices: Vec<PathBuf>,
+    /// Mirror the log devices instead of striping them
+    pub mirror_log: bool,
+    /// L2ARC cache devices
+    pub cache_devices: Vec<PathBuf>,
+    /// Hot spares
+    pub spare_devices: Vec<PathBuf>,
+    /// Pool properties (`zpool create -o`)
+    pub properties: Vec<(String, String)>,
+    /// Root filesystem properties (`zpool create -O`)
+    pub fs_properties: Vec<(String, String)>,
+    /// Alternate root the pool is imported under (`zpool create -R`)
+    pub altroot: Option<PathBuf>,
+    /// Passphrase for `keylocation=prompt` encryption, fed on stdin
+    pub key: Option<Passphrase>,
+}
+
+/// ZFS operations used by the installer
+///
+/// Names are full ZFS names (`pool/dataset`, `pool/dataset@snapshot`). Queries
+/// return `None` when they weren't run, e.g. in dry-run mode.
+pub trait ZfsBackend: Send + Sync {
+    /// Short identifier for log output
+    fn name(&self) -> &'static str;
+
+    /// Create a pool
+    fn pool_create(&self, spec: &PoolSpec) -> Result<()>;
+
+    /// Destroy a pool
+    fn pool_destroy(&self, pool: &str) -> Result<()>;
+
+    /// Import a pool by name
+    fn pool_import(&self, pool: &str) -> Result<()>;
+
+    /// Import a pool by name under `altroot`, without mounting its datasets
+    fn pool_import_at(&self, pool: &str, altroot: &Path) -> Result<()>;
+
+    /// Export a pool
+    fn pool_export(&self, pool: &str) -> Result<()>;
+
+    /// Set a pool property
+    fn pool_set(&self, pool: &str, property: &str, value: &str) -> Result<()>;
+
+    /// Read a pool property
+    fn pool_get(&self, pool: &str, property: &str) -> Result<Option<String>>;
+
+    /// Human-readable pool status
+    fn pool_status(&self, pool: &str) -> Result<Option<String>>;
+
+    /// Create a filesystem dataset with the given properties
+    fn dataset_create(&self, name: &str, properties: &[(String, String)]) -> Result<()>;
+
+    /// Create a `size`-byte volume (zvol) with the given properties
+    fn volume_create(&self, name: &str, size: u64, properties: &[(String, String)]) -> Result<()>;
+
+    /// Set a dataset property
+    fn dataset_set(&self, name: &str, property: &str, value: &str) -> Result<()>;
+
+    /// Read a dataset property
+    fn dataset_get(&self, name: &str, property: &str) -> Result<Option<String>>;
+
+    /// Mount a dataset
+    fn dataset_mount(&self, name: &str) -> Result<()>;
+
+    /// Unmount a dataset
+    fn dataset_unmount(&self, name: &str) -> Result<()>;
+
+    /// Load the encryption key of a dataset, feeding `passphrase` on stdin
+    /// for `keylocation=prompt`; other key locations are read by ZFS itself
+    fn dataset_load_key(&self, name: &str, passphrase: Option<&Passphrase>) -> Result<()>;
+
+    /// Create a snapshot (`pool/dataset@snapshot`), of every descendant too
+    /// if `recursive`
+    ///
+    /// A recursive snapshot is atomic, so all the datasets share one
+    /// consistent point in time.
+    fn dataset_snapshot(&self, snapshot: &str, recursive: bool) -> Result<()>;
+
+    /// Names of `root` and every dataset below it
+    fn dataset_list(&self, root: &str) -> Result<Option<Vec<String>>>;
+
+    /// Datasets at or below `root` that are mounted, with their mountpoints
+    fn dataset_mounts(&self, root: &str) -> Result<Option<Vec<(String, PathBuf)>>>;
+}
+
+/// Pick the best available backend
+///
+/// libzfs_core is only used for real runs with the `libzfs` feature enabled
+/// and a usable `/dev/zfs`; everything else goes through the CLI so dry-run
+/// plans stay visible to the executor.
+pub fn select(executor: Arc<dyn CommandExecutor>, dry_run: bool) -> Arc<dyn ZfsBackend> {
+    let cli = CliBackend::new(executor);
+
+    #[cfg(feature = "libzfs")]
+    if !dry_run {
+        match LzcBackend::open(cli.clone()) {
+            Ok(backend) => return Arc::new(backend),
+            Err(e) => log::warn!("libzfs_core unavailable ({}), using zfs/zpool commands", e),
+        }
+    }
+    #[cfg(not(feature = "libzfs"))]
+    let _ = dry_run;
+
+    Arc::new(cli)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::SystemExecutor;
+    use std::process::Command;
+
+    #[test]
+    fn test_dry_run_selects_cli() {
+        let backend = select(Arc::new(SystemExecutor::new(true)), true);
+        assert_eq!(backend.name(), "cli");
+    }
+
+    /// Scratch pool on a sparse file, destroyed on drop
+    struct ScratchPool {
+        name: String,
+        _dir: tempfile::TempDir,
+        vdev: PathBuf,
+    }
+
+    impl ScratchPool {
+        /// `None` unless running as root on a host with ZFS
+        fn new(tag: &str) -> Option<Self> {
+            if !crate::system::is_root() || !crate::zfs::check_zfs_available().unwrap_or(false) {
+                eprintln!("skipping ZFS conformance test: needs root and ZFS");
+                return None;
+            }
+
+            let dir = tempfile::tempdir().ok()?;
+            let vdev = dir.path().join("vdev.img");
+            std::fs::File::create(&vdev)
+                .and_then(|f| f.set_len(256 * 1024 * 1024))
+                .ok()?;
+
+            Some(Self {
+                name: format!("zbmconf{}{}", tag, std::process::id()),
+                _dir: dir,
+                vdev,
+            })
+        }
+    }
+
+    impl Drop for ScratchPool {
+        fn drop(&mut self) {
+            let _ = Command::new("zpool")
+                .arg("destroy")
+                .arg("-f")
+                .arg(&self.name)
+                .status();
+        }
+    }
+
+    /// Behaviour every backend must share
+    fn conformance(backend: &dyn ZfsBackend, scratch: &ScratchPool) {
+        let pool = scratch.name.as_str();
+        let vdev_dir = scratch.vdev.parent().unwrap().display().to_string();
+
+        backend
+            .pool_create(&PoolSpec {
+                name: pool.to_string(),
+                vdevs: vec![Vdev {
+                    vdev_type: None,
+                    devices: vec![scratch.vdev.clone()],
+                }],
+                properties: vec![("ashift".to_string(), "12".to_string())],
+                fs_properties: vec![("compression".to_string(), "lz4".to_string())],
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            backend.pool_get(pool, "ashift").unwrap().as_deref(),
+            Some("12")
+        );
+        assert!(backend
+            .pool_status(pool)
+            .unwrap()
+            .unwrap()
+            .contains("ONLINE"));
+
+        let data = format!("{}/data", pool);
+        backend
+            .dataset_create(
+                &data,
+                &[
+                    ("canmount".to_string(), "noauto".to_string()),
+                    ("mountpoint".to_string(), "none".to_string()),
+                    ("org.zfsbootmenu:test".to_string(), "yes".to_string()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            backend.dataset_get(&data, "canmount").unwrap().as_deref(),
+            Some("noauto")
+        );
+        assert_eq!(
+            backend
+                .dataset_get(&data, "org.zfsbootmenu:test")
+                .unwrap()
+                .as_deref(),
+            Some("yes")
+        );
+
+        backend.dataset_set(&data, "compression", "off").unwrap();
+        assert_eq!(
+            backend
+                .dataset_get(&data, "compression")
+                .unwrap()
+                .as_deref(),
+            Some("off")
+        );
+
+        backend
+            .dataset_snapshot(&format!("{}@initial", data), false)
+            .unwrap();
+        let names = backend.dataset_list(pool).unwrap().unwrap();
+        assert_eq!(names, vec![pool.to_string(), data.clone()]);
+
+        backend.pool_set(pool, "bootfs", &data).unwrap();
+        assert_eq!(
+            backend.pool_get(pool, "bootfs").unwrap().as_deref(),
+            Some(data.as_str())
+        );
+
+        backend.pool_export(pool).unwrap();
+        let import = Command::new("zpool")
+            .args(["import", "-d", &vdev_dir, pool])
+            .status()
+            .unwrap();
+        assert!(import.success());
+        backend.pool_destroy(pool).unwrap();
+    }
+
+    #[test]
+    fn test_cli_backend_conformance() {
+        if let Some(scratch) = ScratchPool::new("cli") {
+            let backend = CliBackend::new(Arc::new(SystemExecutor::new(false)));
+            conformance(&backend, &scratch);
+        }
+    }
+
+    #[cfg(feature = "libzfs")]
+    #[test]
+    fn test_lzc_backend_conformance() {
+        if let Some(scratch) = ScratchPool::new("lzc") {
+            let cli = CliBackend::new(Arc::new(SystemExecutor::new(false)));
+            let backend = LzcBackend::open(cli).unwrap();
+            conformance(&backend, &scratch);
+        }
+    }
+}
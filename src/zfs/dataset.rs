@@ -1,7 +1,46 @@
 //! ZFS dataset creation and management
 
 use crate::error::{InstallerError, Result};
-use std::process::Command;
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::zfs::backend::{self, CliBackend, ZfsBackend};
+use crate::zfs::layout::{CanMount, DatasetLayout};
+use std::cmp::Reverse;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Name of the swap zvol, relative to the pool
+pub const SWAP_ZVOL: &str = "swap";
+
+/// Snapshot taken of a new install so there is always a pristine state to roll back to
+pub const DEFAULT_INITIAL_SNAPSHOT: &str = "initial";
+
+/// Properties of the swap zvol
+///
+/// Small blocks match the page size, writes go straight to the pool so
+/// swapping out can't deadlock waiting on the ZIL, and only metadata is
+/// cached so swapped pages don't compete with the ARC.
+const SWAP_ZVOL_PROPERTIES: &[(&str, &str)] = &[
+    ("volblocksize", "4096"),
+    ("compression", "zstd-fast"),
+    ("logbias", "throughput"),
+    ("sync", "always"),
+    ("primarycache", "metadata"),
+    ("com.sun:auto-snapshot", "false"),
+];
+
+/// Check a snapshot name is a single component after the `@`
+pub fn validate_snapshot_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(InstallerError::validation("Snapshot name cannot be empty"));
+    }
+    if name.contains(['@', '/']) {
+        return Err(InstallerError::validation(format!(
+            "Snapshot name '{}' must not contain '@' or '/'",
+            name
+        )));
+    }
+    Ok(())
+}
 
 /// Dataset property
 #[derive(Debug, Clone)]
@@ -13,177 +52,252 @@ pub struct DatasetProperty {
 /// ZFS dataset manager
 pub struct DatasetManager {
     pool_name: String,
+    /// Whether the pool root is encrypted
+    encrypted: bool,
+    /// Datasets `create_zbm_datasets` creates
+    layout: DatasetLayout,
+    #[allow(dead_code)] // Enforced by the executor
     dry_run: bool,
+    backend: Arc<dyn ZfsBackend>,
 }
 
 impl DatasetManager {
     /// Create a new dataset manager
     pub fn new(pool_name: String, dry_run: bool) -> Self {
-        Self { pool_name, dry_run }
+        Self {
+            pool_name,
+            encrypted: false,
+            layout: DatasetLayout::default(),
+            dry_run,
+            backend: backend::select(Arc::new(SystemExecutor::new(dry_run)), dry_run),
+        }
     }
 
-    /// Execute a command
-    fn execute(&self, cmd: &mut Command) -> Result<std::process::Output> {
-        let cmd_str = format!("{:?}", cmd);
-
-        if self.dry_run {
-            log::info!("[DRY RUN] Would execute: {}", cmd_str);
-            return Ok(std::process::Output {
-                status: std::process::ExitStatus::default(),
-                stdout: Vec::new(),
-                stderr: Vec::new(),
-            });
-        }
+    /// Use a custom command executor (implies the CLI backend)
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.backend = Arc::new(CliBackend::new(executor));
+        self
+    }
 
-        log::debug!("Executing: {}", cmd_str);
-        let output = cmd.output()?;
+    /// Use a specific ZFS backend
+    pub fn with_backend(mut self, backend: Arc<dyn ZfsBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(InstallerError::ZfsError {
-                operation: cmd_str,
-                details: stderr.to_string(),
-            });
-        }
+    /// Create `layout` instead of the standard ZBM hierarchy
+    pub fn with_layout(mut self, layout: DatasetLayout) -> Self {
+        self.layout = layout;
+        self
+    }
 
-        Ok(output)
+    /// Expect every dataset to inherit encryption from the pool root
+    pub fn with_encryption(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
     }
 
     /// Create a dataset
     pub fn create_dataset(&self, name: &str, properties: &[DatasetProperty]) -> Result<()> {
         log::info!("Creating dataset: {}/{}", self.pool_name, name);
 
-        let mut cmd = Command::new("zfs");
-        cmd.arg("create");
+        let properties: Vec<(String, String)> = properties
+            .iter()
+            .map(|p| (p.key.clone(), p.value.clone()))
+            .collect();
+        self.backend
+            .dataset_create(&format!("{}/{}", self.pool_name, name), &properties)
+    }
 
-        // Add properties
-        for prop in properties {
-            cmd.arg("-o").arg(format!("{}={}", prop.key, prop.value));
-        }
+    /// Create the swap zvol and return its device path
+    ///
+    /// The path (`/dev/zvol/<pool>/swap`) is what mkswap and fstab use.
+    pub fn create_swap_zvol(&self, size: u64) -> Result<PathBuf> {
+        let name = format!("{}/{}", self.pool_name, SWAP_ZVOL);
+        log::info!("Creating swap zvol: {} ({} bytes)", name, size);
 
-        // Dataset name
-        cmd.arg(format!("{}/{}", self.pool_name, name));
+        let properties: Vec<(String, String)> = SWAP_ZVOL_PROPERTIES
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        self.backend.volume_create(&name, size, &properties)?;
 
-        self.execute(&mut cmd)?;
-        Ok(())
+        Ok(self.swap_zvol_path())
     }
 
-    /// Create the standard ZBM dataset hierarchy
+    /// Device path of the swap zvol
+    pub fn swap_zvol_path(&self) -> PathBuf {
+        PathBuf::from(format!("/dev/zvol/{}/{}", self.pool_name, SWAP_ZVOL))
+    }
+
+    /// Create the datasets of the layout, parents first
     pub fn create_zbm_datasets(&self) -> Result<()> {
+        self.create_missing_zbm_datasets(&[])
+    }
+
+    /// Create the datasets of the layout not among `existing` (full names), parents first
+    ///
+    /// Datasets that exist already are kept as they are.
+    pub fn create_missing_zbm_datasets(&self, existing: &[String]) -> Result<()> {
         log::info!("Creating ZBM dataset hierarchy");
 
-        // Define dataset structure
-        // Format: (dataset_path, [(property, value)])
-        let datasets = vec![
-            // Boot environment container
-            ("ROOT", vec![("canmount", "off"), ("mountpoint", "none")]),
-            // Default boot environment
-            (
-                "ROOT/default",
-                vec![("canmount", "noauto"), ("mountpoint", "/")],
-            ),
-            // Home directories
-            ("home", vec![("mountpoint", "/home")]),
-            // Root user home
-            ("home/root", vec![("mountpoint", "/root")]),
-            // Var container
-            ("var", vec![("canmount", "off"), ("mountpoint", "none")]),
-            // System logs
-            (
-                "var/log",
-                vec![
-                    ("mountpoint", "/var/log"),
-                    ("acltype", "posixacl"),
-                    ("xattr", "sa"),
-                ],
-            ),
-            // Cache
-            (
-                "var/cache",
-                vec![
-                    ("mountpoint", "/var/cache"),
-                    ("com.sun:auto-snapshot", "false"),
-                ],
-            ),
-            // Temporary files
-            (
-                "var/tmp",
-                vec![
-                    ("mountpoint", "/var/tmp"),
-                    ("com.sun:auto-snapshot", "false"),
-                ],
-            ),
-            // Optional packages
-            ("opt", vec![("mountpoint", "/opt")]),
-            // Service data
-            ("srv", vec![("mountpoint", "/srv")]),
-            // Local software container
-            ("usr", vec![("canmount", "off"), ("mountpoint", "none")]),
-            // Locally installed software
-            ("usr/local", vec![("mountpoint", "/usr/local")]),
-        ];
-
-        // Create each dataset
-        for (name, props) in datasets {
-            let properties: Vec<DatasetProperty> = props
-                .iter()
-                .map(|(k, v)| DatasetProperty {
-                    key: k.to_string(),
-                    value: v.to_string(),
-                })
-                .collect();
-
-            self.create_dataset(name, &properties)?;
+        for dataset in self.layout.ordered() {
+            let name = format!("{}/{}", self.pool_name, dataset.name);
+            if existing.contains(&name) {
+                log::info!("Adopting existing dataset: {}", name);
+                continue;
+            }
+            log::info!("Creating dataset: {}", name);
+            self.backend
+                .dataset_create(&name, &dataset.zfs_properties())?;
+        }
+
+        if self.encrypted {
+            self.verify_encryption_inherited()?;
         }
 
         log::info!("ZBM dataset hierarchy created successfully");
         Ok(())
     }
 
-    /// Create a snapshot
-    pub fn snapshot(&self, dataset: &str, snapshot_name: &str) -> Result<()> {
+    /// Check every ZBM dataset shares the pool root as its encryption root
+    ///
+    /// The layout sets no encryption properties of its own, so each dataset
+    /// inherits the root's key and ZFSBootMenu only has to unlock it once.
+    pub fn verify_encryption_inherited(&self) -> Result<()> {
+        for name in self.zbm_dataset_names() {
+            // None in dry-run mode: nothing was created to check
+            let Some(root) = self.backend.dataset_get(&name, "encryptionroot")? else {
+                continue;
+            };
+
+            if root != self.pool_name {
+                return Err(InstallerError::zfs(
+                    format!("verify encryption of {}", name),
+                    format!(
+                        "encryption root is '{}', expected the pool root '{}'",
+                        root, self.pool_name
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Full names of the datasets `create_zbm_datasets` creates
+    pub fn zbm_dataset_names(&self) -> Vec<String> {
+        self.layout
+            .ordered()
+            .into_iter()
+            .map(|dataset| format!("{}/{}", self.pool_name, dataset.name))
+            .collect()
+    }
+
+    /// Mount points of the datasets `create_zbm_datasets` creates
+    pub fn zbm_mountpoints(&self) -> Vec<PathBuf> {
+        self.layout.mountpoints()
+    }
+
+    /// List the datasets in the pool (`None` in dry-run mode)
+    pub fn list(&self) -> Result<Option<Vec<String>>> {
+        self.backend.dataset_list(&self.pool_name)
+    }
+
+    /// Create a snapshot of a dataset, or of the pool root if `dataset` is empty
+    ///
+    /// A recursive snapshot covers every descendant, including datasets with
+    /// `com.sun:auto-snapshot=false`: that property only tells external
+    /// snapshot tools to skip a dataset, `zfs snapshot` itself ignores it.
+    pub fn snapshot(&self, dataset: &str, snapshot_name: &str, recursive: bool) -> Result<()> {
+        validate_snapshot_name(snapshot_name)?;
+
+        let name = if dataset.is_empty() {
+            self.pool_name.clone()
+        } else {
+            format!("{}/{}", self.pool_name, dataset)
+        };
         log::info!(
-            "Creating snapshot: {}/{}@{}",
-            self.pool_name,
-            dataset,
+            "Creating {}snapshot: {}@{}",
+            if recursive { "recursive " } else { "" },
+            name,
             snapshot_name
         );
 
-        self.execute(
-            Command::new("zfs")
-                .arg("snapshot")
-                .arg(format!("{}/{}@{}", self.pool_name, dataset, snapshot_name)),
-        )?;
-
-        Ok(())
+        self.backend
+            .dataset_snapshot(&format!("{}@{}", name, snapshot_name), recursive)
     }
 
     /// Mount a dataset
     pub fn mount(&self, dataset: &str) -> Result<()> {
         log::info!("Mounting dataset: {}/{}", self.pool_name, dataset);
 
-        self.execute(
-            Command::new("zfs")
-                .arg("mount")
-                .arg(format!("{}/{}", self.pool_name, dataset)),
-        )?;
-
-        Ok(())
+        self.backend
+            .dataset_mount(&format!("{}/{}", self.pool_name, dataset))
     }
 
     /// Unmount a dataset
     pub fn unmount(&self, dataset: &str) -> Result<()> {
         log::info!("Unmounting dataset: {}/{}", self.pool_name, dataset);
 
-        self.execute(
-            Command::new("zfs")
-                .arg("unmount")
-                .arg(format!("{}/{}", self.pool_name, dataset)),
-        )?;
+        self.backend
+            .dataset_unmount(&format!("{}/{}", self.pool_name, dataset))
+    }
 
+    /// Unmount every mounted dataset in the pool, deepest mountpoint first
+    ///
+    /// A dataset that won't unmount is almost always held open by a process,
+    /// so the error names the mountpoint to check with `fuser`.
+    pub fn unmount_all(&self) -> Result<()> {
+        // None in dry-run mode: nothing was mounted
+        let Some(mut mounts) = self.backend.dataset_mounts(&self.pool_name)? else {
+            return Ok(());
+        };
+        mounts.sort_by_key(|(_, mountpoint)| Reverse(mountpoint.components().count()));
+
+        for (name, mountpoint) in mounts {
+            log::info!("Unmounting {} from {}", name, mountpoint.display());
+            self.backend.dataset_unmount(&name).map_err(|e| {
+                InstallerError::zfs(
+                    format!("unmount {}", name),
+                    format!(
+                        "{} is busy ({}); see what is using it with `fuser -vm {}`",
+                        mountpoint.display(),
+                        e,
+                        mountpoint.display()
+                    ),
+                )
+            })?;
+        }
         Ok(())
     }
 
+    /// Mount the boot environment, then every dataset that mounts
+    /// automatically, parents before the datasets nested in them
+    pub fn mount_all(&self) -> Result<()> {
+        let root = self.layout.root_name();
+        self.mount(root)?;
+        for (dataset, _) in self.layout.mount_order() {
+            if dataset.name != root && dataset.canmount != Some(CanMount::Noauto) {
+                self.mount(&dataset.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set a property on the pool's root dataset
+    pub fn set_root_property(&self, property: &DatasetProperty) -> Result<()> {
+        log::info!(
+            "Setting property {}={} on {}",
+            property.key,
+            property.value,
+            self.pool_name
+        );
+
+        self.backend
+            .dataset_set(&self.pool_name, &property.key, &property.value)
+    }
+
     /// Set a property on a dataset
     pub fn set_property(&self, dataset: &str, property: &DatasetProperty) -> Result<()> {
         log::info!(
@@ -194,14 +308,11 @@ impl DatasetManager {
             dataset
         );
 
-        self.execute(
-            Command::new("zfs")
-                .arg("set")
-                .arg(format!("{}={}", property.key, property.value))
-                .arg(format!("{}/{}", self.pool_name, dataset)),
-        )?;
-
-        Ok(())
+        self.backend.dataset_set(
+            &format!("{}/{}", self.pool_name, dataset),
+            &property.key,
+            &property.value,
+        )
     }
 }
 
@@ -226,4 +337,141 @@ mod tests {
         assert_eq!(prop.key, "mountpoint");
         assert_eq!(prop.value, "/mnt");
     }
+
+    #[test]
+    fn test_snapshot() {
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        let manager =
+            DatasetManager::new("zroot".to_string(), false).with_executor(executor.clone());
+
+        manager.snapshot("ROOT/default", "initial", false).unwrap();
+        // var/cache and var/tmp set com.sun:auto-snapshot=false, but -r still
+        // takes them
+        manager.snapshot("", "install-20240501", true).unwrap();
+        let commands: Vec<String> = executor
+            .commands()
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                "zfs snapshot zroot/ROOT/default@initial",
+                "zfs snapshot -r zroot@install-20240501",
+            ]
+        );
+
+        assert!(manager.snapshot("ROOT/default", "", false).is_err());
+        assert!(manager.snapshot("ROOT/default", "a@b", false).is_err());
+        assert!(manager.snapshot("ROOT/default", "a/b", false).is_err());
+        assert_eq!(executor.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_unmount_all_deepest_first() {
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        executor.respond_program(
+            "zfs",
+            "zroot\tnone\tno\n\
+             zroot/ROOT/default\t/mnt\tyes\n\
+             zroot/usr/local\t/mnt/usr/local\tyes\n\
+             zroot/var/log\t/mnt/var/log\tyes\n\
+             zroot/home\t/mnt/home\tyes\n",
+        );
+        let manager =
+            DatasetManager::new("zroot".to_string(), false).with_executor(executor.clone());
+
+        manager.unmount_all().unwrap();
+        let unmounts: Vec<String> = executor.commands()[1..]
+            .iter()
+            .map(|c| c.argv().join(" "))
+            .collect();
+        assert_eq!(
+            unmounts,
+            vec![
+                "zfs unmount zroot/usr/local",
+                "zfs unmount zroot/var/log",
+                "zfs unmount zroot/home",
+                "zfs unmount zroot/ROOT/default",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_swap_zvol() {
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        let manager =
+            DatasetManager::new("zroot".to_string(), false).with_executor(executor.clone());
+
+        let path = manager.create_swap_zvol(8 * 1024 * 1024 * 1024).unwrap();
+        assert_eq!(path, PathBuf::from("/dev/zvol/zroot/swap"));
+        assert_eq!(
+            executor.commands()[0].argv(),
+            vec![
+                "zfs",
+                "create",
+                "-V",
+                "8589934592",
+                "-o",
+                "volblocksize=4096",
+                "-o",
+                "compression=zstd-fast",
+                "-o",
+                "logbias=throughput",
+                "-o",
+                "sync=always",
+                "-o",
+                "primarycache=metadata",
+                "-o",
+                "com.sun:auto-snapshot=false",
+                "zroot/swap"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encryption_inheritance_checked() {
+        // Dry run: nothing to query, so nothing to reject
+        let manager = DatasetManager::new("zroot".to_string(), true)
+            .with_executor(Arc::new(SystemExecutor::new(true)))
+            .with_encryption(true);
+        assert!(manager.create_zbm_datasets().is_ok());
+
+        // A dataset reporting a different encryption root is rejected
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        let manager = DatasetManager::new("zroot".to_string(), false)
+            .with_executor(executor.clone())
+            .with_encryption(true);
+        let err = manager.create_zbm_datasets().unwrap_err();
+        assert!(err.to_string().contains("expected the pool root 'zroot'"));
+
+        let created = executor
+            .commands()
+            .iter()
+            .filter(|c| c.args.first().map(String::as_str) == Some("create"))
+            .count();
+        assert_eq!(created, DatasetLayout::default().datasets().len());
+        assert!(executor
+            .commands()
+            .iter()
+            .all(|c| !c.args.iter().any(|a| a.starts_with("encryption="))));
+    }
+
+    #[test]
+    fn test_existing_datasets_are_kept() {
+        let executor = Arc::new(crate::exec::RecordingExecutor::new());
+        let manager =
+            DatasetManager::new("zroot".to_string(), false).with_executor(executor.clone());
+
+        let names = manager.zbm_dataset_names();
+        manager.create_missing_zbm_datasets(&names[..1]).unwrap();
+
+        let created: Vec<String> = executor
+            .commands()
+            .iter()
+            .filter(|c| c.args.first().map(String::as_str) == Some("create"))
+            .filter_map(|c| c.args.last().cloned())
+            .collect();
+        assert_eq!(created, names[1..]);
+    }
 }
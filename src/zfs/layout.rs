@@ -0,0 +1,485 @@
+//! Dataset layouts
+//!
+//! A [`DatasetLayout`] lists the datasets created under the pool root. The
+//! default is the ZBM hierarchy (boot environments under `ROOT`, plus
+//! separate datasets for home, logs, caches and the like); a config file can
+//! replace it with a `[[datasets]]` list.
+
+use crate::error::{InstallerError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+/// Container of the boot environments, relative to the pool
+pub const BOOT_ENVIRONMENTS: &str = "ROOT";
+
+/// Name of the boot environment unless one is configured
+pub const DEFAULT_BOOT_ENVIRONMENT: &str = "default";
+
+/// Value of the `canmount` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CanMount {
+    /// Mounted automatically
+    On,
+    /// Never mounted; only holds properties for its children
+    Off,
+    /// Only mounted explicitly (boot environments)
+    Noauto,
+}
+
+impl std::fmt::Display for CanMount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::On => write!(f, "on"),
+            Self::Off => write!(f, "off"),
+            Self::Noauto => write!(f, "noauto"),
+        }
+    }
+}
+
+/// One dataset of a layout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetSpec {
+    /// Name relative to the pool, e.g. `var/log`
+    pub name: String,
+    /// `canmount` property (None = inherit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canmount: Option<CanMount>,
+    /// `mountpoint` property: a path, `none` or `legacy` (None = inherit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mountpoint: Option<String>,
+    /// Any other properties
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+}
+
+impl DatasetSpec {
+    /// A dataset inheriting every property
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            canmount: None,
+            mountpoint: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// Set `canmount`
+    pub fn with_canmount(mut self, canmount: CanMount) -> Self {
+        self.canmount = Some(canmount);
+        self
+    }
+
+    /// Set `mountpoint`
+    pub fn with_mountpoint(mut self, mountpoint: impl Into<String>) -> Self {
+        self.mountpoint = Some(mountpoint.into());
+        self
+    }
+
+    /// Set another property
+    pub fn with_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+
+    /// Properties to create the dataset with: `canmount`, `mountpoint`, then the rest
+    pub fn zfs_properties(&self) -> Vec<(String, String)> {
+        let mut properties = Vec::new();
+        if let Some(canmount) = self.canmount {
+            properties.push(("canmount".to_string(), canmount.to_string()));
+        }
+        if let Some(mountpoint) = &self.mountpoint {
+            properties.push(("mountpoint".to_string(), mountpoint.clone()));
+        }
+        properties.extend(self.properties.iter().map(|(k, v)| (k.clone(), v.clone())));
+        properties
+    }
+
+    /// Where the dataset mounts, if it has a path of its own
+    pub fn mount_path(&self) -> Option<PathBuf> {
+        self.mountpoint
+            .as_deref()
+            .filter(|m| m.starts_with('/'))
+            .map(PathBuf::from)
+    }
+
+    /// Name of the parent dataset, if it isn't the pool root
+    fn parent(&self) -> Option<&str> {
+        self.name.rsplit_once('/').map(|(parent, _)| parent)
+    }
+}
+
+/// The datasets to create under the pool root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DatasetLayout {
+    datasets: Vec<DatasetSpec>,
+}
+
+impl DatasetLayout {
+    /// A layout of exactly these datasets
+    pub fn new(datasets: Vec<DatasetSpec>) -> Self {
+        Self { datasets }
+    }
+
+    /// Datasets as listed
+    pub fn datasets(&self) -> &[DatasetSpec] {
+        &self.datasets
+    }
+
+    /// Datasets with every parent before its children
+    ///
+    /// Datasets keep their listed order, except that a child listed before
+    /// its parent is moved after it.
+    pub fn ordered(&self) -> Vec<&DatasetSpec> {
+        let mut ordered: Vec<&DatasetSpec> = Vec::with_capacity(self.datasets.len());
+        for dataset in &self.datasets {
+            self.push_with_parents(dataset, &mut ordered);
+        }
+        ordered
+    }
+
+    fn push_with_parents<'a>(
+        &'a self,
+        dataset: &'a DatasetSpec,
+        ordered: &mut Vec<&'a DatasetSpec>,
+    ) {
+        if ordered.iter().any(|d| d.name == dataset.name) {
+            return;
+        }
+        if let Some(parent) = dataset
+            .parent()
+            .and_then(|p| self.datasets.iter().find(|d| d.name == p))
+        {
+            self.push_with_parents(parent, ordered);
+        }
+        ordered.push(dataset);
+    }
+
+    /// The boot environment mounted at `/`
+    pub fn root(&self) -> Option<&DatasetSpec> {
+        self.datasets
+            .iter()
+            .find(|d| d.mountpoint.as_deref() == Some("/"))
+    }
+
+    /// Name of the boot environment mounted at `/`, relative to the pool
+    pub fn root_name(&self) -> &str {
+        self.root().map_or("ROOT/default", |d| d.name.as_str())
+    }
+
+    /// Rename the boot environment mounted at `/` to `ROOT/<name>`
+    pub fn with_boot_environment(mut self, name: &str) -> Self {
+        if let Some(root) = self
+            .datasets
+            .iter_mut()
+            .find(|d| d.mountpoint.as_deref() == Some("/"))
+        {
+            root.name = format!("{}/{}", BOOT_ENVIRONMENTS, name);
+        }
+        self
+    }
+
+    /// Mount points of the datasets that have one
+    pub fn mountpoints(&self) -> Vec<PathBuf> {
+        self.datasets
+            .iter()
+            .filter_map(|d| d.mount_path())
+            .collect()
+    }
+
+    /// Datasets that get mounted, each with where it mounts, ordered so every
+    /// mountpoint comes after the ones it is nested in (`/var` before
+    /// `/var/log`)
+    ///
+    /// Datasets with `canmount=off` are left out; one without a mountpoint of
+    /// its own mounts below its parent's.
+    pub fn mount_order(&self) -> Vec<(&DatasetSpec, PathBuf)> {
+        let mut mounts: Vec<(&DatasetSpec, PathBuf)> = self
+            .datasets
+            .iter()
+            .filter(|d| d.canmount != Some(CanMount::Off))
+            .filter_map(|d| self.effective_mount_path(d).map(|path| (d, path)))
+            .collect();
+        // Paths compare component by component, so parents sort first
+        mounts.sort_by(|(_, a), (_, b)| a.cmp(b));
+        mounts
+    }
+
+    /// Where a dataset mounts, following inherited mountpoints
+    fn effective_mount_path(&self, dataset: &DatasetSpec) -> Option<PathBuf> {
+        if dataset.mountpoint.is_some() {
+            return dataset.mount_path();
+        }
+        let parent = dataset
+            .parent()
+            .and_then(|p| self.datasets.iter().find(|d| d.name == p))?;
+        let (_, leaf) = dataset.name.rsplit_once('/')?;
+        Some(self.effective_mount_path(parent)?.join(leaf))
+    }
+
+    /// Check the layout can be created and booted
+    ///
+    /// Names must be unique and well-formed, every parent must be listed, and
+    /// exactly one dataset below `ROOT` must mount at `/`.
+    pub fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for dataset in &self.datasets {
+            let name = &dataset.name;
+            if name.is_empty()
+                || name.starts_with('/')
+                || name.ends_with('/')
+                || name.contains("//")
+                || name.contains(['@', '#', ' '])
+            {
+                return Err(InstallerError::validation(format!(
+                    "Invalid dataset name '{}'",
+                    name
+                )));
+            }
+            if !names.insert(name.as_str()) {
+                return Err(InstallerError::validation(format!(
+                    "Dataset '{}' is listed more than once",
+                    name
+                )));
+            }
+            if let Some(key) = ["canmount", "mountpoint"]
+                .into_iter()
+                .find(|key| dataset.properties.contains_key(*key))
+            {
+                return Err(InstallerError::validation(format!(
+                    "Dataset '{}': set {} with its own key, not under properties",
+                    name, key
+                )));
+            }
+        }
+
+        for dataset in &self.datasets {
+            if let Some(parent) = dataset.parent() {
+                if !names.contains(parent) {
+                    return Err(InstallerError::validation(format!(
+                        "Dataset '{}' needs its parent '{}' in the layout",
+                        dataset.name, parent
+                    )));
+                }
+            }
+        }
+
+        let roots: Vec<&str> = self
+            .datasets
+            .iter()
+            .filter(|d| d.mountpoint.as_deref() == Some("/"))
+            .map(|d| d.name.as_str())
+            .collect();
+        match roots.as_slice() {
+            [root] if root.starts_with(&format!("{}/", BOOT_ENVIRONMENTS)) => Ok(()),
+            [root] => Err(InstallerError::validation(format!(
+                "Dataset '{}' mounts at / but is not a boot environment under {}",
+                root, BOOT_ENVIRONMENTS
+            ))),
+            [] => Err(InstallerError::validation(format!(
+                "The dataset layout needs a boot environment under {} with mountpoint=/",
+                BOOT_ENVIRONMENTS
+            ))),
+            _ => Err(InstallerError::validation(format!(
+                "Only one dataset may mount at /, found {}",
+                roots.join(", ")
+            ))),
+        }
+    }
+}
+
+impl Default for DatasetLayout {
+    /// The standard ZBM hierarchy
+    fn default() -> Self {
+        Self::new(vec![
+            // Boot environment container
+            DatasetSpec::new(BOOT_ENVIRONMENTS)
+                .with_canmount(CanMount::Off)
+                .with_mountpoint("none"),
+            // Default boot environment
+            DatasetSpec::new("ROOT/default")
+                .with_canmount(CanMount::Noauto)
+                .with_mountpoint("/"),
+            // Home directories
+            DatasetSpec::new("home").with_mountpoint("/home"),
+            // Root user home
+            DatasetSpec::new("home/root").with_mountpoint("/root"),
+            // Var container
+            DatasetSpec::new("var")
+                .with_canmount(CanMount::Off)
+                .with_mountpoint("none"),
+            // System logs
+            DatasetSpec::new("var/log")
+                .with_mountpoint("/var/log")
+                .with_property("acltype", "posixacl")
+                .with_property("xattr", "sa"),
+            // Cache
+            DatasetSpec::new("var/cache")
+                .with_mountpoint("/var/cache")
+                .with_property("com.sun:auto-snapshot", "false"),
+            // Temporary files
+            DatasetSpec::new("var/tmp")
+                .with_mountpoint("/var/tmp")
+                .with_property("com.sun:auto-snapshot", "false"),
+            // Optional packages
+            DatasetSpec::new("opt").with_mountpoint("/opt"),
+            // Service data
+            DatasetSpec::new("srv").with_mountpoint("/srv"),
+            // Local software container
+            DatasetSpec::new("usr")
+                .with_canmount(CanMount::Off)
+                .with_mountpoint("none"),
+            // Locally installed software
+            DatasetSpec::new("usr/local").with_mountpoint("/usr/local"),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(specs: &[(&str, Option<&str>)]) -> DatasetLayout {
+        DatasetLayout::new(
+            specs
+                .iter()
+                .map(|(name, mountpoint)| {
+                    let spec = DatasetSpec::new(*name);
+                    match mountpoint {
+                        Some(m) => spec.with_mountpoint(*m),
+                        None => spec,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_mount_order() {
+        let layout = layout(&[
+            ("var", Some("/var")),
+            ("var/log/journal", None),
+            ("var/log", Some("/var/log")),
+            ("ROOT", Some("none")),
+            ("ROOT/default", Some("/")),
+            ("data", Some("/var-data")),
+            ("home", Some("/home")),
+        ]);
+        let order: Vec<(&str, PathBuf)> = layout
+            .mount_order()
+            .into_iter()
+            .map(|(d, path)| (d.name.as_str(), path))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("ROOT/default", PathBuf::from("/")),
+                ("home", PathBuf::from("/home")),
+                ("var", PathBuf::from("/var")),
+                ("var/log", PathBuf::from("/var/log")),
+                ("var/log/journal", PathBuf::from("/var/log/journal")),
+                ("data", PathBuf::from("/var-data")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mount_order_skips_unmountable() {
+        let layout = DatasetLayout::default();
+        let names: Vec<&str> = layout
+            .mount_order()
+            .into_iter()
+            .map(|(d, _)| d.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "ROOT/default",
+                "home",
+                "opt",
+                "home/root",
+                "srv",
+                "usr/local",
+                "var/cache",
+                "var/log",
+                "var/tmp",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_layout_is_valid() {
+        let layout = DatasetLayout::default();
+        layout.validate().unwrap();
+        assert_eq!(layout.root_name(), "ROOT/default");
+        assert_eq!(
+            layout.datasets()[5].zfs_properties(),
+            vec![
+                ("mountpoint".to_string(), "/var/log".to_string()),
+                ("acltype".to_string(), "posixacl".to_string()),
+                ("xattr".to_string(), "sa".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_children_are_ordered_after_parents() {
+        let layout = layout(&[
+            ("home/alice", Some("/home/alice")),
+            ("ROOT/arch", Some("/")),
+            ("home", Some("/home")),
+            ("ROOT", None),
+        ]);
+        layout.validate().unwrap();
+
+        let names: Vec<&str> = layout.ordered().iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["home", "home/alice", "ROOT", "ROOT/arch"]);
+    }
+
+    #[test]
+    fn test_boot_environment_is_renamed() {
+        let layout = DatasetLayout::default().with_boot_environment("void");
+        layout.validate().unwrap();
+        assert_eq!(layout.root_name(), "ROOT/void");
+        assert_eq!(
+            layout.datasets().len(),
+            DatasetLayout::default().datasets().len()
+        );
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let cases = [
+            (
+                layout(&[("ROOT", None), ("ROOT/a", Some("/")), ("ROOT", None)]),
+                "listed more than once",
+            ),
+            (
+                layout(&[
+                    ("ROOT", None),
+                    ("ROOT/a", Some("/")),
+                    ("var/lib/docker", None),
+                ]),
+                "needs its parent 'var/lib'",
+            ),
+            (layout(&[("ROOT", None)]), "needs a boot environment"),
+            (
+                layout(&[("ROOT", None), ("ROOT/a", Some("/")), ("ROOT/b", Some("/"))]),
+                "found ROOT/a, ROOT/b",
+            ),
+            (layout(&[("root", Some("/"))]), "not a boot environment"),
+            (
+                layout(&[("ROOT", None), ("ROOT/a@b", Some("/"))]),
+                "Invalid dataset name",
+            ),
+        ];
+
+        for (layout, expected) in cases {
+            let err = layout.validate().unwrap_err().to_string();
+            assert!(err.contains(expected), "{}: {}", expected, err);
+        }
+    }
+}
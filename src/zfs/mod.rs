@@ -1,14 +1,67 @@
 //! ZFS pool and dataset management
 
+pub mod backend;
 pub mod dataset;
+pub mod layout;
 pub mod pool;
+pub mod status;
 
+pub use backend::{PoolSpec, Vdev, ZfsBackend};
 pub use dataset::{DatasetManager, DatasetProperty};
-pub use pool::ZfsPool;
+pub use layout::{CanMount, DatasetLayout, DatasetSpec};
+pub use pool::{SupportVdevs, ZfsPool};
+pub use status::PoolMember;
 
-use crate::error::Result;
+use crate::disk::SysRoot;
+use crate::error::{InstallerError, Result};
+use crate::exec::CommandExecutor;
+use bytesize::ByteSize;
 use std::process::Command;
 
+/// Where the kernel lists the loaded ZFS module
+pub const MODULE_DIR: &str = "/sys/module/zfs";
+
+/// Module options the installed system loads ZFS with, below its root
+pub const MODPROBE_CONF: &str = "etc/modprobe.d/zfs.conf";
+
+/// Smallest `zfs_arc_max` ZFS accepts; it ignores lower values
+pub const MIN_ARC_MAX: ByteSize = ByteSize::mib(64);
+
+/// ARC limit suggested for a machine with `memory` of RAM
+///
+/// Half the RAM, but leaving at least 2 GiB for everything else, so a
+/// desktop on 4-8 GiB stays usable while ZFS caches.
+pub fn suggested_arc_max(memory: ByteSize) -> ByteSize {
+    let half = memory.as_u64() / 2;
+    let rest = memory.as_u64().saturating_sub(ByteSize::gib(2).as_u64());
+    ByteSize(half.min(rest)).max(MIN_ARC_MAX)
+}
+
+/// `zfs.conf` with `zfs_arc_max` set to `size`
+///
+/// Other module options are kept; an earlier `zfs_arc_max` is dropped from
+/// its `options zfs` line, and the line itself once nothing is left on it.
+pub fn set_arc_max(existing: &str, size: ByteSize) -> String {
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("options") || words.next() != Some("zfs") {
+                return Some(line.to_string());
+            }
+            let kept: Vec<&str> = words
+                .filter(|option| !option.starts_with("zfs_arc_max="))
+                .collect();
+            (!kept.is_empty()).then(|| format!("options zfs {}", kept.join(" ")))
+        })
+        .collect();
+    lines.push(format!("options zfs zfs_arc_max={}", size.as_u64()));
+
+    let mut conf = lines.join("\n");
+    conf.push('\n');
+    conf
+}
+
 /// Check if ZFS is available on the system
 pub fn check_zfs_available() -> Result<bool> {
     Ok(Command::new("zpool")
@@ -35,9 +88,80 @@ pub fn get_zfs_version() -> Result<String> {
     }
 }
 
+/// Make sure the ZFS kernel module is loaded and matches the tools
+///
+/// The tools can be installed while the module failed to build (DKMS on
+/// Debian-likes), and nothing notices until `zpool create` fails on a wiped
+/// disk. A missing module is loaded with `modprobe zfs`. Tools from another
+/// release series than the module (e.g. 2.1 against 2.2) are an error too;
+/// they disagree about ioctls and pool features. Returns the module version.
+pub fn ensure_module_loaded(executor: &dyn CommandExecutor, sys_root: &SysRoot) -> Result<String> {
+    let module = sys_root.join(MODULE_DIR);
+    if !module.exists() {
+        log::info!("ZFS kernel module not loaded, loading it");
+        executor
+            .execute(Command::new("modprobe").arg("zfs"))
+            .map_err(|e| {
+                InstallerError::zfs(
+                    "load the kernel module".to_string(),
+                    format!(
+                        "modprobe zfs failed; the module may not have been built for this \
+                         kernel (check `dkms status`): {}",
+                        e
+                    ),
+                )
+            })?;
+        if !module.exists() {
+            return Err(InstallerError::zfs(
+                "load the kernel module",
+                "the zfs module is not loaded",
+            ));
+        }
+    }
+
+    let module_version = std::fs::read_to_string(module.join("version"))
+        .map(|v| v.trim().to_string())
+        .unwrap_or_default();
+    let tools_version = executor
+        .execute(Command::new("zpool").arg("version"))?
+        .stdout_string()
+        .and_then(|stdout| parse_tools_version(&stdout));
+
+    match (tools_version, release_series(&module_version)) {
+        (Some(tools), Some(series)) if release_series(&tools) != Some(series) => {
+            Err(InstallerError::zfs(
+                "check the kernel module".to_string(),
+                format!(
+                    "module version {} doesn't match the tools ({}); reboot into the \
+                     kernel the module was built for, or rebuild it",
+                    module_version, tools
+                ),
+            ))
+        }
+        _ => Ok(module_version),
+    }
+}
+
+/// Userland version from `zpool version` ("zfs-2.2.2-1", then "zfs-kmod-...")
+fn parse_tools_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("zfs-kmod-"))
+        .find_map(|line| line.strip_prefix("zfs-"))
+        .map(str::to_string)
+}
+
+/// Major and minor version, the release series, of "2.2.2-1"
+fn release_series(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(['.', '-']);
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exec::RecordingExecutor;
 
     #[test]
     fn test_check_zfs_available() {
@@ -45,4 +169,54 @@ mod tests {
         let result = check_zfs_available();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_suggested_arc_max() {
+        assert_eq!(suggested_arc_max(ByteSize::gib(4)), ByteSize::gib(2));
+        assert_eq!(suggested_arc_max(ByteSize::gib(6)), ByteSize::gib(3));
+        assert_eq!(suggested_arc_max(ByteSize::gib(3)), ByteSize::gib(1));
+        assert_eq!(suggested_arc_max(ByteSize::gib(1)), MIN_ARC_MAX);
+    }
+
+    #[test]
+    fn test_set_arc_max_keeps_other_options() {
+        assert_eq!(
+            set_arc_max("", ByteSize::gib(2)),
+            "options zfs zfs_arc_max=2147483648\n"
+        );
+        assert_eq!(
+            set_arc_max(
+                "# tuned\noptions zfs zfs_arc_max=1073741824 zfs_prefetch_disable=1\noptions zfs zfs_arc_max=1\n",
+                ByteSize::gib(2)
+            ),
+            "# tuned\noptions zfs zfs_prefetch_disable=1\noptions zfs zfs_arc_max=2147483648\n"
+        );
+    }
+
+    #[test]
+    fn test_ensure_module_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = SysRoot::new(dir.path());
+        let executor = RecordingExecutor::new();
+
+        // Not loaded, and modprobe can't load it
+        executor.fail_program("modprobe", 1, "modprobe: FATAL: Module zfs not found");
+        let err = ensure_module_loaded(&executor, &root).unwrap_err();
+        assert!(err.to_string().contains("dkms status"), "{err}");
+        assert_eq!(executor.commands()[0].argv(), vec!["modprobe", "zfs"]);
+
+        let module = root.join(MODULE_DIR);
+        std::fs::create_dir_all(&module).unwrap();
+        std::fs::write(module.join("version"), "2.2.2-1\n").unwrap();
+        executor.respond_program_times("zpool", 1, "zfs-2.2.3-1\nzfs-kmod-2.2.2-1\n");
+        assert_eq!(ensure_module_loaded(&executor, &root).unwrap(), "2.2.2-1");
+
+        executor.respond_program("zpool", "zfs-2.1.14-1\nzfs-kmod-2.2.2-1\n");
+        let err = ensure_module_loaded(&executor, &root).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("doesn't match the tools (2.1.14-1)"),
+            "{err}"
+        );
+    }
 }
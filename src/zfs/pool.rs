@@ -1,24 +1,71 @@
 //! ZFS pool creation and management
 
-use crate::config::{Compression, RaidLevel};
+use crate::config::{Compression, EncryptionConfig, RaidLevel, VdevSpec};
+use crate::disk::BlockDevice;
 use crate::error::{InstallerError, Result};
-use std::path::PathBuf;
+use crate::exec::{CommandExecutor, SystemExecutor};
+use crate::zfs::backend::{self, CliBackend, PoolSpec, Vdev, ZfsBackend};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+
+/// Where the installer caches the pool's configuration, so it's imported at boot
+pub const DEFAULT_CACHEFILE: &str = "/etc/zfs/zpool.cache";
+
+/// Where OpenZFS installs the feature sets the `compatibility` property names
+pub const COMPATIBILITY_DIR: &str = "/usr/share/zfs/compatibility.d";
+
+/// Feature set GRUB can read
+pub const GRUB_COMPATIBILITY: &str = "grub2";
+
+/// First legacy pool version with the `bootfs` property
+const BOOTFS_POOL_VERSION: u32 = 8;
+
+/// Devices added to a pool besides its data vdev
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SupportVdevs {
+    /// Separate intent log devices
+    pub log: Vec<PathBuf>,
+    /// Mirror the log devices instead of striping them
+    pub mirror_log: bool,
+    /// L2ARC cache devices
+    pub cache: Vec<PathBuf>,
+    /// Hot spares
+    pub spares: Vec<PathBuf>,
+}
 
 /// ZFS pool manager
 pub struct ZfsPool {
     /// Pool name
     name: String,
-    /// RAID level
-    raid_level: RaidLevel,
-    /// Devices to use
-    devices: Vec<PathBuf>,
+    /// Top-level vdevs, striped together
+    vdevs: Vec<VdevSpec>,
+    /// Log, cache and spare vdevs
+    support: SupportVdevs,
     /// ashift value
     ashift: Option<u8>,
     /// Compression algorithm
     compression: Compression,
+    /// Native encryption for the pool root
+    encryption: Option<EncryptionConfig>,
+    /// Where the pool's configuration is cached (None = the ZFS default)
+    cachefile: Option<PathBuf>,
+    /// Directory the pool's mountpoints are relative to while imported
+    altroot: Option<PathBuf>,
+    /// Feature sets the pool is limited to (None = every feature)
+    compatibility: Option<String>,
+    /// Trim freed blocks automatically
+    autotrim: bool,
+    /// Extra pool properties, overriding the built-in ones
+    pool_options: BTreeMap<String, String>,
+    /// Extra pool root properties, overriding the built-in ones
+    fs_options: BTreeMap<String, String>,
     /// Dry run mode
+    #[allow(dead_code)] // Enforced by the executor
     dry_run: bool,
+    /// Backend that performs the operations
+    backend: Arc<dyn ZfsBackend>,
 }
 
 impl ZfsPool {
@@ -33,100 +80,220 @@ impl ZfsPool {
     ) -> Self {
         Self {
             name,
-            raid_level,
-            devices,
+            vdevs: vec![VdevSpec::new(raid_level, devices)],
+            support: SupportVdevs::default(),
             ashift,
             compression,
+            encryption: None,
+            cachefile: None,
+            altroot: None,
+            compatibility: None,
+            autotrim: false,
+            pool_options: BTreeMap::new(),
+            fs_options: BTreeMap::new(),
             dry_run,
+            backend: backend::select(Arc::new(SystemExecutor::new(dry_run)), dry_run),
         }
     }
 
-    /// Execute a command
-    fn execute(&self, cmd: &mut Command) -> Result<std::process::Output> {
-        let cmd_str = format!("{:?}", cmd);
+    /// Use a custom command executor (implies the CLI backend)
+    pub fn with_executor(mut self, executor: Arc<dyn CommandExecutor>) -> Self {
+        self.backend = Arc::new(CliBackend::new(executor));
+        self
+    }
 
-        if self.dry_run {
-            log::info!("[DRY RUN] Would execute: {}", cmd_str);
-            return Ok(std::process::Output {
-                status: std::process::ExitStatus::default(),
-                stdout: Vec::new(),
-                stderr: Vec::new(),
-            });
-        }
+    /// Use a specific ZFS backend
+    pub fn with_backend(mut self, backend: Arc<dyn ZfsBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Encrypt the pool root (and so every dataset, which inherits it)
+    pub fn with_encryption(mut self, encryption: Option<EncryptionConfig>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Stripe several top-level vdevs instead of the single one given to [`ZfsPool::new`]
+    pub fn with_vdevs(mut self, vdevs: Vec<VdevSpec>) -> Self {
+        self.vdevs = vdevs;
+        self
+    }
+
+    /// Add log, cache and spare vdevs after the data vdev
+    pub fn with_support_vdevs(mut self, support: SupportVdevs) -> Self {
+        self.support = support;
+        self
+    }
+
+    /// Create the pool with `cachefile` set
+    pub fn with_cachefile(mut self, cachefile: Option<PathBuf>) -> Self {
+        self.cachefile = cachefile;
+        self
+    }
+
+    /// Create the pool imported under `altroot`, so its datasets mount below
+    /// it instead of over the running system
+    pub fn with_altroot(mut self, altroot: Option<PathBuf>) -> Self {
+        self.altroot = altroot;
+        self
+    }
+
+    /// Create the pool with `compatibility` set, e.g. `grub2`
+    pub fn with_compatibility(mut self, compatibility: Option<String>) -> Self {
+        self.compatibility = compatibility;
+        self
+    }
+
+    /// Create the pool with `autotrim=on`
+    pub fn with_autotrim(mut self, autotrim: bool) -> Self {
+        self.autotrim = autotrim;
+        self
+    }
+
+    /// Set extra pool (`-o`) and pool root (`-O`) properties, overriding the built-in ones
+    pub fn with_properties(
+        mut self,
+        pool_options: BTreeMap<String, String>,
+        fs_options: BTreeMap<String, String>,
+    ) -> Self {
+        self.pool_options = pool_options;
+        self.fs_options = fs_options;
+        self
+    }
 
-        log::debug!("Executing: {}", cmd_str);
-        let output = cmd.output()?;
+    /// Largest recommended ashift across `devices`, `None` if there are none
+    ///
+    /// A vdev's ashift can't be changed later, so the pool is sized for the
+    /// disk with the largest physical sectors.
+    pub fn detect_ashift(devices: &[BlockDevice]) -> Option<u8> {
+        let ashift = devices.iter().map(BlockDevice::recommended_ashift).max()?;
+        log::info!(
+            "Auto-detected ashift={} from {} device(s)",
+            ashift,
+            devices.len()
+        );
+        Some(ashift)
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(InstallerError::ZfsError {
-                operation: cmd_str,
-                details: stderr.to_string(),
-            });
+    /// Whether to enable autotrim for a pool on `devices`
+    ///
+    /// Only worth it when every device is an SSD: HDDs don't trim, and a
+    /// mixed pool is left to the user to decide.
+    pub fn detect_autotrim(devices: &[BlockDevice]) -> bool {
+        for device in devices {
+            log::info!(
+                "{}: rotational={}",
+                device.path.display(),
+                u8::from(device.rotational)
+            );
         }
 
-        Ok(output)
+        let rotational = devices.iter().filter(|d| d.rotational).count();
+        let autotrim = !devices.is_empty() && rotational == 0;
+        if rotational > 0 && rotational < devices.len() {
+            log::warn!("Pool mixes SSDs and HDDs, leaving autotrim off (set autotrim to override)");
+        } else {
+            log::info!(
+                "Auto-detected autotrim={} from {} device(s)",
+                if autotrim { "on" } else { "off" },
+                devices.len()
+            );
+        }
+        autotrim
     }
 
     /// Create the ZFS pool
     pub fn create(&self) -> Result<()> {
         log::info!("Creating ZFS pool: {}", self.name);
 
-        let mut cmd = Command::new("zpool");
-        cmd.arg("create")
-            .arg("-f") // Force
-            .arg("-m")
-            .arg("none"); // Don't mount automatically
+        self.backend.pool_create(&self.pool_spec())?;
+        log::info!("ZFS pool {} created successfully", self.name);
 
-        // Add pool features
-        cmd.arg("-o").arg("ashift={}".to_string());
-        if let Some(ashift) = self.ashift {
-            cmd.arg("-o").arg(format!("ashift={}", ashift));
-        }
+        Ok(())
+    }
 
+    /// Everything `zpool create` needs for this pool
+    fn pool_spec(&self) -> PoolSpec {
         // Pool properties
-        cmd.arg("-O")
-            .arg("acltype=posixacl")
-            .arg("-O")
-            .arg("xattr=sa")
-            .arg("-O")
-            .arg("dnodesize=auto")
-            .arg("-O")
-            .arg(format!("compression={}", self.compression))
-            .arg("-O")
-            .arg("normalization=formD")
-            .arg("-O")
-            .arg("relatime=on");
-
-        // Pool name
-        cmd.arg(&self.name);
-
-        // Add vdev type if not "none"
-        if let Some(vdev_type) = self.raid_level.vdev_type() {
-            cmd.arg(vdev_type);
+        let mut properties: Vec<(String, String)> = self
+            .ashift
+            .map(|ashift| ("ashift".to_string(), ashift.to_string()))
+            .into_iter()
+            .chain(
+                self.cachefile
+                    .as_ref()
+                    .map(|path| ("cachefile".to_string(), path.display().to_string())),
+            )
+            .chain(
+                self.compatibility
+                    .as_ref()
+                    .map(|features| ("compatibility".to_string(), features.clone())),
+            )
+            .chain(
+                self.autotrim
+                    .then(|| ("autotrim".to_string(), "on".to_string())),
+            )
+            .collect();
+
+        // Root filesystem properties
+        let mut fs_properties = [
+            ("acltype", "posixacl".to_string()),
+            ("xattr", "sa".to_string()),
+            ("dnodesize", "auto".to_string()),
+            ("compression", self.compression.to_string()),
+            ("normalization", "formD".to_string()),
+            ("relatime", "on".to_string()),
+            // The pool root only holds properties for the datasets below it
+            ("canmount", "off".to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect::<Vec<_>>();
+
+        merge_properties(&mut properties, &self.pool_options);
+        merge_properties(&mut fs_properties, &self.fs_options);
+
+        let mut key = None;
+        if let Some(encryption) = &self.encryption {
+            log::info!(
+                "Encrypting pool with {} ({} key, keylocation={})",
+                encryption.algorithm,
+                encryption.keyformat,
+                encryption.keylocation()
+            );
+            fs_properties.extend(encryption.properties());
+            if encryption.uses_prompt() {
+                key = encryption.passphrase.clone();
+            }
         }
 
-        // Add devices
-        for device in &self.devices {
-            cmd.arg(device);
+        PoolSpec {
+            name: self.name.clone(),
+            vdevs: self
+                .vdevs
+                .iter()
+                .map(|vdev| Vdev {
+                    vdev_type: vdev.raid.vdev_type().map(str::to_string),
+                    devices: vdev.devices.clone(),
+                })
+                .collect(),
+            log_devices: self.support.log.clone(),
+            mirror_log: self.support.mirror_log,
+            cache_devices: self.support.cache.clone(),
+            spare_devices: self.support.spares.clone(),
+            properties,
+            fs_properties,
+            altroot: self.altroot.clone(),
+            key,
         }
-
-        self.execute(&mut cmd)?;
-        log::info!("ZFS pool {} created successfully", self.name);
-
-        Ok(())
     }
 
     /// Destroy the pool (for testing/cleanup)
     pub fn destroy(&self) -> Result<()> {
         log::info!("Destroying ZFS pool: {}", self.name);
 
-        self.execute(
-            Command::new("zpool")
-                .arg("destroy")
-                .arg("-f")
-                .arg(&self.name),
-        )?;
+        self.backend.pool_destroy(&self.name)?;
 
         Ok(())
     }
@@ -135,7 +302,7 @@ impl ZfsPool {
     pub fn export(&self) -> Result<()> {
         log::info!("Exporting ZFS pool: {}", self.name);
 
-        self.execute(Command::new("zpool").arg("export").arg(&self.name))?;
+        self.backend.pool_export(&self.name)?;
 
         Ok(())
     }
@@ -144,35 +311,112 @@ impl ZfsPool {
     pub fn import(&self) -> Result<()> {
         log::info!("Importing ZFS pool: {}", self.name);
 
-        self.execute(
-            Command::new("zpool")
-                .arg("import")
-                .arg("-f")
-                .arg(&self.name),
-        )?;
+        self.backend.pool_import(&self.name)?;
+
+        Ok(())
+    }
+
+    /// Import the pool under `altroot` without mounting any of its datasets
+    pub fn import_at(&self, altroot: &Path) -> Result<()> {
+        log::info!(
+            "Importing ZFS pool {} under {}",
+            self.name,
+            altroot.display()
+        );
+
+        self.backend.pool_import_at(&self.name, altroot)?;
+
+        Ok(())
+    }
+
+    /// Load the encryption key after an import, if the pool is encrypted
+    ///
+    /// Imports leave keys unloaded, so nothing below the pool root can be
+    /// mounted until this has run. A prompted passphrase has to be in the
+    /// encryption config already.
+    pub fn load_key(&self) -> Result<()> {
+        let Some(encryption) = &self.encryption else {
+            return Ok(());
+        };
+        let passphrase = if encryption.uses_prompt() {
+            Some(encryption.passphrase.as_ref().ok_or_else(|| {
+                InstallerError::config(format!(
+                    "The encryption passphrase of pool {} is needed to load its key",
+                    self.name
+                ))
+            })?)
+        } else {
+            None
+        };
+        log::info!("Loading the encryption key of pool {}", self.name);
+
+        self.backend.dataset_load_key(&self.name, passphrase)?;
 
         Ok(())
     }
 
+    /// Whether the pool is imported (`false` in dry-run mode)
+    pub fn is_imported(&self) -> bool {
+        matches!(
+            self.backend.pool_get(&self.name, "name"),
+            Ok(Some(name)) if name == self.name
+        )
+    }
+
+    /// Whether `bootfs` can be set on the pool (`None` in dry-run mode)
+    ///
+    /// Pools with feature flags report version `-`; legacy pools need
+    /// version 8 or later.
+    pub fn supports_bootfs(&self) -> Result<Option<bool>> {
+        let version = self.backend.pool_get(&self.name, "version")?;
+        Ok(version.map(|version| match version.parse::<u32>() {
+            Ok(version) => version >= BOOTFS_POOL_VERSION,
+            Err(_) => true,
+        }))
+    }
+
+    /// The pool's `compatibility` property (`None` in dry-run mode)
+    pub fn compatibility(&self) -> Result<Option<String>> {
+        self.backend.pool_get(&self.name, "compatibility")
+    }
+
     /// Set bootfs property
     pub fn set_bootfs(&self, dataset: &str) -> Result<()> {
         log::info!("Setting bootfs to: {}/{}", self.name, dataset);
 
-        self.execute(
-            Command::new("zpool")
-                .arg("set")
-                .arg(format!("bootfs={}/{}", self.name, dataset))
-                .arg(&self.name),
-        )?;
+        self.backend
+            .pool_set(&self.name, "bootfs", &format!("{}/{}", self.name, dataset))?;
+
+        Ok(())
+    }
+
+    /// Set the cachefile property, which also rewrites the cache file
+    pub fn set_cachefile(&self, path: &Path) -> Result<()> {
+        log::info!("Setting cachefile of {} to: {}", self.name, path.display());
+
+        self.backend
+            .pool_set(&self.name, "cachefile", &path.display().to_string())?;
 
         Ok(())
     }
 
     /// Get pool status
+    ///
+    /// In dry-run mode `zpool status` is not run and a `[dry-run]` marker is
+    /// returned instead of pool output.
     pub fn status(&self) -> Result<String> {
-        let output = self.execute(Command::new("zpool").arg("status").arg(&self.name))?;
+        match self.backend.pool_status(&self.name)? {
+            Some(stdout) => Ok(stdout),
+            None => Ok(format!(
+                "[dry-run] status of pool {} not queried",
+                self.name
+            )),
+        }
+    }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    /// Get the pool GUID (`None` in dry-run mode)
+    pub fn guid(&self) -> Result<Option<String>> {
+        self.backend.pool_get(&self.name, "guid")
     }
 
     /// Check if pool exists
@@ -186,9 +430,21 @@ impl ZfsPool {
     }
 }
 
+/// Set `overrides` on top of `properties`, replacing the values of names already there
+fn merge_properties(properties: &mut Vec<(String, String)>, overrides: &BTreeMap<String, String>) {
+    for (name, value) in overrides {
+        match properties.iter_mut().find(|(existing, _)| existing == name) {
+            Some(property) => property.1 = value.clone(),
+            None => properties.push((name.clone(), value.clone())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::InstallerError;
+    use crate::exec::RecordingExecutor;
 
     #[test]
     fn test_zfs_pool_creation() {
@@ -202,7 +458,176 @@ mod tests {
         );
 
         assert_eq!(pool.name, "testpool");
-        assert_eq!(pool.raid_level, RaidLevel::None);
+        assert_eq!(pool.vdevs[0].raid, RaidLevel::None);
+    }
+
+    #[test]
+    fn test_create_args_have_one_ashift() {
+        let ashift_options = |ashift| {
+            let pool = ZfsPool::new(
+                "zroot".to_string(),
+                RaidLevel::Mirror,
+                vec![PathBuf::from("/dev/sda3"), PathBuf::from("/dev/sdb3")],
+                ashift,
+                Compression::Zstd,
+                true,
+            );
+            let args = backend::cli::build_create_args(&pool.pool_spec());
+            args.windows(2)
+                .filter(|w| w[0] == "-o" && w[1].starts_with("ashift="))
+                .map(|w| w[1].clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ashift_options(Some(12)), vec!["ashift=12"]);
+        assert!(ashift_options(None).is_empty());
+        assert!(!backend::cli::build_create_args(&PoolSpec::default())
+            .iter()
+            .any(|a| a.contains('{')));
+    }
+
+    #[test]
+    fn test_cachefile_is_set_at_creation() {
+        let pool = ZfsPool::new(
+            "zroot".to_string(),
+            RaidLevel::None,
+            vec![PathBuf::from("/dev/sda3")],
+            None,
+            Compression::Zstd,
+            true,
+        );
+        let args = backend::cli::build_create_args(&pool.pool_spec());
+        assert!(!args.iter().any(|a| a.starts_with("cachefile=")));
+
+        let pool = pool.with_cachefile(Some(PathBuf::from("/etc/zfs/zpool.cache")));
+        let args = backend::cli::build_create_args(&pool.pool_spec());
+        assert!(args
+            .windows(2)
+            .any(|w| w[0] == "-o" && w[1] == "cachefile=/etc/zfs/zpool.cache"));
+    }
+
+    #[test]
+    fn test_compatibility_is_set_at_creation() {
+        let pool = ZfsPool::new(
+            "zroot".to_string(),
+            RaidLevel::None,
+            vec![PathBuf::from("/dev/sda3")],
+            Some(12),
+            Compression::Zstd,
+            true,
+        )
+        .with_compatibility(Some("openzfs-2.1-linux".to_string()));
+        let args = backend::cli::build_create_args(&pool.pool_spec());
+        assert!(args
+            .windows(2)
+            .any(|w| w[0] == "-o" && w[1] == "compatibility=openzfs-2.1-linux"));
+    }
+
+    #[test]
+    fn test_extra_properties_override_defaults() {
+        let pool = ZfsPool::new(
+            "zroot".to_string(),
+            RaidLevel::None,
+            vec![PathBuf::from("/dev/sda3")],
+            Some(12),
+            Compression::Zstd,
+            true,
+        )
+        .with_properties(
+            BTreeMap::from([("autoexpand".to_string(), "on".to_string())]),
+            BTreeMap::from([
+                ("atime".to_string(), "off".to_string()),
+                ("compression".to_string(), "lz4".to_string()),
+            ]),
+        );
+        let args = backend::cli::build_create_args(&pool.pool_spec()).join(" ");
+        assert!(
+            args.contains("-o ashift=12 -o autoexpand=on -O acltype=posixacl"),
+            "{}",
+            args
+        );
+        assert!(
+            args.contains("-O compression=lz4 -O normalization=formD"),
+            "{}",
+            args
+        );
+        assert!(
+            args.contains("-O relatime=on -O canmount=off -O atime=off zroot"),
+            "{}",
+            args
+        );
+        assert!(!args.contains("compression=zstd"), "{}", args);
+    }
+
+    #[test]
+    fn test_detect_autotrim() {
+        let root = crate::disk::SysRoot::fixture();
+        let device = |rotational| {
+            let mut device = BlockDevice::from_name_in(&root, "sda").unwrap();
+            device.rotational = rotational;
+            device
+        };
+
+        assert!(ZfsPool::detect_autotrim(&[device(false)]));
+        assert!(ZfsPool::detect_autotrim(&[device(false), device(false)]));
+        assert!(!ZfsPool::detect_autotrim(&[device(true), device(true)]));
+        assert!(!ZfsPool::detect_autotrim(&[device(false), device(true)]));
+        assert!(!ZfsPool::detect_autotrim(&[]));
+
+        let pool = ZfsPool::new(
+            "zroot".to_string(),
+            RaidLevel::None,
+            vec![PathBuf::from("/dev/sda3")],
+            None,
+            Compression::Zstd,
+            true,
+        );
+        let autotrim = |pool: &ZfsPool| {
+            backend::cli::build_create_args(&pool.pool_spec()).contains(&"autotrim=on".to_string())
+        };
+        assert!(!autotrim(&pool));
+        assert!(autotrim(&pool.with_autotrim(true)));
+    }
+
+    #[test]
+    fn test_existing_pool_queries() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let pool = ZfsPool::new(
+            "tank".to_string(),
+            RaidLevel::None,
+            Vec::new(),
+            None,
+            Compression::Zstd,
+            false,
+        )
+        .with_executor(executor.clone());
+
+        executor.respond_program_times("zpool", 1, "5\n");
+        assert_eq!(pool.supports_bootfs().unwrap(), Some(false));
+        executor.respond_program_times("zpool", 1, "-\n");
+        assert_eq!(pool.supports_bootfs().unwrap(), Some(true));
+        assert!(!pool.is_imported());
+        executor.respond_program_times("zpool", 1, "tank\n");
+        assert!(pool.is_imported());
+
+        pool.import_at(Path::new("/mnt")).unwrap();
+        assert_eq!(
+            executor.commands().last().unwrap().argv(),
+            ["zpool", "import", "-f", "-N", "-R", "/mnt", "tank"]
+        );
+    }
+
+    #[test]
+    fn test_detect_ashift_takes_largest() {
+        let root = crate::disk::SysRoot::fixture();
+        let device = |name| BlockDevice::from_name_in(&root, name).unwrap();
+
+        assert_eq!(ZfsPool::detect_ashift(&[device("sdb")]), Some(9));
+        assert_eq!(
+            ZfsPool::detect_ashift(&[device("sdb"), device("sda")]),
+            Some(12)
+        );
+        assert_eq!(ZfsPool::detect_ashift(&[]), None);
     }
 
     #[test]
@@ -219,4 +644,115 @@ mod tests {
         // This should return false for a pool that doesn't exist
         assert!(!pool.exists());
     }
+
+    #[test]
+    fn test_command_failure_reported_as_zfs_error() {
+        let executor = Arc::new(RecordingExecutor::new());
+        executor.fail_program("zpool", 1, "pool is busy");
+        let pool = ZfsPool::new(
+            "testpool".to_string(),
+            RaidLevel::None,
+            vec![],
+            None,
+            Compression::Zstd,
+            false,
+        )
+        .with_executor(executor);
+
+        match pool.export().unwrap_err() {
+            InstallerError::ZfsError { details, .. } => assert_eq!(details, "pool is busy"),
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_status_in_dry_run_is_marked() {
+        let pool = ZfsPool::new(
+            "testpool".to_string(),
+            RaidLevel::None,
+            vec![],
+            None,
+            Compression::Zstd,
+            true,
+        );
+
+        let status = pool.status().unwrap();
+        assert!(status.starts_with("[dry-run]"));
+        assert!(!status.contains("state:"));
+    }
+
+    #[test]
+    fn test_encrypted_create_keeps_passphrase_off_command_line() {
+        let executor = Arc::new(RecordingExecutor::new());
+        let pool = ZfsPool::new(
+            "zroot".to_string(),
+            RaidLevel::None,
+            vec![PathBuf::from("/dev/sda3")],
+            Some(12),
+            Compression::Zstd,
+            true,
+        )
+        .with_executor(executor.clone())
+        .with_encryption(Some(EncryptionConfig {
+            passphrase: Some(crate::config::Passphrase::new("correct horse")),
+            ..Default::default()
+        }));
+
+        pool.create().unwrap();
+
+        let create = &executor.commands()[0];
+        let argv = create.argv().join(" ");
+        assert!(argv
+            .contains("-O encryption=aes-256-gcm -O keyformat=passphrase -O keylocation=prompt"));
+        assert!(!argv.contains("correct horse"));
+        assert_eq!(create.stdin.as_deref(), Some(&b"correct horse"[..]));
+    }
+
+    #[test]
+    fn test_load_key_after_import() {
+        let pool = |encryption| {
+            let executor = Arc::new(RecordingExecutor::new());
+            let pool = ZfsPool::new(
+                "zroot".to_string(),
+                RaidLevel::None,
+                Vec::new(),
+                None,
+                Compression::Zstd,
+                true,
+            )
+            .with_executor(executor.clone())
+            .with_encryption(encryption);
+            (pool, executor)
+        };
+
+        // Unencrypted pools have no key to load
+        let (unencrypted, executor) = pool(None);
+        unencrypted.load_key().unwrap();
+        assert!(executor.commands().is_empty());
+
+        // A key file is read by ZFS; a passphrase is fed on stdin
+        let (keyfile, executor) = pool(Some(EncryptionConfig {
+            keylocation: Some("file:///etc/zfs/zroot.key".to_string()),
+            ..Default::default()
+        }));
+        keyfile.load_key().unwrap();
+        assert_eq!(executor.commands()[0].argv(), ["zfs", "load-key", "zroot"]);
+        assert_eq!(executor.commands()[0].stdin, None);
+
+        let (prompted, executor) = pool(Some(EncryptionConfig {
+            passphrase: Some(crate::config::Passphrase::new("correct horse")),
+            ..Default::default()
+        }));
+        prompted.load_key().unwrap();
+        assert_eq!(
+            executor.commands()[0].stdin.as_deref(),
+            Some(&b"correct horse"[..])
+        );
+
+        // Without the passphrase there's nothing to load it with
+        let (missing, executor) = pool(Some(EncryptionConfig::default()));
+        let err = missing.load_key().unwrap_err();
+        assert!(err.to_string().contains("passphrase"), "{err}");
+        assert!(executor.commands().is_empty());
+    }
 }
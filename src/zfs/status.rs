@@ -0,0 +1,188 @@
+//! Devices used by imported pools, from `zpool status -P`, and pools that
+//! can be imported, from `zpool import`
+
+use crate::error::{InstallerError, Result};
+use crate::exec::CommandExecutor;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A leaf vdev of an imported pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolMember {
+    /// Pool name
+    pub pool: String,
+    /// Device path as ZFS reports it, e.g. `/dev/disk/by-id/...-part3`
+    pub device: PathBuf,
+}
+
+/// Leaf vdevs of every imported pool
+///
+/// Returns an empty list in dry-run mode.
+pub fn imported_members(executor: &dyn CommandExecutor) -> Result<Vec<PoolMember>> {
+    let result = executor.execute(Command::new("zpool").args(["status", "-P"]))?;
+    Ok(result
+        .stdout_string()
+        .map(|stdout| parse_status(&stdout))
+        .unwrap_or_default())
+}
+
+/// Parse `zpool status -P` output into leaf vdevs
+///
+/// With `-P` every leaf is printed as a full path, so any config line whose
+/// name starts with `/` is a device. Data, log, cache and spare vdevs are
+/// all included: each is wiped if the disk is reused.
+pub fn parse_status(output: &str) -> Vec<PoolMember> {
+    let mut members = Vec::new();
+    let mut pool = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix("pool:") {
+            pool = Some(name.trim().to_string());
+            continue;
+        }
+
+        let (Some(pool), Some(name)) = (&pool, trimmed.split_whitespace().next()) else {
+            continue;
+        };
+        if name.starts_with('/') {
+            members.push(PoolMember {
+                pool: pool.clone(),
+                device: PathBuf::from(name),
+            });
+        }
+    }
+
+    members
+}
+
+/// Names of the exported pools `zpool import` finds on attached devices
+///
+/// Returns an empty list in dry-run mode.
+pub fn importable_pools(executor: &dyn CommandExecutor) -> Result<Vec<String>> {
+    let result = match executor.execute(Command::new("zpool").arg("import")) {
+        Ok(result) => result,
+        // zpool exits 1 when there is nothing to import
+        Err(InstallerError::CommandFailed { code: 1, .. }) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(result
+        .stdout_string()
+        .map(|stdout| parse_import(&stdout))
+        .unwrap_or_default())
+}
+
+/// Parse the pool names out of `zpool import` output
+pub fn parse_import(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("pool:"))
+        .map(|name| name.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingExecutor;
+
+    const MIRROR: &str = "  pool: zroot
+ state: ONLINE
+  scan: scrub repaired 0B in 00:01:02 with 0 errors on Sun Oct 11 00:25:03 2026
+config:
+
+\tNAME                                            STATE     READ WRITE CKSUM
+\tzroot                                           ONLINE       0     0     0
+\t  mirror-0                                      ONLINE       0     0     0
+\t    /dev/disk/by-id/wwn-0x5002538f42a1b2c3-part3  ONLINE       0     0     0
+\t    /dev/sdb3                                   ONLINE       0     0     0
+
+errors: No known data errors
+";
+
+    const RAIDZ: &str = "  pool: backup
+ state: DEGRADED
+status: One or more devices are faulted in response to persistent errors.
+config:
+
+\tNAME                STATE     READ WRITE CKSUM
+\tbackup              DEGRADED     0     0     0
+\t  raidz2-0          DEGRADED     0     0     0
+\t    /dev/sdc1       ONLINE       0     0     0
+\t    /dev/sdd1       ONLINE       0     0     0
+\t    /dev/sde1       FAULTED      3   114     0  too many errors
+\t    /dev/sdf1       ONLINE       0     0     0
+\tlogs
+\t  /dev/nvme1n1p1    ONLINE       0     0     0
+\tcache
+\t  /dev/nvme1n1p2    ONLINE       0     0     0
+\tspares
+\t  /dev/sdg1         AVAIL
+
+errors: No known data errors
+";
+
+    fn devices(members: &[PoolMember]) -> Vec<&str> {
+        members.iter().map(|m| m.device.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_mirror() {
+        let members = parse_status(MIRROR);
+        assert_eq!(
+            devices(&members),
+            vec!["/dev/disk/by-id/wwn-0x5002538f42a1b2c3-part3", "/dev/sdb3"]
+        );
+        assert!(members.iter().all(|m| m.pool == "zroot"));
+    }
+
+    #[test]
+    fn test_parse_raidz_with_aux_vdevs() {
+        let members = parse_status(RAIDZ);
+        assert_eq!(
+            devices(&members),
+            vec![
+                "/dev/sdc1",
+                "/dev/sdd1",
+                "/dev/sde1",
+                "/dev/sdf1",
+                "/dev/nvme1n1p1",
+                "/dev/nvme1n1p2",
+                "/dev/sdg1"
+            ]
+        );
+        assert!(members.iter().all(|m| m.pool == "backup"));
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let output = "   pool: tank
+     id: 4242424242424242424
+  state: ONLINE
+ action: The pool can be imported using its name or numeric identifier.
+ config:
+
+\ttank        ONLINE
+\t  sdc3      ONLINE
+";
+        assert_eq!(parse_import(output), vec!["tank"]);
+        assert!(parse_import("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_several_pools() {
+        let members = parse_status(&format!("{}\n{}", MIRROR, RAIDZ));
+        assert_eq!(members.len(), 9);
+        assert_eq!(members[1].pool, "zroot");
+        assert_eq!(members[2].pool, "backup");
+        assert!(parse_status("no pools available\n").is_empty());
+    }
+
+    #[test]
+    fn test_imported_members_runs_zpool_status() {
+        let executor = RecordingExecutor::new();
+        executor.respond_program("zpool", MIRROR);
+        assert_eq!(imported_members(&executor).unwrap().len(), 2);
+        assert_eq!(executor.commands()[0].argv(), vec!["zpool", "status", "-P"]);
+    }
+}
@@ -0,0 +1,72 @@
+//! SIGINT/SIGTERM handling
+//!
+//! Installs process-wide signal handlers, so it runs in its own test binary
+//! where they can't cancel commands started by other tests.
+
+use std::fs;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+use zbm_installer::exec::{CommandClass, CommandExecutor, ExecOptions, SystemExecutor};
+use zbm_installer::installer::{cancel_on_signals, CancelToken};
+use zbm_installer::InstallerError;
+
+/// Whether process `pid` has exited (a zombie counts, nobody may reap it)
+fn exited(pid: &str) -> bool {
+    match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => stat
+            .rsplit_once(") ")
+            .is_some_and(|(_, rest)| rest.starts_with('Z')),
+        Err(_) => true,
+    }
+}
+
+fn wait_until(what: &str, done: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_signal_kills_long_running_command_group() {
+    let token = CancelToken::new();
+    cancel_on_signals(&token).unwrap();
+    assert!(cancel_on_signals(&CancelToken::new()).is_err());
+
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("sleep.pid");
+
+    // Signal once the shell has started its background child
+    let signaller = {
+        let pid_file = pid_file.clone();
+        thread::spawn(move || {
+            wait_until("the command to start", || pid_file.exists());
+            // SAFETY: the handler installed above catches the signal
+            assert_eq!(unsafe { libc::kill(libc::getpid(), libc::SIGTERM) }, 0);
+        })
+    };
+
+    let started = Instant::now();
+    let result = SystemExecutor::new(false).execute_with(
+        Command::new("sh").arg("-c").arg(format!(
+            "sleep 30 & echo $! > {}.tmp && mv {0}.tmp {0}; wait",
+            pid_file.display()
+        )),
+        &ExecOptions::new().class(CommandClass::LongRunning),
+    );
+    signaller.join().unwrap();
+
+    assert!(
+        matches!(result, Err(InstallerError::UserCancelled)),
+        "{result:?}"
+    );
+    assert!(started.elapsed() < Duration::from_secs(30));
+    assert!(token.is_cancelled());
+
+    // The shell's background child was in its group and went with it
+    let pid = fs::read_to_string(&pid_file).unwrap();
+    let pid = pid.trim();
+    wait_until("the background child to exit", || exited(pid));
+}